@@ -1,7 +1,7 @@
 use cardio_core::*;
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand};
 use std::io::{self, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 #[derive(Parser)]
 #[command(name = "krep")]
@@ -13,27 +13,217 @@ struct Cli {
     /// Override data directory
     #[arg(long, global = true)]
     data_dir: Option<PathBuf>,
+
+    /// Use a `[profile.<name>]` section of the config (default: $KREP_PROFILE)
+    #[arg(long, global = true)]
+    profile: Option<String>,
 }
 
 #[derive(Subcommand)]
 enum Commands {
     /// Prescribe and perform the next microdose (default)
-    Now {
-        /// Target category (vo2, gtg, mobility)
+    Now(NowArgs),
+
+    /// Stay in the foreground, count down to the next dose and start it when due
+    Watch {
+        /// Minutes between doses, counted from the last logged session
+        #[arg(long, default_value_t = 60, value_parser = clap::value_parser!(u32).range(1..))]
+        every: u32,
+
+        /// Also send a desktop notification (notify-send) when a dose is due
+        #[arg(long)]
+        notify: bool,
+    },
+
+    /// Send a reminder if this hour is in the adaptive schedule (run by the
+    /// systemd timer from `krep install-systemd`)
+    Remind {
+        /// Configured reminder hours, comma-separated (default 9,13,17)
+        #[arg(long, value_delimiter = ',', value_parser = clap::value_parser!(u32).range(0..24))]
+        hours: Vec<u32>,
+
+        /// Print today's adapted schedule instead of reminding
+        #[arg(long)]
+        plan: bool,
+    },
+
+    /// Not now: put off a reminder (reminder timing learns from snoozes)
+    Snooze {
+        /// Minutes to put it off for
+        #[arg(long, default_value_t = 30, value_parser = clap::value_parser!(u32).range(1..))]
+        minutes: u32,
+    },
+
+    /// Show recent sessions with per-set reps
+    History {
+        /// Number of days to include
+        #[arg(long, default_value_t = 7)]
+        days: i64,
+
+        /// Only show sessions with this tag (repeatable)
+        #[arg(long = "tag", value_delimiter = ',')]
+        tags: Vec<String>,
+
+        /// Show session IDs and heart rate (for `krep session attach-hr`)
+        #[arg(long)]
+        ids: bool,
+    },
+
+    /// Amend a logged session
+    Session {
+        #[command(subcommand)]
+        action: SessionAction,
+    },
+
+    /// Log a bodyweight weigh-in (kg)
+    Weigh {
+        /// Weight in kilograms (e.g. 82.4)
+        weight_kg: f64,
+    },
+
+    /// Show session totals and bodyweight trend
+    Stats {
+        /// Number of days to include
+        #[arg(long, default_value_t = 7)]
+        days: i64,
+
+        /// Only count sessions with this tag (repeatable)
+        #[arg(long = "tag", value_delimiter = ',')]
+        tags: Vec<String>,
+
+        /// Compare this week (last 7 days) with the week before
+        #[arg(long)]
+        compare: bool,
+
+        /// Show done vs skipped doses by hour and suggest reminder times
+        #[arg(long)]
+        timing: bool,
+
+        /// Compare skip rate and category mix on strength-training days
+        #[arg(long)]
+        strength: bool,
+
+        /// Output format: text or json (json covers totals, targets, goal, HR,
+        /// --compare and minutes per category)
+        #[arg(long, default_value = "text")]
+        format: String,
+    },
+
+    /// Set or show the weekly dose goal
+    Goal {
+        #[command(subcommand)]
+        action: GoalAction,
+    },
+
+    /// Turn temporary prescription modes on or off
+    Mode {
+        #[command(subcommand)]
+        mode: ModeAction,
+    },
+
+    /// Mark days off (holidays): nothing prescribed, reminded or due, and the streak holds
+    Off {
+        /// Date or range, e.g. 2024-08-10..2024-08-20 (lists off days without one)
+        range: Option<String>,
+
+        /// Remove off days set here that haven't ended yet
+        #[arg(long, conflicts_with = "range")]
+        clear: bool,
+    },
+
+    /// Write a summary report (sessions, totals, streak, PRs, plateaus)
+    Report {
+        /// Cover the trailing 7 days (currently the only period)
+        #[arg(long)]
+        week: bool,
+
+        /// Output format: markdown or html (default from --output's extension, else markdown)
+        #[arg(long)]
+        format: Option<String>,
+
+        /// File to write (default: krep-week-<date>.<ext> in the current directory)
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+
+    /// Back up the data directory and config to one archive (for moving
+    /// machines)
+    Export {
+        /// Archive to write: a zstd-compressed tar with a checksummed manifest
+        /// (e.g. krep-backup.tar.zst)
+        #[arg(long)]
+        archive: PathBuf,
+    },
+
+    /// List unlocked and remaining achievements
+    Achievements,
+
+    /// Print whether a dose is due, the last session and the streak as JSON
+    /// (also written to `[status] file` when set)
+    Status,
+
+    /// Export sessions to a SQLite database for ad-hoc SQL
+    Analytics {
+        #[command(subcommand)]
+        action: AnalyticsAction,
+    },
+
+    /// Check the config file
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+
+    /// Check the CSV archive against its manifest and logged sessions for anomalies
+    /// (duplicate IDs, future timestamps, bad HR, ...)
+    Doctor,
+
+    /// Open the data directory, config file or a reference link with the system opener
+    Open {
+        #[command(subcommand)]
+        target: OpenTarget,
+
+        /// Print the path or URL instead of opening it
+        #[arg(long, global = true)]
+        print: bool,
+    },
+
+    /// Import sessions recorded by other apps
+    Import {
+        #[command(subcommand)]
+        source: ImportSource,
+    },
+
+    /// Merge another krep data directory's sessions and progression state
+    /// into this one (duplicates are matched by session ID)
+    ImportDir {
+        /// The other installation's data directory (e.g. ~/.local/share/krep
+        /// copied from another machine)
+        path: PathBuf,
+
+        /// Report what would be merged without writing anything
         #[arg(long)]
-        category: Option<String>,
+        dry_run: bool,
+    },
 
-        /// Dry run - show prescription without logging
+    /// Write systemd user units for reminders and a nightly rollup
+    InstallSystemd {
+        /// Print the units instead of writing them
         #[arg(long)]
         dry_run: bool,
 
-        /// Auto-complete (for testing) - automatically mark as done
-        #[arg(long, conflicts_with = "auto_complete_skip")]
-        auto_complete: bool,
+        /// Local hours to be reminded at, comma-separated (default: the hours
+        /// `krep stats --timing` suggests, else 9,13,17)
+        #[arg(long, value_delimiter = ',', value_parser = clap::value_parser!(u32).range(0..24))]
+        reminder_hours: Vec<u32>,
+
+        /// Local time of the nightly rollup (HH:MM)
+        #[arg(long, default_value = "03:00")]
+        rollup_at: String,
 
-        /// Auto-skip (for testing) - automatically skip a few prescriptions then mark done
-        #[arg(long, conflicts_with = "auto_complete")]
-        auto_complete_skip: bool,
+        /// Directory for the units (default: ~/.config/systemd/user)
+        #[arg(long)]
+        dir: Option<PathBuf>,
     },
 
     /// Roll up WAL sessions to CSV
@@ -44,101 +234,456 @@ enum Commands {
     },
 }
 
-fn main() -> Result<()> {
-    // Initialize logging
-    cardio_core::logging::init();
+#[derive(Subcommand)]
+enum GoalAction {
+    /// Set the goal (stored in state.json)
+    Set {
+        /// Doses to complete per calendar week (Monday to Sunday)
+        #[arg(long)]
+        weekly_doses: u32,
+
+        /// Lighter goal for weeks with a travel-tagged session
+        #[arg(long)]
+        travel_doses: Option<u32>,
+    },
+
+    /// Show this week's progress
+    Show,
+
+    /// Remove the goal
+    Clear,
+}
+
+#[derive(Subcommand)]
+enum ModeAction {
+    /// Bodyweight-only prescriptions (no kettlebell or pull-up bar) for a
+    /// while; shows whether it is on without a subcommand
+    Travel {
+        #[command(subcommand)]
+        switch: Option<TravelSwitch>,
+    },
+
+    /// Low-sweat (GTG-friendly) prescriptions only, on top of
+    /// `[prescription] office_hours`; shows whether it is on without a subcommand
+    Office {
+        #[command(subcommand)]
+        switch: Option<OfficeSwitch>,
+    },
+
+    /// Mobility only (plus cleared movements) while an injury heals; shows
+    /// the current and past rehab periods without a subcommand
+    Rehab {
+        #[command(subcommand)]
+        switch: Option<RehabSwitch>,
+    },
+}
+
+#[derive(Subcommand)]
+enum TravelSwitch {
+    /// Turn travel mode on (stored in state.json)
+    On {
+        /// Days until it turns itself off
+        #[arg(long, default_value_t = travel::DEFAULT_DAYS, value_parser = clap::value_parser!(u32).range(1..))]
+        days: u32,
+    },
+
+    /// Turn travel mode off
+    Off,
+}
+
+#[derive(Subcommand)]
+enum OfficeSwitch {
+    /// Turn office mode on (stored in state.json)
+    On {
+        /// Hours until it turns itself off (default: the end of the day)
+        #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+        hours: Option<u32>,
+    },
+
+    /// Turn office mode off (office hours still apply)
+    Off,
+}
+
+#[derive(Subcommand)]
+enum RehabSwitch {
+    /// Start rehab for an injury (stored in state.json); re-run to update it
+    On {
+        /// What is injured, e.g. "left knee"
+        #[arg(long)]
+        injury: String,
+
+        /// Last day of rehab (YYYY-MM-DD); without it rehab lasts until `off`
+        #[arg(long)]
+        until: Option<chrono::NaiveDate>,
+
+        /// Movement IDs cleared again besides mobility (comma-separated)
+        #[arg(long, value_delimiter = ',')]
+        cleared: Vec<String>,
+    },
+
+    /// The injury is cleared: end rehab mode
+    Off,
+}
+
+#[derive(Subcommand)]
+enum ConfigAction {
+    /// Report syntax and type errors, unknown keys, unusable paths and
+    /// inconsistent settings, with line numbers
+    Validate {
+        /// Config file to check (default: the standard config path)
+        #[arg(long)]
+        file: Option<PathBuf>,
+    },
+    /// Open the config in $VISUAL/$EDITOR and only save it if it validates
+    /// (the previous version is kept as config.toml.bak)
+    Edit {
+        /// Config file to edit (default: the standard config path)
+        #[arg(long)]
+        file: Option<PathBuf>,
+    },
+    /// Print a JSON Schema of the config for editors and linters
+    Schema,
+    /// Rewrite renamed keys to their current names, keeping comments
+    Migrate {
+        /// Config file to update (default: the standard config path)
+        #[arg(long)]
+        file: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum SessionAction {
+    /// Fill a session's avg/max HR from a Polar or Suunto CSV export and keep
+    /// the series in the data directory
+    AttachHr {
+        /// Session ID (shown by `krep history --ids`)
+        id: uuid::Uuid,
+
+        /// HR recording (CSV)
+        file: PathBuf,
+    },
+}
+
+#[derive(Subcommand)]
+enum ImportSource {
+    /// Short vigorous bouts and strength workouts from a Google Takeout export
+    GoogleFit {
+        /// Takeout directory (or its `Fit/All Sessions` folder, or one session file)
+        path: PathBuf,
+
+        /// Import without asking for confirmation
+        #[arg(long)]
+        yes: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum OpenTarget {
+    /// The data directory (WAL, archive, state)
+    Data,
+    /// The config file (created with defaults if missing)
+    Config,
+    /// A microdose definition's reference video or article
+    Reference {
+        /// Definition ID (e.g. mobility_hip_cars)
+        def_id: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum AnalyticsAction {
+    /// Rebuild the database from the CSV archive and WAL
+    Build {
+        /// Database file (default: analytics.sqlite in the data directory)
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Args, Default)]
+struct NowArgs {
+    /// Target category (vo2, gtg, mobility, core, zone2, breathwork)
+    #[arg(long)]
+    category: Option<String>,
+
+    /// Surprise me: favour microdoses not done recently, from any category
+    #[arg(long, conflicts_with = "category")]
+    surprise: bool,
+
+    /// Dry run - show prescription without logging
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Prescribe as if it were this time, e.g. 2026-03-02T07:30:00Z
+    /// (history after it is ignored; needs --dry-run)
+    #[arg(long, requires = "dry_run")]
+    at: Option<chrono::DateTime<chrono::Utc>>,
+
+    /// Show the reference video link as a QR code (to open on a phone)
+    #[arg(long)]
+    qr: bool,
+
+    /// Auto-complete (for testing) - automatically mark as done
+    #[arg(long, conflicts_with = "auto_complete_skip")]
+    auto_complete: bool,
+
+    /// Auto-skip (for testing) - automatically skip a few prescriptions then mark done
+    #[arg(long, conflicts_with = "auto_complete")]
+    auto_complete_skip: bool,
+
+    /// Reps achieved per set, comma-separated (e.g. 5,5,5,4,3)
+    #[arg(long, value_delimiter = ',')]
+    reps: Option<Vec<i32>>,
+
+    /// Tag the logged session (repeatable or comma-separated, e.g. travel,hotel-gym)
+    #[arg(long = "tag", value_delimiter = ',')]
+    tags: Vec<String>,
+
+    /// Answer today's soreness check-in (e.g. hip:3,shoulder:2 or none)
+    #[arg(long)]
+    sore: Option<String>,
+
+    /// Average heart rate for the logged session (bpm, from a watch or strap)
+    #[arg(long)]
+    avg_hr: Option<u8>,
+
+    /// Max heart rate for the logged session (bpm)
+    #[arg(long)]
+    max_hr: Option<u8>,
+
+    /// Heart-rate drop in the 60 seconds after finishing (bpm)
+    #[arg(long)]
+    hr_recovery: Option<u8>,
+
+    /// How hard the logged session felt (RPE 1-10)
+    #[arg(long, value_parser = clap::value_parser!(u8).range(1..=10))]
+    rpe: Option<u8>,
+}
+
+fn main() -> std::process::ExitCode {
+    match run(Cli::parse()) {
+        Ok(()) => std::process::ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::ExitCode::from(e.exit_code())
+        }
+    }
+}
 
-    let cli = Cli::parse();
+fn run(cli: Cli) -> Result<()> {
+    // Before loading, so a config that fails to parse can still be diagnosed
+    if let Some(Commands::Config { action }) = cli.command {
+        return cmd_config(action);
+    }
 
     // Determine data directory
-    let config = Config::load()?;
+    let config = Config::load_profile(cli.profile.as_deref())?;
+
+    // Initialize logging ([logging] config, KREP_LOG_FORMAT overrides)
+    cardio_core::logging::init_with_config("info", &config.logging, None);
+
+    for problem in config.validate_with_catalog(get_default_catalog()) {
+        eprintln!("Config warning: {}", problem);
+    }
+
     let data_dir = cli.data_dir.unwrap_or_else(|| config.data.data_dir.clone());
 
     match cli.command {
-        Some(Commands::Now {
-            category,
-            dry_run,
-            auto_complete,
-            auto_complete_skip,
-        }) => cmd_now(
+        Some(Commands::Now(args)) => cmd_now(data_dir, args, &config),
+        Some(Commands::Watch { every, notify }) => cmd_watch(data_dir, every, notify, &config),
+        Some(Commands::Remind { hours, plan }) => cmd_remind(data_dir, hours, plan, &config),
+        Some(Commands::Snooze { minutes }) => cmd_snooze(data_dir, minutes, &config),
+        Some(Commands::History { days, tags, ids }) => {
+            cmd_history(data_dir, days, tags, ids, &config)
+        }
+        Some(Commands::Session { action }) => cmd_session(data_dir, action, &config),
+        Some(Commands::Weigh { weight_kg }) => cmd_weigh(data_dir, weight_kg, &config),
+        Some(Commands::Stats {
+            days,
+            tags,
+            compare,
+            timing,
+            strength,
+            format,
+        }) => cmd_stats(
             data_dir,
-            category,
+            StatsArgs {
+                days,
+                tags,
+                compare,
+                timing,
+                strength,
+                format,
+            },
+            &config,
+        ),
+        Some(Commands::Report {
+            week: _,
+            format,
+            output,
+        }) => cmd_report(data_dir, format, output, &config),
+        Some(Commands::Export { archive }) => cmd_export(data_dir, &archive, &config),
+        Some(Commands::Goal { action }) => cmd_goal(data_dir, action, &config),
+        Some(Commands::Mode { mode }) => cmd_mode(data_dir, mode, &config),
+        Some(Commands::Off { range, clear }) => cmd_off(data_dir, range, clear, &config),
+        Some(Commands::Achievements) => cmd_achievements(data_dir, &config),
+        Some(Commands::Status) => cmd_status(data_dir, &config),
+        Some(Commands::Analytics { action }) => cmd_analytics(data_dir, action, &config),
+        Some(Commands::Doctor) => cmd_doctor(data_dir, &config),
+        Some(Commands::Open { target, print }) => cmd_open(data_dir, target, print),
+        Some(Commands::Config { .. }) => unreachable!("handled before loading config"),
+        Some(Commands::Import { source }) => cmd_import(data_dir, source, &config),
+        Some(Commands::ImportDir { path, dry_run }) => {
+            cmd_import_dir(data_dir, &path, dry_run, &config)
+        }
+        Some(Commands::InstallSystemd {
             dry_run,
-            auto_complete,
-            auto_complete_skip,
+            reminder_hours,
+            rollup_at,
+            dir,
+        }) => cmd_install_systemd(
+            data_dir,
+            SystemdArgs {
+                dry_run,
+                reminder_hours,
+                rollup_at,
+                dir,
+            },
             &config,
         ),
-        Some(Commands::Rollup { cleanup }) => cmd_rollup(data_dir, cleanup),
+        Some(Commands::Rollup { cleanup }) => cmd_rollup(data_dir, cleanup, &config),
         None => {
             // Default to "now" command
-            cmd_now(data_dir, None, false, false, false, &config)
+            cmd_now(data_dir, NowArgs::default(), &config)
         }
     }
 }
 
-fn cmd_now(
-    data_dir: PathBuf,
-    category: Option<String>,
-    dry_run: bool,
-    auto_complete: bool,
-    auto_complete_skip: bool,
-    config: &Config,
-) -> Result<()> {
+/// The data directory, with the user's scripts when built with `scripting`
+fn open_store(data_dir: &Path, config: &Config) -> Result<DataStore> {
+    let store = DataStore::open(data_dir, config)?;
+    #[cfg(feature = "scripting")]
+    let store = store.with_scripts(cardio_core::scripting::Scripts::load_dir(
+        &cardio_core::scripting::scripts_dir(&Config::default_config_path()),
+    )?);
+    Ok(store)
+}
+
+fn cmd_now(data_dir: PathBuf, args: NowArgs, config: &Config) -> Result<()> {
     const AUTO_SKIP_SEQUENCE: usize = 3;
 
-    // Ensure directories exist
-    let wal_dir = data_dir.join("wal");
-    std::fs::create_dir_all(&wal_dir)?;
+    let NowArgs {
+        category,
+        surprise,
+        dry_run,
+        at,
+        qr,
+        auto_complete,
+        auto_complete_skip,
+        reps,
+        tags,
+        sore,
+        avg_hr,
+        max_hr,
+        hr_recovery,
+        rpe,
+    } = args;
+    let tags = normalize_tags(&tags);
 
-    // Set up paths
-    let state_path = wal_dir.join("state.json");
-    let wal_path = wal_dir.join("microdose_sessions.wal");
-    let csv_path = data_dir.join("sessions.csv");
-    let strength_path = data_dir.join("strength").join("signal.json");
+    // Open the data directory ([data] overrides individual files)
+    let mut store = open_store(&data_dir, config)?;
+    if let Some(at) = at {
+        store = store.with_clock(std::sync::Arc::new(FixedClock(at)));
+    }
+    // An unwritable data directory still gets a prescription, just unsaved
+    let read_only = match store.paths.ensure_writable(&data_dir) {
+        Ok(()) => false,
+        Err(e) => {
+            eprintln!(
+                "⚠ Data directory {} is not writable ({}).\n  Running read-only: nothing will be saved.",
+                data_dir.display(),
+                e
+            );
+            true
+        }
+    };
+    let catalog = store.catalog;
 
-    // Load catalog and state
-    // Use cached catalog for performance (eliminates 50+ allocations per run)
-    let catalog = get_default_catalog();
+    if let Some(range) = config.off_day_at(&store.state, store.now()) {
+        println!(
+            "Today is an off day ({}): nothing to do. Enjoy it (krep off --clear ends it early).",
+            range.label()
+        );
+        return Ok(());
+    }
+    if !read_only {
+        store.record_strength_history()?;
+        if let Some(abandoned) = store.abandon_stale()? {
+            println!(
+                "The {} dose started at {} was never finished; recorded as abandoned.",
+                abandoned.definition_id,
+                config.timezone().format(abandoned.started_at, "%H:%M")
+            );
+        }
+    }
 
-    let mut user_state = UserMicrodoseState::load(&state_path)?;
-    let strength_signal = load_external_strength(&strength_path)?;
+    // Soreness check-in: from --sore, or asked once per local day
+    let now = store.now();
+    let tz = config.timezone();
+    let interactive = !dry_run && !auto_complete && !auto_complete_skip;
+    let areas = match sore {
+        Some(answer) => Some(soreness::parse_areas(&answer)?),
+        None if interactive && soreness::needs_checkin(&store.state.soreness, &tz, now) => {
+            Some(prompt_soreness()?)
+        }
+        None => None,
+    };
+    if let Some(areas) = areas {
+        let checkin = SorenessCheckin {
+            checked_at: now,
+            areas,
+        };
+        soreness::record_checkin(&mut store.state.soreness, checkin, &tz);
+        if !read_only {
+            store.save_state()?;
+        }
+    }
 
-    // Load recent sessions (7 days)
-    let recent_sessions = load_recent_sessions(&wal_path, &csv_path, 7)?;
+    let recent_sessions = store.recent_sessions(store::PRESCRIPTION_HISTORY_DAYS)?;
 
     // Parse category if provided
     let target_category = category
         .as_ref()
-        .and_then(|c| match c.to_lowercase().as_str() {
-            "vo2" => Some(MicrodoseCategory::Vo2),
-            "gtg" => Some(MicrodoseCategory::Gtg),
-            "mobility" => Some(MicrodoseCategory::Mobility),
-            _ => {
-                eprintln!("Unknown category: {}. Using default selection.", c);
+        .and_then(|c| match c.parse::<MicrodoseCategory>() {
+            Ok(category) => Some(category),
+            Err(e) => {
+                eprintln!("{}. Using default selection.", e);
                 None
             }
         });
 
     // Build user context (mutable for skip logic)
     let mut recent_sessions = recent_sessions;
-    let mut ctx = UserContext {
-        now: chrono::Utc::now(),
-        user_state: user_state.clone(),
-        recent_sessions: recent_sessions.clone(),
-        external_strength: strength_signal,
-        equipment_available: config.equipment.available.clone(),
-    };
+    let mut ctx = store.user_context(recent_sessions.clone(), now);
+
+    // Progression changes made below go to the audit log
+    let state_before = store.state.clone();
 
     // Prescription loop - allows skip to re-prescribe
     let mut skipped_ids = std::collections::HashSet::new();
     let mut auto_skip_count = 0;
+    let mut logged = false;
 
     loop {
         // Update context with current sessions (may include fake skipped ones)
         ctx.recent_sessions = recent_sessions.clone();
 
         // Prescribe next microdose (clone target_category for reuse)
-        let prescription = prescribe_next(&catalog, &ctx, target_category.clone())?;
+        let prescription = if surprise {
+            store.surprise_prescription(&recent_sessions, now)?
+        } else {
+            store.next_prescription(&recent_sessions, target_category.clone(), now)?
+        };
 
         // Skip if we already showed this one
         if skipped_ids.contains(&prescription.definition.id) {
@@ -149,9 +694,32 @@ fn cmd_now(
         }
 
         // Display prescription
-        display_prescription(&prescription);
+        display_prescription(catalog, &prescription, qr);
+        if let Some(profile) = &config.active_profile {
+            println!("  Profile: {}", profile);
+        }
+        if config.office_mode(&ctx.user_state, now) {
+            println!("  Office mode: GTG-friendly doses only");
+        }
+        if let Some(until) = travel::active_until(&ctx.user_state, now) {
+            println!(
+                "  Travel mode: no equipment until {}",
+                tz.format(until, "%Y-%m-%d %H:%M")
+            );
+        }
+        if let Some(phase) = config.phase_at(now) {
+            println!("  Phase: {} until {}", phase.name.as_str(), phase.end);
+        }
 
         if dry_run {
+            match &ctx.readiness {
+                Some(readiness) => println!(
+                    "  Readiness: {} (measured {})",
+                    readiness.label(),
+                    tz.format(readiness.measured_at, "%Y-%m-%d %H:%M")
+                ),
+                None => println!("  Readiness: no signal"),
+            }
             println!("\n[Dry run - not logging session]");
             return Ok(());
         }
@@ -167,7 +735,7 @@ fn cmd_now(
                 UserAction::Done
             }
         } else {
-            prompt_user_action()?
+            prompt_user_action(prescription.definition.interval().is_some())?
         };
 
         match action {
@@ -184,11 +752,90 @@ fn cmd_now(
                 // Add to front of recent sessions to influence round-robin
                 recent_sessions.insert(0, skipped);
 
+                // Skips never reach the WAL; log them for `krep stats --timing`
+                if !read_only {
+                    store.skip(&prescription.definition.id, None)?;
+                }
+
                 println!("\nShowing next option...\n");
                 continue; // Re-prescribe
             }
 
-            UserAction::Done => {
+            UserAction::Done | UserAction::Timer | UserAction::Start => {
+                // Started here (timer or 'g') or earlier (the tray's timer),
+                // the session records its real start, finish, duration and
+                // pauses
+                let started = matches!(action, UserAction::Timer | UserAction::Start);
+                // Killed mid-dose, it times out as abandoned
+                if started && !read_only {
+                    store.start(&prescription.definition.id)?;
+                }
+                let started_at = store.now();
+                // Flat rep targets can be scaled down mid-timer
+                let adjustable = prescription.definition.ladder().is_none()
+                    && !prescription.definition.is_amrap();
+                let target = prescription.reps.filter(|_| adjustable);
+                let mut pause = |paused: bool| -> Result<()> {
+                    if read_only {
+                        return Ok(());
+                    }
+                    if paused {
+                        store.pause()?;
+                    } else {
+                        store.resume()?;
+                    }
+                    Ok(())
+                };
+                let adjusted = match (&action, prescription.definition.interval()) {
+                    (UserAction::Timer, Some(interval)) => {
+                        run_interval_timer(&interval, &config.sound, target, &mut pause)?
+                    }
+                    (UserAction::Start, _) => {
+                        wait_for_finish(&tz, started_at, &mut pause)?;
+                        None
+                    }
+                    _ => None,
+                };
+                let finished_at = store.now();
+
+                // Capture reps actually achieved per set (flag, timer adjustments,
+                // prompt, or nothing); an AMRAP records its rep total as a single set
+                let amrap = prescription.definition.is_amrap();
+                let per_set = match (reps.clone(), adjusted) {
+                    (Some(per_set), _) if amrap => vec![per_set.iter().sum()],
+                    (Some(per_set), _) => per_set,
+                    (None, Some(per_set)) => per_set,
+                    (None, None) if amrap && interactive => {
+                        prompt_amrap_total(prescription.definition.suggested_duration_seconds)?
+                            .into_iter()
+                            .collect()
+                    }
+                    (None, None) if prescription.reps.is_some() && interactive => {
+                        prompt_reps_per_set()?
+                    }
+                    (None, None) => vec![],
+                };
+                let metrics_realized = if per_set.is_empty() {
+                    vec![]
+                } else {
+                    vec![RealizedMetric::Reps {
+                        key: "reps".into(),
+                        per_set: per_set.clone(),
+                    }]
+                };
+
+                // Optional recovery check after VO2: flag, or measured when enabled
+                let hr_recovery = match hr_recovery {
+                    Some(drop) => Some(drop),
+                    None if interactive
+                        && config.heart_rate.recovery_check
+                        && prescription.definition.category == MicrodoseCategory::Vo2 =>
+                    {
+                        measure_hr_recovery()?
+                    }
+                    None => None,
+                };
+
                 // Create real session
                 let session = MicrodoseSession {
                     id: uuid::Uuid::new_v4(),
@@ -199,103 +846,2259 @@ fn cmd_now(
                     actual_duration_seconds: Some(
                         prescription.definition.suggested_duration_seconds,
                     ),
-                    metrics_realized: vec![], // Could capture actual reps here
-                    perceived_rpe: None,
-                    avg_hr: None,
-                    max_hr: None,
+                    metrics_realized,
+                    perceived_rpe: rpe,
+                    avg_hr,
+                    max_hr,
+                    hr_recovery,
+                    hr_series_path: None,
+                    paused_seconds: None,
+                    tags: tags.clone(),
                 };
+                let session = store.timed(session, finished_at);
 
-                // Append to WAL (only Real sessions can reach here)
-                let mut sink = JsonlSink::new(&wal_path);
-                sink.append(&session)?;
-
-                // Ensure base progression state exists for this definition
-                user_state
-                    .progressions
-                    .entry(prescription.definition.id.clone())
-                    .or_insert_with(|| ProgressionState {
-                        reps: prescription.reps.unwrap_or(0),
-                        style: prescription.style.clone().unwrap_or(MovementStyle::None),
-                        level: 0,
-                        last_upgraded: None,
-                    });
-
-                // Update mobility round-robin if applicable
-                if prescription.definition.category == MicrodoseCategory::Mobility {
-                    user_state.last_mobility_def_id = Some(prescription.definition.id.clone());
+                if read_only {
+                    println!("\n✓ Session done (not saved: data directory is read-only)");
+                    return Ok(());
                 }
 
-                // Persist updated state for all real sessions
-                user_state.save(&state_path)?;
+                // Append to WAL (only Real sessions can reach here) and save state
+                let dose = store.log(&session, &prescription)?;
+                for warning in &dose.warnings {
+                    eprintln!("⚠ {}", warning);
+                }
+                logged = true;
 
                 println!("\n✓ Session logged!");
+                if amrap {
+                    let best = store
+                        .state
+                        .personal_bests
+                        .get(&prescription.definition.id)
+                        .copied();
+                    print_amrap_result(&per_set, dose.personal_best, best);
+                } else if let (Some(target), None) =
+                    (prescription.reps, prescription.definition.ladder())
+                {
+                    // Ladder rungs differ by design, so only flat targets get a hint
+                    print_autoregulation(target, &per_set);
+                }
+
+                // Recent sessions felt easy: offer the upgrade right away
+                if prescription.ready_to_progress {
+                    if interactive && prompt_progress_now()? {
+                        increase_intensity(
+                            &prescription.definition.id,
+                            &mut store.state,
+                            config,
+                            ctx.now,
+                        );
+                        store.save_state()?;
+                        let progression = &store.state.progressions[&prescription.definition.id];
+                        println!(
+                            "  ✓ Progressed: level {}, {} reps next time",
+                            progression.level, progression.reps
+                        );
+                    } else if !interactive {
+                        println!("  ↑ Ready to progress - press 'h' next time to go harder");
+                    }
+                }
                 break; // Exit loop
             }
 
             UserAction::Harder => {
+                if read_only {
+                    println!("\nIntensity not changed: data directory is read-only");
+                    return Ok(());
+                }
+                if ctx.progression_paused {
+                    println!("\nIntensity not changed: progression is paused for the deload");
+                    break;
+                }
+
                 // Increase intensity
-                increase_intensity(&prescription.definition.id, &mut user_state, config);
-                user_state.save(&state_path)?;
+                increase_intensity(
+                    &prescription.definition.id,
+                    &mut store.state,
+                    config,
+                    ctx.now,
+                );
+                store.save_state()?;
 
                 println!("\n✓ Intensity increased for next time!");
-                println!(
-                    "  Level: {}",
-                    user_state.progressions[&prescription.definition.id].level
-                );
-                println!(
-                    "  Reps: {}",
-                    user_state.progressions[&prescription.definition.id].reps
-                );
+                let progression = &store.state.progressions[&prescription.definition.id];
+                println!("  Level: {}", progression.level);
+                println!("  Reps: {}", progression.reps);
                 break; // Exit loop
             }
         }
     }
 
+    store.record_progression(&state_before, ctx.now)?;
+
+    if logged {
+        for achievement in store.check_achievements(ctx.now)? {
+            println!(
+                "🏆 Achievement unlocked: {} – {}",
+                achievement.name, achievement.description
+            );
+        }
+    }
+
     Ok(())
 }
 
-fn cmd_rollup(data_dir: PathBuf, cleanup: bool) -> Result<()> {
-    let wal_dir = data_dir.join("wal");
-    let wal_path = wal_dir.join("microdose_sessions.wal");
-    let csv_path = data_dir.join("sessions.csv");
+/// Minutes a due dose is put off by 's' in `krep watch`
+const WATCH_SNOOZE_MINUTES: u32 = 15;
 
-    if !wal_path.exists() {
-        println!("No WAL file found - nothing to roll up.");
-        return Ok(());
-    }
+/// Foreground loop: countdown, bell, then the interactive `now` flow
+fn cmd_watch(data_dir: PathBuf, every: u32, notify: bool, config: &Config) -> Result<()> {
+    use std::io::IsTerminal;
 
-    let count = cardio_core::csv_rollup::wal_to_csv_and_archive(&wal_path, &csv_path)?;
+    let every = chrono::Duration::minutes(i64::from(every));
+    let tz = config.timezone();
+    let live = io::stdout().is_terminal();
+    // A dose started but not logged comes round again one interval later
+    let mut last_prompt: Option<chrono::DateTime<chrono::Utc>> = None;
+    let mut snoozed_until: Option<chrono::DateTime<chrono::Utc>> = None;
 
-    println!("✓ Rolled up {} sessions to CSV", count);
-    println!("  CSV: {}", csv_path.display());
+    loop {
+        let FileStorage {
+            wal_path, csv_path, ..
+        } = FileStorage::with_config(&data_dir, &config.data);
+        let sessions = load_recent_sessions_with(
+            &wal_path,
+            &csv_path,
+            every.num_days() + 1,
+            config.data.exclude_anomalies,
+            config.data.lock_timeout(),
+        )?;
+        let last_session = sessions
+            .iter()
+            .filter_map(|s| s.as_real())
+            .map(|s| s.performed_at)
+            .max();
+        let due = last_session
+            .into_iter()
+            .chain(last_prompt)
+            .max()
+            .map_or_else(chrono::Utc::now, |at| at + every);
+        let due = snoozed_until.map_or(due, |until| until.max(due));
 
-    if cleanup {
-        let cleaned = cardio_core::csv_rollup::cleanup_processed_wals(&wal_dir)?;
-        if cleaned > 0 {
-            println!("✓ Cleaned up {} processed WAL files", cleaned);
+        if live {
+            loop {
+                let left = (due - chrono::Utc::now()).num_seconds();
+                if left <= 0 {
+                    break;
+                }
+                print!(
+                    "\r⏳ Next dose in {} (at {}) · Ctrl-C to quit ",
+                    format_countdown(left),
+                    tz.format(due, "%H:%M")
+                );
+                io::stdout().flush()?;
+                std::thread::sleep(std::time::Duration::from_secs(1));
+            }
+            print!("\r{:<50}\r", "");
+        } else if let Ok(wait) = (due - chrono::Utc::now()).to_std() {
+            println!("Next dose at {}", tz.format(due, "%H:%M"));
+            std::thread::sleep(wait);
         }
-    }
 
-    Ok(())
+        println!("\x07🔔 Time for a microdose!");
+        if notify {
+            send_notification("Time for a microdose", "krep watch: your next dose is due");
+        }
+        print!(
+            "Enter to start, 's' + Enter to snooze {} min, 'q' + Enter to quit: ",
+            WATCH_SNOOZE_MINUTES
+        );
+        io::stdout().flush()?;
+        let mut input = String::new();
+        if io::stdin().read_line(&mut input)? == 0 || input.trim().eq_ignore_ascii_case("q") {
+            println!();
+            return Ok(());
+        }
+        if input.trim().eq_ignore_ascii_case("s") {
+            let now = chrono::Utc::now();
+            timing::append_snooze(
+                &data_dir.join(timing::SNOOZE_LOG_FILE),
+                &timing::SnoozeEvent {
+                    snoozed_at: now,
+                    minutes: WATCH_SNOOZE_MINUTES,
+                },
+                config.data.lock_timeout(),
+            )?;
+            snoozed_until = Some(now + chrono::Duration::minutes(i64::from(WATCH_SNOOZE_MINUTES)));
+            continue;
+        }
+
+        last_prompt = Some(chrono::Utc::now());
+        cmd_now(data_dir.clone(), NowArgs::default(), config)?;
+        println!();
+    }
 }
 
-fn display_prescription(prescription: &PrescribedMicrodose) {
-    println!("\n╭─────────────────────────────────────────╮");
-    println!("│  {:?} MICRODOSE", prescription.definition.category);
-    println!("╰─────────────────────────────────────────╯");
-    println!();
-    println!("  {}", prescription.definition.name);
-    println!(
-        "  Duration: ~{} seconds ({} min)",
-        prescription.definition.suggested_duration_seconds,
-        prescription.definition.suggested_duration_seconds / 60
-    );
-    println!();
+/// Days of history behind the reminder hours (adherence uses the last week)
+const REMINDER_HISTORY_DAYS: i64 = 30;
 
-    for _ in &prescription.definition.blocks {
-        if let Some(reps) = prescription.reps {
-            println!("  → {} reps", reps);
-        }
+fn cmd_remind(data_dir: PathBuf, hours: Vec<u32>, show_plan: bool, config: &Config) -> Result<()> {
+    use chrono::Timelike;
+
+    let configured = if hours.is_empty() {
+        systemd::DEFAULT_REMINDER_HOURS.to_vec()
+    } else {
+        hours
+    };
+    let FileStorage {
+        wal_path,
+        csv_path,
+        state_path,
+        ..
+    } = FileStorage::with_config(&data_dir, &config.data);
+    let now = chrono::Utc::now();
+    if let Some(range) = config.off_day_at(
+        &UserMicrodoseState::load(&state_path, config.data.lock_timeout())?,
+        now,
+    ) {
+        if show_plan {
+            println!("No reminders today: off day ({})", range.label());
+        } else {
+            tracing::debug!("No reminder on an off day ({})", range.label());
+        }
+        return Ok(());
+    }
+    let sessions = load_recent_sessions_with(
+        &wal_path,
+        &csv_path,
+        REMINDER_HISTORY_DAYS,
+        config.data.exclude_anomalies,
+        config.data.lock_timeout(),
+    )?;
+    let skips = timing::read_skips(
+        &data_dir.join(timing::SKIP_LOG_FILE),
+        config.data.lock_timeout(),
+    )?;
+    let snoozes = timing::read_snoozes(
+        &data_dir.join(timing::SNOOZE_LOG_FILE),
+        config.data.lock_timeout(),
+    )?;
+    let tz = config.timezone();
+    let by_hour = |days: i64| {
+        timing::completion_by_hour(
+            &sessions,
+            &skips,
+            &snoozes,
+            &tz,
+            now - chrono::Duration::days(days),
+        )
+    };
+    let plan = cadence::plan(
+        &configured,
+        &by_hour(REMINDER_HISTORY_DAYS),
+        cadence::adherence(&by_hour(cadence::ADHERENCE_DAYS)),
+    );
+
+    if show_plan {
+        let times: Vec<String> = plan.hours.iter().map(|h| format!("{:02}:00", h)).collect();
+        println!("Reminders today: {}", times.join(", "));
+        for (from, to) in &plan.shifted {
+            println!(
+                "  {:02}:00 → {:02}:00 (doses get skipped or snoozed at {:02}:00 but done at {:02}:00)",
+                from, to, from, to
+            );
+        }
+        if plan.spaced_out {
+            println!(
+                "  Spaced out: {:.0}% of doses done in the last {} days",
+                plan.adherence.unwrap_or_default() * 100.0,
+                cadence::ADHERENCE_DAYS
+            );
+        }
+        return Ok(());
+    }
+
+    let hour = tz.time_of(now).hour();
+    if plan.hours.contains(&hour) {
+        remind_now(&data_dir, config, now)?;
+    } else {
+        tracing::debug!(
+            "No reminder at {:02}:00 (reminding at {:?})",
+            hour,
+            plan.hours
+        );
+    }
+    Ok(())
+}
+
+/// Send the reminder: GTG doses get a quick-log notification (Done, Skip,
+/// Open) so they never need a window, anything else the plain one
+fn remind_now(data_dir: &Path, config: &Config, now: chrono::DateTime<chrono::Utc>) -> Result<()> {
+    let store = open_store(data_dir, config)?;
+    let recent_sessions = store.recent_sessions(store::PRESCRIPTION_HISTORY_DAYS)?;
+    let prescription = store.next_prescription(&recent_sessions, None, now)?;
+    if prescription.definition.category != MicrodoseCategory::Gtg {
+        send_notification(
+            "Time for a microdose",
+            "Run: krep now (or krep snooze if now is bad)",
+        );
+        return Ok(());
+    }
+
+    let summary = format!("GTG: {}", prescription.definition.name);
+    let body = prescription
+        .reps_label()
+        .unwrap_or_else(|| "Done logs it as prescribed".to_string());
+    let action = notify_with_actions(
+        &summary,
+        &body,
+        &[("done", "Done"), ("skip", "Skip"), ("open", "Open")],
+    );
+    match action.as_deref() {
+        Some("done") => quick_log(data_dir, config, &prescription)?,
+        Some("skip") => store.skip(&prescription.definition.id, None)?,
+        Some("open") => open_now_in_terminal(data_dir, config)?,
+        // Dismissed or expired
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Notification with action buttons (notify-send 0.7.9+), waiting for the
+/// one picked; `None` when dismissed, or after falling back to a plain
+/// notification when actions aren't supported
+fn notify_with_actions(summary: &str, body: &str, actions: &[(&str, &str)]) -> Option<String> {
+    let mut command = std::process::Command::new("notify-send");
+    command.args(["--app-name=krep", "--wait"]);
+    for (name, label) in actions {
+        command.arg(format!("--action={}={}", name, label));
+    }
+    match command.args([summary, body]).output() {
+        Ok(output) if output.status.success() => {
+            let action = String::from_utf8_lossy(&output.stdout).trim().to_string();
+            (!action.is_empty()).then_some(action)
+        }
+        Ok(_) => {
+            send_notification(summary, "Run: krep now (or krep snooze if now is bad)");
+            None
+        }
+        Err(e) => {
+            eprintln!("Could not send a desktop notification (notify-send: {})", e);
+            None
+        }
+    }
+}
+
+/// Log a dose done as prescribed (the notification's Done), with the same
+/// bookkeeping as `krep now`
+fn quick_log(data_dir: &Path, config: &Config, prescription: &PrescribedMicrodose) -> Result<()> {
+    let now = chrono::Utc::now();
+    // Reopened: the notification may have waited a while
+    let mut store = DataStore::open(data_dir, config)?;
+    let state_before = store.state.clone();
+
+    let dose = store.log(&prescription.done_as_prescribed(now), prescription)?;
+    for warning in &dose.warnings {
+        eprintln!("⚠ {}", warning);
+    }
+    store.record_progression(&state_before, now)?;
+    let unlocked = store.check_achievements(now)?;
+
+    println!("✓ Logged {}", prescription.definition.name);
+    for achievement in unlocked {
+        send_notification(&format!("🏆 {}", achievement.name), achievement.description);
+    }
+    Ok(())
+}
+
+/// The full `krep now` flow for a GTG dose in a terminal ($TERMINAL, else
+/// x-terminal-emulator)
+fn open_now_in_terminal(data_dir: &Path, config: &Config) -> Result<()> {
+    let terminal = std::env::var("TERMINAL")
+        .ok()
+        .filter(|t| !t.is_empty())
+        .unwrap_or_else(|| "x-terminal-emulator".to_string());
+    let mut command = std::process::Command::new(&terminal);
+    command
+        .arg("-e")
+        .arg(std::env::current_exe()?)
+        .args(["now", "--category", "gtg", "--data-dir"])
+        .arg(data_dir);
+    if let Some(profile) = &config.active_profile {
+        command.args(["--profile", profile]);
+    }
+    command
+        .spawn()
+        .map_err(|e| Error::Other(format!("Could not open a terminal ({}): {}", terminal, e)))?;
+    Ok(())
+}
+
+fn cmd_snooze(data_dir: PathBuf, minutes: u32, config: &Config) -> Result<()> {
+    timing::append_snooze(
+        &data_dir.join(timing::SNOOZE_LOG_FILE),
+        &timing::SnoozeEvent {
+            snoozed_at: chrono::Utc::now(),
+            minutes,
+        },
+        config.data.lock_timeout(),
+    )?;
+    println!(
+        "✓ Snoozed for {} min (reminder times adapt to snoozes)",
+        minutes
+    );
+    Ok(())
+}
+
+/// `1:02:03` or `4:05`
+fn format_countdown(seconds: i64) -> String {
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, seconds)
+    } else {
+        format!("{}:{:02}", minutes, seconds)
+    }
+}
+
+/// Desktop notification via notify-send; failures only warn, the bell already rang
+fn send_notification(summary: &str, body: &str) {
+    if let Err(e) = std::process::Command::new("notify-send")
+        .args([summary, body])
+        .status()
+    {
+        eprintln!("Could not send a desktop notification (notify-send: {})", e);
+    }
+}
+
+fn cmd_status(data_dir: PathBuf, config: &Config) -> Result<()> {
+    let current = DataStore::open(&data_dir, config)?.status(chrono::Utc::now())?;
+    if let Some(path) = &config.status.file {
+        status::write(path, &current)?;
+    }
+    println!("{}", serde_json::to_string_pretty(&current)?);
+    Ok(())
+}
+
+fn cmd_achievements(data_dir: PathBuf, config: &Config) -> Result<()> {
+    let state_path = FileStorage::with_config(&data_dir, &config.data).state_path;
+    let mut user_state = UserMicrodoseState::load(&state_path, config.data.lock_timeout())?;
+    let tz = config.timezone();
+
+    // Pick up anything earned before achievements existed
+    let now = chrono::Utc::now();
+    if !achievements::check_data_dir(&data_dir, &mut user_state, config, now)?.is_empty() {
+        user_state.save(&state_path, config.data.lock_timeout())?;
+    }
+
+    println!(
+        "Achievements ({}/{})",
+        user_state.achievements.len(),
+        achievements::ACHIEVEMENTS.len()
+    );
+    for achievement in &achievements::ACHIEVEMENTS {
+        match user_state.achievements.get(achievement.id) {
+            Some(at) => println!(
+                "  🏆 {:<18} {}  ({})",
+                achievement.name,
+                achievement.description,
+                tz.date_of(*at)
+            ),
+            None => println!("  ·  {:<18} {}", achievement.name, achievement.description),
+        }
+    }
+    Ok(())
+}
+
+fn cmd_analytics(data_dir: PathBuf, action: AnalyticsAction, config: &Config) -> Result<()> {
+    match action {
+        AnalyticsAction::Build { output } => {
+            let FileStorage {
+                wal_path, csv_path, ..
+            } = FileStorage::with_config(&data_dir, &config.data);
+            let db_path = output.unwrap_or_else(|| data_dir.join(analytics::ANALYTICS_DB_FILE));
+
+            let count = analytics::build(
+                &wal_path,
+                &csv_path,
+                &db_path,
+                get_default_catalog(),
+                &config.timezone(),
+                config.data.lock_timeout(),
+            )?;
+            println!(
+                "✓ Built analytics database with {} sessions: {}",
+                count,
+                db_path.display()
+            );
+            println!(
+                "  Try: sqlite3 {} 'SELECT * FROM daily_summary'",
+                db_path.display()
+            );
+        }
+    }
+    Ok(())
+}
+
+fn cmd_history(
+    data_dir: PathBuf,
+    days: i64,
+    tags: Vec<String>,
+    ids: bool,
+    config: &Config,
+) -> Result<()> {
+    let FileStorage {
+        wal_path,
+        csv_path,
+        state_path,
+        ..
+    } = FileStorage::with_config(&data_dir, &config.data);
+    let catalog = get_default_catalog();
+    let tz = config.timezone();
+
+    let tags = normalize_tags(&tags);
+    let sessions = load_recent_sessions_with(
+        &wal_path,
+        &csv_path,
+        days,
+        config.data.exclude_anomalies,
+        config.data.lock_timeout(),
+    )?;
+    let sessions: Vec<SessionKind> = cardio_core::history::filter_by_tags(&sessions, &tags)
+        .into_iter()
+        .cloned()
+        .collect();
+    if sessions.is_empty() {
+        println!("No sessions in the last {} days.", days);
+        return Ok(());
+    }
+
+    for session in sessions.iter().filter_map(|s| s.as_real()) {
+        let name = catalog
+            .definition(&session.definition_id)
+            .map(|d| d.name.as_str())
+            .unwrap_or(session.definition_id.as_str());
+        let when = tz.format(session.performed_at, "%Y-%m-%d %H:%M");
+
+        let mut line = format!("{}  {}", when, name);
+        if let Some(per_set) = session.reps_per_set() {
+            line.push_str(&format!("  {}", format_per_set(per_set)));
+        }
+        if !session.tags.is_empty() {
+            line.push_str(&format!("  [{}]", session.tags.join(", ")));
+        }
+        if ids {
+            if let (Some(avg), Some(max)) = (session.avg_hr, session.max_hr) {
+                line.push_str(&format!("  ♥ {}/{}", avg, max));
+            }
+            line.push_str(&format!("  {}", session.id));
+        }
+        println!("{}", line);
+    }
+
+    let now = chrono::Utc::now();
+    let user_state = UserMicrodoseState::load(&state_path, config.data.lock_timeout())?;
+    println!();
+    println!(
+        "Today: {} sessions · Streak: {} days",
+        cardio_core::history::sessions_today(&sessions, &tz, now),
+        goal::forgiving_streak(
+            &sessions,
+            user_state.weekly_goal.as_ref(),
+            &config.off_days(&user_state),
+            &tz,
+            now
+        )
+    );
+
+    Ok(())
+}
+
+fn cmd_weigh(data_dir: PathBuf, weight_kg: f64, config: &Config) -> Result<()> {
+    if !weight_kg.is_finite() || weight_kg <= 0.0 || weight_kg > 500.0 {
+        return Err(Error::Other(format!(
+            "Implausible bodyweight: {} kg",
+            weight_kg
+        )));
+    }
+
+    let entry = cardio_core::bodyweight::BodyweightEntry {
+        measured_at: chrono::Utc::now(),
+        weight_kg,
+    };
+    let path = data_dir.join(cardio_core::bodyweight::BODYWEIGHT_FILE);
+    cardio_core::bodyweight::append_bodyweight(&path, &entry, config.data.lock_timeout())?;
+
+    println!("✓ Logged {:.1} kg", weight_kg);
+    Ok(())
+}
+
+/// Options for `krep stats`
+struct StatsArgs {
+    days: i64,
+    tags: Vec<String>,
+    compare: bool,
+    timing: bool,
+    strength: bool,
+    format: String,
+}
+
+fn cmd_stats(data_dir: PathBuf, args: StatsArgs, config: &Config) -> Result<()> {
+    let StatsArgs {
+        days,
+        tags,
+        compare,
+        timing: show_timing,
+        strength: show_strength,
+        format,
+    } = args;
+    let json = match format.as_str() {
+        "text" => false,
+        "json" => true,
+        other => {
+            return Err(Error::Other(format!(
+                "Unknown stats format '{}' (expected text or json)",
+                other
+            )))
+        }
+    };
+    let FileStorage {
+        wal_path,
+        csv_path,
+        state_path,
+        strength_signal_path,
+        strength_history_path,
+        ..
+    } = FileStorage::with_config(&data_dir, &config.data);
+    let catalog = get_default_catalog();
+
+    let tags = normalize_tags(&tags);
+    let sessions = load_recent_sessions_with(
+        &wal_path,
+        &csv_path,
+        days,
+        config.data.exclude_anomalies,
+        config.data.lock_timeout(),
+    )?;
+    let sessions: Vec<SessionKind> = cardio_core::history::filter_by_tags(&sessions, &tags)
+        .into_iter()
+        .cloned()
+        .collect();
+    if json {
+        let report = stats_report(&data_dir, &sessions, &tags, days, compare, config)?;
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+
+    let summary = cardio_core::stats::summarize_sessions(&sessions, catalog, days);
+
+    println!("Last {} days", days);
+    println!(
+        "  Sessions: {} ({} min)",
+        summary.total_sessions,
+        summary.total_seconds / 60
+    );
+    for (category, count) in &summary.by_category {
+        println!("  {:<10} {}", category, count);
+    }
+
+    // All of history comes from the aggregates cache, recomputed only when
+    // it is missing or stale
+    if tags.is_empty() {
+        let tz = config.timezone();
+        let cache = stats_cache::load_or_rebuild(
+            &wal_path,
+            &csv_path,
+            catalog,
+            &tz,
+            config.data.lock_timeout(),
+        )?;
+        let all_time = cache.all_time();
+        let user_state = UserMicrodoseState::load(&state_path, config.data.lock_timeout())?;
+        println!(
+            "  All time: {} sessions ({} min) · Streak: {} days",
+            all_time.sessions,
+            all_time.seconds / 60,
+            cache.streak(&config.off_days(&user_state), &tz, chrono::Utc::now())
+        );
+    }
+
+    if compare {
+        let fortnight = load_recent_sessions_with(
+            &wal_path,
+            &csv_path,
+            14,
+            config.data.exclude_anomalies,
+            config.data.lock_timeout(),
+        )?;
+        let fortnight: Vec<SessionKind> = cardio_core::history::filter_by_tags(&fortnight, &tags)
+            .into_iter()
+            .cloned()
+            .collect();
+        print_week_comparison(&stats::compare_weeks(
+            &fortnight,
+            catalog,
+            chrono::Utc::now(),
+        ));
+    }
+
+    if show_timing {
+        let skips = timing::read_skips(
+            &data_dir.join(timing::SKIP_LOG_FILE),
+            config.data.lock_timeout(),
+        )?;
+        let snoozes = timing::read_snoozes(
+            &data_dir.join(timing::SNOOZE_LOG_FILE),
+            config.data.lock_timeout(),
+        )?;
+        let since = chrono::Utc::now() - chrono::Duration::days(days);
+        print_timing(&timing::completion_by_hour(
+            &sessions,
+            &skips,
+            &snoozes,
+            &config.timezone(),
+            since,
+        ));
+        let mut reasons: Vec<String> = timing::skip_reasons(&skips, since)
+            .iter()
+            .map(|(reason, count)| format!("{} {}", reason.label(), count))
+            .collect();
+        let ignored = timing::soft_skips(&skips, since);
+        if ignored > 0 {
+            reasons.push(format!("ignored {}", ignored));
+        }
+        if !reasons.is_empty() {
+            println!("  Skip reasons: {}", reasons.join(", "));
+        }
+        let abandoned = timing::read_abandons(
+            &data_dir.join(timing::ABANDON_LOG_FILE),
+            config.data.lock_timeout(),
+        )?
+        .iter()
+        .filter(|a| a.started_at >= since)
+        .count();
+        if abandoned > 0 {
+            println!("  Abandoned (started, never finished): {}", abandoned);
+        }
+    }
+
+    if show_strength {
+        strength::record_sessions(
+            &strength_history_path,
+            &strength::load_strength_sessions(&strength_signal_path)?,
+            config.data.lock_timeout(),
+        )?;
+        let skips = timing::read_skips(
+            &data_dir.join(timing::SKIP_LOG_FILE),
+            config.data.lock_timeout(),
+        )?;
+        let now = chrono::Utc::now();
+        let since = now - chrono::Duration::days(days);
+        let history = strength::read_history(&strength_history_path, config.data.lock_timeout())?;
+        print_strength_adherence(
+            &strength::adherence_by_strength_day(
+                catalog,
+                &sessions,
+                &skips,
+                &history,
+                &config.timezone(),
+                since,
+            ),
+            strength::StrengthHistory::new(history).per_week(since, now),
+        );
+    }
+
+    print_time_in_category(&stats::time_in_category(
+        &sessions,
+        catalog,
+        &config.timezone(),
+    ));
+
+    let now = chrono::Utc::now();
+    let tz = config.timezone();
+    print_rehab(
+        &rehab::summarize(
+            &UserMicrodoseState::load(&state_path, config.data.lock_timeout())?.rehab,
+            &sessions,
+            catalog,
+            now - chrono::Duration::days(days),
+            now,
+        ),
+        &tz,
+    );
+    print_phases(&phase::summarize(
+        &config.phases,
+        &sessions,
+        catalog,
+        &tz,
+        tz.date_of(now - chrono::Duration::days(days)),
+        tz.date_of(now),
+    ));
+    print_household(&household::load(config, &data_dir, catalog, days, now)?);
+
+    // Targets always cover the trailing week of all sessions, like the engine
+    let week = load_recent_sessions_with(
+        &wal_path,
+        &csv_path,
+        7,
+        config.data.exclude_anomalies,
+        config.data.lock_timeout(),
+    )?;
+    let targets = cardio_core::stats::target_progress(
+        &week,
+        catalog,
+        &config.weekly_targets(),
+        chrono::Utc::now() - chrono::Duration::days(7),
+    );
+    if !targets.is_empty() {
+        println!();
+        println!("Weekly targets (last 7 days)");
+        for progress in &targets {
+            println!(
+                "  {:<10} {:>2}/{:<2} {}{}",
+                progress.category.as_str(),
+                progress.done,
+                progress.target,
+                progress_bar(progress.fraction()),
+                if progress.remaining() == 0 {
+                    " ✓"
+                } else {
+                    ""
+                }
+            );
+        }
+    }
+
+    let user_state = UserMicrodoseState::load(&state_path, config.data.lock_timeout())?;
+    if let Some(weekly_goal) = &user_state.weekly_goal {
+        let progress =
+            goal::current_progress(weekly_goal, &week, &config.timezone(), chrono::Utc::now());
+        println!();
+        print_goal_progress(&progress);
+    }
+
+    if let Some(trend) = heart_rate::recovery_trend(&sessions) {
+        println!();
+        println!(
+            "  HR recovery (60s): {} bpm latest, {:.0} bpm mean ({:+} bpm)",
+            trend.latest_bpm, trend.mean_bpm, trend.change_bpm
+        );
+        let series: Vec<f64> = trend.series_bpm.iter().map(|d| f64::from(*d)).collect();
+        println!("  Trend: {}", stats::sparkline(&series));
+    }
+
+    if let Some(max_hr) = config.max_hr() {
+        for hr in heart_rate::compare_sessions(&sessions, catalog, max_hr) {
+            let (low, high) = hr.zone.bpm(max_hr);
+            println!();
+            println!(
+                "  {} HR: {}/{} sessions in zone ({}–{} bpm)",
+                hr.category.as_str(),
+                hr.met_zone,
+                hr.sessions_with_hr,
+                low,
+                high
+            );
+            let bpm = |v: Option<u32>| v.map_or("-".to_string(), |v| format!("{} bpm", v));
+            println!(
+                "    mean avg {}, mean max {}",
+                bpm(hr.mean_avg_hr),
+                bpm(hr.mean_max_hr)
+            );
+        }
+    }
+
+    let since = chrono::Utc::now() - chrono::Duration::days(days);
+
+    // Prescribed reps over the window, per definition with logged progression
+    let events = progression_log::read_events(
+        &data_dir.join(progression_log::PROGRESSION_LOG_FILE),
+        config.data.lock_timeout(),
+    )?;
+    let mut def_ids: Vec<&str> = events.iter().map(|e| e.definition_id.as_str()).collect();
+    def_ids.sort();
+    def_ids.dedup();
+    let mut printed_header = false;
+    for def_id in def_ids {
+        let series: Vec<_> = stats::progression_series(def_id, &events, &sessions)
+            .into_iter()
+            .filter(|p| p.at >= since)
+            .collect();
+        let (Some(first), Some(last)) = (series.first(), series.last()) else {
+            continue;
+        };
+        if !printed_header {
+            println!();
+            println!("Progression");
+            printed_header = true;
+        }
+        let reps: Vec<f64> = series.iter().map(|p| f64::from(p.reps)).collect();
+        let name = catalog
+            .definition(def_id)
+            .map_or(def_id, |d| d.name.as_str());
+        println!(
+            "  {:<32} {} {} → {} reps (level {})",
+            name,
+            stats::sparkline(&reps),
+            first.reps,
+            last.reps,
+            last.level
+        );
+    }
+
+    let weigh_ins = cardio_core::bodyweight::read_bodyweight(
+        &data_dir.join(cardio_core::bodyweight::BODYWEIGHT_FILE),
+        config.data.lock_timeout(),
+    )?;
+    if let Some(trend) = cardio_core::bodyweight::trend(&weigh_ins, since) {
+        println!();
+        print!(
+            "  Bodyweight: {:.1} kg ({:+.1} kg",
+            trend.latest_kg, trend.change_kg
+        );
+        if let Some(slope) = trend.slope_kg_per_week {
+            print!(", {:+.2} kg/week", slope);
+        }
+        println!(")");
+        println!("  Trend: {}", stats::sparkline(&trend.series_kg));
+    }
+
+    Ok(())
+}
+
+/// Ten-cell bar for a 0.0–1.0 fraction
+fn progress_bar(fraction: f64) -> String {
+    let filled = (fraction.clamp(0.0, 1.0) * 10.0).round() as usize;
+    format!("{}{}", "█".repeat(filled), "░".repeat(10 - filled))
+}
+
+fn print_goal_progress(progress: &goal::GoalProgress) {
+    println!(
+        "Weekly goal{}: {}/{} {} {}",
+        if progress.travel {
+            " (travel week)"
+        } else {
+            ""
+        },
+        progress.done,
+        progress.target,
+        progress_bar(progress.fraction()),
+        if progress.met() {
+            "✓".to_string()
+        } else {
+            format!("{} to go", progress.remaining())
+        }
+    );
+}
+
+fn cmd_goal(data_dir: PathBuf, action: GoalAction, config: &Config) -> Result<()> {
+    let FileStorage {
+        wal_path,
+        csv_path,
+        state_path,
+        ..
+    } = FileStorage::with_config(&data_dir, &config.data);
+    let mut user_state = UserMicrodoseState::load(&state_path, config.data.lock_timeout())?;
+
+    match action {
+        GoalAction::Set {
+            weekly_doses,
+            travel_doses,
+        } => {
+            if weekly_doses == 0 {
+                return Err(Error::Other("Weekly goal must be at least 1 dose".into()));
+            }
+            if travel_doses.is_some_and(|t| t == 0 || t > weekly_doses) {
+                return Err(Error::Other(format!(
+                    "Travel goal must be between 1 and {} doses",
+                    weekly_doses
+                )));
+            }
+            user_state.weekly_goal = Some(WeeklyGoal {
+                weekly_doses,
+                travel_weekly_doses: travel_doses,
+            });
+            user_state.save(&state_path, config.data.lock_timeout())?;
+            print!("✓ Weekly goal set to {} doses", weekly_doses);
+            match travel_doses {
+                Some(travel) => println!(" ({} on travel weeks)", travel),
+                None => println!(),
+            }
+        }
+        GoalAction::Show => {}
+        GoalAction::Clear => {
+            user_state.weekly_goal = None;
+            if state_path.exists() {
+                user_state.save(&state_path, config.data.lock_timeout())?;
+            }
+            println!("✓ Weekly goal cleared");
+            return Ok(());
+        }
+    }
+
+    let Some(weekly_goal) = &user_state.weekly_goal else {
+        println!("No weekly goal set (krep goal set --weekly-doses 15)");
+        return Ok(());
+    };
+    let week = load_recent_sessions_with(
+        &wal_path,
+        &csv_path,
+        7,
+        config.data.exclude_anomalies,
+        config.data.lock_timeout(),
+    )?;
+    print_goal_progress(&goal::current_progress(
+        weekly_goal,
+        &week,
+        &config.timezone(),
+        chrono::Utc::now(),
+    ));
+    Ok(())
+}
+
+fn cmd_off(data_dir: PathBuf, range: Option<String>, clear: bool, config: &Config) -> Result<()> {
+    let storage = FileStorage::with_config(&data_dir, &config.data);
+    let today = config.timezone().date_of(chrono::Utc::now());
+
+    if clear {
+        let mut cleared = 0;
+        if storage.state_path.exists() {
+            UserMicrodoseState::update(&storage.state_path, config.data.lock_timeout(), |state| {
+                cleared = off_days::clear_upcoming(&mut state.off_days, today);
+                Ok(())
+            })?;
+        }
+        println!("✓ Cleared {} upcoming off-day range(s)", cleared);
+        return Ok(());
+    }
+    if let Some(range) = range {
+        let range: off_days::OffRange = range.parse()?;
+        UserMicrodoseState::update(&storage.state_path, config.data.lock_timeout(), |state| {
+            off_days::add(&mut state.off_days, range);
+            Ok(())
+        })?;
+        println!(
+            "✓ Off {}: no prescriptions or reminders, and the streak holds",
+            range.label()
+        );
+        return Ok(());
+    }
+
+    let state = UserMicrodoseState::load(&storage.state_path, config.data.lock_timeout())?;
+    let upcoming: Vec<_> = config
+        .off_days(&state)
+        .into_iter()
+        .filter(|range| range.end >= today)
+        .collect();
+    if upcoming.is_empty() {
+        println!("No upcoming off days (krep off 2024-08-10..2024-08-20)");
+        return Ok(());
+    }
+    println!("Off days:");
+    for range in upcoming {
+        let note = if range.contains(today) {
+            " (today)"
+        } else {
+            ""
+        };
+        println!("  {}{}", range.label(), note);
+    }
+    Ok(())
+}
+
+fn cmd_mode(data_dir: PathBuf, mode: ModeAction, config: &Config) -> Result<()> {
+    let storage = FileStorage::with_config(&data_dir, &config.data);
+    let tz = config.timezone();
+    let now = chrono::Utc::now();
+
+    let switch = match mode {
+        ModeAction::Travel { switch } => switch,
+        ModeAction::Office { switch } => return office_mode(&storage, switch, config),
+        ModeAction::Rehab { switch } => return rehab_mode(&storage, switch, config),
+    };
+    match switch {
+        Some(TravelSwitch::On { days }) => {
+            let state = UserMicrodoseState::update(
+                &storage.state_path,
+                config.data.lock_timeout(),
+                |state| {
+                    travel::turn_on(state, now, days);
+                    Ok(())
+                },
+            )?;
+            if let Some(until) = state.travel_mode_until {
+                println!(
+                    "✓ Travel mode on until {}: only doses that need no equipment",
+                    tz.format(until, "%Y-%m-%d %H:%M")
+                );
+            }
+        }
+        Some(TravelSwitch::Off) => {
+            if storage.state_path.exists() {
+                UserMicrodoseState::update(
+                    &storage.state_path,
+                    config.data.lock_timeout(),
+                    |state| {
+                        travel::turn_off(state);
+                        Ok(())
+                    },
+                )?;
+            }
+            println!("✓ Travel mode off");
+        }
+        None => {
+            let state = UserMicrodoseState::load(&storage.state_path, config.data.lock_timeout())?;
+            match travel::active_until(&state, now) {
+                Some(until) => println!(
+                    "Travel mode on until {}",
+                    tz.format(until, "%Y-%m-%d %H:%M")
+                ),
+                None => println!("Travel mode off (krep mode travel on)"),
+            }
+        }
+    }
+    Ok(())
+}
+
+fn office_mode(storage: &FileStorage, switch: Option<OfficeSwitch>, config: &Config) -> Result<()> {
+    let tz = config.timezone();
+    let now = chrono::Utc::now();
+
+    match switch {
+        Some(OfficeSwitch::On { hours }) => {
+            let until = match hours {
+                Some(hours) => now + chrono::Duration::hours(i64::from(hours)),
+                None => office::end_of_day(&tz, now),
+            };
+            UserMicrodoseState::update(&storage.state_path, config.data.lock_timeout(), |state| {
+                office::turn_on(state, until);
+                Ok(())
+            })?;
+            println!(
+                "✓ Office mode on until {}: only GTG-friendly doses",
+                tz.format(until, "%Y-%m-%d %H:%M")
+            );
+        }
+        Some(OfficeSwitch::Off) => {
+            if storage.state_path.exists() {
+                UserMicrodoseState::update(
+                    &storage.state_path,
+                    config.data.lock_timeout(),
+                    |state| {
+                        office::turn_off(state);
+                        Ok(())
+                    },
+                )?;
+            }
+            println!("✓ Office mode off");
+            if office::in_office_hours(&config.office_hours(), &tz, now) {
+                println!("  (still in [prescription] office_hours)");
+            }
+        }
+        None => {
+            let state = UserMicrodoseState::load(&storage.state_path, config.data.lock_timeout())?;
+            if let Some(until) = office::active_until(&state, now) {
+                println!(
+                    "Office mode on until {}",
+                    tz.format(until, "%Y-%m-%d %H:%M")
+                );
+            } else if office::in_office_hours(&config.office_hours(), &tz, now) {
+                println!("Office mode on ([prescription] office_hours)");
+            } else {
+                println!("Office mode off (krep mode office on)");
+            }
+        }
+    }
+    Ok(())
+}
+
+fn rehab_mode(storage: &FileStorage, switch: Option<RehabSwitch>, config: &Config) -> Result<()> {
+    let tz = config.timezone();
+    let now = chrono::Utc::now();
+
+    match switch {
+        Some(RehabSwitch::On {
+            injury,
+            until,
+            cleared,
+        }) => {
+            let injury = injury.trim();
+            if injury.is_empty() {
+                return Err(Error::Other("--injury must not be empty".into()));
+            }
+            let catalog = get_default_catalog();
+            let cleared: Vec<String> = cleared
+                .iter()
+                .map(|id| id.trim().to_string())
+                .filter(|id| !id.is_empty())
+                .collect();
+            if let Some(unknown) = cleared.iter().find(|id| catalog.movement(id).is_none()) {
+                return Err(Error::NotFound(format!("Unknown movement '{}'", unknown)));
+            }
+            // Rehab runs through the last day given
+            let until = until.map(|date| tz.day_start_utc(date + chrono::Duration::days(1)));
+            if until.is_some_and(|until| until <= now) {
+                return Err(Error::Other("--until must not be in the past".into()));
+            }
+
+            UserMicrodoseState::update(&storage.state_path, config.data.lock_timeout(), |state| {
+                rehab::start(&mut state.rehab, injury, now, until, cleared.clone());
+                Ok(())
+            })?;
+            print!("✓ Rehab mode on for {}: mobility only", injury);
+            if !cleared.is_empty() {
+                print!(" plus {}", cleared.join(", "));
+            }
+            match until {
+                Some(until) => println!(
+                    " until {}",
+                    tz.format(until - chrono::Duration::days(1), "%Y-%m-%d")
+                ),
+                None => println!(" until `krep mode rehab off`"),
+            }
+        }
+        Some(RehabSwitch::Off) => {
+            let mut ended = None;
+            if storage.state_path.exists() {
+                UserMicrodoseState::update(
+                    &storage.state_path,
+                    config.data.lock_timeout(),
+                    |state| {
+                        ended = rehab::clear(&mut state.rehab, now).map(|p| p.injury.clone());
+                        Ok(())
+                    },
+                )?;
+            }
+            match ended {
+                Some(injury) => println!("✓ Rehab mode off ({} cleared)", injury),
+                None => println!("Rehab mode was not on"),
+            }
+        }
+        None => {
+            let state = UserMicrodoseState::load(&storage.state_path, config.data.lock_timeout())?;
+            match rehab::active(&state.rehab, now) {
+                Some(period) => println!("{}", rehab::rationale(period)),
+                None => println!("Rehab mode off (krep mode rehab on --injury <what>)"),
+            }
+            for period in state.rehab.iter().rev() {
+                let end = match period.end() {
+                    Some(end) if end <= now => tz.format(end, "%Y-%m-%d"),
+                    Some(end) => format!("{} (planned)", tz.format(end, "%Y-%m-%d")),
+                    None => "ongoing".to_string(),
+                };
+                println!(
+                    "  {}  {} to {}",
+                    period.injury,
+                    tz.format(period.started_at, "%Y-%m-%d"),
+                    end
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Reminder times suggested by `krep stats --timing`
+const SUGGESTED_REMINDERS: usize = 3;
+
+fn print_timing(hours: &[timing::HourStats]) {
+    println!();
+    println!("Done vs skipped by hour");
+    for stats in hours.iter().filter(|h| h.shown() > 0) {
+        println!(
+            "  {:02}:00  {:>3} done {:>3} skipped {:>3} snoozed  {:>3.0}% {}",
+            stats.hour,
+            stats.completed,
+            stats.skipped,
+            stats.snoozed,
+            stats.completion_rate() * 100.0,
+            "█".repeat(stats.completed as usize)
+        );
+    }
+
+    let suggested = timing::suggest_reminder_hours(hours, SUGGESTED_REMINDERS);
+    if suggested.is_empty() {
+        println!(
+            "  Not enough data yet for reminder suggestions ({}+ doses shown per hour)",
+            timing::MIN_HOUR_SAMPLES
+        );
+    } else {
+        let times: Vec<String> = suggested.iter().map(|h| format!("{:02}:00", h)).collect();
+        println!("  Suggested reminder times: {}", times.join(", "));
+    }
+}
+
+/// Build the `--format json` stats report (same shape as the FFI `krep_stats`)
+fn stats_report(
+    data_dir: &std::path::Path,
+    sessions: &[SessionKind],
+    tags: &[String],
+    days: i64,
+    compare: bool,
+    config: &Config,
+) -> Result<stats::StatsReport> {
+    let FileStorage {
+        wal_path,
+        csv_path,
+        state_path,
+        ..
+    } = FileStorage::with_config(data_dir, &config.data);
+    let catalog = get_default_catalog();
+    let tz = config.timezone();
+    let now = chrono::Utc::now();
+
+    let week = load_recent_sessions_with(
+        &wal_path,
+        &csv_path,
+        7,
+        config.data.exclude_anomalies,
+        config.data.lock_timeout(),
+    )?;
+    let week_over_week = if compare {
+        let fortnight = load_recent_sessions_with(
+            &wal_path,
+            &csv_path,
+            14,
+            config.data.exclude_anomalies,
+            config.data.lock_timeout(),
+        )?;
+        let fortnight: Vec<SessionKind> = cardio_core::history::filter_by_tags(&fortnight, tags)
+            .into_iter()
+            .cloned()
+            .collect();
+        Some(stats::compare_weeks(&fortnight, catalog, now))
+    } else {
+        None
+    };
+    let weigh_ins = cardio_core::bodyweight::read_bodyweight(
+        &data_dir.join(cardio_core::bodyweight::BODYWEIGHT_FILE),
+        config.data.lock_timeout(),
+    )?;
+    let user_state = UserMicrodoseState::load(&state_path, config.data.lock_timeout())?;
+
+    Ok(stats::StatsReport {
+        sessions: stats::summarize_sessions(sessions, catalog, days),
+        bodyweight: cardio_core::bodyweight::trend(&weigh_ins, now - chrono::Duration::days(days)),
+        targets: stats::target_progress(
+            &week,
+            catalog,
+            &config.weekly_targets(),
+            now - chrono::Duration::days(7),
+        ),
+        heart_rate: config
+            .max_hr()
+            .map(|max_hr| heart_rate::compare_sessions(sessions, catalog, max_hr))
+            .unwrap_or_default(),
+        hr_recovery: heart_rate::recovery_trend(sessions),
+        goal: user_state
+            .weekly_goal
+            .map(|goal| goal::current_progress(&goal, &week, &tz, now)),
+        week_over_week,
+        time_in_category: stats::time_in_category(sessions, catalog, &tz),
+        rehab: rehab::summarize(
+            &user_state.rehab,
+            sessions,
+            catalog,
+            now - chrono::Duration::days(days),
+            now,
+        ),
+        phases: phase::summarize(
+            &config.phases,
+            sessions,
+            catalog,
+            &tz,
+            tz.date_of(now - chrono::Duration::days(days)),
+            tz.date_of(now),
+        ),
+        household: household::load(config, data_dir, catalog, days, now)?,
+    })
+}
+
+fn print_time_in_category(weeks: &[stats::CategoryWeek]) {
+    if weeks.is_empty() {
+        return;
+    }
+    println!();
+    println!("Minutes per category (weeks from Monday)");
+    for week in weeks {
+        let minutes: Vec<String> = week
+            .minutes
+            .iter()
+            .map(|(category, minutes)| format!("{} {}", category, minutes))
+            .collect();
+        println!("  {}  {}", week.week_start, minutes.join(", "));
+    }
+}
+
+fn print_rehab(periods: &[rehab::RehabSummary], tz: &LocalTz) {
+    if periods.is_empty() {
+        return;
+    }
+    println!();
+    println!("Rehab periods");
+    for period in periods {
+        let end = match period.ended_at {
+            Some(end) => tz.format(end, "%Y-%m-%d"),
+            None => "ongoing".to_string(),
+        };
+        let mix: Vec<String> = period
+            .by_category
+            .iter()
+            .map(|(category, count)| format!("{} {}", category, count))
+            .collect();
+        println!(
+            "  {} ({} to {}): {} sessions, {} min{}",
+            period.injury,
+            tz.format(period.started_at, "%Y-%m-%d"),
+            end,
+            period.sessions,
+            period.total_seconds / 60,
+            if mix.is_empty() {
+                String::new()
+            } else {
+                format!(" ({})", mix.join(", "))
+            }
+        );
+    }
+}
+
+fn print_phases(phases: &[phase::PhaseSummary]) {
+    if phases.is_empty() {
+        return;
+    }
+    println!();
+    println!("Training phases");
+    for summary in phases {
+        let mix: Vec<String> = summary
+            .by_category
+            .iter()
+            .map(|(category, count)| format!("{} {}", category, count))
+            .collect();
+        println!(
+            "  {} {} to {}{}: {} sessions, {} min{}",
+            summary.name.as_str(),
+            summary.start,
+            summary.end,
+            if summary.current { " (current)" } else { "" },
+            summary.sessions,
+            summary.total_seconds / 60,
+            if mix.is_empty() {
+                String::new()
+            } else {
+                format!(" ({})", mix.join(", "))
+            }
+        );
+    }
+}
+
+fn print_household(members: &[household::MemberSummary]) {
+    if members.is_empty() {
+        return;
+    }
+    println!();
+    println!("Household");
+    let total = household::combined(members);
+    for member in members.iter().chain([&total]) {
+        let mix: Vec<String> = member
+            .by_category
+            .iter()
+            .map(|(category, count)| format!("{} {}", category, count))
+            .collect();
+        println!(
+            "  {:<10} {} today, {} sessions, {} min{}",
+            member.name,
+            member.today,
+            member.sessions,
+            member.total_seconds / 60,
+            if mix.is_empty() {
+                String::new()
+            } else {
+                format!(" ({})", mix.join(", "))
+            }
+        );
+    }
+}
+
+fn print_strength_adherence(stats: &[strength::StrengthDayStats], per_week: f64) {
+    println!();
+    println!("Doses on strength days");
+    if stats
+        .iter()
+        .all(|s| s.day_type == strength::NO_STRENGTH_LABEL)
+    {
+        println!("  No strength sessions recorded in this period");
+        return;
+    }
+    println!("  {:.1} strength sessions/week", per_week);
+    for day in stats {
+        let mix: Vec<String> = day
+            .categories
+            .iter()
+            .map(|(category, count)| format!("{} {}", category, count))
+            .collect();
+        println!(
+            "  {:<10} {:>3} days {:>3} done {:>3} skipped  {:>3.0}% skipped  {}",
+            day.day_type,
+            day.days,
+            day.completed,
+            day.skipped,
+            day.skip_rate() * 100.0,
+            mix.join(", ")
+        );
+    }
+
+    if let Some(worst) = strength::worst_strength_day(stats) {
+        let baseline = stats
+            .iter()
+            .find(|s| s.day_type == strength::NO_STRENGTH_LABEL && s.shown() > 0)
+            .map(|s| format!(" (vs {:.0}% on other days)", s.skip_rate() * 100.0))
+            .unwrap_or_default();
+        let day_type = match worst.day_type.as_str() {
+            "lower" | "upper" | "full" => format!("{}-body", worst.day_type),
+            other => other.to_string(),
+        };
+        println!(
+            "  You skip {:.0}% of doses on {} days{}",
+            worst.skip_rate() * 100.0,
+            day_type,
+            baseline
+        );
+    }
+}
+
+fn print_week_comparison(comparison: &stats::WeekComparison) {
+    let (this, last) = (&comparison.this_week, &comparison.last_week);
+    let row = |label: &str, current: f64, previous: f64, precision: usize| {
+        println!(
+            "  {:<10} {:>5.*} {} {:+.*} (last week {:.*})",
+            label,
+            precision,
+            current,
+            stats::trend_marker(current, previous),
+            precision,
+            current - previous,
+            precision,
+            previous
+        );
+    };
+
+    println!();
+    println!("This week vs last week");
+    row(
+        "Doses",
+        this.sessions.total_sessions as f64,
+        last.sessions.total_sessions as f64,
+        0,
+    );
+    row(
+        "Minutes",
+        (this.sessions.total_seconds / 60) as f64,
+        (last.sessions.total_seconds / 60) as f64,
+        0,
+    );
+    for (category, current, previous) in comparison.category_counts() {
+        row(category, current as f64, previous as f64, 0);
+    }
+    match (this.avg_rpe, last.avg_rpe) {
+        (Some(current), Some(previous)) => row("Avg RPE", current, previous, 1),
+        (current, previous) => {
+            let rpe = |v: Option<f64>| v.map_or("-".to_string(), |v| format!("{:.1}", v));
+            println!(
+                "  {:<10} {:>5} (last week {})",
+                "Avg RPE",
+                rpe(current),
+                rpe(previous)
+            );
+        }
+    }
+}
+
+/// Days of history loaded so report PRs compare against earlier totals
+const REPORT_HISTORY_DAYS: i64 = 365;
+
+fn cmd_report(
+    data_dir: PathBuf,
+    format: Option<String>,
+    output: Option<PathBuf>,
+    config: &Config,
+) -> Result<()> {
+    let FileStorage {
+        wal_path,
+        csv_path,
+        state_path,
+        ..
+    } = FileStorage::with_config(&data_dir, &config.data);
+    let catalog = get_default_catalog();
+    let tz = config.timezone();
+
+    let format = match (format, &output) {
+        (Some(format), _) => format.parse()?,
+        (None, Some(path)) if path.extension().is_some_and(|e| e == "html" || e == "htm") => {
+            report::ReportFormat::Html
+        }
+        (None, _) => report::ReportFormat::Markdown,
+    };
+
+    let history = load_recent_sessions_with(
+        &wal_path,
+        &csv_path,
+        REPORT_HISTORY_DAYS,
+        config.data.exclude_anomalies,
+        config.data.lock_timeout(),
+    )?;
+    let events = progression_log::read_events(
+        &data_dir.join(progression_log::PROGRESSION_LOG_FILE),
+        config.data.lock_timeout(),
+    )?;
+    let user_state = UserMicrodoseState::load(&state_path, config.data.lock_timeout())?;
+    let weekly = report::weekly_report(
+        catalog,
+        &history,
+        &events,
+        user_state.weekly_goal.as_ref(),
+        &config.off_days(&user_state),
+        &tz,
+        chrono::Utc::now(),
+    );
+
+    let output = output.unwrap_or_else(|| {
+        PathBuf::from(format!("krep-week-{}.{}", weekly.end, format.extension()))
+    });
+    std::fs::write(&output, weekly.render(format))?;
+
+    println!(
+        "✓ Wrote report for {} – {} to {}",
+        weekly.start,
+        weekly.end,
+        output.display()
+    );
+    Ok(())
+}
+
+fn cmd_config(action: ConfigAction) -> Result<()> {
+    match action {
+        ConfigAction::Validate { file } => {
+            let path = file.unwrap_or_else(Config::default_config_path);
+            let files = config_files(&path)?;
+            if files.is_empty() {
+                println!("No config file at {}; using defaults", path.display());
+                return Ok(());
+            }
+
+            let mut problems = 0;
+            for file in &files {
+                let diagnostics = config::check_file(file, get_default_catalog())?;
+                if diagnostics.is_empty() {
+                    println!("✓ {} is valid", file.display());
+                    continue;
+                }
+                problems += diagnostics.len();
+                for diagnostic in &diagnostics {
+                    match diagnostic.line {
+                        Some(line) => {
+                            println!("{}:{}: {}", file.display(), line, diagnostic.message)
+                        }
+                        None => println!("{}: {}", file.display(), diagnostic.message),
+                    }
+                }
+            }
+            if problems == 0 {
+                return Ok(());
+            }
+            Err(Error::Config(format!(
+                "{} problem(s) in {}",
+                problems,
+                path.display()
+            )))
+        }
+        ConfigAction::Edit { file } => {
+            config_edit(&file.unwrap_or_else(Config::default_config_path))
+        }
+        ConfigAction::Schema => {
+            println!("{}", config::json_schema()?);
+            Ok(())
+        }
+        ConfigAction::Migrate { file } => {
+            let path = file.unwrap_or_else(Config::default_config_path);
+            let mut migrated = 0;
+            for file in config_files(&path)? {
+                let migrations = config::migrate_file(&file)?;
+                for migration in &migrations {
+                    println!("{}: {}", file.display(), migration);
+                }
+                migrated += migrations.len();
+            }
+            if migrated == 0 {
+                println!("✓ No deprecated keys");
+            } else {
+                println!("✓ Migrated {} key(s)", migrated);
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Edit a copy of the config and only replace the original once it validates
+///
+/// Like `visudo`: on problems the user can edit again or discard the edit,
+/// leaving the original untouched.
+fn config_edit(path: &Path) -> Result<()> {
+    let original = if path.exists() {
+        Some(std::fs::read_to_string(path)?)
+    } else {
+        None
+    };
+    let dir = path.parent().unwrap_or_else(|| Path::new("."));
+    std::fs::create_dir_all(dir)?;
+    let draft = tempfile::Builder::new()
+        .prefix("config.")
+        .suffix(".toml")
+        .tempfile_in(dir)?;
+    match &original {
+        Some(contents) => std::fs::write(draft.path(), contents)?,
+        None => Config::default().save_to(draft.path())?,
+    }
+
+    let editor = std::env::var("VISUAL")
+        .ok()
+        .filter(|e| !e.trim().is_empty())
+        .or_else(|| std::env::var("EDITOR").ok())
+        .filter(|e| !e.trim().is_empty())
+        .unwrap_or_else(|| "vi".to_string());
+
+    loop {
+        // Split so EDITOR="code --wait" works
+        let mut words = editor.split_whitespace();
+        let program = words.next().unwrap_or("vi");
+        let status = std::process::Command::new(program)
+            .args(words)
+            .arg(draft.path())
+            .status()
+            .map_err(|e| Error::Config(format!("Failed to run editor '{}': {}", editor, e)))?;
+        if !status.success() {
+            return Err(Error::Config(format!(
+                "Editor exited with {}; {} left unchanged",
+                status,
+                path.display()
+            )));
+        }
+
+        let edited = std::fs::read_to_string(draft.path())?;
+        if original.as_deref() == Some(edited.as_str()) {
+            println!("No changes to {}", path.display());
+            return Ok(());
+        }
+
+        let diagnostics = config::check_file(draft.path(), get_default_catalog())?;
+        if diagnostics.is_empty() {
+            break;
+        }
+        println!("{} problem(s) in the edited config:", diagnostics.len());
+        for diagnostic in &diagnostics {
+            println!("  {}", diagnostic);
+        }
+        print!("(e)dit again or (q)uit without saving? [e]: ");
+        io::stdout().flush()?;
+        let mut input = String::new();
+        io::stdin().read_line(&mut input)?;
+        if matches!(input.trim(), "q" | "Q") || input.is_empty() {
+            return Err(Error::Config(format!(
+                "Edit discarded; {} left unchanged",
+                path.display()
+            )));
+        }
+    }
+
+    if original.is_some() {
+        let backup = path.with_extension("toml.bak");
+        std::fs::copy(path, &backup)?;
+        println!("Previous config saved to {}", backup.display());
+    }
+    draft.persist(path).map_err(|e| Error::Io(e.error))?;
+    println!("✓ {} saved", path.display());
+    Ok(())
+}
+
+/// The config file (if it exists) followed by its drop-ins, in merge order
+fn config_files(path: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = config::drop_in_files(path)?;
+    if path.exists() {
+        files.insert(0, path.to_path_buf());
+    }
+    Ok(files)
+}
+
+fn cmd_doctor(data_dir: PathBuf, config: &Config) -> Result<()> {
+    let FileStorage {
+        wal_path, csv_path, ..
+    } = FileStorage::with_config(&data_dir, &config.data);
+
+    let problems = cardio_core::csv_manifest::verify(&csv_path)?;
+    if problems.is_empty() {
+        println!("✓ CSV archive matches its manifest");
+    } else {
+        println!("⚠ CSV archive does not match its manifest:");
+        for problem in &problems {
+            println!("  {}", problem);
+        }
+    }
+
+    let found = cardio_core::history::check_data(&wal_path, &csv_path, config.data.lock_timeout())?;
+    if found.is_empty() {
+        println!("✓ No session anomalies found");
+        return Ok(());
+    }
+
+    println!("⚠ {} session anomalies:", found.len());
+    for (source, anomaly) in &found {
+        println!("  [{}] {}", source, anomaly);
+    }
+    if config.data.exclude_anomalies {
+        println!("These sessions are excluded from history ([data] exclude_anomalies).");
+    } else {
+        println!("Set [data] exclude_anomalies = true to leave them out of history.");
+    }
+    Ok(())
+}
+
+fn cmd_open(data_dir: PathBuf, target: OpenTarget, print: bool) -> Result<()> {
+    let target = match target {
+        OpenTarget::Data => {
+            std::fs::create_dir_all(&data_dir)?;
+            data_dir.into_os_string()
+        }
+        OpenTarget::Config => {
+            let path = Config::default_config_path();
+            if !path.exists() {
+                if let Some(dir) = path.parent() {
+                    std::fs::create_dir_all(dir)?;
+                }
+                Config::default().save_to(&path)?;
+            }
+            path.into_os_string()
+        }
+        OpenTarget::Reference { def_id } => {
+            let catalog = get_default_catalog();
+            let definition = catalog.definition(&def_id).ok_or_else(|| {
+                Error::NotFound(format!("Unknown microdose definition '{}'", def_id))
+            })?;
+            let url = catalog
+                .reference_url(definition)
+                .ok_or_else(|| Error::NotFound(format!("'{}' has no reference link", def_id)))?;
+            url.into()
+        }
+    };
+
+    if print {
+        println!("{}", target.to_string_lossy());
+        return Ok(());
+    }
+    open_with_system(&target)
+}
+
+/// Hand a path or URL to the desktop's default handler
+fn open_with_system(target: &std::ffi::OsStr) -> Result<()> {
+    let mut command = if cfg!(target_os = "macos") {
+        std::process::Command::new("open")
+    } else if cfg!(windows) {
+        let mut command = std::process::Command::new("cmd");
+        // The empty argument is the window title `start` expects first
+        command.args(["/C", "start", ""]);
+        command
+    } else {
+        std::process::Command::new("xdg-open")
+    };
+    let status = command.arg(target).status().map_err(|e| {
+        Error::Other(format!(
+            "Failed to run the system opener ({}); try `krep open --print`",
+            e
+        ))
+    })?;
+    if !status.success() {
+        return Err(Error::Other(format!(
+            "Opener exited with {} for {}",
+            status,
+            target.to_string_lossy()
+        )));
+    }
+    Ok(())
+}
+
+fn cmd_session(data_dir: PathBuf, action: SessionAction, config: &Config) -> Result<()> {
+    match action {
+        SessionAction::AttachHr { id, file } => {
+            let storage = FileStorage::with_config(&data_dir, &config.data);
+            let session = hr_recording::attach(&storage, &data_dir, id, &file, &config.timezone())?
+                .ok_or_else(|| Error::NotFound(format!("No session with ID {}", id)))?;
+            println!(
+                "✓ Attached HR to {}: avg {} bpm, max {} bpm",
+                session.definition_id,
+                session.avg_hr.unwrap_or(0),
+                session.max_hr.unwrap_or(0)
+            );
+            println!(
+                "  Series saved to {}",
+                hr_recording::series_path(&data_dir, id).display()
+            );
+            Ok(())
+        }
+    }
+}
+
+fn cmd_export(data_dir: PathBuf, archive: &Path, config: &Config) -> Result<()> {
+    let storage = FileStorage::with_config(&data_dir, &config.data);
+    let manifest = backup::write_archive(
+        archive,
+        &data_dir,
+        &storage,
+        Some(&Config::default_config_path()),
+        chrono::Utc::now(),
+    )?;
+    for file in &manifest.files {
+        println!("  {} ({} bytes)", file.path, file.bytes);
+    }
+    println!(
+        "✓ Backed up {} file(s), {} bytes, to {}",
+        manifest.files.len(),
+        manifest.total_bytes(),
+        archive.display()
+    );
+    Ok(())
+}
+
+fn cmd_import(data_dir: PathBuf, source: ImportSource, config: &Config) -> Result<()> {
+    match source {
+        ImportSource::GoogleFit { path, yes } => import_google_fit(data_dir, &path, yes, config),
+    }
+}
+
+fn cmd_import_dir(data_dir: PathBuf, path: &Path, dry_run: bool, config: &Config) -> Result<()> {
+    if !path.is_dir() {
+        return Err(Error::Other(format!(
+            "{} is not a directory",
+            path.display()
+        )));
+    }
+    if data_dir.exists() && path.canonicalize()? == data_dir.canonicalize()? {
+        return Err(Error::Other(
+            "Cannot import the data directory into itself".into(),
+        ));
+    }
+
+    let ours = FileStorage::with_config(&data_dir, &config.data);
+    let theirs = FileStorage::new(path);
+    let report = merge::merge_dir(&ours, &theirs, dry_run)?;
+
+    let verb = if dry_run { "Would import" } else { "Imported" };
+    println!(
+        "{} {} new session(s); {} already here",
+        verb, report.sessions_new, report.sessions_duplicate
+    );
+    println!(
+        "Progressions: {} new, {} more recent · personal bests: {} · achievements: {}",
+        report.progressions_new,
+        report.progressions_updated,
+        report.personal_bests_updated,
+        report.achievements_new
+    );
+    Ok(())
+}
+
+fn import_google_fit(data_dir: PathBuf, path: &Path, yes: bool, config: &Config) -> Result<()> {
+    use cardio_core::google_fit::{self, FitImport};
+
+    let activities = google_fit::read_takeout(path)?;
+    println!(
+        "Read {} activities from {}",
+        activities.len(),
+        path.display()
+    );
+
+    let storage = FileStorage::with_config(&data_dir, &config.data);
+    let history_path = &storage.strength_history_path;
+
+    // Re-running an import must not log the same bouts twice
+    let days = activities
+        .first()
+        .map_or(0, |a| (chrono::Utc::now() - a.start).num_days() + 1);
+    let existing = load_recent_sessions(
+        &storage.wal_path,
+        &storage.csv_path,
+        days,
+        config.data.lock_timeout(),
+    )?;
+    let recorded_strength = strength::read_history(history_path, config.data.lock_timeout())?;
+
+    let mut sessions = Vec::new();
+    let mut workouts = Vec::new();
+    for activity in &activities {
+        match google_fit::classify(activity) {
+            Some(FitImport::Session(session))
+                if !existing
+                    .iter()
+                    .filter_map(|s| s.as_real())
+                    .any(|s| google_fit::is_same_import(s, &session)) =>
+            {
+                sessions.push(session)
+            }
+            Some(FitImport::Strength(signal))
+                if !recorded_strength
+                    .iter()
+                    .any(|s| s.last_session_at == signal.last_session_at) =>
+            {
+                workouts.push(signal)
+            }
+            _ => {}
+        }
+    }
+    if sessions.is_empty() && workouts.is_empty() {
+        println!("Nothing new to import.");
+        return Ok(());
+    }
+
+    let tz = config.timezone();
+    for session in &sessions {
+        let seconds = session.actual_duration_seconds.unwrap_or(0);
+        println!(
+            "  + {}  {}  {}:{:02}",
+            tz.format(session.performed_at, "%Y-%m-%d %H:%M"),
+            session.definition_id,
+            seconds / 60,
+            seconds % 60
+        );
+    }
+    for signal in &workouts {
+        println!(
+            "  + {}  strength ({})",
+            tz.format(signal.last_session_at, "%Y-%m-%d %H:%M"),
+            strength::day_label(&signal.session_type)
+        );
+    }
+
+    if !yes {
+        print!(
+            "Import {} session(s) and {} strength workout(s)? [y/N]: ",
+            sessions.len(),
+            workouts.len()
+        );
+        io::stdout().flush()?;
+        let mut input = String::new();
+        io::stdin().read_line(&mut input)?;
+        if !input.trim().eq_ignore_ascii_case("y") {
+            println!("Nothing imported.");
+            return Ok(());
+        }
+    }
+
+    let mut sink = storage.wal_sink();
+    for session in &sessions {
+        sink.append(session)?;
+    }
+    strength::record_sessions(history_path, &workouts, config.data.lock_timeout())?;
+    println!(
+        "✓ Imported {} session(s) tagged '{}' and {} strength workout(s)",
+        sessions.len(),
+        google_fit::IMPORT_TAG,
+        workouts.len()
+    );
+    Ok(())
+}
+
+struct SystemdArgs {
+    dry_run: bool,
+    reminder_hours: Vec<u32>,
+    rollup_at: String,
+    dir: Option<PathBuf>,
+}
+
+fn cmd_install_systemd(data_dir: PathBuf, args: SystemdArgs, config: &Config) -> Result<()> {
+    let rollup_at = chrono::NaiveTime::parse_from_str(&args.rollup_at, "%H:%M").map_err(|_| {
+        Error::Other(format!(
+            "Invalid --rollup-at '{}' (expected HH:MM)",
+            args.rollup_at
+        ))
+    })?;
+
+    let mut reminder_hours = args.reminder_hours;
+    if reminder_hours.is_empty() {
+        let FileStorage {
+            wal_path, csv_path, ..
+        } = FileStorage::with_config(&data_dir, &config.data);
+        let days = 30;
+        let sessions = load_recent_sessions_with(
+            &wal_path,
+            &csv_path,
+            days,
+            config.data.exclude_anomalies,
+            config.data.lock_timeout(),
+        )?;
+        let skips = timing::read_skips(
+            &data_dir.join(timing::SKIP_LOG_FILE),
+            config.data.lock_timeout(),
+        )?;
+        let snoozes = timing::read_snoozes(
+            &data_dir.join(timing::SNOOZE_LOG_FILE),
+            config.data.lock_timeout(),
+        )?;
+        let hours = timing::completion_by_hour(
+            &sessions,
+            &skips,
+            &snoozes,
+            &config.timezone(),
+            chrono::Utc::now() - chrono::Duration::days(days),
+        );
+        reminder_hours = timing::suggest_reminder_hours(&hours, SUGGESTED_REMINDERS);
+        if reminder_hours.is_empty() {
+            reminder_hours = systemd::DEFAULT_REMINDER_HOURS.to_vec();
+        }
+    }
+    reminder_hours.sort_unstable();
+    reminder_hours.dedup();
+
+    // Timers run without the shell's KREP_* variables, so pin the data dir
+    let mut global_args = vec!["--data-dir".to_string(), data_dir.display().to_string()];
+    if let Some(profile) = &config.active_profile {
+        global_args.extend(["--profile".to_string(), profile.clone()]);
+    }
+    let krep = std::env::current_exe()?;
+    let units = systemd::units(&systemd::UnitOptions {
+        krep: &krep,
+        global_args,
+        reminder_hours,
+        rollup_at,
+        timezone: config.time.timezone.as_deref(),
+    });
+
+    if args.dry_run {
+        for unit in &units {
+            println!("# {}", unit.name);
+            println!("{}", unit.contents);
+        }
+        return Ok(());
+    }
+
+    let dir = match args.dir {
+        Some(dir) => dir,
+        None => dirs::config_dir()
+            .ok_or_else(|| Error::Other("No config directory for systemd units".into()))?
+            .join("systemd")
+            .join("user"),
+    };
+    std::fs::create_dir_all(&dir)?;
+    for unit in &units {
+        let path = dir.join(unit.name);
+        if std::fs::read_to_string(&path).ok().as_deref() == Some(unit.contents.as_str()) {
+            println!("  unchanged {}", path.display());
+            continue;
+        }
+        let mut temp = tempfile::NamedTempFile::new_in(&dir)?;
+        temp.write_all(unit.contents.as_bytes())?;
+        temp.as_file().sync_all()?;
+        temp.persist(&path).map_err(|e| Error::Io(e.error))?;
+        println!("  wrote {}", path.display());
+    }
+    println!("✓ Installed {} units. Enable them with:", units.len());
+    println!("  systemctl --user daemon-reload");
+    println!("  systemctl --user enable --now krep-reminder.timer krep-rollup.timer");
+    Ok(())
+}
+
+/// Days of daily-note summaries `krep rollup` refreshes
+const JOURNAL_SUMMARY_DAYS: u32 = 7;
+
+fn cmd_rollup(data_dir: PathBuf, cleanup: bool, config: &Config) -> Result<()> {
+    let storage = FileStorage::with_config(&data_dir, &config.data);
+    let wal_dir = storage.wal_dir().to_path_buf();
+    let FileStorage {
+        wal_path, csv_path, ..
+    } = storage;
+
+    // Nightly rollups keep the daily-note summaries current
+    if config.journal.daily_note.is_some() {
+        let sessions = load_recent_sessions(
+            &wal_path,
+            &csv_path,
+            JOURNAL_SUMMARY_DAYS.into(),
+            config.data.lock_timeout(),
+        )?;
+        let tz = config.timezone();
+        let notes = journal::refresh_summaries(
+            &config.journal,
+            &sessions,
+            get_default_catalog(),
+            &tz,
+            tz.date_of(chrono::Utc::now()),
+            JOURNAL_SUMMARY_DAYS,
+        )?;
+        println!("✓ Updated the summary in {} daily note(s)", notes);
+    }
+
+    if !wal_path.exists() {
+        println!("No WAL file found - nothing to roll up.");
+        return Ok(());
+    }
+
+    // Report anomalies before they are archived (they are kept, not dropped)
+    let wal_sessions = cardio_core::wal::read_sessions(&wal_path, config.data.lock_timeout())?;
+    let anomalies = cardio_core::anomaly::check_sessions(&wal_sessions, chrono::Utc::now());
+    for anomaly in &anomalies {
+        println!("⚠ Anomaly: {}", anomaly);
+    }
+
+    let count = cardio_core::csv_rollup::wal_to_csv_and_archive(
+        &wal_path,
+        &csv_path,
+        config.data.lock_timeout(),
+    )?;
+
+    println!("✓ Rolled up {} sessions to CSV", count);
+    println!("  CSV: {}", csv_path.display());
+
+    if cleanup {
+        let cleaned = cardio_core::csv_rollup::cleanup_processed_wals(&wal_dir)?;
+        if cleaned > 0 {
+            println!("✓ Cleaned up {} processed WAL files", cleaned);
+        }
+    }
+
+    Ok(())
+}
+
+fn display_prescription(catalog: &Catalog, prescription: &PrescribedMicrodose, qr: bool) {
+    println!("\n╭─────────────────────────────────────────╮");
+    println!("│  {:?} MICRODOSE", prescription.definition.category);
+    println!("╰─────────────────────────────────────────╯");
+    println!();
+    println!("  {}", prescription.definition.name);
+    println!(
+        "  Duration: ~{} seconds ({} min)",
+        prescription.definition.suggested_duration_seconds,
+        prescription.definition.suggested_duration_seconds / 60
+    );
+    if let Some(warmup) = prescription.warmup_label(catalog) {
+        println!("  Warmup first: {}", warmup);
+    }
+    if let Some(target) = prescription.hr_target {
+        println!("  Target HR: {}", target.label());
+    }
+    for line in &prescription.rationale {
+        println!("  ⚕ {}", line);
+    }
+    if prescription.ready_to_progress {
+        println!("  ↑ Ready to progress: your last sessions felt easy");
+    }
+    println!();
+
+    for block in &prescription.definition.blocks {
+        if let Some(interval) = block.interval {
+            println!(
+                "  → Intervals: {} ({}s total)",
+                interval.label(),
+                interval.total_seconds()
+            );
+        }
+
+        if let Some(reps) = prescription.reps_label() {
+            println!("  → {}", reps);
+        }
 
         if let Some(ref style) = prescription.style {
             match style {
@@ -310,23 +3113,107 @@ fn display_prescription(prescription: &PrescribedMicrodose) {
         }
     }
 
-    if let Some(ref url) = prescription.definition.reference_url {
+    if let Some(url) = prescription.reference_url(catalog) {
         println!();
         println!("  ℹ Reference: {}", url);
+        if qr {
+            print_qr(url);
+        }
     }
 
     println!();
 }
 
+/// Print `url` as a QR code drawn with half-block characters
+fn print_qr(url: &str) {
+    use qrcode::render::unicode::Dense1x2;
+
+    match qrcode::QrCode::new(url.as_bytes()) {
+        Ok(code) => {
+            // Blocks are drawn for light modules, suiting dark terminal themes
+            let rendered = code
+                .render::<Dense1x2>()
+                .dark_color(Dense1x2::Light)
+                .light_color(Dense1x2::Dark)
+                .build();
+            println!();
+            for line in rendered.lines() {
+                println!("  {}", line);
+            }
+        }
+        Err(e) => eprintln!("Unable to draw QR code: {}", e),
+    }
+}
+
+/// Format per-set reps as `5/5/5/4/3`
+fn format_per_set(per_set: &[i32]) -> String {
+    per_set
+        .iter()
+        .map(|r| r.to_string())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn print_autoregulation(target: i32, per_set: &[i32]) {
+    let hint = match cardio_core::progression::autoregulate(target, per_set) {
+        Some(cardio_core::progression::Autoregulation::Progress) => {
+            "all sets hit target - press 'h' next time to go harder"
+        }
+        Some(cardio_core::progression::Autoregulation::Hold) => {
+            "close to target - repeat this prescription"
+        }
+        Some(cardio_core::progression::Autoregulation::Regress) => {
+            "several sets fell short - consider backing off"
+        }
+        None => return,
+    };
+    println!(
+        "  Reps: {} (target {}) - {}",
+        format_per_set(per_set),
+        target,
+        hint
+    );
+}
+
+fn print_amrap_result(
+    per_set: &[i32],
+    personal_best: Option<cardio_core::progression::PersonalBest>,
+    best: Option<i32>,
+) {
+    let Some(total) = per_set.first() else {
+        return;
+    };
+    match (personal_best, best) {
+        (Some(pb), _) => match pb.previous {
+            Some(previous) => println!(
+                "  🏆 New personal best: {} reps (was {})",
+                pb.total, previous
+            ),
+            None => println!(
+                "  🏆 First AMRAP logged: {} reps - beat it next time",
+                pb.total
+            ),
+        },
+        (None, Some(best)) => println!("  Reps: {} (best {})", total, best),
+        (None, None) => println!("  Reps: {}", total),
+    }
+}
+
 enum UserAction {
     Done,
     Skip,
     Harder,
+    Timer,
+    Start,
 }
 
-fn prompt_user_action() -> Result<UserAction> {
+fn prompt_user_action(has_timer: bool) -> Result<UserAction> {
     println!("─────────────────────────────────────────");
     println!("Press Enter when done");
+    if has_timer {
+        println!("  't' + Enter to run the interval timer");
+    }
+    println!("  'g' + Enter to start now and time the dose");
     println!("  's' + Enter to skip");
     println!("  'h' + Enter to mark 'harder next time'");
     print!("> ");
@@ -338,8 +3225,298 @@ fn prompt_user_action() -> Result<UserAction> {
     let action = match input.trim().to_lowercase().as_str() {
         "s" => UserAction::Skip,
         "h" => UserAction::Harder,
+        "t" if has_timer => UserAction::Timer,
+        "g" => UserAction::Start,
         _ => UserAction::Done,
     };
 
     Ok(action)
 }
+
+/// Wait for Enter once the dose started at `started_at` is finished; 'p'
+/// pauses it until the next Enter, calling `pause` with the new state
+fn wait_for_finish(
+    tz: &LocalTz,
+    started_at: chrono::DateTime<chrono::Utc>,
+    pause: &mut dyn FnMut(bool) -> Result<()>,
+) -> Result<()> {
+    println!(
+        "\nStarted at {}. Press Enter when finished ('p' + Enter to pause)",
+        tz.format(started_at, "%H:%M:%S")
+    );
+    loop {
+        print!("> ");
+        io::stdout().flush()?;
+        let mut input = String::new();
+        if io::stdin().read_line(&mut input)? == 0 || !input.trim().eq_ignore_ascii_case("p") {
+            return Ok(());
+        }
+        pause(true)?;
+        print!("Paused. Press Enter to resume ");
+        io::stdout().flush()?;
+        io::stdin().read_line(&mut String::new())?;
+        pause(false)?;
+        println!("Resumed. Press Enter when finished ('p' + Enter to pause)");
+    }
+}
+
+/// Lines typed while the interval timer runs, read on another thread so the
+/// countdown keeps ticking
+struct TimerInput {
+    lines: std::sync::mpsc::Receiver<String>,
+    done: std::sync::Arc<std::sync::atomic::AtomicBool>,
+}
+
+impl TimerInput {
+    fn spawn() -> Self {
+        use std::sync::atomic::Ordering;
+
+        let (sender, lines) = std::sync::mpsc::channel();
+        let done = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let finished = done.clone();
+        std::thread::spawn(move || loop {
+            let mut line = String::new();
+            if !matches!(io::stdin().read_line(&mut line), Ok(n) if n > 0) {
+                break;
+            }
+            if sender.send(line).is_err() || finished.load(Ordering::SeqCst) {
+                break;
+            }
+        });
+        Self { lines, done }
+    }
+
+    /// Lines typed since the last call, trimmed
+    fn typed(&self) -> Vec<String> {
+        self.lines
+            .try_iter()
+            .map(|line| line.trim().to_lowercase())
+            .collect()
+    }
+
+    /// Stop reading: the read still waiting takes the next Enter, so ask for it
+    fn finish(self) -> Result<()> {
+        self.done.store(true, std::sync::atomic::Ordering::SeqCst);
+        print!("  Press Enter to continue ");
+        io::stdout().flush()?;
+        while self.lines.try_recv().is_ok() {}
+        let _ = self.lines.recv();
+        Ok(())
+    }
+}
+
+/// Count down work/rest phases in place, ringing the bell on each change;
+/// 'p' + Enter pauses and resumes, calling `pause` with the new state
+///
+/// With a rep `target`, 'a 5' + Enter records 5 reps per round from the
+/// current round on; the reps done in each round are returned if adjusted.
+fn run_interval_timer(
+    interval: &IntervalScheme,
+    sounds: &config::SoundConfig,
+    target: Option<i32>,
+    pause: &mut dyn FnMut(bool) -> Result<()>,
+) -> Result<Option<Vec<i32>>> {
+    let timer = timer::IntervalTimer::new(interval);
+    let player = sound::SoundPlayer::new(sounds);
+    let mut stopwatch = timer::Stopwatch::new(std::time::Instant::now());
+    let input = TimerInput::spawn();
+    let mut adjustments = timer::RepAdjustments::default();
+    let mut last_phase = None;
+    let mut last_second = None;
+
+    println!("\n  'p' + Enter pauses and resumes");
+    if let Some(target) = target {
+        println!(
+            "  'a <reps>' + Enter adjusts the reps (target {}) from this round on",
+            target
+        );
+    }
+    loop {
+        let elapsed = stopwatch.elapsed(std::time::Instant::now()).as_secs() as u32;
+        for line in input.typed() {
+            if line == "p" {
+                pause(stopwatch.toggle(std::time::Instant::now()))?;
+                continue;
+            }
+            let adjusted = line
+                .strip_prefix('a')
+                .and_then(|reps| reps.trim().parse::<i32>().ok())
+                .filter(|&reps| reps >= 0 && target.is_some());
+            if let Some(reps) = adjusted {
+                let round = timer.round_at(elapsed);
+                adjustments.adjust(round, reps);
+                println!("\r  Adjusted: {} reps from round {}{:<20}", reps, round, "");
+            }
+        }
+        if let Some(player) = &player {
+            if last_second != Some(elapsed) {
+                last_second = Some(elapsed);
+                if let Some(cue) = timer.cue(elapsed) {
+                    player.play(cue);
+                }
+            }
+        }
+        let Some(tick) = timer.tick(elapsed) else {
+            break;
+        };
+        // The terminal bell marks phase changes when there are no sounds
+        let bell = if player.is_none() && last_phase != Some(tick.phase_index) {
+            "\x07"
+        } else {
+            ""
+        };
+        last_phase = Some(tick.phase_index);
+        let paused = if stopwatch.is_paused() {
+            "PAUSED · "
+        } else {
+            ""
+        };
+        print!("\r  {}{}{:<30}", bell, paused, tick.label());
+        io::stdout().flush()?;
+        std::thread::sleep(std::time::Duration::from_millis(250));
+    }
+    match &player {
+        Some(player) => {
+            println!("\r  Time! ({}){:<30}", interval.label(), "");
+            player.wait();
+        }
+        None => println!("\r  Time! ({}){:<30}\x07", interval.label(), ""),
+    }
+    input.finish()?;
+    Ok(target
+        .filter(|_| !adjustments.is_empty())
+        .map(|target| adjustments.per_set(target, interval.rounds)))
+}
+
+/// Guided 60-second recovery measurement; returns the HR drop, or `None`
+/// if the user skips or the readings don't make sense
+fn measure_hr_recovery() -> Result<Option<u8>> {
+    let Some(end_hr) = prompt_bpm("Recovery check - HR right now (bpm), Enter to skip: ")? else {
+        return Ok(None);
+    };
+
+    let started = std::time::Instant::now();
+    let total = heart_rate::RECOVERY_SECONDS;
+    while started.elapsed().as_secs() < total {
+        print!(
+            "\r  Rest quietly: {:>2}s",
+            total - started.elapsed().as_secs()
+        );
+        io::stdout().flush()?;
+        std::thread::sleep(std::time::Duration::from_millis(250));
+    }
+    println!("\r  Time!{:<20}\x07", "");
+
+    let Some(after_hr) = prompt_bpm("HR now (bpm), Enter to skip: ")? else {
+        return Ok(None);
+    };
+    match heart_rate::recovery_drop(end_hr, after_hr) {
+        Some(drop) => {
+            println!("  HR recovery: {} bpm in 60s", drop);
+            Ok(Some(drop))
+        }
+        None => {
+            eprintln!("HR went up; not recording recovery.");
+            Ok(None)
+        }
+    }
+}
+
+/// Ask for a heart rate in bpm; Enter (or unparseable input) skips
+fn prompt_bpm(prompt: &str) -> Result<Option<u8>> {
+    print!("{}", prompt);
+    io::stdout().flush()?;
+
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+
+    let input = input.trim();
+    if input.is_empty() {
+        return Ok(None);
+    }
+    match input.parse::<u8>() {
+        Ok(bpm) if bpm > 0 => Ok(Some(bpm)),
+        _ => {
+            eprintln!("Couldn't parse a heart rate; skipping.");
+            Ok(None)
+        }
+    }
+}
+
+/// Ask for today's sore areas; Enter (or unparseable input) records none
+fn prompt_soreness() -> Result<Vec<SoreArea>> {
+    println!(
+        "Any soreness today? Areas: {}",
+        soreness::BODY_AREAS.join(", ")
+    );
+    print!("Area:1-5 (e.g. hip:3,shoulder:2), Enter for none: ");
+    io::stdout().flush()?;
+
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+
+    match soreness::parse_areas(&input) {
+        Ok(areas) => Ok(areas),
+        Err(e) => {
+            eprintln!("{}; not recording soreness.", e);
+            Ok(vec![])
+        }
+    }
+}
+
+/// Ask for an AMRAP rep total; Enter (or unparseable input) skips
+fn prompt_amrap_total(duration_seconds: u32) -> Result<Option<i32>> {
+    print!(
+        "Total reps in the {}s AMRAP, Enter to skip: ",
+        duration_seconds
+    );
+    io::stdout().flush()?;
+
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+
+    let input = input.trim();
+    if input.is_empty() {
+        return Ok(None);
+    }
+    match input.parse::<i32>() {
+        Ok(total) if total >= 0 => Ok(Some(total)),
+        _ => {
+            eprintln!("Couldn't parse a rep total; not recording it.");
+            Ok(None)
+        }
+    }
+}
+
+/// Ask for reps achieved per set; Enter (or unparseable input) skips
+fn prompt_progress_now() -> Result<bool> {
+    print!("Recent sessions felt easy - progress now? [y/N]: ");
+    io::stdout().flush()?;
+
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    Ok(input.trim().eq_ignore_ascii_case("y"))
+}
+
+fn prompt_reps_per_set() -> Result<Vec<i32>> {
+    print!("Reps per set (e.g. 5,5,5,4,3), Enter to skip: ");
+    io::stdout().flush()?;
+
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+
+    let per_set: std::result::Result<Vec<i32>, _> = input
+        .trim()
+        .split(',')
+        .filter(|part| !part.trim().is_empty())
+        .map(|part| part.trim().parse::<i32>())
+        .collect();
+
+    match per_set {
+        Ok(per_set) => Ok(per_set),
+        Err(_) => {
+            eprintln!("Couldn't parse reps; not recording them.");
+            Ok(vec![])
+        }
+    }
+}
@@ -70,9 +70,11 @@ fn clean_environment_full_cycle() {
         "wal should be removed or archived after rollup"
     );
     assert!(
-        !data_dir
-            .join("wal/microdose_sessions.wal.processed")
-            .exists(),
+        !fs::read_dir(data_dir.join("wal")).unwrap().any(|e| e
+            .unwrap()
+            .file_name()
+            .to_string_lossy()
+            .ends_with(".wal.processed")),
         "processed WAL files should be cleaned up"
     );
 
@@ -171,7 +173,10 @@ fn strength_override_prefers_gtg() {
     let wal_lines = read_wal_lines(&wal_path);
     let parsed: Value = serde_json::from_str(&wal_lines[0]).unwrap();
     assert!(
-        parsed["definition_id"].as_str().unwrap().contains("gtg"),
+        parsed["data"]["definition_id"]
+            .as_str()
+            .unwrap()
+            .contains("gtg"),
         "expected GTG definition in WAL"
     );
 }
@@ -93,6 +93,130 @@ fn test_dry_run_does_not_log() {
     assert!(!wal_path.exists());
 }
 
+#[test]
+fn test_started_dose_records_real_timing() {
+    let temp_dir = setup_test_dir();
+    let data_dir = temp_dir.path().to_path_buf();
+
+    // 'g' starts the dose, 'p' pauses it, Enter resumes, Enter finishes
+    // it, Enter skips the reps prompt
+    cli()
+        .arg("now")
+        .arg("--data-dir")
+        .arg(&data_dir)
+        .arg("--category")
+        .arg("mobility")
+        .arg("--sore")
+        .arg("none")
+        .write_stdin("g\np\n\n\n\n")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Press Enter when finished"))
+        .stdout(predicate::str::contains("Paused. Press Enter to resume"))
+        .stdout(predicate::str::contains("Session logged"));
+
+    let wal = fs::read_to_string(data_dir.join("wal/microdose_sessions.wal")).unwrap();
+    let line: serde_json::Value = serde_json::from_str(wal.lines().next().unwrap()).unwrap();
+    let session = &line["data"];
+    // Measured, not the suggested minutes
+    assert!(session["actual_duration_seconds"].as_u64().unwrap() < 60);
+    assert_eq!(session["performed_at"], session["started_at"]);
+    assert!(session["completed_at"].as_str() >= session["started_at"].as_str());
+
+    // Nothing left in progress
+    let state = fs::read_to_string(data_dir.join("wal/state.json")).unwrap();
+    assert!(!state.contains("active_session\":{"));
+}
+
+#[test]
+fn test_dry_run_shows_readiness() {
+    let temp_dir = setup_test_dir();
+    let data_dir = temp_dir.path().to_path_buf();
+
+    cli()
+        .arg("now")
+        .arg("--data-dir")
+        .arg(&data_dir)
+        .arg("--dry-run")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Readiness: no signal"));
+
+    fs::write(
+        data_dir.join("readiness.json"),
+        r#"{"measured_at": "2024-01-15T06:30:00Z", "score": 68, "sleep_hours": 6.25}"#,
+    )
+    .unwrap();
+    cli()
+        .arg("now")
+        .arg("--data-dir")
+        .arg(&data_dir)
+        .arg("--dry-run")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Readiness: score 68, 6.2h sleep"));
+
+    // A corrupt export is ignored, not fatal
+    fs::write(data_dir.join("readiness.json"), "{ not json").unwrap();
+    cli()
+        .arg("now")
+        .arg("--data-dir")
+        .arg(&data_dir)
+        .arg("--dry-run")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Readiness: no signal"));
+}
+
+#[test]
+fn test_dry_run_at_another_time() {
+    let temp_dir = setup_test_dir();
+    let data_dir = temp_dir.path().to_path_buf();
+
+    cli()
+        .arg("now")
+        .arg("--data-dir")
+        .arg(&data_dir)
+        .arg("--category")
+        .arg("vo2")
+        .arg("--auto-complete")
+        .assert()
+        .success();
+
+    // Right after a VO2 dose the rotation moves on...
+    cli()
+        .arg("now")
+        .arg("--data-dir")
+        .arg(&data_dir)
+        .arg("--dry-run")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Vo2 MICRODOSE").not());
+
+    // ...but an hour earlier it hadn't happened yet
+    let earlier = (chrono::Utc::now() - chrono::Duration::hours(1)).to_rfc3339();
+    cli()
+        .arg("now")
+        .arg("--data-dir")
+        .arg(&data_dir)
+        .arg("--dry-run")
+        .arg("--at")
+        .arg(&earlier)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Vo2 MICRODOSE"));
+
+    // Only for dry runs
+    cli()
+        .arg("now")
+        .arg("--data-dir")
+        .arg(&data_dir)
+        .arg("--at")
+        .arg(&earlier)
+        .assert()
+        .failure();
+}
+
 #[test]
 fn test_category_override() {
     let temp_dir = setup_test_dir();
@@ -111,6 +235,1660 @@ fn test_category_override() {
         .stdout(predicate::str::contains("Mobility"));
 }
 
+#[test]
+fn test_vo2_shows_hr_target_and_logs_hr() {
+    let temp_dir = setup_test_dir();
+    let data_dir = temp_dir.path().to_path_buf();
+
+    cli()
+        .arg("now")
+        .arg("--data-dir")
+        .arg(&data_dir)
+        .arg("--category")
+        .arg("vo2")
+        .arg("--auto-complete")
+        .arg("--avg-hr")
+        .arg("142")
+        .arg("--max-hr")
+        .arg("163")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Target HR: aim for 85–92% max"));
+
+    let wal = fs::read_to_string(data_dir.join("wal").join("microdose_sessions.wal")).unwrap();
+    assert!(wal.contains("\"avg_hr\":142"));
+    assert!(wal.contains("\"max_hr\":163"));
+
+    cli()
+        .arg("now")
+        .arg("--data-dir")
+        .arg(&data_dir)
+        .arg("--category")
+        .arg("gtg")
+        .arg("--dry-run")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Target HR").not());
+}
+
+#[test]
+fn test_hr_recovery_in_stats() {
+    let temp_dir = setup_test_dir();
+    let data_dir = temp_dir.path().to_path_buf();
+
+    for drop in ["22", "28"] {
+        cli()
+            .arg("now")
+            .arg("--data-dir")
+            .arg(&data_dir)
+            .arg("--category")
+            .arg("vo2")
+            .arg("--auto-complete")
+            .arg("--hr-recovery")
+            .arg(drop)
+            .assert()
+            .success();
+    }
+
+    cli()
+        .arg("stats")
+        .arg("--data-dir")
+        .arg(&data_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "HR recovery (60s): 28 bpm latest, 25 bpm mean (+6 bpm)",
+        ));
+}
+
+#[test]
+fn test_progression_log_and_stats_sparkline() {
+    let temp_dir = setup_test_dir();
+    let data_dir = temp_dir.path().to_path_buf();
+
+    cli()
+        .arg("now")
+        .arg("--data-dir")
+        .arg(&data_dir)
+        .arg("--category")
+        .arg("vo2")
+        .arg("--auto-complete")
+        .assert()
+        .success();
+
+    let log = fs::read_to_string(data_dir.join("progression.jsonl")).unwrap();
+    assert_eq!(log.lines().count(), 1);
+    assert!(log.contains("\"definition_id\":\"emom_burpee_5m\""));
+
+    cli()
+        .arg("stats")
+        .arg("--data-dir")
+        .arg(&data_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Progression"))
+        .stdout(predicate::str::contains("3 → 3 reps (level 0)"));
+}
+
+#[test]
+fn test_weekly_report_markdown_and_html() {
+    let temp_dir = setup_test_dir();
+    let data_dir = temp_dir.path().to_path_buf();
+
+    cli()
+        .arg("now")
+        .arg("--data-dir")
+        .arg(&data_dir)
+        .arg("--category")
+        .arg("vo2")
+        .arg("--auto-complete")
+        .assert()
+        .success();
+
+    let md_path = data_dir.join("week.md");
+    cli()
+        .arg("report")
+        .arg("--data-dir")
+        .arg(&data_dir)
+        .arg("--week")
+        .arg("--output")
+        .arg(&md_path)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Wrote report"));
+
+    let markdown = fs::read_to_string(&md_path).unwrap();
+    assert!(markdown.starts_with("# Krep weekly report"));
+    assert!(markdown.contains("5-Min EMOM: Burpees"));
+
+    // Format is inferred from the extension
+    let html_path = data_dir.join("week.html");
+    cli()
+        .arg("report")
+        .arg("--data-dir")
+        .arg(&data_dir)
+        .arg("--week")
+        .arg("--output")
+        .arg(&html_path)
+        .assert()
+        .success();
+
+    let html = fs::read_to_string(&html_path).unwrap();
+    assert!(html.contains("<table>"));
+    assert!(html.contains("5-Min EMOM: Burpees"));
+
+    cli()
+        .arg("report")
+        .arg("--data-dir")
+        .arg(&data_dir)
+        .arg("--format")
+        .arg("pdf")
+        .arg("--output")
+        .arg(&md_path)
+        .assert()
+        .failure();
+}
+
+#[test]
+fn test_doctor_and_rollup_report_anomalies() {
+    let temp_dir = setup_test_dir();
+    let data_dir = temp_dir.path().to_path_buf();
+
+    cli()
+        .arg("doctor")
+        .arg("--data-dir")
+        .arg(&data_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("No session anomalies found"));
+
+    cli()
+        .arg("now")
+        .arg("--data-dir")
+        .arg(&data_dir)
+        .arg("--auto-complete")
+        .assert()
+        .success();
+
+    // Duplicate the logged session and add one with an impossible HR
+    let wal_path = data_dir.join("wal").join("microdose_sessions.wal");
+    let line = fs::read_to_string(&wal_path).unwrap();
+    let mut bad: serde_json::Value = serde_json::from_str(line.trim()).unwrap();
+    bad["data"]["id"] = serde_json::json!("00000000-0000-4000-8000-000000000001");
+    bad["data"]["max_hr"] = serde_json::json!(250);
+    fs::write(&wal_path, format!("{}{}{}\n", line, line, bad)).unwrap();
+
+    cli()
+        .arg("doctor")
+        .arg("--data-dir")
+        .arg(&data_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("2 session anomalies"))
+        .stdout(predicate::str::contains("duplicate session ID"))
+        .stdout(predicate::str::contains("impossible max HR of 250 bpm"));
+
+    cli()
+        .arg("rollup")
+        .arg("--data-dir")
+        .arg(&data_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("⚠ Anomaly"))
+        .stdout(predicate::str::contains("Rolled up 3 sessions"));
+}
+
+#[test]
+fn test_stats_compare_weeks() {
+    let temp_dir = setup_test_dir();
+    let data_dir = temp_dir.path().to_path_buf();
+
+    cli()
+        .arg("now")
+        .arg("--data-dir")
+        .arg(&data_dir)
+        .arg("--category")
+        .arg("vo2")
+        .arg("--auto-complete")
+        .assert()
+        .success();
+
+    cli()
+        .arg("stats")
+        .arg("--data-dir")
+        .arg(&data_dir)
+        .arg("--compare")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("This week vs last week"))
+        .stdout(predicate::str::contains("Doses"))
+        .stdout(predicate::str::contains("▲ +1 (last week 0)"));
+}
+
+#[test]
+fn test_stats_all_time_kept_across_log_and_rollup() {
+    let temp_dir = setup_test_dir();
+    let data_dir = temp_dir.path().to_path_buf();
+    let log = || {
+        cli()
+            .arg("now")
+            .arg("--data-dir")
+            .arg(&data_dir)
+            .arg("--auto-complete")
+            .assert()
+            .success();
+    };
+    let stats = |expected: &str| {
+        cli()
+            .arg("stats")
+            .arg("--data-dir")
+            .arg(&data_dir)
+            .assert()
+            .success()
+            .stdout(predicate::str::contains(expected.to_string()));
+    };
+
+    log();
+    stats("All time: 1 sessions");
+    log();
+    cli()
+        .arg("rollup")
+        .arg("--data-dir")
+        .arg(&data_dir)
+        .assert()
+        .success();
+    stats("All time: 2 sessions");
+
+    // A cache out of step with the data is recomputed
+    let cache = fs::read_dir(&data_dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .find(|path| path.to_string_lossy().ends_with(".csv.stats"))
+        .expect("stats cache next to the archive");
+    fs::write(&cache, "{}").unwrap();
+    stats("All time: 2 sessions");
+}
+
+#[test]
+fn test_skips_logged_and_stats_timing() {
+    let temp_dir = setup_test_dir();
+    let data_dir = temp_dir.path().to_path_buf();
+
+    cli()
+        .arg("now")
+        .arg("--data-dir")
+        .arg(&data_dir)
+        .arg("--auto-complete-skip")
+        .assert()
+        .success();
+
+    let skips = fs::read_to_string(data_dir.join("skips.jsonl")).unwrap();
+    assert_eq!(skips.lines().count(), 3);
+
+    cli()
+        .arg("stats")
+        .arg("--data-dir")
+        .arg(&data_dir)
+        .arg("--timing")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Done vs skipped by hour"))
+        .stdout(predicate::str::contains("1 done   3 skipped"))
+        .stdout(predicate::str::contains("Suggested reminder times"));
+}
+
+#[test]
+fn test_goal_set_show_and_stats_progress() {
+    let temp_dir = setup_test_dir();
+    let data_dir = temp_dir.path().to_path_buf();
+
+    cli()
+        .arg("goal")
+        .arg("show")
+        .arg("--data-dir")
+        .arg(&data_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("No weekly goal set"));
+
+    cli()
+        .arg("goal")
+        .arg("set")
+        .arg("--data-dir")
+        .arg(&data_dir)
+        .arg("--weekly-doses")
+        .arg("0")
+        .assert()
+        .failure();
+
+    cli()
+        .arg("goal")
+        .arg("set")
+        .arg("--data-dir")
+        .arg(&data_dir)
+        .arg("--weekly-doses")
+        .arg("15")
+        .arg("--travel-doses")
+        .arg("8")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Weekly goal set to 15 doses (8 on travel weeks)",
+        ))
+        .stdout(predicate::str::contains("Weekly goal: 0/15"));
+
+    let state = fs::read_to_string(data_dir.join("wal").join("state.json")).unwrap();
+    assert!(state.contains("\"weekly_doses\":15"));
+
+    cli()
+        .arg("now")
+        .arg("--data-dir")
+        .arg(&data_dir)
+        .arg("--tag")
+        .arg("travel")
+        .arg("--auto-complete")
+        .assert()
+        .success();
+
+    cli()
+        .arg("stats")
+        .arg("--data-dir")
+        .arg(&data_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Weekly goal (travel week): 1/8"));
+}
+
+#[test]
+fn test_achievements_announced_and_listed() {
+    let temp_dir = setup_test_dir();
+    let data_dir = temp_dir.path().to_path_buf();
+
+    cli()
+        .arg("achievements")
+        .arg("--data-dir")
+        .arg(&data_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Achievements (0/"));
+
+    cli()
+        .arg("now")
+        .arg("--data-dir")
+        .arg(&data_dir)
+        .arg("--auto-complete")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Achievement unlocked: First Dose"));
+
+    // Already unlocked, so not announced again
+    cli()
+        .arg("now")
+        .arg("--data-dir")
+        .arg(&data_dir)
+        .arg("--auto-complete")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Achievement unlocked").not());
+
+    cli()
+        .arg("achievements")
+        .arg("--data-dir")
+        .arg(&data_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Achievements (1/"))
+        .stdout(predicate::str::contains("🏆 First Dose"));
+}
+
+#[test]
+fn test_analytics_build_writes_sqlite() {
+    let temp_dir = setup_test_dir();
+    let data_dir = temp_dir.path().to_path_buf();
+
+    for _ in 0..2 {
+        cli()
+            .arg("now")
+            .arg("--data-dir")
+            .arg(&data_dir)
+            .arg("--auto-complete")
+            .assert()
+            .success();
+    }
+    cli()
+        .arg("rollup")
+        .arg("--data-dir")
+        .arg(&data_dir)
+        .assert()
+        .success();
+    cli()
+        .arg("now")
+        .arg("--data-dir")
+        .arg(&data_dir)
+        .arg("--auto-complete")
+        .assert()
+        .success();
+
+    cli()
+        .arg("analytics")
+        .arg("build")
+        .arg("--data-dir")
+        .arg(&data_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Built analytics database with 3 sessions",
+        ));
+
+    let header = std::fs::read(data_dir.join("analytics.sqlite")).unwrap();
+    assert!(header.starts_with(b"SQLite format 3"));
+}
+
+#[test]
+fn test_stats_strength_day_skip_rate() {
+    let temp_dir = setup_test_dir();
+    let data_dir = temp_dir.path().to_path_buf();
+
+    cli()
+        .arg("stats")
+        .arg("--data-dir")
+        .arg(&data_dir)
+        .arg("--strength")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("No strength sessions recorded"));
+
+    let strength_dir = data_dir.join("strength");
+    fs::create_dir_all(&strength_dir).unwrap();
+    fs::write(
+        strength_dir.join("signal.json"),
+        format!(
+            r#"{{"last_session_at": "{}", "session_type": "lower"}}"#,
+            chrono::Utc::now().to_rfc3339()
+        ),
+    )
+    .unwrap();
+
+    cli()
+        .arg("now")
+        .arg("--data-dir")
+        .arg(&data_dir)
+        .arg("--auto-complete-skip")
+        .assert()
+        .success();
+
+    let history = fs::read_to_string(data_dir.join("strength_history.jsonl")).unwrap();
+    assert_eq!(history.lines().count(), 1);
+
+    cli()
+        .arg("stats")
+        .arg("--data-dir")
+        .arg(&data_dir)
+        .arg("--strength")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Doses on strength days"))
+        .stdout(predicate::str::contains(
+            "You skip 75% of doses on lower-body days",
+        ));
+
+    // Re-reading the same signal doesn't duplicate the history
+    let history = fs::read_to_string(data_dir.join("strength_history.jsonl")).unwrap();
+    assert_eq!(history.lines().count(), 1);
+}
+
+#[test]
+fn test_stats_minutes_per_category_text_and_json() {
+    let temp_dir = setup_test_dir();
+    let data_dir = temp_dir.path().to_path_buf();
+
+    cli()
+        .arg("now")
+        .arg("--data-dir")
+        .arg(&data_dir)
+        .arg("--category")
+        .arg("vo2")
+        .arg("--auto-complete")
+        .assert()
+        .success();
+
+    cli()
+        .arg("stats")
+        .arg("--data-dir")
+        .arg(&data_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Minutes per category"));
+
+    let output = cli()
+        .arg("stats")
+        .arg("--data-dir")
+        .arg(&data_dir)
+        .arg("--format")
+        .arg("json")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["total_sessions"], 1);
+    // Auto-complete logs the suggested duration
+    let seconds = report["total_seconds"].as_u64().unwrap();
+    assert_eq!(
+        report["time_in_category"][0]["minutes"]["vo2"],
+        (seconds + 30) / 60
+    );
+
+    cli()
+        .arg("stats")
+        .arg("--data-dir")
+        .arg(&data_dir)
+        .arg("--format")
+        .arg("yaml")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Unknown stats format"));
+}
+
+#[test]
+fn test_config_validate_reports_lines() {
+    let temp_dir = setup_test_dir();
+    let config_path = temp_dir.path().join("config.toml");
+
+    fs::write(
+        &config_path,
+        format!(
+            "[data]\ndata_dir = {:?}\n\n[progression]\nburpee_rep_ceiling = 12\n",
+            temp_dir.path()
+        ),
+    )
+    .unwrap();
+    cli()
+        .arg("config")
+        .arg("validate")
+        .arg("--file")
+        .arg(&config_path)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("is valid"));
+
+    fs::write(
+        &config_path,
+        "[progression]\nburpee_rep_ceilng = 12\nkb_swing_max_reps = 1\n",
+    )
+    .unwrap();
+    cli()
+        .arg("config")
+        .arg("validate")
+        .arg("--file")
+        .arg(&config_path)
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains(
+            "config.toml:2: unknown key 'progression.burpee_rep_ceilng'",
+        ))
+        .stdout(predicate::str::contains(
+            "config.toml:3: progression.kb_swing_max_reps must be at least",
+        ));
+
+    // Parse errors are located instead of aborting startup
+    fs::write(&config_path, "[progression\nburpee_rep_ceiling = 12\n").unwrap();
+    cli()
+        .arg("config")
+        .arg("validate")
+        .arg("--file")
+        .arg(&config_path)
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("config.toml:1:"));
+}
+
+#[test]
+fn test_config_validate_checks_drop_ins() {
+    let temp_dir = setup_test_dir();
+    let config_path = temp_dir.path().join("config.toml");
+    let drop_ins = temp_dir.path().join("config.d");
+    fs::create_dir_all(&drop_ins).unwrap();
+
+    fs::write(
+        &config_path,
+        format!("[data]\ndata_dir = {:?}\n", temp_dir.path()),
+    )
+    .unwrap();
+    fs::write(
+        drop_ins.join("10-laptop.toml"),
+        "[equipment]\navailable = [\"pullup_bar\"]\n",
+    )
+    .unwrap();
+    fs::write(
+        drop_ins.join("20-typo.toml"),
+        "[progression]\nkb_swing_max_rep = 12\n",
+    )
+    .unwrap();
+
+    cli()
+        .arg("config")
+        .arg("validate")
+        .arg("--file")
+        .arg(&config_path)
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("10-laptop.toml is valid"))
+        .stdout(predicate::str::contains(
+            "20-typo.toml:2: unknown key 'progression.kb_swing_max_rep'",
+        ));
+}
+
+#[test]
+fn test_config_migrate_without_deprecated_keys() {
+    let temp_dir = setup_test_dir();
+    let config_path = temp_dir.path().join("config.toml");
+    let contents = "# tuned for the office\n[progression]\nkb_swing_max_reps = 12\n";
+    fs::write(&config_path, contents).unwrap();
+
+    cli()
+        .arg("config")
+        .arg("migrate")
+        .arg("--file")
+        .arg(&config_path)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("No deprecated keys"));
+    assert_eq!(fs::read_to_string(&config_path).unwrap(), contents);
+}
+
+/// Write an executable editor script that replaces the file it's given
+#[cfg(unix)]
+fn fake_editor(dir: &std::path::Path, name: &str, contents: &str) -> std::path::PathBuf {
+    use std::os::unix::fs::PermissionsExt;
+
+    let script = dir.join(name);
+    fs::write(
+        &script,
+        format!("#!/bin/sh\ncat > \"$1\" <<'EOF'\n{}EOF\n", contents),
+    )
+    .unwrap();
+    fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+    script
+}
+
+#[cfg(unix)]
+#[test]
+fn test_config_edit_validates_before_saving() {
+    let temp_dir = setup_test_dir();
+    let config_path = temp_dir.path().join("config.toml");
+    let original = format!("[data]\ndata_dir = {:?}\n", temp_dir.path());
+    fs::write(&config_path, &original).unwrap();
+
+    // A typo is refused and the original kept
+    let broken = fake_editor(
+        temp_dir.path(),
+        "broken.sh",
+        "[progression]\nkb_swing_max_rep = 12\n",
+    );
+    cli()
+        .arg("config")
+        .arg("edit")
+        .arg("--file")
+        .arg(&config_path)
+        .env_remove("VISUAL")
+        .env("EDITOR", &broken)
+        .write_stdin("q\n")
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains(
+            "line 2: unknown key 'progression.kb_swing_max_rep'",
+        ));
+    assert_eq!(fs::read_to_string(&config_path).unwrap(), original);
+    assert!(!temp_dir.path().join("config.toml.bak").exists());
+
+    let edited = format!(
+        "[data]\ndata_dir = {:?}\n\n[progression]\nkb_swing_max_reps = 12\n",
+        temp_dir.path()
+    );
+    let good = fake_editor(temp_dir.path(), "good.sh", &edited);
+    cli()
+        .arg("config")
+        .arg("edit")
+        .arg("--file")
+        .arg(&config_path)
+        .env_remove("VISUAL")
+        .env("EDITOR", &good)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("saved"));
+    assert_eq!(fs::read_to_string(&config_path).unwrap(), edited);
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("config.toml.bak")).unwrap(),
+        original
+    );
+}
+
+#[test]
+fn test_off_days() {
+    let temp_dir = setup_test_dir();
+    let data_dir = temp_dir.path();
+
+    cli()
+        .arg("off")
+        .arg("2000-01-01..2999-12-31")
+        .arg("--data-dir")
+        .arg(data_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Off 2000-01-01..2999-12-31"));
+
+    cli()
+        .arg("now")
+        .arg("--data-dir")
+        .arg(data_dir)
+        .arg("--auto-complete")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Today is an off day"));
+    assert!(!data_dir.join("wal/microdose_sessions.wal").exists());
+
+    cli()
+        .arg("off")
+        .arg("--data-dir")
+        .arg(data_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("2000-01-01..2999-12-31 (today)"));
+
+    cli()
+        .arg("status")
+        .arg("--data-dir")
+        .arg(data_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\"off_day\": true"));
+
+    cli()
+        .arg("off")
+        .arg("--clear")
+        .arg("--data-dir")
+        .arg(data_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Cleared 1"));
+
+    cli()
+        .arg("now")
+        .arg("--data-dir")
+        .arg(data_dir)
+        .arg("--auto-complete")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Today is an off day").not());
+}
+
+#[test]
+fn test_household_stats() {
+    let temp_dir = setup_test_dir();
+    let config_dir = temp_dir.path().join("config");
+    let anna_dir = temp_dir.path().join("anna");
+    let my_dir = temp_dir.path().join("me");
+    fs::create_dir_all(config_dir.join("krep")).unwrap();
+    fs::write(
+        config_dir.join("krep").join("config.toml"),
+        format!(
+            "[household]\nmembers = [\"anna\"]\n\n[profile.anna]\ndata_dir = \"{}\"\n",
+            anna_dir.display()
+        ),
+    )
+    .unwrap();
+
+    // Anna logs through her profile, which points at her data directory
+    for _ in 0..2 {
+        cli()
+            .env("XDG_CONFIG_HOME", &config_dir)
+            .env_remove("KREP_PROFILE")
+            .arg("--profile")
+            .arg("anna")
+            .arg("now")
+            .arg("--auto-complete")
+            .assert()
+            .success();
+    }
+    cli()
+        .env("XDG_CONFIG_HOME", &config_dir)
+        .env_remove("KREP_PROFILE")
+        .arg("now")
+        .arg("--data-dir")
+        .arg(&my_dir)
+        .arg("--auto-complete")
+        .assert()
+        .success();
+
+    cli()
+        .env("XDG_CONFIG_HOME", &config_dir)
+        .env_remove("KREP_PROFILE")
+        .arg("stats")
+        .arg("--data-dir")
+        .arg(&my_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Household"))
+        .stdout(predicate::str::is_match(r"anna\s+2 today, 2 sessions").unwrap())
+        .stdout(predicate::str::is_match(r"you\s+1 today, 1 sessions").unwrap())
+        .stdout(predicate::str::is_match(r"household\s+3 today, 3 sessions").unwrap());
+}
+
+#[test]
+fn test_profile_restricts_prescriptions() {
+    let temp_dir = setup_test_dir();
+    let config_dir = temp_dir.path().join("config");
+    fs::create_dir_all(config_dir.join("krep")).unwrap();
+    fs::write(
+        config_dir.join("krep").join("config.toml"),
+        "[profile.office]\nequipment = []\ngtg_friendly_only = true\n",
+    )
+    .unwrap();
+    let data_dir = temp_dir.path().join("data");
+
+    cli()
+        .env("XDG_CONFIG_HOME", &config_dir)
+        .env_remove("KREP_PROFILE")
+        .arg("now")
+        .arg("--data-dir")
+        .arg(&data_dir)
+        .arg("--profile")
+        .arg("office")
+        .arg("--category")
+        .arg("vo2")
+        .arg("--dry-run")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Profile: office"))
+        .stdout(predicate::str::contains("Burpees").not())
+        .stdout(predicate::str::contains("Swings").not());
+
+    cli()
+        .env("XDG_CONFIG_HOME", &config_dir)
+        .env("KREP_PROFILE", "gym")
+        .arg("now")
+        .arg("--data-dir")
+        .arg(&data_dir)
+        .arg("--dry-run")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Unknown profile 'gym'"));
+}
+
+#[test]
+fn test_config_schema() {
+    let output = cli().arg("config").arg("schema").output().unwrap();
+    assert!(output.status.success());
+
+    let schema: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(schema["title"], "krep configuration");
+    assert!(schema["properties"]["prescription"].is_object());
+}
+
+#[test]
+fn test_unwritable_data_dir_runs_read_only() {
+    let temp_dir = setup_test_dir();
+    // A data directory that can't be created: its parent is a file
+    let blocked = temp_dir.path().join("not_a_dir");
+    fs::write(&blocked, "").unwrap();
+
+    cli()
+        .arg("now")
+        .arg("--data-dir")
+        .arg(blocked.join("krep"))
+        .arg("--auto-complete")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Running read-only"))
+        .stdout(predicate::str::contains("not saved"));
+}
+
+#[test]
+fn test_qr_code_for_reference_link() {
+    let temp_dir = setup_test_dir();
+
+    cli()
+        .arg("now")
+        .arg("--data-dir")
+        .arg(temp_dir.path())
+        .arg("--category")
+        .arg("mobility")
+        .arg("--dry-run")
+        .arg("--qr")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Reference: https://"))
+        .stdout(predicate::str::contains("▀"));
+}
+
+#[test]
+fn test_open_print_targets() {
+    let temp_dir = setup_test_dir();
+    let data_dir = temp_dir.path().join("data");
+
+    cli()
+        .arg("open")
+        .arg("--data-dir")
+        .arg(&data_dir)
+        .arg("--print")
+        .arg("data")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(data_dir.to_str().unwrap()));
+    assert!(data_dir.is_dir());
+
+    cli()
+        .arg("open")
+        .arg("--print")
+        .arg("reference")
+        .arg("mobility_hip_cars")
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with("https://"));
+
+    cli()
+        .arg("open")
+        .arg("--print")
+        .arg("reference")
+        .arg("tabata_air_squat_4m")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("no reference link"));
+
+    cli()
+        .arg("open")
+        .arg("--print")
+        .arg("reference")
+        .arg("no_such_dose")
+        .assert()
+        .code(66)
+        .stderr(predicate::str::contains("Unknown microdose definition"));
+}
+
+#[test]
+fn test_watch_due_dose_prompts_and_quits() {
+    let temp_dir = setup_test_dir();
+
+    // Nothing logged yet, so a dose is due straight away
+    cli()
+        .arg("watch")
+        .arg("--data-dir")
+        .arg(temp_dir.path())
+        .arg("--every")
+        .arg("30")
+        .write_stdin("q\n")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Time for a microdose!"));
+
+    cli()
+        .arg("watch")
+        .arg("--data-dir")
+        .arg(temp_dir.path())
+        .arg("--every")
+        .arg("0")
+        .assert()
+        .failure();
+}
+
+#[test]
+fn test_now_surprise() {
+    let temp_dir = setup_test_dir();
+
+    cli()
+        .arg("now")
+        .arg("--data-dir")
+        .arg(temp_dir.path())
+        .arg("--surprise")
+        .arg("--dry-run")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Surprise pick: not done recently"));
+
+    cli()
+        .arg("now")
+        .arg("--data-dir")
+        .arg(temp_dir.path())
+        .arg("--surprise")
+        .arg("--category")
+        .arg("vo2")
+        .assert()
+        .failure();
+}
+
+#[test]
+fn test_snooze_and_remind_plan() {
+    let temp_dir = setup_test_dir();
+
+    cli()
+        .arg("remind")
+        .arg("--data-dir")
+        .arg(temp_dir.path())
+        .arg("--hours")
+        .arg("17,9")
+        .arg("--plan")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Reminders today: 09:00, 17:00"));
+
+    cli()
+        .arg("snooze")
+        .arg("--data-dir")
+        .arg(temp_dir.path())
+        .arg("--minutes")
+        .arg("20")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Snoozed for 20 min"));
+    let snoozes = fs::read_to_string(temp_dir.path().join("snoozes.jsonl")).unwrap();
+    assert!(snoozes.contains("\"minutes\":20"));
+
+    // Nothing done and one snooze: adherence is low, so reminders thin out
+    cli()
+        .arg("remind")
+        .arg("--data-dir")
+        .arg(temp_dir.path())
+        .arg("--plan")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Reminders today: 09:00, 17:00"))
+        .stdout(predicate::str::contains("Spaced out: 0% of doses done"));
+}
+
+#[test]
+fn test_install_systemd_units() {
+    let temp_dir = setup_test_dir();
+    let unit_dir = temp_dir.path().join("units");
+
+    cli()
+        .arg("install-systemd")
+        .arg("--data-dir")
+        .arg(temp_dir.path())
+        .arg("--dry-run")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("# krep-reminder.timer"))
+        // No history yet, so the default reminder hours, and the timer fires
+        // around them so `krep remind` can move them
+        .stdout(predicate::str::contains("remind --hours 9,13,17"))
+        .stdout(predicate::str::contains(
+            "OnCalendar=*-*-* 06,07,08,09,10,11,12,13,14,15,16,17,18,19,20:00:00",
+        ))
+        .stdout(predicate::str::contains("rollup --cleanup"));
+    assert!(!unit_dir.exists());
+
+    cli()
+        .arg("install-systemd")
+        .arg("--data-dir")
+        .arg(temp_dir.path())
+        .arg("--dir")
+        .arg(&unit_dir)
+        .arg("--reminder-hours")
+        .arg("18,8")
+        .arg("--rollup-at")
+        .arg("02:30")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("systemctl --user enable --now"));
+    let timer = fs::read_to_string(unit_dir.join("krep-reminder.timer")).unwrap();
+    assert!(timer.contains("OnCalendar=*-*-* 05,06,07,08,09,10,11,15,16,17,18,19,20,21:00:00"));
+    let service = fs::read_to_string(unit_dir.join("krep-reminder.service")).unwrap();
+    assert!(service.contains("remind --hours 8,18"));
+    let rollup = fs::read_to_string(unit_dir.join("krep-rollup.timer")).unwrap();
+    assert!(rollup.contains("OnCalendar=*-*-* 02:30:00"));
+    assert!(unit_dir.join("krep-rollup.service").exists());
+
+    cli()
+        .arg("install-systemd")
+        .arg("--data-dir")
+        .arg(temp_dir.path())
+        .arg("--dry-run")
+        .arg("--rollup-at")
+        .arg("25:00")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("expected HH:MM"));
+}
+
+#[test]
+fn test_import_google_fit_takeout() {
+    let temp_dir = setup_test_dir();
+    let sessions_dir = temp_dir.path().join("takeout/Takeout/Fit/All Sessions");
+    fs::create_dir_all(&sessions_dir).unwrap();
+    let start = chrono::Utc::now() - chrono::Duration::days(1);
+    let write_session = |name: &str, activity: &str, minutes: i64| {
+        let json = format!(
+            r#"{{"fitnessActivity": "{}", "startTime": "{}", "endTime": "{}"}}"#,
+            activity,
+            start.to_rfc3339(),
+            (start + chrono::Duration::minutes(minutes)).to_rfc3339()
+        );
+        fs::write(sessions_dir.join(name), json).unwrap();
+    };
+    write_session("hiit.json", "interval_training", 6);
+    write_session("walk.json", "walking", 30);
+    write_session("lift.json", "strength_training", 50);
+
+    let import = || {
+        let mut cmd = cli();
+        cmd.arg("import")
+            .arg("--data-dir")
+            .arg(temp_dir.path())
+            .arg("google-fit")
+            .arg(temp_dir.path().join("takeout"));
+        cmd
+    };
+
+    // Declining the confirmation writes nothing
+    import()
+        .write_stdin("n\n")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("google_fit_interval_training"))
+        .stdout(predicate::str::contains("Nothing imported."));
+    let wal_path = temp_dir.path().join("wal").join("microdose_sessions.wal");
+    assert!(!wal_path.exists());
+
+    import()
+        .write_stdin("y\n")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Imported 1 session(s) tagged 'google-fit' and 1 strength workout(s)",
+        ));
+    assert!(temp_dir.path().join("strength_history.jsonl").exists());
+
+    // Already imported
+    import()
+        .arg("--yes")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Nothing new to import."));
+
+    cli()
+        .arg("history")
+        .arg("--data-dir")
+        .arg(temp_dir.path())
+        .arg("--tag")
+        .arg("google-fit")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("google_fit_interval_training"));
+}
+
+#[test]
+fn test_journal_daily_note() {
+    let temp_dir = setup_test_dir();
+    let config_dir = temp_dir.path().join("config");
+    let notes_dir = temp_dir.path().join("notes");
+    fs::create_dir_all(config_dir.join("krep")).unwrap();
+    fs::write(
+        config_dir.join("krep").join("config.toml"),
+        format!(
+            "[journal]\ndaily_note = \"{}/{{date}}.md\"\n",
+            notes_dir.display()
+        ),
+    )
+    .unwrap();
+    let data_dir = temp_dir.path().join("data");
+
+    cli()
+        .env("XDG_CONFIG_HOME", &config_dir)
+        .arg("now")
+        .arg("--data-dir")
+        .arg(&data_dir)
+        .arg("--category")
+        .arg("gtg")
+        .arg("--auto-complete")
+        .assert()
+        .success();
+
+    cli()
+        .env("XDG_CONFIG_HOME", &config_dir)
+        .arg("rollup")
+        .arg("--data-dir")
+        .arg(&data_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Updated the summary in 1 daily note(s)",
+        ));
+
+    let notes: Vec<_> = fs::read_dir(&notes_dir).unwrap().collect();
+    assert_eq!(notes.len(), 1);
+    let note = fs::read_to_string(notes[0].as_ref().unwrap().path()).unwrap();
+    let lines: Vec<&str> = note.lines().collect();
+    assert_eq!(lines.len(), 2, "unexpected note: {}", note);
+    assert!(lines[0].starts_with("- "));
+    assert!(lines[1].starts_with("- krep daily: 1 dose"));
+}
+
+#[test]
+fn test_status_file() {
+    let temp_dir = setup_test_dir();
+    let config_dir = temp_dir.path().join("config");
+    let status_path = temp_dir.path().join("ha").join("krep.json");
+    fs::create_dir_all(config_dir.join("krep")).unwrap();
+    fs::write(
+        config_dir.join("krep").join("config.toml"),
+        format!("[status]\nfile = \"{}\"\n", status_path.display()),
+    )
+    .unwrap();
+    let data_dir = temp_dir.path().join("data");
+
+    cli()
+        .env("XDG_CONFIG_HOME", &config_dir)
+        .arg("status")
+        .arg("--data-dir")
+        .arg(&data_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\"due\": true"))
+        .stdout(predicate::str::contains("\"schema_version\": 1"));
+
+    cli()
+        .env("XDG_CONFIG_HOME", &config_dir)
+        .arg("now")
+        .arg("--data-dir")
+        .arg(&data_dir)
+        .arg("--category")
+        .arg("gtg")
+        .arg("--auto-complete")
+        .assert()
+        .success();
+
+    let status: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&status_path).unwrap()).unwrap();
+    assert_eq!(status["due"], false);
+    assert_eq!(status["sessions_today"], 1);
+    assert_eq!(status["streak_days"], 1);
+}
+
+#[test]
+fn test_session_attach_hr() {
+    let temp_dir = setup_test_dir();
+    let data_dir = temp_dir.path();
+
+    cli()
+        .arg("now")
+        .arg("--data-dir")
+        .arg(data_dir)
+        .arg("--category")
+        .arg("vo2")
+        .arg("--auto-complete")
+        .assert()
+        .success();
+
+    let wal = fs::read_to_string(data_dir.join("wal/microdose_sessions.wal")).unwrap();
+    let record: serde_json::Value = serde_json::from_str(wal.lines().next().unwrap()).unwrap();
+    let session = &record["data"];
+    let id = session["id"].as_str().unwrap().to_string();
+    let start: chrono::DateTime<chrono::Utc> =
+        session["started_at"].as_str().unwrap().parse().unwrap();
+    let recording = data_dir.join("polar.csv");
+    let mut csv = String::from("timestamp,HR (bpm)\n");
+    for (seconds, bpm) in [(-120, 90), (10, 140), (60, 170), (7200, 100)] {
+        let at = start + chrono::Duration::seconds(seconds);
+        csv.push_str(&format!("{},{}\n", at.to_rfc3339(), bpm));
+    }
+    fs::write(&recording, csv).unwrap();
+
+    cli()
+        .arg("session")
+        .arg("attach-hr")
+        .arg(&id)
+        .arg(&recording)
+        .arg("--data-dir")
+        .arg(data_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("avg 155 bpm, max 170 bpm"));
+    assert!(data_dir.join("hr").join(format!("{}.csv", id)).exists());
+
+    cli()
+        .arg("history")
+        .arg("--ids")
+        .arg("--data-dir")
+        .arg(data_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!("♥ 155/170  {}", id)));
+
+    cli()
+        .arg("session")
+        .arg("attach-hr")
+        .arg("00000000-0000-4000-8000-000000000001")
+        .arg(&recording)
+        .arg("--data-dir")
+        .arg(data_dir)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("No session with ID"));
+}
+
+#[test]
+fn test_export_archive() {
+    let temp_dir = setup_test_dir();
+    let config_dir = temp_dir.path().join("config");
+    fs::create_dir_all(config_dir.join("krep")).unwrap();
+    fs::write(
+        config_dir.join("krep").join("config.toml"),
+        "[time]\ntimezone = \"UTC\"\n",
+    )
+    .unwrap();
+    let data_dir = temp_dir.path().join("data");
+    let archive = temp_dir.path().join("backup.tar.zst");
+
+    cli()
+        .env("XDG_CONFIG_HOME", &config_dir)
+        .arg("now")
+        .arg("--data-dir")
+        .arg(&data_dir)
+        .arg("--auto-complete")
+        .assert()
+        .success();
+
+    cli()
+        .env("XDG_CONFIG_HOME", &config_dir)
+        .arg("export")
+        .arg("--archive")
+        .arg(&archive)
+        .arg("--data-dir")
+        .arg(&data_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("data/wal/microdose_sessions.wal"))
+        .stdout(predicate::str::contains("config/config.toml"))
+        .stdout(predicate::str::contains("✓ Backed up"));
+
+    // zstd frame magic
+    let bytes = fs::read(&archive).unwrap();
+    assert_eq!(&bytes[..4], &[0x28, 0xb5, 0x2f, 0xfd]);
+}
+
+#[test]
+fn test_import_dir_merges_by_id() {
+    let temp_dir = setup_test_dir();
+    let ours = temp_dir.path().join("ours");
+    let theirs = temp_dir.path().join("theirs");
+    for dir in [&ours, &theirs] {
+        cli()
+            .arg("now")
+            .arg("--data-dir")
+            .arg(dir)
+            .arg("--auto-complete")
+            .assert()
+            .success();
+    }
+
+    cli()
+        .arg("import-dir")
+        .arg(&theirs)
+        .arg("--dry-run")
+        .arg("--data-dir")
+        .arg(&ours)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Would import 1 new session(s); 0 already here",
+        ));
+
+    cli()
+        .arg("import-dir")
+        .arg(&theirs)
+        .arg("--data-dir")
+        .arg(&ours)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Imported 1 new session(s); 0 already here",
+        ));
+    let wal = fs::read_to_string(ours.join("wal/microdose_sessions.wal")).unwrap();
+    assert_eq!(wal.lines().count(), 2);
+
+    cli()
+        .arg("import-dir")
+        .arg(&theirs)
+        .arg("--data-dir")
+        .arg(&ours)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Imported 0 new session(s); 1 already here",
+        ));
+
+    cli()
+        .arg("import-dir")
+        .arg(&ours)
+        .arg("--data-dir")
+        .arg(&ours)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("into itself"));
+}
+
+#[test]
+fn test_vo2_shows_warmup() {
+    let temp_dir = setup_test_dir();
+    let data_dir = temp_dir.path().to_path_buf();
+
+    cli()
+        .arg("now")
+        .arg("--data-dir")
+        .arg(&data_dir)
+        .arg("--category")
+        .arg("vo2")
+        .arg("--dry-run")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Warmup first: 30s Jumping Jacks, 30s Hip Hinges",
+        ));
+
+    cli()
+        .arg("now")
+        .arg("--data-dir")
+        .arg(&data_dir)
+        .arg("--category")
+        .arg("gtg")
+        .arg("--dry-run")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Warmup").not());
+}
+
+#[test]
+fn test_reps_per_set_shown_in_history() {
+    let temp_dir = setup_test_dir();
+    let data_dir = temp_dir.path().to_path_buf();
+
+    cli()
+        .arg("now")
+        .arg("--data-dir")
+        .arg(&data_dir)
+        .arg("--category")
+        .arg("vo2")
+        .arg("--auto-complete")
+        .arg("--reps")
+        .arg("5,5,5,4,3")
+        .assert()
+        .success();
+
+    let wal_content = fs::read_to_string(data_dir.join("wal/microdose_sessions.wal"))
+        .expect("Failed to read WAL");
+    assert!(wal_content.contains("\"per_set\":[5,5,5,4,3]"));
+
+    cli()
+        .arg("history")
+        .arg("--data-dir")
+        .arg(&data_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("5/5/5/4/3"));
+}
+
+#[test]
+fn test_amrap_total_tracks_personal_best() {
+    let temp_dir = setup_test_dir();
+    let data_dir = temp_dir.path().to_path_buf();
+
+    let run_core = |reps: Option<&str>| {
+        let mut cmd = cli();
+        cmd.arg("now")
+            .arg("--data-dir")
+            .arg(&data_dir)
+            .arg("--category")
+            .arg("core")
+            .arg("--auto-complete");
+        if let Some(reps) = reps {
+            cmd.arg("--reps").arg(reps);
+        }
+        cmd.assert().success()
+    };
+
+    run_core(Some("30"))
+        .stdout(predicate::str::contains("AMRAP"))
+        .stdout(predicate::str::contains("First AMRAP logged: 30 reps"));
+
+    // Core round-robin moves on to dead bugs, then back to the AMRAP
+    run_core(None).stdout(predicate::str::contains("Dead Bugs"));
+
+    // Per-set reps for an AMRAP are summed into one total
+    run_core(Some("12,20"))
+        .stdout(predicate::str::contains("max reps (best 30)"))
+        .stdout(predicate::str::contains(
+            "New personal best: 32 reps (was 30)",
+        ));
+
+    let state = fs::read_to_string(data_dir.join("wal/state.json")).unwrap();
+    assert!(state.contains("\"core_amrap_mountain_climber_2m\":32"));
+}
+
+#[test]
+fn test_tags_logged_and_filtered_in_history() {
+    let temp_dir = setup_test_dir();
+    let data_dir = temp_dir.path().to_path_buf();
+
+    cli()
+        .arg("now")
+        .arg("--data-dir")
+        .arg(&data_dir)
+        .arg("--category")
+        .arg("gtg")
+        .arg("--auto-complete")
+        .arg("--tag")
+        .arg("Travel,hotel-gym")
+        .assert()
+        .success();
+
+    cli()
+        .arg("now")
+        .arg("--data-dir")
+        .arg(&data_dir)
+        .arg("--category")
+        .arg("mobility")
+        .arg("--auto-complete")
+        .assert()
+        .success();
+
+    cli()
+        .arg("history")
+        .arg("--data-dir")
+        .arg(&data_dir)
+        .arg("--tag")
+        .arg("travel")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("[travel, hotel-gym]"))
+        .stdout(predicate::str::contains("CARs").not());
+}
+
+#[test]
+fn test_soreness_checkin_avoids_sore_areas() {
+    let temp_dir = setup_test_dir();
+    let data_dir = temp_dir.path().to_path_buf();
+
+    // Fresh history would start with VO2; sore legs rule out swings and burpees
+    cli()
+        .arg("now")
+        .arg("--data-dir")
+        .arg(&data_dir)
+        .arg("--dry-run")
+        .arg("--sore")
+        .arg("legs:4")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Swing").not())
+        .stdout(predicate::str::contains("Burpee").not());
+
+    let state = fs::read_to_string(data_dir.join("wal/state.json")).unwrap();
+    assert!(state.contains("\"legs\""));
+
+    // The check-in is remembered for later runs
+    cli()
+        .arg("now")
+        .arg("--data-dir")
+        .arg(&data_dir)
+        .arg("--dry-run")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Swing").not())
+        .stdout(predicate::str::contains("Burpee").not());
+
+    cli()
+        .arg("now")
+        .arg("--data-dir")
+        .arg(&data_dir)
+        .arg("--dry-run")
+        .arg("--sore")
+        .arg("spleen")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Unknown body area"));
+}
+
+#[test]
+fn test_weigh_and_stats_trend() {
+    let temp_dir = setup_test_dir();
+    let data_dir = temp_dir.path().to_path_buf();
+
+    for weight in ["82.4", "82.0"] {
+        cli()
+            .arg("weigh")
+            .arg(weight)
+            .arg("--data-dir")
+            .arg(&data_dir)
+            .assert()
+            .success();
+    }
+    assert!(data_dir.join("bodyweight.jsonl").exists());
+
+    cli()
+        .arg("weigh")
+        .arg("0")
+        .arg("--data-dir")
+        .arg(&data_dir)
+        .assert()
+        .failure();
+
+    cli()
+        .arg("now")
+        .arg("--data-dir")
+        .arg(&data_dir)
+        .arg("--auto-complete")
+        .assert()
+        .success();
+
+    cli()
+        .arg("stats")
+        .arg("--data-dir")
+        .arg(&data_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Sessions: 1"))
+        .stdout(predicate::str::contains("Bodyweight: 82.0 kg (-0.4 kg"));
+}
+
 #[test]
 fn test_rollup_creates_csv() {
     let temp_dir = setup_test_dir();
@@ -0,0 +1,281 @@
+//! Achievements and milestones.
+//!
+//! Each achievement is a condition over lifetime history (and the
+//! progression log for style-based ones). Conditions are re-checked after a
+//! session is logged; newly met ones are stored with their unlock time in
+//! [`UserMicrodoseState::achievements`] so they stay unlocked after e.g. a
+//! streak breaks.
+
+use crate::progression_log::ProgressionEvent;
+#[cfg(feature = "fs")]
+use crate::Result;
+use crate::{
+    BurpeeStyle, Catalog, LocalTz, MicrodoseCategory, MovementStyle, SessionKind,
+    UserMicrodoseState, WeeklyGoal,
+};
+use chrono::{DateTime, Utc};
+#[cfg(feature = "fs")]
+use std::path::Path;
+
+/// A milestone the user can unlock
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Achievement {
+    pub id: &'static str,
+    pub name: &'static str,
+    pub description: &'static str,
+}
+
+/// Every achievement, in display order
+pub const ACHIEVEMENTS: [Achievement; 7] = [
+    Achievement {
+        id: "first_dose",
+        name: "First Dose",
+        description: "Log your first microdose",
+    },
+    Achievement {
+        id: "first_seal_burpee",
+        name: "Seal of Approval",
+        description: "Complete a burpee EMOM at seal style",
+    },
+    Achievement {
+        id: "streak_7",
+        name: "Full Week",
+        description: "Reach a 7-day streak",
+    },
+    Achievement {
+        id: "streak_30",
+        name: "Habit Formed",
+        description: "Reach a 30-day streak",
+    },
+    Achievement {
+        id: "gtg_100",
+        name: "Greased Groove",
+        description: "Log 100 lifetime GTG doses",
+    },
+    Achievement {
+        id: "doses_100",
+        name: "Century",
+        description: "Log 100 lifetime doses",
+    },
+    Achievement {
+        id: "doses_500",
+        name: "Five Hundred",
+        description: "Log 500 lifetime doses",
+    },
+];
+
+/// History window for lifetime counts (effectively everything)
+pub const LIFETIME_DAYS: i64 = 36_500;
+
+/// Burpee definition whose style progresses to seal
+const BURPEE_DEFINITION: &str = "emom_burpee_5m";
+
+/// Look up an achievement by ID
+pub fn find(id: &str) -> Option<&'static Achievement> {
+    ACHIEVEMENTS.iter().find(|a| a.id == id)
+}
+
+/// Whether a burpee session was done while the progression was at seal style
+fn seal_burpee_done(sessions: &[SessionKind], events: &[ProgressionEvent]) -> bool {
+    let mut burpee_events: Vec<&ProgressionEvent> = events
+        .iter()
+        .filter(|e| e.definition_id == BURPEE_DEFINITION)
+        .collect();
+    burpee_events.sort_by_key(|e| e.changed_at);
+
+    sessions
+        .iter()
+        .filter_map(|s| s.as_real())
+        .filter(|s| s.definition_id == BURPEE_DEFINITION)
+        .any(|s| {
+            burpee_events
+                .iter()
+                .rev()
+                .find(|e| e.changed_at <= s.performed_at)
+                .is_some_and(|e| e.style == MovementStyle::Burpee(BurpeeStyle::Seal))
+        })
+}
+
+/// Achievements whose condition holds for `sessions` (lifetime history)
+///
+/// The streak uses [`goal::forgiving_streak`](crate::goal::forgiving_streak),
+/// so a weekly goal's rest days count and off days are passed over.
+pub fn earned(
+    catalog: &Catalog,
+    sessions: &[SessionKind],
+    events: &[ProgressionEvent],
+    goal: Option<&WeeklyGoal>,
+    off_days: &[crate::off_days::OffRange],
+    tz: &LocalTz,
+    now: DateTime<Utc>,
+) -> Vec<&'static Achievement> {
+    let real: Vec<_> = sessions.iter().filter_map(|s| s.as_real()).collect();
+    let gtg = real
+        .iter()
+        .filter(|s| {
+            catalog
+                .definition(&s.definition_id)
+                .is_some_and(|d| d.category == MicrodoseCategory::Gtg)
+        })
+        .count();
+    let streak = crate::goal::forgiving_streak(sessions, goal, off_days, tz, now);
+
+    ACHIEVEMENTS
+        .iter()
+        .filter(|a| match a.id {
+            "first_dose" => !real.is_empty(),
+            "first_seal_burpee" => seal_burpee_done(sessions, events),
+            "streak_7" => streak >= 7,
+            "streak_30" => streak >= 30,
+            "gtg_100" => gtg >= 100,
+            "doses_100" => real.len() >= 100,
+            "doses_500" => real.len() >= 500,
+            _ => false,
+        })
+        .collect()
+}
+
+/// Record newly earned achievements in the state and return them
+pub fn unlock(
+    state: &mut UserMicrodoseState,
+    earned: &[&'static Achievement],
+    now: DateTime<Utc>,
+) -> Vec<&'static Achievement> {
+    let mut unlocked = Vec::new();
+    for achievement in earned {
+        if !state.achievements.contains_key(achievement.id) {
+            state.achievements.insert(achievement.id.to_string(), now);
+            unlocked.push(*achievement);
+        }
+    }
+    unlocked
+}
+
+/// Check lifetime history in `data_dir` and unlock anything newly earned
+///
+/// Only updates `state`; the caller saves it.
+#[cfg(feature = "fs")]
+pub fn check_data_dir(
+    data_dir: &Path,
+    state: &mut UserMicrodoseState,
+    config: &crate::Config,
+    now: DateTime<Utc>,
+) -> Result<Vec<&'static Achievement>> {
+    let storage = crate::FileStorage::with_config(data_dir, &config.data);
+    let sessions = crate::history::load_recent_sessions_with(
+        &storage.wal_path,
+        &storage.csv_path,
+        LIFETIME_DAYS,
+        config.data.exclude_anomalies,
+        storage.lock_timeout,
+    )?;
+    let events = crate::progression_log::read_events(
+        &data_dir.join(crate::progression_log::PROGRESSION_LOG_FILE),
+        storage.lock_timeout,
+    )?;
+    let earned = earned(
+        crate::get_default_catalog(),
+        &sessions,
+        &events,
+        state.weekly_goal.as_ref(),
+        &config.off_days(state),
+        &config.timezone(),
+        now,
+    );
+    Ok(unlock(state, &earned, now))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{build_default_catalog, MicrodoseSession};
+    use chrono::Duration;
+
+    fn session(def_id: &str, days_ago: i64, now: DateTime<Utc>) -> SessionKind {
+        SessionKind::Real(MicrodoseSession {
+            actual_duration_seconds: Some(300),
+            ..MicrodoseSession::new(def_id, now - Duration::days(days_ago))
+        })
+    }
+
+    fn ids(achievements: &[&Achievement]) -> Vec<&'static str> {
+        achievements.iter().map(|a| a.id).collect()
+    }
+
+    #[test]
+    fn test_earned_counts_and_streaks() {
+        let catalog = build_default_catalog();
+        let tz = LocalTz::from_name(Some("UTC")).unwrap();
+        let now = Utc::now();
+
+        assert!(earned(&catalog, &[], &[], None, &[], &tz, now).is_empty());
+
+        let week: Vec<SessionKind> = (0..7)
+            .map(|d| session("emom_kb_swing_5m", d, now))
+            .collect();
+        assert_eq!(
+            ids(&earned(&catalog, &week, &[], None, &[], &tz, now)),
+            vec!["first_dose", "streak_7"]
+        );
+
+        let gtg: Vec<SessionKind> = (0..100)
+            .map(|_| session("gtg_pullup_band", 40, now))
+            .collect();
+        assert_eq!(
+            ids(&earned(&catalog, &gtg, &[], None, &[], &tz, now)),
+            vec!["first_dose", "gtg_100", "doses_100"]
+        );
+    }
+
+    #[test]
+    fn test_seal_burpee_uses_style_in_effect() {
+        let catalog = build_default_catalog();
+        let tz = LocalTz::from_name(Some("UTC")).unwrap();
+        let now = Utc::now();
+        let event = |days_ago: i64, style: BurpeeStyle| ProgressionEvent {
+            changed_at: now - Duration::days(days_ago),
+            definition_id: BURPEE_DEFINITION.into(),
+            reps: 4,
+            level: 3,
+            style: MovementStyle::Burpee(style),
+        };
+        let events = vec![
+            event(10, BurpeeStyle::SixCount),
+            event(5, BurpeeStyle::Seal),
+        ];
+
+        let before_seal = vec![session(BURPEE_DEFINITION, 8, now)];
+        assert!(!ids(&earned(
+            &catalog,
+            &before_seal,
+            &events,
+            None,
+            &[],
+            &tz,
+            now
+        ))
+        .contains(&"first_seal_burpee"));
+
+        let at_seal = vec![session(BURPEE_DEFINITION, 2, now)];
+        assert!(
+            ids(&earned(&catalog, &at_seal, &events, None, &[], &tz, now))
+                .contains(&"first_seal_burpee")
+        );
+    }
+
+    #[test]
+    fn test_unlock_only_returns_new() {
+        let mut state = UserMicrodoseState::default();
+        let now = Utc::now();
+        let first = find("first_dose").unwrap();
+        let streak = find("streak_7").unwrap();
+
+        assert_eq!(ids(&unlock(&mut state, &[first], now)), vec!["first_dose"]);
+        assert_eq!(
+            ids(&unlock(&mut state, &[first, streak], now)),
+            vec!["streak_7"]
+        );
+        assert_eq!(state.achievements.len(), 2);
+        assert!(find("nope").is_none());
+    }
+}
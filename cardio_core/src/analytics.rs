@@ -0,0 +1,292 @@
+//! SQLite analytics database.
+//!
+//! `krep analytics build` loads the CSV archive and the WAL into a fresh
+//! SQLite file so sessions can be queried with plain SQL. The database is a
+//! disposable export: it is rebuilt from scratch every time and never read
+//! back by krep. Requires the `analytics` feature.
+//!
+//! Tables: `sessions` (one row per session, with local date/hour and total
+//! reps precomputed), `session_tags` and `definitions`. Views:
+//! `daily_summary` and `definition_summary`.
+
+use crate::{Catalog, LocalTz, MicrodoseSession, Result};
+use chrono::Timelike;
+use rusqlite::{params, Connection};
+use std::collections::HashSet;
+use std::path::Path;
+
+/// Default file name of the analytics database within the data directory
+pub const ANALYTICS_DB_FILE: &str = "analytics.sqlite";
+
+const SCHEMA: &str = "
+CREATE TABLE definitions (
+    id TEXT PRIMARY KEY,
+    name TEXT NOT NULL,
+    category TEXT NOT NULL
+);
+
+CREATE TABLE sessions (
+    id TEXT PRIMARY KEY,
+    definition_id TEXT NOT NULL,
+    category TEXT,
+    performed_at TEXT NOT NULL,
+    local_date TEXT NOT NULL,
+    local_hour INTEGER NOT NULL,
+    duration_seconds INTEGER,
+    perceived_rpe INTEGER,
+    avg_hr INTEGER,
+    max_hr INTEGER,
+    hr_recovery INTEGER,
+    hr_series_path TEXT,
+    paused_seconds INTEGER,
+    total_reps INTEGER,
+    metrics_realized TEXT,
+    source TEXT NOT NULL
+);
+
+CREATE TABLE session_tags (
+    session_id TEXT NOT NULL REFERENCES sessions(id),
+    tag TEXT NOT NULL
+);
+
+CREATE INDEX idx_sessions_performed_at ON sessions(performed_at);
+CREATE INDEX idx_sessions_local_date ON sessions(local_date);
+CREATE INDEX idx_sessions_definition ON sessions(definition_id);
+CREATE INDEX idx_session_tags_tag ON session_tags(tag);
+
+CREATE VIEW daily_summary AS
+SELECT local_date,
+       COUNT(*) AS sessions,
+       COALESCE(SUM(duration_seconds), 0) / 60 AS minutes,
+       SUM(total_reps) AS total_reps,
+       AVG(perceived_rpe) AS avg_rpe
+FROM sessions
+GROUP BY local_date
+ORDER BY local_date;
+
+CREATE VIEW definition_summary AS
+SELECT s.definition_id,
+       d.name,
+       s.category,
+       COUNT(*) AS sessions,
+       SUM(s.total_reps) AS total_reps,
+       MAX(s.total_reps) AS best_reps,
+       MIN(s.performed_at) AS first_at,
+       MAX(s.performed_at) AS last_at
+FROM sessions s
+LEFT JOIN definitions d ON d.id = s.definition_id
+GROUP BY s.definition_id
+ORDER BY sessions DESC;
+";
+
+/// Build the analytics database at `db_path` from the CSV archive and WAL
+///
+/// Any existing file is replaced. The database is written to a temp file in
+/// the same directory first, so a failed build leaves the old one intact.
+/// Sessions in both sources (an interrupted rollup) are stored once, from
+/// the WAL (read under its lock, taken within `timeout`). Returns the number
+/// of sessions written.
+pub fn build(
+    wal_path: &Path,
+    csv_path: &Path,
+    db_path: &Path,
+    catalog: &Catalog,
+    tz: &LocalTz,
+    timeout: std::time::Duration,
+) -> Result<usize> {
+    let mut sessions: Vec<(MicrodoseSession, &str)> = crate::wal::read_sessions(wal_path, timeout)?
+        .into_iter()
+        .map(|s| (s, "wal"))
+        .collect();
+    if csv_path.exists() {
+        sessions.extend(
+            crate::history::load_sessions_from_csv_internal(csv_path, None)?
+                .into_iter()
+                .map(|s| (s, "csv")),
+        );
+    }
+
+    let dir = db_path.parent().unwrap_or_else(|| Path::new("."));
+    std::fs::create_dir_all(dir)?;
+    let temp = tempfile::NamedTempFile::new_in(dir)?;
+
+    let mut conn = Connection::open(temp.path())?;
+    conn.execute_batch(SCHEMA)?;
+
+    let mut written = 0;
+    let tx = conn.transaction()?;
+    {
+        let mut insert_definition =
+            tx.prepare("INSERT INTO definitions (id, name, category) VALUES (?1, ?2, ?3)")?;
+        for definition in catalog.definitions() {
+            insert_definition.execute(params![
+                definition.id,
+                definition.name,
+                definition.category.as_str()
+            ])?;
+        }
+
+        let mut insert_session = tx.prepare(
+            "INSERT INTO sessions (id, definition_id, category, performed_at, local_date,
+                 local_hour, duration_seconds, perceived_rpe, avg_hr, max_hr, hr_recovery,
+                 hr_series_path, paused_seconds, total_reps, metrics_realized, source)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)",
+        )?;
+        let mut insert_tag =
+            tx.prepare("INSERT INTO session_tags (session_id, tag) VALUES (?1, ?2)")?;
+
+        let mut seen = HashSet::new();
+        for (session, source) in &sessions {
+            if !seen.insert(session.id) {
+                continue;
+            }
+            written += 1;
+            let id = session.id.to_string();
+            let category = catalog
+                .definition(&session.definition_id)
+                .map(|d| d.category.as_str());
+            let metrics_realized = if session.metrics_realized.is_empty() {
+                None
+            } else {
+                Some(serde_json::to_string(&session.metrics_realized)?)
+            };
+            insert_session.execute(params![
+                id,
+                session.definition_id,
+                category,
+                session.performed_at.to_rfc3339(),
+                tz.date_of(session.performed_at).to_string(),
+                tz.time_of(session.performed_at).hour(),
+                session.actual_duration_seconds,
+                session.perceived_rpe,
+                session.avg_hr,
+                session.max_hr,
+                session.hr_recovery,
+                session.hr_series_path,
+                session.paused_seconds,
+                session
+                    .reps_per_set()
+                    .map(|per_set| per_set.iter().sum::<i32>()),
+                metrics_realized,
+                source,
+            ])?;
+            for tag in &session.tags {
+                insert_tag.execute(params![id, tag])?;
+            }
+        }
+    }
+    tx.commit()?;
+    conn.close().map_err(|(_, e)| e)?;
+
+    temp.persist(db_path)
+        .map_err(|e| crate::Error::Io(e.error))?;
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::build_default_catalog;
+    use crate::wal::{JsonlSink, SessionSink};
+    use chrono::{TimeZone, Utc};
+
+    fn session(def_id: &str, hour: u32, tags: &[&str]) -> MicrodoseSession {
+        MicrodoseSession {
+            actual_duration_seconds: Some(300),
+            metrics_realized: vec![crate::RealizedMetric::Reps {
+                key: "reps".into(),
+                per_set: vec![5, 5, 4],
+            }],
+            perceived_rpe: Some(7),
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            ..MicrodoseSession::new(
+                def_id,
+                Utc.with_ymd_and_hms(2024, 3, 4, hour, 0, 0).unwrap(),
+            )
+        }
+    }
+
+    #[test]
+    fn test_build_tables_and_views() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let wal_path = temp_dir.path().join("wal").join("microdose_sessions.wal");
+        let csv_path = temp_dir.path().join("sessions.csv");
+        let db_path = temp_dir.path().join(ANALYTICS_DB_FILE);
+        let tz = LocalTz::from_name(Some("UTC")).unwrap();
+
+        let archived = session("emom_burpee_5m", 8, &[]);
+        let mut sink = JsonlSink::new(&wal_path);
+        sink.append(&archived).unwrap();
+        crate::csv_rollup::wal_to_csv_and_archive(
+            &wal_path,
+            &csv_path,
+            crate::lock::DEFAULT_TIMEOUT,
+        )
+        .unwrap();
+
+        let mut sink = JsonlSink::new(&wal_path);
+        sink.append(&session("emom_burpee_5m", 18, &["travel"]))
+            .unwrap();
+        // Same session in both sources is only stored once
+        sink.append(&archived).unwrap();
+
+        let catalog = build_default_catalog();
+        assert_eq!(
+            build(
+                &wal_path,
+                &csv_path,
+                &db_path,
+                &catalog,
+                &tz,
+                crate::lock::DEFAULT_TIMEOUT
+            )
+            .unwrap(),
+            2
+        );
+
+        let conn = Connection::open(&db_path).unwrap();
+        let (sessions, total_reps): (i64, i64) = conn
+            .query_row(
+                "SELECT sessions, total_reps FROM daily_summary WHERE local_date = '2024-03-04'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!((sessions, total_reps), (2, 28));
+
+        let (name, best): (String, i64) = conn
+            .query_row(
+                "SELECT name, best_reps FROM definition_summary",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!((name.as_str(), best), ("5-Min EMOM: Burpees", 14));
+
+        let hour: i64 = conn
+            .query_row(
+                "SELECT s.local_hour FROM sessions s
+                 JOIN session_tags t ON t.session_id = s.id WHERE t.tag = 'travel'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(hour, 18);
+
+        // Rebuilding replaces the file rather than appending
+        build(
+            &wal_path,
+            &csv_path,
+            &db_path,
+            &catalog,
+            &tz,
+            crate::lock::DEFAULT_TIMEOUT,
+        )
+        .unwrap();
+        let conn = Connection::open(&db_path).unwrap();
+        let count: i64 = conn
+            .query_row("SELECT COUNT(*) FROM sessions", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 2);
+    }
+}
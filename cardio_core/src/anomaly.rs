@@ -0,0 +1,230 @@
+//! Data anomaly checks.
+//!
+//! Sessions can arrive from hand-edited WALs, old archives, the FFI or a
+//! watch export, so history loads and `krep rollup`/`krep doctor` check for
+//! values that can't be real: repeated session IDs, timestamps in the future,
+//! completions before starts and impossible heart rates. Anomalies are always
+//! reported; with `[data] exclude_anomalies = true` they are also left out of
+//! history loads.
+
+use crate::{MicrodoseSession, SessionKind};
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fmt;
+use uuid::Uuid;
+
+/// Allowance for clock skew between devices before a timestamp counts as future
+pub const FUTURE_TOLERANCE_MINUTES: i64 = 5;
+
+/// Lowest and highest heart rate (bpm) accepted as a real reading
+pub const HR_RANGE: (u8, u8) = (25, 230);
+
+/// What is wrong with a session
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum AnomalyKind {
+    /// Session ID already seen earlier in the same source
+    DuplicateId,
+    /// Performed (or completed) after now
+    FutureTimestamp,
+    /// Completed before it started
+    NegativeDuration { seconds: i64 },
+    /// HR outside [`HR_RANGE`], or an average above the max
+    ImpossibleHr { field: &'static str, bpm: u8 },
+}
+
+impl fmt::Display for AnomalyKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AnomalyKind::DuplicateId => write!(f, "duplicate session ID"),
+            AnomalyKind::FutureTimestamp => write!(f, "timestamp in the future"),
+            AnomalyKind::NegativeDuration { seconds } => {
+                write!(f, "completed {}s before it started", -seconds)
+            }
+            AnomalyKind::ImpossibleHr { field, bpm } => {
+                write!(f, "impossible {} of {} bpm", field, bpm)
+            }
+        }
+    }
+}
+
+/// A problem found in one session
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Anomaly {
+    pub session_id: Uuid,
+    pub definition_id: String,
+    pub performed_at: DateTime<Utc>,
+    #[serde(flatten)]
+    pub kind: AnomalyKind,
+}
+
+impl fmt::Display for Anomaly {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ({} at {}): {}",
+            self.session_id,
+            self.definition_id,
+            self.performed_at.to_rfc3339(),
+            self.kind
+        )
+    }
+}
+
+/// Anomalies in a single session, ignoring duplicates
+fn check_session(session: &MicrodoseSession, now: DateTime<Utc>) -> Vec<AnomalyKind> {
+    let mut kinds = Vec::new();
+
+    let latest = now + Duration::minutes(FUTURE_TOLERANCE_MINUTES);
+    if session.performed_at > latest || session.completed_at.is_some_and(|t| t > latest) {
+        kinds.push(AnomalyKind::FutureTimestamp);
+    }
+
+    if let (Some(started), Some(completed)) = (session.started_at, session.completed_at) {
+        let seconds = (completed - started).num_seconds();
+        if seconds < 0 {
+            kinds.push(AnomalyKind::NegativeDuration { seconds });
+        }
+    }
+
+    let (low, high) = HR_RANGE;
+    for (field, bpm) in [("avg HR", session.avg_hr), ("max HR", session.max_hr)] {
+        if let Some(bpm) = bpm.filter(|bpm| !(low..=high).contains(bpm)) {
+            kinds.push(AnomalyKind::ImpossibleHr { field, bpm });
+        }
+    }
+    if let (Some(avg), Some(max)) = (session.avg_hr, session.max_hr) {
+        if avg > max {
+            kinds.push(AnomalyKind::ImpossibleHr {
+                field: "avg HR above max",
+                bpm: avg,
+            });
+        }
+    }
+    if let Some(drop) = session.hr_recovery.filter(|drop| *drop > high - low) {
+        kinds.push(AnomalyKind::ImpossibleHr {
+            field: "HR recovery",
+            bpm: drop,
+        });
+    }
+
+    kinds
+}
+
+/// Check sessions from one source (WAL or CSV archive), in file order
+///
+/// The first copy of a repeated ID is treated as the original; later copies
+/// are reported as duplicates.
+pub fn check_sessions<'a>(
+    sessions: impl IntoIterator<Item = &'a MicrodoseSession>,
+    now: DateTime<Utc>,
+) -> Vec<Anomaly> {
+    let mut seen = HashSet::new();
+    let mut anomalies = Vec::new();
+    for session in sessions {
+        let mut kinds = check_session(session, now);
+        if !seen.insert(session.id) {
+            kinds.insert(0, AnomalyKind::DuplicateId);
+        }
+        anomalies.extend(kinds.into_iter().map(|kind| Anomaly {
+            session_id: session.id,
+            definition_id: session.definition_id.clone(),
+            performed_at: session.performed_at,
+            kind,
+        }));
+    }
+    anomalies
+}
+
+/// Drop sessions with anomalies (keeping the first copy of a duplicated ID)
+pub fn exclude_anomalies(sessions: Vec<SessionKind>, now: DateTime<Utc>) -> Vec<SessionKind> {
+    let mut seen = HashSet::new();
+    sessions
+        .into_iter()
+        .filter(|session| match session.as_real() {
+            Some(real) => seen.insert(real.id) && check_session(real, now).is_empty(),
+            None => true,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session() -> MicrodoseSession {
+        let now = Utc::now();
+        MicrodoseSession {
+            started_at: Some(now - Duration::minutes(5)),
+            completed_at: Some(now),
+            actual_duration_seconds: Some(300),
+            avg_hr: Some(140),
+            max_hr: Some(165),
+            hr_recovery: Some(30),
+            ..MicrodoseSession::new("emom_burpee_5m", now)
+        }
+    }
+
+    #[test]
+    fn test_clean_session_has_no_anomalies() {
+        let clean = session();
+        assert!(check_sessions([&clean], Utc::now()).is_empty());
+    }
+
+    #[test]
+    fn test_detects_each_kind() {
+        let now = Utc::now();
+        let original = session();
+        let duplicate = original.clone();
+
+        let mut future = session();
+        future.performed_at = now + Duration::days(1);
+
+        let mut negative = session();
+        negative.completed_at = negative.started_at.map(|t| t - Duration::seconds(30));
+
+        let mut bad_hr = session();
+        bad_hr.avg_hr = Some(250);
+        bad_hr.max_hr = Some(180);
+
+        let sessions = [original, duplicate, future, negative, bad_hr];
+        let kinds: Vec<AnomalyKind> = check_sessions(&sessions, now)
+            .into_iter()
+            .map(|a| a.kind)
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                AnomalyKind::DuplicateId,
+                AnomalyKind::FutureTimestamp,
+                AnomalyKind::NegativeDuration { seconds: -30 },
+                AnomalyKind::ImpossibleHr {
+                    field: "avg HR",
+                    bpm: 250
+                },
+                AnomalyKind::ImpossibleHr {
+                    field: "avg HR above max",
+                    bpm: 250
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_exclude_anomalies() {
+        let now = Utc::now();
+        let original = session();
+        let mut future = session();
+        future.performed_at = now + Duration::hours(2);
+
+        let sessions = vec![
+            SessionKind::Real(original.clone()),
+            SessionKind::Real(original.clone()),
+            SessionKind::Real(future),
+        ];
+        let kept = exclude_anomalies(sessions, now);
+        assert_eq!(kept.len(), 1);
+        assert_eq!(kept[0].as_real().unwrap().id, original.id);
+    }
+}
@@ -0,0 +1,452 @@
+//! proptest [`Arbitrary`] implementations for the core types.
+//!
+//! With the `proptest` feature, `any::<MicrodoseSession>()`,
+//! `any::<MetricSpec>()`, `any::<ProgressionState>()` and `any::<Catalog>()`
+//! generate values the rest of the crate accepts: sessions the WAL and CSV
+//! archive can store (normalized tags, plausible RPE and heart rates), rep
+//! metrics with `min <= default <= max`, and catalogs that pass
+//! [`Catalog::validate`] (every block's movement exists, every category has
+//! a definition). That keeps property tests on persistence round-trips and
+//! engine invariants about the property rather than about filtering out
+//! malformed input.
+
+use crate::{
+    BandSpec, BurpeeStyle, Catalog, IntervalScheme, MetricSpec, MicrodoseBlock, MicrodoseCategory,
+    MicrodoseDefinition, MicrodoseSession, Movement, MovementKind, MovementStyle, ProgressionState,
+    RealizedMetric, RepLadder,
+};
+use chrono::{DateTime, Utc};
+use proptest::prelude::*;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// Lowercase identifier, e.g. a metric key or definition ID suffix
+fn key() -> impl Strategy<Value = String> {
+    "[a-z][a-z0-9_]{0,11}"
+}
+
+/// A whole second between 2020 and 2035
+fn timestamp() -> impl Strategy<Value = DateTime<Utc>> {
+    (1_577_836_800i64..2_051_222_400).prop_map(|secs| DateTime::from_timestamp(secs, 0).unwrap())
+}
+
+/// Tags as [`crate::normalize_tags`] leaves them
+fn tags() -> impl Strategy<Value = Vec<String>> {
+    prop::collection::vec("[a-z][a-z0-9-]{0,9}", 0..4).prop_map(crate::normalize_tags)
+}
+
+impl Arbitrary for MicrodoseCategory {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        prop::sample::select(MicrodoseCategory::ALL.to_vec()).boxed()
+    }
+}
+
+impl Arbitrary for MovementKind {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        prop::sample::select(vec![
+            MovementKind::KettlebellSwing,
+            MovementKind::Burpee,
+            MovementKind::Pullup,
+            MovementKind::MobilityDrill,
+            MovementKind::CoreDrill,
+            MovementKind::StairWalk,
+            MovementKind::Breathing,
+            MovementKind::WarmupDrill,
+            MovementKind::Squat,
+        ])
+        .boxed()
+    }
+}
+
+impl Arbitrary for MovementStyle {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        prop_oneof![
+            Just(MovementStyle::None),
+            prop::sample::select(vec![
+                BurpeeStyle::FourCount,
+                BurpeeStyle::SixCount,
+                BurpeeStyle::SixCountTwoPump,
+                BurpeeStyle::Seal,
+            ])
+            .prop_map(MovementStyle::Burpee),
+            prop_oneof![
+                Just(BandSpec::None),
+                "[a-z]{3,8}".prop_map(BandSpec::NamedColour),
+            ]
+            .prop_map(MovementStyle::Band),
+        ]
+        .boxed()
+    }
+}
+
+impl Arbitrary for RealizedMetric {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        prop_oneof![
+            (key(), prop::collection::vec(0..50i32, 1..11))
+                .prop_map(|(key, per_set)| RealizedMetric::Reps { key, per_set }),
+            (key(), "[a-z]{3,8}").prop_map(|(key, value)| RealizedMetric::Band { key, value }),
+        ]
+        .boxed()
+    }
+}
+
+/// A rep metric's `(key, default, min, max, step, progressable)`, with
+/// `min <= default <= max`
+#[allow(clippy::type_complexity)]
+fn rep_range() -> impl Strategy<Value = (String, i32, i32, i32, i32, bool)> {
+    (key(), 1..10i32, 0..10i32, 0..20i32, 1..4i32, any::<bool>()).prop_map(
+        |(key, min, above_min, range, step, progressable)| {
+            let max = min + range;
+            let default = min + above_min.min(range);
+            (key, default, min, max, step, progressable)
+        },
+    )
+}
+
+impl Arbitrary for MetricSpec {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        prop_oneof![
+            rep_range().prop_map(
+                |(key, default, min, max, step, progressable)| MetricSpec::Reps {
+                    key,
+                    default,
+                    min,
+                    max,
+                    step,
+                    progressable,
+                }
+            ),
+            rep_range().prop_map(|(key, default, min, max, step, progressable)| {
+                MetricSpec::RepsPerSide {
+                    key,
+                    default,
+                    min,
+                    max,
+                    step,
+                    progressable,
+                }
+            }),
+            key().prop_map(|key| MetricSpec::AmrapReps { key }),
+            (key(), "[a-z]{3,8}", any::<bool>()).prop_map(|(key, default, progressable)| {
+                MetricSpec::Band {
+                    key,
+                    default,
+                    progressable,
+                }
+            }),
+        ]
+        .boxed()
+    }
+}
+
+impl Arbitrary for ProgressionState {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        (
+            1..50i32,
+            any::<MovementStyle>(),
+            0..20u32,
+            prop::option::of(timestamp()),
+        )
+            .prop_map(|(reps, style, level, last_upgraded)| ProgressionState {
+                reps,
+                style,
+                level,
+                last_upgraded,
+            })
+            .boxed()
+    }
+}
+
+impl Arbitrary for MicrodoseSession {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        let when = (
+            timestamp(),
+            prop::option::of(0..3_600u32),
+            any::<bool>(),
+            prop::option::of(1..600u32),
+        );
+        let effort = (
+            prop::collection::vec(any::<RealizedMetric>(), 0..3),
+            prop::option::of(1..=10u8),
+            prop::option::of(60..=200u8),
+            prop::option::of(0..=60u8),
+        );
+        (
+            any::<u128>(),
+            key(),
+            when,
+            effort,
+            prop::option::of("hr/[a-z0-9]{8}\\.csv"),
+            tags(),
+        )
+            .prop_map(
+                |(
+                    id,
+                    def_suffix,
+                    (performed_at, duration, timed, paused),
+                    effort,
+                    hr_series_path,
+                    tags,
+                )| {
+                    let (metrics_realized, perceived_rpe, avg_hr, hr_recovery) = effort;
+                    // Timed sessions started `duration` seconds (plus any
+                    // pause) before they were performed
+                    let paused = paused.filter(|_| timed && duration.is_some());
+                    let (started_at, completed_at) = match duration.filter(|_| timed) {
+                        Some(seconds) => (
+                            Some(
+                                performed_at
+                                    - chrono::Duration::seconds(
+                                        (seconds + paused.unwrap_or(0)).into(),
+                                    ),
+                            ),
+                            Some(performed_at),
+                        ),
+                        None => (None, None),
+                    };
+                    MicrodoseSession {
+                        id: Uuid::from_u128(id),
+                        definition_id: format!("def_{}", def_suffix),
+                        performed_at,
+                        started_at,
+                        completed_at,
+                        actual_duration_seconds: duration,
+                        metrics_realized,
+                        perceived_rpe,
+                        avg_hr,
+                        max_hr: avg_hr.map(|avg| avg.saturating_add(15)),
+                        hr_recovery,
+                        hr_series_path,
+                        paused_seconds: paused,
+                        tags,
+                    }
+                },
+            )
+            .boxed()
+    }
+}
+
+/// A block on one of `movements` movements, with metrics that suit its
+/// interval or ladder
+fn block(movements: usize) -> impl Strategy<Value = MicrodoseBlock> {
+    let structure = prop_oneof![
+        Just((None, None)),
+        (1..120u32, 0..60u32, 1..10u32).prop_map(|(work_seconds, rest_seconds, rounds)| {
+            let interval = IntervalScheme {
+                work_seconds,
+                rest_seconds,
+                rounds,
+            };
+            (Some(interval), None)
+        }),
+        (1..3i32, any::<bool>()).prop_map(|(step, descending)| {
+            let ladder = RepLadder {
+                start: 1,
+                step,
+                descending,
+            };
+            (None, Some(ladder))
+        }),
+    ];
+    (
+        0..movements,
+        any::<MovementStyle>(),
+        10..300u32,
+        structure,
+        prop::collection::vec(any::<MetricSpec>(), 0..3),
+    )
+        .prop_map(
+            |(movement, movement_style, duration_hint_seconds, (interval, ladder), mut metrics)| {
+                // A ladder peaks at its rep metric, so it needs one first
+                if ladder.is_some() && metrics.iter().find_map(|m| m.default_reps()).is_none() {
+                    metrics.insert(
+                        0,
+                        MetricSpec::Reps {
+                            key: "reps".into(),
+                            default: 3,
+                            min: 1,
+                            max: 10,
+                            step: 1,
+                            progressable: true,
+                        },
+                    );
+                }
+                MicrodoseBlock {
+                    movement_id: format!("m{}", movement),
+                    movement_style,
+                    duration_hint_seconds,
+                    interval,
+                    ladder,
+                    metrics,
+                }
+            },
+        )
+}
+
+/// Definition `index` in `category`, on one of `movements` movements
+fn definition(
+    index: usize,
+    category: MicrodoseCategory,
+    movements: usize,
+) -> impl Strategy<Value = MicrodoseDefinition> {
+    (
+        "[A-Z][a-z]{2,10}( [A-Z][a-z]{2,10})?",
+        any::<bool>(),
+        prop::collection::vec(block(movements), 1..3),
+        0..120u32,
+    )
+        .prop_map(move |(name, gtg_friendly, blocks, spare)| {
+            let mut definition = MicrodoseDefinition {
+                id: format!("d{}", index),
+                name,
+                category: category.clone(),
+                suggested_duration_seconds: 0,
+                gtg_friendly,
+                blocks,
+                reference_url: None,
+            };
+            definition.suggested_duration_seconds = definition.blocks_seconds() + spare;
+            definition
+        })
+}
+
+impl Arbitrary for Catalog {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    /// 1-8 movements and a definition in every category plus up to 6 more
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        (
+            1..9usize,
+            prop::collection::vec(any::<MicrodoseCategory>(), 0..7),
+        )
+            .prop_flat_map(|(movement_count, extra)| {
+                let movements = prop::collection::vec(
+                    (
+                        "[A-Z][a-z]{2,10}",
+                        any::<MovementKind>(),
+                        any::<MovementStyle>(),
+                        prop::collection::vec("[a-z_]{3,10}", 0..3),
+                    ),
+                    movement_count,
+                );
+                let definitions: Vec<_> = MicrodoseCategory::ALL
+                    .into_iter()
+                    .chain(extra)
+                    .enumerate()
+                    .map(|(index, category)| definition(index, category, movement_count))
+                    .collect();
+                (movements, definitions)
+            })
+            .prop_map(|(movements, definitions)| {
+                let movements: HashMap<String, Movement> = movements
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, (name, kind, default_style, tags))| {
+                        let id = format!("m{}", index);
+                        let movement = Movement {
+                            id: id.clone(),
+                            name,
+                            kind,
+                            default_style,
+                            tags,
+                            reference_url: None,
+                        };
+                        (id, movement)
+                    })
+                    .collect();
+                let microdoses = definitions
+                    .into_iter()
+                    .map(|definition| (definition.id.clone(), definition))
+                    .collect();
+                Catalog::new(movements, microdoses)
+            })
+            .boxed()
+    }
+}
+
+#[cfg(all(test, feature = "fs"))]
+mod tests {
+    use super::*;
+    use crate::{JsonlSink, SessionSink, UserContext, UserMicrodoseState};
+
+    proptest! {
+        #[test]
+        fn test_arbitrary_catalogs_validate(catalog in any::<Catalog>()) {
+            prop_assert_eq!(catalog.validate(), Vec::<String>::new());
+        }
+
+        #[test]
+        fn test_sessions_round_trip_through_the_wal(
+            sessions in prop::collection::vec(any::<MicrodoseSession>(), 1..5)
+        ) {
+            let temp_dir = tempfile::tempdir().unwrap();
+            let wal = temp_dir.path().join("sessions.wal");
+            let mut sink = JsonlSink::new(&wal);
+            for session in &sessions {
+                sink.append(session).unwrap();
+            }
+
+            let read = crate::wal::read_sessions(&wal, crate::lock::DEFAULT_TIMEOUT).unwrap();
+            prop_assert_eq!(
+                serde_json::to_value(&read).unwrap(),
+                serde_json::to_value(&sessions).unwrap()
+            );
+        }
+
+        #[test]
+        fn test_progressions_round_trip_through_the_state_file(
+            progressions in prop::collection::hash_map(key(), any::<ProgressionState>(), 0..5)
+        ) {
+            let temp_dir = tempfile::tempdir().unwrap();
+            let path = temp_dir.path().join("state.json");
+            let state = UserMicrodoseState {
+                progressions,
+                ..Default::default()
+            };
+            state.save(&path, crate::lock::DEFAULT_TIMEOUT).unwrap();
+
+            let loaded = UserMicrodoseState::load(&path, crate::lock::DEFAULT_TIMEOUT).unwrap();
+            prop_assert_eq!(
+                serde_json::to_value(&loaded.progressions).unwrap(),
+                serde_json::to_value(&state.progressions).unwrap()
+            );
+        }
+
+        #[test]
+        fn test_prescriptions_come_from_the_catalog(
+            catalog in any::<Catalog>(),
+            category in prop::option::of(any::<MicrodoseCategory>()),
+            now in timestamp(),
+        ) {
+            let ctx = UserContext::builder(now).build();
+            let prescribed = crate::prescribe_next(&catalog, &ctx, category.clone()).unwrap();
+
+            prop_assert!(catalog.definition(&prescribed.definition.id).is_some());
+            if let Some(category) = category {
+                prop_assert_eq!(prescribed.definition.category, category);
+            }
+        }
+    }
+}
@@ -0,0 +1,167 @@
+//! Async variants of the persistence and history APIs.
+//!
+//! Enabled with the `tokio` feature. Each function runs its blocking
+//! counterpart on tokio's blocking thread pool, so async consumers (REST
+//! handlers, daemons) don't have to wrap file IO in `spawn_blocking` themselves.
+//! Like their counterparts, they wait up to `timeout` for file locks.
+
+use crate::wal::SessionSink;
+#[cfg(feature = "config-toml")]
+use crate::Config;
+use crate::{
+    Error, ExternalStrengthSignal, JsonlSink, MicrodoseSession, Result, SessionKind,
+    UserMicrodoseState,
+};
+use chrono::{DateTime, Utc};
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Run a blocking closure on the tokio blocking pool and flatten the join error
+async fn run_blocking<T, F>(f: F) -> Result<T>
+where
+    F: FnOnce() -> Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| Error::Other(format!("Blocking task failed: {}", e)))?
+}
+
+/// Append a session to the WAL (async variant of [`JsonlSink::append`])
+pub async fn append_session(
+    wal_path: impl Into<PathBuf>,
+    session: MicrodoseSession,
+    timeout: Duration,
+) -> Result<()> {
+    let wal_path = wal_path.into();
+    run_blocking(move || {
+        JsonlSink::new(wal_path)
+            .lock_timeout(timeout)
+            .append(&session)
+    })
+    .await
+}
+
+/// Read all sessions from a WAL file (async variant of [`crate::wal::read_sessions`])
+pub async fn read_sessions(
+    wal_path: impl Into<PathBuf>,
+    timeout: Duration,
+) -> Result<Vec<MicrodoseSession>> {
+    let wal_path = wal_path.into();
+    run_blocking(move || crate::wal::read_sessions(&wal_path, timeout)).await
+}
+
+/// Read WAL sessions since a cutoff (async variant of [`crate::wal::read_sessions_since`])
+pub async fn read_sessions_since(
+    wal_path: impl Into<PathBuf>,
+    cutoff: DateTime<Utc>,
+    timeout: Duration,
+) -> Result<Vec<MicrodoseSession>> {
+    let wal_path = wal_path.into();
+    run_blocking(move || crate::wal::read_sessions_since(&wal_path, cutoff, timeout)).await
+}
+
+/// Load recent sessions from WAL and CSV (async variant of [`crate::load_recent_sessions`])
+pub async fn load_recent_sessions(
+    wal_path: impl Into<PathBuf>,
+    csv_path: impl Into<PathBuf>,
+    days: i64,
+    timeout: Duration,
+) -> Result<Vec<SessionKind>> {
+    let wal_path = wal_path.into();
+    let csv_path = csv_path.into();
+    run_blocking(move || crate::load_recent_sessions(&wal_path, &csv_path, days, timeout)).await
+}
+
+/// Load user state (async variant of [`UserMicrodoseState::load`])
+pub async fn load_state(
+    state_path: impl Into<PathBuf>,
+    timeout: Duration,
+) -> Result<UserMicrodoseState> {
+    let state_path = state_path.into();
+    run_blocking(move || UserMicrodoseState::load(&state_path, timeout)).await
+}
+
+/// Save user state atomically (async variant of [`UserMicrodoseState::save`])
+pub async fn save_state(
+    state: UserMicrodoseState,
+    state_path: impl Into<PathBuf>,
+    timeout: Duration,
+) -> Result<()> {
+    let state_path = state_path.into();
+    run_blocking(move || state.save(&state_path, timeout)).await
+}
+
+/// Load the external strength signal (async variant of [`crate::load_external_strength`])
+pub async fn load_external_strength(
+    signal_path: impl Into<PathBuf>,
+) -> Result<Option<ExternalStrengthSignal>> {
+    let signal_path = signal_path.into();
+    run_blocking(move || crate::load_external_strength(&signal_path)).await
+}
+
+/// Load configuration from a path (async variant of [`Config::load_from`])
+#[cfg(feature = "config-toml")]
+pub async fn load_config(config_path: impl Into<PathBuf>) -> Result<Config> {
+    let config_path = config_path.into();
+    run_blocking(move || Config::load_from(&config_path)).await
+}
+
+/// Roll up the WAL into CSV (async variant of [`crate::csv_rollup::wal_to_csv_and_archive`])
+#[cfg(feature = "csv-archive")]
+pub async fn wal_to_csv_and_archive(
+    wal_path: impl Into<PathBuf>,
+    csv_path: impl Into<PathBuf>,
+    timeout: Duration,
+) -> Result<usize> {
+    let wal_path = wal_path.into();
+    let csv_path = csv_path.into();
+    run_blocking(move || crate::csv_rollup::wal_to_csv_and_archive(&wal_path, &csv_path, timeout))
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_async_append_and_load() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let wal_path = temp_dir.path().join("sessions.wal");
+        let csv_path = temp_dir.path().join("sessions.csv");
+
+        let session = MicrodoseSession::new("emom_burpee_5m", Utc::now());
+        let session_id = session.id;
+        append_session(&wal_path, session, crate::lock::DEFAULT_TIMEOUT)
+            .await
+            .unwrap();
+
+        let sessions = load_recent_sessions(&wal_path, &csv_path, 7, crate::lock::DEFAULT_TIMEOUT)
+            .await
+            .unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].as_real().unwrap().id, session_id);
+    }
+
+    #[tokio::test]
+    async fn test_async_state_roundtrip() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let state_path = temp_dir.path().join("state.json");
+
+        let state = UserMicrodoseState {
+            last_mobility_def_id: Some("mobility_hip_cars".into()),
+            ..Default::default()
+        };
+        save_state(state, &state_path, crate::lock::DEFAULT_TIMEOUT)
+            .await
+            .unwrap();
+
+        let loaded = load_state(&state_path, crate::lock::DEFAULT_TIMEOUT)
+            .await
+            .unwrap();
+        assert_eq!(
+            loaded.last_mobility_def_id,
+            Some("mobility_hip_cars".into())
+        );
+    }
+}
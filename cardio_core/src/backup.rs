@@ -0,0 +1,342 @@
+//! Whole-data-directory backup archives.
+//!
+//! [`write_archive`] packs everything needed to move krep to another machine
+//! into one zstd-compressed tarball: every file in the data directory (WAL,
+//! processed WALs, CSV archive, state, logs, attached HR series), any store
+//! configured outside it, and the config file with its drop-ins. A
+//! [`Manifest`] with each file's size and SHA-256 is the first entry, so a
+//! restore can verify the archive before touching anything.
+//!
+//! Derived files (the CSV byte-offset index, the analytics database) are left
+//! out; krep rebuilds them.
+
+use crate::{FileStorage, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+
+/// Name of the manifest entry
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+/// Version of the archive layout
+pub const FORMAT_VERSION: u32 = 1;
+
+/// Default file name of the analytics database, rebuilt by `krep analytics build`
+const ANALYTICS_DB_FILE: &str = "analytics.sqlite";
+
+/// zstd level: well compressed, still fast for a personal data directory
+const COMPRESSION_LEVEL: i32 = 10;
+
+/// Contents of a backup archive
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Manifest {
+    pub format_version: u32,
+    pub created_at: DateTime<Utc>,
+    /// krep version that wrote the archive
+    pub krep_version: String,
+    /// Data directory the archive was taken from
+    pub data_dir: PathBuf,
+    pub files: Vec<ManifestEntry>,
+}
+
+/// One archived file
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct ManifestEntry {
+    /// Path inside the archive: `data/...` for the data directory,
+    /// `external/<store>/...` for stores configured outside it, `config/...`
+    pub path: String,
+    /// Where the file was read from
+    pub source: PathBuf,
+    pub bytes: u64,
+    /// Hex SHA-256 of the contents
+    pub sha256: String,
+}
+
+impl Manifest {
+    pub fn total_bytes(&self) -> u64 {
+        self.files.iter().map(|f| f.bytes).sum()
+    }
+}
+
+/// Write a backup of `data_dir` (and `storage`'s stores outside it, and the
+/// config at `config_path` with its drop-ins) to `out`
+///
+/// The archive is written to a temp file next to `out` and renamed into
+/// place, so an interrupted export never leaves a truncated backup. The WAL
+/// is read under its shared lock, so a dose logged meanwhile is either
+/// wholly in the archive or not at all.
+pub fn write_archive(
+    out: &Path,
+    data_dir: &Path,
+    storage: &FileStorage,
+    config_path: Option<&Path>,
+    now: DateTime<Utc>,
+) -> Result<Manifest> {
+    let skip = [
+        absolute(&crate::csv_index::index_path(&storage.csv_path))?,
+        absolute(&crate::wal_index::index_path(&storage.wal_path))?,
+        absolute(&data_dir.join(ANALYTICS_DB_FILE))?,
+        absolute(out)?,
+    ];
+
+    let mut sources = Vec::new();
+    if data_dir.is_dir() {
+        for file in files_under(data_dir)? {
+            let relative = file.strip_prefix(data_dir).unwrap_or(&file);
+            sources.push((archive_path("data", relative), file));
+        }
+    }
+    let stores = [
+        ("wal", &storage.wal_path),
+        ("csv", &storage.csv_path),
+        ("state", &storage.state_path),
+        ("strength", &storage.strength_signal_path),
+        ("strength", &storage.strength_history_path),
+        ("readiness", &storage.readiness_signal_path),
+    ];
+    for (store, path) in stores {
+        if path.is_file() && !path.starts_with(data_dir) {
+            let name = path.file_name().map(Path::new).unwrap_or(path);
+            sources.push((
+                archive_path(&format!("external/{}", store), name),
+                path.clone(),
+            ));
+        }
+    }
+    if let Some(config_path) = config_path {
+        let config_dir = config_path.parent().unwrap_or_else(|| Path::new("."));
+        let mut config_files = crate::config::drop_in_files(config_path)?;
+        if config_path.is_file() {
+            config_files.insert(0, config_path.to_path_buf());
+        }
+        for file in config_files {
+            let relative = file.strip_prefix(config_dir).unwrap_or(&file);
+            sources.push((archive_path("config", relative), file));
+        }
+    }
+    let mut kept = Vec::with_capacity(sources.len());
+    for (path, source) in sources {
+        if !skip.contains(&absolute(&source)?) && !is_temp_file(&source) {
+            kept.push((path, source));
+        }
+    }
+
+    let mut contents = Vec::with_capacity(kept.len());
+    let mut files = Vec::with_capacity(kept.len());
+    for (path, source) in kept {
+        let bytes = read_file(&source, storage)?;
+        files.push(ManifestEntry {
+            path,
+            bytes: bytes.len() as u64,
+            sha256: sha256_hex(&bytes),
+            source,
+        });
+        contents.push(bytes);
+    }
+    let manifest = Manifest {
+        format_version: FORMAT_VERSION,
+        created_at: now,
+        krep_version: env!("CARGO_PKG_VERSION").to_string(),
+        data_dir: data_dir.to_path_buf(),
+        files,
+    };
+
+    let dir = out.parent().unwrap_or_else(|| Path::new("."));
+    std::fs::create_dir_all(dir)?;
+    let temp = tempfile::NamedTempFile::new_in(dir)?;
+    let encoder = zstd::Encoder::new(temp.as_file(), COMPRESSION_LEVEL)?;
+    let mut tar = tar::Builder::new(encoder);
+    append(
+        &mut tar,
+        MANIFEST_FILE,
+        &serde_json::to_vec_pretty(&manifest)?,
+        now,
+    )?;
+    for (entry, bytes) in manifest.files.iter().zip(&contents) {
+        append(&mut tar, &entry.path, bytes, now)?;
+    }
+    tar.into_inner()?.finish()?;
+    temp.as_file().sync_all()?;
+    temp.persist(out).map_err(|e| crate::Error::Io(e.error))?;
+
+    tracing::info!(
+        "Wrote backup of {} file(s) to {:?}",
+        manifest.files.len(),
+        out
+    );
+    Ok(manifest)
+}
+
+/// `path` made absolute against the current directory (without touching
+/// the filesystem, so it works for files not yet written)
+fn absolute(path: &Path) -> Result<PathBuf> {
+    if path.is_absolute() {
+        Ok(path.to_path_buf())
+    } else {
+        Ok(std::env::current_dir()?.join(path))
+    }
+}
+
+/// Every regular file under `dir`, sorted
+fn files_under(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        for entry in std::fs::read_dir(&dir)? {
+            let entry = entry?;
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() {
+                pending.push(entry.path());
+            } else if file_type.is_file() {
+                files.push(entry.path());
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Archive path with `/` separators whatever the platform
+fn archive_path(prefix: &str, relative: &Path) -> String {
+    let mut path = prefix.to_string();
+    for component in relative.components() {
+        path.push('/');
+        path.push_str(&component.as_os_str().to_string_lossy());
+    }
+    path
+}
+
+/// Temp files from atomic writes in progress (`.tmpXXXXXX`)
+fn is_temp_file(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|n| n.to_str())
+        .is_some_and(|n| n.starts_with(".tmp"))
+}
+
+fn read_file(path: &Path, storage: &FileStorage) -> Result<Vec<u8>> {
+    if path != storage.wal_path {
+        return Ok(std::fs::read(path)?);
+    }
+    let file = std::fs::File::open(path)?;
+    crate::lock::lock_shared_within(&file, path, storage.lock_timeout)?;
+    let bytes = std::fs::read(path);
+    fs2::FileExt::unlock(&file)?;
+    Ok(bytes?)
+}
+
+fn append<W: std::io::Write>(
+    tar: &mut tar::Builder<W>,
+    path: &str,
+    bytes: &[u8],
+    mtime: DateTime<Utc>,
+) -> Result<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(bytes.len() as u64);
+    header.set_mode(0o600);
+    header.set_mtime(mtime.timestamp().max(0) as u64);
+    header.set_cksum();
+    tar.append_data(&mut header, path, bytes)?;
+    Ok(())
+}
+
+fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{JsonlSink, MicrodoseSession, SessionSink};
+    use std::io::Read;
+
+    /// Entries of an archive as (path, contents)
+    fn read_archive(path: &Path) -> Vec<(String, Vec<u8>)> {
+        let decoder = zstd::Decoder::new(std::fs::File::open(path).unwrap()).unwrap();
+        let mut archive = tar::Archive::new(decoder);
+        archive
+            .entries()
+            .unwrap()
+            .map(|entry| {
+                let mut entry = entry.unwrap();
+                let path = entry.path().unwrap().to_string_lossy().into_owned();
+                let mut bytes = Vec::new();
+                entry.read_to_end(&mut bytes).unwrap();
+                (path, bytes)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_archive_contents_and_manifest() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let data_dir = temp_dir.path().join("data");
+        let elsewhere = temp_dir.path().join("sync");
+        let config_path = temp_dir.path().join("config").join("config.toml");
+        std::fs::create_dir_all(elsewhere.clone()).unwrap();
+        std::fs::create_dir_all(config_path.parent().unwrap().join("config.d")).unwrap();
+        std::fs::write(&config_path, "[time]\ntimezone = \"UTC\"\n").unwrap();
+        std::fs::write(
+            config_path
+                .parent()
+                .unwrap()
+                .join("config.d/10-laptop.toml"),
+            "[sound]\nenabled = false\n",
+        )
+        .unwrap();
+
+        let mut storage = FileStorage::new(&data_dir);
+        storage.csv_path = elsewhere.join("sessions.csv");
+        JsonlSink::new(&storage.wal_path)
+            .append(&MicrodoseSession::new("gtg_pullup_ladder", Utc::now()))
+            .unwrap();
+        std::fs::write(&storage.csv_path, "id\n").unwrap();
+        std::fs::write(crate::csv_index::index_path(&storage.csv_path), "{}").unwrap();
+        std::fs::write(data_dir.join("bodyweight.jsonl"), "{}\n").unwrap();
+        std::fs::write(data_dir.join(ANALYTICS_DB_FILE), "sqlite").unwrap();
+        std::fs::write(data_dir.join(".tmpAbC123"), "partial").unwrap();
+
+        // Written into the data directory itself: must not include itself
+        let out = data_dir.join("backup.tar.zst");
+        let now = Utc::now();
+        let manifest = write_archive(&out, &data_dir, &storage, Some(&config_path), now).unwrap();
+
+        let paths: Vec<&str> = manifest.files.iter().map(|f| f.path.as_str()).collect();
+        assert_eq!(
+            paths,
+            vec![
+                "data/bodyweight.jsonl",
+                "data/wal/microdose_sessions.wal",
+                "external/csv/sessions.csv",
+                "config/config.toml",
+                "config/config.d/10-laptop.toml",
+            ]
+        );
+
+        let entries = read_archive(&out);
+        assert_eq!(entries[0].0, MANIFEST_FILE);
+        let stored: Manifest = serde_json::from_slice(&entries[0].1).unwrap();
+        assert_eq!(stored, manifest);
+        assert_eq!(entries.len(), manifest.files.len() + 1);
+        for ((path, bytes), entry) in entries[1..].iter().zip(&manifest.files) {
+            assert_eq!(path, &entry.path);
+            assert_eq!(bytes.len() as u64, entry.bytes);
+            assert_eq!(sha256_hex(bytes), entry.sha256);
+        }
+        assert_eq!(
+            manifest.files[3].sha256,
+            sha256_hex(b"[time]\ntimezone = \"UTC\"\n")
+        );
+    }
+
+    #[test]
+    fn test_sha256_hex() {
+        assert_eq!(
+            sha256_hex(b""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+}
@@ -0,0 +1,204 @@
+//! Optional bodyweight log.
+//!
+//! Weigh-ins are appended to their own JSONL file (`bodyweight.jsonl` in the
+//! data directory), separate from the session WAL. The trend helpers are
+//! pure and always available; file access requires the `fs` feature.
+
+#[cfg(feature = "fs")]
+use crate::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "fs")]
+use std::fs::{File, OpenOptions};
+#[cfg(feature = "fs")]
+use std::io::{BufRead, BufReader, Write};
+#[cfg(feature = "fs")]
+use std::path::Path;
+
+/// File name of the bodyweight log within the data directory
+pub const BODYWEIGHT_FILE: &str = "bodyweight.jsonl";
+
+/// A single weigh-in
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct BodyweightEntry {
+    pub measured_at: DateTime<Utc>,
+    pub weight_kg: f64,
+}
+
+/// Bodyweight change over a window of weigh-ins
+#[derive(Clone, Debug, Serialize, PartialEq)]
+pub struct BodyweightTrend {
+    pub entries: usize,
+    pub latest_kg: f64,
+    /// Latest minus earliest weigh-in in the window
+    pub change_kg: f64,
+    /// Least-squares slope; `None` unless the weigh-ins span at least a day
+    pub slope_kg_per_week: Option<f64>,
+    /// Weights in chronological order (for sparklines)
+    pub series_kg: Vec<f64>,
+}
+
+/// Summarize weigh-ins at or after `since`
+///
+/// Returns `None` if there are no weigh-ins in the window.
+pub fn trend(entries: &[BodyweightEntry], since: DateTime<Utc>) -> Option<BodyweightTrend> {
+    let mut window: Vec<&BodyweightEntry> =
+        entries.iter().filter(|e| e.measured_at >= since).collect();
+    window.sort_by_key(|e| e.measured_at);
+
+    let first = window.first()?;
+    let last = window.last()?;
+
+    Some(BodyweightTrend {
+        entries: window.len(),
+        latest_kg: last.weight_kg,
+        change_kg: last.weight_kg - first.weight_kg,
+        slope_kg_per_week: slope_per_week(&window),
+        series_kg: window.iter().map(|e| e.weight_kg).collect(),
+    })
+}
+
+/// Ordinary least-squares slope of weight against time, in kg/week
+fn slope_per_week(window: &[&BodyweightEntry]) -> Option<f64> {
+    let origin = window.first()?.measured_at;
+    if window.last()?.measured_at - origin < chrono::Duration::days(1) {
+        return None;
+    }
+    let points: Vec<(f64, f64)> = window
+        .iter()
+        .map(|e| {
+            let weeks = (e.measured_at - origin).num_seconds() as f64 / (7.0 * 86_400.0);
+            (weeks, e.weight_kg)
+        })
+        .collect();
+
+    let n = points.len() as f64;
+    let mean_x = points.iter().map(|(x, _)| x).sum::<f64>() / n;
+    let mean_y = points.iter().map(|(_, y)| y).sum::<f64>() / n;
+    let var_x: f64 = points.iter().map(|(x, _)| (x - mean_x).powi(2)).sum();
+    if var_x == 0.0 {
+        return None;
+    }
+    let cov: f64 = points
+        .iter()
+        .map(|(x, y)| (x - mean_x) * (y - mean_y))
+        .sum();
+    Some(cov / var_x)
+}
+
+/// Append a weigh-in to the bodyweight log (with an exclusive lock, taken
+/// within `timeout`)
+#[cfg(feature = "fs")]
+pub fn append_bodyweight(
+    path: &Path,
+    entry: &BodyweightEntry,
+    timeout: std::time::Duration,
+) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    crate::lock::lock_exclusive_within(&file, path, timeout)?;
+
+    let mut writer = std::io::BufWriter::new(&file);
+    let line = serde_json::to_string(entry)?;
+    writer.write_all(line.as_bytes())?;
+    writer.write_all(b"\n")?;
+    writer.flush()?;
+
+    fs2::FileExt::unlock(&file)?;
+    tracing::debug!("Logged bodyweight {} kg", entry.weight_kg);
+    Ok(())
+}
+
+/// Read all weigh-ins, skipping malformed lines (with a shared lock, taken
+/// within `timeout`)
+#[cfg(feature = "fs")]
+pub fn read_bodyweight(path: &Path, timeout: std::time::Duration) -> Result<Vec<BodyweightEntry>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let file = File::open(path)?;
+    crate::lock::lock_shared_within(&file, path, timeout)?;
+
+    let mut entries = Vec::new();
+    for (line_num, line) in BufReader::new(&file).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<BodyweightEntry>(&line) {
+            Ok(entry) => entries.push(entry),
+            Err(e) => {
+                tracing::warn!(
+                    "Failed to parse bodyweight entry at line {}: {}",
+                    line_num + 1,
+                    e
+                );
+            }
+        }
+    }
+
+    fs2::FileExt::unlock(&file)?;
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn entry(days_ago: i64, weight_kg: f64) -> BodyweightEntry {
+        BodyweightEntry {
+            measured_at: Utc::now() - Duration::days(days_ago),
+            weight_kg,
+        }
+    }
+
+    #[test]
+    fn test_trend_slope_and_change() {
+        let entries = vec![
+            entry(14, 83.0),
+            entry(7, 82.5),
+            entry(0, 82.0),
+            entry(60, 90.0),
+        ];
+        let trend = trend(&entries, Utc::now() - Duration::days(30)).unwrap();
+
+        assert_eq!(trend.entries, 3);
+        assert_eq!(trend.latest_kg, 82.0);
+        assert!((trend.change_kg + 1.0).abs() < 1e-9);
+        assert!((trend.slope_kg_per_week.unwrap() + 0.5).abs() < 1e-6);
+        assert_eq!(trend.series_kg, vec![83.0, 82.5, 82.0]);
+    }
+
+    #[test]
+    fn test_trend_single_entry_has_no_slope() {
+        let trend = trend(&[entry(1, 80.0)], Utc::now() - Duration::days(7)).unwrap();
+        assert_eq!(trend.slope_kg_per_week, None);
+        assert_eq!(trend.change_kg, 0.0);
+        assert!(super::trend(&[], Utc::now()).is_none());
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn test_append_and_read_roundtrip() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join(BODYWEIGHT_FILE);
+
+        append_bodyweight(&path, &entry(1, 82.4), crate::lock::DEFAULT_TIMEOUT).unwrap();
+        append_bodyweight(&path, &entry(0, 82.1), crate::lock::DEFAULT_TIMEOUT).unwrap();
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(b"not json\n")
+            .unwrap();
+
+        let entries = read_bodyweight(&path, crate::lock::DEFAULT_TIMEOUT).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].weight_kg, 82.1);
+    }
+}
@@ -0,0 +1,177 @@
+//! Adaptive reminder cadence.
+//!
+//! The reminder timer fires at every candidate hour (each configured
+//! reminder hour and the [`SHIFT_RANGE_HOURS`] around it) and `krep remind`
+//! asks [`plan`] whether this is a reminder hour. A reminder hour where
+//! doses keep getting skipped or snoozed moves to a nearby hour where they
+//! get done, and when adherence drops the reminders are spaced out rather
+//! than repeated.
+
+use crate::timing::{HourStats, MIN_HOUR_SAMPLES};
+use serde::Serialize;
+
+/// How far (in hours) a reminder may move from its configured hour
+pub const SHIFT_RANGE_HOURS: u32 = 3;
+
+/// Reminder hours whose completion rate is below this move elsewhere
+pub const SHIFT_BELOW_RATE: f64 = 0.34;
+
+/// Days of history behind the adherence check
+pub const ADHERENCE_DAYS: i64 = 7;
+
+/// Adherence below this spaces reminders out
+pub const LOW_ADHERENCE: f64 = 0.5;
+
+/// When to remind today
+#[derive(Clone, Debug, Default, Serialize, PartialEq)]
+pub struct ReminderPlan {
+    /// Local hours to remind at, earliest first
+    pub hours: Vec<u32>,
+    /// Configured hours that moved, as `(from, to)`
+    pub shifted: Vec<(u32, u32)>,
+    /// Share of shown doses done over the last [`ADHERENCE_DAYS`]
+    /// (`None` with nothing shown)
+    pub adherence: Option<f64>,
+    /// Some reminders were dropped because adherence is low
+    pub spaced_out: bool,
+}
+
+/// Hours the reminder timer needs to fire at for any plan from `configured`
+pub fn candidate_hours(configured: &[u32]) -> Vec<u32> {
+    let mut hours: Vec<u32> = configured
+        .iter()
+        .flat_map(|&hour| {
+            hour.saturating_sub(SHIFT_RANGE_HOURS)..=(hour + SHIFT_RANGE_HOURS).min(23)
+        })
+        .collect();
+    hours.sort_unstable();
+    hours.dedup();
+    hours
+}
+
+/// Share of shown doses that were done across `hours` (`None` with nothing shown)
+pub fn adherence(hours: &[HourStats]) -> Option<f64> {
+    let completed: u32 = hours.iter().map(|h| h.completed).sum();
+    let shown: u32 = hours.iter().map(HourStats::shown).sum();
+    (shown > 0).then(|| f64::from(completed) / f64::from(shown))
+}
+
+/// Adapt `configured` reminder hours to recent behaviour
+///
+/// `by_hour` is the longer-term picture per local hour (from
+/// [`completion_by_hour`](crate::timing::completion_by_hour)) and
+/// `recent_adherence` the share done over the last [`ADHERENCE_DAYS`].
+///
+/// - A configured hour with [`MIN_HOUR_SAMPLES`] shown doses and a completion
+///   rate below [`SHIFT_BELOW_RATE`] moves to the best-done hour within
+///   [`SHIFT_RANGE_HOURS`] that does better and isn't already a reminder
+/// - With adherence below [`LOW_ADHERENCE`], every other reminder is
+///   dropped (at least one is kept)
+pub fn plan(
+    configured: &[u32],
+    by_hour: &[HourStats],
+    recent_adherence: Option<f64>,
+) -> ReminderPlan {
+    let stats = |hour: u32| by_hour.get(hour as usize).copied().unwrap_or_default();
+    let mut hours = configured.to_vec();
+    hours.sort_unstable();
+    hours.dedup();
+
+    let mut shifted = Vec::new();
+    for index in 0..hours.len() {
+        let from = hours[index];
+        let current = stats(from);
+        if current.shown() < MIN_HOUR_SAMPLES || current.completion_rate() >= SHIFT_BELOW_RATE {
+            continue;
+        }
+        let better = candidate_hours(&[from])
+            .into_iter()
+            .filter(|hour| !hours.contains(hour))
+            .map(stats)
+            .filter(|h| {
+                h.shown() >= MIN_HOUR_SAMPLES
+                    && h.completed > 0
+                    && h.completion_rate() > current.completion_rate()
+            })
+            .max_by(|a, b| {
+                a.completion_rate()
+                    .total_cmp(&b.completion_rate())
+                    .then(a.completed.cmp(&b.completed))
+                    // Nearer hours win ties
+                    .then(b.hour.abs_diff(from).cmp(&a.hour.abs_diff(from)))
+            });
+        if let Some(better) = better {
+            hours[index] = better.hour;
+            shifted.push((from, better.hour));
+        }
+    }
+    hours.sort_unstable();
+
+    let spaced_out = recent_adherence.is_some_and(|rate| rate < LOW_ADHERENCE) && hours.len() > 1;
+    if spaced_out {
+        hours = hours.into_iter().step_by(2).collect();
+    }
+
+    ReminderPlan {
+        hours,
+        shifted,
+        adherence: recent_adherence,
+        spaced_out,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn by_hour(entries: &[(u32, u32, u32)]) -> Vec<HourStats> {
+        let mut hours: Vec<HourStats> = (0..24)
+            .map(|hour| HourStats {
+                hour,
+                ..HourStats::default()
+            })
+            .collect();
+        for &(hour, completed, skipped) in entries {
+            hours[hour as usize].completed = completed;
+            hours[hour as usize].skipped = skipped;
+        }
+        hours
+    }
+
+    #[test]
+    fn test_candidate_hours() {
+        assert_eq!(candidate_hours(&[1]), vec![0, 1, 2, 3, 4]);
+        assert_eq!(
+            candidate_hours(&[9, 13, 22]),
+            vec![6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 19, 20, 21, 22, 23]
+        );
+    }
+
+    #[test]
+    fn test_skipped_hour_moves_to_where_doses_get_done() {
+        // 14:00 reminders get skipped, doses happen at 16:00
+        let hours = by_hour(&[(9, 3, 1), (14, 0, 4), (16, 4, 0), (17, 2, 0)]);
+
+        let plan = plan(&[9, 14, 17], &hours, Some(0.8));
+        assert_eq!(plan.hours, vec![9, 16, 17]);
+        assert_eq!(plan.shifted, vec![(14, 16)]);
+        assert!(!plan.spaced_out);
+
+        // Too few samples to judge: nothing moves
+        let quiet = by_hour(&[(14, 0, 1), (16, 4, 0)]);
+        assert_eq!(super::plan(&[14], &quiet, None).hours, vec![14]);
+    }
+
+    #[test]
+    fn test_low_adherence_spaces_reminders_out() {
+        let hours = by_hour(&[]);
+        assert_eq!(adherence(&by_hour(&[(9, 1, 3)])), Some(0.25));
+        assert_eq!(adherence(&hours), None);
+
+        let plan = plan(&[9, 13, 17], &hours, Some(0.25));
+        assert_eq!(plan.hours, vec![9, 17]);
+        assert!(plan.spaced_out);
+
+        assert_eq!(super::plan(&[9], &hours, Some(0.0)).hours, vec![9]);
+    }
+}
@@ -4,7 +4,7 @@
 
 use crate::types::*;
 use once_cell::sync::Lazy;
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 
 /// Cached default catalog - built once and reused across all operations
 static DEFAULT_CATALOG: Lazy<Catalog> = Lazy::new(|| build_default_catalog_internal());
@@ -41,7 +41,14 @@ fn build_default_catalog_internal() -> Catalog {
             name: "Kettlebell Swing (2-hand)".into(),
             kind: MovementKind::KettlebellSwing,
             default_style: MovementStyle::None,
-            tags: vec!["vo2".into(), "hinge".into(), "posterior_chain".into()],
+            tags: vec![
+                "vo2".into(),
+                "hinge".into(),
+                "posterior_chain".into(),
+                "hip".into(),
+                "lower_back".into(),
+                "legs".into(),
+            ],
             reference_url: Some("https://www.youtube.com/watch?v=YSxHifyI6s8".into()),
         },
     );
@@ -53,7 +60,15 @@ fn build_default_catalog_internal() -> Catalog {
             name: "Burpee".into(),
             kind: MovementKind::Burpee,
             default_style: MovementStyle::Burpee(BurpeeStyle::FourCount),
-            tags: vec!["vo2".into(), "full_body".into(), "bodyweight".into()],
+            tags: vec![
+                "vo2".into(),
+                "full_body".into(),
+                "bodyweight".into(),
+                "shoulder".into(),
+                "wrist".into(),
+                "knee".into(),
+                "legs".into(),
+            ],
             reference_url: Some("https://www.youtube.com/watch?v=TU8QYVW0gDU".into()),
         },
     );
@@ -70,6 +85,8 @@ fn build_default_catalog_internal() -> Catalog {
                 "gtg_ok".into(),
                 "upper_body".into(),
                 "pull".into(),
+                "shoulder".into(),
+                "elbow".into(),
             ],
             reference_url: Some("https://www.youtube.com/watch?v=eGo4IYlbE5g".into()),
         },
@@ -99,6 +116,128 @@ fn build_default_catalog_internal() -> Catalog {
         },
     );
 
+    movements.insert(
+        "dead_bug".into(),
+        Movement {
+            id: "dead_bug".into(),
+            name: "Dead Bug".into(),
+            kind: MovementKind::CoreDrill,
+            default_style: MovementStyle::None,
+            tags: vec![
+                "core".into(),
+                "anti_extension".into(),
+                "gtg_ok".into(),
+                "lower_back".into(),
+            ],
+            reference_url: None,
+        },
+    );
+
+    movements.insert(
+        "stair_walk".into(),
+        Movement {
+            id: "stair_walk".into(),
+            name: "Stair Walk".into(),
+            kind: MovementKind::StairWalk,
+            default_style: MovementStyle::None,
+            tags: vec![
+                "zone2".into(),
+                "aerobic".into(),
+                "low_impact".into(),
+                "knee".into(),
+                "legs".into(),
+            ],
+            reference_url: None,
+        },
+    );
+
+    movements.insert(
+        "box_breathing".into(),
+        Movement {
+            id: "box_breathing".into(),
+            name: "Box Breathing (4-4-4-4)".into(),
+            kind: MovementKind::Breathing,
+            default_style: MovementStyle::None,
+            tags: vec![
+                "breathwork".into(),
+                "downregulation".into(),
+                "gtg_ok".into(),
+            ],
+            reference_url: None,
+        },
+    );
+
+    movements.insert(
+        "air_squat".into(),
+        Movement {
+            id: "air_squat".into(),
+            name: "Air Squat".into(),
+            kind: MovementKind::Squat,
+            default_style: MovementStyle::None,
+            tags: vec![
+                "vo2".into(),
+                "squat".into(),
+                "bodyweight".into(),
+                "hip".into(),
+                "knee".into(),
+                "legs".into(),
+            ],
+            reference_url: None,
+        },
+    );
+
+    movements.insert(
+        "mountain_climber".into(),
+        Movement {
+            id: "mountain_climber".into(),
+            name: "Mountain Climber".into(),
+            kind: MovementKind::CoreDrill,
+            default_style: MovementStyle::None,
+            tags: vec![
+                "core".into(),
+                "bodyweight".into(),
+                "shoulder".into(),
+                "wrist".into(),
+            ],
+            reference_url: None,
+        },
+    );
+
+    // Warmup drills (prepended to VO2 prescriptions, see `WarmupConfig`)
+    movements.insert(
+        "jumping_jack".into(),
+        Movement {
+            id: "jumping_jack".into(),
+            name: "Jumping Jacks".into(),
+            kind: MovementKind::WarmupDrill,
+            default_style: MovementStyle::None,
+            tags: vec![
+                "warmup".into(),
+                "bodyweight".into(),
+                "knee".into(),
+                "legs".into(),
+            ],
+            reference_url: None,
+        },
+    );
+
+    movements.insert(
+        "hip_hinge".into(),
+        Movement {
+            id: "hip_hinge".into(),
+            name: "Hip Hinges".into(),
+            kind: MovementKind::WarmupDrill,
+            default_style: MovementStyle::None,
+            tags: vec![
+                "warmup".into(),
+                "hinge".into(),
+                "hip".into(),
+                "lower_back".into(),
+            ],
+            reference_url: None,
+        },
+    );
+
     // ========================================================================
     // Microdose Definitions
     // ========================================================================
@@ -117,6 +256,8 @@ fn build_default_catalog_internal() -> Catalog {
                 movement_id: "kb_swing_2h".into(),
                 movement_style: MovementStyle::None,
                 duration_hint_seconds: 60,
+                interval: Some(IntervalScheme::emom(5)),
+                ladder: None,
                 metrics: vec![MetricSpec::Reps {
                     key: "reps".into(),
                     default: 5,
@@ -143,6 +284,8 @@ fn build_default_catalog_internal() -> Catalog {
                 movement_id: "burpee".into(),
                 movement_style: MovementStyle::Burpee(BurpeeStyle::FourCount),
                 duration_hint_seconds: 60,
+                interval: Some(IntervalScheme::emom(5)),
+                ladder: None,
                 metrics: vec![MetricSpec::Reps {
                     key: "reps".into(),
                     default: 3,
@@ -155,6 +298,80 @@ fn build_default_catalog_internal() -> Catalog {
         },
     );
 
+    // VO2 Tabata: Air Squats (8 × 20s on / 10s off)
+    microdoses.insert(
+        "tabata_air_squat_4m".into(),
+        MicrodoseDefinition {
+            id: "tabata_air_squat_4m".into(),
+            name: "Tabata: Air Squats".into(),
+            category: MicrodoseCategory::Vo2,
+            suggested_duration_seconds: IntervalScheme::tabata().total_seconds(),
+            gtg_friendly: false,
+            reference_url: None,
+            blocks: vec![MicrodoseBlock {
+                movement_id: "air_squat".into(),
+                movement_style: MovementStyle::None,
+                duration_hint_seconds: 20,
+                interval: Some(IntervalScheme::tabata()),
+                ladder: None,
+                metrics: vec![], // Max reps in each 20s work interval
+            }],
+        },
+    );
+
+    // GTG: Pull-up ladder (1-2-3-2-1; progression raises the peak)
+    microdoses.insert(
+        "gtg_pullup_ladder".into(),
+        MicrodoseDefinition {
+            id: "gtg_pullup_ladder".into(),
+            name: "GTG: Pull-up Ladder".into(),
+            category: MicrodoseCategory::Gtg,
+            suggested_duration_seconds: 180,
+            gtg_friendly: true,
+            reference_url: None,
+            blocks: vec![MicrodoseBlock {
+                movement_id: "pullup".into(),
+                movement_style: MovementStyle::None,
+                duration_hint_seconds: 180,
+                interval: None,
+                ladder: Some(RepLadder {
+                    start: 1,
+                    step: 1,
+                    descending: true,
+                }),
+                metrics: vec![MetricSpec::Reps {
+                    key: "reps".into(),
+                    default: 3,
+                    min: 2,
+                    max: 6,
+                    step: 1,
+                    progressable: true,
+                }],
+            }],
+        },
+    );
+
+    // Core AMRAP: Mountain Climbers (2 minutes, rep total captured)
+    microdoses.insert(
+        "core_amrap_mountain_climber_2m".into(),
+        MicrodoseDefinition {
+            id: "core_amrap_mountain_climber_2m".into(),
+            name: "2-Min AMRAP: Mountain Climbers".into(),
+            category: MicrodoseCategory::Core,
+            suggested_duration_seconds: 120,
+            gtg_friendly: false,
+            reference_url: None,
+            blocks: vec![MicrodoseBlock {
+                movement_id: "mountain_climber".into(),
+                movement_style: MovementStyle::None,
+                duration_hint_seconds: 120,
+                interval: None,
+                ladder: None,
+                metrics: vec![MetricSpec::AmrapReps { key: "reps".into() }],
+            }],
+        },
+    );
+
     // GTG: Pull-ups (banded)
     microdoses.insert(
         "gtg_pullup_band".into(),
@@ -169,6 +386,8 @@ fn build_default_catalog_internal() -> Catalog {
                 movement_id: "pullup".into(),
                 movement_style: MovementStyle::Band(BandSpec::NamedColour("red".into())),
                 duration_hint_seconds: 30,
+                interval: None,
+                ladder: None,
                 metrics: vec![
                     MetricSpec::Reps {
                         key: "reps".into(),
@@ -193,7 +412,7 @@ fn build_default_catalog_internal() -> Catalog {
         "mobility_hip_cars".into(),
         MicrodoseDefinition {
             id: "mobility_hip_cars".into(),
-            name: "Hip CARs (3 reps each side)".into(),
+            name: "Hip CARs".into(),
             category: MicrodoseCategory::Mobility,
             suggested_duration_seconds: 120,
             gtg_friendly: true,
@@ -202,8 +421,10 @@ fn build_default_catalog_internal() -> Catalog {
                 movement_id: "hip_cars".into(),
                 movement_style: MovementStyle::None,
                 duration_hint_seconds: 120,
-                metrics: vec![MetricSpec::Reps {
-                    key: "reps_per_side".into(),
+                interval: None,
+                ladder: None,
+                metrics: vec![MetricSpec::RepsPerSide {
+                    key: "reps".into(),
                     default: 3,
                     min: 2,
                     max: 5,
@@ -219,7 +440,7 @@ fn build_default_catalog_internal() -> Catalog {
         "mobility_shoulder_cars".into(),
         MicrodoseDefinition {
             id: "mobility_shoulder_cars".into(),
-            name: "Shoulder CARs (3 reps each side)".into(),
+            name: "Shoulder CARs".into(),
             category: MicrodoseCategory::Mobility,
             suggested_duration_seconds: 120,
             gtg_friendly: true,
@@ -228,8 +449,10 @@ fn build_default_catalog_internal() -> Catalog {
                 movement_id: "shoulder_cars".into(),
                 movement_style: MovementStyle::None,
                 duration_hint_seconds: 120,
-                metrics: vec![MetricSpec::Reps {
-                    key: "reps_per_side".into(),
+                interval: None,
+                ladder: None,
+                metrics: vec![MetricSpec::RepsPerSide {
+                    key: "reps".into(),
                     default: 3,
                     min: 2,
                     max: 5,
@@ -240,13 +463,272 @@ fn build_default_catalog_internal() -> Catalog {
         },
     );
 
-    Catalog {
-        movements,
-        microdoses,
-    }
+    // Core: Dead Bugs
+    microdoses.insert(
+        "core_dead_bug".into(),
+        MicrodoseDefinition {
+            id: "core_dead_bug".into(),
+            name: "Core: Dead Bugs".into(),
+            category: MicrodoseCategory::Core,
+            suggested_duration_seconds: 90,
+            gtg_friendly: true,
+            reference_url: None,
+            blocks: vec![MicrodoseBlock {
+                movement_id: "dead_bug".into(),
+                movement_style: MovementStyle::None,
+                duration_hint_seconds: 90,
+                interval: None,
+                ladder: None,
+                metrics: vec![MetricSpec::RepsPerSide {
+                    key: "reps".into(),
+                    default: 5,
+                    min: 3,
+                    max: 10,
+                    step: 1,
+                    progressable: false,
+                }],
+            }],
+        },
+    );
+
+    // Zone 2: Stair walk (steady, conversational pace)
+    microdoses.insert(
+        "zone2_stair_walk_10m".into(),
+        MicrodoseDefinition {
+            id: "zone2_stair_walk_10m".into(),
+            name: "Zone 2: Stair Walk (conversational pace)".into(),
+            category: MicrodoseCategory::Zone2,
+            suggested_duration_seconds: 600,
+            gtg_friendly: false,
+            reference_url: None,
+            blocks: vec![MicrodoseBlock {
+                movement_id: "stair_walk".into(),
+                movement_style: MovementStyle::None,
+                duration_hint_seconds: 600,
+                interval: None,
+                ladder: None,
+                metrics: vec![],
+            }],
+        },
+    );
+
+    // Breathwork: Box breathing
+    microdoses.insert(
+        "breathwork_box_3m".into(),
+        MicrodoseDefinition {
+            id: "breathwork_box_3m".into(),
+            name: "Box Breathing (4s in, 4s hold, 4s out, 4s hold)".into(),
+            category: MicrodoseCategory::Breathwork,
+            suggested_duration_seconds: 180,
+            gtg_friendly: true,
+            reference_url: None,
+            blocks: vec![MicrodoseBlock {
+                movement_id: "box_breathing".into(),
+                movement_style: MovementStyle::None,
+                duration_hint_seconds: 180,
+                interval: None,
+                ladder: None,
+                metrics: vec![],
+            }],
+        },
+    );
+
+    Catalog::new(movements, microdoses)
 }
 
 impl Catalog {
+    /// Build a catalog and index it
+    pub fn new(
+        movements: HashMap<String, Movement>,
+        microdoses: HashMap<String, MicrodoseDefinition>,
+    ) -> Self {
+        let mut catalog = Catalog {
+            movements,
+            microdoses,
+            index: CatalogIndex::default(),
+        };
+        catalog.reindex();
+        catalog
+    }
+
+    /// Every movement, keyed by ID
+    pub fn movements(&self) -> &HashMap<String, Movement> {
+        &self.movements
+    }
+
+    /// Every definition, keyed by ID
+    pub fn microdoses(&self) -> &HashMap<String, MicrodoseDefinition> {
+        &self.microdoses
+    }
+
+    /// Add or replace a movement, returning the one it replaced
+    pub fn insert_movement(&mut self, movement: Movement) -> Option<Movement> {
+        let replaced = self.movements.insert(movement.id.clone(), movement);
+        self.reindex();
+        replaced
+    }
+
+    /// Add or replace a definition, returning the one it replaced
+    pub fn insert_microdose(
+        &mut self,
+        definition: MicrodoseDefinition,
+    ) -> Option<MicrodoseDefinition> {
+        let replaced = self.microdoses.insert(definition.id.clone(), definition);
+        self.reindex();
+        replaced
+    }
+
+    /// Remove a definition by ID
+    pub fn remove_microdose(&mut self, id: &str) -> Option<MicrodoseDefinition> {
+        let removed = self.microdoses.remove(id);
+        self.reindex();
+        removed
+    }
+
+    /// Keep only the definitions for which `keep` returns true
+    pub fn retain_microdoses(&mut self, mut keep: impl FnMut(&MicrodoseDefinition) -> bool) {
+        self.microdoses.retain(|_, definition| keep(definition));
+        self.reindex();
+    }
+
+    /// Rebuild the per-category and per-tag indices
+    fn reindex(&mut self) {
+        let mut index = CatalogIndex::default();
+        for (id, definition) in &self.microdoses {
+            index.all.push(id.clone());
+            index
+                .by_category
+                .entry(definition.category.clone())
+                .or_default()
+                .push(id.clone());
+
+            let tags: BTreeSet<&String> = self
+                .movements_in(definition)
+                .flat_map(|movement| &movement.tags)
+                .collect();
+            for tag in tags {
+                index
+                    .by_tag
+                    .entry(tag.clone())
+                    .or_default()
+                    .push(id.clone());
+            }
+        }
+        index.all.sort();
+        for ids in index
+            .by_category
+            .values_mut()
+            .chain(index.by_tag.values_mut())
+        {
+            ids.sort();
+        }
+        self.index = index;
+    }
+
+    /// A definition by ID
+    pub fn definition(&self, id: &str) -> Option<&MicrodoseDefinition> {
+        self.microdoses.get(id)
+    }
+
+    /// A movement by ID
+    pub fn movement(&self, id: &str) -> Option<&Movement> {
+        self.movements.get(id)
+    }
+
+    /// Every definition, sorted by ID
+    pub fn definitions(&self) -> impl Iterator<Item = &MicrodoseDefinition> + '_ {
+        self.indexed(Some(&self.index.all))
+    }
+
+    /// Definitions in a category, sorted by ID
+    pub fn in_category(
+        &self,
+        category: &MicrodoseCategory,
+    ) -> impl Iterator<Item = &MicrodoseDefinition> + '_ {
+        self.indexed(self.index.by_category.get(category))
+    }
+
+    /// Definitions using a movement tagged `tag` (e.g. a body area), sorted by ID
+    pub fn with_tag(&self, tag: &str) -> impl Iterator<Item = &MicrodoseDefinition> + '_ {
+        self.indexed(self.index.by_tag.get(tag))
+    }
+
+    /// Definitions that need nothing beyond the `available` equipment
+    /// (`[equipment] available` entries such as `kettlebell`), sorted by ID
+    ///
+    /// Every block's movement must be known; see [`MovementKind::equipment`].
+    pub fn doable_with<'a, S: AsRef<str>>(
+        &'a self,
+        available: &'a [S],
+    ) -> impl Iterator<Item = &'a MicrodoseDefinition> + 'a {
+        self.definitions().filter(move |definition| {
+            definition.blocks.iter().all(|block| {
+                self.movement(&block.movement_id).is_some_and(|movement| {
+                    movement
+                        .kind
+                        .equipment()
+                        .is_none_or(|needed| available.iter().any(|a| a.as_ref() == needed))
+                })
+            })
+        })
+    }
+
+    /// The known movements of a definition's blocks, in block order
+    pub fn movements_in<'a>(
+        &'a self,
+        definition: &'a MicrodoseDefinition,
+    ) -> impl Iterator<Item = &'a Movement> + 'a {
+        definition
+            .blocks
+            .iter()
+            .filter_map(|block| self.movement(&block.movement_id))
+    }
+
+    fn indexed<'a>(
+        &'a self,
+        ids: Option<&'a Vec<String>>,
+    ) -> impl Iterator<Item = &'a MicrodoseDefinition> + 'a {
+        ids.into_iter()
+            .flatten()
+            .filter_map(|id| self.microdoses.get(id))
+    }
+
+    /// Reference video or article for a definition: its own, else the first
+    /// one among its movements
+    pub fn reference_url<'a>(&'a self, definition: &'a MicrodoseDefinition) -> Option<&'a str> {
+        definition.reference_url.as_deref().or_else(|| {
+            self.movements_in(definition)
+                .find_map(|movement| movement.reference_url.as_deref())
+        })
+    }
+
+    /// Copy of the catalog with only the definitions in `keep` (e.g. the
+    /// results of a query)
+    pub fn filtered<'a>(&self, keep: impl IntoIterator<Item = &'a MicrodoseDefinition>) -> Catalog {
+        Catalog::new(
+            self.movements.clone(),
+            keep.into_iter()
+                .map(|definition| (definition.id.clone(), definition.clone()))
+                .collect(),
+        )
+    }
+
+    /// Copy of the catalog with only GTG-friendly microdoses
+    pub fn gtg_friendly(&self) -> Catalog {
+        self.filtered(
+            self.definitions()
+                .filter(|definition| definition.gtg_friendly),
+        )
+    }
+
+    /// Copy of the catalog with only microdoses that need no equipment
+    ///
+    /// A definition is kept when every block's movement is known and
+    /// bodyweight-only (see [`MovementKind::needs_equipment`]).
+    pub fn without_equipment(&self) -> Catalog {
+        self.filtered(self.doable_with::<&str>(&[]))
+    }
+
     /// Validate the catalog for consistency and completeness
     ///
     /// Returns a list of validation errors, or empty Vec if valid.
@@ -296,11 +778,52 @@ impl Catalog {
                     ));
                 }
 
+                if let Some(interval) = &block.interval {
+                    if interval.rounds == 0 || interval.work_seconds == 0 {
+                        errors.push(format!(
+                            "Microdose '{}' has an interval with no work (work {}s, {} rounds)",
+                            id, interval.work_seconds, interval.rounds
+                        ));
+                    }
+                    if def.suggested_duration_seconds < interval.total_seconds() {
+                        errors.push(format!(
+                            "Microdose '{}' suggests {}s but its intervals take {}s",
+                            id,
+                            def.suggested_duration_seconds,
+                            interval.total_seconds()
+                        ));
+                    }
+                }
+
+                if let Some(ladder) = &block.ladder {
+                    if ladder.start < 1 || ladder.step < 1 {
+                        errors.push(format!(
+                            "Microdose '{}': ladder needs start and step of at least 1",
+                            id
+                        ));
+                    }
+                    let peak = block.metrics.iter().find_map(|m| m.default_reps());
+                    match peak {
+                        Some(peak) if peak < ladder.start => errors.push(format!(
+                            "Microdose '{}': ladder peak {} is below its start {}",
+                            id, peak, ladder.start
+                        )),
+                        Some(_) => {}
+                        None => errors.push(format!(
+                            "Microdose '{}': ladder needs a reps metric for its peak",
+                            id
+                        )),
+                    }
+                }
+
                 // Validate metrics
                 for metric in &block.metrics {
                     match metric {
                         MetricSpec::Reps {
                             min, max, default, ..
+                        }
+                        | MetricSpec::RepsPerSide {
+                            min, max, default, ..
                         } => {
                             if default < min {
                                 errors.push(format!(
@@ -321,6 +844,14 @@ impl Catalog {
                                 ));
                             }
                         }
+                        MetricSpec::AmrapReps { .. } => {
+                            if block.duration_hint_seconds == 0 {
+                                errors.push(format!(
+                                    "Microdose '{}': AMRAP block needs a duration",
+                                    id
+                                ));
+                            }
+                        }
                         MetricSpec::Band { default, .. } => {
                             if default.is_empty() {
                                 errors.push(format!(
@@ -335,27 +866,10 @@ impl Catalog {
         }
 
         // Check that we have at least one microdose in each category
-        let has_vo2 = self
-            .microdoses
-            .values()
-            .any(|d| d.category == MicrodoseCategory::Vo2);
-        let has_gtg = self
-            .microdoses
-            .values()
-            .any(|d| d.category == MicrodoseCategory::Gtg);
-        let has_mobility = self
-            .microdoses
-            .values()
-            .any(|d| d.category == MicrodoseCategory::Mobility);
-
-        if !has_vo2 {
-            errors.push("Catalog has no VO2 microdoses".to_string());
-        }
-        if !has_gtg {
-            errors.push("Catalog has no GTG microdoses".to_string());
-        }
-        if !has_mobility {
-            errors.push("Catalog has no Mobility microdoses".to_string());
+        for category in MicrodoseCategory::ALL {
+            if !self.microdoses.values().any(|d| d.category == category) {
+                errors.push(format!("Catalog has no {:?} microdoses", category));
+            }
         }
 
         errors
@@ -369,17 +883,17 @@ mod tests {
     #[test]
     fn test_catalog_loads() {
         let catalog = build_default_catalog();
-        assert_eq!(catalog.movements.len(), 5);
-        assert_eq!(catalog.microdoses.len(), 5);
+        assert_eq!(catalog.movements().len(), 12);
+        assert_eq!(catalog.microdoses().len(), 11);
     }
 
     #[test]
     fn test_all_referenced_movements_exist() {
         let catalog = build_default_catalog();
-        for def in catalog.microdoses.values() {
+        for def in catalog.microdoses().values() {
             for block in &def.blocks {
                 assert!(
-                    catalog.movements.contains_key(&block.movement_id),
+                    catalog.movements().contains_key(&block.movement_id),
                     "Movement {} referenced but not found",
                     block.movement_id
                 );
@@ -391,7 +905,7 @@ mod tests {
     fn test_vo2_category_exists() {
         let catalog = build_default_catalog();
         let vo2_count = catalog
-            .microdoses
+            .microdoses()
             .values()
             .filter(|d| d.category == MicrodoseCategory::Vo2)
             .count();
@@ -402,7 +916,7 @@ mod tests {
     fn test_gtg_category_exists() {
         let catalog = build_default_catalog();
         let gtg_count = catalog
-            .microdoses
+            .microdoses()
             .values()
             .filter(|d| d.category == MicrodoseCategory::Gtg)
             .count();
@@ -413,7 +927,7 @@ mod tests {
     fn test_mobility_category_exists() {
         let catalog = build_default_catalog();
         let mobility_count = catalog
-            .microdoses
+            .microdoses()
             .values()
             .filter(|d| d.category == MicrodoseCategory::Mobility)
             .count();
@@ -423,6 +937,162 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_every_category_exists() {
+        let catalog = build_default_catalog();
+        for category in MicrodoseCategory::ALL {
+            assert!(
+                catalog
+                    .microdoses()
+                    .values()
+                    .any(|d| d.category == category),
+                "Should have at least 1 {:?} workout",
+                category
+            );
+        }
+    }
+
+    #[test]
+    fn test_category_and_tag_indices() {
+        let mut catalog = build_default_catalog();
+
+        for category in MicrodoseCategory::ALL {
+            let mut expected: Vec<_> = catalog
+                .microdoses()
+                .values()
+                .filter(|d| d.category == category)
+                .map(|d| d.id.clone())
+                .collect();
+            expected.sort();
+            let indexed: Vec<_> = catalog
+                .in_category(&category)
+                .map(|d| d.id.clone())
+                .collect();
+            assert_eq!(indexed, expected);
+        }
+
+        let hip: Vec<_> = catalog.with_tag("hip").map(|d| d.id.as_str()).collect();
+        assert!(hip.contains(&"mobility_hip_cars"));
+        assert!(catalog.with_tag("no_such_tag").next().is_none());
+
+        // Edits keep the index in sync
+        catalog.remove_microdose("mobility_hip_cars");
+        assert!(catalog.with_tag("hip").all(|d| d.id != "mobility_hip_cars"));
+    }
+
+    #[test]
+    fn test_definition_queries() {
+        let catalog = build_default_catalog();
+
+        let all: Vec<_> = catalog.definitions().map(|d| d.id.as_str()).collect();
+        assert_eq!(all.len(), catalog.microdoses().len());
+        assert!(all.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(
+            catalog.definition("emom_burpee_5m").map(|d| d.id.as_str()),
+            Some("emom_burpee_5m")
+        );
+        assert!(catalog.definition("no_such_dose").is_none());
+
+        let swings = catalog.definition("emom_kb_swing_5m").unwrap();
+        assert_eq!(
+            catalog
+                .movements_in(swings)
+                .map(|m| m.kind.equipment())
+                .collect::<Vec<_>>(),
+            vec![Some("kettlebell")]
+        );
+
+        // Only a kettlebell: swings yes, pull-ups no
+        let with_kb: Vec<_> = catalog.doable_with(&["kettlebell"]).collect();
+        assert!(with_kb.iter().any(|d| d.id == "emom_kb_swing_5m"));
+        assert!(with_kb.iter().all(|d| d.id != "gtg_pullup_band"));
+        let bodyweight = catalog.without_equipment();
+        assert_eq!(
+            catalog.doable_with::<&str>(&[]).count(),
+            bodyweight.microdoses().len()
+        );
+        assert!(bodyweight.definition("emom_kb_swing_5m").is_none());
+    }
+
+    #[test]
+    fn test_reference_url_falls_back_to_movement() {
+        let mut catalog = build_default_catalog();
+        let hip = catalog.microdoses()["mobility_hip_cars"].clone();
+        assert_eq!(
+            catalog.reference_url(&hip),
+            catalog.movements()["hip_cars"].reference_url.as_deref()
+        );
+        let squat = catalog.microdoses()["tabata_air_squat_4m"].clone();
+        assert_eq!(catalog.reference_url(&squat), None);
+
+        let own = "https://example.com/tabata";
+        let mut squat = squat;
+        squat.reference_url = Some(own.into());
+        catalog.insert_microdose(squat.clone());
+        assert_eq!(catalog.reference_url(&squat), Some(own));
+    }
+
+    #[test]
+    fn test_interval_schemes() {
+        let catalog = build_default_catalog();
+        let emom = catalog.microdoses()["emom_kb_swing_5m"].blocks[0]
+            .interval
+            .unwrap();
+        assert_eq!(emom.label(), "EMOM × 5");
+        assert_eq!(emom.total_seconds(), 300);
+        assert_eq!(emom.phases().len(), 5);
+
+        let tabata = &catalog.microdoses()["tabata_air_squat_4m"];
+        assert_eq!(tabata.interval(), Some(IntervalScheme::tabata()));
+        assert_eq!(tabata.interval().unwrap().label(), "Tabata 20:10 × 8");
+        // 8 × 20s work with 7 rests between rounds
+        assert_eq!(tabata.blocks_seconds(), 230);
+        assert_eq!(tabata.suggested_duration_seconds, 230);
+
+        let thirty_thirty = IntervalScheme {
+            work_seconds: 30,
+            rest_seconds: 30,
+            rounds: 10,
+        };
+        assert_eq!(thirty_thirty.label(), "30:30 × 10");
+        // No rest after the final round
+        assert_eq!(thirty_thirty.total_seconds(), 570);
+        let phases = thirty_thirty.phases();
+        assert_eq!(phases.len(), 19);
+        assert_eq!(phases[1].kind, IntervalPhaseKind::Rest);
+        assert_eq!(phases[18].kind, IntervalPhaseKind::Work);
+        assert_eq!(phases[18].round, 10);
+
+        let mut catalog = catalog;
+        let mut burpees = catalog.microdoses()["emom_burpee_5m"].clone();
+        burpees.blocks[0].interval = Some(IntervalScheme {
+            work_seconds: 40,
+            rest_seconds: 20,
+            rounds: 0,
+        });
+        catalog.insert_microdose(burpees);
+        assert_eq!(catalog.validate().len(), 1);
+    }
+
+    #[test]
+    fn test_rep_ladder() {
+        let catalog = build_default_catalog();
+        let ladder = catalog.microdoses()["gtg_pullup_ladder"].ladder().unwrap();
+        assert_eq!(ladder.rungs(3), vec![1, 2, 3, 2, 1]);
+        assert_eq!(ladder.label(3), "1-2-3-2-1");
+        assert_eq!(ladder.total(3), 9);
+        assert_eq!(ladder.total(4), 16);
+
+        let climb = RepLadder {
+            start: 2,
+            step: 2,
+            descending: false,
+        };
+        assert_eq!(climb.label(7), "2-4-6");
+        // A peak below the start is a single rung
+        assert_eq!(climb.rungs(1), vec![1]);
+    }
+
     #[test]
     fn test_default_catalog_validates() {
         let catalog = build_default_catalog();
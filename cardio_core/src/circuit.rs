@@ -0,0 +1,164 @@
+//! Circuits: doses with more than one block, tracked block by block.
+//!
+//! The tray shows each block of a circuit as a checklist row that is marked
+//! done (optionally with the reps realized) or skipped, and logs the session
+//! once every block is resolved. Reps entered for done blocks are recorded
+//! as one [`RealizedMetric::Reps`] per block, keyed by the block's movement.
+
+use crate::{Catalog, MicrodoseBlock, MicrodoseDefinition, RealizedMetric};
+
+/// Whether `definition` has more than one block
+pub fn is_circuit(definition: &MicrodoseDefinition) -> bool {
+    definition.blocks.len() > 1
+}
+
+/// Where one block of a circuit stands
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BlockStatus {
+    #[default]
+    Pending,
+    Done,
+    Skipped,
+}
+
+/// One block's row in the checklist
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BlockProgress {
+    pub status: BlockStatus,
+    /// Reps realized, if entered (only recorded for done blocks)
+    pub reps: Option<i32>,
+}
+
+/// Progress through a circuit's blocks, in block order
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Checklist {
+    pub blocks: Vec<BlockProgress>,
+}
+
+impl Checklist {
+    /// Every block of `definition` pending
+    pub fn new(definition: &MicrodoseDefinition) -> Self {
+        Checklist {
+            blocks: vec![BlockProgress::default(); definition.blocks.len()],
+        }
+    }
+
+    /// Set a block's status (out-of-range indexes are ignored)
+    pub fn set_status(&mut self, index: usize, status: BlockStatus) {
+        if let Some(block) = self.blocks.get_mut(index) {
+            block.status = status;
+        }
+    }
+
+    /// Set a block's realized reps (out-of-range indexes are ignored)
+    pub fn set_reps(&mut self, index: usize, reps: Option<i32>) {
+        if let Some(block) = self.blocks.get_mut(index) {
+            block.reps = reps.filter(|&r| r >= 0);
+        }
+    }
+
+    /// Blocks neither done nor skipped
+    pub fn pending(&self) -> usize {
+        self.blocks
+            .iter()
+            .filter(|b| b.status == BlockStatus::Pending)
+            .count()
+    }
+
+    /// Ready to log: every block resolved and at least one done
+    pub fn complete(&self) -> bool {
+        self.pending() == 0 && self.blocks.iter().any(|b| b.status == BlockStatus::Done)
+    }
+
+    /// Reps entered for done blocks, one metric per block keyed by movement
+    pub fn metrics(&self, definition: &MicrodoseDefinition) -> Vec<RealizedMetric> {
+        definition
+            .blocks
+            .iter()
+            .zip(&self.blocks)
+            .filter(|(_, progress)| progress.status == BlockStatus::Done)
+            .filter_map(|(block, progress)| {
+                progress.reps.map(|reps| RealizedMetric::Reps {
+                    key: block.movement_id.clone(),
+                    per_set: vec![reps],
+                })
+            })
+            .collect()
+    }
+}
+
+/// Checklist row text for a block, e.g. "Burpee: 5 reps" or "Hip CARs: 3/side"
+/// (the duration when the block has no rep target)
+pub fn block_label(block: &MicrodoseBlock, catalog: &Catalog) -> String {
+    let name = catalog
+        .movement(&block.movement_id)
+        .map_or(block.movement_id.as_str(), |m| m.name.as_str());
+    let target = block
+        .metrics
+        .iter()
+        .find_map(|m| {
+            m.default_reps().map(|reps| {
+                if m.is_per_side() {
+                    format!("{}/side", reps)
+                } else {
+                    format!("{} reps", reps)
+                }
+            })
+        })
+        .unwrap_or_else(|| format!("{} sec", block.total_seconds()));
+    format!("{}: {}", name, target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::build_default_catalog;
+
+    fn circuit(catalog: &Catalog) -> MicrodoseDefinition {
+        let mut definition = catalog.microdoses()["emom_burpee_5m"].clone();
+        let hip_cars = catalog.microdoses()["mobility_hip_cars"].blocks[0].clone();
+        definition.blocks.push(hip_cars);
+        definition
+    }
+
+    #[test]
+    fn test_checklist_resolves_block_by_block() {
+        let catalog = build_default_catalog();
+        let definition = circuit(&catalog);
+        assert!(is_circuit(&definition));
+        assert!(!is_circuit(&catalog.microdoses()["emom_burpee_5m"]));
+
+        let mut checklist = Checklist::new(&definition);
+        assert_eq!(checklist.pending(), 2);
+        assert!(!checklist.complete());
+
+        checklist.set_status(0, BlockStatus::Done);
+        checklist.set_reps(0, Some(25));
+        checklist.set_reps(1, Some(8));
+        checklist.set_status(5, BlockStatus::Done);
+        assert!(!checklist.complete());
+
+        // A skipped block resolves it but records nothing
+        checklist.set_status(1, BlockStatus::Skipped);
+        assert!(checklist.complete());
+        assert_eq!(
+            checklist.metrics(&definition),
+            vec![RealizedMetric::Reps {
+                key: definition.blocks[0].movement_id.clone(),
+                per_set: vec![25],
+            }]
+        );
+
+        // Skipping everything isn't a dose
+        checklist.set_status(0, BlockStatus::Skipped);
+        assert!(!checklist.complete());
+    }
+
+    #[test]
+    fn test_block_label() {
+        let catalog = build_default_catalog();
+        let definition = circuit(&catalog);
+        assert!(block_label(&definition.blocks[0], &catalog).ends_with(" reps"));
+        assert!(block_label(&definition.blocks[1], &catalog).ends_with("/side"));
+    }
+}
@@ -0,0 +1,57 @@
+//! Where "now" comes from.
+//!
+//! Time-dependent code takes a `now` (or a [`Clock`] to read one from)
+//! instead of calling `Utc::now()` itself, so tests and `krep now --at`
+//! can run it at any instant. Frontends use [`SystemClock`]; tests and
+//! dry runs use a [`FixedClock`].
+
+use chrono::{DateTime, Duration, Utc};
+
+/// A source of the current time
+pub trait Clock: Send + Sync + std::fmt::Debug {
+    fn now(&self) -> DateTime<Utc>;
+}
+
+/// The real time
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// Always the same instant, until moved with [`FixedClock::advance`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FixedClock(pub DateTime<Utc>);
+
+impl FixedClock {
+    pub fn advance(&mut self, by: Duration) {
+        self.0 += by;
+    }
+}
+
+impl Clock for FixedClock {
+    fn now(&self) -> DateTime<Utc> {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fixed_clock() {
+        let at = DateTime::parse_from_rfc3339("2026-03-02T07:30:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let mut clock = FixedClock(at);
+        assert_eq!(clock.now(), at);
+        assert_eq!(clock.now(), at);
+
+        clock.advance(Duration::hours(2));
+        assert_eq!(clock.now(), at + Duration::hours(2));
+    }
+}
@@ -1,13 +1,70 @@
 //! Configuration file support for Krep.
 //!
-//! Configuration is loaded from `$XDG_CONFIG_HOME/krep/config.toml`.
+//! Configuration is loaded from `$XDG_CONFIG_HOME/krep/config.toml`, with
+//! drop-ins from `config.d/*.toml` merged over it in lexical order and
+//! `KREP_*` environment variables (see [`ENV_OVERRIDES`]) layered on top.
+//! Keys that have been renamed are mapped to their new names (see
+//! [`KEY_MIGRATIONS`]) with a warning.
+//! Loading and saving require the `config-toml` feature; without it
+//! `Config` is defaults-only.
 
 use crate::{Error, Result};
+use crate::{
+    MetricSpec, MicrodoseBlock, MicrodoseCategory, MicrodoseDefinition, MovementStyle,
+    StrengthSessionType,
+};
+use chrono::{DateTime, Datelike, NaiveTime, Utc, Weekday};
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 use std::path::{Path, PathBuf};
 
+/// JSON Schema (draft 7) for `config.toml`, generated from the serde types
+///
+/// Field doc comments become descriptions, so editors can autocomplete and
+/// explain keys.
+#[cfg(feature = "config-schema")]
+pub fn json_schema() -> Result<String> {
+    let mut schema = schemars::schema_for!(Config);
+    schema.schema.metadata().title = Some("krep configuration".to_string());
+    Ok(serde_json::to_string_pretty(&schema)?)
+}
+
+/// Name of the drop-in directory next to `config.toml`
+pub const DROP_IN_DIR: &str = "config.d";
+
+/// Environment variable selecting a `[profile.<name>]` section (the CLI's
+/// `--profile` takes precedence)
+pub const PROFILE_ENV: &str = "KREP_PROFILE";
+
+/// Renamed or moved keys, as `(old, new)` dotted paths
+///
+/// Loading maps old keys to new ones with a warning, and
+/// `krep config migrate` rewrites the files. Add an entry whenever a key is
+/// renamed so existing configs keep working.
+pub const KEY_MIGRATIONS: &[(&str, &str)] = &[];
+
+/// Environment variables that override config keys, as `(variable, key)`
+///
+/// Lists are comma-separated; booleans accept `true/false`, `1/0`,
+/// `yes/no` and `on/off`. Empty variables are ignored.
+pub const ENV_OVERRIDES: [(&str, &str); 12] = [
+    ("KREP_DATA_DIR", "data.data_dir"),
+    ("KREP_EXCLUDE_ANOMALIES", "data.exclude_anomalies"),
+    ("KREP_LOCK_TIMEOUT_SECS", "data.lock_timeout_secs"),
+    ("KREP_EQUIPMENT", "equipment.available"),
+    ("KREP_BURPEE_REP_CEILING", "progression.burpee_rep_ceiling"),
+    ("KREP_KB_SWING_MAX_REPS", "progression.kb_swing_max_reps"),
+    ("KREP_ROTATION", "prescription.rotation"),
+    ("KREP_VO2_GAP_HOURS", "prescription.vo2_gap_hours"),
+    ("KREP_TIMEZONE", "time.timezone"),
+    ("KREP_MAX_HR", "heart_rate.max_hr"),
+    ("KREP_AGE", "heart_rate.age"),
+    ("KREP_WARMUP", "warmup.enabled"),
+];
+
 /// Application configuration
 #[derive(Clone, Debug, Serialize, Deserialize, Default)]
+#[cfg_attr(feature = "config-schema", derive(schemars::JsonSchema))]
 pub struct Config {
     #[serde(default)]
     pub data: DataConfig,
@@ -18,27 +75,274 @@ pub struct Config {
     #[serde(default)]
     pub progression: ProgressionConfig,
 
+    #[serde(default)]
+    pub prescription: PrescriptionConfig,
+
+    #[serde(default)]
+    pub time: TimeConfig,
+
+    #[serde(default)]
+    pub warmup: WarmupConfig,
+
+    #[serde(default)]
+    pub heart_rate: HeartRateConfig,
+
     #[serde(default)]
     pub mobility: MobilityConfig,
+
+    #[serde(default)]
+    pub logging: LoggingConfig,
+
+    #[serde(default)]
+    pub sound: SoundConfig,
+
+    #[serde(default)]
+    pub tray: TrayConfig,
+
+    #[serde(default)]
+    pub journal: JournalConfig,
+
+    #[serde(default)]
+    pub status: StatusConfig,
+
+    #[serde(default)]
+    pub strength: StrengthConfig,
+
+    /// Training phases (`[[phases]]`) by local date range
+    #[serde(default)]
+    pub phases: Vec<crate::phase::Phase>,
+
+    #[serde(default)]
+    pub household: HouseholdConfig,
+
+    #[serde(default)]
+    pub off: OffConfig,
+
+    #[serde(default)]
+    pub session: SessionConfig,
+
+    /// Named overrides, e.g. `[profile.office]`, selected at load time
+    #[serde(default)]
+    pub profile: BTreeMap<String, ProfileConfig>,
+
+    /// Name of the profile applied at load time, if any
+    #[serde(skip)]
+    pub active_profile: Option<String>,
+}
+
+/// Settings a named profile overrides; unset fields keep the main config's value
+#[derive(Clone, Debug, Serialize, Deserialize, Default)]
+#[cfg_attr(feature = "config-schema", derive(schemars::JsonSchema))]
+pub struct ProfileConfig {
+    /// Replaces `equipment.available`
+    #[serde(default)]
+    pub equipment: Option<Vec<String>>,
+
+    /// Replaces `prescription.rotation`
+    #[serde(default)]
+    pub rotation: Option<Vec<MicrodoseCategory>>,
+
+    /// Replaces `prescription.gtg_friendly_only`
+    #[serde(default)]
+    pub gtg_friendly_only: Option<bool>,
+
+    /// Replaces `data.data_dir`, e.g. for another person in the household
+    #[serde(default)]
+    pub data_dir: Option<PathBuf>,
+}
+
+/// Regular strength training schedule
+///
+/// On a scheduled day the engine spares the muscles the session will train
+/// until it starts, rather than waiting for the strength signal afterwards.
+#[derive(Clone, Debug, Serialize, Deserialize, Default)]
+#[cfg_attr(feature = "config-schema", derive(schemars::JsonSchema))]
+pub struct StrengthConfig {
+    /// Weekdays with a lower-body session (`"mon"`, `"thu"`, ...)
+    #[serde(default)]
+    pub lower: Vec<String>,
+
+    /// Weekdays with an upper-body session
+    #[serde(default)]
+    pub upper: Vec<String>,
+
+    /// Weekdays with a full-body session
+    #[serde(default)]
+    pub full: Vec<String>,
+
+    /// Usual local start time (`"18:00"`); doses after it aren't spared.
+    /// Unset spares the muscles all day
+    #[serde(default)]
+    pub at: Option<String>,
+}
+
+impl StrengthConfig {
+    /// Session type scheduled on `weekday`, if any
+    pub fn session_on(&self, weekday: Weekday) -> Option<StrengthSessionType> {
+        [
+            (&self.lower, StrengthSessionType::Lower),
+            (&self.upper, StrengthSessionType::Upper),
+            (&self.full, StrengthSessionType::Full),
+        ]
+        .into_iter()
+        .find(|(days, _)| {
+            days.iter()
+                .any(|day| day.parse::<Weekday>().is_ok_and(|d| d == weekday))
+        })
+        .map(|(_, session_type)| session_type)
+    }
+
+    /// Usual start time (`None` when unset or invalid)
+    pub fn start_time(&self) -> Option<NaiveTime> {
+        self.at
+            .as_deref()
+            .and_then(|at| NaiveTime::parse_from_str(at.trim(), "%H:%M").ok())
+    }
+}
+
+/// Holidays and breaks (see [`crate::off_days`])
+#[derive(Clone, Debug, Serialize, Deserialize, Default)]
+#[cfg_attr(feature = "config-schema", derive(schemars::JsonSchema))]
+pub struct OffConfig {
+    /// Local dates (`"2024-12-25"`) or ranges (`"2024-08-10..2024-08-20"`)
+    /// with nothing prescribed, reminded or due
+    #[serde(default)]
+    pub days: Vec<String>,
+}
+
+/// Doses in progress
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "config-schema", derive(schemars::JsonSchema))]
+pub struct SessionConfig {
+    /// Minutes after a dose is started (timer running) with nothing logged
+    /// before it is recorded as abandoned
+    #[serde(default = "default_abandon_after_minutes")]
+    pub abandon_after_minutes: u32,
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        Self {
+            abandon_after_minutes: default_abandon_after_minutes(),
+        }
+    }
+}
+
+impl SessionConfig {
+    /// How long a started dose may run before it counts as abandoned
+    pub fn abandon_after(&self) -> chrono::Duration {
+        chrono::Duration::minutes(i64::from(self.abandon_after_minutes))
+    }
+}
+
+/// Shared accountability with other people on this machine
+#[derive(Clone, Debug, Serialize, Deserialize, Default)]
+#[cfg_attr(feature = "config-schema", derive(schemars::JsonSchema))]
+pub struct HouseholdConfig {
+    /// Profiles (each with its own `data_dir`) whose doses `krep stats` and
+    /// the tray show next to yours
+    #[serde(default)]
+    pub members: Vec<String>,
 }
 
 /// Data storage configuration
 #[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "config-schema", derive(schemars::JsonSchema))]
 pub struct DataConfig {
     #[serde(default = "default_data_dir")]
     pub data_dir: PathBuf,
+
+    /// Leave sessions with anomalies (duplicate IDs, future timestamps,
+    /// negative durations, impossible HR) out of history loads
+    #[serde(default)]
+    pub exclude_anomalies: bool,
+
+    /// Session WAL (default `<data_dir>/wal/microdose_sessions.wal`)
+    ///
+    /// This and the other path overrides resolve relative paths against
+    /// `data_dir`.
+    #[serde(default)]
+    pub wal_path: Option<PathBuf>,
+
+    /// CSV archive (default `<data_dir>/sessions.csv`)
+    #[serde(default)]
+    pub csv_path: Option<PathBuf>,
+
+    /// Progression state (default `<data_dir>/wal/state.json`)
+    #[serde(default)]
+    pub state_path: Option<PathBuf>,
+
+    /// Strength signal (default `<data_dir>/strength/signal.json`)
+    #[serde(default)]
+    pub strength_signal_path: Option<PathBuf>,
+
+    /// Strength history log (default `<data_dir>/strength_history.jsonl`)
+    #[serde(default)]
+    pub strength_history_path: Option<PathBuf>,
+
+    /// Readiness signal (default `<data_dir>/readiness.json`)
+    #[serde(default)]
+    pub readiness_signal_path: Option<PathBuf>,
+
+    /// Seconds a read or write of a data file (WAL, state, logs) waits for
+    /// the lock held by another process (e.g. a rollup) before failing (0:
+    /// fail immediately)
+    #[serde(default = "default_lock_timeout_secs")]
+    pub lock_timeout_secs: u64,
+
+    /// Let the tray roll the WAL up into the CSV archive by itself, once it
+    /// holds sessions from an earlier day or grows past `auto_rollup_wal_kb`
+    #[serde(default = "default_auto_rollup")]
+    pub auto_rollup: bool,
+
+    /// WAL size in KiB that triggers an automatic rollup
+    #[serde(default = "default_auto_rollup_wal_kb")]
+    pub auto_rollup_wal_kb: u64,
 }
 
 impl Default for DataConfig {
     fn default() -> Self {
         Self {
             data_dir: default_data_dir(),
+            exclude_anomalies: false,
+            wal_path: None,
+            csv_path: None,
+            state_path: None,
+            strength_signal_path: None,
+            strength_history_path: None,
+            readiness_signal_path: None,
+            lock_timeout_secs: default_lock_timeout_secs(),
+            auto_rollup: default_auto_rollup(),
+            auto_rollup_wal_kb: default_auto_rollup_wal_kb(),
         }
     }
 }
 
+impl DataConfig {
+    /// `lock_timeout_secs` as a duration
+    pub fn lock_timeout(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.lock_timeout_secs)
+    }
+
+    /// Overridden storage paths as `(key, path)`, resolved against `data_dir`
+    pub fn path_overrides(&self, data_dir: &Path) -> Vec<(&'static str, PathBuf)> {
+        [
+            ("data.wal_path", &self.wal_path),
+            ("data.csv_path", &self.csv_path),
+            ("data.state_path", &self.state_path),
+            ("data.strength_signal_path", &self.strength_signal_path),
+            ("data.strength_history_path", &self.strength_history_path),
+            ("data.readiness_signal_path", &self.readiness_signal_path),
+        ]
+        .into_iter()
+        .filter_map(|(key, path)| path.as_ref().map(|p| (key, data_dir.join(p))))
+        .collect()
+    }
+}
+
 /// Equipment availability configuration
 #[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "config-schema", derive(schemars::JsonSchema))]
 pub struct EquipmentConfig {
     #[serde(default = "default_equipment")]
     pub available: Vec<String>,
@@ -54,6 +358,7 @@ impl Default for EquipmentConfig {
 
 /// Progression parameters configuration
 #[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "config-schema", derive(schemars::JsonSchema))]
 pub struct ProgressionConfig {
     #[serde(default = "default_burpee_rep_ceiling")]
     pub burpee_rep_ceiling: i32,
@@ -71,8 +376,128 @@ impl Default for ProgressionConfig {
     }
 }
 
+/// Prescription engine configuration
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "config-schema", derive(schemars::JsonSchema))]
+pub struct PrescriptionConfig {
+    /// Category round-robin order (categories missing from the catalog are skipped)
+    #[serde(default = "default_rotation")]
+    pub rotation: Vec<MicrodoseCategory>,
+
+    /// Doses in a row per rotation turn, by category name (default 1),
+    /// e.g. `gtg = 2` for two GTG doses per VO2
+    #[serde(default)]
+    pub weights: BTreeMap<String, u32>,
+
+    /// Sessions per trailing 7 days, by category name, e.g.
+    /// `{ vo2 = 3, gtg = 10 }`; the category furthest behind goes first
+    #[serde(default)]
+    pub weekly_targets: BTreeMap<String, u32>,
+
+    /// Hours since the last VO2 dose after which VO2 is prescribed next
+    #[serde(default = "default_vo2_gap_hours")]
+    pub vo2_gap_hours: u32,
+
+    /// Only prescribe GTG-friendly microdoses (no sweat, no floor work),
+    /// e.g. in an office profile
+    #[serde(default)]
+    pub gtg_friendly_only: bool,
+
+    /// Local weekday windows (`"09:00-17:30"`) during which only
+    /// GTG-friendly microdoses are prescribed
+    #[serde(default)]
+    pub office_hours: Vec<String>,
+}
+
+impl Default for PrescriptionConfig {
+    fn default() -> Self {
+        Self {
+            rotation: default_rotation(),
+            weights: BTreeMap::new(),
+            weekly_targets: BTreeMap::new(),
+            vo2_gap_hours: default_vo2_gap_hours(),
+            gtg_friendly_only: false,
+            office_hours: Vec::new(),
+        }
+    }
+}
+
+/// Local-time configuration
+#[derive(Clone, Debug, Serialize, Deserialize, Default)]
+#[cfg_attr(feature = "config-schema", derive(schemars::JsonSchema))]
+pub struct TimeConfig {
+    /// IANA timezone (e.g. "Asia/Tokyo"); unset uses the system timezone
+    #[serde(default)]
+    pub timezone: Option<String>,
+}
+
+/// Heart-rate configuration for zone targets
+#[derive(Clone, Debug, Serialize, Deserialize, Default)]
+#[cfg_attr(feature = "config-schema", derive(schemars::JsonSchema))]
+pub struct HeartRateConfig {
+    /// Measured max HR in bpm; takes precedence over `age`
+    #[serde(default)]
+    pub max_hr: Option<u32>,
+
+    /// Age in years, used to estimate max HR (220 − age) when `max_hr` is unset
+    #[serde(default)]
+    pub age: Option<u32>,
+
+    /// Offer a 60-second HR recovery measurement after VO2 doses
+    #[serde(default)]
+    pub recovery_check: bool,
+}
+
+/// Warmup prepended to VO2 prescriptions
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "config-schema", derive(schemars::JsonSchema))]
+pub struct WarmupConfig {
+    /// Set to false to skip the warmup
+    #[serde(default = "default_warmup_enabled")]
+    pub enabled: bool,
+
+    /// Catalog movement IDs, performed in order
+    #[serde(default = "default_warmup_movements")]
+    pub movements: Vec<String>,
+
+    /// Seconds spent on each movement
+    #[serde(default = "default_warmup_seconds")]
+    pub seconds_per_movement: u32,
+}
+
+impl Default for WarmupConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_warmup_enabled(),
+            movements: default_warmup_movements(),
+            seconds_per_movement: default_warmup_seconds(),
+        }
+    }
+}
+
+impl WarmupConfig {
+    /// Warmup blocks for the engine (empty when disabled)
+    pub fn blocks(&self) -> Vec<MicrodoseBlock> {
+        if !self.enabled {
+            return vec![];
+        }
+        self.movements
+            .iter()
+            .map(|movement_id| MicrodoseBlock {
+                movement_id: movement_id.clone(),
+                movement_style: MovementStyle::None,
+                duration_hint_seconds: self.seconds_per_movement,
+                interval: None,
+                ladder: None,
+                metrics: vec![],
+            })
+            .collect()
+    }
+}
+
 /// Custom mobility drill definition
 #[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "config-schema", derive(schemars::JsonSchema))]
 pub struct CustomMobilityDrill {
     pub id: String,
     pub name: String,
@@ -81,12 +506,219 @@ pub struct CustomMobilityDrill {
 
 /// Mobility drills configuration
 #[derive(Clone, Debug, Serialize, Deserialize, Default)]
+#[cfg_attr(feature = "config-schema", derive(schemars::JsonSchema))]
 pub struct MobilityConfig {
     #[serde(default)]
     pub custom: Vec<CustomMobilityDrill>,
 }
 
+/// Log output format
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "config-schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum LogFormat {
+    /// Human-readable compact lines
+    #[default]
+    Text,
+    /// One JSON object per line (for journald/ELK shipping)
+    Json,
+}
+
+/// Logging configuration
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "config-schema", derive(schemars::JsonSchema))]
+pub struct LoggingConfig {
+    #[serde(default)]
+    pub format: LogFormat,
+
+    /// Rotate file logs once they exceed this size (0 disables size rotation)
+    #[serde(default = "default_log_max_size_mb")]
+    pub max_size_mb: u64,
+
+    /// Number of rotated files to keep
+    #[serde(default = "default_log_max_files")]
+    pub max_files: usize,
+
+    /// Also rotate file logs when the local date changes
+    #[serde(default = "default_log_rotate_daily")]
+    pub rotate_daily: bool,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            format: LogFormat::default(),
+            max_size_mb: default_log_max_size_mb(),
+            max_files: default_log_max_files(),
+            rotate_daily: default_log_rotate_daily(),
+        }
+    }
+}
+
+/// Tone set for timer cues
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "config-schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum SoundStyle {
+    /// Short single tones
+    #[default]
+    Beep,
+    /// Rising two- and three-note chimes
+    Chime,
+}
+
+/// Audio cues during interval timers (played only in builds with the
+/// `sound` feature)
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "config-schema", derive(schemars::JsonSchema))]
+pub struct SoundConfig {
+    /// Play cues at minute rollovers, the last 10 seconds and completion
+    #[serde(default)]
+    pub enabled: bool,
+
+    #[serde(default)]
+    pub style: SoundStyle,
+
+    /// Playback volume, 0.0-1.0
+    #[serde(default = "default_sound_volume")]
+    pub volume: f32,
+}
+
+impl Default for SoundConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            style: SoundStyle::default(),
+            volume: default_sound_volume(),
+        }
+    }
+}
+
+/// How the tray popup shows a dose's reference video
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "config-schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum ReferenceMedia {
+    /// Only the "Learn" link
+    #[default]
+    Link,
+    /// The video's thumbnail (downloaded once, then cached) above the link
+    Thumbnail,
+    /// An embedded player; tray builds without the `webkit` feature show the
+    /// thumbnail instead
+    Player,
+}
+
+/// How the tray presents a prescription
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "config-schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum PopupStyle {
+    /// The full window
+    #[default]
+    Full,
+    /// A small window with the name, reps, Done, Skip and Details…
+    Compact,
+    /// Compact for GTG-friendly doses, the full window otherwise
+    Quick,
+}
+
+impl PopupStyle {
+    /// Whether `definition` gets the compact popup
+    ///
+    /// Doses needing more than Done (a timer, an AMRAP total or a circuit
+    /// checklist) always get the full window.
+    pub fn compact_for(&self, definition: &MicrodoseDefinition) -> bool {
+        let fits = definition.interval().is_none()
+            && !definition.is_amrap()
+            && !crate::circuit::is_circuit(definition);
+        match self {
+            PopupStyle::Full => false,
+            PopupStyle::Compact => fits,
+            PopupStyle::Quick => fits && definition.gtg_friendly,
+        }
+    }
+}
+
+/// Tray popup settings
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "config-schema", derive(schemars::JsonSchema))]
+pub struct TrayConfig {
+    /// Extra media for YouTube reference links (other links stay a link)
+    #[serde(default)]
+    pub reference_media: ReferenceMedia,
+
+    /// Compact popup for quick doses, with Details… for the full window
+    #[serde(default)]
+    pub popup: PopupStyle,
+
+    /// Minutes an untouched prescription popup stays open before closing
+    /// itself; unset keeps it open until answered
+    #[serde(default)]
+    pub auto_close_minutes: Option<u32>,
+
+    /// Log an auto-closed popup as a soft skip in `skips.jsonl`
+    #[serde(default = "default_soft_skip_on_close")]
+    pub soft_skip_on_close: bool,
+
+    /// Minutes after an auto-close to show the popup once more (only while
+    /// doses are still due); unset doesn't re-remind
+    #[serde(default)]
+    pub remind_again_minutes: Option<u32>,
+}
+
+impl Default for TrayConfig {
+    fn default() -> Self {
+        Self {
+            reference_media: ReferenceMedia::default(),
+            popup: PopupStyle::default(),
+            auto_close_minutes: None,
+            soft_skip_on_close: default_soft_skip_on_close(),
+            remind_again_minutes: None,
+        }
+    }
+}
+
+fn default_soft_skip_on_close() -> bool {
+    true
+}
+
+/// Markdown daily-note journaling
+#[derive(Clone, Debug, Serialize, Deserialize, Default)]
+#[cfg_attr(feature = "config-schema", derive(schemars::JsonSchema))]
+pub struct JournalConfig {
+    /// Daily-note path, e.g. "~/Notes/Daily/{date}.md"; `{date}`
+    /// (YYYY-MM-DD), `{year}`, `{month}` and `{day}` are the local date.
+    /// Unset disables journaling
+    #[serde(default)]
+    pub daily_note: Option<String>,
+}
+
+/// JSON status file for home automation (e.g. a Home Assistant sensor)
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "config-schema", derive(schemars::JsonSchema))]
+pub struct StatusConfig {
+    /// Where to write the status after each dose and `krep status`; unset
+    /// writes none
+    #[serde(default)]
+    pub file: Option<PathBuf>,
+
+    /// Minutes after a session before the next dose counts as due
+    #[serde(default = "default_due_after_minutes")]
+    pub due_after_minutes: u32,
+}
+
+impl Default for StatusConfig {
+    fn default() -> Self {
+        Self {
+            file: None,
+            due_after_minutes: default_due_after_minutes(),
+        }
+    }
+}
+
 // Default value functions
+#[cfg(feature = "fs")]
 fn default_data_dir() -> PathBuf {
     let base = dirs::data_local_dir().unwrap_or_else(|| {
         let home = std::env::var("HOME").expect("HOME environment variable not set");
@@ -95,6 +727,24 @@ fn default_data_dir() -> PathBuf {
     base.join("krep")
 }
 
+/// Without `fs` there is no XDG lookup; embedders supply their own storage
+#[cfg(not(feature = "fs"))]
+fn default_data_dir() -> PathBuf {
+    PathBuf::from("krep")
+}
+
+fn default_due_after_minutes() -> u32 {
+    60
+}
+
+fn default_sound_volume() -> f32 {
+    0.5
+}
+
+fn default_lock_timeout_secs() -> u64 {
+    10
+}
+
 fn default_equipment() -> Vec<String> {
     vec!["kettlebell".into(), "pullup_bar".into(), "bands".into()]
 }
@@ -107,27 +757,229 @@ fn default_kb_swing_max_reps() -> i32 {
     15
 }
 
+fn default_vo2_gap_hours() -> u32 {
+    4
+}
+
+fn default_auto_rollup() -> bool {
+    true
+}
+
+fn default_auto_rollup_wal_kb() -> u64 {
+    256
+}
+
+fn default_abandon_after_minutes() -> u32 {
+    60
+}
+
+fn default_rotation() -> Vec<MicrodoseCategory> {
+    vec![
+        MicrodoseCategory::Vo2,
+        MicrodoseCategory::Gtg,
+        MicrodoseCategory::Mobility,
+    ]
+}
+
+fn default_warmup_enabled() -> bool {
+    true
+}
+
+fn default_warmup_movements() -> Vec<String> {
+    vec!["jumping_jack".into(), "hip_hinge".into()]
+}
+
+fn default_warmup_seconds() -> u32 {
+    30
+}
+
+fn default_log_max_size_mb() -> u64 {
+    10
+}
+
+fn default_log_max_files() -> usize {
+    5
+}
+
+fn default_log_rotate_daily() -> bool {
+    true
+}
+
 impl Config {
     /// Load configuration from the standard config path
+    ///
+    /// Drop-ins and environment overrides apply whether or not the file exists.
+    #[cfg(feature = "config-toml")]
     pub fn load() -> Result<Self> {
+        Self::load_profile(None)
+    }
+
+    /// Load configuration from the standard config path with a profile
+    /// (`None` uses [`PROFILE_ENV`], if set)
+    #[cfg(feature = "config-toml")]
+    pub fn load_profile(profile: Option<&str>) -> Result<Self> {
         let config_path = Self::default_config_path();
-        if config_path.exists() {
-            Self::load_from(&config_path)
-        } else {
+        if !config_path.exists() && drop_in_files(&config_path)?.is_empty() {
             tracing::info!("No config file found at {:?}, using defaults", config_path);
-            Ok(Self::default())
         }
+        Self::load_from_profile(&config_path, profile)
     }
 
     /// Load configuration from a specific path
+    #[cfg(feature = "config-toml")]
     pub fn load_from(path: &Path) -> Result<Self> {
-        let contents = std::fs::read_to_string(path)?;
-        let config: Config = toml::from_str(&contents)?;
-        tracing::info!("Loaded config from {:?}", path);
+        Self::load_from_profile(path, None)
+    }
+
+    /// Load configuration from a specific path with a profile
+    ///
+    /// The `config.d/*.toml` drop-ins next to it are merged over it in
+    /// lexical order (see [`drop_in_files`]), then the profile (`profile`,
+    /// else [`PROFILE_ENV`]) and environment overrides are applied. A
+    /// missing main file counts as empty.
+    #[cfg(feature = "config-toml")]
+    pub fn load_from_profile(path: &Path, profile: Option<&str>) -> Result<Self> {
+        let mut merged = if path.exists() {
+            let table = read_table(path)?;
+            tracing::info!("Loaded config from {:?}", path);
+            table
+        } else {
+            toml::Table::new()
+        };
+        for drop_in in drop_in_files(path)? {
+            merge_tables(&mut merged, read_table(&drop_in)?);
+            tracing::info!("Merged config drop-in {:?}", drop_in);
+        }
+
+        let mut config: Config = toml::Value::Table(merged).try_into()?;
+        let profile = profile
+            .map(str::to_string)
+            .or_else(|| std::env::var(PROFILE_ENV).ok().filter(|p| !p.is_empty()));
+        if let Some(name) = profile {
+            config.apply_profile(&name)?;
+        }
+        config.apply_env()?;
+        Ok(config)
+    }
+
+    /// Apply the overrides of `[profile.<name>]` and record it as active
+    pub fn apply_profile(&mut self, name: &str) -> Result<()> {
+        let Some(profile) = self.profile.get(name).cloned() else {
+            let defined: Vec<&str> = self.profile.keys().map(String::as_str).collect();
+            return Err(Error::Config(format!(
+                "Unknown profile '{}' (defined: {})",
+                name,
+                if defined.is_empty() {
+                    "none".to_string()
+                } else {
+                    defined.join(", ")
+                }
+            )));
+        };
+
+        if let Some(equipment) = profile.equipment {
+            self.equipment.available = equipment;
+        }
+        if let Some(rotation) = profile.rotation {
+            self.prescription.rotation = rotation;
+        }
+        if let Some(gtg_friendly_only) = profile.gtg_friendly_only {
+            self.prescription.gtg_friendly_only = gtg_friendly_only;
+        }
+        if let Some(data_dir) = profile.data_dir {
+            self.data.data_dir = data_dir;
+        }
+        self.active_profile = Some(name.to_string());
+        tracing::info!("Using profile '{}'", name);
+        Ok(())
+    }
+
+    /// The config household member `name` runs with: this one with their
+    /// profile (and so their data directory) applied
+    pub fn for_member(&self, name: &str) -> Result<Self> {
+        let mut config = self.clone();
+        config.apply_profile(name)?;
         Ok(config)
     }
 
+    /// Apply the `KREP_*` overrides in [`ENV_OVERRIDES`] from the environment
+    pub fn apply_env(&mut self) -> Result<()> {
+        self.apply_overrides(|name| std::env::var(name).ok())
+    }
+
+    /// Apply [`ENV_OVERRIDES`] using `var` to look up each variable
+    ///
+    /// Fails on the first value that doesn't parse; range checks are left to
+    /// [`Config::validate`].
+    pub fn apply_overrides(&mut self, var: impl Fn(&str) -> Option<String>) -> Result<()> {
+        for (name, key) in ENV_OVERRIDES {
+            let Some(value) = var(name) else {
+                continue;
+            };
+            let value = value.trim();
+            if value.is_empty() {
+                continue;
+            }
+            let invalid = |e: &dyn std::fmt::Display| {
+                Error::Config(format!(
+                    "{} ({}): invalid value '{}': {}",
+                    name, key, value, e
+                ))
+            };
+            let list = || -> Vec<String> {
+                value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|s| !s.is_empty())
+                    .map(String::from)
+                    .collect()
+            };
+            let boolean = || match value.to_lowercase().as_str() {
+                "true" | "1" | "yes" | "on" => Ok(true),
+                "false" | "0" | "no" | "off" => Ok(false),
+                _ => Err(invalid(&"expected true or false")),
+            };
+
+            match key {
+                "data.data_dir" => self.data.data_dir = PathBuf::from(value),
+                "data.exclude_anomalies" => self.data.exclude_anomalies = boolean()?,
+                "data.lock_timeout_secs" => {
+                    self.data.lock_timeout_secs = value.parse().map_err(|e| invalid(&e))?
+                }
+                "equipment.available" => self.equipment.available = list(),
+                "progression.burpee_rep_ceiling" => {
+                    self.progression.burpee_rep_ceiling = value.parse().map_err(|e| invalid(&e))?
+                }
+                "progression.kb_swing_max_reps" => {
+                    self.progression.kb_swing_max_reps = value.parse().map_err(|e| invalid(&e))?
+                }
+                "prescription.rotation" => {
+                    self.prescription.rotation = list()
+                        .iter()
+                        .map(|c| c.parse())
+                        .collect::<Result<_>>()
+                        .map_err(|e| invalid(&e))?
+                }
+                "prescription.vo2_gap_hours" => {
+                    self.prescription.vo2_gap_hours = value.parse().map_err(|e| invalid(&e))?
+                }
+                "time.timezone" => self.time.timezone = Some(value.to_string()),
+                "heart_rate.max_hr" => {
+                    self.heart_rate.max_hr = Some(value.parse().map_err(|e| invalid(&e))?)
+                }
+                "heart_rate.age" => {
+                    self.heart_rate.age = Some(value.parse().map_err(|e| invalid(&e))?)
+                }
+                "warmup.enabled" => self.warmup.enabled = boolean()?,
+                _ => unreachable!("unhandled override {}", key),
+            }
+            tracing::debug!("{} overrides {}", name, key);
+        }
+        Ok(())
+    }
+
     /// Get the default config file path
+    #[cfg(feature = "fs")]
     pub fn default_config_path() -> PathBuf {
         let base = dirs::config_dir().unwrap_or_else(|| {
             let home = std::env::var("HOME").expect("HOME environment variable not set");
@@ -136,13 +988,21 @@ impl Config {
         base.join("krep").join("config.toml")
     }
 
+    /// Get the default config file path (relative when built without `fs`)
+    #[cfg(not(feature = "fs"))]
+    pub fn default_config_path() -> PathBuf {
+        PathBuf::from("krep").join("config.toml")
+    }
+
     /// Save the current configuration to the default path
+    #[cfg(feature = "config-toml")]
     pub fn save(&self) -> Result<()> {
         let config_path = Self::default_config_path();
         self.save_to(&config_path)
     }
 
     /// Save the current configuration to a specific path
+    #[cfg(feature = "config-toml")]
     pub fn save_to(&self, path: &Path) -> Result<()> {
         // Ensure parent directory exists
         if let Some(parent) = path.parent() {
@@ -155,9 +1015,895 @@ impl Config {
         tracing::info!("Saved config to {:?}", path);
         Ok(())
     }
+
+    /// Timezone for day boundaries and time-of-day rules
+    ///
+    /// Falls back to the system timezone (with a warning) if the configured
+    /// name is invalid; [`Config::validate`] reports the error.
+    pub fn timezone(&self) -> crate::LocalTz {
+        crate::LocalTz::from_name(self.time.timezone.as_deref()).unwrap_or_else(|e| {
+            tracing::warn!("{}; using system timezone", e);
+            crate::LocalTz::System
+        })
+    }
+
+    /// Rotation weights by category (unknown names are left out;
+    /// [`Config::validate`] reports them)
+    pub fn rotation_weights(&self) -> HashMap<MicrodoseCategory, u32> {
+        self.prescription
+            .weights
+            .iter()
+            .filter_map(|(name, weight)| Some((name.parse().ok()?, *weight)))
+            .collect()
+    }
+
+    /// The `[[phases]]` entry covering the local date of `now`, if any
+    pub fn phase_at(&self, now: DateTime<Utc>) -> Option<&crate::phase::Phase> {
+        crate::phase::current(&self.phases, self.timezone().date_of(now))
+    }
+
+    /// [`Config::rotation_weights`] with the current phase's weights on top
+    pub fn rotation_weights_at(&self, now: DateTime<Utc>) -> HashMap<MicrodoseCategory, u32> {
+        let mut weights = self.rotation_weights();
+        if let Some(phase) = self.phase_at(now) {
+            weights.extend(
+                phase
+                    .weights
+                    .iter()
+                    .filter_map(|(name, weight)| Some((name.parse().ok()?, *weight))),
+            );
+        }
+        weights
+    }
+
+    /// Progression rules applied per upgrade at `now` (1 outside any phase,
+    /// 0 during a deload)
+    pub fn progression_step_at(&self, now: DateTime<Utc>) -> u32 {
+        self.phase_at(now)
+            .map_or(1, crate::phase::Phase::progression_step)
+    }
+
+    /// Max HR in bpm, measured or estimated from age (`None` if neither is set)
+    pub fn max_hr(&self) -> Option<u32> {
+        self.heart_rate
+            .max_hr
+            .or_else(|| self.heart_rate.age.map(crate::heart_rate::max_hr_from_age))
+    }
+
+    /// Weekly session targets by category (unknown names are left out;
+    /// [`Config::validate`] reports them)
+    pub fn weekly_targets(&self) -> HashMap<MicrodoseCategory, u32> {
+        self.prescription
+            .weekly_targets
+            .iter()
+            .filter_map(|(name, target)| Some((name.parse().ok()?, *target)))
+            .collect()
+    }
+
+    /// Office hours windows (invalid ones are left out; [`Config::validate`]
+    /// reports them)
+    pub fn office_hours(&self) -> Vec<crate::office::OfficeWindow> {
+        self.prescription
+            .office_hours
+            .iter()
+            .filter_map(|window| window.parse().ok())
+            .collect()
+    }
+
+    /// Whether office mode is on at `now`: inside `office_hours` or switched
+    /// on with `krep mode office on`
+    pub fn office_mode(&self, state: &crate::UserMicrodoseState, now: DateTime<Utc>) -> bool {
+        crate::office::in_office_hours(&self.office_hours(), &self.timezone(), now)
+            || crate::office::active_until(state, now).is_some()
+    }
+
+    /// Value for [`UserContext::gtg_friendly_only`](crate::UserContext) at
+    /// `now`: `gtg_friendly_only`, or office mode
+    pub fn gtg_friendly_only_at(
+        &self,
+        state: &crate::UserMicrodoseState,
+        now: DateTime<Utc>,
+    ) -> bool {
+        self.prescription.gtg_friendly_only || self.office_mode(state, now)
+    }
+
+    /// Off days from `[off] days` (invalid entries are left out and reported
+    /// by [`Config::validate`]) and those set with `krep off`
+    pub fn off_days(&self, state: &crate::UserMicrodoseState) -> Vec<crate::off_days::OffRange> {
+        let mut ranges: Vec<crate::off_days::OffRange> = self
+            .off
+            .days
+            .iter()
+            .filter_map(|days| days.parse().ok())
+            .collect();
+        ranges.extend(state.off_days.iter().copied());
+        ranges
+    }
+
+    /// The off-day range covering the local date at `now`, if any
+    pub fn off_day_at(
+        &self,
+        state: &crate::UserMicrodoseState,
+        now: DateTime<Utc>,
+    ) -> Option<crate::off_days::OffRange> {
+        crate::off_days::covering(&self.off_days(state), self.timezone().date_of(now)).copied()
+    }
+
+    /// Value for [`UserContext::planned_strength`](crate::UserContext) at
+    /// `now`: the session scheduled today in `[strength]`, if it is still
+    /// ahead (or its start time is unset)
+    pub fn planned_strength_at(&self, now: DateTime<Utc>) -> Option<StrengthSessionType> {
+        let tz = self.timezone();
+        if self
+            .strength
+            .start_time()
+            .is_some_and(|at| tz.time_of(now) >= at)
+        {
+            return None;
+        }
+        self.strength.session_on(tz.date_of(now).weekday())
+    }
+
+    /// [`Config::validate`] plus checks against the catalog in use: every
+    /// rotation category (and so every weighted one) and every targeted
+    /// category must have microdoses
+    pub fn validate_with_catalog(&self, catalog: &crate::Catalog) -> Vec<String> {
+        let mut errors = self.validate();
+        for category in &self.prescription.rotation {
+            if catalog.in_category(category).next().is_none() {
+                errors.push(format!(
+                    "prescription.rotation: catalog has no '{}' microdoses",
+                    category.as_str()
+                ));
+            }
+        }
+        for category in self.weekly_targets().keys() {
+            if catalog.in_category(category).next().is_none() {
+                errors.push(format!(
+                    "prescription.weekly_targets: catalog has no '{}' microdoses",
+                    category.as_str()
+                ));
+            }
+        }
+
+        // Rep ceilings below the catalog's minimum could never be prescribed
+        // (non-positive values are already reported by `validate`)
+        for (field, definition_id, value) in [
+            (
+                "progression.burpee_rep_ceiling",
+                "emom_burpee_5m",
+                self.progression.burpee_rep_ceiling,
+            ),
+            (
+                "progression.kb_swing_max_reps",
+                "emom_kb_swing_5m",
+                self.progression.kb_swing_max_reps,
+            ),
+        ] {
+            let min = catalog.definition(definition_id).and_then(|d| {
+                d.blocks
+                    .iter()
+                    .flat_map(|b| &b.metrics)
+                    .find_map(|m| match m {
+                        MetricSpec::Reps { min, .. } => Some(*min),
+                        _ => None,
+                    })
+            });
+            if let Some(min) = min.filter(|min| value > 0 && value < *min) {
+                errors.push(format!(
+                    "{} must be at least the catalog minimum of {} reps for {} (got {})",
+                    field, min, definition_id, value
+                ));
+            }
+        }
+        errors
+    }
+
+    /// Validate the configuration for internal consistency
+    ///
+    /// Returns a list of validation errors, or empty Vec if valid.
+    pub fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+
+        if self.progression.burpee_rep_ceiling <= 0 {
+            errors.push(format!(
+                "progression.burpee_rep_ceiling must be positive (got {})",
+                self.progression.burpee_rep_ceiling
+            ));
+        }
+        if self.progression.kb_swing_max_reps <= 0 {
+            errors.push(format!(
+                "progression.kb_swing_max_reps must be positive (got {})",
+                self.progression.kb_swing_max_reps
+            ));
+        }
+
+        if self.equipment.available.iter().any(|e| e.trim().is_empty()) {
+            errors.push("equipment.available contains an empty entry".to_string());
+        }
+
+        if self.prescription.rotation.is_empty() {
+            errors.push("prescription.rotation must list at least one category".to_string());
+        }
+        for (name, profile) in &self.profile {
+            if profile.rotation.as_ref().is_some_and(|r| r.is_empty()) {
+                errors.push(format!(
+                    "profile.{}.rotation must list at least one category",
+                    name
+                ));
+            }
+            if profile
+                .equipment
+                .iter()
+                .flatten()
+                .any(|e| e.trim().is_empty())
+            {
+                errors.push(format!(
+                    "profile.{}.equipment contains an empty entry",
+                    name
+                ));
+            }
+        }
+        for name in &self.household.members {
+            match self.profile.get(name) {
+                None => errors.push(format!(
+                    "household.members: '{}' is not a defined profile",
+                    name
+                )),
+                Some(profile) if profile.data_dir.is_none() => errors.push(format!(
+                    "household.members: profile.{} needs its own data_dir",
+                    name
+                )),
+                Some(_) => {}
+            }
+        }
+        let mut seen_categories = std::collections::HashSet::new();
+        for category in &self.prescription.rotation {
+            if !seen_categories.insert(category) {
+                errors.push(format!(
+                    "prescription.rotation lists '{}' more than once",
+                    category.as_str()
+                ));
+            }
+        }
+
+        for (name, weight) in &self.prescription.weights {
+            match name.parse::<MicrodoseCategory>() {
+                Ok(category) if !self.prescription.rotation.contains(&category) => {
+                    errors.push(format!(
+                        "prescription.weights.{}: category is not in prescription.rotation",
+                        name
+                    ));
+                }
+                Ok(_) => {}
+                Err(e) => errors.push(format!("prescription.weights.{}: {}", name, e)),
+            }
+            if *weight == 0 {
+                errors.push(format!(
+                    "prescription.weights.{} must be at least 1 (remove it from rotation instead)",
+                    name
+                ));
+            }
+        }
+
+        if self.prescription.vo2_gap_hours == 0 {
+            errors.push("prescription.vo2_gap_hours must be at least 1".to_string());
+        }
+
+        if self.data.auto_rollup_wal_kb == 0 {
+            errors.push("data.auto_rollup_wal_kb must be at least 1".to_string());
+        }
+
+        if self.session.abandon_after_minutes == 0 {
+            errors.push("session.abandon_after_minutes must be at least 1".to_string());
+        }
+
+        for (index, phase) in self.phases.iter().enumerate() {
+            if phase.end < phase.start {
+                errors.push(format!("phases: {} ends before it starts", phase.label()));
+            }
+            if let Some(other) = self.phases[..index]
+                .iter()
+                .find(|other| other.start <= phase.end && phase.start <= other.end)
+            {
+                errors.push(format!(
+                    "phases: {} overlaps {}",
+                    phase.label(),
+                    other.label()
+                ));
+            }
+            for (name, weight) in &phase.weights {
+                if let Err(e) = name.parse::<MicrodoseCategory>() {
+                    errors.push(format!("phases: {} weights.{}: {}", phase.label(), name, e));
+                }
+                if *weight == 0 {
+                    errors.push(format!(
+                        "phases: {} weights.{} must be at least 1",
+                        phase.label(),
+                        name
+                    ));
+                }
+            }
+        }
+
+        let mut scheduled = std::collections::HashSet::new();
+        for (field, days) in [
+            ("lower", &self.strength.lower),
+            ("upper", &self.strength.upper),
+            ("full", &self.strength.full),
+        ] {
+            for day in days {
+                match day.parse::<Weekday>() {
+                    Ok(weekday) if !scheduled.insert(weekday) => errors.push(format!(
+                        "strength.{}: {} already has a strength session",
+                        field, day
+                    )),
+                    Ok(_) => {}
+                    Err(_) => errors.push(format!(
+                        "strength.{}: '{}' is not a weekday (expected mon, tue, ...)",
+                        field, day
+                    )),
+                }
+            }
+        }
+        if let Some(at) = self
+            .strength
+            .at
+            .as_deref()
+            .filter(|_| self.strength.start_time().is_none())
+        {
+            errors.push(format!("strength.at: '{}' is not a HH:MM time", at));
+        }
+
+        if self.tray.auto_close_minutes == Some(0) {
+            errors.push("tray.auto_close_minutes must be at least 1".to_string());
+        }
+        match self.tray.remind_again_minutes {
+            Some(0) => errors.push("tray.remind_again_minutes must be at least 1".to_string()),
+            Some(_) if self.tray.auto_close_minutes.is_none() => {
+                errors.push("tray.remind_again_minutes needs tray.auto_close_minutes".to_string())
+            }
+            _ => {}
+        }
+
+        for days in &self.off.days {
+            if let Err(e) = days.parse::<crate::off_days::OffRange>() {
+                errors.push(format!("off.days: {}", e));
+            }
+        }
+
+        for window in &self.prescription.office_hours {
+            if let Err(e) = window.parse::<crate::office::OfficeWindow>() {
+                errors.push(format!("prescription.office_hours: {}", e));
+            }
+        }
+
+        for (name, target) in &self.prescription.weekly_targets {
+            if let Err(e) = name.parse::<MicrodoseCategory>() {
+                errors.push(format!("prescription.weekly_targets.{}: {}", name, e));
+            }
+            if *target == 0 {
+                errors.push(format!(
+                    "prescription.weekly_targets.{} must be at least 1 (remove it instead)",
+                    name
+                ));
+            }
+        }
+
+        if let Some(max_hr) = self.heart_rate.max_hr {
+            if !(100..=230).contains(&max_hr) {
+                errors.push(format!(
+                    "heart_rate.max_hr must be 100-230 bpm (got {})",
+                    max_hr
+                ));
+            }
+        }
+        if let Some(age) = self.heart_rate.age {
+            if !(10..=100).contains(&age) {
+                errors.push(format!("heart_rate.age must be 10-100 (got {})", age));
+            }
+        }
+
+        if !(0.0..=1.0).contains(&self.sound.volume) {
+            errors.push(format!(
+                "sound.volume must be 0.0-1.0 (got {})",
+                self.sound.volume
+            ));
+        }
+
+        #[cfg(feature = "fs")]
+        if let Some(template) = &self.journal.daily_note {
+            for placeholder in crate::journal::unknown_placeholders(template) {
+                errors.push(format!(
+                    "journal.daily_note: unknown placeholder {} (expected {})",
+                    placeholder,
+                    crate::journal::PLACEHOLDERS.join(", ")
+                ));
+            }
+        }
+
+        if self.status.due_after_minutes == 0 {
+            errors.push("status.due_after_minutes must be positive (got 0)".into());
+        }
+
+        if let Err(e) = crate::LocalTz::from_name(self.time.timezone.as_deref()) {
+            errors.push(format!("time.timezone: {}", e));
+        }
+
+        if self.warmup.enabled {
+            if !(1..=300).contains(&self.warmup.seconds_per_movement) {
+                errors.push(format!(
+                    "warmup.seconds_per_movement must be 1-300 (got {})",
+                    self.warmup.seconds_per_movement
+                ));
+            }
+            if self.warmup.movements.iter().any(|m| m.trim().is_empty()) {
+                errors.push("warmup.movements contains an empty entry".to_string());
+            }
+        }
+
+        if self.logging.max_files == 0 {
+            errors.push("logging.max_files must be at least 1".to_string());
+        }
+
+        let mut seen_ids = std::collections::HashSet::new();
+        for drill in &self.mobility.custom {
+            if drill.id.trim().is_empty() {
+                errors.push("mobility.custom drill has empty id".to_string());
+            } else if !seen_ids.insert(drill.id.as_str()) {
+                errors.push(format!(
+                    "mobility.custom drill id '{}' is duplicated",
+                    drill.id
+                ));
+            }
+            if drill.name.trim().is_empty() {
+                errors.push(format!(
+                    "mobility.custom drill '{}' has empty name",
+                    drill.id
+                ));
+            }
+        }
+
+        errors
+    }
+
+    /// Describe the differences between this config and a newer one
+    ///
+    /// Each entry reads `section.key: old -> new`. Used to log what a
+    /// live reload actually changed.
+    #[cfg(feature = "config-toml")]
+    pub fn diff(&self, other: &Config) -> Vec<String> {
+        let mut changes = Vec::new();
+        match (toml::Value::try_from(self), toml::Value::try_from(other)) {
+            (Ok(old), Ok(new)) => diff_values("", &old, &new, &mut changes),
+            _ => tracing::warn!("Unable to serialize configs for comparison"),
+        }
+        changes
+    }
+}
+
+/// `*.toml` files in the drop-in directory next to `config_path`, in lexical order
+///
+/// Empty when the directory doesn't exist.
+#[cfg(feature = "config-toml")]
+pub fn drop_in_files(config_path: &Path) -> Result<Vec<PathBuf>> {
+    let dir = config_path
+        .parent()
+        .unwrap_or_else(|| Path::new("."))
+        .join(DROP_IN_DIR);
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(&dir)? {
+        let path = entry?.path();
+        if path.is_file() && path.extension().is_some_and(|e| e == "toml") {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Parse a TOML file into a table, naming the file in parse errors
+///
+/// Deprecated keys are mapped to their new names, with a warning.
+#[cfg(feature = "config-toml")]
+fn read_table(path: &Path) -> Result<toml::Table> {
+    let contents = std::fs::read_to_string(path)?;
+    let mut doc = parse_document(path, &contents)?;
+    let migrations = migrate_document(&mut doc, KEY_MIGRATIONS);
+    if migrations.is_empty() {
+        return toml::from_str(&contents)
+            .map_err(|e| Error::Toml(format!("{}: {}", path.display(), e)));
+    }
+    for migration in &migrations {
+        tracing::warn!(
+            "{}: {}; run `krep config migrate` to update the file",
+            path.display(),
+            migration
+        );
+    }
+    toml::from_str(&doc.to_string()).map_err(|e| Error::Toml(format!("{}: {}", path.display(), e)))
+}
+
+#[cfg(feature = "config-toml")]
+fn parse_document(path: &Path, contents: &str) -> Result<toml_edit::DocumentMut> {
+    contents
+        .parse()
+        .map_err(|e| Error::Toml(format!("{}: {}", path.display(), e)))
+}
+
+/// A deprecated key found in a config file
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KeyMigration {
+    pub from: String,
+    pub to: String,
+    /// The new key was already set, so the old value was dropped
+    pub conflict: bool,
+}
+
+impl std::fmt::Display for KeyMigration {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "'{}' is now '{}'", self.from, self.to)?;
+        if self.conflict {
+            write!(f, " (already set; old value dropped)")?;
+        }
+        Ok(())
+    }
+}
+
+/// Rewrite deprecated keys in a config file in place, keeping comments and
+/// layout. Returns the keys moved; the file is untouched when there are none.
+#[cfg(feature = "config-toml")]
+pub fn migrate_file(path: &Path) -> Result<Vec<KeyMigration>> {
+    use std::io::Write;
+
+    let contents = std::fs::read_to_string(path)?;
+    let mut doc = parse_document(path, &contents)?;
+    let migrations = migrate_document(&mut doc, KEY_MIGRATIONS);
+    if !migrations.is_empty() {
+        // Through a temp file, so a crash mid-write can't truncate the config
+        let dir = path.parent().unwrap_or_else(|| Path::new("."));
+        let mut temp = tempfile::NamedTempFile::new_in(dir)?;
+        temp.write_all(doc.to_string().as_bytes())?;
+        temp.as_file().sync_all()?;
+        temp.persist(path).map_err(|e| Error::Io(e.error))?;
+        tracing::info!("Migrated {} key(s) in {:?}", migrations.len(), path);
+    }
+    Ok(migrations)
+}
+
+/// Move each `(old, new)` key present in `doc`
+///
+/// If the new key is already set it wins and the old value is dropped.
+#[cfg(feature = "config-toml")]
+fn migrate_document(
+    doc: &mut toml_edit::DocumentMut,
+    migrations: &[(&str, &str)],
+) -> Vec<KeyMigration> {
+    let mut applied = Vec::new();
+    for (from, to) in migrations {
+        let from_path: Vec<&str> = from.split('.').collect();
+        let Some((decor, item)) = take_item(doc.as_table_mut(), &from_path) else {
+            continue;
+        };
+        let to_path: Vec<&str> = to.split('.').collect();
+        let conflict = get_item(doc.as_table(), &to_path).is_some();
+        if !conflict {
+            put_item(doc.as_table_mut(), &to_path, decor, item);
+        }
+        applied.push(KeyMigration {
+            from: from.to_string(),
+            to: to.to_string(),
+            conflict,
+        });
+    }
+    applied
+}
+
+#[cfg(feature = "config-toml")]
+fn get_item<'a>(table: &'a dyn toml_edit::TableLike, path: &[&str]) -> Option<&'a toml_edit::Item> {
+    match path {
+        [key] => table.get(key),
+        [key, rest @ ..] => get_item(table.get(key)?.as_table_like()?, rest),
+        [] => None,
+    }
+}
+
+/// Remove the item at `path`, with its key's decor (comments above it)
+#[cfg(feature = "config-toml")]
+fn take_item(
+    table: &mut dyn toml_edit::TableLike,
+    path: &[&str],
+) -> Option<(toml_edit::Decor, toml_edit::Item)> {
+    match path {
+        [key] => {
+            let decor = table.get_key_value(key)?.0.leaf_decor().clone();
+            Some((decor, table.remove(key)?))
+        }
+        [key, rest @ ..] => take_item(table.get_mut(key)?.as_table_like_mut()?, rest),
+        [] => None,
+    }
+}
+
+#[cfg(feature = "config-toml")]
+fn put_item(
+    table: &mut dyn toml_edit::TableLike,
+    path: &[&str],
+    decor: toml_edit::Decor,
+    item: toml_edit::Item,
+) {
+    match path {
+        [key] => {
+            let key = toml_edit::Key::new(*key).with_leaf_decor(decor);
+            table.entry_format(&key).or_insert(item);
+        }
+        [key, rest @ ..] => {
+            if let Some(child) = table
+                .entry(key)
+                .or_insert(toml_edit::table())
+                .as_table_like_mut()
+            {
+                put_item(child, rest, decor, item);
+            }
+        }
+        [] => {}
+    }
+}
+
+/// Merge `overlay` into `base`: tables merge key by key, anything else
+/// (including arrays) replaces the base value
+#[cfg(feature = "config-toml")]
+fn merge_tables(base: &mut toml::Table, overlay: toml::Table) {
+    for (key, value) in overlay {
+        match value {
+            toml::Value::Table(overlay_table) => {
+                if let Some(toml::Value::Table(base_table)) = base.get_mut(&key) {
+                    merge_tables(base_table, overlay_table);
+                } else {
+                    base.insert(key, toml::Value::Table(overlay_table));
+                }
+            }
+            value => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Recursively compare two TOML values, recording changed leaf keys
+#[cfg(feature = "config-toml")]
+fn diff_values(prefix: &str, old: &toml::Value, new: &toml::Value, changes: &mut Vec<String>) {
+    match (old, new) {
+        (toml::Value::Table(old_table), toml::Value::Table(new_table)) => {
+            let keys: std::collections::BTreeSet<&String> =
+                old_table.keys().chain(new_table.keys()).collect();
+            for key in keys {
+                let path = if prefix.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", prefix, key)
+                };
+                match (old_table.get(key), new_table.get(key)) {
+                    (Some(o), Some(n)) => diff_values(&path, o, n, changes),
+                    (Some(o), None) => changes.push(format!("{}: {} -> (unset)", path, o)),
+                    (None, Some(n)) => changes.push(format!("{}: (unset) -> {}", path, n)),
+                    (None, None) => {}
+                }
+            }
+        }
+        (o, n) if o != n => changes.push(format!("{}: {} -> {}", prefix, o, n)),
+        _ => {}
+    }
+}
+
+/// A problem found by [`check_file`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConfigDiagnostic {
+    /// 1-based line in the config file, when it can be located
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl std::fmt::Display for ConfigDiagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "line {}: {}", line, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+/// Check a config file more thoroughly than [`Config::load_from`]
+///
+/// Reports TOML syntax and type errors with their line, deprecated keys,
+/// keys krep doesn't know (usually typos, which loading silently ignores),
+/// everything [`Config::validate_with_catalog`] finds, and whether the data
+/// directory is usable. An empty result means the file is valid. Errors only if the
+/// file can't be read.
+#[cfg(feature = "config-toml")]
+pub fn check_file(path: &Path, catalog: &crate::Catalog) -> Result<Vec<ConfigDiagnostic>> {
+    let contents = std::fs::read_to_string(path)?;
+
+    let mut unknown = Vec::new();
+    let parsed: std::result::Result<Config, _> =
+        serde_ignored::deserialize(toml::Deserializer::new(&contents), |key| {
+            unknown.push(key.to_string())
+        });
+    let config = match parsed {
+        Ok(config) => config,
+        Err(e) => {
+            return Ok(vec![ConfigDiagnostic {
+                line: e
+                    .span()
+                    .map(|span| contents[..span.start].matches('\n').count() + 1),
+                message: e.message().trim().to_string(),
+            }]);
+        }
+    };
+
+    // Deprecated keys are reported as such rather than as unknown, and the
+    // remaining checks see them under their new names
+    let mut doc = parse_document(path, &contents)?;
+    let migrations = migrate_document(&mut doc, KEY_MIGRATIONS);
+    let config = if migrations.is_empty() {
+        config
+    } else {
+        toml::from_str(&doc.to_string())?
+    };
+    let mut diagnostics: Vec<ConfigDiagnostic> = migrations
+        .iter()
+        .map(|migration| ConfigDiagnostic {
+            line: find_key_line(&contents, &migration.from),
+            message: format!("{}; run `krep config migrate`", migration),
+        })
+        .collect();
+    diagnostics.extend(
+        unknown
+            .into_iter()
+            .filter(|key| {
+                !migrations
+                    .iter()
+                    .any(|m| key == &m.from || key.starts_with(&format!("{}.", m.from)))
+            })
+            .map(|key| ConfigDiagnostic {
+                line: find_key_line(&contents, &key),
+                message: format!("unknown key '{}'", key),
+            }),
+    );
+
+    #[cfg_attr(not(feature = "fs"), allow(unused_mut))]
+    let mut problems = config.validate_with_catalog(catalog);
+    #[cfg(feature = "fs")]
+    problems.extend(config.check_paths());
+    diagnostics.extend(problems.into_iter().map(|message| {
+        // Messages start with the dotted key they are about
+        let key = message
+            .split_whitespace()
+            .next()
+            .unwrap_or_default()
+            .trim_end_matches(':');
+        ConfigDiagnostic {
+            line: find_key_line(&contents, key),
+            message,
+        }
+    }));
+
+    Ok(diagnostics)
+}
+
+/// Line (1-based) where a dotted key, or its closest parent, is set
+///
+/// A line-based scan of table headers and `key =` lines; array indices in
+/// the key are ignored, so entries in arrays of tables resolve to the first
+/// one that sets the key.
+#[cfg(feature = "config-toml")]
+fn find_key_line(contents: &str, key: &str) -> Option<usize> {
+    let mut segments: Vec<&str> = key
+        .split('.')
+        .filter(|s| !s.is_empty() && s.parse::<usize>().is_err())
+        .collect();
+
+    while !segments.is_empty() {
+        let wanted = segments.join(".");
+        let mut table = String::new();
+        for (index, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.starts_with('[') {
+                table = line
+                    .trim_matches(|c| c == '[' || c == ']')
+                    .trim()
+                    .to_string();
+                if table == wanted {
+                    return Some(index + 1);
+                }
+            } else if let Some((name, _)) = line.split_once('=') {
+                let name = name.trim().trim_matches('"');
+                let full = if table.is_empty() {
+                    name.to_string()
+                } else {
+                    format!("{}.{}", table, name)
+                };
+                if full == wanted {
+                    return Some(index + 1);
+                }
+            }
+        }
+        segments.pop();
+    }
+    None
+}
+
+#[cfg(feature = "fs")]
+impl Config {
+    /// Whether the data directory, and the directories of any overridden
+    /// WAL/CSV/state paths, exist and are writable or can be created
+    pub fn check_paths(&self) -> Vec<String> {
+        let data_dir = &self.data.data_dir;
+        let mut problems: Vec<String> = check_dir("data.data_dir", data_dir).into_iter().collect();
+
+        let overrides = self.data.path_overrides(data_dir);
+        for (key, path) in &overrides {
+            if path.is_dir() {
+                problems.push(format!("{}: {} is a directory", key, path.display()));
+                continue;
+            }
+            // The signals are written by other tools; krep only reads them
+            if *key == "data.strength_signal_path" || *key == "data.readiness_signal_path" {
+                continue;
+            }
+            if let Some(parent) = path.parent() {
+                problems.extend(check_dir(key, parent));
+            }
+        }
+        for (i, (key, path)) in overrides.iter().enumerate() {
+            if let Some((other, _)) = overrides[..i].iter().find(|(_, p)| p == path) {
+                problems.push(format!(
+                    "{}: same file as {} ({})",
+                    key,
+                    other,
+                    path.display()
+                ));
+            }
+        }
+        problems
+    }
+}
+
+/// Whether `dir` is a writable directory, or can be created
+#[cfg(feature = "fs")]
+fn check_dir(key: &str, dir: &Path) -> Option<String> {
+    if dir.exists() {
+        if !dir.is_dir() {
+            return Some(format!("{}: {} is not a directory", key, dir.display()));
+        }
+        return tempfile::tempfile_in(dir)
+            .err()
+            .map(|e| format!("{}: {} is not writable ({})", key, dir.display(), e));
+    }
+
+    // Created on first use, so the closest existing parent must be writable
+    match dir.ancestors().skip(1).find(|p| p.is_dir()) {
+        Some(parent) => tempfile::tempfile_in(parent).err().map(|e| {
+            format!(
+                "{}: {} does not exist and can't be created in {} ({})",
+                key,
+                dir.display(),
+                parent.display(),
+                e
+            )
+        }),
+        None => Some(format!(
+            "{}: {} does not exist and has no existing parent",
+            key,
+            dir.display()
+        )),
+    }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "config-toml"))]
 mod tests {
     use super::*;
 
@@ -192,4 +1938,796 @@ burpee_rep_ceiling = 12
         assert_eq!(config.progression.burpee_rep_ceiling, 12);
         assert_eq!(config.progression.kb_swing_max_reps, 15); // default
     }
+
+    #[test]
+    fn test_default_config_validates() {
+        assert!(Config::default().validate().is_empty());
+    }
+
+    #[test]
+    fn test_validate_rejects_bad_values() {
+        let mut config = Config::default();
+        config.progression.burpee_rep_ceiling = 0;
+        config.mobility.custom = vec![
+            CustomMobilityDrill {
+                id: "neck".into(),
+                name: "Neck CARs".into(),
+                url: None,
+            },
+            CustomMobilityDrill {
+                id: "neck".into(),
+                name: "Neck CARs again".into(),
+                url: None,
+            },
+        ];
+
+        let errors = config.validate();
+        assert_eq!(errors.len(), 2, "unexpected errors: {:?}", errors);
+    }
+
+    #[test]
+    fn test_sound_config() {
+        let config: Config = toml::from_str(
+            r#"
+[sound]
+enabled = true
+style = "chime"
+volume = 0.8
+"#,
+        )
+        .unwrap();
+        assert!(config.sound.enabled);
+        assert_eq!(config.sound.style, SoundStyle::Chime);
+        assert!(config.validate().is_empty());
+
+        let mut config = Config::default();
+        assert!(!config.sound.enabled);
+        config.sound.volume = 1.5;
+        assert_eq!(
+            config.validate(),
+            vec!["sound.volume must be 0.0-1.0 (got 1.5)"]
+        );
+    }
+
+    #[test]
+    fn test_tray_config() {
+        let config: Config = toml::from_str(
+            r#"
+[tray]
+reference_media = "thumbnail"
+"#,
+        )
+        .unwrap();
+        assert_eq!(config.tray.reference_media, ReferenceMedia::Thumbnail);
+        assert_eq!(Config::default().tray.reference_media, ReferenceMedia::Link);
+        assert!(toml::from_str::<Config>("[tray]\nreference_media = \"gif\"").is_err());
+        assert!(config.tray.soft_skip_on_close);
+        assert_eq!(config.tray.auto_close_minutes, None);
+
+        let mut config: Config = toml::from_str(
+            r#"
+[tray]
+auto_close_minutes = 10
+remind_again_minutes = 30
+soft_skip_on_close = false
+"#,
+        )
+        .unwrap();
+        assert_eq!(config.tray.auto_close_minutes, Some(10));
+        assert!(!config.tray.soft_skip_on_close);
+        assert!(config.validate().is_empty());
+
+        config.tray.auto_close_minutes = None;
+        assert_eq!(
+            config.validate(),
+            vec!["tray.remind_again_minutes needs tray.auto_close_minutes"]
+        );
+    }
+
+    #[test]
+    fn test_popup_style() {
+        let config: Config = toml::from_str("[tray]\npopup = \"quick\"").unwrap();
+        assert_eq!(config.tray.popup, PopupStyle::Quick);
+        assert_eq!(Config::default().tray.popup, PopupStyle::Full);
+
+        let catalog = crate::build_default_catalog();
+        let gtg = catalog
+            .microdoses()
+            .values()
+            .find(|d| d.gtg_friendly && PopupStyle::Compact.compact_for(d))
+            .unwrap();
+        assert!(PopupStyle::Quick.compact_for(gtg));
+        assert!(!PopupStyle::Full.compact_for(gtg));
+
+        // An EMOM needs its timer
+        let emom = &catalog.microdoses()["emom_burpee_5m"];
+        assert!(!PopupStyle::Compact.compact_for(emom));
+        assert!(!PopupStyle::Quick.compact_for(emom));
+    }
+
+    #[test]
+    fn test_journal_config() {
+        let config: Config = toml::from_str(
+            r#"
+[journal]
+daily_note = "~/Notes/Daily/{date}.md"
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.journal.daily_note.as_deref(),
+            Some("~/Notes/Daily/{date}.md")
+        );
+        assert!(config.validate().is_empty());
+
+        let mut config = Config::default();
+        config.journal.daily_note = Some("/notes/{week}.md".into());
+        assert_eq!(
+            config.validate(),
+            vec!["journal.daily_note: unknown placeholder {week} (expected {date}, {year}, {month}, {day})"]
+        );
+    }
+
+    #[test]
+    fn test_status_config() {
+        let config: Config = toml::from_str(
+            r#"
+[status]
+file = "/srv/homeassistant/krep.json"
+due_after_minutes = 90
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.status.file,
+            Some(PathBuf::from("/srv/homeassistant/krep.json"))
+        );
+        assert_eq!(config.status.due_after_minutes, 90);
+        assert!(config.validate().is_empty());
+
+        let mut config = Config::default();
+        assert_eq!(config.status.file, None);
+        assert_eq!(config.status.due_after_minutes, 60);
+        config.status.due_after_minutes = 0;
+        assert_eq!(
+            config.validate(),
+            vec!["status.due_after_minutes must be positive (got 0)"]
+        );
+    }
+
+    #[test]
+    fn test_rotation_config() {
+        let config: Config = toml::from_str(
+            r#"
+[prescription]
+rotation = ["vo2", "core", "zone2", "breathwork"]
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.prescription.rotation,
+            vec![
+                MicrodoseCategory::Vo2,
+                MicrodoseCategory::Core,
+                MicrodoseCategory::Zone2,
+                MicrodoseCategory::Breathwork,
+            ]
+        );
+        assert!(config.validate().is_empty());
+
+        let mut config = Config::default();
+        config.prescription.rotation = vec![MicrodoseCategory::Gtg, MicrodoseCategory::Gtg];
+        assert_eq!(config.validate().len(), 1);
+    }
+
+    #[test]
+    fn test_rotation_weights_config() {
+        let config: Config = toml::from_str(
+            r#"
+[prescription]
+rotation = ["vo2", "gtg", "mobility"]
+weights = { gtg = 2 }
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.rotation_weights(),
+            HashMap::from([(MicrodoseCategory::Gtg, 2)])
+        );
+        assert!(config
+            .validate_with_catalog(&crate::build_default_catalog())
+            .is_empty());
+
+        let mut config = Config::default();
+        config.prescription.weights.insert("core".into(), 2);
+        config.prescription.weights.insert("cardio".into(), 0);
+        assert_eq!(config.validate().len(), 3);
+
+        // Categories must exist in the catalog in use
+        let mut catalog = crate::build_default_catalog();
+        catalog.retain_microdoses(|d| d.category != MicrodoseCategory::Mobility);
+        let errors = Config::default().validate_with_catalog(&catalog);
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].contains("'mobility'"));
+    }
+
+    #[test]
+    fn test_weekly_targets_config() {
+        let config: Config = toml::from_str(
+            r#"
+[prescription]
+weekly_targets = { vo2 = 3, gtg = 10, mobility = 5 }
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.weekly_targets(),
+            HashMap::from([
+                (MicrodoseCategory::Vo2, 3),
+                (MicrodoseCategory::Gtg, 10),
+                (MicrodoseCategory::Mobility, 5),
+            ])
+        );
+        assert!(config
+            .validate_with_catalog(&crate::build_default_catalog())
+            .is_empty());
+
+        let mut config = Config::default();
+        config
+            .prescription
+            .weekly_targets
+            .insert("cardio".into(), 2);
+        config.prescription.weekly_targets.insert("core".into(), 0);
+        assert_eq!(config.validate().len(), 2);
+    }
+
+    #[test]
+    fn test_heart_rate_config() {
+        assert_eq!(Config::default().max_hr(), None);
+
+        let config: Config = toml::from_str("[heart_rate]\nage = 40\n").unwrap();
+        assert_eq!(config.max_hr(), Some(180));
+
+        let config: Config = toml::from_str("[heart_rate]\nmax_hr = 188\nage = 40\n").unwrap();
+        assert_eq!(config.max_hr(), Some(188));
+        assert!(config.validate().is_empty());
+
+        let mut config = Config::default();
+        config.heart_rate.max_hr = Some(300);
+        config.heart_rate.age = Some(4);
+        assert_eq!(config.validate().len(), 2);
+    }
+
+    #[test]
+    fn test_timezone_config() {
+        let config: Config = toml::from_str("[time]\ntimezone = \"Asia/Tokyo\"\n").unwrap();
+        assert_eq!(
+            config.timezone(),
+            crate::LocalTz::Named(chrono_tz::Asia::Tokyo)
+        );
+        assert!(config.validate().is_empty());
+
+        assert_eq!(Config::default().timezone(), crate::LocalTz::System);
+
+        let mut config = Config::default();
+        config.time.timezone = Some("Nowhere/Special".into());
+        assert_eq!(config.validate().len(), 1);
+        assert_eq!(config.timezone(), crate::LocalTz::System);
+    }
+
+    #[test]
+    fn test_warmup_config() {
+        let blocks = Config::default().warmup.blocks();
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[0].movement_id, "jumping_jack");
+        assert_eq!(blocks[0].duration_hint_seconds, 30);
+
+        let config: Config = toml::from_str("[warmup]\nenabled = false\n").unwrap();
+        assert!(config.warmup.blocks().is_empty());
+
+        let mut config = Config::default();
+        config.warmup.seconds_per_movement = 0;
+        assert_eq!(config.validate().len(), 1);
+    }
+
+    #[test]
+    fn test_diff_reports_changed_keys() {
+        let old = Config::default();
+        let mut new = old.clone();
+        new.progression.kb_swing_max_reps = 20;
+        new.equipment.available.push("rower".into());
+
+        let changes = old.diff(&new);
+        assert_eq!(changes.len(), 2, "unexpected changes: {:?}", changes);
+        assert!(changes[0].starts_with("equipment.available:"));
+        assert!(changes[1].starts_with("progression.kb_swing_max_reps: 15 -> 20"));
+        assert!(old.diff(&old).is_empty());
+    }
+
+    #[test]
+    fn test_rep_ceiling_below_catalog_minimum() {
+        let catalog = crate::build_default_catalog();
+        let mut config = Config::default();
+        assert!(config.validate_with_catalog(&catalog).is_empty());
+
+        config.progression.burpee_rep_ceiling = 1;
+        let errors = config.validate_with_catalog(&catalog);
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].starts_with("progression.burpee_rep_ceiling must be at least"));
+    }
+
+    #[test]
+    fn test_check_file_reports_lines() {
+        let catalog = crate::build_default_catalog();
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("config.toml");
+
+        std::fs::write(
+            &path,
+            format!(
+                "[data]\ndata_dir = {:?}\n\n[progression]\nburpee_rep_ceiling = 1\nkb_swing_max = 20\n",
+                temp_dir.path()
+            ),
+        )
+        .unwrap();
+        let diagnostics = check_file(&path, &catalog).unwrap();
+        assert_eq!(diagnostics.len(), 2, "{:?}", diagnostics);
+        assert_eq!(diagnostics[0].line, Some(6));
+        assert_eq!(
+            diagnostics[0].message,
+            "unknown key 'progression.kb_swing_max'"
+        );
+        assert_eq!(diagnostics[1].line, Some(5));
+        assert_eq!(
+            diagnostics[1].to_string(),
+            "line 5: progression.burpee_rep_ceiling must be at least the catalog minimum \
+             of 2 reps for emom_burpee_5m (got 1)"
+        );
+
+        std::fs::write(&path, "[progression]\nburpee_rep_ceiling = \"ten\"\n").unwrap();
+        let diagnostics = check_file(&path, &catalog).unwrap();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].line, Some(2));
+        assert!(diagnostics[0].message.contains("invalid type"));
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn test_check_paths() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut config = Config::default();
+
+        config.data.data_dir = temp_dir.path().join("not").join("yet");
+        assert!(config.check_paths().is_empty());
+
+        let file = temp_dir.path().join("file");
+        std::fs::write(&file, "").unwrap();
+        config.data.data_dir = file;
+        assert!(config.check_paths()[0].contains("is not a directory"));
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn test_check_path_overrides() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let config: Config = toml::from_str(&format!(
+            "[data]\ndata_dir = {:?}\nwal_path = \"wal/sessions.log\"\n\
+             csv_path = {:?}\nstate_path = \"wal\"\n",
+            temp_dir.path(),
+            temp_dir.path().join("wal").join("sessions.log"),
+        ))
+        .unwrap();
+        std::fs::create_dir_all(temp_dir.path().join("wal")).unwrap();
+
+        let problems = config.check_paths();
+        assert_eq!(problems.len(), 2, "{:?}", problems);
+        assert!(problems[0].starts_with("data.state_path:"));
+        assert!(problems[0].contains("is a directory"));
+        assert!(problems[1].starts_with("data.csv_path: same file as data.wal_path"));
+    }
+
+    #[test]
+    fn test_env_overrides() {
+        let env: HashMap<&str, &str> = HashMap::from([
+            ("KREP_DATA_DIR", "/srv/krep"),
+            ("KREP_EQUIPMENT", "kettlebell, band ,"),
+            ("KREP_VO2_GAP_HOURS", "6"),
+            ("KREP_ROTATION", "vo2,mobility"),
+            ("KREP_WARMUP", "off"),
+            ("KREP_MAX_HR", " "),
+            ("KREP_LOCK_TIMEOUT_SECS", "30"),
+        ]);
+        let mut config = Config::default();
+        config
+            .apply_overrides(|name| env.get(name).map(|v| v.to_string()))
+            .unwrap();
+
+        assert_eq!(config.data.data_dir, PathBuf::from("/srv/krep"));
+        assert_eq!(config.equipment.available, vec!["kettlebell", "band"]);
+        assert_eq!(config.prescription.vo2_gap_hours, 6);
+        assert_eq!(
+            config.prescription.rotation,
+            vec![MicrodoseCategory::Vo2, MicrodoseCategory::Mobility]
+        );
+        assert!(!config.warmup.enabled);
+        assert_eq!(config.data.lock_timeout_secs, 30);
+        // Empty values are ignored
+        assert_eq!(config.heart_rate.max_hr, None);
+    }
+
+    #[test]
+    fn test_env_override_invalid_value() {
+        let mut config = Config::default();
+        let err = config
+            .apply_overrides(|name| (name == "KREP_VO2_GAP_HOURS").then(|| "soon".to_string()))
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("KREP_VO2_GAP_HOURS (prescription.vo2_gap_hours): invalid value 'soon'"));
+
+        let err = config
+            .apply_overrides(|name| (name == "KREP_EXCLUDE_ANOMALIES").then(|| "maybe".to_string()))
+            .unwrap_err();
+        assert!(err.to_string().contains("expected true or false"));
+    }
+
+    #[test]
+    fn test_drop_ins_merge_in_lexical_order() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("config.toml");
+        let drop_ins = temp_dir.path().join(DROP_IN_DIR);
+        std::fs::create_dir_all(&drop_ins).unwrap();
+
+        std::fs::write(
+            &path,
+            "[equipment]\navailable = [\"kettlebell\", \"pullup_bar\"]\n\n\
+             [progression]\nburpee_rep_ceiling = 8\nkb_swing_max_reps = 12\n",
+        )
+        .unwrap();
+        std::fs::write(
+            drop_ins.join("20-late.toml"),
+            "[progression]\nkb_swing_max_reps = 14\n",
+        )
+        .unwrap();
+        std::fs::write(
+            drop_ins.join("10-work-laptop.toml"),
+            "[equipment]\navailable = [\"pullup_bar\"]\n\n[progression]\nkb_swing_max_reps = 9\n",
+        )
+        .unwrap();
+        std::fs::write(drop_ins.join("notes.txt"), "not toml").unwrap();
+
+        let config = Config::load_from(&path).unwrap();
+        // Arrays are replaced, tables merge key by key, later files win
+        assert_eq!(config.equipment.available, vec!["pullup_bar"]);
+        assert_eq!(config.progression.burpee_rep_ceiling, 8);
+        assert_eq!(config.progression.kb_swing_max_reps, 14);
+
+        // Drop-ins apply without a main file too
+        std::fs::remove_file(&path).unwrap();
+        let config = Config::load_from(&path).unwrap();
+        assert_eq!(config.progression.kb_swing_max_reps, 14);
+        assert_eq!(config.progression.burpee_rep_ceiling, 10);
+
+        std::fs::write(drop_ins.join("30-bad.toml"), "[progression\n").unwrap();
+        let err = Config::load_from(&path).unwrap_err();
+        assert!(err.to_string().contains("30-bad.toml"));
+    }
+
+    #[test]
+    fn test_migrate_document_moves_keys() {
+        let migrations = [
+            ("progression.kb_max", "progression.kb_swing_max_reps"),
+            ("vo2_gap", "prescription.vo2_gap_hours"),
+            ("progression.burpee_max", "progression.burpee_rep_ceiling"),
+            ("time.tz", "time.timezone"),
+        ];
+        let mut doc: toml_edit::DocumentMut = "\
+# My settings
+vo2_gap = 6
+
+[progression]
+kb_max = 20 # heavier bell
+burpee_max = 12
+burpee_rep_ceiling = 8
+"
+        .parse()
+        .unwrap();
+
+        let applied = migrate_document(&mut doc, &migrations);
+        assert_eq!(applied.len(), 3);
+        assert!(!applied[0].conflict);
+        assert!(applied[2].conflict);
+        assert_eq!(
+            applied[2].to_string(),
+            "'progression.burpee_max' is now 'progression.burpee_rep_ceiling' \
+             (already set; old value dropped)"
+        );
+
+        let text = doc.to_string();
+        assert!(text.contains("# My settings"));
+        assert!(text.contains("kb_swing_max_reps = 20 # heavier bell"));
+        let config: Config = toml::from_str(&text).unwrap();
+        assert_eq!(config.progression.kb_swing_max_reps, 20);
+        assert_eq!(config.progression.burpee_rep_ceiling, 8);
+        assert_eq!(config.prescription.vo2_gap_hours, 6);
+
+        // Already migrated documents are left alone
+        assert!(migrate_document(&mut doc, &migrations).is_empty());
+        assert_eq!(doc.to_string(), text);
+    }
+
+    #[test]
+    fn test_migrate_file_without_deprecated_keys() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("config.toml");
+        let contents = "# comment\n[progression]\nkb_swing_max_reps = 12\n";
+        std::fs::write(&path, contents).unwrap();
+
+        assert!(migrate_file(&path).unwrap().is_empty());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), contents);
+    }
+
+    #[test]
+    fn test_profiles() {
+        let toml = r#"
+[equipment]
+available = ["kettlebell", "pullup_bar"]
+
+[profile.office]
+equipment = []
+gtg_friendly_only = true
+
+[profile.home]
+rotation = ["vo2", "mobility"]
+"#;
+        let mut config: Config = toml::from_str(toml).unwrap();
+        assert!(config.validate().is_empty());
+
+        let mut office = config.clone();
+        office.apply_profile("office").unwrap();
+        assert!(office.equipment.available.is_empty());
+        assert!(office.prescription.gtg_friendly_only);
+        assert_eq!(office.active_profile.as_deref(), Some("office"));
+        assert_eq!(office.prescription.rotation, default_rotation());
+
+        config.apply_profile("home").unwrap();
+        assert_eq!(config.equipment.available, vec!["kettlebell", "pullup_bar"]);
+        assert!(!config.prescription.gtg_friendly_only);
+        assert_eq!(
+            config.prescription.rotation,
+            vec![MicrodoseCategory::Vo2, MicrodoseCategory::Mobility]
+        );
+
+        let err = config.apply_profile("gym").unwrap_err();
+        assert!(err.to_string().contains("defined: home, office"));
+    }
+
+    #[test]
+    fn test_household_members() {
+        let toml = r#"
+[data]
+data_dir = "/home/me/krep"
+
+[household]
+members = ["anna", "office", "bob"]
+
+[profile.anna]
+data_dir = "/home/anna/krep"
+
+[profile.office]
+gtg_friendly_only = true
+"#;
+        let config: Config = toml::from_str(toml).unwrap();
+        assert_eq!(
+            config.validate(),
+            vec![
+                "household.members: profile.office needs its own data_dir",
+                "household.members: 'bob' is not a defined profile",
+            ]
+        );
+
+        let anna = config.for_member("anna").unwrap();
+        assert_eq!(anna.data.data_dir, PathBuf::from("/home/anna/krep"));
+        assert_eq!(config.data.data_dir, PathBuf::from("/home/me/krep"));
+    }
+
+    #[test]
+    fn test_off_days() {
+        let toml = r#"
+[off]
+days = ["2024-12-24..2024-12-26", "2024-13-01"]
+"#;
+        let config: Config = toml::from_str(toml).unwrap();
+        assert_eq!(
+            config.validate(),
+            vec!["off.days: Configuration error: Invalid off days '2024-13-01' (expected YYYY-MM-DD or YYYY-MM-DD..YYYY-MM-DD)"]
+        );
+
+        let mut state = crate::UserMicrodoseState::default();
+        state
+            .off_days
+            .push("2024-08-10..2024-08-20".parse().unwrap());
+        assert_eq!(config.off_days(&state).len(), 2);
+
+        use chrono::TimeZone;
+        let christmas = Utc.with_ymd_and_hms(2024, 12, 25, 12, 0, 0).unwrap();
+        assert_eq!(
+            config.off_day_at(&state, christmas).unwrap().label(),
+            "2024-12-24..2024-12-26"
+        );
+        let after = Utc.with_ymd_and_hms(2024, 12, 27, 12, 0, 0).unwrap();
+        assert!(config.off_day_at(&state, after).is_none());
+    }
+
+    #[test]
+    fn test_office_hours() {
+        let mut config = Config::default();
+        config.time.timezone = Some("Asia/Tokyo".into());
+        config.prescription.office_hours = vec!["09:00-17:30".into(), "lunch".into()];
+        assert_eq!(
+            config.validate(),
+            vec!["prescription.office_hours: Configuration error: Invalid office hours 'lunch' (expected HH:MM-HH:MM)"]
+        );
+        assert_eq!(config.office_hours().len(), 1);
+
+        let state = crate::UserMicrodoseState::default();
+        // Wednesday 2024-01-10, 10:00 and 20:00 in Tokyo
+        use chrono::TimeZone;
+        let working = Utc.with_ymd_and_hms(2024, 1, 10, 1, 0, 0).unwrap();
+        let evening = Utc.with_ymd_and_hms(2024, 1, 10, 11, 0, 0).unwrap();
+        assert!(config.gtg_friendly_only_at(&state, working));
+        assert!(!config.gtg_friendly_only_at(&state, evening));
+
+        let mut state = state;
+        crate::office::turn_on(&mut state, evening + chrono::Duration::hours(1));
+        assert!(config.gtg_friendly_only_at(&state, evening));
+    }
+
+    #[test]
+    fn test_strength_schedule() {
+        let mut config: Config = toml::from_str(
+            r#"
+            [time]
+            timezone = "Asia/Tokyo"
+
+            [strength]
+            lower = ["mon", "thu"]
+            upper = ["Tuesday", "fri"]
+            at = "18:00"
+            "#,
+        )
+        .unwrap();
+        assert!(config.validate().is_empty());
+
+        // Thursday 2024-01-11 in Tokyo: 10:00, then 19:00 (after the session)
+        use chrono::TimeZone;
+        let morning = Utc.with_ymd_and_hms(2024, 1, 11, 1, 0, 0).unwrap();
+        let evening = Utc.with_ymd_and_hms(2024, 1, 11, 10, 0, 0).unwrap();
+        assert_eq!(
+            config.planned_strength_at(morning),
+            Some(StrengthSessionType::Lower)
+        );
+        assert_eq!(config.planned_strength_at(evening), None);
+        // Tuesday, Wednesday
+        assert_eq!(
+            config.planned_strength_at(morning - chrono::Duration::days(2)),
+            Some(StrengthSessionType::Upper)
+        );
+        assert_eq!(
+            config.planned_strength_at(morning - chrono::Duration::days(1)),
+            None
+        );
+
+        config.strength.at = None;
+        assert_eq!(
+            config.planned_strength_at(evening),
+            Some(StrengthSessionType::Lower)
+        );
+
+        config.strength.full = vec!["thu".into(), "someday".into()];
+        config.strength.at = Some("6pm".into());
+        assert_eq!(
+            config.validate(),
+            vec![
+                "strength.full: thu already has a strength session",
+                "strength.full: 'someday' is not a weekday (expected mon, tue, ...)",
+                "strength.at: '6pm' is not a HH:MM time",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_phases() {
+        let config: Config = toml::from_str(
+            r#"
+            [prescription]
+            weights = { gtg = 2 }
+
+            [[phases]]
+            name = "intensification"
+            start = "2026-10-01"
+            end = "2026-10-21"
+            weights = { vo2 = 2, gtg = 1 }
+
+            [[phases]]
+            name = "deload"
+            start = "2026-10-22"
+            end = "2026-10-28"
+            "#,
+        )
+        .unwrap();
+        assert!(config.validate().is_empty());
+
+        use chrono::TimeZone;
+        let before = Utc.with_ymd_and_hms(2026, 9, 30, 12, 0, 0).unwrap();
+        let intensification = Utc.with_ymd_and_hms(2026, 10, 10, 12, 0, 0).unwrap();
+        let deload = Utc.with_ymd_and_hms(2026, 10, 25, 12, 0, 0).unwrap();
+        let config = Config {
+            time: TimeConfig {
+                timezone: Some("UTC".into()),
+            },
+            ..config
+        };
+
+        assert!(config.phase_at(before).is_none());
+        assert_eq!(
+            config.rotation_weights_at(before),
+            config.rotation_weights()
+        );
+        assert_eq!(config.progression_step_at(before), 1);
+
+        let weights = config.rotation_weights_at(intensification);
+        assert_eq!(weights[&MicrodoseCategory::Vo2], 2);
+        assert_eq!(weights[&MicrodoseCategory::Gtg], 1);
+        assert_eq!(config.progression_step_at(intensification), 2);
+
+        assert_eq!(
+            config.rotation_weights_at(deload),
+            config.rotation_weights()
+        );
+        assert_eq!(config.progression_step_at(deload), 0);
+
+        let mut config = config;
+        config.phases[1].start = "2026-10-20".parse().unwrap();
+        config.phases[1].weights.insert("cardio".into(), 0);
+        let errors = config.validate();
+        assert_eq!(errors.len(), 3);
+        assert!(errors[0].contains("overlaps intensification 2026-10-01..2026-10-21"));
+    }
+
+    #[test]
+    fn test_profile_selected_at_load() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("config.toml");
+        std::fs::write(&path, "[profile.office]\ngtg_friendly_only = true\n").unwrap();
+
+        let config = Config::load_from_profile(&path, Some("office")).unwrap();
+        assert!(config.prescription.gtg_friendly_only);
+        assert!(Config::load_from_profile(&path, Some("gym")).is_err());
+    }
+
+    #[cfg(feature = "config-schema")]
+    #[test]
+    fn test_json_schema() {
+        let schema: serde_json::Value = serde_json::from_str(&json_schema().unwrap()).unwrap();
+        assert_eq!(schema["title"], "krep configuration");
+
+        let properties = schema["properties"].as_object().unwrap();
+        assert!(properties.contains_key("profile"));
+        assert!(!properties.contains_key("active_profile"));
+
+        let prescription = &schema["definitions"]["PrescriptionConfig"]["properties"];
+        assert_eq!(prescription["vo2_gap_hours"]["default"], 4);
+        assert!(prescription["vo2_gap_hours"]["description"]
+            .as_str()
+            .unwrap()
+            .contains("VO2"));
+        assert_eq!(
+            schema["definitions"]["MicrodoseCategory"]["enum"]
+                .as_array()
+                .unwrap()
+                .len(),
+            MicrodoseCategory::ALL.len()
+        );
+    }
 }
@@ -0,0 +1,173 @@
+//! Live reload of `config.toml` for long-running processes (tray, daemon).
+//!
+//! The watcher observes the config file's directory (editors usually replace
+//! the file atomically), including the `config.d` drop-ins, and re-validates
+//! on change. Invalid edits are logged and ignored, so a typo never takes
+//! down a running process.
+
+use crate::{Config, Error, Result};
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver};
+
+/// Watches a config file and holds the most recent valid configuration
+pub struct ConfigWatcher {
+    // Dropping the watcher stops notifications
+    _watcher: RecommendedWatcher,
+    rx: Receiver<()>,
+    path: PathBuf,
+    current: Config,
+}
+
+impl ConfigWatcher {
+    /// Start watching `path`, starting from an already-loaded config
+    pub fn new(path: &Path, initial: Config) -> Result<Self> {
+        let dir = path
+            .parent()
+            .ok_or_else(|| Error::Config(format!("Config path {:?} has no parent", path)))?;
+        std::fs::create_dir_all(dir)?;
+
+        let file_name = path.file_name().map(|n| n.to_os_string());
+        let drop_in_dir = dir.join(crate::config::DROP_IN_DIR);
+        let (tx, rx) = channel();
+
+        let mut watcher =
+            notify::recommended_watcher(move |res: notify::Result<notify::Event>| match res {
+                Ok(event) => {
+                    let touches_config = event.paths.iter().any(|p| {
+                        p.file_name().map(|n| n.to_os_string()) == file_name
+                            || p.starts_with(&drop_in_dir)
+                    });
+                    if touches_config {
+                        let _ = tx.send(());
+                    }
+                }
+                Err(e) => tracing::warn!("Config watch error: {}", e),
+            })
+            .map_err(|e| Error::Config(format!("Failed to create config watcher: {}", e)))?;
+
+        // Recursive so drop-ins are seen, even if config.d is created later
+        watcher
+            .watch(dir, RecursiveMode::Recursive)
+            .map_err(|e| Error::Config(format!("Failed to watch {:?}: {}", dir, e)))?;
+
+        tracing::info!("Watching {:?} for config changes", path);
+
+        Ok(Self {
+            _watcher: watcher,
+            rx,
+            path: path.to_path_buf(),
+            current: initial,
+        })
+    }
+
+    /// The most recent valid configuration
+    pub fn current(&self) -> &Config {
+        &self.current
+    }
+
+    /// Apply any pending reload without blocking
+    ///
+    /// Returns true if the active configuration changed.
+    pub fn poll(&mut self) -> bool {
+        // Coalesce bursts of events (write + rename + chmod) into one reload
+        if self.rx.try_iter().count() == 0 {
+            return false;
+        }
+
+        if !self.path.exists() {
+            tracing::info!(
+                "Config file {:?} removed, using drop-ins and defaults",
+                self.path
+            );
+        }
+        let reloaded = match Config::load_from(&self.path) {
+            Ok(config) => config,
+            Err(e) => {
+                tracing::warn!("Ignoring config change, failed to load: {}", e);
+                return false;
+            }
+        };
+
+        let errors = reloaded.validate_with_catalog(crate::get_default_catalog());
+        if !errors.is_empty() {
+            for error in &errors {
+                tracing::warn!("Ignoring config change, invalid value: {}", error);
+            }
+            return false;
+        }
+
+        let changes = self.current.diff(&reloaded);
+        if changes.is_empty() {
+            tracing::debug!("Config file touched but nothing changed");
+            return false;
+        }
+
+        for change in &changes {
+            tracing::info!("Config reloaded: {}", change);
+        }
+        self.current = reloaded;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    /// Poll until the watcher reports a change or the timeout expires
+    fn poll_until_changed(watcher: &mut ConfigWatcher) -> bool {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while Instant::now() < deadline {
+            if watcher.poll() {
+                return true;
+            }
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        false
+    }
+
+    #[test]
+    fn test_reload_applies_valid_change() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("config.toml");
+        let mut watcher = ConfigWatcher::new(&path, Config::default()).unwrap();
+        assert!(!watcher.poll());
+
+        std::fs::write(&path, "[progression]\nkb_swing_max_reps = 20\n").unwrap();
+
+        assert!(poll_until_changed(&mut watcher));
+        assert_eq!(watcher.current().progression.kb_swing_max_reps, 20);
+    }
+
+    #[test]
+    fn test_reload_ignores_invalid_change() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("config.toml");
+        let mut watcher = ConfigWatcher::new(&path, Config::default()).unwrap();
+
+        std::fs::write(&path, "[progression]\nburpee_rep_ceiling = 0\n").unwrap();
+
+        assert!(!poll_until_changed(&mut watcher));
+        assert_eq!(watcher.current().progression.burpee_rep_ceiling, 10);
+    }
+
+    #[test]
+    fn test_reload_on_drop_in_change() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("config.toml");
+        let mut watcher = ConfigWatcher::new(&path, Config::default()).unwrap();
+
+        let drop_ins = temp_dir.path().join(crate::config::DROP_IN_DIR);
+        std::fs::create_dir_all(&drop_ins).unwrap();
+        std::fs::write(
+            drop_ins.join("laptop.toml"),
+            "[progression]\nkb_swing_max_reps = 12\n",
+        )
+        .unwrap();
+
+        assert!(poll_until_changed(&mut watcher));
+        assert_eq!(watcher.current().progression.kb_swing_max_reps, 12);
+    }
+}
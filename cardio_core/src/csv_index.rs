@@ -0,0 +1,325 @@
+//! Byte-offset index over the CSV archive.
+//!
+//! The archive only grows by appending at rollup, so it is a sequence of
+//! chunks in roughly chronological order. The index (`sessions.csv.idx`,
+//! JSON) records where each chunk starts and the range of `performed_at`
+//! in it, letting [`start_offset`] skip straight to the first chunk that
+//! can hold sessions newer than a cutoff instead of parsing the whole file.
+//!
+//! The index is a cache: it stores the archive length it describes, and a
+//! missing, unreadable or stale index (the CSV changed outside rollup)
+//! just means a full scan. Rollup rebuilds it when needed.
+
+use crate::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Current index format version; other versions are ignored and rebuilt
+const INDEX_VERSION: u32 = 1;
+
+/// Rows per chunk when rebuilding an index from an existing archive
+const REBUILD_CHUNK_ROWS: usize = 1000;
+
+/// Index over a CSV archive
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct CsvIndex {
+    pub version: u32,
+    /// Archive length in bytes when the index was written
+    pub csv_len: u64,
+    pub chunks: Vec<Chunk>,
+}
+
+/// A run of rows appended together
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Chunk {
+    /// Byte offset of the chunk's first row
+    pub offset: u64,
+    pub rows: usize,
+    pub earliest: DateTime<Utc>,
+    pub latest: DateTime<Utc>,
+}
+
+impl Chunk {
+    fn new(offset: u64, at: DateTime<Utc>) -> Self {
+        Self {
+            offset,
+            rows: 1,
+            earliest: at,
+            latest: at,
+        }
+    }
+
+    fn add(&mut self, at: DateTime<Utc>) {
+        self.rows += 1;
+        self.earliest = self.earliest.min(at);
+        self.latest = self.latest.max(at);
+    }
+}
+
+/// Index file for a CSV archive: the archive path with `.idx` appended
+pub fn index_path(csv_path: &Path) -> PathBuf {
+    let mut path = csv_path.as_os_str().to_owned();
+    path.push(".idx");
+    PathBuf::from(path)
+}
+
+/// Load the index if it exists, is readable and matches the archive length
+pub fn load(csv_path: &Path) -> Option<CsvIndex> {
+    let contents = std::fs::read_to_string(index_path(csv_path)).ok()?;
+    let index: CsvIndex = match serde_json::from_str(&contents) {
+        Ok(index) => index,
+        Err(e) => {
+            tracing::warn!("Ignoring unreadable CSV index for {:?}: {}", csv_path, e);
+            return None;
+        }
+    };
+    let csv_len = std::fs::metadata(csv_path).ok()?.len();
+    if index.version != INDEX_VERSION || index.csv_len != csv_len {
+        tracing::debug!("CSV index for {:?} is stale", csv_path);
+        return None;
+    }
+    Some(index)
+}
+
+/// Where to start reading for sessions at or after `cutoff`
+///
+/// `None` means there is no usable index and the whole file must be read.
+/// An offset at the end of the file means no chunk can match.
+pub fn start_offset(csv_path: &Path, cutoff: DateTime<Utc>) -> Option<u64> {
+    let index = load(csv_path)?;
+    Some(
+        index
+            .chunks
+            .iter()
+            .filter(|chunk| chunk.latest >= cutoff)
+            .map(|chunk| chunk.offset)
+            .min()
+            .unwrap_or(index.csv_len),
+    )
+}
+
+/// Byte ranges holding every chunk that overlaps `[since, until)`
+///
+/// Adjacent chunks are merged into one range. `None` means there is no
+/// usable index and the whole file must be read.
+pub fn ranges(
+    csv_path: &Path,
+    since: Option<DateTime<Utc>>,
+    until: Option<DateTime<Utc>>,
+) -> Option<Vec<(u64, u64)>> {
+    let index = load(csv_path)?;
+    let mut ranges: Vec<(u64, u64)> = Vec::new();
+    for (i, chunk) in index.chunks.iter().enumerate() {
+        if since.is_some_and(|since| chunk.latest < since)
+            || until.is_some_and(|until| chunk.earliest >= until)
+        {
+            continue;
+        }
+        let end = index
+            .chunks
+            .get(i + 1)
+            .map_or(index.csv_len, |next| next.offset);
+        match ranges.last_mut() {
+            Some(last) if last.1 == chunk.offset => last.1 = end,
+            _ => ranges.push((chunk.offset, end)),
+        }
+    }
+    Some(ranges)
+}
+
+/// Record rows just appended to the archive
+///
+/// `previous_len` is the archive length before the append and `offset` the
+/// byte offset of the first new row. If the existing index doesn't describe
+/// exactly `previous_len` bytes it is rebuilt from the archive first.
+pub fn record_append(
+    csv_path: &Path,
+    previous_len: u64,
+    offset: u64,
+    performed_at: impl IntoIterator<Item = DateTime<Utc>>,
+) -> Result<()> {
+    let mut index = match read_raw(csv_path) {
+        Some(index) if index.version == INDEX_VERSION && index.csv_len == previous_len => index,
+        _ => {
+            tracing::info!("Rebuilding CSV index for {:?}", csv_path);
+            rebuild(csv_path)?
+        }
+    };
+
+    if index.csv_len < std::fs::metadata(csv_path)?.len() {
+        let mut chunk: Option<Chunk> = None;
+        for at in performed_at {
+            match chunk.as_mut() {
+                Some(chunk) => chunk.add(at),
+                None => chunk = Some(Chunk::new(offset, at)),
+            }
+        }
+        index.chunks.extend(chunk);
+        index.csv_len = std::fs::metadata(csv_path)?.len();
+    }
+    save(csv_path, &index)
+}
+
+/// Build an index by scanning the whole archive
+pub fn rebuild(csv_path: &Path) -> Result<CsvIndex> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(true)
+        .flexible(true)
+        .from_path(csv_path)?;
+    let column = reader.headers()?.iter().position(|h| h == "performed_at");
+
+    let mut chunks = Vec::new();
+    let mut current: Option<Chunk> = None;
+    let mut record = csv::StringRecord::new();
+    if let Some(column) = column {
+        loop {
+            let offset = reader.position().byte();
+            if !reader.read_record(&mut record)? {
+                break;
+            }
+            // Rows with unparseable dates are still inside a chunk's byte
+            // range; they just don't widen its date range
+            let Some(at) = record
+                .get(column)
+                .and_then(|s| DateTime::parse_from_rfc3339(s).ok())
+                .map(|dt| dt.with_timezone(&Utc))
+            else {
+                continue;
+            };
+            match current.as_mut() {
+                Some(chunk) => chunk.add(at),
+                None => current = Some(Chunk::new(offset, at)),
+            }
+            if current
+                .as_ref()
+                .is_some_and(|c| c.rows >= REBUILD_CHUNK_ROWS)
+            {
+                chunks.extend(current.take());
+            }
+        }
+        chunks.extend(current);
+    }
+
+    Ok(CsvIndex {
+        version: INDEX_VERSION,
+        csv_len: std::fs::metadata(csv_path)?.len(),
+        chunks,
+    })
+}
+
+/// Read the index file without checking it against the archive
+fn read_raw(csv_path: &Path) -> Option<CsvIndex> {
+    let contents = std::fs::read_to_string(index_path(csv_path)).ok()?;
+    serde_json::from_str(&contents).ok()
+}
+
+/// Write the index atomically next to the archive
+fn save(csv_path: &Path, index: &CsvIndex) -> Result<()> {
+    let path = index_path(csv_path);
+    let dir = path.parent().unwrap_or_else(|| Path::new("."));
+    let temp = tempfile::NamedTempFile::new_in(dir)?;
+    serde_json::to_writer(temp.as_file(), index)?;
+    temp.persist(&path).map_err(|e| crate::Error::Io(e.error))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wal::{JsonlSink, SessionSink};
+    use crate::MicrodoseSession;
+    use chrono::{Duration, TimeZone};
+
+    fn session_at(at: DateTime<Utc>) -> MicrodoseSession {
+        MicrodoseSession {
+            actual_duration_seconds: Some(300),
+            ..MicrodoseSession::new("emom_burpee_5m", at)
+        }
+    }
+
+    fn roll_up(dir: &Path, csv_path: &Path, sessions: &[MicrodoseSession]) {
+        let wal_path = dir.join("wal").join("microdose_sessions.wal");
+        let mut sink = JsonlSink::new(&wal_path);
+        for session in sessions {
+            sink.append(session).unwrap();
+        }
+        crate::csv_rollup::wal_to_csv_and_archive(
+            &wal_path,
+            csv_path,
+            crate::lock::DEFAULT_TIMEOUT,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_rollup_maintains_index() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let csv_path = temp_dir.path().join("sessions.csv");
+        let old = Utc.with_ymd_and_hms(2023, 1, 1, 8, 0, 0).unwrap();
+        let recent = Utc::now() - Duration::days(1);
+
+        roll_up(
+            temp_dir.path(),
+            &csv_path,
+            &[session_at(old), session_at(old + Duration::hours(2))],
+        );
+        roll_up(temp_dir.path(), &csv_path, &[session_at(recent)]);
+
+        let index = load(&csv_path).unwrap();
+        assert_eq!(index.chunks.len(), 2);
+        assert_eq!(index.chunks[0].rows, 2);
+        assert_eq!(index.chunks[0].latest, old + Duration::hours(2));
+
+        // Only the second chunk is read for recent history
+        let offset = start_offset(&csv_path, Utc::now() - Duration::days(7)).unwrap();
+        assert_eq!(offset, index.chunks[1].offset);
+        let loaded = crate::load_recent_sessions(
+            &temp_dir.path().join("wal").join("microdose_sessions.wal"),
+            &csv_path,
+            7,
+            crate::lock::DEFAULT_TIMEOUT,
+        )
+        .unwrap();
+        assert_eq!(loaded.len(), 1);
+
+        // Nothing newer than every chunk: skip to the end
+        let offset = start_offset(&csv_path, Utc::now() + Duration::days(1)).unwrap();
+        assert_eq!(offset, index.csv_len);
+
+        // A rebuilt index covers the same rows
+        assert_eq!(
+            rebuild(&csv_path)
+                .unwrap()
+                .chunks
+                .iter()
+                .map(|c| c.rows)
+                .sum::<usize>(),
+            3
+        );
+    }
+
+    #[test]
+    fn test_stale_index_is_ignored_and_rebuilt() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let csv_path = temp_dir.path().join("sessions.csv");
+        let recent = Utc::now() - Duration::days(1);
+        roll_up(temp_dir.path(), &csv_path, &[session_at(recent)]);
+
+        // An edit outside rollup invalidates the index
+        let mut contents = std::fs::read_to_string(&csv_path).unwrap();
+        let row = contents.lines().nth(1).unwrap().to_string();
+        // Same row under another ID (a UUID is 36 characters)
+        contents.push_str(&format!(
+            "00000000-0000-4000-8000-000000000001{}\n",
+            &row[36..]
+        ));
+        std::fs::write(&csv_path, contents).unwrap();
+        assert!(start_offset(&csv_path, Utc::now() - Duration::days(7)).is_none());
+
+        // The next rollup rebuilds it, including the hand-added row
+        roll_up(temp_dir.path(), &csv_path, &[session_at(recent)]);
+        let index = load(&csv_path).unwrap();
+        assert_eq!(index.chunks.iter().map(|c| c.rows).sum::<usize>(), 3);
+    }
+}
@@ -0,0 +1,313 @@
+//! Integrity manifest for the CSV archive.
+//!
+//! The manifest (`sessions.csv.manifest.json`, JSON) records each archive
+//! partition's row count, length and checksum as of the last write by krep.
+//! Rollup extends it as it appends, and the rewrites krep makes itself
+//! (column migration, amending a session) refresh it, so a file that no
+//! longer matches was changed behind krep's back: truncated by a full disk,
+//! cut short by an interrupted copy or sync, or edited by hand.
+//! [`verify`] reports the differences for `krep doctor`.
+//!
+//! Unlike the byte-offset index this is not a cache and is never rebuilt
+//! silently on read; only a write by krep replaces it.
+//!
+//! The archive is currently a single partition (the CSV file itself); the
+//! list leaves room for splitting it later.
+
+use crate::Result;
+use serde::{Deserialize, Serialize};
+use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
+
+/// Current manifest format version
+const MANIFEST_VERSION: u32 = 1;
+
+/// FNV-1a 64-bit offset basis: the checksum of no bytes
+pub(crate) const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+
+/// Continue an FNV-1a 64-bit checksum over more bytes
+pub(crate) fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, &b| {
+        (hash ^ u64::from(b)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Manifest of a CSV archive
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct CsvManifest {
+    pub version: u32,
+    pub partitions: Vec<Partition>,
+}
+
+/// One archive file
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Partition {
+    /// File name, relative to the manifest
+    pub file: String,
+    /// Session rows (not counting the header)
+    pub rows: u64,
+    pub bytes: u64,
+    /// Hex FNV-1a 64 of the whole file
+    pub checksum: String,
+}
+
+/// Manifest file for a CSV archive: the archive path with `.manifest.json` appended
+pub fn manifest_path(csv_path: &Path) -> PathBuf {
+    let mut path = csv_path.as_os_str().to_owned();
+    path.push(".manifest.json");
+    PathBuf::from(path)
+}
+
+/// Read the manifest, if there is a readable one
+pub fn load(csv_path: &Path) -> Option<CsvManifest> {
+    let contents = std::fs::read_to_string(manifest_path(csv_path)).ok()?;
+    serde_json::from_str(&contents).ok()
+}
+
+/// Describe the archive as it is now
+pub fn scan(csv_path: &Path) -> Result<Partition> {
+    let file = csv_path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let (bytes, checksum) = checksum_file(csv_path, 0, FNV_OFFSET)?;
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(true)
+        .flexible(true)
+        .from_path(csv_path)?;
+    let mut rows = 0;
+    let mut record = csv::ByteRecord::new();
+    while reader.read_byte_record(&mut record)? {
+        rows += 1;
+    }
+    Ok(Partition {
+        file,
+        rows,
+        bytes,
+        checksum: format!("{:016x}", checksum),
+    })
+}
+
+/// Rewrite the manifest from the archive as it is now
+///
+/// For rewrites made by krep itself; anything else should go through
+/// [`verify`] first.
+pub fn refresh(csv_path: &Path) -> Result<()> {
+    let partition = scan(csv_path)?;
+    save(
+        csv_path,
+        &CsvManifest {
+            version: MANIFEST_VERSION,
+            partitions: vec![partition],
+        },
+    )
+}
+
+/// Record `rows` rows just appended after the first `previous_len` bytes
+///
+/// The checksum is extended over the new bytes only. If the manifest doesn't
+/// describe exactly `previous_len` bytes (missing, or the archive changed
+/// outside krep) it is recomputed from the whole file, with a warning when
+/// there was one to contradict.
+pub fn record_append(csv_path: &Path, previous_len: u64, rows: u64) -> Result<()> {
+    let current = load(csv_path)
+        .filter(|m| m.version == MANIFEST_VERSION)
+        .and_then(|m| m.partitions.into_iter().next());
+    let partition = match current {
+        Some(partition) if partition.bytes == previous_len => {
+            let hash = u64::from_str_radix(&partition.checksum, 16).ok();
+            match hash {
+                Some(hash) => {
+                    let (bytes, checksum) = checksum_file(csv_path, previous_len, hash)?;
+                    Partition {
+                        rows: partition.rows + rows,
+                        bytes,
+                        checksum: format!("{:016x}", checksum),
+                        ..partition
+                    }
+                }
+                None => scan(csv_path)?,
+            }
+        }
+        Some(_) => {
+            tracing::warn!(
+                "CSV archive {:?} changed since its manifest was written; recomputing it",
+                csv_path
+            );
+            scan(csv_path)?
+        }
+        None => scan(csv_path)?,
+    };
+    save(
+        csv_path,
+        &CsvManifest {
+            version: MANIFEST_VERSION,
+            partitions: vec![partition],
+        },
+    )
+}
+
+/// Differences between the archive and its manifest
+///
+/// Empty when they match, or when there is no archive. An archive without
+/// a manifest (written before manifests existed) is reported once; the next
+/// rollup writes one.
+pub fn verify(csv_path: &Path) -> Result<Vec<String>> {
+    if !csv_path.exists() {
+        return Ok(Vec::new());
+    }
+    let Some(manifest) = load(csv_path) else {
+        return Ok(vec![format!(
+            "{} has no manifest yet (written at the next rollup)",
+            manifest_path(csv_path).display()
+        )]);
+    };
+
+    let actual = scan(csv_path)?;
+    let mut problems = Vec::new();
+    for expected in &manifest.partitions {
+        if expected.file != actual.file {
+            problems.push(format!(
+                "manifest lists unknown partition {}",
+                expected.file
+            ));
+            continue;
+        }
+        if actual.bytes < expected.bytes {
+            problems.push(format!(
+                "{} is truncated: {} bytes, manifest says {}",
+                actual.file, actual.bytes, expected.bytes
+            ));
+        } else if actual.bytes > expected.bytes {
+            problems.push(format!(
+                "{} grew outside krep: {} bytes, manifest says {}",
+                actual.file, actual.bytes, expected.bytes
+            ));
+        }
+        if actual.rows != expected.rows {
+            problems.push(format!(
+                "{} has {} rows, manifest says {}",
+                actual.file, actual.rows, expected.rows
+            ));
+        }
+        if actual.bytes == expected.bytes && actual.checksum != expected.checksum {
+            problems.push(format!(
+                "{} checksum mismatch: contents changed since the last rollup",
+                actual.file
+            ));
+        }
+    }
+    Ok(problems)
+}
+
+/// Length and checksum of the file from `offset` on, continuing from `hash`
+/// (returns the full file length)
+fn checksum_file(path: &Path, offset: u64, hash: u64) -> Result<(u64, u64)> {
+    use std::io::{Seek, SeekFrom};
+
+    let mut file = std::fs::File::open(path)?;
+    file.seek(SeekFrom::Start(offset))?;
+    let mut reader = BufReader::new(file);
+    let mut buf = [0u8; 64 * 1024];
+    let (mut len, mut hash) = (offset, hash);
+    loop {
+        let n = reader.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hash = fnv1a(hash, &buf[..n]);
+        len += n as u64;
+    }
+    Ok((len, hash))
+}
+
+/// Write the manifest atomically next to the archive
+fn save(csv_path: &Path, manifest: &CsvManifest) -> Result<()> {
+    let path = manifest_path(csv_path);
+    let dir = path.parent().unwrap_or_else(|| Path::new("."));
+    let temp = tempfile::NamedTempFile::new_in(dir)?;
+    serde_json::to_writer_pretty(temp.as_file(), manifest)?;
+    temp.persist(&path).map_err(|e| crate::Error::Io(e.error))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::csv_rollup::wal_to_csv_and_archive;
+    use crate::wal::{JsonlSink, SessionSink};
+    use crate::MicrodoseSession;
+    use std::io::Write;
+
+    fn append_and_roll_up(dir: &Path, count: usize) {
+        let wal_path = dir.join("sessions.wal");
+        let mut sink = JsonlSink::new(&wal_path);
+        for _ in 0..count {
+            sink.append(&MicrodoseSession {
+                actual_duration_seconds: Some(120),
+                ..MicrodoseSession::new("gtg_pullup_ladder", chrono::Utc::now())
+            })
+            .unwrap();
+        }
+        wal_to_csv_and_archive(
+            &wal_path,
+            &dir.join("sessions.csv"),
+            crate::lock::DEFAULT_TIMEOUT,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_rollup_extends_manifest() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let csv_path = temp_dir.path().join("sessions.csv");
+
+        append_and_roll_up(temp_dir.path(), 2);
+        append_and_roll_up(temp_dir.path(), 3);
+
+        let manifest = load(&csv_path).unwrap();
+        assert_eq!(manifest.partitions.len(), 1);
+        // The incrementally extended checksum matches a full scan
+        assert_eq!(manifest.partitions[0], scan(&csv_path).unwrap());
+        assert_eq!(manifest.partitions[0].rows, 5);
+        assert_eq!(manifest.partitions[0].file, "sessions.csv");
+        assert!(verify(&csv_path).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_verify_detects_damage() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let csv_path = temp_dir.path().join("sessions.csv");
+        append_and_roll_up(temp_dir.path(), 3);
+        let original = std::fs::read(&csv_path).unwrap();
+
+        // Interrupted copy: the last row is cut off
+        std::fs::write(&csv_path, &original[..original.len() - 40]).unwrap();
+        let problems = verify(&csv_path).unwrap();
+        assert!(problems[0].contains("is truncated"), "{:?}", problems);
+
+        // Same length, one byte flipped
+        let mut edited = original.clone();
+        let last = edited.len() - 2;
+        edited[last] = if edited[last] == b'x' { b'y' } else { b'x' };
+        std::fs::write(&csv_path, &edited).unwrap();
+        assert_eq!(
+            verify(&csv_path).unwrap(),
+            vec!["sessions.csv checksum mismatch: contents changed since the last rollup"]
+        );
+
+        // Appended by hand
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&csv_path)
+            .unwrap();
+        file.write_all(b"extra\n").unwrap();
+        assert!(verify(&csv_path).unwrap()[0].contains("grew outside krep"));
+
+        std::fs::remove_file(manifest_path(&csv_path)).unwrap();
+        assert!(verify(&csv_path).unwrap()[0].contains("has no manifest yet"));
+        assert!(verify(&temp_dir.path().join("missing.csv"))
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -4,8 +4,30 @@
 //! to prevent data loss.
 
 use crate::{MicrodoseSession, Result};
+use std::collections::HashSet;
 use std::fs::OpenOptions;
-use std::path::Path;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Column order of the CSV archive (must match [`CsvRow`])
+pub const CSV_HEADERS: &[&str] = &[
+    "id",
+    "definition_id",
+    "performed_at",
+    "started_at",
+    "completed_at",
+    "duration",
+    "perceived_rpe",
+    "avg_hr",
+    "max_hr",
+    "metrics_realized",
+    "tags",
+    "hr_recovery",
+    "hr_series_path",
+    "paused_seconds",
+];
 
 /// A row in the CSV output
 #[derive(Debug, serde::Serialize)]
@@ -19,6 +41,16 @@ struct CsvRow {
     perceived_rpe: Option<u8>,
     avg_hr: Option<u8>,
     max_hr: Option<u8>,
+    /// JSON-encoded `Vec<RealizedMetric>` (empty when nothing was recorded)
+    metrics_realized: Option<String>,
+    /// `;`-separated session tags
+    tags: String,
+    /// 60-second HR drop in bpm
+    hr_recovery: Option<u8>,
+    /// HR series file relative to the data directory
+    hr_series_path: Option<String>,
+    /// Seconds paused mid-dose
+    paused_seconds: Option<u32>,
 }
 
 impl From<&MicrodoseSession> for CsvRow {
@@ -33,26 +65,223 @@ impl From<&MicrodoseSession> for CsvRow {
             perceived_rpe: session.perceived_rpe,
             avg_hr: session.avg_hr,
             max_hr: session.max_hr,
+            metrics_realized: if session.metrics_realized.is_empty() {
+                None
+            } else {
+                serde_json::to_string(&session.metrics_realized).ok()
+            },
+            tags: session.tags.join(";"),
+            hr_recovery: session.hr_recovery,
+            hr_series_path: session.hr_series_path.clone(),
+            paused_seconds: session.paused_seconds,
         }
     }
 }
 
+/// Bring an existing CSV archive up to the current column layout
+///
+/// Archives written before a column was added are rewritten with the new
+/// columns left empty. The rewrite goes through a temp file in the same
+/// directory and is persisted atomically, so a crash leaves the old file intact.
+fn migrate_csv_headers(csv_path: &Path) -> Result<()> {
+    if !csv_path.exists() || std::fs::metadata(csv_path)?.len() == 0 {
+        return Ok(());
+    }
+
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .from_path(csv_path)?;
+    let headers = reader.headers()?.clone();
+    if headers.iter().eq(CSV_HEADERS.iter().copied()) {
+        return Ok(());
+    }
+
+    tracing::info!("Migrating CSV archive {:?} to current columns", csv_path);
+
+    // Map each current column to its position in the old layout (if any)
+    let positions: Vec<Option<usize>> = CSV_HEADERS
+        .iter()
+        .map(|name| headers.iter().position(|h| h == *name))
+        .collect();
+
+    let dir = csv_path.parent().unwrap_or_else(|| Path::new("."));
+    let temp = tempfile::NamedTempFile::new_in(dir)?;
+    let mut writer = csv::Writer::from_writer(temp.as_file());
+    writer.write_record(CSV_HEADERS)?;
+    for record in reader.records() {
+        let record = record?;
+        writer.write_record(
+            positions
+                .iter()
+                .map(|pos| pos.and_then(|i| record.get(i)).unwrap_or("")),
+        )?;
+    }
+    writer.flush()?;
+    drop(writer);
+    temp.as_file().sync_all()?;
+    temp.persist(csv_path)
+        .map_err(|e| crate::Error::Io(e.error))?;
+    crate::csv_manifest::refresh(csv_path)?;
+
+    Ok(())
+}
+
+/// Record of a rollup in progress, written before the CSV is touched
+///
+/// If the process dies after appending to the CSV but before the WAL is
+/// archived, the next rollup finds the manifest and skips the sessions the
+/// interrupted run already appended instead of duplicating them.
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct RollupManifest {
+    /// CSV length before the append
+    csv_len: u64,
+    /// Checksum of the WAL contents being rolled up
+    wal_checksum: u64,
+    session_ids: Vec<Uuid>,
+}
+
+/// Manifest file for a WAL (`<wal>.rollup` next to it)
+pub fn manifest_path(wal_path: &Path) -> PathBuf {
+    wal_path.with_extension("wal.rollup")
+}
+
+/// FNV-1a, enough to tell whether the WAL changed between runs
+fn checksum(bytes: &[u8]) -> u64 {
+    crate::csv_manifest::fnv1a(crate::csv_manifest::FNV_OFFSET, bytes)
+}
+
+fn read_manifest(path: &Path) -> Option<RollupManifest> {
+    let contents = std::fs::read_to_string(path).ok()?;
+    match serde_json::from_str(&contents) {
+        Ok(manifest) => Some(manifest),
+        Err(e) => {
+            tracing::warn!("Ignoring unreadable rollup manifest {:?}: {}", path, e);
+            None
+        }
+    }
+}
+
+/// Write the manifest atomically and durably before the CSV is appended to
+fn write_manifest(path: &Path, manifest: &RollupManifest) -> Result<()> {
+    let dir = path.parent().unwrap_or_else(|| Path::new("."));
+    let temp = tempfile::NamedTempFile::new_in(dir)?;
+    serde_json::to_writer(temp.as_file(), manifest)?;
+    temp.as_file().sync_all()?;
+    temp.persist(path).map_err(|e| crate::Error::Io(e.error))?;
+    Ok(())
+}
+
+/// IDs from the manifest found in rows appended after `manifest.csv_len`
+fn appended_ids(csv_path: &Path, manifest: &RollupManifest) -> Result<HashSet<Uuid>> {
+    let expected: HashSet<Uuid> = manifest.session_ids.iter().copied().collect();
+    let mut found = HashSet::new();
+    if !csv_path.exists() {
+        return Ok(found);
+    }
+
+    let mut file = std::fs::File::open(csv_path)?;
+    file.seek(SeekFrom::Start(manifest.csv_len))?;
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(file);
+    // Only complete rows count: a torn last row is appended again
+    for record in reader.records().flatten() {
+        if record.len() != CSV_HEADERS.len() {
+            continue;
+        }
+        if let Some(id) = record.get(0).and_then(|id| Uuid::parse_str(id).ok()) {
+            if expected.contains(&id) {
+                found.insert(id);
+            }
+        }
+    }
+    Ok(found)
+}
+
+/// Length of a file, 0 if it doesn't exist
+fn file_len(path: &Path) -> u64 {
+    std::fs::metadata(path).map_or(0, |m| m.len())
+}
+
+/// Name to archive a rolled-up WAL under, unique among earlier archives
+///
+/// `microdose_sessions.wal` becomes
+/// `microdose_sessions.20261016T021500.123456Z.wal.processed`, so archives
+/// sort in rollup order (a `-N` suffix on the timestamp keeps it unique if
+/// an archive already has that name).
+fn processed_path(wal_path: &Path, at: chrono::DateTime<chrono::Utc>) -> PathBuf {
+    let stamp = at.format("%Y%m%dT%H%M%S%.6fZ").to_string();
+    let mut path = wal_path.with_extension(format!("{}.wal.processed", stamp));
+    let mut n = 1;
+    while path.exists() {
+        path = wal_path.with_extension(format!("{}-{}.wal.processed", stamp, n));
+        n += 1;
+    }
+    path
+}
+
+/// Whether the file is empty or ends with a newline (no torn last row)
+fn ends_with_newline(file: &mut std::fs::File) -> Result<bool> {
+    if file.metadata()?.len() == 0 {
+        return Ok(true);
+    }
+    let mut last = [0u8; 1];
+    file.seek(SeekFrom::End(-1))?;
+    file.read_exact(&mut last)?;
+    Ok(last[0] == b'\n')
+}
+
 /// Roll up WAL sessions into CSV and archive the WAL atomically
 ///
 /// This function:
 /// 1. Reads all sessions from the WAL
-/// 2. Appends them to the CSV file (creates with headers if needed)
-/// 3. Syncs the CSV to disk
-/// 4. Renames the WAL to .processed
-/// 5. Returns the number of sessions processed
+/// 2. Writes a rollup manifest listing them (see below)
+/// 3. Appends them to the CSV file (creates with headers if needed)
+/// 4. Syncs the CSV to disk and updates its index (see [`crate::csv_index`])
+/// 5. Renames the WAL to a timestamped `.wal.processed` archive, removes
+///    its index (see [`crate::wal_index`]), carries the stats cache over
+///    (see [`crate::stats_cache`]) and removes the manifest
+/// 6. Returns the number of sessions appended
+///
+/// A manifest left behind by an interrupted run means its sessions may
+/// already be in the CSV; those found there are skipped, so re-running
+/// rollup after a crash never duplicates rows.
 ///
 /// # Safety
+/// - The WAL's exclusive lock is held throughout, so concurrent appends
+///   wait and then go to a new WAL
 /// - CSV is fsynced before WAL is renamed
 /// - WAL is renamed (not deleted) to allow manual recovery if needed
 /// - Processed WAL files can be cleaned up manually
-pub fn wal_to_csv_and_archive(wal_path: &Path, csv_path: &Path) -> Result<usize> {
+/// - Waiting for the lock gives up after `timeout`
+pub fn wal_to_csv_and_archive(
+    wal_path: &Path,
+    csv_path: &Path,
+    timeout: Duration,
+) -> Result<usize> {
+    // Hold the WAL's exclusive lock until it is archived: appends wait, then
+    // start a new WAL instead of landing in the archived one. A rollup that
+    // waited on another one finds the WAL archived, and starts over on
+    // whatever is at the path now
+    let wal = loop {
+        let wal = match std::fs::File::open(wal_path) {
+            Ok(wal) => wal,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                tracing::info!("No sessions in WAL to roll up");
+                return Ok(0);
+            }
+            Err(e) => return Err(e.into()),
+        };
+        crate::lock::lock_exclusive_within(&wal, wal_path, timeout)?;
+        if crate::wal::is_current(&wal, wal_path) {
+            break wal;
+        }
+        fs2::FileExt::unlock(&wal)?;
+    };
+
     // Read all sessions from WAL
-    let sessions = crate::wal::read_sessions(wal_path)?;
+    let mut sessions = crate::wal::read_sessions_locked(&wal, wal_path)?;
 
     if sessions.is_empty() {
         tracing::info!("No sessions in WAL to roll up");
@@ -63,22 +292,67 @@ pub fn wal_to_csv_and_archive(wal_path: &Path, csv_path: &Path) -> Result<usize>
     if let Some(parent) = csv_path.parent() {
         std::fs::create_dir_all(parent)?;
     }
+    let lengths_before = (file_len(wal_path), file_len(csv_path));
+
+    // Older archives may predate newer columns
+    migrate_csv_headers(csv_path)?;
+
+    let manifest_path = manifest_path(wal_path);
+    let wal_checksum = checksum(&std::fs::read(wal_path)?);
+    let session_ids: Vec<Uuid> = sessions.iter().map(|s| s.id).collect();
+    // A resumed run keeps the original starting point, so a second crash
+    // still finds everything appended since
+    let mut resume_from = None;
+    if let Some(interrupted) = read_manifest(&manifest_path) {
+        if interrupted.wal_checksum != wal_checksum {
+            tracing::warn!("WAL changed since the interrupted rollup of {:?}", wal_path);
+        }
+        let already = appended_ids(csv_path, &interrupted)?;
+        sessions.retain(|s| !already.contains(&s.id));
+        resume_from = Some(interrupted.csv_len);
+        tracing::info!(
+            "Resuming interrupted rollup: {} session(s) already in CSV",
+            already.len()
+        );
+    }
 
     // Open CSV file for appending
-    let file = OpenOptions::new()
+    let mut file = OpenOptions::new()
         .create(true)
+        .read(true)
         .append(true)
         .open(csv_path)?;
 
     // Determine if we need to write headers by checking file size after opening
     // This avoids an extra stat() syscall
-    let needs_headers = file.metadata()?.len() == 0;
+    let previous_len = file.metadata()?.len();
+    let needs_headers = previous_len == 0;
+
+    write_manifest(
+        &manifest_path,
+        &RollupManifest {
+            csv_len: resume_from.unwrap_or(previous_len),
+            wal_checksum,
+            session_ids,
+        },
+    )?;
+
+    // A crash mid-append can leave a partial row; never append onto it
+    if !ends_with_newline(&mut file)? {
+        tracing::warn!("CSV archive {:?} ends in a partial row", csv_path);
+        file.write_all(b"\n")?;
+    }
 
-    // CSV writer automatically writes headers if the serialized type has them
-    // For appending, we need to skip headers manually if file already has content
+    // Headers are written explicitly so the offset of the first new row is
+    // known for the index
     let mut writer = csv::WriterBuilder::new()
-        .has_headers(needs_headers)
+        .has_headers(false)
         .from_writer(file);
+    if needs_headers {
+        writer.write_record(CSV_HEADERS)?;
+        writer.flush()?;
+    }
+    let rows_offset = writer.get_ref().metadata()?.len();
 
     // Write all sessions to CSV
     for session in &sessions {
@@ -95,18 +369,175 @@ pub fn wal_to_csv_and_archive(wal_path: &Path, csv_path: &Path) -> Result<usize>
 
     tracing::info!("Wrote {} sessions to CSV", sessions.len());
 
+    // The index only speeds up reads; a failure here leaves it stale, which
+    // readers detect and fall back to a full scan
+    if let Err(e) = crate::csv_index::record_append(
+        csv_path,
+        previous_len,
+        rows_offset,
+        sessions.iter().map(|s| s.performed_at),
+    ) {
+        tracing::warn!("Failed to update CSV index for {:?}: {}", csv_path, e);
+    }
+    crate::csv_manifest::record_append(csv_path, previous_len, sessions.len() as u64)?;
+
     // Atomically archive the WAL by renaming it
-    let processed_path = wal_path.with_extension("wal.processed");
+    let processed_path = processed_path(wal_path, chrono::Utc::now());
     std::fs::rename(wal_path, &processed_path)?;
+    // Before unlocking, so the new WAL never meets the old one's index
+    match std::fs::remove_file(crate::wal_index::index_path(wal_path)) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+            tracing::warn!("Failed to remove WAL index for {:?}: {}", wal_path, e)
+        }
+        _ => {}
+    }
+    fs2::FileExt::unlock(&wal)?;
 
     tracing::info!("Archived WAL to {:?}", processed_path);
 
+    // Like the index, a cache that isn't carried over is rebuilt on next use
+    let (wal_len, csv_len) = lengths_before;
+    if let Err(e) = crate::stats_cache::record_rollup(wal_path, csv_path, wal_len, csv_len) {
+        tracing::warn!("Failed to update stats cache for {:?}: {}", csv_path, e);
+    }
+
+    // The WAL is archived, so nothing is left to resume
+    if let Err(e) = std::fs::remove_file(&manifest_path) {
+        tracing::warn!(
+            "Failed to remove rollup manifest {:?}: {}",
+            manifest_path,
+            e
+        );
+    }
+
     Ok(sessions.len())
 }
 
+/// Rewrite the archived session with `id`, returning the updated session
+///
+/// Like [`crate::wal::update_session`]: `f` edits the session, and if it
+/// fails or no row has that ID the archive is left untouched. The archive is
+/// first brought up to the current columns, other rows are copied as they
+/// are, and the rewrite is persisted atomically. The byte-offset index and
+/// stats cache are removed so the next read rebuilds them.
+///
+/// The WAL at `wal_path` stays exclusively locked throughout, as in
+/// [`wal_to_csv_and_archive`], so a rollup can't append rows the rewrite
+/// would then drop. The lock is taken within `timeout`.
+pub fn update_session(
+    wal_path: &Path,
+    csv_path: &Path,
+    id: Uuid,
+    timeout: Duration,
+    f: impl FnOnce(&mut MicrodoseSession) -> Result<()>,
+) -> Result<Option<MicrodoseSession>> {
+    if !csv_path.exists() {
+        return Ok(None);
+    }
+    if let Some(parent) = wal_path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let wal = crate::wal::lock_current(wal_path, timeout)?;
+    let result = rewrite_session(csv_path, id, f);
+    fs2::FileExt::unlock(&wal)?;
+    result
+}
+
+/// [`update_session`] with the WAL already locked
+fn rewrite_session(
+    csv_path: &Path,
+    id: Uuid,
+    f: impl FnOnce(&mut MicrodoseSession) -> Result<()>,
+) -> Result<Option<MicrodoseSession>> {
+    migrate_csv_headers(csv_path)?;
+
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .from_path(csv_path)?;
+    let headers = reader.headers()?.clone();
+    let id_str = id.to_string();
+    let records = reader
+        .records()
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    let Some(index) = records.iter().position(|r| r.get(0) == Some(&id_str)) else {
+        return Ok(None);
+    };
+
+    let mut session =
+        crate::history::session_from_record(&headers, &records[index]).map_err(|e| {
+            crate::Error::Corruption {
+                path: csv_path.to_path_buf(),
+                detail: e.to_string(),
+            }
+        })?;
+    f(&mut session)?;
+
+    let dir = csv_path.parent().unwrap_or_else(|| Path::new("."));
+    let temp = tempfile::NamedTempFile::new_in(dir)?;
+    let mut writer = csv::Writer::from_writer(temp.as_file());
+    writer.write_record(&headers)?;
+    for (i, record) in records.iter().enumerate() {
+        if i == index {
+            writer.serialize(CsvRow::from(&session))?;
+        } else {
+            writer.write_record(record)?;
+        }
+    }
+    writer.flush()?;
+    drop(writer);
+    temp.as_file().sync_all()?;
+    temp.persist(csv_path)
+        .map_err(|e| crate::Error::Io(e.error))?;
+    crate::csv_manifest::refresh(csv_path)?;
+
+    for cache in [
+        crate::csv_index::index_path(csv_path),
+        crate::stats_cache::cache_path(csv_path),
+    ] {
+        match std::fs::remove_file(&cache) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                tracing::warn!("Failed to remove stale cache {:?}: {}", cache, e)
+            }
+            _ => {}
+        }
+    }
+
+    tracing::debug!("Updated session {} in CSV archive", id);
+    Ok(Some(session))
+}
+
+/// Whether a rollup is due without being asked for (`[data] auto_rollup`
+/// in the tray): the WAL has reached `max_bytes` or holds sessions from
+/// before the local today (read under the shared lock, taken within
+/// `timeout`)
+pub fn auto_rollup_due(
+    wal_path: &Path,
+    max_bytes: u64,
+    tz: &crate::LocalTz,
+    now: chrono::DateTime<chrono::Utc>,
+    timeout: Duration,
+) -> Result<bool> {
+    let len = file_len(wal_path);
+    if len == 0 {
+        return Ok(false);
+    }
+    if len >= max_bytes {
+        return Ok(true);
+    }
+    // Below the threshold, so reading it all is cheap
+    let today = tz.date_of(now);
+    let mut earlier = false;
+    crate::wal::for_each_session(wal_path, timeout, |session| {
+        earlier |= tz.date_of(session.performed_at) < today;
+        Ok(())
+    })?;
+    Ok(earlier)
+}
+
 /// Clean up old processed WAL files
 ///
-/// This removes all .wal.processed files in the given directory.
+/// This removes all .wal.processed files in the given directory (every
+/// archive [`wal_to_csv_and_archive`] has left there).
 pub fn cleanup_processed_wals(dir: &Path) -> Result<usize> {
     if !dir.exists() {
         return Ok(0);
@@ -117,12 +548,14 @@ pub fn cleanup_processed_wals(dir: &Path) -> Result<usize> {
         let entry = entry?;
         let path = entry.path();
 
-        if let Some(extension) = path.extension() {
-            if extension == "processed" {
-                std::fs::remove_file(&path)?;
-                tracing::debug!("Removed processed WAL: {:?}", path);
-                count += 1;
-            }
+        if entry
+            .file_name()
+            .to_string_lossy()
+            .ends_with(".wal.processed")
+        {
+            std::fs::remove_file(&path)?;
+            tracing::debug!("Removed processed WAL: {:?}", path);
+            count += 1;
         }
     }
 
@@ -143,19 +576,110 @@ mod tests {
 
     fn create_test_session(def_id: &str) -> MicrodoseSession {
         MicrodoseSession {
-            id: Uuid::new_v4(),
-            definition_id: def_id.into(),
-            performed_at: Utc::now(),
             started_at: Some(Utc::now()),
             completed_at: Some(Utc::now()),
             actual_duration_seconds: Some(300),
-            metrics_realized: vec![],
             perceived_rpe: Some(7),
             avg_hr: Some(145),
             max_hr: Some(165),
+            ..MicrodoseSession::new(def_id, Utc::now())
         }
     }
 
+    #[test]
+    fn test_update_archived_session() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let wal_path = temp_dir.path().join("sessions.wal");
+        let csv_path = temp_dir.path().join("sessions.csv");
+
+        let first = create_test_session("gtg_pullup_ladder");
+        let mut second = create_test_session("vo2_emom_burpees");
+        second.avg_hr = None;
+        second.max_hr = None;
+        let mut sink = crate::wal::JsonlSink::new(&wal_path);
+        sink.append(&first).unwrap();
+        sink.append(&second).unwrap();
+        assert!(crate::wal_index::index_path(&wal_path).exists());
+        wal_to_csv_and_archive(&wal_path, &csv_path, crate::lock::DEFAULT_TIMEOUT).unwrap();
+        assert!(crate::csv_index::index_path(&csv_path).exists());
+        assert!(!crate::wal_index::index_path(&wal_path).exists());
+
+        let updated = update_session(
+            &wal_path,
+            &csv_path,
+            second.id,
+            crate::lock::DEFAULT_TIMEOUT,
+            |s| {
+                s.avg_hr = Some(151);
+                s.max_hr = Some(172);
+                s.hr_series_path = Some("hr/series.csv".into());
+                Ok(())
+            },
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(updated.definition_id, "vo2_emom_burpees");
+        assert!(!crate::csv_index::index_path(&csv_path).exists());
+
+        let sessions = crate::history::load_sessions_from_csv_internal(&csv_path, None).unwrap();
+        assert_eq!(sessions.len(), 2);
+        assert_eq!(sessions[0].id, first.id);
+        assert_eq!(sessions[0].avg_hr, Some(145));
+        assert_eq!(sessions[1].avg_hr, Some(151));
+        assert_eq!(sessions[1].max_hr, Some(172));
+        assert_eq!(sessions[1].hr_series_path.as_deref(), Some("hr/series.csv"));
+        assert_eq!(sessions[0].hr_series_path, None);
+
+        assert!(update_session(
+            &wal_path,
+            &csv_path,
+            Uuid::new_v4(),
+            crate::lock::DEFAULT_TIMEOUT,
+            |_| Ok(())
+        )
+        .unwrap()
+        .is_none());
+    }
+
+    #[test]
+    fn test_rollup_waits_for_an_archive_update() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let wal_path = temp_dir.path().join("sessions.wal");
+        let csv_path = temp_dir.path().join("sessions.csv");
+
+        let first = create_test_session("gtg_pullup_ladder");
+        let mut sink = crate::wal::JsonlSink::new(&wal_path);
+        sink.append(&first).unwrap();
+        wal_to_csv_and_archive(&wal_path, &csv_path, crate::lock::DEFAULT_TIMEOUT).unwrap();
+        sink.append(&create_test_session("vo2_emom_burpees"))
+            .unwrap();
+
+        // A rollup started mid-update waits, so its rows survive the rewrite
+        let mut rollup = None;
+        update_session(
+            &wal_path,
+            &csv_path,
+            first.id,
+            crate::lock::DEFAULT_TIMEOUT,
+            |s| {
+                let (wal_path, csv_path) = (wal_path.clone(), csv_path.clone());
+                rollup = Some(std::thread::spawn(move || {
+                    wal_to_csv_and_archive(&wal_path, &csv_path, crate::lock::DEFAULT_TIMEOUT)
+                }));
+                std::thread::sleep(std::time::Duration::from_millis(200));
+                s.avg_hr = Some(151);
+                Ok(())
+            },
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(rollup.unwrap().join().unwrap().unwrap(), 1);
+
+        let sessions = crate::history::load_sessions_from_csv_internal(&csv_path, None).unwrap();
+        assert_eq!(sessions.len(), 2);
+        assert_eq!(sessions[0].avg_hr, Some(151));
+    }
+
     #[test]
     fn test_wal_to_csv_creates_file() {
         let temp_dir = tempfile::tempdir().unwrap();
@@ -170,7 +694,8 @@ mod tests {
         }
 
         // Roll up to CSV
-        let count = wal_to_csv_and_archive(&wal_path, &csv_path).unwrap();
+        let count =
+            wal_to_csv_and_archive(&wal_path, &csv_path, crate::lock::DEFAULT_TIMEOUT).unwrap();
         assert_eq!(count, 3);
 
         // Verify CSV exists
@@ -178,7 +703,7 @@ mod tests {
 
         // Verify WAL was archived
         assert!(!wal_path.exists());
-        assert!(wal_path.with_extension("wal.processed").exists());
+        assert_eq!(processed_wals(temp_dir.path()).len(), 1);
     }
 
     #[test]
@@ -190,13 +715,15 @@ mod tests {
         // First rollup
         let mut sink = crate::wal::JsonlSink::new(&wal_path);
         sink.append(&create_test_session("def_1")).unwrap();
-        let count1 = wal_to_csv_and_archive(&wal_path, &csv_path).unwrap();
+        let count1 =
+            wal_to_csv_and_archive(&wal_path, &csv_path, crate::lock::DEFAULT_TIMEOUT).unwrap();
         assert_eq!(count1, 1);
 
         // Second rollup (appends)
         let mut sink = crate::wal::JsonlSink::new(&wal_path);
         sink.append(&create_test_session("def_2")).unwrap();
-        let count2 = wal_to_csv_and_archive(&wal_path, &csv_path).unwrap();
+        let count2 =
+            wal_to_csv_and_archive(&wal_path, &csv_path, crate::lock::DEFAULT_TIMEOUT).unwrap();
         assert_eq!(count2, 1);
 
         // Verify CSV has both entries
@@ -205,6 +732,154 @@ mod tests {
         assert_eq!(record_count, 2);
     }
 
+    #[test]
+    fn test_rollups_keep_every_archive() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let wal_path = temp_dir.path().join("sessions.wal");
+        let csv_path = temp_dir.path().join("sessions.csv");
+
+        for def_id in ["def_1", "def_2", "def_3"] {
+            let mut sink = crate::wal::JsonlSink::new(&wal_path);
+            sink.append(&create_test_session(def_id)).unwrap();
+            assert_eq!(
+                wal_to_csv_and_archive(&wal_path, &csv_path, crate::lock::DEFAULT_TIMEOUT).unwrap(),
+                1
+            );
+        }
+
+        let archived: Vec<usize> = processed_wals(temp_dir.path())
+            .iter()
+            .map(|path| {
+                crate::wal::read_sessions(path, crate::lock::DEFAULT_TIMEOUT)
+                    .unwrap()
+                    .len()
+            })
+            .collect();
+        assert_eq!(archived, vec![1, 1, 1]);
+        assert_eq!(cleanup_processed_wals(temp_dir.path()).unwrap(), 3);
+    }
+
+    #[test]
+    fn test_rollup_waiting_on_another_finds_the_wal_archived() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let wal_path = temp_dir.path().join("sessions.wal");
+        let csv_path = temp_dir.path().join("sessions.csv");
+        let mut sink = crate::wal::JsonlSink::new(&wal_path);
+        sink.append(&create_test_session("def_1")).unwrap();
+
+        // Another rollup holds the lock, then archives the WAL
+        let held = File::open(&wal_path).unwrap();
+        crate::lock::lock_exclusive(&held, &wal_path).unwrap();
+        let rollup = {
+            let (wal_path, csv_path) = (wal_path.clone(), csv_path.clone());
+            std::thread::spawn(move || {
+                wal_to_csv_and_archive(&wal_path, &csv_path, crate::lock::DEFAULT_TIMEOUT)
+            })
+        };
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        std::fs::rename(&wal_path, temp_dir.path().join("sessions.wal.processed")).unwrap();
+        fs2::FileExt::unlock(&held).unwrap();
+
+        // The waiting rollup doesn't append the archived sessions again
+        assert_eq!(rollup.join().unwrap().unwrap(), 0);
+        assert!(!csv_path.exists());
+    }
+
+    /// Archived WALs in `dir`, oldest first
+    fn processed_wals(dir: &Path) -> Vec<PathBuf> {
+        let mut paths: Vec<PathBuf> = std::fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.to_string_lossy().ends_with(".wal.processed"))
+            .collect();
+        paths.sort();
+        paths
+    }
+
+    /// Put the WAL back and leave the manifest an interrupted run would have
+    fn simulate_interrupted_rollup(wal_path: &Path, csv_len: u64) {
+        let dir = wal_path.parent().unwrap();
+        let archived = processed_wals(dir).pop().unwrap();
+        std::fs::rename(archived, wal_path).unwrap();
+        write_manifest(
+            &manifest_path(wal_path),
+            &RollupManifest {
+                csv_len,
+                wal_checksum: checksum(&std::fs::read(wal_path).unwrap()),
+                session_ids: crate::wal::read_sessions(wal_path, crate::lock::DEFAULT_TIMEOUT)
+                    .unwrap()
+                    .iter()
+                    .map(|s| s.id)
+                    .collect(),
+            },
+        )
+        .unwrap();
+    }
+
+    fn valid_rows(csv_path: &Path) -> usize {
+        csv::Reader::from_path(csv_path)
+            .unwrap()
+            .into_records()
+            .filter(|r| r.is_ok())
+            .count()
+    }
+
+    #[test]
+    fn test_rerun_after_crash_does_not_duplicate() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let wal_path = temp_dir.path().join("sessions.wal");
+        let csv_path = temp_dir.path().join("sessions.csv");
+
+        let mut sink = crate::wal::JsonlSink::new(&wal_path);
+        sink.append(&create_test_session("def_0")).unwrap();
+        wal_to_csv_and_archive(&wal_path, &csv_path, crate::lock::DEFAULT_TIMEOUT).unwrap();
+        let csv_len = std::fs::metadata(&csv_path).unwrap().len();
+
+        let mut sink = crate::wal::JsonlSink::new(&wal_path);
+        sink.append(&create_test_session("def_1")).unwrap();
+        sink.append(&create_test_session("def_2")).unwrap();
+        wal_to_csv_and_archive(&wal_path, &csv_path, crate::lock::DEFAULT_TIMEOUT).unwrap();
+
+        // Died after the CSV sync, before the WAL rename
+        simulate_interrupted_rollup(&wal_path, csv_len);
+        assert_eq!(
+            wal_to_csv_and_archive(&wal_path, &csv_path, crate::lock::DEFAULT_TIMEOUT).unwrap(),
+            0
+        );
+        assert_eq!(valid_rows(&csv_path), 3);
+        assert!(!wal_path.exists());
+        assert!(!manifest_path(&wal_path).exists());
+    }
+
+    #[test]
+    fn test_rerun_after_torn_append_rewrites_partial_row() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let wal_path = temp_dir.path().join("sessions.wal");
+        let csv_path = temp_dir.path().join("sessions.csv");
+
+        let mut sink = crate::wal::JsonlSink::new(&wal_path);
+        sink.append(&create_test_session("def_1")).unwrap();
+        sink.append(&create_test_session("def_2")).unwrap();
+        wal_to_csv_and_archive(&wal_path, &csv_path, crate::lock::DEFAULT_TIMEOUT).unwrap();
+
+        // Died partway through writing the second row
+        let contents = std::fs::read(&csv_path).unwrap();
+        std::fs::write(&csv_path, &contents[..contents.len() - 20]).unwrap();
+        simulate_interrupted_rollup(&wal_path, 0);
+
+        assert_eq!(
+            wal_to_csv_and_archive(&wal_path, &csv_path, crate::lock::DEFAULT_TIMEOUT).unwrap(),
+            1
+        );
+        let ids: Vec<String> = csv::Reader::from_path(&csv_path)
+            .unwrap()
+            .into_records()
+            .filter_map(|r| r.ok())
+            .map(|r| r[1].to_string())
+            .collect();
+        assert_eq!(ids, ["def_1", "def_2"]);
+    }
+
     #[test]
     fn test_empty_wal() {
         let temp_dir = tempfile::tempdir().unwrap();
@@ -214,7 +889,8 @@ mod tests {
         // Create empty WAL
         File::create(&wal_path).unwrap();
 
-        let count = wal_to_csv_and_archive(&wal_path, &csv_path).unwrap();
+        let count =
+            wal_to_csv_and_archive(&wal_path, &csv_path, crate::lock::DEFAULT_TIMEOUT).unwrap();
         assert_eq!(count, 0);
     }
 
@@ -235,4 +911,86 @@ mod tests {
         assert!(!temp_dir.path().join("s2.wal.processed").exists());
         assert!(temp_dir.path().join("keep.wal").exists());
     }
+
+    #[test]
+    fn test_csv_row_matches_headers() {
+        let mut writer = csv::Writer::from_writer(vec![]);
+        writer
+            .serialize(CsvRow::from(&create_test_session("def_1")))
+            .unwrap();
+        let data = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+        assert_eq!(data.lines().next().unwrap(), CSV_HEADERS.join(","));
+    }
+
+    #[test]
+    fn test_rollup_migrates_old_headers() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let wal_path = temp_dir.path().join("sessions.wal");
+        let csv_path = temp_dir.path().join("sessions.csv");
+
+        // Archive written before metrics_realized existed
+        std::fs::write(
+            &csv_path,
+            "id,definition_id,performed_at,started_at,completed_at,duration,perceived_rpe,avg_hr,max_hr\n\
+             8d3c1a4e-7f0b-4b8e-9a51-3f2d6c0e1b77,def_0,2025-01-01T00:00:00+00:00,,,300,,,\n",
+        )
+        .unwrap();
+
+        let mut session = create_test_session("def_1");
+        session.metrics_realized = vec![crate::RealizedMetric::Reps {
+            key: "reps".into(),
+            per_set: vec![5, 5, 4],
+        }];
+        let mut sink = crate::wal::JsonlSink::new(&wal_path);
+        sink.append(&session).unwrap();
+        wal_to_csv_and_archive(&wal_path, &csv_path, crate::lock::DEFAULT_TIMEOUT).unwrap();
+
+        let mut reader = csv::Reader::from_path(&csv_path).unwrap();
+        assert_eq!(
+            reader.headers().unwrap().iter().collect::<Vec<_>>(),
+            CSV_HEADERS
+        );
+        let records: Vec<_> = reader.records().map(|r| r.unwrap()).collect();
+        assert_eq!(records.len(), 2);
+        assert_eq!(&records[0][1], "def_0");
+        assert_eq!(&records[0][9], "");
+        assert!(records[1][9].contains("\"per_set\":[5,5,4]"));
+    }
+
+    #[test]
+    fn test_auto_rollup_due_overnight_or_over_size() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let wal_path = temp_dir.path().join("test.wal");
+        let tz = crate::LocalTz::from_name(Some("UTC")).unwrap();
+        let now = Utc::now();
+        assert!(!auto_rollup_due(&wal_path, 1024, &tz, now, crate::lock::DEFAULT_TIMEOUT).unwrap());
+
+        let mut sink = crate::JsonlSink::new(&wal_path);
+        sink.append(&create_test_session("emom_burpee_5m")).unwrap();
+        assert!(!auto_rollup_due(
+            &wal_path,
+            1024 * 1024,
+            &tz,
+            now,
+            crate::lock::DEFAULT_TIMEOUT
+        )
+        .unwrap());
+        assert!(auto_rollup_due(&wal_path, 16, &tz, now, crate::lock::DEFAULT_TIMEOUT).unwrap());
+        assert!(auto_rollup_due(
+            &wal_path,
+            1024 * 1024,
+            &tz,
+            now + chrono::Duration::days(1),
+            crate::lock::DEFAULT_TIMEOUT
+        )
+        .unwrap());
+
+        wal_to_csv_and_archive(
+            &wal_path,
+            &temp_dir.path().join("sessions.csv"),
+            crate::lock::DEFAULT_TIMEOUT,
+        )
+        .unwrap();
+        assert!(!auto_rollup_due(&wal_path, 16, &tz, now, crate::lock::DEFAULT_TIMEOUT).unwrap());
+    }
 }
@@ -5,11 +5,15 @@
 //! - Check time since last VO2 session
 //! - Round-robin selection for categories and definitions
 
+use crate::heart_rate::{HrTarget, HrZone};
+use crate::progression::PersonalBest;
 use crate::{
-    Catalog, Error, MicrodoseCategory, MicrodoseDefinition, Result, StrengthSessionType,
-    UserContext,
+    Catalog, Error, MicrodoseBlock, MicrodoseCategory, MicrodoseDefinition, MicrodoseSession,
+    MovementStyle, ProgressionState, RealizedMetric, Result, SessionKind, StrengthSessionType,
+    UserContext, UserMicrodoseState,
 };
-use chrono::Duration;
+use chrono::{DateTime, Duration, Utc};
+use std::borrow::Cow;
 
 /// A prescribed microdose with computed intensity parameters
 #[derive(Clone, Debug)]
@@ -17,6 +21,185 @@ pub struct PrescribedMicrodose {
     pub definition: MicrodoseDefinition,
     pub reps: Option<i32>,
     pub style: Option<crate::MovementStyle>,
+    /// Generated warmup blocks, done before the definition's blocks
+    pub warmup: Vec<MicrodoseBlock>,
+    /// Heart-rate zone to work in (VO2 and Zone 2 only)
+    pub hr_target: Option<HrTarget>,
+    /// Why the choice was restricted (e.g. rehab mode), one line each
+    pub rationale: Vec<String>,
+    /// Recent sessions felt easy: offer to progress now
+    /// (see [`crate::progression::ready_to_progress`])
+    pub ready_to_progress: bool,
+}
+
+impl PrescribedMicrodose {
+    /// Human-readable rep target, e.g. "5 reps", "3/side", "max reps (best 42)"
+    /// or "ladder 1-2-3-2-1 (9 reps)"
+    pub fn reps_label(&self) -> Option<String> {
+        if let (Some(ladder), Some(peak)) = (self.definition.ladder(), self.reps) {
+            return Some(format!(
+                "ladder {} ({} reps)",
+                ladder.label(peak),
+                ladder.total(peak)
+            ));
+        }
+        if self.definition.is_amrap() {
+            return Some(match self.reps {
+                Some(best) if best > 0 => format!("max reps (best {})", best),
+                _ => "max reps".to_string(),
+            });
+        }
+        self.reps.map(|reps| {
+            if self.definition.reps_per_side() {
+                format!("{}/side", reps)
+            } else {
+                format!("{} reps", reps)
+            }
+        })
+    }
+
+    /// Warmup summary, e.g. "30s Jumping Jacks, 30s Hip Hinges"
+    pub fn warmup_label(&self, catalog: &Catalog) -> Option<String> {
+        if self.warmup.is_empty() {
+            return None;
+        }
+        let parts: Vec<String> = self
+            .warmup
+            .iter()
+            .map(|block| {
+                let name = catalog
+                    .movement(&block.movement_id)
+                    .map_or(block.movement_id.as_str(), |m| m.name.as_str());
+                format!("{}s {}", block.duration_hint_seconds, name)
+            })
+            .collect();
+        Some(parts.join(", "))
+    }
+
+    /// Reference video or article (see [`Catalog::reference_url`])
+    pub fn reference_url<'a>(&'a self, catalog: &'a Catalog) -> Option<&'a str> {
+        catalog.reference_url(&self.definition)
+    }
+
+    /// Total warmup time in seconds
+    pub fn warmup_seconds(&self) -> u32 {
+        self.warmup.iter().map(|b| b.duration_hint_seconds).sum()
+    }
+
+    /// A session for this dose done exactly as prescribed at `now`, for
+    /// one-tap logging: the prescribed reps (each rung of a ladder) are the
+    /// realized reps; AMRAPs have no total to record
+    pub fn done_as_prescribed(&self, now: DateTime<Utc>) -> MicrodoseSession {
+        let per_set = match (self.reps, self.definition.ladder()) {
+            _ if self.definition.is_amrap() => vec![],
+            (Some(peak), Some(ladder)) => ladder.rungs(peak),
+            (Some(reps), None) => vec![reps],
+            (None, _) => vec![],
+        };
+        MicrodoseSession {
+            id: uuid::Uuid::new_v4(),
+            definition_id: self.definition.id.clone(),
+            performed_at: now,
+            started_at: Some(now),
+            completed_at: Some(now),
+            actual_duration_seconds: Some(self.definition.suggested_duration_seconds),
+            metrics_realized: if per_set.is_empty() {
+                vec![]
+            } else {
+                vec![RealizedMetric::Reps {
+                    key: "reps".into(),
+                    per_set,
+                }]
+            },
+            perceived_rpe: None,
+            avg_hr: None,
+            max_hr: None,
+            hr_recovery: None,
+            hr_series_path: None,
+            paused_seconds: None,
+            tags: vec![],
+        }
+    }
+
+    /// Total reps of work (both sides for unilateral drills, every rung of a ladder)
+    pub fn total_reps(&self) -> Option<i32> {
+        self.reps.map(|reps| match self.definition.ladder() {
+            Some(ladder) => ladder.total(reps),
+            None => crate::progression::total_work(reps, self.definition.reps_per_side()),
+        })
+    }
+
+    /// What logging `session` of this dose changes in the user state
+    ///
+    /// Like [`StateDelta::for_session`], and the definition starts being
+    /// tracked at the prescribed reps and style if it isn't yet.
+    pub fn state_delta(&self, session: &MicrodoseSession) -> StateDelta {
+        StateDelta {
+            progression: Some(ProgressionState {
+                reps: self.reps.unwrap_or(0),
+                style: self.style.clone().unwrap_or(MovementStyle::None),
+                level: 0,
+                last_upgraded: None,
+            }),
+            ..StateDelta::for_session(&self.definition, session)
+        }
+    }
+}
+
+/// The user-state changes a logged session commits
+///
+/// Prescribing never touches the state; frontends build one of these when a
+/// session is logged (see [`PrescribedMicrodose::state_delta`]) and
+/// [`StateDelta::apply`] it, so the mobility rotation and AMRAP bests are
+/// kept in one place.
+#[derive(Clone, Debug)]
+pub struct StateDelta {
+    pub definition_id: String,
+    pub at: DateTime<Utc>,
+    /// Progression to start from if the definition has none yet
+    pub progression: Option<ProgressionState>,
+    /// AMRAP rep total, recorded as a personal best if it beats the last
+    pub amrap_total: Option<i32>,
+    /// Whether the definition becomes the last mobility dose in the rotation
+    pub mobility: bool,
+}
+
+impl StateDelta {
+    /// The changes a session of `definition` commits, when there is no
+    /// prescription to start a progression from (e.g. an FFI log)
+    pub fn for_session(definition: &MicrodoseDefinition, session: &MicrodoseSession) -> Self {
+        StateDelta {
+            definition_id: definition.id.clone(),
+            at: session.performed_at,
+            progression: None,
+            amrap_total: session
+                .reps_per_set()
+                .filter(|_| definition.is_amrap())
+                .map(|per_set| per_set.iter().sum()),
+            mobility: definition.category == MicrodoseCategory::Mobility,
+        }
+    }
+
+    /// Whether applying would leave the state as it is
+    pub fn is_empty(&self) -> bool {
+        self.progression.is_none() && self.amrap_total.is_none() && !self.mobility
+    }
+
+    /// Commit the changes, returning a new AMRAP personal best
+    pub fn apply(&self, state: &mut UserMicrodoseState) -> Option<PersonalBest> {
+        if let Some(progression) = &self.progression {
+            state
+                .progressions
+                .entry(self.definition_id.clone())
+                .or_insert_with(|| progression.clone());
+        }
+        if self.mobility {
+            state.last_mobility_def_id = Some(self.definition_id.clone());
+        }
+        self.amrap_total.and_then(|total| {
+            crate::progression::record_amrap(&self.definition_id, total, state, self.at)
+        })
+    }
 }
 
 /// Prescribe the next microdose based on context and rules
@@ -27,122 +210,351 @@ pub struct PrescribedMicrodose {
 ///    - If lower-body strength session ≤ 24h ago → GTG pullup OR mobility
 ///
 /// 2. **VO2 timing**:
-///    - If last VO2 session > 4h ago → VO2 category
+///    - If last VO2 session > `ctx.vo2_gap_hours` ago (default 4h) → VO2 category
 ///
 /// 3. **Default round-robin**:
-///    - Cycle through [VO2, GTG, Mobility] categories
+///    - Cycle through `ctx.rotation` (default [VO2, GTG, Mobility]),
+///      skipping categories with no definitions in the catalog
+///
+/// With `ctx.gtg_friendly_only`, only GTG-friendly definitions are considered;
+/// while travel mode is on, only definitions that need no equipment; and
+/// during rehab, only mobility and definitions whose movements are cleared.
+/// Sore areas, and the muscles a strength session planned later today
+/// (`ctx.planned_strength`) will train, are avoided where possible.
 ///
 pub fn prescribe_next(
     catalog: &Catalog,
     ctx: &UserContext,
     target_category: Option<MicrodoseCategory>,
 ) -> Result<PrescribedMicrodose> {
+    let catalog = narrowed(catalog, ctx);
+    let catalog = catalog.as_ref();
+
     // Determine category to prescribe
-    let mut category = if let Some(cat) = target_category {
+    let mut category = if let Some(cat) = target_category.clone() {
         cat
     } else {
-        determine_category(ctx)?
+        determine_category(catalog, ctx)?
     };
 
     tracing::info!("Prescribing microdose from category: {:?}", category);
 
     // Fallback if the determined category doesn't exist in catalog
-    // Try in order: suggested → Vo2 → Gtg → Mobility → error
+    // Try in order: suggested → rotation → every other category → error
     if !has_category(catalog, &category) {
         tracing::warn!(
             "Category {:?} not found in catalog, trying fallbacks",
             category
         );
 
-        let fallback_order = vec![
-            MicrodoseCategory::Vo2,
-            MicrodoseCategory::Gtg,
-            MicrodoseCategory::Mobility,
-        ];
-
-        category = fallback_order
-            .into_iter()
+        category = ctx
+            .rotation
+            .iter()
+            .chain(MicrodoseCategory::ALL.iter())
             .find(|cat| has_category(catalog, cat))
+            .cloned()
             .ok_or_else(|| Error::Prescription("No microdoses available in catalog".into()))?;
 
         tracing::info!("Using fallback category: {:?}", category);
     }
 
+    // Steer away from categories where every definition loads a sore area
+    // (or one a strength session later today will train)
+    // (an explicitly requested category is kept; its least-loading pick wins)
+    let sore = avoided_areas(ctx);
+    if target_category.is_none() && !sore.is_empty() && !has_unloaded(catalog, &category, &sore) {
+        if let Some(alternative) = ctx
+            .rotation
+            .iter()
+            .chain(MicrodoseCategory::ALL.iter())
+            .find(|cat| has_unloaded(catalog, cat, &sore))
+        {
+            tracing::info!(
+                "Avoiding areas {:?}: switching from {:?} to {:?}",
+                sore,
+                category,
+                alternative
+            );
+            category = alternative.clone();
+        } else {
+            tracing::warn!("Every microdose loads a sore area ({:?})", sore);
+        }
+    }
+
     // Select definition from category
     let definition = select_definition_from_category(catalog, ctx, &category)?;
 
-    // Compute intensity based on progression state
+    Ok(prescription(catalog, ctx, definition))
+}
+
+/// Prescribe a microdose picked for novelty rather than by the rules
+///
+/// Every definition the current modes allow is weighted by how rarely it
+/// shows up in `ctx.recent_sessions` (see [`crate::novelty`]), skipping
+/// those that load a sore or soon-trained area when others are left, and `roll` (in
+/// `[0, 1)`, e.g. from [`crate::novelty::roll`]) picks one.
+pub fn prescribe_surprise(
+    catalog: &Catalog,
+    ctx: &UserContext,
+    roll: f64,
+) -> Result<PrescribedMicrodose> {
+    let catalog = narrowed(catalog, ctx);
+    let catalog = catalog.as_ref();
+
+    let mut weighted = crate::novelty::weights(catalog, &ctx.recent_sessions);
+    let sore = avoided_areas(ctx);
+    if !sore.is_empty() {
+        let unloaded: Vec<_> = weighted
+            .iter()
+            .filter(|(d, _)| !crate::soreness::loads_sore_area(catalog, d, &sore))
+            .copied()
+            .collect();
+        if !unloaded.is_empty() {
+            weighted = unloaded;
+        }
+    }
+    let definition = crate::novelty::pick(&weighted, roll)
+        .ok_or_else(|| Error::Prescription("No microdoses available in catalog".into()))?;
+    tracing::info!("Surprise pick: {}", definition.id);
+
+    let done = ctx
+        .recent_sessions
+        .iter()
+        .filter_map(|s| s.as_real())
+        .filter(|s| s.definition_id == definition.id)
+        .count();
+    let mut prescribed = prescription(catalog, ctx, definition);
+    prescribed.rationale.push(match done {
+        0 => "Surprise pick: not done recently".to_string(),
+        n => format!("Surprise pick: done {}× recently", n),
+    });
+    Ok(prescribed)
+}
+
+/// Movement tags to steer away from: sore areas plus those trained by a
+/// strength session planned later today
+fn avoided_areas(ctx: &UserContext) -> Vec<String> {
+    let mut areas = crate::soreness::sore_areas(&ctx.user_state.soreness, ctx.now);
+    if let Some(planned) = &ctx.planned_strength {
+        areas.extend(
+            crate::strength::trained_areas(planned)
+                .iter()
+                .map(|area| area.to_string()),
+        );
+        areas.sort();
+        areas.dedup();
+    }
+    areas
+}
+
+/// The catalog narrowed by the modes in effect
+///
+/// An office-style profile keeps GTG-friendly doses, travel mode those that
+/// need no equipment, and rehab mode mobility plus the movements cleared so
+/// far.
+pub(crate) fn narrowed<'a>(catalog: &'a Catalog, ctx: &UserContext) -> Cow<'a, Catalog> {
+    let mut catalog = Cow::Borrowed(catalog);
+    if ctx.gtg_friendly_only {
+        catalog = Cow::Owned(catalog.gtg_friendly());
+    }
+    if crate::travel::is_active(&ctx.user_state, ctx.now) {
+        catalog = Cow::Owned(catalog.without_equipment());
+    }
+    if let Some(period) = crate::rehab::active(&ctx.user_state.rehab, ctx.now) {
+        catalog = Cow::Owned(crate::rehab::restrict(&catalog, period));
+    }
+    catalog
+}
+
+/// Intensity, warmup, HR target and rationale for a chosen definition
+pub(crate) fn prescription(
+    catalog: &Catalog,
+    ctx: &UserContext,
+    definition: &MicrodoseDefinition,
+) -> PrescribedMicrodose {
     let (reps, style) = compute_intensity(definition, ctx);
+    // Ladder rungs sit below the peak by design, so ladders go by RPE alone
+    let ready_to_progress = !ctx.progression_paused
+        && crate::progression::ready_to_progress(
+            &definition.id,
+            reps.filter(|_| definition.ladder().is_none()),
+            &ctx.user_state,
+            &ctx.recent_sessions,
+            ctx.now,
+        );
+    let rehab = crate::rehab::active(&ctx.user_state.rehab, ctx.now);
 
-    Ok(PrescribedMicrodose {
+    PrescribedMicrodose {
         definition: definition.clone(),
         reps,
         style,
-    })
+        warmup: warmup_for(catalog, ctx, &definition.category),
+        hr_target: HrZone::for_category(&definition.category).map(|zone| HrTarget {
+            zone,
+            max_hr: ctx.max_hr,
+        }),
+        rationale: rehab
+            .map(crate::rehab::rationale)
+            .into_iter()
+            .chain(ctx.planned_strength.as_ref().map(|planned| {
+                format!(
+                    "{} strength planned later today: sparing {}",
+                    crate::strength::day_label(planned),
+                    crate::strength::trained_areas(planned).join(", ")
+                )
+            }))
+            .collect(),
+        ready_to_progress,
+    }
 }
 
 /// Determine which category to prescribe from based on context
-fn determine_category(ctx: &UserContext) -> Result<MicrodoseCategory> {
+fn determine_category(catalog: &Catalog, ctx: &UserContext) -> Result<MicrodoseCategory> {
     // Rule 1: Recent lower-body strength → prefer GTG or Mobility
-    if let Some(ref strength) = ctx.external_strength {
-        let time_since_strength = ctx.now - strength.last_session_at;
-
-        if time_since_strength < Duration::hours(24)
-            && strength.session_type == StrengthSessionType::Lower
-        {
-            tracing::info!(
-                "Recent lower-body strength detected ({} hours ago), preferring GTG/Mobility",
-                time_since_strength.num_hours()
-            );
-            return Ok(MicrodoseCategory::Gtg);
-        }
+    // (the latest session may be upper body after a lower-body one)
+    let recent_lower = crate::strength::last_within(
+        ctx.strength_history
+            .sessions()
+            .iter()
+            .chain(&ctx.external_strength),
+        &StrengthSessionType::Lower,
+        Duration::hours(24),
+        ctx.now,
+    );
+    if let Some(strength) = recent_lower {
+        tracing::info!(
+            "Recent lower-body strength detected ({} hours ago), preferring GTG/Mobility",
+            (ctx.now - strength.last_session_at).num_hours()
+        );
+        return Ok(MicrodoseCategory::Gtg);
     }
 
-    // Rule 2: Check time since last VO2 session
-    let last_vo2 = crate::history::find_last_session_by_category(&ctx.recent_sessions, "vo2");
+    // Rule 2: Check time since last VO2 session (newest first, by catalog category)
+    let last_vo2 = ctx.recent_sessions.iter().find(|s| {
+        category_of(catalog, s.definition_id()).as_ref() == Some(&MicrodoseCategory::Vo2)
+    });
 
     if let Some(last_vo2_session) = last_vo2 {
         let time_since_vo2 = ctx.now - last_vo2_session.timestamp();
 
-        if time_since_vo2 > Duration::hours(4) {
+        if time_since_vo2 > Duration::hours(i64::from(ctx.vo2_gap_hours)) {
             tracing::info!(
-                "Last VO2 session was {} hours ago (> 4h), prescribing VO2",
-                time_since_vo2.num_hours()
+                "Last VO2 session was {} hours ago (> {}h), prescribing VO2",
+                time_since_vo2.num_hours(),
+                ctx.vo2_gap_hours
             );
             return Ok(MicrodoseCategory::Vo2);
         }
     }
     // If no VO2 in history, fall through to round-robin
 
-    // Rule 3: Default round-robin based on last category
-    let last_category = ctx.recent_sessions.first().and_then(|s| {
-        // Infer category from definition ID
-        let def_id = s.definition_id();
-        if def_id.contains("vo2") || def_id.contains("emom") {
-            Some(MicrodoseCategory::Vo2)
-        } else if def_id.contains("gtg") {
-            Some(MicrodoseCategory::Gtg)
-        } else if def_id.contains("mobility") {
-            Some(MicrodoseCategory::Mobility)
-        } else {
-            None
-        }
-    });
+    // Rule 3: Weekly targets → the category furthest behind goes first
+    if let Some(category) = furthest_behind_target(catalog, ctx) {
+        tracing::info!("Furthest behind weekly target: {:?}", category);
+        return Ok(category);
+    }
 
-    let next_category = match last_category {
-        Some(MicrodoseCategory::Vo2) => MicrodoseCategory::Gtg,
-        Some(MicrodoseCategory::Gtg) => MicrodoseCategory::Mobility,
-        Some(MicrodoseCategory::Mobility) => MicrodoseCategory::Vo2,
-        None => MicrodoseCategory::Vo2, // Default to VO2 if unknown
+    // Rule 4: Default round-robin based on last category
+    let rotation: Vec<MicrodoseCategory> = if ctx.rotation.is_empty() {
+        MicrodoseCategory::ALL.to_vec()
+    } else {
+        ctx.rotation.clone()
+    };
+    let rotation: Vec<MicrodoseCategory> = rotation
+        .into_iter()
+        .filter(|cat| has_category(catalog, cat))
+        .collect();
+
+    let last_category = ctx
+        .recent_sessions
+        .first()
+        .and_then(|s| category_of(catalog, s.definition_id()));
+
+    let next_category = match last_category
+        .and_then(|last| Some((rotation.iter().position(|cat| *cat == last)?, last)))
+    {
+        Some((idx, last)) => {
+            // Stay on a weighted category until its run of doses is complete
+            let weight = ctx.rotation_weights.get(&last).copied().unwrap_or(1);
+            let run = ctx
+                .recent_sessions
+                .iter()
+                .take_while(|s| category_of(catalog, s.definition_id()).as_ref() == Some(&last))
+                .count();
+            if run < weight as usize {
+                last
+            } else {
+                rotation[(idx + 1) % rotation.len()].clone()
+            }
+        }
+        // Unknown or out-of-rotation last category: start from the top
+        None => rotation.first().cloned().unwrap_or(MicrodoseCategory::Vo2),
     };
 
     tracing::info!("Round-robin selection: {:?}", next_category);
     Ok(next_category)
 }
 
+/// Category with the smallest share of its weekly target done over the
+/// trailing 7 days, if any target is unmet
+///
+/// Categories skipped just now are passed over so a skip moves on to the
+/// next gap (or the round-robin) instead of repeating the category.
+fn furthest_behind_target(catalog: &Catalog, ctx: &UserContext) -> Option<MicrodoseCategory> {
+    if ctx.weekly_targets.is_empty() {
+        return None;
+    }
+
+    let just_skipped: Vec<MicrodoseCategory> = ctx
+        .recent_sessions
+        .iter()
+        .take_while(|s| matches!(s, SessionKind::ShownButSkipped { .. }))
+        .filter_map(|s| category_of(catalog, s.definition_id()))
+        .collect();
+
+    let progress = crate::stats::target_progress(
+        &ctx.recent_sessions,
+        catalog,
+        &ctx.weekly_targets,
+        ctx.now - Duration::days(7),
+    );
+    progress
+        .into_iter()
+        .filter(|p| p.remaining() > 0)
+        .filter(|p| has_category(catalog, &p.category) && !just_skipped.contains(&p.category))
+        // Lowest share done first; ties go to the larger absolute gap
+        .min_by(|a, b| {
+            a.fraction()
+                .total_cmp(&b.fraction())
+                .then(b.remaining().cmp(&a.remaining()))
+        })
+        .map(|p| p.category)
+}
+
+/// Category of a definition ID, from the catalog or (for IDs no longer in
+/// the catalog) the ID naming convention
+fn category_of(catalog: &Catalog, def_id: &str) -> Option<MicrodoseCategory> {
+    if let Some(def) = catalog.definition(def_id) {
+        return Some(def.category.clone());
+    }
+    if def_id.contains("vo2") || def_id.contains("emom") {
+        return Some(MicrodoseCategory::Vo2);
+    }
+    MicrodoseCategory::ALL
+        .into_iter()
+        .find(|cat| def_id.contains(cat.as_str()))
+}
+
 /// Helper to check if a catalog has any microdoses in a category
 fn has_category(catalog: &Catalog, category: &MicrodoseCategory) -> bool {
-    catalog.microdoses.values().any(|d| &d.category == category)
+    catalog.in_category(category).next().is_some()
+}
+
+/// Whether a category has a definition that loads none of the sore areas
+fn has_unloaded(catalog: &Catalog, category: &MicrodoseCategory, sore: &[String]) -> bool {
+    catalog
+        .in_category(category)
+        .any(|d| !crate::soreness::loads_sore_area(catalog, d, sore))
 }
 
 /// Select a specific definition from a category
@@ -151,12 +563,8 @@ fn select_definition_from_category<'a>(
     ctx: &UserContext,
     category: &MicrodoseCategory,
 ) -> Result<&'a MicrodoseDefinition> {
-    // Get all definitions in the category
-    let mut candidates: Vec<_> = catalog
-        .microdoses
-        .values()
-        .filter(|d| &d.category == category)
-        .collect();
+    // Definitions in the category, sorted by ID for deterministic selection
+    let mut candidates: Vec<_> = catalog.in_category(category).collect();
 
     if candidates.is_empty() {
         return Err(Error::Prescription(format!(
@@ -165,8 +573,18 @@ fn select_definition_from_category<'a>(
         )));
     }
 
-    // Sort for deterministic selection
-    candidates.sort_by_key(|d| &d.id);
+    // Drop definitions that load a sore area, unless that would leave none
+    let sore = avoided_areas(ctx);
+    if !sore.is_empty() {
+        let unloaded: Vec<_> = candidates
+            .iter()
+            .filter(|d| !crate::soreness::loads_sore_area(catalog, d, &sore))
+            .copied()
+            .collect();
+        if !unloaded.is_empty() {
+            candidates = unloaded;
+        }
+    }
 
     // Handle category-specific selection logic
     match category {
@@ -175,29 +593,37 @@ fn select_definition_from_category<'a>(
             let last_vo2_def = ctx
                 .recent_sessions
                 .iter()
-                .find(|s| {
-                    let def_id = s.definition_id();
-                    def_id.contains("vo2") || def_id.contains("emom")
-                })
-                .map(|s| s.definition_id());
-
-            // Pick the one we didn't do last time
-            if let Some(last) = last_vo2_def {
-                candidates
-                    .iter()
-                    .find(|d| d.id != last)
-                    .copied()
-                    .or_else(|| candidates.first().copied())
-                    .ok_or_else(|| Error::Prescription("No VO2 definition available".into()))
-            } else {
+                .map(|s| s.definition_id())
+                .find(|def_id| category_of(catalog, def_id) == Some(MicrodoseCategory::Vo2));
+
+            // Pick the one after the one we did last time
+            match last_vo2_def {
+                Some(last) => match candidates.iter().position(|d| d.id == last) {
+                    Some(idx) => Ok(candidates[(idx + 1) % candidates.len()]),
+                    None => candidates
+                        .iter()
+                        .find(|d| d.id != last)
+                        .copied()
+                        .ok_or_else(|| Error::Prescription("No VO2 definition available".into())),
+                },
                 // No previous VO2, pick first
-                Ok(candidates[0])
+                None => Ok(candidates[0]),
             }
         }
 
-        MicrodoseCategory::Gtg => {
-            // Just pick the first (only one GTG definition in default catalog)
-            Ok(candidates[0])
+        MicrodoseCategory::Gtg
+        | MicrodoseCategory::Core
+        | MicrodoseCategory::Zone2
+        | MicrodoseCategory::Breathwork => {
+            // Round-robin through definitions, after the most recent one shown
+            let last_idx = ctx
+                .recent_sessions
+                .iter()
+                .find_map(|s| candidates.iter().position(|d| d.id == s.definition_id()));
+            match last_idx {
+                Some(idx) => Ok(candidates[(idx + 1) % candidates.len()]),
+                None => Ok(candidates[0]),
+            }
         }
 
         MicrodoseCategory::Mobility => {
@@ -220,6 +646,29 @@ fn select_definition_from_category<'a>(
     }
 }
 
+/// Warmup blocks for a category: only VO2 gets one, and movements missing
+/// from the catalog are dropped
+fn warmup_for(
+    catalog: &Catalog,
+    ctx: &UserContext,
+    category: &MicrodoseCategory,
+) -> Vec<MicrodoseBlock> {
+    if *category != MicrodoseCategory::Vo2 {
+        return vec![];
+    }
+    ctx.warmup
+        .iter()
+        .filter(|block| {
+            let known = catalog.movement(&block.movement_id).is_some();
+            if !known {
+                tracing::warn!("Unknown warmup movement '{}', skipping", block.movement_id);
+            }
+            known
+        })
+        .cloned()
+        .collect()
+}
+
 /// Compute intensity (reps/style) based on progression state
 fn compute_intensity(
     definition: &MicrodoseDefinition,
@@ -230,12 +679,8 @@ fn compute_intensity(
     } else {
         // No progression state - use defaults from definition
         let first_block = definition.blocks.first();
-        let default_reps = first_block.and_then(|b| {
-            b.metrics.iter().find_map(|m| match m {
-                crate::MetricSpec::Reps { default, .. } => Some(*default),
-                _ => None,
-            })
-        });
+        let default_reps =
+            first_block.and_then(|b| b.metrics.iter().find_map(|m| m.default_reps()));
 
         let default_style = first_block.map(|b| b.movement_style.clone());
 
@@ -246,19 +691,12 @@ fn compute_intensity(
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{
-        build_default_catalog, ExternalStrengthSignal, ProgressionState, UserMicrodoseState,
-    };
+    use crate::{build_default_catalog, ExternalStrengthSignal};
     use chrono::Utc;
+    use std::collections::HashMap;
 
     fn create_test_context() -> UserContext {
-        UserContext {
-            now: Utc::now(),
-            user_state: UserMicrodoseState::default(),
-            recent_sessions: vec![],
-            external_strength: None,
-            equipment_available: vec![],
-        }
+        UserContext::builder(Utc::now()).build()
     }
 
     #[test]
@@ -286,6 +724,29 @@ mod tests {
         assert_eq!(prescribed.definition.category, MicrodoseCategory::Gtg);
     }
 
+    #[test]
+    fn test_lower_strength_found_in_history() {
+        let catalog = build_default_catalog();
+        let now = Utc::now();
+        // An upper session since yesterday's legs is the latest signal
+        let ctx = UserContext::builder(now)
+            .external_strength(Some(ExternalStrengthSignal {
+                last_session_at: now - Duration::hours(1),
+                session_type: StrengthSessionType::Upper,
+            }))
+            .strength_history(crate::strength::StrengthHistory::new(vec![
+                ExternalStrengthSignal {
+                    last_session_at: now - Duration::hours(12),
+                    session_type: StrengthSessionType::Lower,
+                },
+            ]))
+            .build();
+
+        let prescribed = prescribe_next(&catalog, &ctx, None).unwrap();
+
+        assert_eq!(prescribed.definition.category, MicrodoseCategory::Gtg);
+    }
+
     #[test]
     fn test_respects_target_category() {
         let catalog = build_default_catalog();
@@ -299,7 +760,7 @@ mod tests {
     #[test]
     fn test_compute_intensity_with_progression() {
         let catalog = build_default_catalog();
-        let def = catalog.microdoses.get("emom_burpee_5m").unwrap();
+        let def = catalog.microdoses().get("emom_burpee_5m").unwrap();
 
         let mut ctx = create_test_context();
         ctx.user_state.progressions.insert(
@@ -324,7 +785,7 @@ mod tests {
     #[test]
     fn test_compute_intensity_without_progression() {
         let catalog = build_default_catalog();
-        let def = catalog.microdoses.get("emom_burpee_5m").unwrap();
+        let def = catalog.microdoses().get("emom_burpee_5m").unwrap();
 
         let ctx = create_test_context();
 
@@ -334,6 +795,340 @@ mod tests {
         assert_eq!(reps, Some(3));
     }
 
+    #[test]
+    fn test_per_side_reps_label_and_total() {
+        let catalog = build_default_catalog();
+        let ctx = create_test_context();
+
+        let hips = catalog.microdoses().get("mobility_hip_cars").unwrap();
+        let (reps, style) = compute_intensity(hips, &ctx);
+        let prescription = PrescribedMicrodose {
+            definition: hips.clone(),
+            reps,
+            style,
+            warmup: vec![],
+            hr_target: None,
+            rationale: vec![],
+            ready_to_progress: false,
+        };
+        assert_eq!(prescription.reps_label().as_deref(), Some("3/side"));
+        assert_eq!(prescription.total_reps(), Some(6));
+
+        let burpees = catalog.microdoses().get("emom_burpee_5m").unwrap();
+        let (reps, style) = compute_intensity(burpees, &ctx);
+        let prescription = PrescribedMicrodose {
+            definition: burpees.clone(),
+            reps,
+            style,
+            warmup: vec![],
+            hr_target: None,
+            rationale: vec![],
+            ready_to_progress: false,
+        };
+        assert_eq!(prescription.reps_label().as_deref(), Some("3 reps"));
+        assert_eq!(prescription.total_reps(), Some(3));
+    }
+
+    #[test]
+    fn test_ladder_prescription() {
+        let catalog = build_default_catalog();
+        let mut ctx = create_test_context();
+
+        // GTG rotates from the banded pull-ups to the ladder
+        let p = prescribe_next(&catalog, &ctx, Some(MicrodoseCategory::Gtg)).unwrap();
+        assert_eq!(p.definition.id, "gtg_pullup_band");
+        ctx.recent_sessions.insert(
+            0,
+            crate::SessionKind::ShownButSkipped {
+                definition_id: p.definition.id.clone(),
+                shown_at: ctx.now,
+            },
+        );
+
+        let p = prescribe_next(&catalog, &ctx, Some(MicrodoseCategory::Gtg)).unwrap();
+        assert_eq!(p.definition.id, "gtg_pullup_ladder");
+        assert_eq!(p.reps_label().as_deref(), Some("ladder 1-2-3-2-1 (9 reps)"));
+        assert_eq!(p.total_reps(), Some(9));
+
+        // Progression extends the peak
+        crate::increase_intensity(
+            "gtg_pullup_ladder",
+            &mut ctx.user_state,
+            &crate::Config::default(),
+            ctx.now,
+        );
+        let p = prescribe_next(&catalog, &ctx, Some(MicrodoseCategory::Gtg)).unwrap();
+        assert_eq!(
+            p.reps_label().as_deref(),
+            Some("ladder 1-2-3-4-3-2-1 (16 reps)")
+        );
+    }
+
+    #[test]
+    fn test_amrap_target_is_personal_best() {
+        let catalog = build_default_catalog();
+        let mut ctx = create_test_context();
+        let id = "core_amrap_mountain_climber_2m";
+
+        let p = prescribe_next(&catalog, &ctx, Some(MicrodoseCategory::Core)).unwrap();
+        assert_eq!(p.definition.id, id);
+        assert_eq!(p.reps, None);
+        assert_eq!(p.reps_label().as_deref(), Some("max reps"));
+
+        crate::progression::record_amrap(id, 42, &mut ctx.user_state, ctx.now);
+        let p = prescribe_next(&catalog, &ctx, Some(MicrodoseCategory::Core)).unwrap();
+        assert_eq!(p.reps, Some(42));
+        assert_eq!(p.reps_label().as_deref(), Some("max reps (best 42)"));
+    }
+
+    #[test]
+    fn test_state_delta_commits_rotation_and_amrap() {
+        let catalog = build_default_catalog();
+        let mut ctx = create_test_context();
+
+        // Prescribing leaves the state alone; applying the delta moves the rotation on
+        let p = prescribe_next(&catalog, &ctx, Some(MicrodoseCategory::Mobility)).unwrap();
+        assert_eq!(ctx.user_state.last_mobility_def_id, None);
+        let delta = p.state_delta(&p.done_as_prescribed(ctx.now));
+        assert!(delta.mobility);
+        assert_eq!(delta.apply(&mut ctx.user_state), None);
+        assert_eq!(
+            ctx.user_state.last_mobility_def_id.as_deref(),
+            Some(p.definition.id.as_str())
+        );
+        assert!(ctx.user_state.progressions.contains_key(&p.definition.id));
+        let next = prescribe_next(&catalog, &ctx, Some(MicrodoseCategory::Mobility)).unwrap();
+        assert_ne!(next.definition.id, p.definition.id);
+
+        // An AMRAP total becomes the personal best
+        let p = prescribe_next(&catalog, &ctx, Some(MicrodoseCategory::Core)).unwrap();
+        let mut session = p.done_as_prescribed(ctx.now);
+        session.metrics_realized = vec![RealizedMetric::Reps {
+            key: "reps".into(),
+            per_set: vec![30],
+        }];
+        let delta = StateDelta::for_session(&p.definition, &session);
+        assert_eq!(delta.amrap_total, Some(30));
+        assert!(delta.progression.is_none());
+        let best = delta.apply(&mut ctx.user_state).unwrap();
+        assert_eq!(best.total, 30);
+        assert_eq!(ctx.user_state.personal_bests[&p.definition.id], 30);
+    }
+
+    #[test]
+    fn test_rotation_includes_new_categories() {
+        let catalog = build_default_catalog();
+        let mut ctx = create_test_context();
+        ctx.rotation = vec![
+            MicrodoseCategory::Mobility,
+            MicrodoseCategory::Core,
+            MicrodoseCategory::Zone2,
+            MicrodoseCategory::Breathwork,
+        ];
+
+        let mut seen = Vec::new();
+        for _ in 0..5 {
+            let p = prescribe_next(&catalog, &ctx, None).unwrap();
+            seen.push(p.definition.category.clone());
+            ctx.recent_sessions.insert(
+                0,
+                crate::SessionKind::ShownButSkipped {
+                    definition_id: p.definition.id.clone(),
+                    shown_at: ctx.now,
+                },
+            );
+        }
+
+        assert_eq!(
+            seen,
+            vec![
+                MicrodoseCategory::Mobility,
+                MicrodoseCategory::Core,
+                MicrodoseCategory::Zone2,
+                MicrodoseCategory::Breathwork,
+                MicrodoseCategory::Mobility,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rotation_weights() {
+        let catalog = build_default_catalog();
+        let mut ctx = create_test_context();
+        ctx.rotation = vec![MicrodoseCategory::Vo2, MicrodoseCategory::Gtg];
+        ctx.rotation_weights = HashMap::from([(MicrodoseCategory::Gtg, 2)]);
+
+        let mut seen = Vec::new();
+        for _ in 0..6 {
+            let p = prescribe_next(&catalog, &ctx, None).unwrap();
+            seen.push(p.definition.category.clone());
+            ctx.recent_sessions.insert(
+                0,
+                crate::SessionKind::ShownButSkipped {
+                    definition_id: p.definition.id.clone(),
+                    shown_at: ctx.now,
+                },
+            );
+        }
+
+        use MicrodoseCategory::{Gtg, Vo2};
+        assert_eq!(seen, vec![Vo2, Gtg, Gtg, Vo2, Gtg, Gtg]);
+    }
+
+    #[test]
+    fn test_weekly_targets_fill_largest_gap() {
+        let catalog = build_default_catalog();
+        let mut ctx = create_test_context();
+        ctx.weekly_targets = HashMap::from([
+            (MicrodoseCategory::Vo2, 3),
+            (MicrodoseCategory::Gtg, 10),
+            (MicrodoseCategory::Mobility, 5),
+        ]);
+        // Keep the last VO2 dose (30h ago) inside the gap so targets decide
+        ctx.vo2_gap_hours = 48;
+
+        let real = |def_id: &str, hours_ago: i64| {
+            crate::SessionKind::Real(crate::MicrodoseSession {
+                actual_duration_seconds: Some(300),
+                ..crate::MicrodoseSession::new(def_id, ctx.now - chrono::Duration::hours(hours_ago))
+            })
+        };
+
+        // VO2 1/3, GTG 5/10, mobility 3/5 → VO2 is furthest behind
+        let mut sessions: Vec<_> = (0..5).map(|h| real("gtg_pullup_band", h + 1)).collect();
+        sessions.extend((0..3).map(|h| real("mobility_hip_cars", h + 10)));
+        sessions.push(real("emom_burpee_5m", 30));
+        // Older than a week: doesn't count
+        sessions.push(real("emom_burpee_5m", 24 * 8));
+        sessions.push(real("emom_burpee_5m", 24 * 8));
+        ctx.recent_sessions = sessions.clone();
+        let p = prescribe_next(&catalog, &ctx, None).unwrap();
+        assert_eq!(p.definition.category, MicrodoseCategory::Vo2);
+
+        // Skipping it moves on to the next gap: GTG (5/10) before mobility (3/5)
+        ctx.recent_sessions.insert(
+            0,
+            crate::SessionKind::ShownButSkipped {
+                definition_id: p.definition.id.clone(),
+                shown_at: ctx.now,
+            },
+        );
+        let p = prescribe_next(&catalog, &ctx, None).unwrap();
+        assert_eq!(p.definition.category, MicrodoseCategory::Gtg);
+
+        // All targets met → plain round-robin (last was GTG)
+        ctx.weekly_targets = HashMap::from([(MicrodoseCategory::Vo2, 1)]);
+        ctx.recent_sessions = sessions;
+        let p = prescribe_next(&catalog, &ctx, None).unwrap();
+        assert_eq!(p.definition.category, MicrodoseCategory::Mobility);
+    }
+
+    #[test]
+    fn test_hr_target_for_vo2() {
+        let catalog = build_default_catalog();
+        let mut ctx = create_test_context();
+        ctx.max_hr = Some(180);
+
+        let vo2 = prescribe_next(&catalog, &ctx, Some(MicrodoseCategory::Vo2)).unwrap();
+        assert_eq!(
+            vo2.hr_target.map(|t| t.label()).as_deref(),
+            Some("aim for 85–92% max (153–166 bpm)")
+        );
+
+        let gtg = prescribe_next(&catalog, &ctx, Some(MicrodoseCategory::Gtg)).unwrap();
+        assert_eq!(gtg.hr_target, None);
+    }
+
+    #[test]
+    fn test_target_new_category() {
+        let catalog = build_default_catalog();
+        let ctx = create_test_context();
+        for category in [
+            MicrodoseCategory::Core,
+            MicrodoseCategory::Zone2,
+            MicrodoseCategory::Breathwork,
+        ] {
+            let p = prescribe_next(&catalog, &ctx, Some(category.clone())).unwrap();
+            assert_eq!(p.definition.category, category);
+        }
+    }
+
+    #[test]
+    fn test_warmup_only_for_vo2() {
+        let catalog = build_default_catalog();
+        let mut ctx = create_test_context();
+
+        let vo2 = prescribe_next(&catalog, &ctx, Some(MicrodoseCategory::Vo2)).unwrap();
+        assert_eq!(
+            vo2.warmup_label(&catalog).as_deref(),
+            Some("30s Jumping Jacks, 30s Hip Hinges")
+        );
+        assert_eq!(vo2.warmup_seconds(), 60);
+
+        let gtg = prescribe_next(&catalog, &ctx, Some(MicrodoseCategory::Gtg)).unwrap();
+        assert!(gtg.warmup.is_empty());
+
+        // Disabled warmup and unknown movements produce no blocks
+        ctx.warmup = vec![];
+        let vo2 = prescribe_next(&catalog, &ctx, Some(MicrodoseCategory::Vo2)).unwrap();
+        assert!(vo2.warmup_label(&catalog).is_none());
+
+        let mut config = crate::Config::default();
+        config.warmup.movements = vec!["cartwheel".into()];
+        ctx.warmup = config.warmup.blocks();
+        let vo2 = prescribe_next(&catalog, &ctx, Some(MicrodoseCategory::Vo2)).unwrap();
+        assert!(vo2.warmup.is_empty());
+    }
+
+    #[test]
+    fn test_avoids_sore_areas() {
+        let catalog = build_default_catalog();
+        let mut ctx = create_test_context();
+        ctx.user_state.soreness.push(crate::SorenessCheckin {
+            checked_at: ctx.now - Duration::hours(20),
+            areas: vec![crate::SoreArea {
+                area: "legs".into(),
+                severity: 3,
+            }],
+        });
+
+        // Both VO2 definitions load the legs, so the round-robin moves on
+        let p = prescribe_next(&catalog, &ctx, None).unwrap();
+        assert_eq!(p.definition.category, MicrodoseCategory::Gtg);
+
+        // Within a requested category, pick a definition that spares the area
+        ctx.user_state.soreness[0].areas[0].area = "shoulder".into();
+        ctx.user_state.last_mobility_def_id = Some("mobility_hip_cars".into());
+        let p = prescribe_next(&catalog, &ctx, Some(MicrodoseCategory::Mobility)).unwrap();
+        assert_eq!(p.definition.id, "mobility_hip_cars");
+
+        // After 48h the area is no longer avoided
+        ctx.user_state.soreness[0].areas[0].area = "legs".into();
+        ctx.user_state.soreness[0].checked_at = ctx.now - Duration::hours(49);
+        let p = prescribe_next(&catalog, &ctx, None).unwrap();
+        assert_eq!(p.definition.category, MicrodoseCategory::Vo2);
+    }
+
+    #[test]
+    fn test_spares_muscles_before_planned_strength() {
+        let catalog = build_default_catalog();
+        let mut ctx = create_test_context();
+        ctx.planned_strength = Some(StrengthSessionType::Lower);
+
+        // VO2 would be next, but every VO2 definition loads the legs
+        let p = prescribe_next(&catalog, &ctx, None).unwrap();
+        assert_eq!(p.definition.category, MicrodoseCategory::Gtg);
+        assert_eq!(
+            p.rationale,
+            vec!["lower strength planned later today: sparing legs, posterior_chain"]
+        );
+
+        // Upper-body day: the legs are fair game again
+        ctx.planned_strength = Some(StrengthSessionType::Upper);
+        let p = prescribe_next(&catalog, &ctx, None).unwrap();
+        assert_eq!(p.definition.category, MicrodoseCategory::Vo2);
+    }
+
     // ========================================================================
     // Behavioral Tests for SessionKind Safety and Edge Cases
     // ========================================================================
@@ -345,9 +1140,7 @@ mod tests {
         let mut catalog = build_default_catalog();
 
         // Keep only VO2 microdoses
-        catalog
-            .microdoses
-            .retain(|_id, def| def.category == MicrodoseCategory::Vo2);
+        catalog.retain_microdoses(|def| def.category == MicrodoseCategory::Vo2);
 
         let ctx = create_test_context();
 
@@ -358,16 +1151,10 @@ mod tests {
         // Create a context with history of the first prescription
         let mut ctx2 = create_test_context();
         ctx2.recent_sessions = vec![crate::SessionKind::Real(crate::MicrodoseSession {
-            id: uuid::Uuid::new_v4(),
-            definition_id: p1.definition.id.clone(),
-            performed_at: Utc::now(),
             started_at: Some(Utc::now()),
             completed_at: Some(Utc::now()),
             actual_duration_seconds: Some(300),
-            metrics_realized: vec![],
-            perceived_rpe: None,
-            avg_hr: None,
-            max_hr: None,
+            ..crate::MicrodoseSession::new(&p1.definition.id, Utc::now())
         })];
 
         // Second prescription should still be VO2 (no infinite loop)
@@ -406,6 +1193,193 @@ mod tests {
         assert_eq!(p2.definition.category, MicrodoseCategory::Gtg);
     }
 
+    #[test]
+    fn test_vo2_gap_hours() {
+        let catalog = build_default_catalog();
+        let mut ctx = create_test_context();
+        let three_hours_ago = Utc::now() - Duration::hours(3);
+        ctx.recent_sessions = vec![crate::SessionKind::Real(crate::MicrodoseSession {
+            started_at: Some(three_hours_ago),
+            completed_at: Some(three_hours_ago),
+            actual_duration_seconds: Some(300),
+            ..crate::MicrodoseSession::new("emom_burpee_5m", three_hours_ago)
+        })];
+
+        // Within the default 4h gap, the rotation moves on from VO2
+        assert_eq!(
+            determine_category(&catalog, &ctx).unwrap(),
+            MicrodoseCategory::Gtg
+        );
+
+        ctx.vo2_gap_hours = 2;
+        assert_eq!(
+            determine_category(&catalog, &ctx).unwrap(),
+            MicrodoseCategory::Vo2
+        );
+    }
+
+    #[test]
+    fn test_gtg_friendly_only() {
+        let catalog = build_default_catalog();
+        let mut ctx = create_test_context();
+        ctx.gtg_friendly_only = true;
+
+        for target in [None, Some(MicrodoseCategory::Vo2)] {
+            let prescription = prescribe_next(&catalog, &ctx, target).unwrap();
+            assert!(
+                prescription.definition.gtg_friendly,
+                "{} is not GTG-friendly",
+                prescription.definition.id
+            );
+        }
+    }
+
+    #[test]
+    fn test_travel_mode_needs_no_equipment() {
+        let catalog = build_default_catalog();
+        let mut ctx = create_test_context();
+        crate::travel::turn_on(&mut ctx.user_state, ctx.now, 3);
+
+        for target in [
+            None,
+            Some(MicrodoseCategory::Vo2),
+            Some(MicrodoseCategory::Gtg),
+        ] {
+            let prescription = prescribe_next(&catalog, &ctx, target).unwrap();
+            for block in &prescription.definition.blocks {
+                assert!(
+                    !catalog.movements()[&block.movement_id]
+                        .kind
+                        .needs_equipment(),
+                    "{} needs equipment",
+                    prescription.definition.id
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_rehab_mode_prescribes_mobility() {
+        let catalog = build_default_catalog();
+        let mut ctx = create_test_context();
+        crate::rehab::start(
+            &mut ctx.user_state.rehab,
+            "left knee",
+            ctx.now,
+            None,
+            vec![],
+        );
+
+        for target in [None, Some(MicrodoseCategory::Vo2)] {
+            let prescription = prescribe_next(&catalog, &ctx, target).unwrap();
+            assert_eq!(
+                prescription.definition.category,
+                MicrodoseCategory::Mobility
+            );
+            assert_eq!(
+                prescription.rationale,
+                vec!["Rehab mode (left knee): mobility only"]
+            );
+        }
+
+        crate::rehab::clear(&mut ctx.user_state.rehab, ctx.now);
+        let prescription = prescribe_next(&catalog, &ctx, None).unwrap();
+        assert!(prescription.rationale.is_empty());
+    }
+
+    #[test]
+    fn test_done_as_prescribed() {
+        let catalog = build_default_catalog();
+        let ctx = create_test_context();
+        let mut prescription =
+            prescribe_next(&catalog, &ctx, Some(MicrodoseCategory::Gtg)).unwrap();
+        let now = Utc::now();
+
+        let session = prescription.done_as_prescribed(now);
+        assert_eq!(session.definition_id, prescription.definition.id);
+        assert_eq!(session.performed_at, now);
+        assert_eq!(
+            session.reps_per_set(),
+            prescription.reps.map(|r| vec![r]).as_deref()
+        );
+
+        // Ladders record every rung
+        prescription.definition = catalog.microdoses()["gtg_pullup_ladder"].clone();
+        prescription.reps = Some(3);
+        let session = prescription.done_as_prescribed(now);
+        assert_eq!(
+            session.reps_per_set().unwrap().iter().sum::<i32>(),
+            prescription.total_reps().unwrap()
+        );
+
+        // Timed from start to finish, the measured duration is kept
+        let started = now - Duration::seconds(95);
+        let session = prescription.done_as_prescribed(now).timed(started, now, 0);
+        assert_eq!(session.performed_at, started);
+        assert_eq!(session.completed_at, Some(now));
+        assert_eq!(session.actual_duration_seconds, Some(95));
+    }
+
+    #[test]
+    fn test_surprise_favours_what_was_not_done() {
+        let catalog = build_default_catalog();
+        let mut ctx = create_test_context();
+        let first = catalog.microdoses().keys().min().unwrap().clone();
+        // Half-way into the first definition's share while all weigh the same
+        let roll = 0.5 / catalog.microdoses().len() as f64;
+
+        // A low roll lands on the first definition by ID...
+        let prescription = prescribe_surprise(&catalog, &ctx, roll).unwrap();
+        assert_eq!(prescription.definition.id, first);
+        assert_eq!(
+            prescription.rationale,
+            vec!["Surprise pick: not done recently"]
+        );
+
+        // ...until it has been done a lot, shrinking its share
+        ctx.recent_sessions = (0..9)
+            .map(|_| SessionKind::ShownButSkipped {
+                definition_id: first.clone(),
+                shown_at: ctx.now,
+            })
+            .collect();
+        assert_eq!(
+            prescribe_surprise(&catalog, &ctx, roll)
+                .unwrap()
+                .definition
+                .id,
+            first,
+            "skips don't count as done"
+        );
+        ctx.recent_sessions = (0..9)
+            .map(|_| {
+                SessionKind::Real(crate::MicrodoseSession {
+                    actual_duration_seconds: Some(60),
+                    ..crate::MicrodoseSession::new(&first, ctx.now)
+                })
+            })
+            .collect();
+        let prescription = prescribe_surprise(&catalog, &ctx, roll).unwrap();
+        assert_ne!(prescription.definition.id, first);
+
+        // Modes still narrow the choice
+        crate::rehab::start(
+            &mut ctx.user_state.rehab,
+            "left knee",
+            ctx.now,
+            None,
+            vec![],
+        );
+        for roll in [0.0, 0.5, 0.99] {
+            let prescription = prescribe_surprise(&catalog, &ctx, roll).unwrap();
+            assert_eq!(
+                prescription.definition.category,
+                MicrodoseCategory::Mobility
+            );
+            assert_eq!(prescription.rationale.len(), 2);
+        }
+    }
+
     #[test]
     fn test_mixed_history_with_skip_patterns() {
         // Test that round-robin works correctly with mix of Real and ShownButSkipped
@@ -417,32 +1391,20 @@ mod tests {
         // Create history: VO2 (real) → GTG (skipped) → Mobility (real)
         ctx.recent_sessions = vec![
             crate::SessionKind::Real(crate::MicrodoseSession {
-                id: uuid::Uuid::new_v4(),
-                definition_id: "mobility_hip_cars".to_string(),
-                performed_at: now - Duration::hours(1),
                 started_at: Some(now - Duration::hours(1)),
                 completed_at: Some(now - Duration::hours(1)),
                 actual_duration_seconds: Some(60),
-                metrics_realized: vec![],
-                perceived_rpe: None,
-                avg_hr: None,
-                max_hr: None,
+                ..crate::MicrodoseSession::new("mobility_hip_cars", now - Duration::hours(1))
             }),
             crate::SessionKind::ShownButSkipped {
                 definition_id: "gtg_pullup_band".to_string(),
                 shown_at: now - Duration::hours(2),
             },
             crate::SessionKind::Real(crate::MicrodoseSession {
-                id: uuid::Uuid::new_v4(),
-                definition_id: "emom_burpee_5m".to_string(),
-                performed_at: now - Duration::hours(3),
                 started_at: Some(now - Duration::hours(3)),
                 completed_at: Some(now - Duration::hours(3)),
                 actual_duration_seconds: Some(300),
-                metrics_realized: vec![],
-                perceived_rpe: None,
-                avg_hr: None,
-                max_hr: None,
+                ..crate::MicrodoseSession::new("emom_burpee_5m", now - Duration::hours(3))
             }),
         ];
 
@@ -1,4 +1,8 @@
 //! Error types for the cardio_core library.
+//!
+//! Every variant has a stable [`Error::code`] (e.g. `"not_found"`) for
+//! frontends that branch on the kind of failure, and an [`Error::exit_code`]
+//! the CLI exits with.
 
 use std::io;
 
@@ -16,13 +20,17 @@ pub enum Error {
     #[error("JSON error: {0}")]
     Json(#[from] serde_json::Error),
 
-    /// CSV error
+    /// CSV error (message only, so the variant exists without `csv-archive`)
     #[error("CSV error: {0}")]
-    Csv(#[from] csv::Error),
+    Csv(String),
 
-    /// TOML parsing error
+    /// TOML parsing error (message only, so the variant exists without `config-toml`)
     #[error("TOML error: {0}")]
-    Toml(#[from] toml::de::Error),
+    Toml(String),
+
+    /// SQLite error (message only, so the variant exists without `analytics`)
+    #[error("SQLite error: {0}")]
+    Sqlite(String),
 
     /// Configuration validation error
     #[error("Configuration error: {0}")]
@@ -36,11 +44,140 @@ pub enum Error {
     #[error("State error: {0}")]
     State(String),
 
+    /// A file lock was still held by another process after the timeout
+    #[error(
+        "Timed out after {:.1}s waiting for the lock on {path:?}; another krep process may be stuck",
+        .waited.as_secs_f64()
+    )]
+    LockTimeout {
+        path: std::path::PathBuf,
+        waited: std::time::Duration,
+    },
+
     /// Prescription engine error
     #[error("Prescription error: {0}")]
     Prescription(String),
 
+    /// A data file exists but its contents can't be read back
+    #[error("Corrupted data in {path:?}: {detail}")]
+    Corruption {
+        path: std::path::PathBuf,
+        detail: String,
+    },
+
+    /// A session, definition, movement or other named item doesn't exist
+    #[error("{0}")]
+    NotFound(String),
+
+    /// A file was written by a newer krep in a format this one can't read
+    #[error(
+        "{path:?} uses format version {found}, but this krep only reads up to {supported}; upgrade krep"
+    )]
+    SchemaVersion {
+        path: std::path::PathBuf,
+        found: u32,
+        supported: u32,
+    },
+
     /// Generic error
     #[error("{0}")]
     Other(String),
 }
+
+impl Error {
+    /// Stable identifier of the kind of failure, for frontends to branch on
+    /// (the messages may change between releases; these don't)
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::Io(_) => "io",
+            Error::Json(_) => "json",
+            Error::Csv(_) => "csv",
+            Error::Toml(_) => "toml",
+            Error::Sqlite(_) => "sqlite",
+            Error::Config(_) => "config",
+            Error::CatalogValidation(_) => "catalog_validation",
+            Error::State(_) => "state",
+            Error::LockTimeout { .. } => "lock_timeout",
+            Error::Prescription(_) => "prescription",
+            Error::Corruption { .. } => "corruption",
+            Error::NotFound(_) => "not_found",
+            Error::SchemaVersion { .. } => "schema_version",
+            Error::Other(_) => "other",
+        }
+    }
+
+    /// Process exit status for the CLI, following the BSD `sysexits.h`
+    /// conventions where one fits
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            // EX_DATAERR: input data was malformed
+            Error::Json(_) | Error::Csv(_) | Error::Corruption { .. } => 65,
+            // EX_NOINPUT: the named thing doesn't exist
+            Error::NotFound(_) => 66,
+            // EX_SOFTWARE
+            Error::CatalogValidation(_) | Error::Prescription(_) | Error::State(_) => 70,
+            // EX_IOERR
+            Error::Io(_) | Error::Sqlite(_) => 74,
+            // EX_TEMPFAIL: retrying later may work
+            Error::LockTimeout { .. } => 75,
+            // EX_PROTOCOL: the other side speaks a newer format
+            Error::SchemaVersion { .. } => 76,
+            // EX_CONFIG
+            Error::Config(_) | Error::Toml(_) => 78,
+            Error::Other(_) => 1,
+        }
+    }
+}
+
+#[cfg(feature = "csv-archive")]
+impl From<csv::Error> for Error {
+    fn from(e: csv::Error) -> Self {
+        Error::Csv(e.to_string())
+    }
+}
+
+#[cfg(feature = "config-toml")]
+impl From<toml::de::Error> for Error {
+    fn from(e: toml::de::Error) -> Self {
+        Error::Toml(e.to_string())
+    }
+}
+
+#[cfg(feature = "analytics")]
+impl From<rusqlite::Error> for Error {
+    fn from(e: rusqlite::Error) -> Self {
+        Error::Sqlite(e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_codes() {
+        let not_found = Error::NotFound("No session with ID 1".into());
+        assert_eq!(not_found.code(), "not_found");
+        assert_eq!(not_found.exit_code(), 66);
+        assert_eq!(not_found.to_string(), "No session with ID 1");
+
+        let newer = Error::SchemaVersion {
+            path: PathBuf::from("sessions.wal"),
+            found: 3,
+            supported: 1,
+        };
+        assert_eq!(newer.code(), "schema_version");
+        assert!(newer.to_string().contains("format version 3"));
+
+        let corrupt = Error::Corruption {
+            path: PathBuf::from("sessions.csv"),
+            detail: "Invalid UUID".into(),
+        };
+        assert_eq!(corrupt.code(), "corruption");
+        assert_eq!(corrupt.exit_code(), 65);
+
+        assert_eq!(Error::Config("x".into()).exit_code(), 78);
+        assert_eq!(Error::Other("x".into()).exit_code(), 1);
+    }
+}
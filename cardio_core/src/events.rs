@@ -0,0 +1,249 @@
+//! Hooks on a dose's lifecycle.
+//!
+//! A [`SessionObserver`] hears about each prescription shown, session
+//! logged, skip recorded, dose abandoned and progression change made
+//! through a [`DataStore`], which calls every observer subscribed to its
+//! [`Observers`]. The daily note ([`DailyNote`]) and the status file
+//! ([`StatusFile`]) are observers subscribed by [`DataStore::open`];
+//! frontends and embedders add their own (a webhook, a chat bot) with
+//! [`Observers::subscribe`] instead of wiring side effects around each call.
+//!
+//! Observers run after the change is saved, so one that fails only produces
+//! a warning: in [`Logged::warnings`](crate::store::Logged) for a logged
+//! session, in the log otherwise.
+
+use crate::progression_log::ProgressionEvent;
+use crate::timing::{AbandonEvent, SkipEvent};
+use crate::{
+    journal, DataStore, MicrodoseDefinition, MicrodoseSession, PrescribedMicrodose, Result,
+};
+use std::sync::Arc;
+
+/// Reacts to what happens through a [`DataStore`]; every hook defaults to
+/// doing nothing
+pub trait SessionObserver: Send + Sync {
+    /// What the observer does, for warnings (e.g. "daily note")
+    fn name(&self) -> &str;
+
+    /// A prescription was made (shown, not yet done)
+    fn on_prescribed(&self, _store: &DataStore, _prescription: &PrescribedMicrodose) -> Result<()> {
+        Ok(())
+    }
+
+    /// A session was appended to the WAL and the state saved
+    fn on_logged(
+        &self,
+        _store: &DataStore,
+        _session: &MicrodoseSession,
+        _definition: &MicrodoseDefinition,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    /// A prescription was skipped (or its popup ignored)
+    fn on_skipped(&self, _store: &DataStore, _skip: &SkipEvent) -> Result<()> {
+        Ok(())
+    }
+
+    /// A started dose was never finished
+    fn on_abandoned(&self, _store: &DataStore, _abandoned: &AbandonEvent) -> Result<()> {
+        Ok(())
+    }
+
+    /// Progressions changed (never called with no changes)
+    fn on_progressed(&self, _store: &DataStore, _changes: &[ProgressionEvent]) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// The observers a [`DataStore`] notifies, in subscription order
+#[derive(Clone, Default)]
+pub struct Observers {
+    observers: Vec<Arc<dyn SessionObserver>>,
+}
+
+impl std::fmt::Debug for Observers {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list()
+            .entries(self.observers.iter().map(|o| o.name()))
+            .finish()
+    }
+}
+
+impl Observers {
+    pub fn subscribe(&mut self, observer: Arc<dyn SessionObserver>) {
+        self.observers.push(observer);
+    }
+
+    pub fn prescribed(&self, store: &DataStore, prescription: &PrescribedMicrodose) -> Vec<String> {
+        self.notify(|o| o.on_prescribed(store, prescription))
+    }
+
+    pub fn logged(
+        &self,
+        store: &DataStore,
+        session: &MicrodoseSession,
+        definition: &MicrodoseDefinition,
+    ) -> Vec<String> {
+        self.notify(|o| o.on_logged(store, session, definition))
+    }
+
+    pub fn skipped(&self, store: &DataStore, skip: &SkipEvent) -> Vec<String> {
+        self.notify(|o| o.on_skipped(store, skip))
+    }
+
+    pub fn abandoned(&self, store: &DataStore, abandoned: &AbandonEvent) -> Vec<String> {
+        self.notify(|o| o.on_abandoned(store, abandoned))
+    }
+
+    pub fn progressed(&self, store: &DataStore, changes: &[ProgressionEvent]) -> Vec<String> {
+        if changes.is_empty() {
+            return Vec::new();
+        }
+        self.notify(|o| o.on_progressed(store, changes))
+    }
+
+    /// Call every observer, collecting failures as warnings
+    fn notify(&self, call: impl Fn(&dyn SessionObserver) -> Result<()>) -> Vec<String> {
+        self.observers
+            .iter()
+            .filter_map(|observer| {
+                call(observer.as_ref())
+                    .err()
+                    .map(|e| format!("Could not update the {}: {}", observer.name(), e))
+            })
+            .collect()
+    }
+}
+
+/// Appends logged doses to the `[journal] daily_note`, if one is configured
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DailyNote;
+
+impl SessionObserver for DailyNote {
+    fn name(&self) -> &str {
+        "daily note"
+    }
+
+    fn on_logged(
+        &self,
+        store: &DataStore,
+        session: &MicrodoseSession,
+        definition: &MicrodoseDefinition,
+    ) -> Result<()> {
+        journal::record_dose(
+            &store.config.journal,
+            session,
+            &definition.name,
+            &store.config.timezone(),
+        )
+    }
+}
+
+/// Refreshes `[status] file`, if one is configured, after each dose
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StatusFile;
+
+impl SessionObserver for StatusFile {
+    fn name(&self) -> &str {
+        "status file"
+    }
+
+    fn on_logged(
+        &self,
+        store: &DataStore,
+        _session: &MicrodoseSession,
+        _definition: &MicrodoseDefinition,
+    ) -> Result<()> {
+        store.write_status_file()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Config, MicrodoseCategory};
+    use std::sync::Mutex;
+
+    /// Writes down every hook it hears
+    #[derive(Default)]
+    struct Recorder {
+        heard: Mutex<Vec<String>>,
+    }
+
+    impl SessionObserver for Recorder {
+        fn name(&self) -> &str {
+            "recorder"
+        }
+
+        fn on_prescribed(&self, _: &DataStore, prescription: &PrescribedMicrodose) -> Result<()> {
+            let id = &prescription.definition.id;
+            self.heard
+                .lock()
+                .unwrap()
+                .push(format!("prescribed {}", id));
+            Ok(())
+        }
+
+        fn on_logged(
+            &self,
+            _: &DataStore,
+            _: &MicrodoseSession,
+            definition: &MicrodoseDefinition,
+        ) -> Result<()> {
+            let id = &definition.id;
+            self.heard.lock().unwrap().push(format!("logged {}", id));
+            Err(crate::Error::Other("webhook down".into()))
+        }
+
+        fn on_skipped(&self, _: &DataStore, skip: &SkipEvent) -> Result<()> {
+            let id = &skip.definition_id;
+            self.heard.lock().unwrap().push(format!("skipped {}", id));
+            Ok(())
+        }
+
+        fn on_progressed(&self, _: &DataStore, changes: &[ProgressionEvent]) -> Result<()> {
+            self.heard
+                .lock()
+                .unwrap()
+                .push(format!("progressed {}", changes.len()));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_observers_hear_the_lifecycle() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut store = DataStore::open(temp_dir.path(), &Config::default()).unwrap();
+        let recorder = Arc::new(Recorder::default());
+        store.observers.subscribe(recorder.clone());
+        let now = store.now();
+
+        let prescription = store
+            .next_prescription(&[], Some(MicrodoseCategory::Mobility), now)
+            .unwrap();
+        let id = prescription.definition.id.clone();
+        store.skip(&id, None).unwrap();
+        let before = store.state.clone();
+        // A failing observer only warns
+        let logged = store
+            .log(&prescription.done_as_prescribed(now), &prescription)
+            .unwrap();
+        assert_eq!(
+            logged.warnings,
+            vec!["Could not update the recorder: webhook down".to_string()]
+        );
+        assert_eq!(store.recent_sessions(1).unwrap().len(), 1);
+        store.record_progression(&before, now).unwrap();
+
+        assert_eq!(
+            *recorder.heard.lock().unwrap(),
+            vec![
+                format!("prescribed {}", id),
+                format!("skipped {}", id),
+                format!("logged {}", id),
+                "progressed 1".to_string(),
+            ]
+        );
+    }
+}
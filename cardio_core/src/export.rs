@@ -0,0 +1,125 @@
+//! Streaming session export.
+//!
+//! [`write_ndjson`] writes one JSON session per line as it reads them, for
+//! export commands, servers and sync tooling. Only the WAL's session IDs are
+//! kept in memory (to drop sessions that are also in the CSV archive after an
+//! interrupted rollup), never the sessions themselves.
+
+use crate::history::HistoryQuery;
+use crate::{FileStorage, MicrodoseSession, Result};
+use std::collections::HashSet;
+use std::io::Write;
+
+/// Write the sessions matching `query` as newline-delimited JSON
+///
+/// Archived sessions come first, then the WAL, each in file order (oldest
+/// first for data written by krep). Returns the number of sessions written.
+pub fn write_ndjson<W: Write>(
+    mut writer: W,
+    storage: &FileStorage,
+    query: &HistoryQuery,
+) -> Result<usize> {
+    let mut written = 0;
+    let mut write = |session: &MicrodoseSession| -> Result<()> {
+        serde_json::to_writer(&mut writer, session)?;
+        writer.write_all(b"\n")?;
+        written += 1;
+        Ok(())
+    };
+
+    #[cfg(feature = "csv-archive")]
+    if storage.csv_path.exists() {
+        let mut in_wal = HashSet::new();
+        crate::wal::for_each_session(&storage.wal_path, storage.lock_timeout, |session| {
+            in_wal.insert(session.id);
+            Ok(())
+        })?;
+
+        let archived =
+            crate::history::CsvSessions::open(&storage.csv_path, query.since, query.until)?;
+        for session in archived {
+            if query.matches(&session) && !in_wal.contains(&session.id) {
+                write(&session)?;
+            }
+        }
+    }
+
+    // The WAL can repeat an ID only through corruption; keep the first
+    let mut seen = HashSet::new();
+    crate::wal::for_each_session(&storage.wal_path, storage.lock_timeout, |session| {
+        if query.matches(&session) && seen.insert(session.id) {
+            write(&session)?;
+        }
+        Ok(())
+    })?;
+
+    writer.flush()?;
+    Ok(written)
+}
+
+#[cfg(all(test, feature = "csv-archive"))]
+mod tests {
+    use super::*;
+    use crate::wal::{JsonlSink, SessionSink};
+    use chrono::{Duration, Utc};
+    use uuid::Uuid;
+
+    fn session(definition_id: &str, days_ago: i64, tags: &[&str]) -> MicrodoseSession {
+        let at = Utc::now() - Duration::days(days_ago);
+        MicrodoseSession {
+            started_at: Some(at),
+            completed_at: Some(at),
+            actual_duration_seconds: Some(300),
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            ..MicrodoseSession::new(definition_id, at)
+        }
+    }
+
+    #[test]
+    fn test_write_ndjson_filters_and_dedups() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let storage = FileStorage::new(temp_dir.path());
+
+        let old = session("emom_burpee_5m", 40, &[]);
+        let archived = session("gtg_pullup_ladder", 10, &["travel"]);
+        let mut sink = JsonlSink::new(&storage.wal_path);
+        sink.append(&old).unwrap();
+        sink.append(&archived).unwrap();
+        crate::csv_rollup::wal_to_csv_and_archive(
+            &storage.wal_path,
+            &storage.csv_path,
+            crate::lock::DEFAULT_TIMEOUT,
+        )
+        .unwrap();
+
+        // Recent sessions, one of them also left in the archive by a crashed rollup
+        let recent = session("gtg_pullup_ladder", 1, &["travel"]);
+        let untagged = session("gtg_pullup_ladder", 1, &[]);
+        let mut sink = JsonlSink::new(&storage.wal_path);
+        sink.append(&archived).unwrap();
+        sink.append(&recent).unwrap();
+        sink.append(&untagged).unwrap();
+
+        let query = HistoryQuery {
+            since: Some(Utc::now() - Duration::days(30)),
+            tags: vec!["travel".into()],
+            ..HistoryQuery::default()
+        };
+        let mut out = Vec::new();
+        assert_eq!(write_ndjson(&mut out, &storage, &query).unwrap(), 2);
+
+        let ids: Vec<Uuid> = String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str::<MicrodoseSession>(line).unwrap().id)
+            .collect();
+        assert_eq!(ids, vec![archived.id, recent.id]);
+
+        let query = HistoryQuery {
+            definition_id: Some("emom_burpee_5m".into()),
+            ..HistoryQuery::default()
+        };
+        let mut out = Vec::new();
+        assert_eq!(write_ndjson(&mut out, &storage, &query).unwrap(), 1);
+    }
+}
@@ -0,0 +1,256 @@
+//! Weekly dose goals.
+//!
+//! A [`WeeklyGoal`] counts real sessions per local calendar week (Monday to
+//! Sunday). Weeks with a `travel`-tagged session use the lighter travel goal
+//! when one is set. A week that meets its goal also forgives missed days in
+//! the streak, so rest days don't reset it.
+
+use crate::off_days::{self, OffRange};
+use crate::{LocalTz, SessionKind, WeeklyGoal};
+use chrono::{DateTime, Datelike, Duration, NaiveDate, Utc};
+use serde::Serialize;
+
+/// Session tag that marks a travel week
+pub const TRAVEL_TAG: &str = "travel";
+
+/// Monday of the local calendar week containing `date`
+pub fn week_start(date: NaiveDate) -> NaiveDate {
+    date - Duration::days(i64::from(date.weekday().num_days_from_monday()))
+}
+
+/// Progress toward the goal in one calendar week
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct GoalProgress {
+    /// Monday of the week
+    pub week_start: NaiveDate,
+    pub target: u32,
+    pub done: u32,
+    /// Whether the lighter travel goal applies
+    pub travel: bool,
+}
+
+impl GoalProgress {
+    /// Doses still needed this week
+    pub fn remaining(&self) -> u32 {
+        self.target.saturating_sub(self.done)
+    }
+
+    pub fn met(&self) -> bool {
+        self.done >= self.target
+    }
+
+    /// Share of the goal done, capped at 1.0
+    pub fn fraction(&self) -> f64 {
+        if self.target == 0 {
+            return 1.0;
+        }
+        (f64::from(self.done) / f64::from(self.target)).min(1.0)
+    }
+}
+
+/// Progress for the calendar week containing `date`
+pub fn week_progress(
+    goal: &WeeklyGoal,
+    sessions: &[SessionKind],
+    tz: &LocalTz,
+    date: NaiveDate,
+) -> GoalProgress {
+    let start = week_start(date);
+    let in_week: Vec<_> = sessions
+        .iter()
+        .filter_map(|s| s.as_real())
+        .filter(|s| week_start(tz.date_of(s.performed_at)) == start)
+        .collect();
+    let travel = in_week
+        .iter()
+        .any(|s| s.tags.iter().any(|t| t == TRAVEL_TAG));
+    let target = match (travel, goal.travel_weekly_doses) {
+        (true, Some(lighter)) => lighter,
+        _ => goal.weekly_doses,
+    };
+
+    GoalProgress {
+        week_start: start,
+        target,
+        done: in_week.len() as u32,
+        travel,
+    }
+}
+
+/// Progress for the current local week
+pub fn current_progress(
+    goal: &WeeklyGoal,
+    sessions: &[SessionKind],
+    tz: &LocalTz,
+    now: DateTime<Utc>,
+) -> GoalProgress {
+    week_progress(goal, sessions, tz, tz.date_of(now))
+}
+
+/// Day streak where missed days in goal-meeting weeks don't break it
+///
+/// Like [`current_streak`](crate::history::current_streak), but a day
+/// without sessions still counts when its calendar week met the goal.
+/// Off days without sessions are passed over. Without a goal this is the
+/// plain streak. Days before the first loaded
+/// session are never counted.
+pub fn forgiving_streak(
+    sessions: &[SessionKind],
+    goal: Option<&WeeklyGoal>,
+    off_days: &[OffRange],
+    tz: &LocalTz,
+    now: DateTime<Utc>,
+) -> u32 {
+    let Some(goal) = goal else {
+        return crate::history::current_streak(sessions, off_days, tz, now);
+    };
+    let days = crate::history::sessions_per_local_day(sessions, tz);
+    let Some(first) = days.keys().next().copied() else {
+        return 0;
+    };
+
+    let mut day = tz.date_of(now);
+    if !days.contains_key(&day) {
+        day = match day.pred_opt() {
+            Some(yesterday) => yesterday,
+            None => return 0,
+        };
+    }
+
+    let mut streak = 0;
+    let mut week_met: Option<(NaiveDate, bool)> = None;
+    while day >= first {
+        if !days.contains_key(&day) && off_days::is_off(off_days, day) {
+            day = match day.pred_opt() {
+                Some(prev) => prev,
+                None => break,
+            };
+            continue;
+        }
+        if !days.contains_key(&day) {
+            let start = week_start(day);
+            let met = match week_met {
+                Some((cached, met)) if cached == start => met,
+                _ => week_progress(goal, sessions, tz, day).met(),
+            };
+            week_met = Some((start, met));
+            if !met {
+                break;
+            }
+        }
+        streak += 1;
+        day = match day.pred_opt() {
+            Some(prev) => prev,
+            None => break,
+        };
+    }
+    streak
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MicrodoseSession;
+    use chrono::TimeZone;
+
+    fn session_on(day: u32, tags: &[&str]) -> SessionKind {
+        SessionKind::Real(MicrodoseSession {
+            id: uuid::Uuid::new_v4(),
+            definition_id: "emom_burpee_5m".into(),
+            // March 2024: the 4th and 11th are Mondays
+            performed_at: Utc.with_ymd_and_hms(2024, 3, day, 12, 0, 0).unwrap(),
+            started_at: None,
+            completed_at: None,
+            actual_duration_seconds: Some(300),
+            metrics_realized: vec![],
+            perceived_rpe: None,
+            avg_hr: None,
+            max_hr: None,
+            hr_recovery: None,
+            hr_series_path: None,
+            paused_seconds: None,
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+        })
+    }
+
+    fn utc() -> LocalTz {
+        LocalTz::from_name(Some("UTC")).unwrap()
+    }
+
+    #[test]
+    fn test_week_progress_uses_travel_goal() {
+        let goal = WeeklyGoal {
+            weekly_doses: 5,
+            travel_weekly_doses: Some(2),
+        };
+        let date = NaiveDate::from_ymd_opt(2024, 3, 7).unwrap();
+        assert_eq!(
+            week_start(date),
+            NaiveDate::from_ymd_opt(2024, 3, 4).unwrap()
+        );
+
+        let home = vec![session_on(4, &[]), session_on(6, &[]), session_on(3, &[])];
+        let progress = week_progress(&goal, &home, &utc(), date);
+        assert_eq!(progress.done, 2);
+        assert_eq!(progress.target, 5);
+        assert_eq!(progress.remaining(), 3);
+        assert!(!progress.met());
+
+        let away = vec![session_on(4, &["travel"]), session_on(6, &[])];
+        let progress = week_progress(&goal, &away, &utc(), date);
+        assert!(progress.travel);
+        assert_eq!(progress.target, 2);
+        assert!(progress.met());
+    }
+
+    #[test]
+    fn test_forgiving_streak() {
+        let now = Utc.with_ymd_and_hms(2024, 3, 13, 20, 0, 0).unwrap();
+        // Week of the 4th: three sessions (Mon, Wed, Fri); this week: Tue, Wed
+        let sessions = vec![
+            session_on(13, &[]),
+            session_on(12, &[]),
+            session_on(8, &[]),
+            session_on(6, &[]),
+            session_on(4, &[]),
+        ];
+
+        assert_eq!(forgiving_streak(&sessions, None, &[], &utc(), now), 2);
+
+        // Last week met a 3-dose goal, so its gaps are forgiven, but Monday
+        // the 11th (this week, goal not yet met) breaks the streak
+        let goal = WeeklyGoal {
+            weekly_doses: 3,
+            travel_weekly_doses: None,
+        };
+        assert_eq!(
+            forgiving_streak(&sessions, Some(&goal), &[], &utc(), now),
+            2
+        );
+
+        let mut sessions = sessions;
+        sessions.push(session_on(11, &[]));
+        assert_eq!(
+            forgiving_streak(&sessions, Some(&goal), &[], &utc(), now),
+            10
+        );
+
+        let strict = WeeklyGoal {
+            weekly_doses: 4,
+            travel_weekly_doses: None,
+        };
+        assert_eq!(
+            forgiving_streak(&sessions, Some(&strict), &[], &utc(), now),
+            3
+        );
+
+        // A holiday over the gaps keeps the strict streak going without
+        // counting the days off
+        let holiday: Vec<OffRange> = vec!["2024-03-09..2024-03-10".parse().unwrap()];
+        assert_eq!(
+            forgiving_streak(&sessions, Some(&strict), &holiday, &utc(), now),
+            4
+        );
+        assert_eq!(forgiving_streak(&sessions, None, &holiday, &utc(), now), 4);
+    }
+}
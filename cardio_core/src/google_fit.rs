@@ -0,0 +1,288 @@
+//! Google Fit Takeout import.
+//!
+//! A Takeout export keeps one JSON file per recorded activity under
+//! `Takeout/Fit/All Sessions/`. Short vigorous bouts (HIIT, skipping, stair
+//! sprints, or any activity averaging vigorous heart points) become sessions
+//! tagged [`IMPORT_TAG`]; strength workouts go to the strength history that
+//! `krep stats --strength` reads. Everything else is left out.
+//!
+//! Imported sessions use `google_fit_<activity>` definition IDs, which are
+//! not in the catalog: they count towards totals and streaks but never move
+//! a progression.
+
+use crate::{ExternalStrengthSignal, MicrodoseSession, Result, StrengthSessionType};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+/// Tag on every imported session
+pub const IMPORT_TAG: &str = "google-fit";
+
+/// Prefix of imported sessions' definition IDs
+pub const DEFINITION_PREFIX: &str = "google_fit_";
+
+/// Longest bout still counted as a microdose
+pub const MAX_MICRODOSE_MINUTES: i64 = 20;
+
+/// Shortest bout worth importing
+pub const MIN_MICRODOSE_MINUTES: i64 = 1;
+
+/// Heart points per minute from which a bout counts as vigorous (Fit awards
+/// 1 per moderate minute and 2 per vigorous minute)
+pub const VIGOROUS_HEART_POINTS_PER_MINUTE: f64 = 1.5;
+
+/// Activities that are interval-style whatever their heart points
+const INTERVAL_ACTIVITIES: [&str; 6] = [
+    "high_intensity_interval_training",
+    "interval_training",
+    "circuit_training",
+    "crossfit",
+    "jumping_rope",
+    "stair_climbing",
+];
+
+/// Activities recorded to the strength history
+const STRENGTH_ACTIVITIES: [&str; 2] = ["strength_training", "weightlifting"];
+
+/// One activity from the export
+#[derive(Clone, Debug, PartialEq)]
+pub struct FitActivity {
+    /// Fit activity type, e.g. `running` or `high_intensity_interval_training`
+    pub activity: String,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    /// Heart points earned, when recorded
+    pub heart_points: Option<f64>,
+}
+
+impl FitActivity {
+    pub fn duration_seconds(&self) -> i64 {
+        (self.end - self.start).num_seconds()
+    }
+}
+
+/// What an activity imports as
+#[derive(Clone, Debug)]
+pub enum FitImport {
+    /// A logged session
+    Session(MicrodoseSession),
+    /// A strength history entry
+    Strength(ExternalStrengthSignal),
+}
+
+/// Session file format (`All Sessions/*.json`)
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SessionFile {
+    fitness_activity: String,
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+    #[serde(default)]
+    aggregate: Vec<Aggregate>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Aggregate {
+    metric_name: String,
+    #[serde(default)]
+    float_value: Option<f64>,
+    #[serde(default)]
+    int_value: Option<i64>,
+}
+
+/// Parse one session file
+pub fn parse_session(json: &str) -> Result<FitActivity> {
+    let file: SessionFile = serde_json::from_str(json)?;
+    let heart_points = file
+        .aggregate
+        .iter()
+        .find(|a| a.metric_name == "com.google.heart_minutes.summary")
+        .and_then(|a| a.float_value.or(a.int_value.map(|v| v as f64)));
+    Ok(FitActivity {
+        activity: file.fitness_activity,
+        start: file.start_time,
+        end: file.end_time,
+        heart_points,
+    })
+}
+
+/// The session files under `path`: a Takeout directory (or its `Fit` or
+/// `All Sessions` folder), or a single session file
+pub fn session_files(path: &Path) -> Result<Vec<PathBuf>> {
+    if path.is_file() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    let dir = [
+        path.join("Takeout").join("Fit").join("All Sessions"),
+        path.join("Fit").join("All Sessions"),
+        path.join("All Sessions"),
+        path.to_path_buf(),
+    ]
+    .into_iter()
+    .find(|dir| dir.is_dir())
+    .ok_or_else(|| crate::Error::Other(format!("{:?} is not a Takeout export", path)))?;
+
+    let mut files: Vec<PathBuf> = std::fs::read_dir(&dir)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|p| p.extension().is_some_and(|ext| ext == "json"))
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+/// Read every session file under `path`, oldest first; files that fail to
+/// parse are logged and skipped
+pub fn read_takeout(path: &Path) -> Result<Vec<FitActivity>> {
+    let mut activities = Vec::new();
+    for file in session_files(path)? {
+        match std::fs::read_to_string(&file)
+            .map_err(crate::Error::from)
+            .and_then(|json| parse_session(&json))
+        {
+            Ok(activity) => activities.push(activity),
+            Err(e) => tracing::warn!("Skipping Fit session {:?}: {}", file, e),
+        }
+    }
+    activities.sort_by_key(|a| a.start);
+    Ok(activities)
+}
+
+/// What `activity` imports as, or `None` when it isn't plausible
+pub fn classify(activity: &FitActivity) -> Option<FitImport> {
+    let seconds = activity.duration_seconds();
+    if seconds <= 0 {
+        return None;
+    }
+
+    if STRENGTH_ACTIVITIES.contains(&activity.activity.as_str()) {
+        return Some(FitImport::Strength(ExternalStrengthSignal {
+            last_session_at: activity.start,
+            session_type: StrengthSessionType::Other(activity.activity.clone()),
+        }));
+    }
+
+    let minutes = seconds as f64 / 60.0;
+    if minutes < MIN_MICRODOSE_MINUTES as f64 || minutes > MAX_MICRODOSE_MINUTES as f64 {
+        return None;
+    }
+    let interval = INTERVAL_ACTIVITIES
+        .iter()
+        .any(|a| activity.activity.split('.').next() == Some(a));
+    let vigorous = activity
+        .heart_points
+        .is_some_and(|points| points / minutes >= VIGOROUS_HEART_POINTS_PER_MINUTE);
+    if !interval && !vigorous {
+        return None;
+    }
+
+    Some(FitImport::Session(MicrodoseSession {
+        id: Uuid::new_v4(),
+        definition_id: definition_id(&activity.activity),
+        performed_at: activity.start,
+        started_at: Some(activity.start),
+        completed_at: Some(activity.end),
+        actual_duration_seconds: u32::try_from(seconds).ok(),
+        metrics_realized: vec![],
+        perceived_rpe: None,
+        avg_hr: None,
+        max_hr: None,
+        hr_recovery: None,
+        hr_series_path: None,
+        paused_seconds: None,
+        tags: vec![IMPORT_TAG.to_string()],
+    }))
+}
+
+/// Definition ID for an imported activity, e.g. `google_fit_jumping_rope`
+pub fn definition_id(activity: &str) -> String {
+    format!("{}{}", DEFINITION_PREFIX, activity.replace('.', "_"))
+}
+
+/// Whether `session` is an earlier import of the same bout
+pub fn is_same_import(session: &MicrodoseSession, candidate: &MicrodoseSession) -> bool {
+    session.definition_id == candidate.definition_id
+        && session.performed_at == candidate.performed_at
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn activity(kind: &str, minutes: i64, heart_points: Option<f64>) -> FitActivity {
+        let start = "2024-05-01T07:00:00Z".parse().unwrap();
+        FitActivity {
+            activity: kind.into(),
+            start,
+            end: start + chrono::Duration::minutes(minutes),
+            heart_points,
+        }
+    }
+
+    #[test]
+    fn test_parse_session() {
+        let json = r#"{
+            "fitnessActivity": "high_intensity_interval_training",
+            "startTime": "2024-05-01T07:00:00.000Z",
+            "endTime": "2024-05-01T07:08:00.000Z",
+            "duration": "480s",
+            "segment": [],
+            "aggregate": [
+                {"metricName": "com.google.calories.expended", "floatValue": 80.5},
+                {"metricName": "com.google.heart_minutes.summary", "floatValue": 16.0}
+            ]
+        }"#;
+        let parsed = parse_session(json).unwrap();
+        assert_eq!(parsed.activity, "high_intensity_interval_training");
+        assert_eq!(parsed.duration_seconds(), 480);
+        assert_eq!(parsed.heart_points, Some(16.0));
+        assert!(parse_session("{}").is_err());
+    }
+
+    #[test]
+    fn test_classify() {
+        // Interval activities count regardless of heart points
+        let Some(FitImport::Session(session)) = classify(&activity("jumping_rope", 5, None)) else {
+            panic!("jumping rope should import as a session");
+        };
+        assert_eq!(session.definition_id, "google_fit_jumping_rope");
+        assert_eq!(session.actual_duration_seconds, Some(300));
+        assert_eq!(session.tags, vec![IMPORT_TAG]);
+
+        // Other activities need vigorous heart points
+        assert!(classify(&activity("running", 10, Some(20.0))).is_some());
+        assert!(classify(&activity("running", 10, Some(10.0))).is_none());
+        assert!(classify(&activity("walking", 10, None)).is_none());
+
+        // Too long or too short for a microdose
+        assert!(classify(&activity("interval_training", 45, Some(90.0))).is_none());
+        assert!(classify(&activity("interval_training", 0, None)).is_none());
+
+        // Strength workouts go to the strength history, whatever the length
+        assert!(matches!(
+            classify(&activity("strength_training", 60, None)),
+            Some(FitImport::Strength(_))
+        ));
+    }
+
+    #[test]
+    fn test_read_takeout_layout() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let sessions = temp_dir.path().join("Takeout/Fit/All Sessions");
+        std::fs::create_dir_all(&sessions).unwrap();
+        std::fs::write(
+            sessions.join("2024-05-01T07_00_00Z_HIIT.json"),
+            r#"{"fitnessActivity": "interval_training",
+                "startTime": "2024-05-01T07:00:00Z", "endTime": "2024-05-01T07:06:00Z"}"#,
+        )
+        .unwrap();
+        std::fs::write(sessions.join("broken.json"), "not json").unwrap();
+        std::fs::write(sessions.join("notes.txt"), "ignored").unwrap();
+
+        let activities = read_takeout(temp_dir.path()).unwrap();
+        assert_eq!(activities.len(), 1);
+        assert_eq!(activities[0].activity, "interval_training");
+        assert!(read_takeout(&temp_dir.path().join("missing")).is_err());
+    }
+}
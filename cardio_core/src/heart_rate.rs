@@ -0,0 +1,288 @@
+//! Heart-rate zone targets.
+//!
+//! Zones are a share of the user's max HR (`[heart_rate]` config, given
+//! directly or estimated from age). Prescriptions carry the zone for their
+//! category and `krep stats` compares logged avg/max HR against it.
+//!
+//! HR recovery (the drop in the 60 seconds after a session ends) is stored
+//! per session; a growing drop is a simple fitness indicator.
+
+use crate::{Catalog, MicrodoseCategory, SessionKind};
+use serde::{Deserialize, Serialize};
+
+/// Which logged value a zone is judged on
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HrMeasure {
+    /// Session max HR must reach the zone (short hard efforts)
+    Peak,
+    /// Session avg HR must sit inside the zone (steady efforts)
+    Average,
+}
+
+/// Target zone as a percentage range of max HR
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HrZone {
+    pub low_pct: u8,
+    pub high_pct: u8,
+    pub measure: HrMeasure,
+}
+
+impl HrZone {
+    pub const VO2: HrZone = HrZone {
+        low_pct: 85,
+        high_pct: 92,
+        measure: HrMeasure::Peak,
+    };
+
+    pub const ZONE2: HrZone = HrZone {
+        low_pct: 60,
+        high_pct: 70,
+        measure: HrMeasure::Average,
+    };
+
+    /// Zone for a category, if it has one
+    pub fn for_category(category: &MicrodoseCategory) -> Option<HrZone> {
+        match category {
+            MicrodoseCategory::Vo2 => Some(Self::VO2),
+            MicrodoseCategory::Zone2 => Some(Self::ZONE2),
+            _ => None,
+        }
+    }
+
+    /// Zone bounds in bpm for a max HR
+    pub fn bpm(&self, max_hr: u32) -> (u32, u32) {
+        let at = |pct: u8| (max_hr * u32::from(pct) + 50) / 100;
+        (at(self.low_pct), at(self.high_pct))
+    }
+
+    /// Whether a session's logged HR meets the zone (`None` without the
+    /// value the zone is judged on)
+    pub fn met_by(&self, max_hr: u32, avg_hr: Option<u8>, peak_hr: Option<u8>) -> Option<bool> {
+        let (low, high) = self.bpm(max_hr);
+        match self.measure {
+            HrMeasure::Peak => peak_hr.map(|hr| u32::from(hr) >= low),
+            HrMeasure::Average => avg_hr.map(|hr| (low..=high).contains(&u32::from(hr))),
+        }
+    }
+}
+
+/// Zone attached to a prescription, with bpm bounds when max HR is known
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HrTarget {
+    pub zone: HrZone,
+    pub max_hr: Option<u32>,
+}
+
+impl HrTarget {
+    /// e.g. "aim for 85–92% max (157–170 bpm)"
+    pub fn label(&self) -> String {
+        let verb = match self.zone.measure {
+            HrMeasure::Peak => "aim for",
+            HrMeasure::Average => "stay at",
+        };
+        let mut label = format!("{} {}–{}% max", verb, self.zone.low_pct, self.zone.high_pct);
+        if let Some(max_hr) = self.max_hr {
+            let (low, high) = self.zone.bpm(max_hr);
+            label.push_str(&format!(" ({}–{} bpm)", low, high));
+        }
+        label
+    }
+}
+
+/// Max HR estimated from age (220 − age)
+pub fn max_hr_from_age(age: u32) -> u32 {
+    220u32.saturating_sub(age)
+}
+
+/// Logged HR for one category compared against its zone
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct HrComparison {
+    pub category: MicrodoseCategory,
+    pub zone: HrZone,
+    /// Sessions with the HR value the zone is judged on
+    pub sessions_with_hr: usize,
+    /// Of those, sessions that met the zone
+    pub met_zone: usize,
+    pub mean_avg_hr: Option<u32>,
+    pub mean_max_hr: Option<u32>,
+}
+
+/// Compare logged HR with each category's zone (categories without
+/// HR-logged sessions are left out)
+pub fn compare_sessions(
+    sessions: &[SessionKind],
+    catalog: &Catalog,
+    max_hr: u32,
+) -> Vec<HrComparison> {
+    MicrodoseCategory::ALL
+        .into_iter()
+        .filter_map(|category| {
+            let zone = HrZone::for_category(&category)?;
+            let logged: Vec<_> = sessions
+                .iter()
+                .filter_map(|s| s.as_real())
+                .filter(|s| {
+                    catalog
+                        .definition(&s.definition_id)
+                        .is_some_and(|d| d.category == category)
+                })
+                .filter(|s| s.avg_hr.is_some() || s.max_hr.is_some())
+                .collect();
+            if logged.is_empty() {
+                return None;
+            }
+
+            let judged: Vec<bool> = logged
+                .iter()
+                .filter_map(|s| zone.met_by(max_hr, s.avg_hr, s.max_hr))
+                .collect();
+            let mean = |values: Vec<u8>| {
+                (!values.is_empty()).then(|| {
+                    let sum: u32 = values.iter().map(|v| u32::from(*v)).sum();
+                    (sum + values.len() as u32 / 2) / values.len() as u32
+                })
+            };
+
+            Some(HrComparison {
+                category,
+                zone,
+                sessions_with_hr: judged.len(),
+                met_zone: judged.iter().filter(|met| **met).count(),
+                mean_avg_hr: mean(logged.iter().filter_map(|s| s.avg_hr).collect()),
+                mean_max_hr: mean(logged.iter().filter_map(|s| s.max_hr).collect()),
+            })
+        })
+        .collect()
+}
+
+/// How long after the session the recovery reading is taken
+pub const RECOVERY_SECONDS: u64 = 60;
+
+/// HR drop from the end of a session to [`RECOVERY_SECONDS`] later
+///
+/// `None` when HR went up (a misread or the user kept moving).
+pub fn recovery_drop(end_hr: u8, after_hr: u8) -> Option<u8> {
+    end_hr.checked_sub(after_hr)
+}
+
+/// HR recovery over a window of sessions
+#[derive(Clone, Debug, Serialize, PartialEq)]
+pub struct RecoveryTrend {
+    pub latest_bpm: u8,
+    pub mean_bpm: f64,
+    /// Latest minus earliest drop in the window (positive = recovering faster)
+    pub change_bpm: i32,
+    /// Drops in chronological order (for sparklines)
+    pub series_bpm: Vec<u8>,
+}
+
+/// Recovery trend from sessions with a logged HR drop (`None` if there are none)
+pub fn recovery_trend(sessions: &[SessionKind]) -> Option<RecoveryTrend> {
+    let mut logged: Vec<_> = sessions
+        .iter()
+        .filter_map(|s| s.as_real())
+        .filter_map(|s| Some((s.performed_at, s.hr_recovery?)))
+        .collect();
+    logged.sort_by_key(|(at, _)| *at);
+
+    let series_bpm: Vec<u8> = logged.into_iter().map(|(_, drop)| drop).collect();
+    let first = *series_bpm.first()?;
+    let latest = *series_bpm.last()?;
+    let sum: u32 = series_bpm.iter().map(|d| u32::from(*d)).sum();
+    Some(RecoveryTrend {
+        latest_bpm: latest,
+        mean_bpm: f64::from(sum) / series_bpm.len() as f64,
+        change_bpm: i32::from(latest) - i32::from(first),
+        series_bpm,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{build_default_catalog, MicrodoseSession};
+    use chrono::Utc;
+
+    fn with_hr(def_id: &str, avg_hr: Option<u8>, max_hr: Option<u8>) -> SessionKind {
+        SessionKind::Real(MicrodoseSession {
+            actual_duration_seconds: Some(300),
+            avg_hr,
+            max_hr,
+            ..MicrodoseSession::new(def_id, Utc::now())
+        })
+    }
+
+    #[test]
+    fn test_zone_bpm_and_label() {
+        assert_eq!(max_hr_from_age(40), 180);
+        assert_eq!(HrZone::VO2.bpm(180), (153, 166));
+
+        let target = HrTarget {
+            zone: HrZone::VO2,
+            max_hr: None,
+        };
+        assert_eq!(target.label(), "aim for 85–92% max");
+
+        let target = HrTarget {
+            zone: HrZone::ZONE2,
+            max_hr: Some(180),
+        };
+        assert_eq!(target.label(), "stay at 60–70% max (108–126 bpm)");
+
+        assert_eq!(HrZone::for_category(&MicrodoseCategory::Gtg), None);
+    }
+
+    #[test]
+    fn test_met_by() {
+        assert_eq!(HrZone::VO2.met_by(180, Some(140), Some(160)), Some(true));
+        assert_eq!(HrZone::VO2.met_by(180, Some(140), Some(150)), Some(false));
+        assert_eq!(HrZone::VO2.met_by(180, Some(140), None), None);
+        assert_eq!(HrZone::ZONE2.met_by(180, Some(130), None), Some(false));
+        assert_eq!(HrZone::ZONE2.met_by(180, Some(115), None), Some(true));
+    }
+
+    #[test]
+    fn test_compare_sessions() {
+        let catalog = build_default_catalog();
+        let sessions = vec![
+            with_hr("emom_burpee_5m", Some(140), Some(160)),
+            with_hr("emom_kb_swing_5m", Some(130), Some(148)),
+            with_hr("emom_burpee_5m", None, None),
+            with_hr("gtg_pullup_band", Some(110), Some(120)),
+        ];
+
+        let comparisons = compare_sessions(&sessions, &catalog, 180);
+        assert_eq!(comparisons.len(), 1);
+        let vo2 = &comparisons[0];
+        assert_eq!(vo2.category, MicrodoseCategory::Vo2);
+        assert_eq!(vo2.sessions_with_hr, 2);
+        assert_eq!(vo2.met_zone, 1);
+        assert_eq!(vo2.mean_avg_hr, Some(135));
+        assert_eq!(vo2.mean_max_hr, Some(154));
+    }
+
+    #[test]
+    fn test_recovery_trend() {
+        assert_eq!(recovery_drop(165, 140), Some(25));
+        assert_eq!(recovery_drop(120, 130), None);
+
+        let mut sessions = Vec::new();
+        for (days_ago, drop) in [(1, Some(30)), (5, Some(22)), (3, None), (2, Some(26))] {
+            let mut session = with_hr("emom_burpee_5m", None, None);
+            if let SessionKind::Real(ref mut s) = session {
+                s.performed_at = Utc::now() - chrono::Duration::days(days_ago);
+                s.hr_recovery = drop;
+            }
+            sessions.push(session);
+        }
+
+        let trend = recovery_trend(&sessions).unwrap();
+        assert_eq!(trend.series_bpm, vec![22, 26, 30]);
+        assert_eq!(trend.latest_bpm, 30);
+        assert_eq!(trend.change_bpm, 8);
+        assert!((trend.mean_bpm - 26.0).abs() < 1e-9);
+
+        assert!(recovery_trend(&sessions[2..3]).is_none());
+    }
+}
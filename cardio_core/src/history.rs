@@ -2,16 +2,30 @@
 //!
 //! This module loads recent session history from both WAL and CSV files
 //! to provide context for the prescription engine.
-
-use crate::{MicrodoseSession, Result, SessionKind};
+//!
+//! File loading requires the `fs` feature (and `csv-archive` for the CSV
+//! half); the history queries used by the engine are always available.
+
+#[cfg(feature = "csv-archive")]
+use crate::MicrodoseSession;
+#[cfg(feature = "fs")]
+use crate::Result;
+use crate::SessionKind;
+#[cfg(feature = "fs")]
 use chrono::{DateTime, Duration, Utc};
+#[cfg(feature = "csv-archive")]
 use csv::ReaderBuilder;
+#[cfg(feature = "csv-archive")]
 use serde::Deserialize;
+#[cfg(feature = "fs")]
 use std::collections::HashSet;
+#[cfg(feature = "fs")]
 use std::path::Path;
+#[cfg(feature = "csv-archive")]
 use uuid::Uuid;
 
 /// CSV row format for reading archived sessions
+#[cfg(feature = "csv-archive")]
 #[derive(Debug, Deserialize)]
 struct CsvRow {
     id: String,
@@ -23,8 +37,24 @@ struct CsvRow {
     perceived_rpe: Option<u8>,
     avg_hr: Option<u8>,
     max_hr: Option<u8>,
+    /// JSON-encoded realized metrics (column absent in older archives)
+    #[serde(default)]
+    metrics_realized: Option<String>,
+    /// `;`-separated tags (column absent in older archives)
+    #[serde(default)]
+    tags: Option<String>,
+    /// 60-second HR drop (column absent in older archives)
+    #[serde(default)]
+    hr_recovery: Option<u8>,
+    /// HR series file (column absent in older archives)
+    #[serde(default)]
+    hr_series_path: Option<String>,
+    /// Seconds paused mid-dose (column absent in older archives)
+    #[serde(default)]
+    paused_seconds: Option<u32>,
 }
 
+#[cfg(feature = "csv-archive")]
 impl TryFrom<CsvRow> for MicrodoseSession {
     type Error = crate::Error;
 
@@ -48,6 +78,18 @@ impl TryFrom<CsvRow> for MicrodoseSession {
             .and_then(|s| DateTime::parse_from_rfc3339(s).ok())
             .map(|dt| dt.with_timezone(&Utc));
 
+        let metrics_realized = match row.metrics_realized.as_deref() {
+            Some(json) if !json.trim().is_empty() => serde_json::from_str(json)
+                .map_err(|e| crate::Error::Other(format!("Invalid metrics_realized: {}", e)))?,
+            _ => vec![],
+        };
+
+        let tags = row
+            .tags
+            .as_deref()
+            .map(|t| crate::normalize_tags([t]))
+            .unwrap_or_default();
+
         Ok(MicrodoseSession {
             id,
             definition_id: row.definition_id,
@@ -55,31 +97,61 @@ impl TryFrom<CsvRow> for MicrodoseSession {
             started_at,
             completed_at,
             actual_duration_seconds: row.duration,
-            metrics_realized: vec![], // Not stored in CSV
+            metrics_realized,
             perceived_rpe: row.perceived_rpe,
             avg_hr: row.avg_hr,
             max_hr: row.max_hr,
+            hr_recovery: row.hr_recovery,
+            hr_series_path: row.hr_series_path.filter(|p| !p.is_empty()),
+            paused_seconds: row.paused_seconds,
+            tags,
         })
     }
 }
 
+/// Parse one archive row, read with the archive's `headers`, into a session
+#[cfg(feature = "csv-archive")]
+pub(crate) fn session_from_record(
+    headers: &csv::StringRecord,
+    record: &csv::StringRecord,
+) -> Result<MicrodoseSession> {
+    record.deserialize::<CsvRow>(Some(headers))?.try_into()
+}
+
 /// Load sessions from the last N days from both WAL and CSV
 ///
 /// Returns sessions sorted by performed_at (newest first).
 /// Automatically deduplicates sessions that appear in both WAL and CSV.
-/// All loaded sessions are wrapped in SessionKind::Real.
+/// All loaded sessions are wrapped in SessionKind::Real. The WAL's lock is
+/// taken within `timeout`.
+#[cfg(feature = "fs")]
 pub fn load_recent_sessions(
     wal_path: &Path,
     csv_path: &Path,
     days: i64,
+    timeout: std::time::Duration,
+) -> Result<Vec<SessionKind>> {
+    load_sessions_before(wal_path, csv_path, days, Utc::now(), timeout)
+}
+
+/// [`load_recent_sessions`] as of `now`: the `days` before it, leaving out
+/// anything performed later
+#[cfg(feature = "fs")]
+fn load_sessions_before(
+    wal_path: &Path,
+    csv_path: &Path,
+    days: i64,
+    now: DateTime<Utc>,
+    timeout: std::time::Duration,
 ) -> Result<Vec<SessionKind>> {
-    let cutoff = Utc::now() - Duration::days(days);
+    let cutoff = now - Duration::days(days);
     let mut sessions = Vec::new();
     let mut seen_ids = HashSet::new();
 
     // Load from WAL first (most recent) - use optimized date filtering
     if wal_path.exists() {
-        let wal_sessions = crate::wal::read_sessions_since(wal_path, cutoff)?;
+        let wal_sessions = crate::wal::read_sessions_since(wal_path, cutoff, timeout)?;
+        warn_anomalies("WAL", &wal_sessions);
         for session in wal_sessions {
             seen_ids.insert(session.id);
             sessions.push(SessionKind::Real(session));
@@ -88,8 +160,10 @@ pub fn load_recent_sessions(
     }
 
     // Load from CSV (archived) - use optimized date filtering
+    #[cfg(feature = "csv-archive")]
     if csv_path.exists() {
         let csv_sessions = load_sessions_from_csv_since(csv_path, cutoff)?;
+        warn_anomalies("CSV", &csv_sessions);
         let mut csv_count = 0;
         for session in csv_sessions {
             if !seen_ids.contains(&session.id) {
@@ -100,6 +174,11 @@ pub fn load_recent_sessions(
         }
         tracing::debug!("Loaded {} sessions from CSV", csv_count);
     }
+    #[cfg(not(feature = "csv-archive"))]
+    let _ = csv_path;
+
+    // Nothing from `now`'s future (a replayed past instant)
+    sessions.retain(|s| s.timestamp() <= now);
 
     // Sort by timestamp, newest first
     sessions.sort_by(|a, b| b.timestamp().cmp(&a.timestamp()));
@@ -113,51 +192,332 @@ pub fn load_recent_sessions(
     Ok(sessions)
 }
 
+/// [`load_recent_sessions`], leaving out sessions with anomalies when
+/// `exclude_anomalies` is set (`[data] exclude_anomalies` in the config)
+#[cfg(feature = "fs")]
+pub fn load_recent_sessions_with(
+    wal_path: &Path,
+    csv_path: &Path,
+    days: i64,
+    exclude_anomalies: bool,
+    timeout: std::time::Duration,
+) -> Result<Vec<SessionKind>> {
+    load_recent_sessions_at(
+        wal_path,
+        csv_path,
+        days,
+        exclude_anomalies,
+        Utc::now(),
+        timeout,
+    )
+}
+
+/// [`load_recent_sessions_with`] as of `now` (e.g. from a
+/// [`Clock`](crate::Clock)): sessions performed after it are left out
+#[cfg(feature = "fs")]
+pub fn load_recent_sessions_at(
+    wal_path: &Path,
+    csv_path: &Path,
+    days: i64,
+    exclude_anomalies: bool,
+    now: DateTime<Utc>,
+    timeout: std::time::Duration,
+) -> Result<Vec<SessionKind>> {
+    let sessions = load_sessions_before(wal_path, csv_path, days, now, timeout)?;
+    if !exclude_anomalies {
+        return Ok(sessions);
+    }
+    let loaded = sessions.len();
+    let kept = crate::anomaly::exclude_anomalies(sessions, now);
+    if kept.len() < loaded {
+        tracing::info!("Excluded {} anomalous session(s)", loaded - kept.len());
+    }
+    Ok(kept)
+}
+
+/// Log anomalies found in one source's sessions
+#[cfg(feature = "fs")]
+fn warn_anomalies(source: &str, sessions: &[crate::MicrodoseSession]) {
+    for anomaly in crate::anomaly::check_sessions(sessions, Utc::now()) {
+        tracing::warn!("{} session anomaly: {}", source, anomaly);
+    }
+}
+
+/// Check every session in the WAL and CSV archive for anomalies
+///
+/// Each source is checked on its own (a session present in both after an
+/// interrupted rollup is not a duplicate). Returns `(source, anomaly)` pairs.
+/// The WAL's lock is taken within `timeout`.
+#[cfg(feature = "fs")]
+pub fn check_data(
+    wal_path: &Path,
+    csv_path: &Path,
+    timeout: std::time::Duration,
+) -> Result<Vec<(&'static str, crate::anomaly::Anomaly)>> {
+    let now = Utc::now();
+    #[cfg_attr(not(feature = "csv-archive"), allow(unused_mut))]
+    let mut found: Vec<_> =
+        crate::anomaly::check_sessions(&crate::wal::read_sessions(wal_path, timeout)?, now)
+            .into_iter()
+            .map(|a| ("WAL", a))
+            .collect();
+
+    #[cfg(feature = "csv-archive")]
+    if csv_path.exists() {
+        let archived = load_sessions_from_csv_internal(csv_path, None)?;
+        found.extend(
+            crate::anomaly::check_sessions(&archived, now)
+                .into_iter()
+                .map(|a| ("CSV", a)),
+        );
+    }
+    #[cfg(not(feature = "csv-archive"))]
+    let _ = csv_path;
+
+    Ok(found)
+}
+
 /// Load sessions from CSV since a specific cutoff date
 ///
-/// This is more memory-efficient for large CSV files as it skips parsing
-/// and allocating sessions older than the cutoff.
+/// With a current index (see [`crate::csv_index`]) only the chunks that can
+/// hold sessions after the cutoff are read; otherwise the whole file is
+/// scanned, skipping allocation for older sessions.
+#[cfg(feature = "csv-archive")]
 fn load_sessions_from_csv_since(
     path: &Path,
     cutoff: DateTime<Utc>,
 ) -> Result<Vec<MicrodoseSession>> {
-    load_sessions_from_csv_internal(path, Some(cutoff))
+    Ok(CsvSessions::open(path, Some(cutoff), None)?.collect())
 }
 
 /// Internal helper to load CSV sessions with optional date filtering
-fn load_sessions_from_csv_internal(
+#[cfg(feature = "csv-archive")]
+pub(crate) fn load_sessions_from_csv_internal(
     path: &Path,
     cutoff: Option<DateTime<Utc>>,
 ) -> Result<Vec<MicrodoseSession>> {
-    let mut reader = ReaderBuilder::new().has_headers(true).from_path(path)?;
+    Ok(CsvSessions::open(path, cutoff, None)?.collect())
+}
 
-    let mut sessions = Vec::new();
-    for result in reader.deserialize::<CsvRow>() {
-        match result {
-            Ok(row) => match MicrodoseSession::try_from(row) {
-                Ok(session) => {
-                    // Filter by cutoff date if provided
-                    if let Some(cutoff_date) = cutoff {
-                        if session.performed_at >= cutoff_date {
-                            sessions.push(session);
-                        }
-                        // Skip old sessions without allocating
-                    } else {
-                        sessions.push(session);
+/// Streaming reader over archived sessions in `[since, until)`
+///
+/// Sessions are parsed one row at a time, so memory stays flat however large
+/// the archive is. With a current index only the chunks overlapping the
+/// window are read and the stream ends after the last of them; without one
+/// the whole file is scanned. Rows that fail to parse are logged and skipped.
+#[cfg(feature = "csv-archive")]
+pub struct CsvSessions {
+    rows: csv::DeserializeRecordsIntoIter<Box<dyn std::io::Read>, CsvRow>,
+    since: Option<DateTime<Utc>>,
+    until: Option<DateTime<Utc>>,
+}
+
+#[cfg(feature = "csv-archive")]
+impl CsvSessions {
+    /// Open `path` for sessions performed at or after `since` and before
+    /// `until` (either bound may be open)
+    pub fn open(
+        path: &Path,
+        since: Option<DateTime<Utc>>,
+        until: Option<DateTime<Utc>>,
+    ) -> Result<Self> {
+        use std::io::Read;
+
+        let file = std::fs::File::open(path)?;
+        let input: Box<dyn Read> = match crate::csv_index::ranges(path, since, until) {
+            Some(ranges) => {
+                tracing::debug!("Reading {} indexed range(s) of {:?}", ranges.len(), path);
+                // Rollup keeps the archive on the current columns, so the
+                // header can be supplied instead of read from the file
+                let header = format!("{}\n", crate::csv_rollup::CSV_HEADERS.join(","));
+                Box::new(std::io::Cursor::new(header.into_bytes()).chain(ByteRanges {
+                    file,
+                    ranges: ranges.into(),
+                    remaining: 0,
+                }))
+            }
+            None => Box::new(file),
+        };
+
+        Ok(Self {
+            rows: ReaderBuilder::new()
+                .has_headers(true)
+                .from_reader(input)
+                .into_deserialize(),
+            since,
+            until,
+        })
+    }
+}
+
+#[cfg(feature = "csv-archive")]
+impl Iterator for CsvSessions {
+    type Item = MicrodoseSession;
+
+    fn next(&mut self) -> Option<MicrodoseSession> {
+        for result in self.rows.by_ref() {
+            let session = match result {
+                Ok(row) => match MicrodoseSession::try_from(row) {
+                    Ok(session) => session,
+                    Err(e) => {
+                        tracing::warn!("Failed to parse CSV row: {}", e);
+                        continue;
                     }
-                }
+                },
                 Err(e) => {
-                    tracing::warn!("Failed to parse CSV row: {}", e);
-                    // Continue processing other rows
+                    tracing::warn!("Failed to deserialize CSV row: {}", e);
+                    continue;
                 }
-            },
-            Err(e) => {
-                tracing::warn!("Failed to deserialize CSV row: {}", e);
+            };
+            // Skip sessions outside the window without keeping them
+            if self.since.is_some_and(|since| session.performed_at < since)
+                || self
+                    .until
+                    .is_some_and(|until| session.performed_at >= until)
+            {
+                continue;
             }
+            return Some(session);
         }
+        None
     }
+}
 
-    Ok(sessions)
+/// Reads the given `[start, end)` byte ranges of a file back to back
+#[cfg(feature = "csv-archive")]
+struct ByteRanges {
+    file: std::fs::File,
+    ranges: std::collections::VecDeque<(u64, u64)>,
+    /// Bytes left in the current range
+    remaining: u64,
+}
+
+#[cfg(feature = "csv-archive")]
+impl std::io::Read for ByteRanges {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        use std::io::Seek;
+
+        while self.remaining == 0 {
+            let Some((start, end)) = self.ranges.pop_front() else {
+                return Ok(0);
+            };
+            self.file.seek(std::io::SeekFrom::Start(start))?;
+            self.remaining = end.saturating_sub(start);
+        }
+        let len = self.remaining.min(buf.len() as u64) as usize;
+        let read = self.file.read(&mut buf[..len])?;
+        if read == 0 {
+            // Truncated since the index was checked
+            self.ranges.clear();
+        }
+        self.remaining = self.remaining.saturating_sub(read as u64);
+        Ok(read)
+    }
+}
+
+/// Which sessions to read: a time window plus optional filters
+#[derive(Clone, Debug, Default)]
+pub struct HistoryQuery {
+    /// Performed at or after this instant
+    pub since: Option<chrono::DateTime<chrono::Utc>>,
+    /// Performed before this instant
+    pub until: Option<chrono::DateTime<chrono::Utc>>,
+    /// Keep sessions carrying at least one of these (normalized) tags; empty
+    /// keeps all
+    pub tags: Vec<String>,
+    /// Keep only sessions of this definition
+    pub definition_id: Option<String>,
+}
+
+impl HistoryQuery {
+    /// Whether a session falls in the window and passes the filters
+    pub fn matches(&self, session: &crate::MicrodoseSession) -> bool {
+        self.since.is_none_or(|since| session.performed_at >= since)
+            && self.until.is_none_or(|until| session.performed_at < until)
+            && session.has_any_tag(&self.tags)
+            && self
+                .definition_id
+                .as_ref()
+                .is_none_or(|id| &session.definition_id == id)
+    }
+}
+
+/// Keep only real sessions carrying at least one of `tags`
+///
+/// An empty tag list keeps every real session. Skipped entries are dropped
+/// since they never carry tags.
+pub fn filter_by_tags<'a>(sessions: &'a [SessionKind], tags: &[String]) -> Vec<&'a SessionKind> {
+    sessions
+        .iter()
+        .filter(|s| s.as_real().is_some_and(|real| real.has_any_tag(tags)))
+        .collect()
+}
+
+/// Count real sessions per local calendar day
+pub fn sessions_per_local_day(
+    sessions: &[SessionKind],
+    tz: &crate::LocalTz,
+) -> std::collections::BTreeMap<chrono::NaiveDate, usize> {
+    let mut days = std::collections::BTreeMap::new();
+    for session in sessions.iter().filter_map(|s| s.as_real()) {
+        *days.entry(tz.date_of(session.performed_at)).or_insert(0) += 1;
+    }
+    days
+}
+
+/// Number of real sessions performed on the local "today"
+pub fn sessions_today(
+    sessions: &[SessionKind],
+    tz: &crate::LocalTz,
+    now: chrono::DateTime<chrono::Utc>,
+) -> usize {
+    let today = tz.date_of(now);
+    sessions
+        .iter()
+        .filter_map(|s| s.as_real())
+        .filter(|s| tz.date_of(s.performed_at) == today)
+        .count()
+}
+
+/// Consecutive local days (ending today, or yesterday if nothing yet today)
+/// with at least one real session; off days without sessions are passed over
+pub fn current_streak(
+    sessions: &[SessionKind],
+    off_days: &[crate::off_days::OffRange],
+    tz: &crate::LocalTz,
+    now: chrono::DateTime<chrono::Utc>,
+) -> u32 {
+    let days = sessions_per_local_day(sessions, tz);
+    streak_ending(|day| days.contains_key(&day), off_days, tz.date_of(now))
+}
+
+/// [`current_streak`] over any record of which local days had sessions
+pub fn streak_ending(
+    has_sessions: impl Fn(chrono::NaiveDate) -> bool,
+    off_days: &[crate::off_days::OffRange],
+    today: chrono::NaiveDate,
+) -> u32 {
+    let mut day = today;
+    if !has_sessions(day) {
+        day = match day.pred_opt() {
+            Some(yesterday) => yesterday,
+            None => return 0,
+        };
+    }
+
+    let mut streak = 0;
+    loop {
+        if has_sessions(day) {
+            streak += 1;
+        } else if !crate::off_days::is_off(off_days, day) {
+            break;
+        }
+        day = match day.pred_opt() {
+            Some(prev) => prev,
+            None => break,
+        };
+    }
+    streak
 }
 
 /// Find the most recent session for a given category
@@ -171,23 +531,20 @@ pub fn find_last_session_by_category<'a>(
         .find(|s| s.definition_id().contains(category))
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "csv-archive"))]
 mod tests {
     use super::*;
     use crate::wal::SessionSink;
 
     fn create_test_session(def_id: &str, days_ago: i64) -> MicrodoseSession {
         MicrodoseSession {
-            id: Uuid::new_v4(),
-            definition_id: def_id.into(),
-            performed_at: Utc::now() - Duration::days(days_ago),
             started_at: Some(Utc::now() - Duration::days(days_ago)),
             completed_at: Some(Utc::now() - Duration::days(days_ago)),
             actual_duration_seconds: Some(300),
-            metrics_realized: vec![],
             perceived_rpe: Some(7),
             avg_hr: Some(145),
             max_hr: Some(165),
+            ..MicrodoseSession::new(def_id, Utc::now() - Duration::days(days_ago))
         }
     }
 
@@ -203,7 +560,8 @@ mod tests {
         sink.append(&create_test_session("vo2_2", 3)).unwrap();
         sink.append(&create_test_session("vo2_3", 10)).unwrap(); // Too old
 
-        let sessions = load_recent_sessions(&wal_path, &csv_path, 7).unwrap();
+        let sessions =
+            load_recent_sessions(&wal_path, &csv_path, 7, crate::lock::DEFAULT_TIMEOUT).unwrap();
         assert_eq!(sessions.len(), 2);
         // Verify they're all Real sessions
         assert!(sessions.iter().all(|s| matches!(s, SessionKind::Real(_))));
@@ -222,11 +580,21 @@ mod tests {
         sink.append(&session).unwrap();
 
         // Roll up to CSV (which includes the same session)
-        crate::csv_rollup::wal_to_csv_and_archive(&wal_path, &csv_path).unwrap();
+        crate::csv_rollup::wal_to_csv_and_archive(
+            &wal_path,
+            &csv_path,
+            crate::lock::DEFAULT_TIMEOUT,
+        )
+        .unwrap();
 
         // Load - should get only 1 session despite it being in CSV
-        let sessions =
-            load_recent_sessions(&temp_dir.path().join("nonexistent.wal"), &csv_path, 7).unwrap();
+        let sessions = load_recent_sessions(
+            &temp_dir.path().join("nonexistent.wal"),
+            &csv_path,
+            7,
+            crate::lock::DEFAULT_TIMEOUT,
+        )
+        .unwrap();
 
         // Find the session
         let found = sessions.iter().find(|s| {
@@ -266,13 +634,143 @@ mod tests {
         sink.append(&old).unwrap();
         sink.append(&new).unwrap();
 
-        let sessions = load_recent_sessions(&wal_path, &csv_path, 7).unwrap();
+        let sessions =
+            load_recent_sessions(&wal_path, &csv_path, 7, crate::lock::DEFAULT_TIMEOUT).unwrap();
 
         // Should be sorted newest first
         assert_eq!(sessions[0].definition_id(), "new");
         assert_eq!(sessions[1].definition_id(), "old");
     }
 
+    #[test]
+    fn test_realized_metrics_survive_csv_rollup() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let wal_path = temp_dir.path().join("sessions.wal");
+        let csv_path = temp_dir.path().join("sessions.csv");
+
+        let mut session = create_test_session("emom_burpee_5m", 1);
+        session.metrics_realized = vec![crate::RealizedMetric::Reps {
+            key: "reps".into(),
+            per_set: vec![5, 5, 5, 4, 3],
+        }];
+        let mut sink = crate::wal::JsonlSink::new(&wal_path);
+        sink.append(&session).unwrap();
+        crate::csv_rollup::wal_to_csv_and_archive(
+            &wal_path,
+            &csv_path,
+            crate::lock::DEFAULT_TIMEOUT,
+        )
+        .unwrap();
+
+        let sessions =
+            load_recent_sessions(&wal_path, &csv_path, 7, crate::lock::DEFAULT_TIMEOUT).unwrap();
+        match &sessions[0] {
+            SessionKind::Real(loaded) => {
+                assert_eq!(loaded.reps_per_set(), Some(&[5, 5, 5, 4, 3][..]))
+            }
+            _ => panic!("Expected Real session"),
+        }
+    }
+
+    #[test]
+    fn test_tags_survive_csv_rollup_and_filter() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let wal_path = temp_dir.path().join("sessions.wal");
+        let csv_path = temp_dir.path().join("sessions.csv");
+
+        let mut travel = create_test_session("emom_burpee_5m", 1);
+        travel.tags = crate::normalize_tags(["Travel, hotel-gym", "travel"]);
+        assert_eq!(travel.tags, vec!["travel", "hotel-gym"]);
+        let home = create_test_session("gtg_pullup_band", 2);
+
+        let mut sink = crate::wal::JsonlSink::new(&wal_path);
+        sink.append(&travel).unwrap();
+        sink.append(&home).unwrap();
+        crate::csv_rollup::wal_to_csv_and_archive(
+            &wal_path,
+            &csv_path,
+            crate::lock::DEFAULT_TIMEOUT,
+        )
+        .unwrap();
+
+        let sessions =
+            load_recent_sessions(&wal_path, &csv_path, 7, crate::lock::DEFAULT_TIMEOUT).unwrap();
+        assert_eq!(filter_by_tags(&sessions, &[]).len(), 2);
+
+        let filtered = filter_by_tags(&sessions, &["hotel-gym".to_string()]);
+        assert_eq!(filtered.len(), 1);
+        assert_eq!(filtered[0].as_real().unwrap().tags, travel.tags);
+    }
+
+    #[test]
+    fn test_csv_sessions_stream_window() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let wal_path = temp_dir.path().join("sessions.wal");
+        let csv_path = temp_dir.path().join("sessions.csv");
+
+        // One rollup per session, so each lands in its own index chunk
+        for (def_id, days_ago) in [("old", 30), ("middle", 10), ("new", 1)] {
+            let mut sink = crate::wal::JsonlSink::new(&wal_path);
+            sink.append(&create_test_session(def_id, days_ago)).unwrap();
+            crate::csv_rollup::wal_to_csv_and_archive(
+                &wal_path,
+                &csv_path,
+                crate::lock::DEFAULT_TIMEOUT,
+            )
+            .unwrap();
+        }
+
+        let since = Some(Utc::now() - Duration::days(20));
+        let until = Some(Utc::now() - Duration::days(5));
+        assert_eq!(
+            crate::csv_index::ranges(&csv_path, since, until)
+                .unwrap()
+                .len(),
+            1
+        );
+        let ids = |sessions: CsvSessions| sessions.map(|s| s.definition_id).collect::<Vec<_>>();
+        assert_eq!(
+            ids(CsvSessions::open(&csv_path, since, until).unwrap()),
+            ["middle"]
+        );
+        assert_eq!(
+            ids(CsvSessions::open(&csv_path, since, None).unwrap()),
+            ["middle", "new"]
+        );
+
+        // Without the index the same window comes from a full scan
+        std::fs::remove_file(crate::csv_index::index_path(&csv_path)).unwrap();
+        assert_eq!(
+            ids(CsvSessions::open(&csv_path, since, until).unwrap()),
+            ["middle"]
+        );
+    }
+
+    #[test]
+    fn test_streak_and_today_use_local_days() {
+        use chrono::TimeZone;
+
+        let tokyo = crate::LocalTz::from_name(Some("Asia/Tokyo")).unwrap();
+        let at = |d: u32, h: u32| {
+            let mut session = create_test_session("emom_burpee_5m", 0);
+            session.performed_at = Utc.with_ymd_and_hms(2025, 3, d, h, 0, 0).unwrap();
+            SessionKind::Real(session)
+        };
+
+        // Tokyo dates: Mar 3 (01:00 UTC), Mar 2 (16:00 UTC on Mar 1), Mar 1 (10:00 UTC)
+        let sessions = vec![at(3, 1), at(1, 16), at(1, 10)];
+        let now = Utc.with_ymd_and_hms(2025, 3, 3, 2, 0, 0).unwrap();
+
+        assert_eq!(current_streak(&sessions, &[], &tokyo, now), 3);
+        assert_eq!(sessions_today(&sessions, &tokyo, now), 1);
+        assert_eq!(sessions_per_local_day(&sessions, &tokyo).len(), 3);
+
+        // The same sessions span only two UTC days
+        let utc = crate::LocalTz::from_name(Some("UTC")).unwrap();
+        assert_eq!(sessions_per_local_day(&sessions, &utc).len(), 2);
+        assert_eq!(current_streak(&sessions, &[], &utc, now), 1);
+    }
+
     #[test]
     fn test_find_last_session_by_category() {
         let s1 = create_test_session("emom_vo2", 3);
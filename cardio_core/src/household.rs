@@ -0,0 +1,200 @@
+//! Household view: linked profiles' doses side by side.
+//!
+//! `[household] members` names profiles that point at another person's data
+//! directory (`[profile.anna] data_dir = ...`). `krep stats` shows everyone's
+//! sessions next to yours with a combined total, and the tray tooltip reads
+//! e.g. "Anna: 4 doses today, you: 1". Everything stays on this machine: the
+//! other data directories are only read.
+
+use crate::{Catalog, LocalTz, SessionKind};
+use chrono::NaiveDate;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// Label for the person running krep
+pub const YOU: &str = "you";
+
+/// One person's sessions in the window
+#[derive(Clone, Debug, Serialize, PartialEq)]
+pub struct MemberSummary {
+    /// Profile name (or [`YOU`])
+    pub name: String,
+    /// Sessions on today's local date
+    pub today: usize,
+    pub sessions: usize,
+    pub total_seconds: u64,
+    /// Session count keyed by category name (`vo2`, …, or `unknown`)
+    pub by_category: BTreeMap<String, usize>,
+}
+
+/// Summarize one member's real sessions (skipped entries are ignored)
+pub fn summarize_member(
+    name: &str,
+    sessions: &[SessionKind],
+    catalog: &Catalog,
+    tz: &LocalTz,
+    today: NaiveDate,
+) -> MemberSummary {
+    let mut summary = MemberSummary {
+        name: name.to_string(),
+        today: 0,
+        sessions: 0,
+        total_seconds: 0,
+        by_category: BTreeMap::new(),
+    };
+    for session in sessions.iter().filter_map(|s| s.as_real()) {
+        summary.sessions += 1;
+        if tz.date_of(session.performed_at) == today {
+            summary.today += 1;
+        }
+        summary.total_seconds += u64::from(session.actual_duration_seconds.unwrap_or(0));
+        let category = catalog
+            .definition(&session.definition_id)
+            .map(|d| d.category.as_str())
+            .unwrap_or("unknown");
+        *summary.by_category.entry(category.to_string()).or_insert(0) += 1;
+    }
+    summary
+}
+
+/// Everyone's sessions added up (named "household")
+pub fn combined(members: &[MemberSummary]) -> MemberSummary {
+    let mut total = MemberSummary {
+        name: "household".to_string(),
+        today: 0,
+        sessions: 0,
+        total_seconds: 0,
+        by_category: BTreeMap::new(),
+    };
+    for member in members {
+        total.today += member.today;
+        total.sessions += member.sessions;
+        total.total_seconds += member.total_seconds;
+        for (category, count) in &member.by_category {
+            *total.by_category.entry(category.clone()).or_insert(0) += count;
+        }
+    }
+    total
+}
+
+/// Today's doses per member, e.g. "Anna: 4 doses today, you: 1"
+/// (`None` without members)
+pub fn today_line(members: &[MemberSummary]) -> Option<String> {
+    let (first, rest) = members.split_first()?;
+    let mut line = format!(
+        "{}: {} dose{} today",
+        first.name,
+        first.today,
+        if first.today == 1 { "" } else { "s" }
+    );
+    for member in rest {
+        line.push_str(&format!(", {}: {}", member.name, member.today));
+    }
+    Some(line)
+}
+
+/// Summaries for the configured household members followed by
+/// [`YOU`] (reading `data_dir` with `config`), over the last `days` days
+///
+/// Members are listed with their profile name as written, except the
+/// profile in use, which is you. Nothing is returned without members.
+#[cfg(feature = "fs")]
+pub fn load(
+    config: &crate::Config,
+    data_dir: &std::path::Path,
+    catalog: &Catalog,
+    days: i64,
+    now: chrono::DateTime<chrono::Utc>,
+) -> crate::Result<Vec<MemberSummary>> {
+    use crate::{load_recent_sessions_with, FileStorage};
+
+    let tz = config.timezone();
+    let today = tz.date_of(now);
+    let load_sessions = |member: &crate::Config, data_dir: &std::path::Path| {
+        let storage = FileStorage::with_config(data_dir, &member.data);
+        load_recent_sessions_with(
+            &storage.wal_path,
+            &storage.csv_path,
+            days,
+            member.data.exclude_anomalies,
+            storage.lock_timeout,
+        )
+    };
+
+    let mut members = Vec::new();
+    for name in &config.household.members {
+        if config.active_profile.as_deref() == Some(name.as_str()) {
+            continue;
+        }
+        let member = config.for_member(name)?;
+        let sessions = load_sessions(&member, &member.data.data_dir)?;
+        members.push(summarize_member(name, &sessions, catalog, &tz, today));
+    }
+    if members.is_empty() {
+        return Ok(members);
+    }
+    let sessions = load_sessions(config, data_dir)?;
+    members.push(summarize_member(YOU, &sessions, catalog, &tz, today));
+    Ok(members)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::build_default_catalog;
+    use chrono::{Duration, Utc};
+
+    fn session(def_id: &str, days_ago: i64) -> SessionKind {
+        SessionKind::Real(crate::MicrodoseSession {
+            actual_duration_seconds: Some(300),
+            ..crate::MicrodoseSession::new(def_id, Utc::now() - Duration::days(days_ago))
+        })
+    }
+
+    #[test]
+    fn test_household_summaries() {
+        let catalog = build_default_catalog();
+        let tz = LocalTz::from_name(Some("UTC")).unwrap();
+        let today = Utc::now().date_naive();
+
+        let anna = summarize_member(
+            "Anna",
+            &[
+                session("emom_burpee_5m", 0),
+                session("gtg_pullup_band", 0),
+                session("gtg_pullup_band", 2),
+            ],
+            &catalog,
+            &tz,
+            today,
+        );
+        assert_eq!(anna.today, 2);
+        assert_eq!(anna.sessions, 3);
+        assert_eq!(anna.by_category["gtg"], 2);
+
+        let you = summarize_member(
+            YOU,
+            &[
+                session("gtg_pullup_band", 0),
+                SessionKind::ShownButSkipped {
+                    definition_id: "emom_burpee_5m".into(),
+                    shown_at: Utc::now(),
+                },
+            ],
+            &catalog,
+            &tz,
+            today,
+        );
+        assert_eq!(you.sessions, 1);
+
+        let members = vec![anna, you];
+        assert_eq!(today_line(&members).unwrap(), "Anna: 2 doses today, you: 1");
+        assert_eq!(today_line(&[]), None);
+
+        let total = combined(&members);
+        assert_eq!(total.sessions, 4);
+        assert_eq!(total.today, 3);
+        assert_eq!(total.total_seconds, 1200);
+        assert_eq!(total.by_category["gtg"], 3);
+    }
+}
@@ -0,0 +1,401 @@
+//! Heart-rate recordings from watch and chest-strap exports.
+//!
+//! A recording is a CSV export of the HR series a device captured during a
+//! session. Two layouts are understood:
+//!
+//! - Polar Flow: a summary header (`Date`, `Start time`, ...) followed by a
+//!   sample table whose `Time` column is the elapsed time since the start
+//! - Suunto and most other tools: one row per sample with a timestamp column
+//!   (`timestamp`, `time`, ...) and a heart-rate column (`HR (bpm)`,
+//!   `heart_rate`, ...)
+//!
+//! Timestamps without an offset are local time. Attaching a recording fills
+//! the session's `avg_hr`/`max_hr` from the samples inside its window and
+//! keeps a copy of the file at [`series_path`], so the full series stays
+//! next to the session it belongs to.
+
+use crate::{Error, LocalTz, MicrodoseSession, Result};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+/// Directory under the data directory holding attached recordings
+pub const HR_SERIES_DIR: &str = "hr";
+
+/// One heart-rate sample
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HrSample {
+    pub at: DateTime<Utc>,
+    pub bpm: u8,
+}
+
+/// Parse a Polar or Suunto (or similar) CSV export into samples
+///
+/// Samples without a heart rate (sensor dropouts) are skipped.
+pub fn parse_csv(text: &str, tz: &LocalTz) -> Result<Vec<HrSample>> {
+    let rows: Vec<Vec<&str>> = text
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(split_row)
+        .collect();
+    let Some(header) = rows.first() else {
+        return Err(Error::Other("HR recording is empty".into()));
+    };
+
+    let samples = if column(header, &["starttime"]).is_some() {
+        parse_polar(&rows, tz)?
+    } else {
+        parse_timestamped(&rows, tz)?
+    };
+    if samples.is_empty() {
+        return Err(Error::Other(
+            "HR recording has no heart-rate samples".into(),
+        ));
+    }
+    Ok(samples)
+}
+
+/// Start and end of a session: its start (or performed_at) to its end, taking
+/// whichever of `completed_at` and start + duration is later
+pub fn session_window(session: &MicrodoseSession) -> (DateTime<Utc>, DateTime<Utc>) {
+    let start = session.started_at.unwrap_or(session.performed_at);
+    let by_duration =
+        start + chrono::Duration::seconds(session.actual_duration_seconds.unwrap_or(0).into());
+    let end = session
+        .completed_at
+        .map_or(by_duration, |completed| completed.max(by_duration));
+    (start, end)
+}
+
+/// Average and maximum bpm of the samples in `[start, end]`, if any
+pub fn summarize(
+    samples: &[HrSample],
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Option<(u8, u8)> {
+    let window: Vec<u32> = samples
+        .iter()
+        .filter(|s| s.at >= start && s.at <= end)
+        .map(|s| u32::from(s.bpm))
+        .collect();
+    let max = *window.iter().max()?;
+    let avg = (window.iter().sum::<u32>() as f64 / window.len() as f64).round();
+    Some((avg as u8, max as u8))
+}
+
+/// A session's recording relative to the data directory, as stored in
+/// [`MicrodoseSession::hr_series_path`]
+pub fn series_file(session_id: Uuid) -> String {
+    format!("{}/{}.csv", HR_SERIES_DIR, session_id)
+}
+
+/// Where the recording attached to a session is kept
+pub fn series_path(data_dir: &Path, session_id: Uuid) -> PathBuf {
+    data_dir.join(series_file(session_id))
+}
+
+/// Samples of the recording attached to `session`, if it has one
+#[cfg(feature = "fs")]
+pub fn load_series(
+    data_dir: &Path,
+    session: &MicrodoseSession,
+    tz: &LocalTz,
+) -> Result<Option<Vec<HrSample>>> {
+    let Some(file) = &session.hr_series_path else {
+        return Ok(None);
+    };
+    let text = std::fs::read_to_string(data_dir.join(file))?;
+    parse_csv(&text, tz).map(Some)
+}
+
+/// Fill a session's HR fields from `samples`
+///
+/// Fails, leaving the session alone, when no sample falls in its window.
+pub fn apply(session: &mut MicrodoseSession, samples: &[HrSample]) -> Result<()> {
+    let (start, end) = session_window(session);
+    let (avg, max) = summarize(samples, start, end).ok_or_else(|| {
+        Error::Other(format!(
+            "HR recording has no samples between {} and {}",
+            start.to_rfc3339(),
+            end.to_rfc3339()
+        ))
+    })?;
+    session.avg_hr = Some(avg);
+    session.max_hr = Some(max);
+    Ok(())
+}
+
+/// Attach the recording at `recording` to the session with `id`
+///
+/// The session is updated where it lives (the WAL, else the CSV archive),
+/// pointing `hr_series_path` at the copy of the file made at
+/// [`series_path`]. Returns the updated session, or `None` if no session has
+/// that ID.
+#[cfg(feature = "fs")]
+pub fn attach(
+    storage: &crate::FileStorage,
+    data_dir: &Path,
+    id: Uuid,
+    recording: &Path,
+    tz: &LocalTz,
+) -> Result<Option<MicrodoseSession>> {
+    let text = std::fs::read_to_string(recording)?;
+    let samples = parse_csv(&text, tz)?;
+
+    let record = |session: &mut MicrodoseSession| {
+        apply(session, &samples)?;
+        session.hr_series_path = Some(series_file(id));
+        Ok(())
+    };
+
+    let updated = crate::wal::update_session(&storage.wal_path, id, storage.lock_timeout, record)?;
+    #[cfg(feature = "csv-archive")]
+    let updated = match updated {
+        Some(session) => Some(session),
+        None => crate::csv_rollup::update_session(
+            &storage.wal_path,
+            &storage.csv_path,
+            id,
+            storage.lock_timeout,
+            record,
+        )?,
+    };
+    let Some(session) = updated else {
+        return Ok(None);
+    };
+
+    let path = series_path(data_dir, id);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::copy(recording, &path)?;
+    Ok(Some(session))
+}
+
+/// Split a CSV line on commas, trimming whitespace and surrounding quotes
+fn split_row(line: &str) -> Vec<&str> {
+    line.split(',')
+        .map(|field| field.trim().trim_matches('"').trim())
+        .collect()
+}
+
+/// Position of the first column whose normalized name starts with one of
+/// `names` (lowercase, letters and digits only: `HR (bpm)` is `hrbpm`)
+fn column(header: &[&str], names: &[&str]) -> Option<usize> {
+    header.iter().position(|h| {
+        let normalized: String = h
+            .chars()
+            .filter(char::is_ascii_alphanumeric)
+            .map(|c| c.to_ascii_lowercase())
+            .collect();
+        names.iter().any(|name| normalized.starts_with(name))
+    })
+}
+
+const HR_COLUMNS: [&str; 3] = ["hr", "heartrate", "pulse"];
+
+fn parse_bpm(field: Option<&&str>) -> Option<u8> {
+    let bpm = field?.parse::<f64>().ok()?.round();
+    (1.0..=255.0).contains(&bpm).then_some(bpm as u8)
+}
+
+/// Polar Flow: summary header and values, then the sample table
+fn parse_polar(rows: &[Vec<&str>], tz: &LocalTz) -> Result<Vec<HrSample>> {
+    let invalid = |what: &str| Error::Other(format!("Invalid Polar export: {}", what));
+    let (header, summary) = (&rows[0], rows.get(1).ok_or_else(|| invalid("no summary"))?);
+    let field = |names: &[&str]| column(header, names).and_then(|i| summary.get(i).copied());
+    let date = field(&["date"])
+        .and_then(|d| NaiveDate::parse_from_str(d, "%d-%m-%Y").ok())
+        .ok_or_else(|| invalid("missing or bad Date"))?;
+    let time = field(&["starttime"])
+        .and_then(|t| NaiveTime::parse_from_str(t, "%H:%M:%S").ok())
+        .ok_or_else(|| invalid("missing or bad Start time"))?;
+    let start = tz.to_utc(date.and_time(time));
+
+    let samples_header = rows.get(2).ok_or_else(|| invalid("no sample table"))?;
+    let time_col = column(samples_header, &["time"]).ok_or_else(|| invalid("no Time column"))?;
+    let hr_col = column(samples_header, &HR_COLUMNS).ok_or_else(|| invalid("no HR column"))?;
+    Ok(rows[3..]
+        .iter()
+        .filter_map(|row| {
+            let elapsed = NaiveTime::parse_from_str(row.get(time_col)?, "%H:%M:%S").ok()?;
+            let bpm = parse_bpm(row.get(hr_col))?;
+            Some(HrSample {
+                at: start + (elapsed - NaiveTime::MIN),
+                bpm,
+            })
+        })
+        .collect())
+}
+
+/// One sample per row with a timestamp column
+fn parse_timestamped(rows: &[Vec<&str>], tz: &LocalTz) -> Result<Vec<HrSample>> {
+    let header = &rows[0];
+    let time_col = column(header, &["timestamp", "time", "datetime", "date"]).ok_or_else(|| {
+        Error::Other("HR recording has no timestamp column (timestamp, time)".into())
+    })?;
+    let hr_col = column(header, &HR_COLUMNS).ok_or_else(|| {
+        Error::Other("HR recording has no heart-rate column (hr, heart_rate)".into())
+    })?;
+    Ok(rows[1..]
+        .iter()
+        .filter_map(|row| {
+            Some(HrSample {
+                at: parse_timestamp(row.get(time_col)?, tz)?,
+                bpm: parse_bpm(row.get(hr_col))?,
+            })
+        })
+        .collect())
+}
+
+/// RFC 3339, local `YYYY-MM-DD HH:MM:SS` (or with a `T`), or Unix seconds
+fn parse_timestamp(value: &str, tz: &LocalTz) -> Option<DateTime<Utc>> {
+    if let Ok(t) = DateTime::parse_from_rfc3339(value) {
+        return Some(t.with_timezone(&Utc));
+    }
+    for format in ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M:%S%.f"] {
+        if let Ok(local) = NaiveDateTime::parse_from_str(value, format) {
+            return Some(tz.to_utc(local));
+        }
+    }
+    DateTime::from_timestamp(value.parse().ok()?, 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn utc() -> LocalTz {
+        LocalTz::from_name(Some("UTC")).unwrap()
+    }
+
+    fn session(start: DateTime<Utc>, seconds: u32) -> MicrodoseSession {
+        MicrodoseSession {
+            started_at: Some(start),
+            completed_at: Some(start),
+            actual_duration_seconds: Some(seconds),
+            ..MicrodoseSession::new("emom_burpees", start)
+        }
+    }
+
+    #[test]
+    fn test_parse_polar_export() {
+        let text = "Name,Sport,Date,Start time,Duration,Average heart rate (bpm)\n\
+                    Me,OTHER_INDOOR,15-01-2024,10:30:00,00:05:00,140\n\
+                    Sample rate,Time,HR (bpm),Speed (km/h)\n\
+                    1,00:00:00,95,\n\
+                    1,00:00:01,,\n\
+                    1,00:01:00,150,\n";
+        let tokyo = LocalTz::from_name(Some("Asia/Tokyo")).unwrap();
+        let samples = parse_csv(text, &tokyo).unwrap();
+        let start = Utc.with_ymd_and_hms(2024, 1, 15, 1, 30, 0).unwrap();
+        assert_eq!(
+            samples,
+            vec![
+                HrSample { at: start, bpm: 95 },
+                HrSample {
+                    at: start + chrono::Duration::minutes(1),
+                    bpm: 150
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_timestamped_export() {
+        let text = "\"timestamp\",\"heart_rate\"\n\
+                    2024-01-15T10:30:00Z,120\n\
+                    2024-01-15 10:30:05,130.4\n\
+                    1705314610,0\n\
+                    1705314615,140\n";
+        let samples = parse_csv(text, &utc()).unwrap();
+        let start = Utc.with_ymd_and_hms(2024, 1, 15, 10, 30, 0).unwrap();
+        assert_eq!(
+            samples
+                .iter()
+                .map(|s| (s.at - start).num_seconds())
+                .collect::<Vec<_>>(),
+            vec![0, 5, 15]
+        );
+        assert_eq!(
+            samples.iter().map(|s| s.bpm).collect::<Vec<_>>(),
+            vec![120, 130, 140]
+        );
+
+        assert!(parse_csv("time,speed\n2024-01-15T10:30:00Z,12\n", &utc()).is_err());
+        assert!(parse_csv("timestamp,hr\n", &utc()).is_err());
+    }
+
+    #[test]
+    fn test_apply_uses_session_window() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 15, 10, 30, 0).unwrap();
+        let at = |seconds: i64, bpm: u8| HrSample {
+            at: start + chrono::Duration::seconds(seconds),
+            bpm,
+        };
+        // Warmup before and cooldown after the 5-minute dose are ignored
+        let samples = [
+            at(-60, 100),
+            at(0, 130),
+            at(150, 160),
+            at(300, 171),
+            at(400, 110),
+        ];
+
+        let mut dose = session(start, 300);
+        apply(&mut dose, &samples).unwrap();
+        assert_eq!(dose.avg_hr, Some(154));
+        assert_eq!(dose.max_hr, Some(171));
+
+        let mut later = session(start + chrono::Duration::hours(2), 300);
+        assert!(apply(&mut later, &samples).is_err());
+        assert_eq!(later.avg_hr, None);
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn test_attach_records_series() {
+        use crate::{FileStorage, JsonlSink, SessionSink};
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let storage = FileStorage::new(temp_dir.path());
+        let start = Utc.with_ymd_and_hms(2024, 1, 15, 10, 30, 0).unwrap();
+        let dose = session(start, 300);
+        JsonlSink::new(&storage.wal_path).append(&dose).unwrap();
+        let recording = temp_dir.path().join("suunto.csv");
+        std::fs::write(
+            &recording,
+            "timestamp,hr\n2024-01-15T10:31:00Z,140\n2024-01-15T10:32:00Z,160\n",
+        )
+        .unwrap();
+
+        let updated = attach(&storage, temp_dir.path(), dose.id, &recording, &utc())
+            .unwrap()
+            .unwrap();
+        assert_eq!((updated.avg_hr, updated.max_hr), (Some(150), Some(160)));
+        assert_eq!(updated.hr_series_path, Some(series_file(dose.id)));
+
+        let stored =
+            crate::wal::read_sessions(&storage.wal_path, crate::lock::DEFAULT_TIMEOUT).unwrap();
+        assert_eq!(stored[0].hr_series_path, updated.hr_series_path);
+        let series = load_series(temp_dir.path(), &stored[0], &utc())
+            .unwrap()
+            .unwrap();
+        assert_eq!(series.len(), 2);
+        assert_eq!(load_series(temp_dir.path(), &dose, &utc()).unwrap(), None);
+    }
+
+    #[test]
+    fn test_series_path() {
+        let id = Uuid::nil();
+        assert_eq!(
+            series_file(id),
+            "hr/00000000-0000-0000-0000-000000000000.csv"
+        );
+        assert_eq!(
+            series_path(Path::new("/data"), id),
+            PathBuf::from("/data/hr/00000000-0000-0000-0000-000000000000.csv")
+        );
+    }
+}
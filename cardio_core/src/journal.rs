@@ -0,0 +1,306 @@
+//! Markdown daily-note journaling.
+//!
+//! With `[journal] daily_note` set, each completed dose appends a line to
+//! that day's note and `krep rollup` writes a one-line daily summary, so
+//! training shows up in an existing journal (e.g. an Obsidian vault). The
+//! summary line is recognised by [`SUMMARY_PREFIX`] and rewritten in place,
+//! so refreshing it never duplicates it.
+
+use crate::config::JournalConfig;
+use crate::{Catalog, LocalTz, MicrodoseSession, Result, SessionKind};
+use chrono::NaiveDate;
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Start of the daily summary line
+pub const SUMMARY_PREFIX: &str = "- krep daily:";
+
+/// Placeholders a daily-note template may use
+pub const PLACEHOLDERS: [&str; 4] = ["{date}", "{year}", "{month}", "{day}"];
+
+/// Daily-note path for `date`: placeholders filled in and a leading `~/`
+/// expanded to the home directory
+pub fn note_path(template: &str, date: NaiveDate) -> PathBuf {
+    let filled = template
+        .replace("{date}", &date.format("%Y-%m-%d").to_string())
+        .replace("{year}", &date.format("%Y").to_string())
+        .replace("{month}", &date.format("%m").to_string())
+        .replace("{day}", &date.format("%d").to_string());
+    match filled.strip_prefix("~/").zip(dirs::home_dir()) {
+        Some((rest, home)) => home.join(rest),
+        None => PathBuf::from(filled),
+    }
+}
+
+/// Placeholders in `template` that [`note_path`] does not fill in
+pub fn unknown_placeholders(template: &str) -> Vec<String> {
+    let mut unknown = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let Some(len) = rest[start..].find('}') else {
+            break;
+        };
+        let placeholder = &rest[start..start + len + 1];
+        if !PLACEHOLDERS.contains(&placeholder) {
+            unknown.push(placeholder.to_string());
+        }
+        rest = &rest[start + len + 1..];
+    }
+    unknown
+}
+
+/// Journal line for a completed dose, e.g.
+/// `- 07:42 EMOM Burpees (5:00) 5,5,5,4,3 #travel`
+pub fn dose_line(session: &MicrodoseSession, name: &str, tz: &LocalTz) -> String {
+    let mut line = format!("- {} {}", tz.format(session.performed_at, "%H:%M"), name);
+    if let Some(seconds) = session.actual_duration_seconds {
+        line.push_str(&format!(" ({}:{:02})", seconds / 60, seconds % 60));
+    }
+    if let Some(per_set) = session.reps_per_set() {
+        let reps: Vec<String> = per_set.iter().map(|r| r.to_string()).collect();
+        line.push_str(&format!(" {}", reps.join(",")));
+    }
+    for tag in &session.tags {
+        line.push_str(&format!(" #{}", tag));
+    }
+    line
+}
+
+/// Summary line for `date`, or `None` without sessions that day, e.g.
+/// `- krep daily: 4 doses, 18 min (gtg 2, vo2 1, mobility 1)`
+pub fn summary_line(
+    sessions: &[SessionKind],
+    catalog: &Catalog,
+    date: NaiveDate,
+    tz: &LocalTz,
+) -> Option<String> {
+    let done: Vec<&MicrodoseSession> = sessions
+        .iter()
+        .filter_map(|s| s.as_real())
+        .filter(|s| tz.date_of(s.performed_at) == date)
+        .collect();
+    if done.is_empty() {
+        return None;
+    }
+
+    let seconds: u32 = done.iter().filter_map(|s| s.actual_duration_seconds).sum();
+    let mut per_category: BTreeMap<&str, usize> = BTreeMap::new();
+    for session in &done {
+        let category = catalog
+            .definition(&session.definition_id)
+            .map_or("other", |d| d.category.as_str());
+        *per_category.entry(category).or_default() += 1;
+    }
+    let mut categories: Vec<(&str, usize)> = per_category.into_iter().collect();
+    categories.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+    let categories: Vec<String> = categories
+        .iter()
+        .map(|(category, count)| format!("{} {}", category, count))
+        .collect();
+
+    Some(format!(
+        "{} {} dose{}, {} min ({})",
+        SUMMARY_PREFIX,
+        done.len(),
+        if done.len() == 1 { "" } else { "s" },
+        (seconds + 30) / 60,
+        categories.join(", ")
+    ))
+}
+
+/// Append a completed dose to its day's note (no-op without a daily note)
+pub fn record_dose(
+    config: &JournalConfig,
+    session: &MicrodoseSession,
+    name: &str,
+    tz: &LocalTz,
+) -> Result<()> {
+    let Some(template) = &config.daily_note else {
+        return Ok(());
+    };
+    let path = note_path(template, tz.date_of(session.performed_at));
+    append_line(&path, &dose_line(session, name, tz))
+}
+
+/// Write the summary line into the notes of the last `days` days (up to
+/// `today`) that have sessions; returns how many notes were updated
+pub fn refresh_summaries(
+    config: &JournalConfig,
+    sessions: &[SessionKind],
+    catalog: &Catalog,
+    tz: &LocalTz,
+    today: NaiveDate,
+    days: u32,
+) -> Result<usize> {
+    let Some(template) = &config.daily_note else {
+        return Ok(0);
+    };
+    let mut updated = 0;
+    for back in 0..days {
+        let date = today - chrono::Duration::days(back.into());
+        if let Some(line) = summary_line(sessions, catalog, date, tz) {
+            write_summary(&note_path(template, date), &line)?;
+            updated += 1;
+        }
+    }
+    Ok(updated)
+}
+
+/// Append a line to a note, creating it (and its directory) if needed
+pub fn append_line(path: &Path, line: &str) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    // Don't glue the line onto a last line without a newline
+    let needs_newline = std::fs::read(path)
+        .map(|contents| !contents.is_empty() && !contents.ends_with(b"\n"))
+        .unwrap_or(false);
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    if needs_newline {
+        file.write_all(b"\n")?;
+    }
+    writeln!(file, "{}", line)?;
+    Ok(())
+}
+
+/// Replace the note's summary line with `line`, or append it if there is none
+pub fn write_summary(path: &Path, line: &str) -> Result<()> {
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return append_line(path, line),
+        Err(e) => return Err(e.into()),
+    };
+    if !contents.lines().any(|l| l.starts_with(SUMMARY_PREFIX)) {
+        return append_line(path, line);
+    }
+
+    let mut updated: String = contents
+        .lines()
+        .map(|l| {
+            if l.starts_with(SUMMARY_PREFIX) {
+                line
+            } else {
+                l
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    if contents.ends_with('\n') {
+        updated.push('\n');
+    }
+    if updated != contents {
+        // Atomic, so a crash can't leave a half-written note
+        let dir = path.parent().unwrap_or_else(|| Path::new("."));
+        let mut temp = tempfile::NamedTempFile::new_in(dir)?;
+        temp.write_all(updated.as_bytes())?;
+        temp.persist(path).map_err(|e| crate::Error::Io(e.error))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone, Utc};
+
+    fn session(definition_id: &str, hour: u32, seconds: u32) -> MicrodoseSession {
+        let at = Utc.with_ymd_and_hms(2024, 5, 1, hour, 0, 0).unwrap();
+        MicrodoseSession {
+            started_at: Some(at),
+            completed_at: Some(at + Duration::seconds(i64::from(seconds))),
+            actual_duration_seconds: Some(seconds),
+            ..MicrodoseSession::new(definition_id, at)
+        }
+    }
+
+    #[test]
+    fn test_note_path_and_placeholders() {
+        let date = NaiveDate::from_ymd_opt(2024, 5, 1).unwrap();
+        assert_eq!(
+            note_path("/notes/{year}/{month}/{date}.md", date),
+            PathBuf::from("/notes/2024/05/2024-05-01.md")
+        );
+        assert!(unknown_placeholders("/notes/{date}.md").is_empty());
+        assert_eq!(
+            unknown_placeholders("/notes/{week}-{day}.md"),
+            vec!["{week}"]
+        );
+    }
+
+    #[test]
+    fn test_lines() {
+        let tz = LocalTz::from_name(Some("UTC")).unwrap();
+        let catalog = crate::build_default_catalog();
+        let mut burpees = session("emom_burpee_5m", 7, 300);
+        burpees.tags = vec!["travel".into()];
+        assert_eq!(
+            dose_line(&burpees, "EMOM Burpees", &tz),
+            "- 07:00 EMOM Burpees (5:00) #travel"
+        );
+
+        let sessions: Vec<SessionKind> = [
+            burpees,
+            session("gtg_pullup_ladder", 9, 120),
+            session("gtg_pullup_ladder", 12, 120),
+            session("google_fit_jumping_rope", 18, 200),
+        ]
+        .into_iter()
+        .map(SessionKind::Real)
+        .collect();
+        let date = NaiveDate::from_ymd_opt(2024, 5, 1).unwrap();
+        assert_eq!(
+            summary_line(&sessions, &catalog, date, &tz).unwrap(),
+            "- krep daily: 4 doses, 12 min (gtg 2, other 1, vo2 1)"
+        );
+        assert!(summary_line(&sessions, &catalog, date.succ_opt().unwrap(), &tz).is_none());
+    }
+
+    #[test]
+    fn test_record_and_refresh() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let tz = LocalTz::from_name(Some("UTC")).unwrap();
+        let catalog = crate::build_default_catalog();
+        let config = JournalConfig {
+            daily_note: Some(format!("{}/{{date}}.md", temp_dir.path().display())),
+        };
+        let pullups = session("gtg_pullup_ladder", 9, 120);
+        record_dose(&config, &pullups, "GTG Pull-ups", &tz).unwrap();
+
+        let today = NaiveDate::from_ymd_opt(2024, 5, 2).unwrap();
+        let sessions = vec![SessionKind::Real(pullups)];
+        assert_eq!(
+            refresh_summaries(&config, &sessions, &catalog, &tz, today, 7).unwrap(),
+            1
+        );
+        assert_eq!(
+            std::fs::read_to_string(temp_dir.path().join("2024-05-01.md")).unwrap(),
+            "- 09:00 GTG Pull-ups (2:00)\n- krep daily: 1 dose, 2 min (gtg 1)\n"
+        );
+
+        let disabled = JournalConfig::default();
+        assert_eq!(
+            refresh_summaries(&disabled, &sessions, &catalog, &tz, today, 7).unwrap(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_write_summary_replaces_in_place() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("daily").join("2024-05-01.md");
+
+        append_line(&path, "- 07:00 EMOM Burpees").unwrap();
+        write_summary(&path, "- krep daily: 1 dose, 5 min (vo2 1)").unwrap();
+        append_line(&path, "- 09:00 GTG Pull-ups").unwrap();
+        write_summary(&path, "- krep daily: 2 doses, 7 min (gtg 1, vo2 1)").unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "- 07:00 EMOM Burpees\n- krep daily: 2 doses, 7 min (gtg 1, vo2 1)\n- 09:00 GTG Pull-ups\n"
+        );
+    }
+}
@@ -5,30 +5,142 @@
 //! This crate provides:
 //! - Domain types (movements, microdoses, sessions, metrics)
 //! - Catalog management
-//! - Prescription engine
-//! - Persistence (WAL, CSV, state)
-//! - Progression logic
+//! - Prescription engine, including novelty-weighted "surprise me" picks,
+//!   run against a [`Clock`] so any instant can be replayed
+//! - Block-by-block checklists for multi-block doses (circuits)
+//! - Persistence (WAL, CSV, state) and data anomaly checks, with a
+//!   [`DataStore`] façade over a data directory for frontends and
+//!   observers on each session's lifecycle
+//! - Streaming NDJSON export, Google Fit Takeout import and merging
+//!   another data directory
+//! - Progression logic and audit log
+//! - Weekly dose goals and achievements
+//! - Daily soreness check-ins, travel mode (bodyweight-only doses),
+//!   office mode (low-sweat doses) and rehab mode (mobility only)
+//! - Off days (holidays) that pause prescriptions, reminders and streaks
+//! - Heart-rate zone targets and attached HR recordings (Polar, Suunto)
+//! - External strength and readiness (HRV, sleep) signals
+//! - Statistics, bodyweight trends and weekly reports
+//! - Household view of linked profiles' doses
+//! - Markdown daily-note journaling
+//! - JSON status file for home automation
+//! - Completion-by-hour analysis for reminder times
+//! - systemd user units for reminders and nightly rollup
+//! - Timer audio cues (`sound` feature)
+//! - Reference video thumbnails and embeds
+//! - Async wrappers for persistence (`tokio` feature)
+//! - SQLite analytics export (`analytics` feature)
+//! - Data-directory backup archives (`backup` feature)
+//! - User scripts for prescription rules and progression policies
+//!   (`scripting` feature)
+//! - proptest `Arbitrary` implementations for property tests (`proptest`
+//!   feature)
+//!
+//! The engine, progression and types compile without the `fs` feature
+//! (e.g. for `wasm32-unknown-unknown`); frontends then supply history and
+//! state through the [`storage::Storage`] trait.
 
+pub mod achievements;
+#[cfg(feature = "analytics")]
+pub mod analytics;
+pub mod anomaly;
+#[cfg(feature = "proptest")]
+pub mod arbitrary;
+#[cfg(feature = "tokio")]
+pub mod async_api;
+#[cfg(feature = "backup")]
+pub mod backup;
+pub mod bodyweight;
+pub mod cadence;
 pub mod catalog;
+pub mod circuit;
+pub mod clock;
 pub mod config;
+#[cfg(feature = "config-watch")]
+pub mod config_watch;
+#[cfg(feature = "csv-archive")]
+pub mod csv_index;
+#[cfg(feature = "csv-archive")]
+pub mod csv_manifest;
+#[cfg(feature = "csv-archive")]
 pub mod csv_rollup;
 pub mod engine;
 pub mod error;
+#[cfg(feature = "fs")]
+pub mod events;
+#[cfg(feature = "fs")]
+pub mod export;
+pub mod goal;
+#[cfg(feature = "fs")]
+pub mod google_fit;
+pub mod heart_rate;
 pub mod history;
+pub mod household;
+pub mod hr_recording;
+#[cfg(feature = "fs")]
+pub mod journal;
+#[cfg(feature = "fs")]
+pub mod lock;
+#[cfg(feature = "logging")]
 pub mod logging;
+#[cfg(feature = "fs")]
+pub mod merge;
+pub mod novelty;
+pub mod off_days;
+pub mod office;
+pub mod phase;
 pub mod progression;
+pub mod progression_log;
+pub mod readiness;
+pub mod reference;
+pub mod rehab;
+pub mod report;
+#[cfg(feature = "scripting")]
+pub mod scripting;
+pub mod soreness;
+pub mod sound;
+#[cfg(feature = "fs")]
 pub mod state;
+pub mod stats;
+#[cfg(feature = "csv-archive")]
+pub mod stats_cache;
+pub mod status;
+pub mod storage;
+#[cfg(feature = "fs")]
+pub mod store;
 pub mod strength;
+pub mod systemd;
+pub mod time;
+pub mod timer;
+pub mod timing;
+pub mod travel;
 pub mod types;
+#[cfg(feature = "fs")]
 pub mod wal;
+#[cfg(feature = "fs")]
+pub mod wal_index;
 
 // Re-export commonly used types
 pub use catalog::{build_default_catalog, get_default_catalog};
+pub use clock::{Clock, FixedClock, SystemClock};
 pub use config::Config;
-pub use engine::{prescribe_next, PrescribedMicrodose};
+#[cfg(feature = "config-watch")]
+pub use config_watch::ConfigWatcher;
+pub use engine::{prescribe_next, prescribe_surprise, PrescribedMicrodose, StateDelta};
 pub use error::{Error, Result};
-pub use history::load_recent_sessions;
+#[cfg(feature = "csv-archive")]
+pub use history::CsvSessions;
+pub use history::HistoryQuery;
+#[cfg(feature = "fs")]
+pub use history::{load_recent_sessions, load_recent_sessions_at, load_recent_sessions_with};
 pub use progression::increase_intensity;
+#[cfg(feature = "fs")]
+pub use storage::FileStorage;
+pub use storage::{MemoryStorage, Storage};
+#[cfg(feature = "fs")]
+pub use store::DataStore;
 pub use strength::load_external_strength;
+pub use time::LocalTz;
 pub use types::*;
+#[cfg(feature = "fs")]
 pub use wal::{JsonlSink, SessionSink};
@@ -0,0 +1,104 @@
+//! File lock acquisition with a timeout.
+//!
+//! `fs2` locks block until the holder lets go, so one hung process could
+//! wedge every other invocation. These helpers retry a non-blocking lock
+//! with exponential backoff and give up with [`Error::LockTimeout`] once
+//! the timeout passes. Every store takes the timeout from its caller, which
+//! passes `[data] lock_timeout_secs` ([`crate::FileStorage::lock_timeout`])
+//! or [`DEFAULT_TIMEOUT`].
+
+use crate::{Error, Result};
+use fs2::FileExt;
+use std::fs::File;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// First retry delay; doubles up to [`MAX_BACKOFF`]
+const INITIAL_BACKOFF: Duration = Duration::from_millis(10);
+
+/// Longest delay between retries
+const MAX_BACKOFF: Duration = Duration::from_millis(500);
+
+/// How long acquisition waits by default (the `lock_timeout_secs` default)
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Take a shared lock on `file` (opened from `path`), waiting up to [`DEFAULT_TIMEOUT`]
+pub fn lock_shared(file: &File, path: &Path) -> Result<()> {
+    lock_shared_within(file, path, DEFAULT_TIMEOUT)
+}
+
+/// Take a shared lock on `file` (opened from `path`), waiting up to `timeout`
+pub fn lock_shared_within(file: &File, path: &Path, timeout: Duration) -> Result<()> {
+    acquire(path, timeout, || FileExt::try_lock_shared(file))
+}
+
+/// Take an exclusive lock on `file` (opened from `path`), waiting up to [`DEFAULT_TIMEOUT`]
+pub fn lock_exclusive(file: &File, path: &Path) -> Result<()> {
+    lock_exclusive_within(file, path, DEFAULT_TIMEOUT)
+}
+
+/// Take an exclusive lock on `file` (opened from `path`), waiting up to `timeout`
+///
+/// A zero timeout fails as soon as the lock is found held.
+pub fn lock_exclusive_within(file: &File, path: &Path, timeout: Duration) -> Result<()> {
+    acquire(path, timeout, || FileExt::try_lock_exclusive(file))
+}
+
+fn acquire(
+    path: &Path,
+    timeout: Duration,
+    try_lock: impl Fn() -> std::io::Result<()>,
+) -> Result<()> {
+    let start = Instant::now();
+    let mut backoff = INITIAL_BACKOFF;
+    loop {
+        match try_lock() {
+            Ok(()) => return Ok(()),
+            Err(e) if is_contended(&e) => {}
+            Err(e) => return Err(e.into()),
+        }
+
+        let waited = start.elapsed();
+        if waited >= timeout {
+            return Err(Error::LockTimeout {
+                path: path.to_path_buf(),
+                waited,
+            });
+        }
+        if backoff == INITIAL_BACKOFF {
+            tracing::debug!("Waiting for lock on {:?}", path);
+        }
+        std::thread::sleep(backoff.min(timeout - waited));
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+    }
+}
+
+/// Whether a `try_lock_*` error means another handle holds the lock
+fn is_contended(e: &std::io::Error) -> bool {
+    e.kind() == std::io::ErrorKind::WouldBlock
+        || e.raw_os_error() == fs2::lock_contended_error().raw_os_error()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lock_times_out_while_held() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("test.wal");
+        let holder = File::create(&path).unwrap();
+        FileExt::lock_exclusive(&holder).unwrap();
+
+        let other = File::open(&path).unwrap();
+        let err = acquire(&path, Duration::from_millis(50), || {
+            FileExt::try_lock_shared(&other)
+        })
+        .unwrap_err();
+        assert!(matches!(err, Error::LockTimeout { .. }));
+        assert!(err.to_string().contains("test.wal"));
+
+        FileExt::unlock(&holder).unwrap();
+        lock_shared(&other, &path).unwrap();
+    }
+}
@@ -1,9 +1,20 @@
 //! Logging infrastructure for Krep.
 //!
-//! Provides centralized tracing setup for all binaries.
+//! Provides centralized tracing setup for all binaries, with optional JSON
+//! output and size/date-based rotation for file logs.
 
+use crate::config::{LogFormat, LoggingConfig};
+use chrono::{Local, NaiveDate};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tracing_subscriber::fmt::MakeWriter;
 use tracing_subscriber::{fmt, prelude::*, EnvFilter};
 
+/// Environment variable that overrides the configured log format (`text` or `json`)
+pub const LOG_FORMAT_ENV: &str = "KREP_LOG_FORMAT";
+
 /// Initialize logging with sensible defaults
 ///
 /// This sets up tracing with:
@@ -23,13 +34,162 @@ pub fn init() {
 ///
 /// This can still be overridden by RUST_LOG environment variable.
 pub fn init_with_level(default_level: &str) {
+    init_with_config(default_level, &LoggingConfig::default(), None)
+}
+
+/// Initialize logging from the `[logging]` config section
+///
+/// # Arguments
+/// * `default_level` - Default log level, overridable by RUST_LOG
+/// * `options` - Output format and rotation limits
+/// * `file` - Log file path; stderr is used when `None` or the file can't be opened
+///
+/// The format can be overridden with `KREP_LOG_FORMAT=json|text`.
+pub fn init_with_config(default_level: &str, options: &LoggingConfig, file: Option<&Path>) {
     let filter =
         EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(default_level));
+    let format = effective_format(options.format);
+
+    let rotating = file.and_then(|path| match RotatingFile::open(path, options) {
+        Ok(writer) => Some(writer),
+        Err(e) => {
+            eprintln!(
+                "Unable to open log file {:?}: {}. Logging to stderr.",
+                path, e
+            );
+            None
+        }
+    });
+
+    match rotating {
+        Some(writer) => install(filter, format, Mutex::new(writer), false),
+        None => install(filter, format, io::stderr, true),
+    }
+}
 
-    tracing_subscriber::registry()
+/// Resolve the output format, giving `KREP_LOG_FORMAT` precedence over config
+fn effective_format(configured: LogFormat) -> LogFormat {
+    match std::env::var(LOG_FORMAT_ENV) {
+        Ok(value) if value.eq_ignore_ascii_case("json") => LogFormat::Json,
+        Ok(value) if value.eq_ignore_ascii_case("text") => LogFormat::Text,
+        _ => configured,
+    }
+}
+
+/// Install the global subscriber with the selected format and writer
+fn install<W>(filter: EnvFilter, format: LogFormat, writer: W, ansi: bool)
+where
+    W: for<'a> MakeWriter<'a> + Send + Sync + 'static,
+{
+    let (json, text) = match format {
+        LogFormat::Json => (Some(fmt::layer().json().with_writer(writer)), None),
+        LogFormat::Text => (
+            None,
+            Some(fmt::layer().compact().with_ansi(ansi).with_writer(writer)),
+        ),
+    };
+
+    let _ = tracing_subscriber::registry()
         .with(filter)
-        .with(fmt::layer().compact())
-        .init();
+        .with(json)
+        .with(text)
+        .try_init();
+}
+
+/// Append-only log file that rotates by size and (optionally) local date
+///
+/// Rotated files are numbered `krep_tray.log.1` (newest) through
+/// `krep_tray.log.N`; the oldest is removed once `max_files` is exceeded.
+pub struct RotatingFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    opened_on: NaiveDate,
+    max_bytes: u64,
+    max_files: usize,
+    rotate_daily: bool,
+}
+
+impl RotatingFile {
+    /// Open (or create) the log file using the configured limits
+    pub fn open(path: &Path, options: &LoggingConfig) -> io::Result<Self> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let metadata = file.metadata()?;
+        let opened_on = metadata
+            .modified()
+            .map(|t| chrono::DateTime::<Local>::from(t).date_naive())
+            .unwrap_or_else(|_| Local::now().date_naive());
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            file,
+            size: metadata.len(),
+            opened_on,
+            max_bytes: options.max_size_mb.saturating_mul(1024 * 1024),
+            max_files: options.max_files.max(1),
+            rotate_daily: options.rotate_daily,
+        })
+    }
+
+    fn needs_rotation(&self, incoming: usize) -> bool {
+        let too_big =
+            self.max_bytes > 0 && self.size > 0 && self.size + incoming as u64 > self.max_bytes;
+        let new_day = self.rotate_daily && Local::now().date_naive() != self.opened_on;
+        too_big || new_day
+    }
+
+    fn rotated_path(&self, index: usize) -> PathBuf {
+        let mut name = self.path.as_os_str().to_os_string();
+        name.push(format!(".{}", index));
+        PathBuf::from(name)
+    }
+
+    /// Shift `log.N-1 → log.N`, …, `log → log.1` and reopen an empty log
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+
+        let oldest = self.rotated_path(self.max_files);
+        if oldest.exists() {
+            std::fs::remove_file(&oldest)?;
+        }
+        for index in (1..self.max_files).rev() {
+            let from = self.rotated_path(index);
+            if from.exists() {
+                std::fs::rename(&from, self.rotated_path(index + 1))?;
+            }
+        }
+        std::fs::rename(&self.path, self.rotated_path(1))?;
+
+        self.file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        self.size = 0;
+        self.opened_on = Local::now().date_naive();
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.needs_rotation(buf.len()) {
+            if let Err(e) = self.rotate() {
+                // Keep logging to the current file rather than dropping lines
+                eprintln!("Log rotation failed for {:?}: {}", self.path, e);
+            }
+        }
+        let written = self.file.write(buf)?;
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
 }
 
 /// Initialize logging for testing (captures logs for test output)
@@ -40,3 +200,34 @@ pub fn init_test() {
         .with_env_filter(EnvFilter::new("debug"))
         .try_init();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rotating_file_rotates_by_size() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let log_path = temp_dir.path().join("krep_tray.log");
+        let options = LoggingConfig {
+            max_size_mb: 1,
+            max_files: 2,
+            rotate_daily: false,
+            ..LoggingConfig::default()
+        };
+
+        let mut file = RotatingFile::open(&log_path, &options).unwrap();
+        let chunk = vec![b'x'; 600 * 1024];
+        for _ in 0..4 {
+            file.write_all(&chunk).unwrap();
+        }
+        file.flush().unwrap();
+
+        // 4 chunks of 600 KiB with a 1 MiB cap → current + 2 rotated files
+        assert!(log_path.exists());
+        assert!(temp_dir.path().join("krep_tray.log.1").exists());
+        assert!(temp_dir.path().join("krep_tray.log.2").exists());
+        assert!(!temp_dir.path().join("krep_tray.log.3").exists());
+        assert!(std::fs::metadata(&log_path).unwrap().len() <= 1024 * 1024);
+    }
+}
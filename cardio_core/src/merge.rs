@@ -0,0 +1,294 @@
+//! Merging another krep data directory into this one.
+//!
+//! Sessions are matched by UUID: a session whose ID is already here (in the
+//! WAL or the CSV archive) is a duplicate, everything else is appended to the
+//! WAL. Progression state is merged per definition, keeping whichever side
+//! progressed more recently, while personal bests and achievements are
+//! combined. Merging the same directory twice changes nothing the second time.
+
+use crate::{FileStorage, MicrodoseSession, Result, UserMicrodoseState};
+use std::collections::HashSet;
+use uuid::Uuid;
+
+/// What a merge added
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MergeReport {
+    pub sessions_new: usize,
+    pub sessions_duplicate: usize,
+    /// Definitions with no progression here before
+    pub progressions_new: usize,
+    /// Definitions whose progression was replaced by a more recent one
+    pub progressions_updated: usize,
+    pub personal_bests_updated: usize,
+    pub achievements_new: usize,
+}
+
+/// IDs of every session in `storage` (WAL and CSV archive)
+pub fn session_ids(storage: &FileStorage) -> Result<HashSet<Uuid>> {
+    let mut ids = HashSet::new();
+    crate::wal::for_each_session(&storage.wal_path, storage.lock_timeout, |session| {
+        ids.insert(session.id);
+        Ok(())
+    })?;
+    #[cfg(feature = "csv-archive")]
+    if storage.csv_path.exists() {
+        for session in crate::history::CsvSessions::open(&storage.csv_path, None, None)? {
+            ids.insert(session.id);
+        }
+    }
+    Ok(ids)
+}
+
+/// Every session in `storage`, each ID once, oldest first
+pub fn all_sessions(storage: &FileStorage) -> Result<Vec<MicrodoseSession>> {
+    let mut seen = HashSet::new();
+    let mut sessions = Vec::new();
+    crate::wal::for_each_session(&storage.wal_path, storage.lock_timeout, |session| {
+        if seen.insert(session.id) {
+            sessions.push(session);
+        }
+        Ok(())
+    })?;
+    #[cfg(feature = "csv-archive")]
+    if storage.csv_path.exists() {
+        for session in crate::history::CsvSessions::open(&storage.csv_path, None, None)? {
+            if seen.insert(session.id) {
+                sessions.push(session);
+            }
+        }
+    }
+    sessions.sort_by_key(|s| s.performed_at);
+    Ok(sessions)
+}
+
+/// Split `theirs` into sessions not in `known` and the number of duplicates
+pub fn new_sessions(
+    theirs: Vec<MicrodoseSession>,
+    known: &HashSet<Uuid>,
+) -> (Vec<MicrodoseSession>, usize) {
+    let total = theirs.len();
+    let new: Vec<_> = theirs
+        .into_iter()
+        .filter(|s| !known.contains(&s.id))
+        .collect();
+    let duplicates = total - new.len();
+    (new, duplicates)
+}
+
+/// Merge `theirs` into `ours`, counting what changed into `report`
+///
+/// - Progressions: a definition only they have is added; for one both have,
+///   theirs replaces ours when it was upgraded more recently
+/// - Personal bests: the higher total
+/// - Achievements: the earlier unlock
+/// - Weekly goal and mobility rotation: ours, or theirs when we have none
+pub fn merge_state(
+    ours: &mut UserMicrodoseState,
+    theirs: &UserMicrodoseState,
+    report: &mut MergeReport,
+) {
+    for (id, their_progression) in &theirs.progressions {
+        match ours.progressions.get_mut(id) {
+            None => {
+                ours.progressions
+                    .insert(id.clone(), their_progression.clone());
+                report.progressions_new += 1;
+            }
+            Some(our_progression)
+                if their_progression.last_upgraded > our_progression.last_upgraded =>
+            {
+                *our_progression = their_progression.clone();
+                report.progressions_updated += 1;
+            }
+            Some(_) => {}
+        }
+    }
+
+    for (id, &total) in &theirs.personal_bests {
+        match ours.personal_bests.get_mut(id) {
+            Some(best) if total <= *best => continue,
+            Some(best) => *best = total,
+            None => {
+                ours.personal_bests.insert(id.clone(), total);
+            }
+        }
+        report.personal_bests_updated += 1;
+    }
+
+    for (id, &unlocked) in &theirs.achievements {
+        match ours.achievements.get_mut(id) {
+            None => {
+                ours.achievements.insert(id.clone(), unlocked);
+                report.achievements_new += 1;
+            }
+            Some(ours_at) if unlocked < *ours_at => *ours_at = unlocked,
+            Some(_) => {}
+        }
+    }
+
+    if ours.weekly_goal.is_none() {
+        ours.weekly_goal = theirs.weekly_goal.clone();
+    }
+    if ours.last_mobility_def_id.is_none() {
+        ours.last_mobility_def_id = theirs.last_mobility_def_id.clone();
+    }
+}
+
+/// Merge the data directory behind `theirs` into `ours`
+///
+/// New sessions are appended to our WAL (oldest first) and the merged state
+/// is saved. With `dry_run` nothing is written; the report says what would
+/// have been.
+pub fn merge_dir(ours: &FileStorage, theirs: &FileStorage, dry_run: bool) -> Result<MergeReport> {
+    use crate::SessionSink;
+
+    let mut report = MergeReport::default();
+    let known = session_ids(ours)?;
+    let (new, duplicates) = new_sessions(all_sessions(theirs)?, &known);
+    report.sessions_new = new.len();
+    report.sessions_duplicate = duplicates;
+
+    let mut state = UserMicrodoseState::load(&ours.state_path, ours.lock_timeout)?;
+    if theirs.state_path.exists() {
+        let their_state = UserMicrodoseState::load(&theirs.state_path, theirs.lock_timeout)?;
+        merge_state(&mut state, &their_state, &mut report);
+    }
+
+    if dry_run {
+        return Ok(report);
+    }
+    let mut sink = ours.wal_sink();
+    for session in &new {
+        sink.append(session)?;
+    }
+    if theirs.state_path.exists() {
+        state.save(&ours.state_path, ours.lock_timeout)?;
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{JsonlSink, MovementStyle, ProgressionState, SessionSink, WeeklyGoal};
+    use chrono::{Duration, Utc};
+
+    fn session(hours_ago: i64) -> MicrodoseSession {
+        let at = Utc::now() - Duration::hours(hours_ago);
+        MicrodoseSession {
+            started_at: Some(at),
+            completed_at: Some(at),
+            actual_duration_seconds: Some(120),
+            ..MicrodoseSession::new("gtg_pullup_ladder", at)
+        }
+    }
+
+    fn progression(reps: i32, days_ago: Option<i64>) -> ProgressionState {
+        ProgressionState {
+            reps,
+            style: MovementStyle::None,
+            level: 0,
+            last_upgraded: days_ago.map(|d| Utc::now() - Duration::days(d)),
+        }
+    }
+
+    #[test]
+    fn test_merge_state() {
+        let now = Utc::now();
+        let mut ours = UserMicrodoseState::default();
+        ours.progressions
+            .insert("gtg_pullup_ladder".into(), progression(5, Some(3)));
+        ours.progressions
+            .insert("vo2_emom_burpees".into(), progression(8, Some(1)));
+        ours.personal_bests.insert("amrap_climbers".into(), 40);
+        ours.achievements
+            .insert("first_dose".into(), now - Duration::days(5));
+
+        let mut theirs = UserMicrodoseState::default();
+        theirs
+            .progressions
+            .insert("gtg_pullup_ladder".into(), progression(7, Some(1)));
+        theirs
+            .progressions
+            .insert("vo2_emom_burpees".into(), progression(6, Some(2)));
+        theirs
+            .progressions
+            .insert("core_dead_bug".into(), progression(10, None));
+        theirs.personal_bests.insert("amrap_climbers".into(), 45);
+        theirs.personal_bests.insert("amrap_squats".into(), 30);
+        theirs
+            .achievements
+            .insert("first_dose".into(), now - Duration::days(9));
+        theirs
+            .achievements
+            .insert("streak_7".into(), now - Duration::days(2));
+        theirs.weekly_goal = Some(WeeklyGoal {
+            weekly_doses: 15,
+            travel_weekly_doses: None,
+        });
+
+        let mut report = MergeReport::default();
+        merge_state(&mut ours, &theirs, &mut report);
+        assert_eq!(ours.progressions["gtg_pullup_ladder"].reps, 7);
+        assert_eq!(ours.progressions["vo2_emom_burpees"].reps, 8);
+        assert_eq!(ours.progressions["core_dead_bug"].reps, 10);
+        assert_eq!(ours.personal_bests["amrap_climbers"], 45);
+        assert_eq!(ours.personal_bests["amrap_squats"], 30);
+        assert_eq!(ours.achievements["first_dose"], now - Duration::days(9));
+        assert!(ours.achievements.contains_key("streak_7"));
+        assert_eq!(ours.weekly_goal, theirs.weekly_goal);
+        assert_eq!(
+            report,
+            MergeReport {
+                sessions_new: 0,
+                sessions_duplicate: 0,
+                progressions_new: 1,
+                progressions_updated: 1,
+                personal_bests_updated: 2,
+                achievements_new: 1,
+            }
+        );
+
+        // Merging again changes nothing
+        let mut again = MergeReport::default();
+        merge_state(&mut ours, &theirs, &mut again);
+        assert_eq!(again, MergeReport::default());
+    }
+
+    #[test]
+    fn test_merge_dir_dedups_by_id() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let ours = FileStorage::new(&temp_dir.path().join("ours"));
+        let theirs = FileStorage::new(&temp_dir.path().join("theirs"));
+
+        let shared = session(5);
+        JsonlSink::new(&ours.wal_path).append(&shared).unwrap();
+        let mut sink = JsonlSink::new(&theirs.wal_path);
+        let (newer, older) = (session(1), session(30));
+        for s in [&newer, &shared, &older] {
+            sink.append(s).unwrap();
+        }
+
+        let dry = merge_dir(&ours, &theirs, true).unwrap();
+        assert_eq!((dry.sessions_new, dry.sessions_duplicate), (2, 1));
+        assert_eq!(
+            crate::wal::read_sessions(&ours.wal_path, crate::lock::DEFAULT_TIMEOUT)
+                .unwrap()
+                .len(),
+            1
+        );
+
+        let report = merge_dir(&ours, &theirs, false).unwrap();
+        assert_eq!(report, dry);
+        let merged: Vec<Uuid> =
+            crate::wal::read_sessions(&ours.wal_path, crate::lock::DEFAULT_TIMEOUT)
+                .unwrap()
+                .iter()
+                .map(|s| s.id)
+                .collect();
+        assert_eq!(merged, vec![shared.id, older.id, newer.id]);
+
+        let again = merge_dir(&ours, &theirs, false).unwrap();
+        assert_eq!((again.sessions_new, again.sessions_duplicate), (0, 3));
+    }
+}
@@ -0,0 +1,96 @@
+//! Novelty weighting for "surprise me" prescriptions.
+//!
+//! Every definition in the catalog gets a weight of `1 / (1 + n)`, where
+//! `n` is how often it was done in the recent sessions, so a definition
+//! never done recently is twice as likely as one done once and a favourite
+//! done every day rarely comes up. [`pick`] draws from the weights with a
+//! roll in `[0, 1)`, which [`roll`] provides in practice.
+
+use crate::{Catalog, MicrodoseDefinition, SessionKind};
+use std::collections::HashMap;
+
+/// Definitions with their novelty weights, sorted by ID
+pub fn weights<'a>(
+    catalog: &'a Catalog,
+    sessions: &[SessionKind],
+) -> Vec<(&'a MicrodoseDefinition, f64)> {
+    let mut done: HashMap<&str, u32> = HashMap::new();
+    for session in sessions.iter().filter_map(|s| s.as_real()) {
+        *done.entry(session.definition_id.as_str()).or_insert(0) += 1;
+    }
+
+    catalog
+        .definitions()
+        .map(|definition| {
+            let count = done.get(definition.id.as_str()).copied().unwrap_or(0);
+            (definition, 1.0 / f64::from(count + 1))
+        })
+        .collect()
+}
+
+/// The entry `roll` (in `[0, 1)`) lands on, proportionally to the weights
+/// (`None` when there are none)
+pub fn pick<T: Copy>(weighted: &[(T, f64)], roll: f64) -> Option<T> {
+    let total: f64 = weighted.iter().map(|(_, weight)| weight).sum();
+    let mut target = roll.clamp(0.0, 1.0) * total;
+    for (item, weight) in weighted {
+        if target < *weight {
+            return Some(*item);
+        }
+        target -= weight;
+    }
+    // Rounding (or a roll of exactly 1.0) falls off the end
+    weighted.last().map(|(item, _)| *item)
+}
+
+/// A random roll in `[0, 1)` (from the OS RNG behind UUID v4)
+pub fn roll() -> f64 {
+    let (high, _) = uuid::Uuid::new_v4().as_u64_pair();
+    (high >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::build_default_catalog;
+    use chrono::Utc;
+
+    fn done(def_id: &str) -> SessionKind {
+        SessionKind::Real(crate::MicrodoseSession {
+            actual_duration_seconds: Some(300),
+            ..crate::MicrodoseSession::new(def_id, Utc::now())
+        })
+    }
+
+    #[test]
+    fn test_weights_favour_what_was_not_done() {
+        let catalog = build_default_catalog();
+        let sessions = vec![
+            done("emom_burpee_5m"),
+            done("emom_burpee_5m"),
+            done("emom_burpee_5m"),
+            done("gtg_pullup_band"),
+        ];
+
+        let weighted = weights(&catalog, &sessions);
+        assert_eq!(weighted.len(), catalog.microdoses().len());
+        let weight = |id: &str| weighted.iter().find(|(d, _)| d.id == id).unwrap().1;
+        assert_eq!(weight("emom_burpee_5m"), 0.25);
+        assert_eq!(weight("gtg_pullup_band"), 0.5);
+        assert_eq!(weight("mobility_hip_cars"), 1.0);
+    }
+
+    #[test]
+    fn test_pick_is_proportional() {
+        let weighted = [("a", 1.0), ("b", 0.5), ("c", 0.5)];
+        assert_eq!(pick(&weighted, 0.0), Some("a"));
+        assert_eq!(pick(&weighted, 0.49), Some("a"));
+        assert_eq!(pick(&weighted, 0.5), Some("b"));
+        assert_eq!(pick(&weighted, 0.99), Some("c"));
+        assert_eq!(pick(&weighted, 1.0), Some("c"));
+        assert_eq!(pick::<&str>(&[], 0.5), None);
+
+        let roll = roll();
+        assert!((0.0..1.0).contains(&roll));
+    }
+}
@@ -0,0 +1,129 @@
+//! Off days: holidays and breaks.
+//!
+//! Off days come from `[off] days` in the config and from `krep off`
+//! (stored in [`UserMicrodoseState::off_days`](crate::UserMicrodoseState)).
+//! On an off day nothing is prescribed, reminded or due, and a day without
+//! sessions doesn't break the streak; sessions still logged count as usual.
+
+use crate::{Error, Result};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// Local dates from `start` to `end` (inclusive)
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OffRange {
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+}
+
+impl OffRange {
+    /// Whether `date` falls in the range
+    pub fn contains(&self, date: NaiveDate) -> bool {
+        self.start <= date && date <= self.end
+    }
+
+    /// `2024-08-10..2024-08-20`, or just the date for a single day
+    pub fn label(&self) -> String {
+        if self.start == self.end {
+            self.start.to_string()
+        } else {
+            format!("{}..{}", self.start, self.end)
+        }
+    }
+}
+
+impl FromStr for OffRange {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || {
+            Error::Config(format!(
+                "Invalid off days '{}' (expected YYYY-MM-DD or YYYY-MM-DD..YYYY-MM-DD)",
+                s
+            ))
+        };
+        let parse =
+            |d: &str| NaiveDate::parse_from_str(d.trim(), "%Y-%m-%d").map_err(|_| invalid());
+        let (start, end) = match s.split_once("..") {
+            Some((start, end)) => (parse(start)?, parse(end)?),
+            None => {
+                let day = parse(s)?;
+                (day, day)
+            }
+        };
+        if end < start {
+            return Err(Error::Config(format!(
+                "Off days '{}' end before they start",
+                s
+            )));
+        }
+        Ok(OffRange { start, end })
+    }
+}
+
+/// The range covering `date`, if any
+pub fn covering(ranges: &[OffRange], date: NaiveDate) -> Option<&OffRange> {
+    ranges.iter().find(|range| range.contains(date))
+}
+
+/// Whether `date` is an off day
+pub fn is_off(ranges: &[OffRange], date: NaiveDate) -> bool {
+    covering(ranges, date).is_some()
+}
+
+/// Add `range`, keeping the list in date order
+pub fn add(ranges: &mut Vec<OffRange>, range: OffRange) {
+    if !ranges.contains(&range) {
+        ranges.push(range);
+        ranges.sort_by_key(|r| (r.start, r.end));
+    }
+}
+
+/// Remove the ranges that haven't ended before `today`, returning how many
+pub fn clear_upcoming(ranges: &mut Vec<OffRange>, today: NaiveDate) -> usize {
+    let before = ranges.len();
+    ranges.retain(|range| range.end < today);
+    before - ranges.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(s: &str) -> NaiveDate {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_parse_off_range() {
+        let range: OffRange = "2024-08-10..2024-08-20".parse().unwrap();
+        assert_eq!(range.start, date("2024-08-10"));
+        assert_eq!(range.end, date("2024-08-20"));
+        assert_eq!(range.label(), "2024-08-10..2024-08-20");
+        assert!(range.contains(date("2024-08-20")));
+        assert!(!range.contains(date("2024-08-21")));
+
+        let day: OffRange = " 2024-12-25 ".parse().unwrap();
+        assert_eq!(day.label(), "2024-12-25");
+
+        assert!("2024-08-20..2024-08-10".parse::<OffRange>().is_err());
+        assert!("next week".parse::<OffRange>().is_err());
+    }
+
+    #[test]
+    fn test_add_and_clear() {
+        let mut ranges = Vec::new();
+        add(&mut ranges, "2024-12-24..2024-12-26".parse().unwrap());
+        add(&mut ranges, "2024-08-10..2024-08-20".parse().unwrap());
+        add(&mut ranges, "2024-08-10..2024-08-20".parse().unwrap());
+        assert_eq!(ranges.len(), 2);
+        assert_eq!(ranges[0].start, date("2024-08-10"));
+        assert!(is_off(&ranges, date("2024-12-25")));
+        assert!(!is_off(&ranges, date("2024-12-27")));
+
+        // Mid-holiday: the current and later ranges go
+        assert_eq!(clear_upcoming(&mut ranges, date("2024-08-15")), 2);
+        assert!(ranges.is_empty());
+    }
+}
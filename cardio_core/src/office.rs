@@ -0,0 +1,163 @@
+//! Office mode: low-sweat (GTG-friendly) prescriptions only.
+//!
+//! Office mode is on during the weekday windows in `[prescription]
+//! office_hours` (local time), or after `krep mode office on` until the
+//! expiry stored in
+//! [`UserMicrodoseState::office_mode_until`](crate::UserMicrodoseState).
+//! Frontends fold it into [`UserContext::gtg_friendly_only`](crate::UserContext)
+//! through [`Config::gtg_friendly_only_at`](crate::Config::gtg_friendly_only_at).
+
+use crate::{Error, LocalTz, Result, UserMicrodoseState};
+use chrono::{DateTime, Datelike, Duration, NaiveTime, Utc, Weekday};
+use std::str::FromStr;
+
+/// A local time-of-day window, e.g. `09:00-17:30`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OfficeWindow {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+impl OfficeWindow {
+    /// Whether `time` falls in the window (start inclusive, end exclusive)
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        self.start <= time && time < self.end
+    }
+}
+
+impl FromStr for OfficeWindow {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || {
+            Error::Config(format!(
+                "Invalid office hours '{}' (expected HH:MM-HH:MM)",
+                s
+            ))
+        };
+        let (start, end) = s.split_once('-').ok_or_else(invalid)?;
+        let parse = |t: &str| NaiveTime::parse_from_str(t.trim(), "%H:%M").map_err(|_| invalid());
+        let (start, end) = (parse(start)?, parse(end)?);
+        if start >= end {
+            return Err(Error::Config(format!(
+                "Office hours '{}' must end after they start",
+                s
+            )));
+        }
+        Ok(OfficeWindow { start, end })
+    }
+}
+
+/// Whether `now` is inside one of `windows` on a local weekday (Monday to Friday)
+pub fn in_office_hours(windows: &[OfficeWindow], tz: &LocalTz, now: DateTime<Utc>) -> bool {
+    if matches!(tz.date_of(now).weekday(), Weekday::Sat | Weekday::Sun) {
+        return false;
+    }
+    let time = tz.time_of(now);
+    windows.iter().any(|window| window.contains(time))
+}
+
+/// Expiry of the manual office mode if it is on at `now`
+pub fn active_until(state: &UserMicrodoseState, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    state.office_mode_until.filter(|until| *until > now)
+}
+
+/// Turn office mode on until `until`
+pub fn turn_on(state: &mut UserMicrodoseState, until: DateTime<Utc>) {
+    state.office_mode_until = Some(until);
+}
+
+/// Turn the manual office mode off (office hours still apply)
+pub fn turn_off(state: &mut UserMicrodoseState) {
+    state.office_mode_until = None;
+}
+
+/// Start of the next local day: when a manual office mode ends by default
+pub fn end_of_day(tz: &LocalTz, now: DateTime<Utc>) -> DateTime<Utc> {
+    tz.day_start_utc(tz.date_of(now) + Duration::days(1))
+}
+
+/// Flip the manual office mode (on until the end of the local day),
+/// returning the new expiry (`None` = now off)
+pub fn toggle(
+    state: &mut UserMicrodoseState,
+    tz: &LocalTz,
+    now: DateTime<Utc>,
+) -> Option<DateTime<Utc>> {
+    if active_until(state, now).is_some() {
+        turn_off(state);
+        None
+    } else {
+        let until = end_of_day(tz, now);
+        turn_on(state, until);
+        Some(until)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_parse_office_window() {
+        let window: OfficeWindow = "09:00-17:30".parse().unwrap();
+        assert_eq!(window.start, NaiveTime::from_hms_opt(9, 0, 0).unwrap());
+        assert_eq!(window.end, NaiveTime::from_hms_opt(17, 30, 0).unwrap());
+        assert_eq!(
+            " 9:00 - 12:00 "
+                .parse::<OfficeWindow>()
+                .unwrap()
+                .end
+                .to_string(),
+            "12:00:00"
+        );
+
+        assert!("09:00".parse::<OfficeWindow>().is_err());
+        assert!("9am-5pm".parse::<OfficeWindow>().is_err());
+        assert!("17:00-09:00".parse::<OfficeWindow>().is_err());
+    }
+
+    #[test]
+    fn test_office_hours_on_weekdays_only() {
+        let tz = LocalTz::from_name(Some("Asia/Tokyo")).unwrap();
+        let windows = vec![
+            "09:00-12:00".parse().unwrap(),
+            "13:00-17:30".parse().unwrap(),
+        ];
+        // Wednesday 2024-01-10, Tokyo local times (UTC+9)
+        let at = |h, m| {
+            tz.to_utc(
+                chrono::NaiveDate::from_ymd_opt(2024, 1, 10)
+                    .unwrap()
+                    .and_hms_opt(h, m, 0)
+                    .unwrap(),
+            )
+        };
+
+        assert!(in_office_hours(&windows, &tz, at(9, 0)));
+        assert!(!in_office_hours(&windows, &tz, at(12, 30)));
+        assert!(in_office_hours(&windows, &tz, at(17, 29)));
+        assert!(!in_office_hours(&windows, &tz, at(17, 30)));
+
+        // Saturday 2024-01-13 11:00 Tokyo
+        let saturday = Utc.with_ymd_and_hms(2024, 1, 13, 2, 0, 0).unwrap();
+        assert!(!in_office_hours(&windows, &tz, saturday));
+    }
+
+    #[test]
+    fn test_toggle_lasts_until_end_of_day() {
+        let tz = LocalTz::from_name(Some("Asia/Tokyo")).unwrap();
+        // 2024-01-10 15:00 Tokyo
+        let now = Utc.with_ymd_and_hms(2024, 1, 10, 6, 0, 0).unwrap();
+        let mut state = UserMicrodoseState::default();
+
+        let until = toggle(&mut state, &tz, now).unwrap();
+        assert_eq!(until, Utc.with_ymd_and_hms(2024, 1, 10, 15, 0, 0).unwrap());
+        assert_eq!(active_until(&state, now), Some(until));
+        assert_eq!(active_until(&state, until), None);
+
+        assert_eq!(toggle(&mut state, &tz, now), None);
+        assert_eq!(state.office_mode_until, None);
+    }
+}
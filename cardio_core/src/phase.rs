@@ -0,0 +1,224 @@
+//! Training phases: a lightweight periodization layer.
+//!
+//! `[[phases]]` in the config lists named phases (accumulation,
+//! intensification, deload) over local date ranges. While one is current its
+//! `weights` override `[prescription] weights` and its progression step sets
+//! how many progression rules one upgrade applies (a deload pauses
+//! progression). `krep stats` reports sessions per phase so the boundaries
+//! show up in the history.
+
+use crate::{Catalog, LocalTz, SessionKind};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Kind of training phase
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+#[cfg_attr(feature = "config-schema", derive(schemars::JsonSchema))]
+pub enum PhaseKind {
+    /// Build volume: steady, one-step progression
+    Accumulation,
+    /// Push intensity: bigger progression steps
+    Intensification,
+    /// Recover: no progression
+    Deload,
+}
+
+impl PhaseKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            PhaseKind::Accumulation => "accumulation",
+            PhaseKind::Intensification => "intensification",
+            PhaseKind::Deload => "deload",
+        }
+    }
+
+    /// Progression rules applied per upgrade unless the phase sets its own
+    pub fn default_progression_step(self) -> u32 {
+        match self {
+            PhaseKind::Accumulation => 1,
+            PhaseKind::Intensification => 2,
+            PhaseKind::Deload => 0,
+        }
+    }
+}
+
+/// One `[[phases]]` entry
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(feature = "config-schema", derive(schemars::JsonSchema))]
+pub struct Phase {
+    pub name: PhaseKind,
+    /// First day (local date)
+    #[cfg_attr(feature = "config-schema", schemars(with = "String"))]
+    pub start: NaiveDate,
+    /// Last day (local date, inclusive)
+    #[cfg_attr(feature = "config-schema", schemars(with = "String"))]
+    pub end: NaiveDate,
+    /// Rotation weights by category name, replacing those in
+    /// `[prescription] weights` while the phase is current
+    #[serde(default)]
+    pub weights: BTreeMap<String, u32>,
+    /// Progression rules applied per upgrade (default: 1 for accumulation,
+    /// 2 for intensification, 0 for deload)
+    #[serde(default)]
+    pub progression_step: Option<u32>,
+}
+
+impl Phase {
+    /// Whether `date` falls in the phase
+    pub fn contains(&self, date: NaiveDate) -> bool {
+        self.start <= date && date <= self.end
+    }
+
+    /// Progression rules applied per upgrade during the phase
+    pub fn progression_step(&self) -> u32 {
+        self.progression_step
+            .unwrap_or_else(|| self.name.default_progression_step())
+    }
+
+    /// Short label, e.g. `deload 2026-11-01..2026-11-07`
+    pub fn label(&self) -> String {
+        format!("{} {}..{}", self.name.as_str(), self.start, self.end)
+    }
+}
+
+/// The phase covering `date`, if any
+pub fn current(phases: &[Phase], date: NaiveDate) -> Option<&Phase> {
+    phases.iter().find(|phase| phase.contains(date))
+}
+
+/// Sessions done during one phase
+#[derive(Clone, Debug, Serialize, PartialEq)]
+pub struct PhaseSummary {
+    pub name: PhaseKind,
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+    /// The phase covers today
+    pub current: bool,
+    pub sessions: usize,
+    pub total_seconds: u64,
+    /// Session count keyed by category name (`vo2`, …, or `unknown`)
+    pub by_category: BTreeMap<String, usize>,
+}
+
+/// Summaries of the phases overlapping the local dates from `since` to
+/// `today`, in date order
+pub fn summarize(
+    phases: &[Phase],
+    sessions: &[SessionKind],
+    catalog: &Catalog,
+    tz: &LocalTz,
+    since: NaiveDate,
+    today: NaiveDate,
+) -> Vec<PhaseSummary> {
+    let mut summaries: Vec<PhaseSummary> = phases
+        .iter()
+        .filter(|phase| phase.start <= today && phase.end >= since)
+        .map(|phase| {
+            let mut summary = PhaseSummary {
+                name: phase.name,
+                start: phase.start,
+                end: phase.end,
+                current: phase.contains(today),
+                sessions: 0,
+                total_seconds: 0,
+                by_category: BTreeMap::new(),
+            };
+            for session in sessions
+                .iter()
+                .filter_map(|s| s.as_real())
+                .filter(|s| phase.contains(tz.date_of(s.performed_at)))
+            {
+                summary.sessions += 1;
+                summary.total_seconds += u64::from(session.actual_duration_seconds.unwrap_or(0));
+                let category = catalog
+                    .definition(&session.definition_id)
+                    .map(|d| d.category.as_str())
+                    .unwrap_or("unknown");
+                *summary.by_category.entry(category.to_string()).or_insert(0) += 1;
+            }
+            summary
+        })
+        .collect();
+    summaries.sort_by_key(|summary| summary.start);
+    summaries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::build_default_catalog;
+    use chrono::{Duration, Utc};
+
+    fn phase(name: PhaseKind, start: NaiveDate, days: i64) -> Phase {
+        Phase {
+            name,
+            start,
+            end: start + Duration::days(days - 1),
+            weights: BTreeMap::new(),
+            progression_step: None,
+        }
+    }
+
+    #[test]
+    fn test_current_phase_and_step() {
+        let start = NaiveDate::from_ymd_opt(2026, 10, 1).unwrap();
+        let mut phases = vec![
+            phase(PhaseKind::Accumulation, start, 21),
+            phase(PhaseKind::Deload, start + Duration::days(21), 7),
+        ];
+
+        assert!(current(&phases, start - Duration::days(1)).is_none());
+        let first = current(&phases, start + Duration::days(20)).unwrap();
+        assert_eq!(first.name, PhaseKind::Accumulation);
+        assert_eq!(first.progression_step(), 1);
+        let deload = current(&phases, start + Duration::days(27)).unwrap();
+        assert_eq!(deload.progression_step(), 0);
+        assert_eq!(deload.label(), "deload 2026-10-22..2026-10-28");
+        assert!(current(&phases, start + Duration::days(28)).is_none());
+
+        phases[1].progression_step = Some(1);
+        assert_eq!(phases[1].progression_step(), 1);
+    }
+
+    #[test]
+    fn test_summarize_phases() {
+        let catalog = build_default_catalog();
+        let tz = LocalTz::from_name(Some("UTC")).unwrap();
+        let today = Utc::now().date_naive();
+        let session = |days_ago: i64| {
+            SessionKind::Real(crate::MicrodoseSession {
+                actual_duration_seconds: Some(300),
+                ..crate::MicrodoseSession::new(
+                    "emom_burpee_5m",
+                    Utc::now() - Duration::days(days_ago),
+                )
+            })
+        };
+        let phases = vec![
+            phase(PhaseKind::Deload, today - Duration::days(2), 7),
+            phase(PhaseKind::Accumulation, today - Duration::days(30), 28),
+            phase(PhaseKind::Intensification, today - Duration::days(90), 7),
+        ];
+        let sessions = vec![session(0), session(1), session(3), session(40)];
+
+        let summaries = summarize(
+            &phases,
+            &sessions,
+            &catalog,
+            &tz,
+            today - Duration::days(30),
+            today,
+        );
+        assert_eq!(summaries.len(), 2);
+        assert_eq!(summaries[0].name, PhaseKind::Accumulation);
+        assert_eq!(summaries[0].sessions, 1);
+        assert!(!summaries[0].current);
+        assert_eq!(summaries[1].name, PhaseKind::Deload);
+        assert_eq!(summaries[1].sessions, 2);
+        assert_eq!(summaries[1].total_seconds, 600);
+        assert_eq!(summaries[1].by_category["vo2"], 2);
+        assert!(summaries[1].current);
+    }
+}
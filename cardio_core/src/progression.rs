@@ -4,9 +4,18 @@
 //! - Burpees: Reps increase to ceiling, then style upgrades
 //! - KB swings: Linear rep progression with configurable max
 //! - Pullups: Rep progression (band selection is manual)
+//! - Ladders: The peak rung rises
+//!
+//! It also suggests autoregulation from per-set reps actually achieved,
+//! tracks AMRAP personal bests, spots plateaus and flags definitions that
+//! are ready to progress.
 
-use crate::{BurpeeStyle, Config, MovementStyle, ProgressionState, UserMicrodoseState};
-use chrono::Utc;
+use crate::progression_log::ProgressionEvent;
+use crate::{
+    BurpeeStyle, Config, MicrodoseSession, MovementStyle, ProgressionState, SessionKind,
+    UserMicrodoseState,
+};
+use chrono::{DateTime, Duration, Utc};
 
 /// Upgrade burpee intensity based on current state
 ///
@@ -14,12 +23,12 @@ use chrono::Utc;
 /// 1. Increase reps until ceiling (default 10)
 /// 2. Then upgrade style and reset reps
 /// 3. Style progression: 4-count → 6-count → 6-count-2-pump → seal
-pub fn upgrade_burpee(state: &mut ProgressionState, rep_ceiling: i32) {
+pub fn upgrade_burpee(state: &mut ProgressionState, rep_ceiling: i32, now: DateTime<Utc>) {
     // If we haven't hit the ceiling, just increment reps
     if state.reps < rep_ceiling {
         state.reps += 1;
         state.level += 1;
-        state.last_upgraded = Some(Utc::now());
+        state.last_upgraded = Some(now);
         tracing::debug!("Burpee progression: increased reps to {}", state.reps);
         return;
     }
@@ -39,7 +48,7 @@ pub fn upgrade_burpee(state: &mut ProgressionState, rep_ceiling: i32) {
             // Max level - just increase reps to ceiling
             state.reps = rep_ceiling;
             state.level += 1;
-            state.last_upgraded = Some(Utc::now());
+            state.last_upgraded = Some(now);
             tracing::debug!("Burpee progression: at max level (Seal @ {})", rep_ceiling);
             return;
         }
@@ -52,7 +61,7 @@ pub fn upgrade_burpee(state: &mut ProgressionState, rep_ceiling: i32) {
     state.style = new_style.clone();
     state.reps = new_reps;
     state.level += 1;
-    state.last_upgraded = Some(Utc::now());
+    state.last_upgraded = Some(now);
 
     tracing::debug!(
         "Burpee progression: upgraded style to {:?}, reset reps to {}",
@@ -64,11 +73,16 @@ pub fn upgrade_burpee(state: &mut ProgressionState, rep_ceiling: i32) {
 /// Upgrade KB swing intensity (simple linear progression)
 ///
 /// Progression: base_reps + level, capped at max_reps
-pub fn upgrade_kb_swing(state: &mut ProgressionState, base_reps: i32, max_reps: i32) {
+pub fn upgrade_kb_swing(
+    state: &mut ProgressionState,
+    base_reps: i32,
+    max_reps: i32,
+    now: DateTime<Utc>,
+) {
     if state.reps < max_reps {
         state.reps = (base_reps + state.level as i32 + 1).min(max_reps);
         state.level += 1;
-        state.last_upgraded = Some(Utc::now());
+        state.last_upgraded = Some(now);
         tracing::debug!("KB swing progression: increased to {} reps", state.reps);
     } else {
         tracing::debug!("KB swing progression: already at max ({} reps)", max_reps);
@@ -79,21 +93,168 @@ pub fn upgrade_kb_swing(state: &mut ProgressionState, base_reps: i32, max_reps:
 ///
 /// Progression: Increase reps up to a ceiling
 /// Band selection is manual (user decides when to reduce assistance)
-pub fn upgrade_pullup(state: &mut ProgressionState, max_reps: i32) {
+pub fn upgrade_pullup(state: &mut ProgressionState, max_reps: i32, now: DateTime<Utc>) {
     if state.reps < max_reps {
         state.reps += 1;
         state.level += 1;
-        state.last_upgraded = Some(Utc::now());
+        state.last_upgraded = Some(now);
         tracing::debug!("Pullup progression: increased to {} reps", state.reps);
     } else {
         tracing::debug!("Pullup progression: already at max ({} reps)", max_reps);
     }
 }
 
+/// Upgrade a rep ladder by raising its peak (1-2-3-2-1 → 1-2-3-4-3-2-1)
+///
+/// The rep count is the ladder peak, so one more rep extends the ladder
+/// by a rung each way.
+pub fn upgrade_ladder_peak(state: &mut ProgressionState, max_peak: i32, now: DateTime<Utc>) {
+    if state.reps < max_peak {
+        state.reps += 1;
+        state.level += 1;
+        state.last_upgraded = Some(now);
+        tracing::debug!("Ladder progression: peak raised to {} reps", state.reps);
+    } else {
+        tracing::debug!(
+            "Ladder progression: already at max peak ({} reps)",
+            max_peak
+        );
+    }
+}
+
+/// Definitions with progression rules in [`increase_intensity`]
+pub const PROGRESSIVE_DEFINITIONS: [&str; 4] = [
+    "emom_burpee_5m",
+    "emom_kb_swing_5m",
+    "gtg_pullup_band",
+    "gtg_pullup_ladder",
+];
+
+/// Days without a progression change before a regular definition plateaus
+pub const PLATEAU_DAYS: i64 = 14;
+
+/// Sessions within [`PLATEAU_DAYS`] needed before a plateau is reported
+pub const PLATEAU_MIN_SESSIONS: usize = 3;
+
+/// A definition done regularly without its progression changing
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Plateau {
+    pub definition_id: String,
+    /// Sessions in the last [`PLATEAU_DAYS`]
+    pub sessions: usize,
+    pub last_change: DateTime<Utc>,
+}
+
+/// Progressive definitions done at least [`PLATEAU_MIN_SESSIONS`] times in
+/// the last [`PLATEAU_DAYS`] whose latest progression-log event is older
+/// than that (definitions without any logged event are skipped)
+pub fn plateaus(
+    events: &[ProgressionEvent],
+    sessions: &[SessionKind],
+    now: DateTime<Utc>,
+) -> Vec<Plateau> {
+    let cutoff = now - Duration::days(PLATEAU_DAYS);
+    PROGRESSIVE_DEFINITIONS
+        .iter()
+        .filter_map(|def_id| {
+            let last_change = events
+                .iter()
+                .filter(|e| e.definition_id == *def_id)
+                .map(|e| e.changed_at)
+                .max()?;
+            let done = sessions
+                .iter()
+                .filter_map(|s| s.as_real())
+                .filter(|s| s.definition_id == *def_id && s.performed_at >= cutoff)
+                .count();
+            (last_change < cutoff && done >= PLATEAU_MIN_SESSIONS).then(|| Plateau {
+                definition_id: def_id.to_string(),
+                sessions: done,
+                last_change,
+            })
+        })
+        .collect()
+}
+
+/// Highest perceived RPE (1-10) that still counts as an easy session
+pub const READY_MAX_RPE: u8 = 6;
+
+/// Easy sessions in a row needed before progress is suggested
+pub const READY_MIN_SESSIONS: usize = 3;
+
+/// Days after an upgrade before progress is suggested again
+pub const READY_MIN_DAYS: i64 = 7;
+
+/// Whether a session at `target` reps felt easy
+///
+/// Every recorded signal must agree (RPE at most [`READY_MAX_RPE`], every
+/// set at or above target); a session with neither recorded doesn't count.
+fn felt_easy(session: &MicrodoseSession, target: Option<i32>) -> bool {
+    let low_rpe = session.perceived_rpe.map(|rpe| rpe <= READY_MAX_RPE);
+    let hit_target = match (session.reps_per_set(), target) {
+        (Some(per_set), Some(target)) if !per_set.is_empty() && target > 0 => {
+            Some(per_set.iter().all(|&reps| reps >= target))
+        }
+        _ => None,
+    };
+    match (low_rpe, hit_target) {
+        (None, None) => false,
+        (low_rpe, hit_target) => low_rpe.unwrap_or(true) && hit_target.unwrap_or(true),
+    }
+}
+
+/// Whether a progressive definition looks ready for more at `now`
+///
+/// True when it was last upgraded at least [`READY_MIN_DAYS`] ago (or never)
+/// and its last [`READY_MIN_SESSIONS`] sessions since then all felt easy at
+/// `target` reps (`None` = judge by RPE only, e.g. for ladders).
+pub fn ready_to_progress(
+    def_id: &str,
+    target: Option<i32>,
+    user_state: &UserMicrodoseState,
+    sessions: &[SessionKind],
+    now: DateTime<Utc>,
+) -> bool {
+    if !PROGRESSIVE_DEFINITIONS.contains(&def_id) {
+        return false;
+    }
+    let last_upgraded = user_state
+        .progressions
+        .get(def_id)
+        .and_then(|state| state.last_upgraded);
+    if last_upgraded.is_some_and(|at| now - at < Duration::days(READY_MIN_DAYS)) {
+        return false;
+    }
+
+    let mut since_upgrade: Vec<&MicrodoseSession> = sessions
+        .iter()
+        .filter_map(|s| s.as_real())
+        .filter(|s| {
+            s.definition_id == def_id
+                && s.performed_at <= now
+                && last_upgraded.is_none_or(|at| s.performed_at > at)
+        })
+        .collect();
+    since_upgrade.sort_by_key(|s| s.performed_at);
+    since_upgrade.len() >= READY_MIN_SESSIONS
+        && since_upgrade
+            .iter()
+            .rev()
+            .take(READY_MIN_SESSIONS)
+            .all(|s| felt_easy(s, target))
+}
+
 /// Upgrade intensity for a specific microdose definition
 ///
-/// This is the main entry point for progression upgrades.
-pub fn increase_intensity(def_id: &str, user_state: &mut UserMicrodoseState, config: &Config) {
+/// This is the main entry point for progression upgrades. A training phase
+/// can apply the definition's rule more than once, or not at all (deload);
+/// see [`Config::progression_step_at`]. `now` is the upgrade time.
+pub fn increase_intensity(
+    def_id: &str,
+    user_state: &mut UserMicrodoseState,
+    config: &Config,
+    now: DateTime<Utc>,
+) {
     // Get or create progression state
     let state = user_state
         .progressions
@@ -104,6 +265,7 @@ pub fn increase_intensity(def_id: &str, user_state: &mut UserMicrodoseState, con
                 "emom_burpee_5m" => (3, MovementStyle::Burpee(BurpeeStyle::FourCount)),
                 "emom_kb_swing_5m" => (5, MovementStyle::None),
                 "gtg_pullup_band" => (3, MovementStyle::None),
+                "gtg_pullup_ladder" => (3, MovementStyle::None),
                 _ => (3, MovementStyle::None),
             };
 
@@ -115,19 +277,30 @@ pub fn increase_intensity(def_id: &str, user_state: &mut UserMicrodoseState, con
             }
         });
 
-    // Apply progression rules based on definition ID
-    match def_id {
-        "emom_burpee_5m" => {
-            upgrade_burpee(state, config.progression.burpee_rep_ceiling);
-        }
-        "emom_kb_swing_5m" => {
-            upgrade_kb_swing(state, 5, config.progression.kb_swing_max_reps);
-        }
-        "gtg_pullup_band" => {
-            upgrade_pullup(state, 8);
-        }
-        _ => {
-            tracing::warn!("Unknown definition ID for progression: {}", def_id);
+    // Apply progression rules based on definition ID, once per step of the
+    // current training phase (none during a deload)
+    let steps = config.progression_step_at(now);
+    if steps == 0 {
+        tracing::info!("Deload phase: progression paused for {}", def_id);
+    }
+    for _ in 0..steps {
+        match def_id {
+            "emom_burpee_5m" => {
+                upgrade_burpee(state, config.progression.burpee_rep_ceiling, now);
+            }
+            "emom_kb_swing_5m" => {
+                upgrade_kb_swing(state, 5, config.progression.kb_swing_max_reps, now);
+            }
+            "gtg_pullup_band" => {
+                upgrade_pullup(state, 8, now);
+            }
+            "gtg_pullup_ladder" => {
+                upgrade_ladder_peak(state, 6, now);
+            }
+            _ => {
+                tracing::warn!("Unknown definition ID for progression: {}", def_id);
+                break;
+            }
         }
     }
 
@@ -139,10 +312,146 @@ pub fn increase_intensity(def_id: &str, user_state: &mut UserMicrodoseState, con
     );
 }
 
+/// A new AMRAP personal best
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PersonalBest {
+    pub total: i32,
+    /// Best before this one (`None` for the first recorded total)
+    pub previous: Option<i32>,
+}
+
+/// Record an AMRAP rep total
+///
+/// A total above the previous best becomes the new personal best and the
+/// definition's rep target (the number to beat), bumping its level.
+pub fn record_amrap(
+    def_id: &str,
+    total: i32,
+    user_state: &mut UserMicrodoseState,
+    now: DateTime<Utc>,
+) -> Option<PersonalBest> {
+    let previous = user_state.personal_bests.get(def_id).copied();
+    if total <= 0 || previous.is_some_and(|best| total <= best) {
+        return None;
+    }
+
+    user_state.personal_bests.insert(def_id.to_string(), total);
+    let state = user_state
+        .progressions
+        .entry(def_id.to_string())
+        .or_insert_with(|| ProgressionState {
+            reps: 0,
+            style: MovementStyle::None,
+            level: 0,
+            last_upgraded: None,
+        });
+    state.reps = total;
+    state.level += 1;
+    state.last_upgraded = Some(now);
+
+    tracing::info!(
+        "New AMRAP best for {}: {} reps (was {:?})",
+        def_id,
+        total,
+        previous
+    );
+    Some(PersonalBest { total, previous })
+}
+
+/// Total reps of work for a rep target
+///
+/// Unilateral (per-side) targets are performed on both sides, so the work
+/// is double the per-side count.
+pub fn total_work(reps: i32, per_side: bool) -> i32 {
+    if per_side {
+        reps * 2
+    } else {
+        reps
+    }
+}
+
+/// Suggested adjustment based on the reps actually achieved in each set
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Autoregulation {
+    /// Every set hit the target - ready for more
+    Progress,
+    /// Close to target - repeat the same prescription
+    Hold,
+    /// Several sets fell well short - back off
+    Regress,
+}
+
+/// Compare per-set reps against the prescribed target
+///
+/// Rules:
+/// - All sets at or above target → progress
+/// - Two or more sets more than one rep short → regress
+/// - Otherwise → hold
+///
+/// Returns `None` when no sets were recorded or the target is unknown.
+pub fn autoregulate(target_reps: i32, per_set: &[i32]) -> Option<Autoregulation> {
+    if per_set.is_empty() || target_reps <= 0 {
+        return None;
+    }
+
+    if per_set.iter().all(|&reps| reps >= target_reps) {
+        return Some(Autoregulation::Progress);
+    }
+
+    let well_short = per_set
+        .iter()
+        .filter(|&&reps| reps < target_reps - 1)
+        .count();
+    if well_short >= 2 {
+        Some(Autoregulation::Regress)
+    } else {
+        Some(Autoregulation::Hold)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_ladder_peak_progression() {
+        let mut state = ProgressionState {
+            reps: 5,
+            style: MovementStyle::None,
+            level: 2,
+            last_upgraded: None,
+        };
+        upgrade_ladder_peak(&mut state, 6, Utc::now());
+        assert_eq!(state.reps, 6);
+        assert_eq!(state.level, 3);
+
+        // Capped at the max peak
+        upgrade_ladder_peak(&mut state, 6, Utc::now());
+        assert_eq!(state.reps, 6);
+        assert_eq!(state.level, 3);
+    }
+
+    #[test]
+    fn test_record_amrap_personal_bests() {
+        let mut user_state = UserMicrodoseState::default();
+        let now = Utc::now();
+        let id = "core_amrap_mountain_climber_2m";
+
+        let first = record_amrap(id, 40, &mut user_state, now).unwrap();
+        assert_eq!(first.previous, None);
+        assert_eq!(user_state.progressions[id].reps, 40);
+
+        // Matching or missing the best is not a PR
+        assert!(record_amrap(id, 40, &mut user_state, now).is_none());
+        assert!(record_amrap(id, 35, &mut user_state, now).is_none());
+        assert!(record_amrap(id, 0, &mut user_state, now).is_none());
+
+        let second = record_amrap(id, 46, &mut user_state, now).unwrap();
+        assert_eq!(second.previous, Some(40));
+        assert_eq!(user_state.personal_bests[id], 46);
+        assert_eq!(user_state.progressions[id].level, 2);
+    }
+
     #[test]
     fn test_burpee_reps_progression() {
         let mut state = ProgressionState {
@@ -154,7 +463,7 @@ mod tests {
 
         // Should increase reps until ceiling
         for expected_reps in 4..=10 {
-            upgrade_burpee(&mut state, 10);
+            upgrade_burpee(&mut state, 10, Utc::now());
             assert_eq!(state.reps, expected_reps);
         }
     }
@@ -169,7 +478,7 @@ mod tests {
         };
 
         // At ceiling, should upgrade to 6-count
-        upgrade_burpee(&mut state, 10);
+        upgrade_burpee(&mut state, 10, Utc::now());
         assert!(matches!(
             state.style,
             MovementStyle::Burpee(BurpeeStyle::SixCount)
@@ -188,30 +497,30 @@ mod tests {
 
         // Progress through all styles
         for _ in 0..7 {
-            upgrade_burpee(&mut state, 10);
+            upgrade_burpee(&mut state, 10, Utc::now());
         } // 4-count at 10
 
-        upgrade_burpee(&mut state, 10); // Should upgrade to 6-count
+        upgrade_burpee(&mut state, 10, Utc::now()); // Should upgrade to 6-count
         assert!(matches!(
             state.style,
             MovementStyle::Burpee(BurpeeStyle::SixCount)
         ));
 
         for _ in 0..4 {
-            upgrade_burpee(&mut state, 10);
+            upgrade_burpee(&mut state, 10, Utc::now());
         } // 6-count at 10
 
-        upgrade_burpee(&mut state, 10); // Should upgrade to 6-count-2-pump
+        upgrade_burpee(&mut state, 10, Utc::now()); // Should upgrade to 6-count-2-pump
         assert!(matches!(
             state.style,
             MovementStyle::Burpee(BurpeeStyle::SixCountTwoPump)
         ));
 
         for _ in 0..5 {
-            upgrade_burpee(&mut state, 10);
+            upgrade_burpee(&mut state, 10, Utc::now());
         } // 6-count-2-pump at 10
 
-        upgrade_burpee(&mut state, 10); // Should upgrade to Seal
+        upgrade_burpee(&mut state, 10, Utc::now()); // Should upgrade to Seal
         assert!(matches!(
             state.style,
             MovementStyle::Burpee(BurpeeStyle::Seal)
@@ -227,11 +536,11 @@ mod tests {
             last_upgraded: None,
         };
 
-        upgrade_kb_swing(&mut state, 5, 15);
+        upgrade_kb_swing(&mut state, 5, 15, Utc::now());
         assert_eq!(state.reps, 6);
         assert_eq!(state.level, 1);
 
-        upgrade_kb_swing(&mut state, 5, 15);
+        upgrade_kb_swing(&mut state, 5, 15, Utc::now());
         assert_eq!(state.reps, 7);
         assert_eq!(state.level, 2);
     }
@@ -245,11 +554,11 @@ mod tests {
             last_upgraded: None,
         };
 
-        upgrade_kb_swing(&mut state, 5, 15);
+        upgrade_kb_swing(&mut state, 5, 15, Utc::now());
         assert_eq!(state.reps, 15);
 
         // Should not exceed max
-        upgrade_kb_swing(&mut state, 5, 15);
+        upgrade_kb_swing(&mut state, 5, 15, Utc::now());
         assert_eq!(state.reps, 15);
     }
 
@@ -263,12 +572,12 @@ mod tests {
         };
 
         for expected_reps in 4..=8 {
-            upgrade_pullup(&mut state, 8);
+            upgrade_pullup(&mut state, 8, Utc::now());
             assert_eq!(state.reps, expected_reps);
         }
 
         // Should not exceed max
-        upgrade_pullup(&mut state, 8);
+        upgrade_pullup(&mut state, 8, Utc::now());
         assert_eq!(state.reps, 8);
     }
 
@@ -276,12 +585,177 @@ mod tests {
     fn test_increase_intensity_creates_state() {
         let mut user_state = UserMicrodoseState::default();
         let config = Config::default();
+        let now = DateTime::parse_from_rfc3339("2026-03-02T07:30:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
 
-        increase_intensity("emom_burpee_5m", &mut user_state, &config);
+        increase_intensity("emom_burpee_5m", &mut user_state, &config, now);
 
         assert!(user_state.progressions.contains_key("emom_burpee_5m"));
         let state = &user_state.progressions["emom_burpee_5m"];
         assert_eq!(state.reps, 4); // Started at 3, increased to 4
         assert_eq!(state.level, 1);
+        assert_eq!(state.last_upgraded, Some(now));
+    }
+
+    #[test]
+    fn test_increase_intensity_follows_phase() {
+        let today = Utc::now().date_naive();
+        let mut config = Config::default();
+        config.time.timezone = Some("UTC".into());
+        config.phases = vec![crate::phase::Phase {
+            name: crate::phase::PhaseKind::Intensification,
+            start: today - Duration::days(1),
+            end: today + Duration::days(1),
+            weights: Default::default(),
+            progression_step: None,
+        }];
+
+        let mut user_state = UserMicrodoseState::default();
+        increase_intensity("emom_burpee_5m", &mut user_state, &config, Utc::now());
+        assert_eq!(user_state.progressions["emom_burpee_5m"].reps, 5);
+
+        config.phases[0].name = crate::phase::PhaseKind::Deload;
+        increase_intensity("emom_burpee_5m", &mut user_state, &config, Utc::now());
+        assert_eq!(user_state.progressions["emom_burpee_5m"].reps, 5);
+        assert_eq!(user_state.progressions["emom_burpee_5m"].level, 2);
+    }
+
+    #[test]
+    fn test_autoregulate() {
+        assert_eq!(
+            autoregulate(5, &[5, 5, 6, 5, 5]),
+            Some(Autoregulation::Progress)
+        );
+        assert_eq!(
+            autoregulate(5, &[5, 5, 5, 4, 4]),
+            Some(Autoregulation::Hold)
+        );
+        assert_eq!(
+            autoregulate(5, &[5, 5, 5, 4, 3]),
+            Some(Autoregulation::Hold)
+        );
+        assert_eq!(
+            autoregulate(5, &[5, 4, 3, 3, 2]),
+            Some(Autoregulation::Regress)
+        );
+        assert_eq!(autoregulate(5, &[]), None);
+        assert_eq!(autoregulate(0, &[3, 3]), None);
+    }
+
+    #[test]
+    fn test_plateaus() {
+        let now = Utc::now();
+        let event = |def_id: &str, days_ago: i64| ProgressionEvent {
+            changed_at: now - Duration::days(days_ago),
+            definition_id: def_id.into(),
+            reps: 3,
+            level: 0,
+            style: MovementStyle::None,
+        };
+        let session = |def_id: &str, days_ago: i64| {
+            SessionKind::Real(crate::MicrodoseSession {
+                actual_duration_seconds: Some(300),
+                ..crate::MicrodoseSession::new(def_id, now - Duration::days(days_ago))
+            })
+        };
+
+        let events = vec![
+            event("emom_burpee_5m", 30),
+            event("emom_kb_swing_5m", 3),
+            event("gtg_pullup_band", 40),
+            event("mobility_hip_cars", 40),
+        ];
+        let mut sessions = Vec::new();
+        for days_ago in [1, 4, 9] {
+            sessions.push(session("emom_burpee_5m", days_ago));
+            sessions.push(session("emom_kb_swing_5m", days_ago));
+            sessions.push(session("mobility_hip_cars", days_ago));
+        }
+        // Only two recent pull-up sessions
+        sessions.push(session("gtg_pullup_band", 2));
+        sessions.push(session("gtg_pullup_band", 20));
+        sessions.push(session("gtg_pullup_band", 5));
+
+        let found = plateaus(&events, &sessions, Utc::now());
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].definition_id, "emom_burpee_5m");
+        assert_eq!(found[0].sessions, 3);
+    }
+
+    #[test]
+    fn test_ready_to_progress() {
+        let now = Utc::now();
+        let session = |days_ago: i64, rpe: Option<u8>, per_set: Vec<i32>| {
+            SessionKind::Real(crate::MicrodoseSession {
+                actual_duration_seconds: Some(300),
+                metrics_realized: if per_set.is_empty() {
+                    vec![]
+                } else {
+                    vec![crate::RealizedMetric::Reps {
+                        key: "reps".into(),
+                        per_set,
+                    }]
+                },
+                perceived_rpe: rpe,
+                ..crate::MicrodoseSession::new("emom_burpee_5m", now - Duration::days(days_ago))
+            })
+        };
+        let mut state = UserMicrodoseState::default();
+        let mut sessions = vec![
+            session(1, Some(5), vec![]),
+            session(2, None, vec![5, 5, 5, 5, 5]),
+            session(3, Some(4), vec![5, 6, 5, 5, 5]),
+        ];
+        let ready = |state: &UserMicrodoseState, sessions: &[SessionKind]| {
+            ready_to_progress("emom_burpee_5m", Some(5), state, sessions, now)
+        };
+        assert!(ready(&state, &sessions));
+
+        // Not for definitions without progression rules
+        assert!(!ready_to_progress(
+            "mobility_hip_cars",
+            None,
+            &state,
+            &sessions,
+            now
+        ));
+
+        // A missed set or a hard session in the last three holds it back
+        sessions.push(session(0, Some(8), vec![]));
+        assert!(!ready(&state, &sessions));
+        sessions.pop();
+        sessions.push(session(0, None, vec![5, 5, 4, 5, 5]));
+        assert!(!ready(&state, &sessions));
+        sessions.pop();
+
+        // Sessions with nothing recorded say nothing either way
+        sessions.push(session(0, None, vec![]));
+        assert!(!ready(&state, &sessions));
+        sessions.pop();
+
+        // Not within a week of the last upgrade
+        state.progressions.insert(
+            "emom_burpee_5m".into(),
+            ProgressionState {
+                reps: 5,
+                style: MovementStyle::None,
+                level: 1,
+                last_upgraded: Some(now - Duration::days(5)),
+            },
+        );
+        assert!(!ready(&state, &sessions));
+
+        state
+            .progressions
+            .get_mut("emom_burpee_5m")
+            .unwrap()
+            .last_upgraded = Some(now - Duration::days(10));
+        assert!(ready(&state, &sessions));
+
+        // Only sessions since the upgrade count
+        sessions.remove(0);
+        sessions.push(session(12, Some(3), vec![]));
+        assert!(!ready(&state, &sessions));
     }
 }
@@ -0,0 +1,186 @@
+//! Progression audit log.
+//!
+//! Every change to a definition's [`ProgressionState`](crate::ProgressionState)
+//! (first session, "harder", AMRAP targets, …) is appended to its own JSONL
+//! file (`progression.jsonl` in the data directory) so trends can be charted
+//! later; `state.json` only keeps the latest values. Diffing is pure; file
+//! access requires the `fs` feature.
+
+#[cfg(feature = "fs")]
+use crate::Result;
+use crate::{MovementStyle, UserMicrodoseState};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "fs")]
+use std::fs::{File, OpenOptions};
+#[cfg(feature = "fs")]
+use std::io::{BufRead, BufReader, Write};
+#[cfg(feature = "fs")]
+use std::path::Path;
+
+/// File name of the progression log within the data directory
+pub const PROGRESSION_LOG_FILE: &str = "progression.jsonl";
+
+/// A definition's progression after a change
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct ProgressionEvent {
+    pub changed_at: DateTime<Utc>,
+    pub definition_id: String,
+    pub reps: i32,
+    pub level: u32,
+    pub style: MovementStyle,
+}
+
+/// Events for progressions that are new or changed between two states
+///
+/// Ordered by definition ID so the log is stable across runs.
+pub fn changes(
+    before: &UserMicrodoseState,
+    after: &UserMicrodoseState,
+    now: DateTime<Utc>,
+) -> Vec<ProgressionEvent> {
+    let mut events: Vec<ProgressionEvent> = after
+        .progressions
+        .iter()
+        .filter(|(def_id, state)| {
+            before.progressions.get(*def_id).is_none_or(|old| {
+                old.reps != state.reps || old.level != state.level || old.style != state.style
+            })
+        })
+        .map(|(def_id, state)| ProgressionEvent {
+            changed_at: now,
+            definition_id: def_id.clone(),
+            reps: state.reps,
+            level: state.level,
+            style: state.style.clone(),
+        })
+        .collect();
+    events.sort_by(|a, b| a.definition_id.cmp(&b.definition_id));
+    events
+}
+
+/// Append events to the progression log (with an exclusive lock, taken
+/// within `timeout`)
+#[cfg(feature = "fs")]
+pub fn append_events(
+    path: &Path,
+    events: &[ProgressionEvent],
+    timeout: std::time::Duration,
+) -> Result<()> {
+    if events.is_empty() {
+        return Ok(());
+    }
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    crate::lock::lock_exclusive_within(&file, path, timeout)?;
+
+    let mut writer = std::io::BufWriter::new(&file);
+    for event in events {
+        let line = serde_json::to_string(event)?;
+        writer.write_all(line.as_bytes())?;
+        writer.write_all(b"\n")?;
+    }
+    writer.flush()?;
+
+    fs2::FileExt::unlock(&file)?;
+    tracing::debug!("Logged {} progression change(s)", events.len());
+    Ok(())
+}
+
+/// Read all progression events, skipping malformed lines (with a shared
+/// lock, taken within `timeout`)
+#[cfg(feature = "fs")]
+pub fn read_events(path: &Path, timeout: std::time::Duration) -> Result<Vec<ProgressionEvent>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let file = File::open(path)?;
+    crate::lock::lock_shared_within(&file, path, timeout)?;
+
+    let mut events = Vec::new();
+    for (line_num, line) in BufReader::new(&file).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<ProgressionEvent>(&line) {
+            Ok(event) => events.push(event),
+            Err(e) => {
+                tracing::warn!(
+                    "Failed to parse progression event at line {}: {}",
+                    line_num + 1,
+                    e
+                );
+            }
+        }
+    }
+
+    fs2::FileExt::unlock(&file)?;
+    Ok(events)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ProgressionState;
+
+    fn state(reps: i32, level: u32) -> ProgressionState {
+        ProgressionState {
+            reps,
+            style: MovementStyle::None,
+            level,
+            last_upgraded: None,
+        }
+    }
+
+    #[test]
+    fn test_changes_only_new_or_changed() {
+        let mut before = UserMicrodoseState::default();
+        before.progressions.insert("a".into(), state(3, 0));
+        before.progressions.insert("b".into(), state(5, 1));
+
+        let mut after = before.clone();
+        after.progressions.insert("b".into(), state(6, 2));
+        after.progressions.insert("c".into(), state(1, 0));
+
+        let now = Utc::now();
+        let events = changes(&before, &after, now);
+        let ids: Vec<&str> = events.iter().map(|e| e.definition_id.as_str()).collect();
+        assert_eq!(ids, vec!["b", "c"]);
+        assert_eq!(events[0].reps, 6);
+        assert_eq!(events[0].level, 2);
+        assert!(changes(&after, &after, now).is_empty());
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn test_append_and_read_roundtrip() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join(PROGRESSION_LOG_FILE);
+        assert!(read_events(&path, crate::lock::DEFAULT_TIMEOUT)
+            .unwrap()
+            .is_empty());
+
+        let before = UserMicrodoseState::default();
+        let mut after = before.clone();
+        after.progressions.insert("a".into(), state(3, 0));
+        let events = changes(&before, &after, Utc::now());
+
+        append_events(&path, &events, crate::lock::DEFAULT_TIMEOUT).unwrap();
+        append_events(&path, &[], crate::lock::DEFAULT_TIMEOUT).unwrap();
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .and_then(|mut f| f.write_all(b"not json\n"))
+            .unwrap();
+
+        assert_eq!(
+            read_events(&path, crate::lock::DEFAULT_TIMEOUT).unwrap(),
+            events
+        );
+    }
+}
@@ -0,0 +1,172 @@
+//! External readiness (HRV) signal loader.
+//!
+//! Like the strength signal, readiness comes from another tool (a watch or
+//! ring export) that writes `readiness.json` in the data directory:
+//!
+//! ```json
+//! { "measured_at": "2024-01-15T06:30:00Z", "score": 72, "resting_hr": 54, "sleep_hours": 7.5 }
+//! ```
+//!
+//! Every field but `measured_at` is optional. A missing or unreadable file
+//! means no signal, and implausible values are dropped, so a broken export
+//! never stops a prescription. The signal is carried in
+//! [`UserContext::readiness`](crate::UserContext) for rules to use;
+//! `krep now --dry-run` prints it to check the pipeline.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// File name of the readiness signal within the data directory
+pub const READINESS_FILE: &str = "readiness.json";
+
+/// Plausible resting heart rates in bpm
+const RESTING_HR_RANGE: std::ops::RangeInclusive<u32> = 25..=220;
+
+/// Readiness reported by an external system
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ReadinessSignal {
+    #[serde(alias = "at")]
+    pub measured_at: DateTime<Utc>,
+    /// Readiness or HRV score, 0-100
+    #[serde(default)]
+    pub score: Option<u32>,
+    /// Resting heart rate in bpm
+    #[serde(default)]
+    pub resting_hr: Option<u32>,
+    /// Hours slept the night before
+    #[serde(default)]
+    pub sleep_hours: Option<f64>,
+}
+
+impl ReadinessSignal {
+    /// Summary such as "score 72, resting HR 54 bpm, 7.5h sleep"
+    pub fn label(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(score) = self.score {
+            parts.push(format!("score {}", score));
+        }
+        if let Some(hr) = self.resting_hr {
+            parts.push(format!("resting HR {} bpm", hr));
+        }
+        if let Some(hours) = self.sleep_hours {
+            parts.push(format!("{:.1}h sleep", hours));
+        }
+        if parts.is_empty() {
+            "no measurements".to_string()
+        } else {
+            parts.join(", ")
+        }
+    }
+
+    /// Drop values no device reports, warning about each
+    fn without_implausible(mut self, path: &Path) -> Self {
+        if self.score.is_some_and(|s| s > 100) {
+            tracing::warn!("Ignoring readiness score {:?} in {:?}", self.score, path);
+            self.score = None;
+        }
+        if self
+            .resting_hr
+            .is_some_and(|hr| !RESTING_HR_RANGE.contains(&hr))
+        {
+            tracing::warn!("Ignoring resting HR {:?} in {:?}", self.resting_hr, path);
+            self.resting_hr = None;
+        }
+        if self
+            .sleep_hours
+            .is_some_and(|h| !h.is_finite() || !(0.0..=24.0).contains(&h))
+        {
+            tracing::warn!("Ignoring sleep hours {:?} in {:?}", self.sleep_hours, path);
+            self.sleep_hours = None;
+        }
+        self
+    }
+}
+
+/// Load the readiness signal from a JSON file
+///
+/// Returns None if the file doesn't exist or can't be read or parsed (the
+/// problem is logged).
+pub fn load_readiness(path: &Path) -> Option<ReadinessSignal> {
+    if !path.exists() {
+        tracing::debug!("No readiness file found at {:?}", path);
+        return None;
+    }
+
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) => {
+            tracing::warn!(
+                "Failed to read readiness at {:?}: {}. Ignoring signal.",
+                path,
+                e
+            );
+            return None;
+        }
+    };
+
+    match serde_json::from_str::<ReadinessSignal>(&contents) {
+        Ok(signal) => {
+            tracing::info!("Loaded readiness from {:?}", path);
+            Some(signal.without_implausible(path))
+        }
+        Err(e) => {
+            tracing::warn!(
+                "Failed to parse readiness at {:?}: {}. Ignoring signal.",
+                path,
+                e
+            );
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_readiness() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join(READINESS_FILE);
+
+        let json = r#"{
+            "measured_at": "2024-01-15T06:30:00Z",
+            "score": 72,
+            "resting_hr": 54,
+            "sleep_hours": 7.5
+        }"#;
+        std::fs::write(&path, json).unwrap();
+
+        let signal = load_readiness(&path).unwrap();
+        assert_eq!(signal.score, Some(72));
+        assert_eq!(signal.label(), "score 72, resting HR 54 bpm, 7.5h sleep");
+    }
+
+    #[test]
+    fn test_missing_fields_and_implausible_values() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join(READINESS_FILE);
+
+        std::fs::write(&path, r#"{"at": "2024-01-15T06:30:00Z", "resting_hr": 52}"#).unwrap();
+        let signal = load_readiness(&path).unwrap();
+        assert_eq!((signal.score, signal.resting_hr), (None, Some(52)));
+
+        std::fs::write(
+            &path,
+            r#"{"measured_at": "2024-01-15T06:30:00Z", "score": 250, "resting_hr": 0, "sleep_hours": 30}"#,
+        )
+        .unwrap();
+        assert_eq!(load_readiness(&path).unwrap().label(), "no measurements");
+    }
+
+    #[test]
+    fn test_missing_or_corrupt_file_is_no_signal() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join(READINESS_FILE);
+        assert!(load_readiness(&path).is_none());
+
+        std::fs::write(&path, "{ \"score\": 7").unwrap();
+        assert!(load_readiness(&path).is_none());
+    }
+}
@@ -0,0 +1,106 @@
+//! Reference video links.
+//!
+//! Catalog reference URLs are mostly YouTube videos. These helpers pull out
+//! the video ID so frontends can show a thumbnail or an embedded player
+//! instead of a bare link; other links are left alone.
+
+use std::path::{Path, PathBuf};
+
+/// Length of a YouTube video ID
+const VIDEO_ID_LEN: usize = 11;
+
+/// Video ID of a YouTube link (`watch?v=`, `youtu.be/`, `/shorts/`, `/embed/`)
+pub fn youtube_id(url: &str) -> Option<&str> {
+    let rest = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))?;
+    let (host, path) = rest.split_once('/')?;
+    let host = host.strip_prefix("www.").unwrap_or(host);
+    let host = host.strip_prefix("m.").unwrap_or(host);
+
+    let candidate = match host {
+        "youtu.be" => path,
+        "youtube.com" | "youtube-nocookie.com" => {
+            if let Some(query) = path.strip_prefix("watch?") {
+                query
+                    .split('&')
+                    .find_map(|param| param.strip_prefix("v="))?
+            } else {
+                path.strip_prefix("shorts/")
+                    .or_else(|| path.strip_prefix("embed/"))?
+            }
+        }
+        _ => return None,
+    };
+    let id = candidate
+        .split(['?', '&', '#', '/'])
+        .next()
+        .unwrap_or_default();
+    let valid = id.len() == VIDEO_ID_LEN
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    valid.then_some(id)
+}
+
+/// Thumbnail image for a YouTube link
+pub fn thumbnail_url(url: &str) -> Option<String> {
+    youtube_id(url).map(|id| format!("https://img.youtube.com/vi/{}/hqdefault.jpg", id))
+}
+
+/// Embeddable player page for a YouTube link
+pub fn embed_url(url: &str) -> Option<String> {
+    youtube_id(url).map(|id| format!("https://www.youtube-nocookie.com/embed/{}", id))
+}
+
+/// Where a link's thumbnail is cached under `cache_dir`
+pub fn thumbnail_cache_path(cache_dir: &Path, url: &str) -> Option<PathBuf> {
+    youtube_id(url).map(|id| cache_dir.join("thumbnails").join(format!("{}.jpg", id)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_youtube_id_forms() {
+        for url in [
+            "https://www.youtube.com/watch?v=YSxHifyI6s8",
+            "https://youtube.com/watch?feature=share&v=YSxHifyI6s8",
+            "https://m.youtube.com/watch?v=YSxHifyI6s8&t=42s",
+            "https://youtu.be/YSxHifyI6s8?t=10",
+            "https://www.youtube.com/shorts/YSxHifyI6s8",
+            "http://www.youtube-nocookie.com/embed/YSxHifyI6s8",
+        ] {
+            assert_eq!(youtube_id(url), Some("YSxHifyI6s8"), "{}", url);
+        }
+
+        for url in [
+            "https://vimeo.com/123456789",
+            "https://www.youtube.com/watch?v=short",
+            "https://www.youtube.com/channel/UC1234567890",
+            "https://example.com/youtube.com/watch?v=YSxHifyI6s8",
+            "youtube.com/watch?v=YSxHifyI6s8",
+        ] {
+            assert_eq!(youtube_id(url), None, "{}", url);
+        }
+    }
+
+    #[test]
+    fn test_derived_urls() {
+        let url = "https://youtu.be/YSxHifyI6s8";
+        assert_eq!(
+            thumbnail_url(url).as_deref(),
+            Some("https://img.youtube.com/vi/YSxHifyI6s8/hqdefault.jpg")
+        );
+        assert_eq!(
+            embed_url(url).as_deref(),
+            Some("https://www.youtube-nocookie.com/embed/YSxHifyI6s8")
+        );
+        assert_eq!(
+            thumbnail_cache_path(Path::new("/cache/krep"), url),
+            Some(PathBuf::from("/cache/krep/thumbnails/YSxHifyI6s8.jpg"))
+        );
+        assert_eq!(thumbnail_url("https://example.com/video"), None);
+    }
+}
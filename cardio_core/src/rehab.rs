@@ -0,0 +1,217 @@
+//! Rehab mode: mobility only while an injury heals.
+//!
+//! `krep mode rehab on --injury <what>` opens a [`RehabPeriod`] in
+//! [`UserMicrodoseState::rehab`](crate::UserMicrodoseState). While it is
+//! active the engine only prescribes mobility microdoses, plus any whose
+//! movements have all been cleared, and says so in the prescription's
+//! rationale. Periods are kept after they end so stats can report the
+//! sessions done during each one separately.
+
+use crate::{Catalog, MicrodoseCategory, MicrodoseDefinition, RehabPeriod, SessionKind};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// The period active at `now`, if any
+pub fn active(periods: &[RehabPeriod], now: DateTime<Utc>) -> Option<&RehabPeriod> {
+    periods.last().filter(|period| period.contains(now))
+}
+
+/// Start rehab for `injury` at `now`
+///
+/// Re-running for the injury already in rehab updates its end and cleared
+/// movements; a different injury ends the active period and starts a new one.
+pub fn start(
+    periods: &mut Vec<RehabPeriod>,
+    injury: &str,
+    now: DateTime<Utc>,
+    until: Option<DateTime<Utc>>,
+    cleared_movements: Vec<String>,
+) {
+    if active(periods, now).is_some() {
+        let current = periods.last_mut().expect("active period exists");
+        if current.injury == injury {
+            current.until = until;
+            current.cleared_movements = cleared_movements;
+            return;
+        }
+        current.cleared_at = Some(now);
+    }
+    periods.push(RehabPeriod {
+        injury: injury.to_string(),
+        started_at: now,
+        until,
+        cleared_at: None,
+        cleared_movements,
+    });
+}
+
+/// End the active period at `now`, returning it (`None` if none was active)
+pub fn clear(periods: &mut [RehabPeriod], now: DateTime<Utc>) -> Option<&RehabPeriod> {
+    active(periods, now)?;
+    let current = periods.last_mut()?;
+    current.cleared_at = Some(now);
+    Some(current)
+}
+
+/// Whether `definition` may be prescribed during `period`
+///
+/// Mobility always may; anything else only when every block's movement
+/// has been cleared.
+pub fn allows(definition: &MicrodoseDefinition, period: &RehabPeriod) -> bool {
+    definition.category == MicrodoseCategory::Mobility
+        || definition
+            .blocks
+            .iter()
+            .all(|block| period.cleared_movements.contains(&block.movement_id))
+}
+
+/// Copy of the catalog with only the microdoses `period` allows
+pub fn restrict(catalog: &Catalog, period: &RehabPeriod) -> Catalog {
+    catalog.filtered(
+        catalog
+            .definitions()
+            .filter(|definition| allows(definition, period)),
+    )
+}
+
+/// Explanation shown with prescriptions during `period`
+pub fn rationale(period: &RehabPeriod) -> String {
+    let mut text = format!("Rehab mode ({}): mobility only", period.injury);
+    if !period.cleared_movements.is_empty() {
+        text.push_str(&format!(
+            " plus cleared {}",
+            period.cleared_movements.join(", ")
+        ));
+    }
+    text
+}
+
+/// Sessions done during one rehab period
+#[derive(Clone, Debug, Serialize, PartialEq)]
+pub struct RehabSummary {
+    pub injury: String,
+    pub started_at: DateTime<Utc>,
+    /// `None` while the period is still open
+    pub ended_at: Option<DateTime<Utc>>,
+    pub sessions: usize,
+    pub total_seconds: u64,
+    /// Session count keyed by category name (`mobility`, …, or `unknown`)
+    pub by_category: BTreeMap<String, usize>,
+}
+
+/// Summaries of the periods overlapping the window from `since` to `now`,
+/// oldest first
+pub fn summarize(
+    periods: &[RehabPeriod],
+    sessions: &[SessionKind],
+    catalog: &Catalog,
+    since: DateTime<Utc>,
+    now: DateTime<Utc>,
+) -> Vec<RehabSummary> {
+    periods
+        .iter()
+        .filter(|period| period.started_at <= now && period.end().is_none_or(|end| end > since))
+        .map(|period| {
+            let mut summary = RehabSummary {
+                injury: period.injury.clone(),
+                started_at: period.started_at,
+                ended_at: period.end().filter(|end| *end <= now),
+                sessions: 0,
+                total_seconds: 0,
+                by_category: BTreeMap::new(),
+            };
+            for session in sessions
+                .iter()
+                .filter_map(|s| s.as_real())
+                .filter(|s| period.contains(s.performed_at))
+            {
+                summary.sessions += 1;
+                summary.total_seconds += u64::from(session.actual_duration_seconds.unwrap_or(0));
+                let category = catalog
+                    .definition(&session.definition_id)
+                    .map(|d| d.category.as_str())
+                    .unwrap_or("unknown");
+                *summary.by_category.entry(category.to_string()).or_insert(0) += 1;
+            }
+            summary
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::build_default_catalog;
+    use chrono::Duration;
+
+    #[test]
+    fn test_rehab_period_lifecycle() {
+        let now = Utc::now();
+        let mut periods = Vec::new();
+        assert!(active(&periods, now).is_none());
+
+        start(
+            &mut periods,
+            "left knee",
+            now,
+            Some(now + Duration::days(14)),
+            vec![],
+        );
+        assert_eq!(active(&periods, now).unwrap().injury, "left knee");
+        assert!(active(&periods, now + Duration::days(14)).is_none());
+
+        // Same injury: updated in place
+        start(
+            &mut periods,
+            "left knee",
+            now,
+            None,
+            vec!["stair_walk".into()],
+        );
+        assert_eq!(periods.len(), 1);
+        assert_eq!(periods[0].until, None);
+
+        // Another injury: the knee period ends, the shoulder one starts
+        let later = now + Duration::days(3);
+        start(&mut periods, "shoulder", later, None, vec![]);
+        assert_eq!(periods.len(), 2);
+        assert_eq!(periods[0].end(), Some(later));
+
+        assert_eq!(
+            clear(&mut periods, later + Duration::days(1))
+                .unwrap()
+                .injury,
+            "shoulder"
+        );
+        assert!(active(&periods, later + Duration::days(2)).is_none());
+        assert!(clear(&mut periods, later + Duration::days(2)).is_none());
+    }
+
+    #[test]
+    fn test_restrict_keeps_mobility_and_cleared() {
+        let catalog = build_default_catalog();
+        let mut period = RehabPeriod {
+            injury: "knee".into(),
+            started_at: Utc::now(),
+            until: None,
+            cleared_at: None,
+            cleared_movements: vec![],
+        };
+
+        let restricted = restrict(&catalog, &period);
+        assert!(!restricted.microdoses().is_empty());
+        assert!(restricted
+            .microdoses()
+            .values()
+            .all(|d| d.category == MicrodoseCategory::Mobility));
+
+        period.cleared_movements = vec!["box_breathing".into()];
+        let restricted = restrict(&catalog, &period);
+        assert!(restricted
+            .microdoses()
+            .values()
+            .any(|d| d.category == MicrodoseCategory::Breathwork));
+        assert!(rationale(&period).contains("plus cleared box_breathing"));
+    }
+}
@@ -0,0 +1,420 @@
+//! Weekly summary reports (`krep report --week`).
+//!
+//! [`weekly_report`] gathers the numbers from loaded history and the
+//! progression log; [`WeeklyReport::to_markdown`] and
+//! [`WeeklyReport::to_html`] render them for a journal or an email.
+
+use crate::progression::{self, Plateau};
+use crate::progression_log::ProgressionEvent;
+use crate::stats::{summarize_sessions, SessionSummary};
+use crate::{Catalog, LocalTz, SessionKind, WeeklyGoal};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+
+/// Days covered by a weekly report (ending with the local "today")
+pub const REPORT_DAYS: i64 = 7;
+
+/// Output format for a rendered report
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReportFormat {
+    Markdown,
+    Html,
+}
+
+impl ReportFormat {
+    /// File extension for the format
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ReportFormat::Markdown => "md",
+            ReportFormat::Html => "html",
+        }
+    }
+}
+
+impl std::str::FromStr for ReportFormat {
+    type Err = crate::Error;
+
+    fn from_str(s: &str) -> crate::Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "md" | "markdown" => Ok(ReportFormat::Markdown),
+            "html" => Ok(ReportFormat::Html),
+            other => Err(crate::Error::Other(format!(
+                "Unknown report format '{}' (expected markdown or html)",
+                other
+            ))),
+        }
+    }
+}
+
+/// One logged session, ready for display
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReportSession {
+    /// Local time, e.g. "2024-03-10 08:15"
+    pub when: String,
+    pub name: String,
+    /// Reps per set, e.g. "5/5/4"
+    pub reps: Option<String>,
+    pub tags: Vec<String>,
+}
+
+/// An AMRAP total that beat every earlier total for the definition
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReportBest {
+    pub name: String,
+    pub total: i32,
+    pub previous: i32,
+}
+
+/// A plateau with the definition's display name
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReportPlateau {
+    pub name: String,
+    pub plateau: Plateau,
+}
+
+/// Everything in a weekly report
+#[derive(Clone, Debug, PartialEq)]
+pub struct WeeklyReport {
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+    pub summary: SessionSummary,
+    /// Streak as of the end of the report
+    pub streak: u32,
+    /// Sessions in chronological order
+    pub sessions: Vec<ReportSession>,
+    pub personal_bests: Vec<ReportBest>,
+    pub plateaus: Vec<ReportPlateau>,
+}
+
+/// Build the report for the [`REPORT_DAYS`] local days ending at `now`
+///
+/// `history` should reach back further than the week so personal bests are
+/// compared against earlier totals; `events` is the progression log,
+/// `goal` (if set) forgives missed days in the streak and `off_days` are
+/// passed over by it.
+pub fn weekly_report(
+    catalog: &Catalog,
+    history: &[SessionKind],
+    events: &[ProgressionEvent],
+    goal: Option<&WeeklyGoal>,
+    off_days: &[crate::off_days::OffRange],
+    tz: &LocalTz,
+    now: DateTime<Utc>,
+) -> WeeklyReport {
+    let end = tz.date_of(now);
+    let start = end - Duration::days(REPORT_DAYS - 1);
+    let since = tz.day_start_utc(start);
+    let name_of = |def_id: &str| {
+        catalog
+            .definition(def_id)
+            .map_or(def_id.to_string(), |d| d.name.clone())
+    };
+
+    let week: Vec<SessionKind> = history
+        .iter()
+        .filter(|s| s.as_real().is_some_and(|r| r.performed_at >= since))
+        .cloned()
+        .collect();
+
+    let mut real: Vec<_> = week.iter().filter_map(|s| s.as_real()).collect();
+    real.sort_by_key(|s| s.performed_at);
+    let sessions = real
+        .iter()
+        .map(|s| ReportSession {
+            when: tz.format(s.performed_at, "%Y-%m-%d %H:%M"),
+            name: name_of(&s.definition_id),
+            reps: s.reps_per_set().map(|per_set| {
+                per_set
+                    .iter()
+                    .map(|r| r.to_string())
+                    .collect::<Vec<_>>()
+                    .join("/")
+            }),
+            tags: s.tags.clone(),
+        })
+        .collect();
+
+    // An AMRAP PR beats every earlier total in the loaded history
+    let mut personal_bests = Vec::new();
+    for session in &real {
+        let amrap = catalog
+            .definition(&session.definition_id)
+            .is_some_and(|d| d.is_amrap());
+        let Some(total) = session
+            .reps_per_set()
+            .filter(|_| amrap)
+            .map(|per_set| per_set.iter().sum::<i32>())
+        else {
+            continue;
+        };
+        let previous = history
+            .iter()
+            .filter_map(|s| s.as_real())
+            .filter(|s| {
+                s.definition_id == session.definition_id && s.performed_at < session.performed_at
+            })
+            .filter_map(|s| s.reps_per_set())
+            .map(|per_set| per_set.iter().sum::<i32>())
+            .max();
+        if let Some(previous) = previous.filter(|p| total > *p) {
+            personal_bests.push(ReportBest {
+                name: name_of(&session.definition_id),
+                total,
+                previous,
+            });
+        }
+    }
+
+    let plateaus = progression::plateaus(events, history, now)
+        .into_iter()
+        .map(|plateau| ReportPlateau {
+            name: name_of(&plateau.definition_id),
+            plateau,
+        })
+        .collect();
+
+    WeeklyReport {
+        start,
+        end,
+        summary: summarize_sessions(&week, catalog, REPORT_DAYS),
+        streak: crate::goal::forgiving_streak(history, goal, off_days, tz, now),
+        sessions,
+        personal_bests,
+        plateaus,
+    }
+}
+
+impl WeeklyReport {
+    /// Render in the given format
+    pub fn render(&self, format: ReportFormat) -> String {
+        match format {
+            ReportFormat::Markdown => self.to_markdown(),
+            ReportFormat::Html => self.to_html(),
+        }
+    }
+
+    fn title(&self) -> String {
+        format!("Krep weekly report: {} – {}", self.start, self.end)
+    }
+
+    fn totals(&self) -> Vec<String> {
+        let mut lines = vec![
+            format!(
+                "Sessions: {} ({} min)",
+                self.summary.total_sessions,
+                self.summary.total_seconds / 60
+            ),
+            format!("Streak: {} days", self.streak),
+        ];
+        if !self.summary.by_category.is_empty() {
+            let by_category: Vec<String> = self
+                .summary
+                .by_category
+                .iter()
+                .map(|(category, count)| format!("{} {}", category, count))
+                .collect();
+            lines.push(format!("By category: {}", by_category.join(", ")));
+        }
+        lines
+    }
+
+    fn best_line(best: &ReportBest) -> String {
+        format!("{}: {} reps (was {})", best.name, best.total, best.previous)
+    }
+
+    fn plateau_line(plateau: &ReportPlateau) -> String {
+        format!(
+            "{}: {} sessions in {} days, no progression since {} - try \"harder\"",
+            plateau.name,
+            plateau.plateau.sessions,
+            progression::PLATEAU_DAYS,
+            plateau.plateau.last_change.format("%Y-%m-%d")
+        )
+    }
+
+    /// Markdown (GitHub-flavoured tables)
+    pub fn to_markdown(&self) -> String {
+        let cell = |s: &str| s.replace('|', "\\|");
+        let mut out = format!("# {}\n\n## Totals\n\n", self.title());
+        for line in self.totals() {
+            out.push_str(&format!("- {}\n", line));
+        }
+
+        out.push_str("\n## Sessions\n\n");
+        if self.sessions.is_empty() {
+            out.push_str("No sessions this week.\n");
+        } else {
+            out.push_str("| When | Microdose | Reps | Tags |\n|---|---|---|---|\n");
+            for s in &self.sessions {
+                out.push_str(&format!(
+                    "| {} | {} | {} | {} |\n",
+                    s.when,
+                    cell(&s.name),
+                    s.reps.as_deref().unwrap_or(""),
+                    cell(&s.tags.join(", "))
+                ));
+            }
+        }
+
+        if !self.personal_bests.is_empty() {
+            out.push_str("\n## Personal bests\n\n");
+            for best in &self.personal_bests {
+                out.push_str(&format!("- {}\n", Self::best_line(best)));
+            }
+        }
+        if !self.plateaus.is_empty() {
+            out.push_str("\n## Plateaus\n\n");
+            for plateau in &self.plateaus {
+                out.push_str(&format!("- {}\n", Self::plateau_line(plateau)));
+            }
+        }
+        out
+    }
+
+    /// Standalone HTML page
+    pub fn to_html(&self) -> String {
+        let mut out = format!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n</head>\n<body>\n<h1>{}</h1>\n",
+            escape_html(&self.title()),
+            escape_html(&self.title())
+        );
+
+        out.push_str("<h2>Totals</h2>\n<ul>\n");
+        for line in self.totals() {
+            out.push_str(&format!("<li>{}</li>\n", escape_html(&line)));
+        }
+        out.push_str("</ul>\n<h2>Sessions</h2>\n");
+        if self.sessions.is_empty() {
+            out.push_str("<p>No sessions this week.</p>\n");
+        } else {
+            out.push_str(
+                "<table>\n<tr><th>When</th><th>Microdose</th><th>Reps</th><th>Tags</th></tr>\n",
+            );
+            for s in &self.sessions {
+                out.push_str(&format!(
+                    "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                    escape_html(&s.when),
+                    escape_html(&s.name),
+                    escape_html(s.reps.as_deref().unwrap_or("")),
+                    escape_html(&s.tags.join(", "))
+                ));
+            }
+            out.push_str("</table>\n");
+        }
+
+        let sections = [
+            (
+                "Personal bests",
+                self.personal_bests
+                    .iter()
+                    .map(Self::best_line)
+                    .collect::<Vec<_>>(),
+            ),
+            (
+                "Plateaus",
+                self.plateaus.iter().map(Self::plateau_line).collect(),
+            ),
+        ];
+        for (heading, lines) in sections {
+            if lines.is_empty() {
+                continue;
+            }
+            out.push_str(&format!("<h2>{}</h2>\n<ul>\n", heading));
+            for line in lines {
+                out.push_str(&format!("<li>{}</li>\n", escape_html(&line)));
+            }
+            out.push_str("</ul>\n");
+        }
+
+        out.push_str("</body>\n</html>\n");
+        out
+    }
+}
+
+/// Escape text for HTML element content and attributes
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{build_default_catalog, MicrodoseSession, RealizedMetric};
+    use chrono::TimeZone;
+
+    fn session(def_id: &str, at: DateTime<Utc>, per_set: &[i32], tags: &[&str]) -> SessionKind {
+        SessionKind::Real(MicrodoseSession {
+            actual_duration_seconds: Some(120),
+            metrics_realized: if per_set.is_empty() {
+                vec![]
+            } else {
+                vec![RealizedMetric::Reps {
+                    key: "reps".into(),
+                    per_set: per_set.to_vec(),
+                }]
+            },
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            ..MicrodoseSession::new(def_id, at)
+        })
+    }
+
+    fn sample() -> WeeklyReport {
+        let catalog = build_default_catalog();
+        let tz = LocalTz::from_name(Some("UTC")).unwrap();
+        let now = Utc.with_ymd_and_hms(2024, 3, 10, 20, 0, 0).unwrap();
+        let amrap = "core_amrap_mountain_climber_2m";
+        let history = vec![
+            session(amrap, now - Duration::hours(2), &[42], &[]),
+            session(
+                "emom_burpee_5m",
+                now - Duration::days(1),
+                &[5, 5, 4],
+                &["travel"],
+            ),
+            session(amrap, now - Duration::days(3), &[38], &[]),
+            // Before the week: only a comparison point for the PR
+            session(amrap, now - Duration::days(10), &[40], &[]),
+        ];
+        weekly_report(&catalog, &history, &[], None, &[], &tz, now)
+    }
+
+    #[test]
+    fn test_weekly_report_contents() {
+        let report = sample();
+        assert_eq!(report.start.to_string(), "2024-03-04");
+        assert_eq!(report.end.to_string(), "2024-03-10");
+        assert_eq!(report.summary.total_sessions, 3);
+        assert_eq!(report.streak, 2);
+        assert_eq!(report.sessions.len(), 3);
+        assert_eq!(report.sessions[1].reps.as_deref(), Some("5/5/4"));
+
+        // 38 didn't beat the earlier 40; 42 did
+        assert_eq!(report.personal_bests.len(), 1);
+        assert_eq!(report.personal_bests[0].total, 42);
+        assert_eq!(report.personal_bests[0].previous, 40);
+    }
+
+    #[test]
+    fn test_render_markdown_and_html() {
+        let report = sample();
+
+        let md = report.to_markdown();
+        assert!(md.starts_with("# Krep weekly report: 2024-03-04 – 2024-03-10"));
+        assert!(md.contains("| 2024-03-09 20:00 | 5-Min EMOM: Burpees"));
+        assert!(md.contains("| 5/5/4 | travel |"));
+        assert!(
+            md.contains("## Personal bests\n\n- 2-Min AMRAP: Mountain Climbers: 42 reps (was 40)")
+        );
+        assert!(!md.contains("## Plateaus"));
+
+        let html = report.render(ReportFormat::Html);
+        assert!(html.contains("<h1>Krep weekly report: 2024-03-04 – 2024-03-10</h1>"));
+        assert!(html.contains("<td>5/5/4</td><td>travel</td>"));
+        assert_eq!(escape_html("<a & \"b\">"), "&lt;a &amp; &quot;b&quot;&gt;");
+
+        assert_eq!("HTML".parse::<ReportFormat>().unwrap(), ReportFormat::Html);
+        assert!("pdf".parse::<ReportFormat>().is_err());
+    }
+}
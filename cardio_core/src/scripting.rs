@@ -0,0 +1,463 @@
+//! User scripts for prescription rules and progression policies.
+//!
+//! With the `scripting` feature, `*.rhai` files in the `scripts` directory
+//! next to the config file (see [`scripts_dir`]) can override the built-in
+//! rules. A script may define either or both of:
+//!
+//! ```rhai
+//! // The definition ID to prescribe, or () to leave it to the built-in rules
+//! fn prescribe(ctx) {
+//!     if ctx.category == () && ctx.recent.len() == 0 { "mobility_hip_cars" } else { () }
+//! }
+//!
+//! // Whether to offer progressing `id`; `ready` is the built-in verdict
+//! fn ready_to_progress(ctx, id, ready) {
+//!     ready && ctx.recent.len() >= 5
+//! }
+//! ```
+//!
+//! Scripts run in order of file name: the first `prescribe` to return an
+//! ID wins, and each `ready_to_progress` gets the previous verdict. `ctx` is
+//! a read-only snapshot of the [`UserContext`] and the catalog narrowed by
+//! the current modes:
+//!
+//! - `now`: RFC 3339 timestamp
+//! - `category`: the requested category (e.g. `"gtg"`) or `()`
+//! - `recent`: recent sessions, newest first, as `#{definition_id, category,
+//!   performed_at, rpe}` (`rpe` may be `()`)
+//! - `definitions`: allowed definitions as `#{id, name, category,
+//!   gtg_friendly}`
+//! - `progressions`: `#{reps, level}` by definition ID
+//! - `equipment`: available equipment names
+//! - `strength`: `#{type, hours_ago}` for the last strength session, or `()`
+//!
+//! Scripts are sandboxed: no file or module access, no `eval`, and limits
+//! on operations, call depth and data sizes. A script that fails to parse is
+//! a config error; one that fails at run time (or names a definition that
+//! isn't allowed) is logged and the built-in rules apply.
+
+use crate::{
+    Catalog, Error, MicrodoseCategory, PrescribedMicrodose, Result, StrengthSessionType,
+    UserContext,
+};
+use rhai::{Array, Dynamic, Engine, Map, Scope, AST};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Directory of scripts, next to the config file
+pub const SCRIPTS_DIR: &str = "scripts";
+
+/// Operations a single call may run before it is stopped
+const MAX_OPERATIONS: u64 = 100_000;
+
+/// The scripts directory next to `config_path`
+pub fn scripts_dir(config_path: &Path) -> PathBuf {
+    config_path
+        .parent()
+        .unwrap_or_else(|| Path::new("."))
+        .join(SCRIPTS_DIR)
+}
+
+/// Compiled user scripts (none by default)
+#[derive(Clone)]
+pub struct Scripts {
+    engine: Arc<Engine>,
+    scripts: Vec<(PathBuf, AST)>,
+}
+
+impl std::fmt::Debug for Scripts {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list()
+            .entries(self.scripts.iter().map(|(path, _)| path))
+            .finish()
+    }
+}
+
+impl Default for Scripts {
+    fn default() -> Self {
+        Self {
+            engine: Arc::new(sandboxed_engine()),
+            scripts: Vec::new(),
+        }
+    }
+}
+
+impl Scripts {
+    /// Compile every `*.rhai` file in `dir`, in lexical order
+    ///
+    /// None when the directory doesn't exist.
+    pub fn load_dir(dir: &Path) -> Result<Self> {
+        let mut scripts = Self::default();
+        if !dir.is_dir() {
+            return Ok(scripts);
+        }
+
+        let mut paths = Vec::new();
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.is_file() && path.extension().is_some_and(|e| e == "rhai") {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+        for path in paths {
+            let source = std::fs::read_to_string(&path)?;
+            scripts.add(path, &source)?;
+        }
+        Ok(scripts)
+    }
+
+    /// Compile `source`, named `path` in errors and logs
+    pub fn add(&mut self, path: PathBuf, source: &str) -> Result<()> {
+        let ast = self
+            .engine
+            .compile(source)
+            .map_err(|e| Error::Config(format!("{}: {}", path.display(), e)))?;
+        tracing::debug!("Loaded script {}", path.display());
+        self.scripts.push((path, ast));
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.scripts.is_empty()
+    }
+
+    /// The dose a script picks, if any does
+    ///
+    /// `ctx` and `category` are what [`crate::prescribe_next`] would get.
+    pub fn prescribe(
+        &self,
+        catalog: &Catalog,
+        ctx: &UserContext,
+        category: Option<&MicrodoseCategory>,
+    ) -> Option<PrescribedMicrodose> {
+        let catalog = crate::engine::narrowed(catalog, ctx);
+        let mut snapshot = None;
+        for (path, ast) in self.defining("prescribe") {
+            let snapshot = snapshot.get_or_insert_with(|| context_map(&catalog, ctx, category));
+            let picked = match self.call(path, ast, "prescribe", (snapshot.clone(),)) {
+                Some(picked) if picked.is_unit() => continue,
+                Some(picked) => picked,
+                None => continue,
+            };
+            let id = match picked.into_immutable_string() {
+                Ok(id) => id,
+                Err(found) => {
+                    tracing::warn!(
+                        "{}: prescribe() returned a {}, not a definition ID",
+                        path.display(),
+                        found
+                    );
+                    continue;
+                }
+            };
+            let Some(definition) = catalog.definition(&id) else {
+                tracing::warn!(
+                    "{}: prescribe() picked {}, which isn't available now",
+                    path.display(),
+                    id
+                );
+                continue;
+            };
+
+            let mut prescribed = crate::engine::prescription(&catalog, ctx, definition);
+            prescribed
+                .rationale
+                .push(format!("Picked by {}", script_name(path)));
+            return Some(prescribed);
+        }
+        None
+    }
+
+    /// Let the scripts' progression policies revise
+    /// `prescription.ready_to_progress`
+    pub fn review_progression(
+        &self,
+        catalog: &Catalog,
+        ctx: &UserContext,
+        prescription: &mut PrescribedMicrodose,
+    ) {
+        let mut snapshot = None;
+        for (path, ast) in self.defining("ready_to_progress") {
+            let snapshot = snapshot.get_or_insert_with(|| {
+                context_map(&crate::engine::narrowed(catalog, ctx), ctx, None)
+            });
+            let args = (
+                snapshot.clone(),
+                prescription.definition.id.clone(),
+                prescription.ready_to_progress,
+            );
+            match self.call(path, ast, "ready_to_progress", args) {
+                Some(verdict) if verdict.is_unit() => {}
+                Some(verdict) => match verdict.as_bool() {
+                    Ok(ready) => prescription.ready_to_progress = ready,
+                    Err(found) => tracing::warn!(
+                        "{}: ready_to_progress() returned a {}, not a bool",
+                        path.display(),
+                        found
+                    ),
+                },
+                None => {}
+            }
+        }
+    }
+
+    /// Scripts that define `name`, in order
+    fn defining<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a (PathBuf, AST)> {
+        self.scripts
+            .iter()
+            .filter(move |(_, ast)| ast.iter_functions().any(|f| f.name == name))
+    }
+
+    /// Call `name`, logging a failure
+    fn call(
+        &self,
+        path: &Path,
+        ast: &AST,
+        name: &str,
+        args: impl rhai::FuncArgs,
+    ) -> Option<Dynamic> {
+        match self
+            .engine
+            .call_fn::<Dynamic>(&mut Scope::new(), ast, name, args)
+        {
+            Ok(result) => Some(result),
+            Err(e) => {
+                tracing::warn!("{}: {}() failed: {}", path.display(), name, e);
+                None
+            }
+        }
+    }
+}
+
+/// An engine without file or module access and with resource limits
+fn sandboxed_engine() -> Engine {
+    let mut engine = Engine::new();
+    engine.set_module_resolver(rhai::module_resolvers::DummyModuleResolver::new());
+    engine.disable_symbol("eval");
+    engine.set_max_operations(MAX_OPERATIONS);
+    engine.set_max_call_levels(32);
+    engine.set_max_expr_depths(64, 32);
+    engine.set_max_string_size(10_000);
+    engine.set_max_array_size(10_000);
+    engine.set_max_map_size(10_000);
+    engine.on_print(|text| tracing::info!("script: {}", text));
+    engine.on_debug(|text, _, pos| tracing::debug!("script {}: {}", pos, text));
+    engine
+}
+
+/// "name" for `dir/name.rhai`
+fn script_name(path: &Path) -> String {
+    path.file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_else(|| path.display().to_string())
+}
+
+/// The `ctx` map scripts see
+fn context_map(catalog: &Catalog, ctx: &UserContext, category: Option<&MicrodoseCategory>) -> Map {
+    let recent: Array = ctx
+        .recent_sessions
+        .iter()
+        .filter_map(|s| s.as_real())
+        .map(|session| {
+            let mut map = Map::new();
+            map.insert("definition_id".into(), session.definition_id.clone().into());
+            map.insert(
+                "category".into(),
+                catalog
+                    .definition(&session.definition_id)
+                    .map(|d| Dynamic::from(d.category.as_str()))
+                    .unwrap_or(Dynamic::UNIT),
+            );
+            map.insert(
+                "performed_at".into(),
+                session.performed_at.to_rfc3339().into(),
+            );
+            map.insert(
+                "rpe".into(),
+                session
+                    .perceived_rpe
+                    .map(|rpe| Dynamic::from(rpe as i64))
+                    .unwrap_or(Dynamic::UNIT),
+            );
+            Dynamic::from_map(map)
+        })
+        .collect();
+
+    let definitions: Array = catalog
+        .definitions()
+        .map(|definition| {
+            let mut map = Map::new();
+            map.insert("id".into(), definition.id.clone().into());
+            map.insert("name".into(), definition.name.clone().into());
+            map.insert("category".into(), definition.category.as_str().into());
+            map.insert("gtg_friendly".into(), definition.gtg_friendly.into());
+            Dynamic::from_map(map)
+        })
+        .collect();
+
+    let progressions: Map = ctx
+        .user_state
+        .progressions
+        .iter()
+        .map(|(id, state)| {
+            let mut map = Map::new();
+            map.insert("reps".into(), (state.reps as i64).into());
+            map.insert("level".into(), (state.level as i64).into());
+            (id.as_str().into(), Dynamic::from_map(map))
+        })
+        .collect();
+
+    let strength = match &ctx.external_strength {
+        Some(signal) => {
+            let mut map = Map::new();
+            let kind = match &signal.session_type {
+                StrengthSessionType::Lower => "lower",
+                StrengthSessionType::Upper => "upper",
+                StrengthSessionType::Full => "full",
+                StrengthSessionType::Other(other) => other.as_str(),
+            };
+            map.insert("type".into(), kind.into());
+            map.insert(
+                "hours_ago".into(),
+                (ctx.now - signal.last_session_at).num_hours().into(),
+            );
+            Dynamic::from_map(map)
+        }
+        None => Dynamic::UNIT,
+    };
+
+    let mut map = Map::new();
+    map.insert("now".into(), ctx.now.to_rfc3339().into());
+    map.insert(
+        "category".into(),
+        category
+            .map(|c| Dynamic::from(c.as_str()))
+            .unwrap_or(Dynamic::UNIT),
+    );
+    map.insert("recent".into(), recent.into());
+    map.insert("definitions".into(), definitions.into());
+    map.insert("progressions".into(), progressions.into());
+    map.insert(
+        "equipment".into(),
+        ctx.equipment_available
+            .iter()
+            .map(|e| Dynamic::from(e.clone()))
+            .collect::<Array>()
+            .into(),
+    );
+    map.insert("strength".into(), strength);
+    map
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{get_default_catalog, SessionKind};
+    use chrono::Utc;
+
+    fn scripts(source: &str) -> Scripts {
+        let mut scripts = Scripts::default();
+        scripts.add(PathBuf::from("rules.rhai"), source).unwrap();
+        scripts
+    }
+
+    fn mobility_id() -> String {
+        get_default_catalog()
+            .definitions()
+            .find(|d| d.category == MicrodoseCategory::Mobility)
+            .unwrap()
+            .id
+            .clone()
+    }
+
+    #[test]
+    fn test_script_prescribes() {
+        let catalog = get_default_catalog();
+        let id = mobility_id();
+        let ctx = UserContext::builder(Utc::now()).build();
+        let scripts = scripts(&format!(
+            r#"
+            fn prescribe(ctx) {{
+                if ctx.recent.len() == 0 {{ "{}" }} else {{ () }}
+            }}
+            "#,
+            id
+        ));
+
+        let prescribed = scripts.prescribe(catalog, &ctx, None).unwrap();
+        assert_eq!(prescribed.definition.id, id);
+        assert_eq!(prescribed.rationale, vec!["Picked by rules".to_string()]);
+
+        // () leaves it to the built-in rules
+        let session = prescribed.done_as_prescribed(Utc::now());
+        let ctx = UserContext::builder(Utc::now())
+            .recent_sessions(vec![SessionKind::Real(session)])
+            .build();
+        assert!(scripts.prescribe(catalog, &ctx, None).is_none());
+    }
+
+    #[test]
+    fn test_bad_scripts_fall_back() {
+        let catalog = get_default_catalog();
+        let ctx = UserContext::builder(Utc::now()).build();
+
+        // Unknown IDs, wrong types and runaway loops are ignored
+        for source in [
+            r#"fn prescribe(ctx) { "no_such_dose" }"#,
+            "fn prescribe(ctx) { 42 }",
+            "fn prescribe(ctx) { loop {} }",
+            r#"fn prescribe(ctx) { import "other" as o; o::pick() }"#,
+        ] {
+            assert!(scripts(source).prescribe(catalog, &ctx, None).is_none());
+        }
+
+        // Parse errors name the file
+        let err = Scripts::default()
+            .add(PathBuf::from("broken.rhai"), "fn prescribe(ctx) {")
+            .unwrap_err();
+        assert!(err.to_string().contains("broken.rhai"));
+    }
+
+    #[test]
+    fn test_progression_policy() {
+        let catalog = get_default_catalog();
+        let ctx = UserContext::builder(Utc::now()).build();
+        let mut prescribed =
+            crate::prescribe_next(catalog, &ctx, Some(MicrodoseCategory::Mobility)).unwrap();
+        prescribed.ready_to_progress = true;
+
+        scripts("fn ready_to_progress(ctx, id, ready) { () }").review_progression(
+            catalog,
+            &ctx,
+            &mut prescribed,
+        );
+        assert!(prescribed.ready_to_progress);
+        scripts(r#"fn ready_to_progress(ctx, id, ready) { ready && ctx.now == "" }"#)
+            .review_progression(catalog, &ctx, &mut prescribed);
+        assert!(!prescribed.ready_to_progress);
+    }
+
+    #[test]
+    fn test_load_dir() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        assert!(Scripts::load_dir(temp_dir.path()).unwrap().is_empty());
+        std::fs::write(temp_dir.path().join("b.rhai"), "fn prescribe(ctx) { () }").unwrap();
+        std::fs::write(temp_dir.path().join("a.rhai"), "fn prescribe(ctx) { () }").unwrap();
+        std::fs::write(temp_dir.path().join("notes.txt"), "not a script").unwrap();
+
+        let scripts = Scripts::load_dir(temp_dir.path()).unwrap();
+        assert_eq!(
+            format!("{:?}", scripts),
+            format!(
+                "[{:?}, {:?}]",
+                temp_dir.path().join("a.rhai"),
+                temp_dir.path().join("b.rhai")
+            )
+        );
+        assert_eq!(
+            scripts_dir(&temp_dir.path().join("config.toml")),
+            temp_dir.path().join(SCRIPTS_DIR)
+        );
+    }
+}
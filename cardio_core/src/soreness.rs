@@ -0,0 +1,223 @@
+//! Daily soreness / pain check-in.
+//!
+//! Users report sore body areas at most once per local day. Check-ins live
+//! in [`UserMicrodoseState::soreness`](crate::UserMicrodoseState) and the
+//! engine avoids definitions whose movement tags load a sore area for
+//! [`AVOID_WINDOW_HOURS`] after the report.
+
+use crate::{Catalog, Error, LocalTz, MicrodoseDefinition, Result, SoreArea, SorenessCheckin};
+use chrono::{DateTime, Duration, Utc};
+
+/// Body areas that can be reported; each matches a movement tag
+pub const BODY_AREAS: [&str; 8] = [
+    "shoulder",
+    "elbow",
+    "wrist",
+    "lower_back",
+    "core",
+    "hip",
+    "knee",
+    "legs",
+];
+
+/// How long a reported area is avoided
+pub const AVOID_WINDOW_HOURS: i64 = 48;
+
+/// Lowest severity that makes the engine avoid an area (1 is just a note)
+pub const AVOID_MIN_SEVERITY: u8 = 2;
+
+/// Severity used when an area is given without one
+const DEFAULT_SEVERITY: u8 = 3;
+
+/// Parse a check-in answer such as `"hip:3, shoulder:2"`
+///
+/// Entries are separated by `,` or `;`; a missing severity means 3. An
+/// empty answer or `"none"` means nothing is sore.
+pub fn parse_areas(input: &str) -> Result<Vec<SoreArea>> {
+    let input = input.trim();
+    if input.is_empty() || input.eq_ignore_ascii_case("none") || input.eq_ignore_ascii_case("no") {
+        return Ok(vec![]);
+    }
+
+    let mut areas: Vec<SoreArea> = Vec::new();
+    for entry in input
+        .split([',', ';'])
+        .map(str::trim)
+        .filter(|e| !e.is_empty())
+    {
+        let (area, severity) = match entry.split_once(':') {
+            Some((area, severity)) => {
+                let severity = severity
+                    .trim()
+                    .parse::<u8>()
+                    .ok()
+                    .filter(|s| (1..=5).contains(s));
+                let severity = severity.ok_or_else(|| {
+                    Error::Other(format!("Severity for '{}' must be 1-5", area.trim()))
+                })?;
+                (area, severity)
+            }
+            None => (entry, DEFAULT_SEVERITY),
+        };
+
+        let area = area.trim().to_lowercase().replace([' ', '-'], "_");
+        if !BODY_AREAS.contains(&area.as_str()) {
+            return Err(Error::Other(format!(
+                "Unknown body area '{}' (expected one of: {})",
+                area,
+                BODY_AREAS.join(", ")
+            )));
+        }
+
+        // Repeated areas keep the highest severity
+        match areas.iter_mut().find(|a| a.area == area) {
+            Some(existing) => existing.severity = existing.severity.max(severity),
+            None => areas.push(SoreArea { area, severity }),
+        }
+    }
+    Ok(areas)
+}
+
+/// Whether no check-in has been recorded yet on the local "today"
+pub fn needs_checkin(checkins: &[SorenessCheckin], tz: &LocalTz, now: DateTime<Utc>) -> bool {
+    let today = tz.date_of(now);
+    !checkins.iter().any(|c| tz.date_of(c.checked_at) == today)
+}
+
+/// Record a check-in, replacing any earlier one from the same local day and
+/// dropping check-ins older than the avoidance window
+pub fn record_checkin(checkins: &mut Vec<SorenessCheckin>, checkin: SorenessCheckin, tz: &LocalTz) {
+    let day = tz.date_of(checkin.checked_at);
+    let cutoff = checkin.checked_at - Duration::hours(AVOID_WINDOW_HOURS);
+    checkins.retain(|c| c.checked_at >= cutoff && tz.date_of(c.checked_at) != day);
+    checkins.push(checkin);
+    checkins.sort_by_key(|c| c.checked_at);
+}
+
+/// Areas to avoid at `now`: reported within the window at or above
+/// [`AVOID_MIN_SEVERITY`]
+pub fn sore_areas(checkins: &[SorenessCheckin], now: DateTime<Utc>) -> Vec<String> {
+    let cutoff = now - Duration::hours(AVOID_WINDOW_HOURS);
+    let mut areas: Vec<String> = checkins
+        .iter()
+        .filter(|c| c.checked_at >= cutoff && c.checked_at <= now)
+        .flat_map(|c| c.areas.iter())
+        .filter(|a| a.severity >= AVOID_MIN_SEVERITY)
+        .map(|a| a.area.clone())
+        .collect();
+    areas.sort();
+    areas.dedup();
+    areas
+}
+
+/// Whether any movement in the definition is tagged with a sore area
+pub fn loads_sore_area(
+    catalog: &Catalog,
+    definition: &MicrodoseDefinition,
+    sore: &[String],
+) -> bool {
+    definition.blocks.iter().any(|block| {
+        catalog
+            .movement(&block.movement_id)
+            .is_some_and(|m| m.tags.iter().any(|tag| sore.contains(tag)))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::build_default_catalog;
+    use chrono::TimeZone;
+
+    fn checkin(checked_at: DateTime<Utc>, areas: &[(&str, u8)]) -> SorenessCheckin {
+        SorenessCheckin {
+            checked_at,
+            areas: areas
+                .iter()
+                .map(|(area, severity)| SoreArea {
+                    area: area.to_string(),
+                    severity: *severity,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_parse_areas() {
+        assert!(parse_areas("").unwrap().is_empty());
+        assert!(parse_areas(" None ").unwrap().is_empty());
+
+        let areas = parse_areas("Hip:4; lower back, hip:2").unwrap();
+        assert_eq!(
+            areas,
+            vec![
+                SoreArea {
+                    area: "hip".into(),
+                    severity: 4
+                },
+                SoreArea {
+                    area: "lower_back".into(),
+                    severity: 3
+                },
+            ]
+        );
+
+        assert!(parse_areas("hip:6").is_err());
+        assert!(parse_areas("elbow:x").is_err());
+        assert!(parse_areas("spleen:2").is_err());
+    }
+
+    #[test]
+    fn test_once_per_local_day() {
+        let tz = LocalTz::from_name(Some("UTC")).unwrap();
+        let morning = Utc.with_ymd_and_hms(2024, 3, 10, 8, 0, 0).unwrap();
+        let mut checkins = Vec::new();
+        assert!(needs_checkin(&checkins, &tz, morning));
+
+        record_checkin(&mut checkins, checkin(morning, &[("hip", 3)]), &tz);
+        assert!(!needs_checkin(
+            &checkins,
+            &tz,
+            morning + Duration::hours(10)
+        ));
+        assert!(needs_checkin(&checkins, &tz, morning + Duration::days(1)));
+
+        // A second answer the same day replaces the first
+        record_checkin(
+            &mut checkins,
+            checkin(morning + Duration::hours(2), &[]),
+            &tz,
+        );
+        assert_eq!(checkins.len(), 1);
+        assert!(checkins[0].areas.is_empty());
+
+        // Check-ins older than the window are dropped
+        record_checkin(
+            &mut checkins,
+            checkin(morning + Duration::days(3), &[]),
+            &tz,
+        );
+        assert_eq!(checkins.len(), 1);
+    }
+
+    #[test]
+    fn test_sore_areas_window_and_severity() {
+        let now = Utc.with_ymd_and_hms(2024, 3, 10, 12, 0, 0).unwrap();
+        let checkins = vec![
+            checkin(now - Duration::hours(50), &[("knee", 5)]),
+            checkin(now - Duration::hours(30), &[("hip", 3), ("wrist", 1)]),
+            checkin(now - Duration::hours(2), &[("shoulder", 2)]),
+        ];
+        assert_eq!(sore_areas(&checkins, now), vec!["hip", "shoulder"]);
+    }
+
+    #[test]
+    fn test_loads_sore_area() {
+        let catalog = build_default_catalog();
+        let swings = &catalog.microdoses()["emom_kb_swing_5m"];
+        let pullups = &catalog.microdoses()["gtg_pullup_band"];
+        let sore = vec!["lower_back".to_string()];
+        assert!(loads_sore_area(&catalog, swings, &sore));
+        assert!(!loads_sore_area(&catalog, pullups, &sore));
+    }
+}
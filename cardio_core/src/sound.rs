@@ -0,0 +1,130 @@
+//! Audio cues for interval timers.
+//!
+//! Cues are synthesized tones (no sound files), picked by [`tones`] from the
+//! `[sound]` style. Playback needs the `sound` feature (rodio); without it
+//! [`SoundPlayer::new`] returns `None` and frontends stay silent, so they
+//! don't need feature gates of their own.
+
+use crate::config::{SoundConfig, SoundStyle};
+use crate::timer::TimerCue;
+
+/// A tone: frequency in Hz and length in milliseconds
+pub type Tone = (f32, u64);
+
+/// Tones played in sequence for a cue
+pub fn tones(style: SoundStyle, cue: TimerCue) -> &'static [Tone] {
+    match (style, cue) {
+        (SoundStyle::Beep, TimerCue::MinuteRollover) => &[(880.0, 150)],
+        (SoundStyle::Beep, TimerCue::Countdown) => &[(660.0, 80)],
+        (SoundStyle::Beep, TimerCue::Complete) => &[(880.0, 600)],
+        (SoundStyle::Chime, TimerCue::MinuteRollover) => &[(1046.5, 120), (1318.5, 160)],
+        (SoundStyle::Chime, TimerCue::Countdown) => &[(1318.5, 60)],
+        (SoundStyle::Chime, TimerCue::Complete) => &[(1046.5, 200), (1318.5, 200), (1568.0, 400)],
+    }
+}
+
+/// Total length of a cue
+pub fn cue_duration(style: SoundStyle, cue: TimerCue) -> std::time::Duration {
+    std::time::Duration::from_millis(tones(style, cue).iter().map(|(_, ms)| ms).sum())
+}
+
+/// Plays cues on the default audio output
+///
+/// Cues queue up and play in order. Dropping the player stops playback, so
+/// keep it alive (or call [`SoundPlayer::wait`]) until the last cue ends.
+pub struct SoundPlayer {
+    style: SoundStyle,
+    #[cfg(feature = "sound")]
+    sink: rodio::Sink,
+    #[cfg(feature = "sound")]
+    _stream: rodio::OutputStream,
+}
+
+impl SoundPlayer {
+    /// Open the default output; `None` when sounds are disabled, the build
+    /// lacks the `sound` feature or there is no audio device
+    pub fn new(config: &SoundConfig) -> Option<Self> {
+        if !config.enabled {
+            return None;
+        }
+        Self::open(config)
+    }
+
+    #[cfg(feature = "sound")]
+    fn open(config: &SoundConfig) -> Option<Self> {
+        let (stream, handle) = match rodio::OutputStream::try_default() {
+            Ok(output) => output,
+            Err(e) => {
+                tracing::warn!("No audio output for timer sounds: {}", e);
+                return None;
+            }
+        };
+        let sink = match rodio::Sink::try_new(&handle) {
+            Ok(sink) => sink,
+            Err(e) => {
+                tracing::warn!("Unable to play timer sounds: {}", e);
+                return None;
+            }
+        };
+        sink.set_volume(config.volume.clamp(0.0, 1.0));
+        Some(Self {
+            style: config.style,
+            sink,
+            _stream: stream,
+        })
+    }
+
+    #[cfg(not(feature = "sound"))]
+    fn open(_config: &SoundConfig) -> Option<Self> {
+        tracing::warn!("[sound] is enabled but this build has no sound support (feature `sound`)");
+        None
+    }
+
+    /// Queue a cue
+    pub fn play(&self, cue: TimerCue) {
+        #[cfg(feature = "sound")]
+        for &(frequency, millis) in tones(self.style, cue) {
+            use rodio::Source;
+            self.sink.append(
+                rodio::source::SineWave::new(frequency)
+                    .take_duration(std::time::Duration::from_millis(millis))
+                    .amplify(0.3),
+            );
+        }
+        #[cfg(not(feature = "sound"))]
+        let _ = (self.style, cue);
+    }
+
+    /// Block until queued cues have played
+    pub fn wait(&self) {
+        #[cfg(feature = "sound")]
+        self.sink.sleep_until_end();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_every_cue_has_tones() {
+        for style in [SoundStyle::Beep, SoundStyle::Chime] {
+            for cue in [
+                TimerCue::MinuteRollover,
+                TimerCue::Countdown,
+                TimerCue::Complete,
+            ] {
+                assert!(!tones(style, cue).is_empty());
+                // Countdown cues must fit in their second
+                if cue == TimerCue::Countdown {
+                    assert!(cue_duration(style, cue).as_millis() < 1000);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_disabled_player_is_none() {
+        assert!(SoundPlayer::new(&SoundConfig::default()).is_none());
+    }
+}
@@ -4,18 +4,19 @@
 //! with proper file locking to prevent concurrent access issues.
 
 use crate::{Error, Result, UserMicrodoseState};
-use fs2::FileExt;
 use std::fs::File;
 use std::io::{Read, Write};
 use std::path::Path;
+use std::time::Duration;
 use tempfile::NamedTempFile;
 
 impl UserMicrodoseState {
-    /// Load user state from a file with shared locking
+    /// Load user state from a file with shared locking, waiting up to
+    /// `timeout` for another process's lock
     ///
     /// Returns default state if file doesn't exist.
     /// If file is corrupted, logs a warning and returns default state.
-    pub fn load(path: &Path) -> Result<Self> {
+    pub fn load(path: &Path, timeout: Duration) -> Result<Self> {
         if !path.exists() {
             tracing::info!("No state file found, using default state");
             return Ok(Self::default());
@@ -33,20 +34,26 @@ impl UserMicrodoseState {
             }
         };
 
-        // Acquire shared lock for reading
-        if let Err(e) = file.lock_shared() {
-            tracing::warn!(
-                "Unable to lock state file {:?}: {}. Using defaults.",
-                path,
-                e
-            );
-            return Ok(Self::default());
+        // Acquire shared lock for reading; a timeout means another process
+        // is stuck holding it, so report that rather than fall back to
+        // defaults a later save would write over the real state
+        match crate::lock::lock_shared_within(&file, path, timeout) {
+            Ok(()) => {}
+            Err(e @ Error::LockTimeout { .. }) => return Err(e),
+            Err(e) => {
+                tracing::warn!(
+                    "Unable to lock state file {:?}: {}. Using defaults.",
+                    path,
+                    e
+                );
+                return Ok(Self::default());
+            }
         }
 
         let mut contents = String::new();
         let mut reader = std::io::BufReader::new(&file);
         if let Err(e) = reader.read_to_string(&mut contents) {
-            let _ = file.unlock();
+            let _ = fs2::FileExt::unlock(&file);
             tracing::warn!(
                 "Failed to read state file {:?}: {}. Using defaults.",
                 path,
@@ -55,7 +62,7 @@ impl UserMicrodoseState {
             return Ok(Self::default());
         }
 
-        file.unlock()?;
+        fs2::FileExt::unlock(&file)?;
 
         match serde_json::from_str::<UserMicrodoseState>(&contents) {
             Ok(state) => {
@@ -79,7 +86,9 @@ impl UserMicrodoseState {
     /// 1. Writing to a temp file
     /// 2. Syncing to disk
     /// 3. Renaming over the original
-    pub fn save(&self, path: &Path) -> Result<()> {
+    ///
+    /// The lock is taken within `timeout`.
+    pub fn save(&self, path: &Path, timeout: Duration) -> Result<()> {
         // Ensure parent directory exists
         if let Some(parent) = path.parent() {
             std::fs::create_dir_all(parent)?;
@@ -91,7 +100,7 @@ impl UserMicrodoseState {
         })?)?;
 
         // Acquire exclusive lock on the temp file to serialize concurrent writers
-        temp.as_file().lock_exclusive()?;
+        crate::lock::lock_exclusive_within(temp.as_file(), temp.path(), timeout)?;
 
         {
             let mut writer = std::io::BufWriter::new(temp.as_file());
@@ -102,7 +111,7 @@ impl UserMicrodoseState {
         }
 
         temp.as_file().sync_all()?;
-        temp.as_file().unlock()?;
+        fs2::FileExt::unlock(temp.as_file())?;
 
         // Atomically replace old state file
         temp.persist(path).map_err(|e| Error::Io(e.error))?;
@@ -114,14 +123,14 @@ impl UserMicrodoseState {
     /// Load state, modify it, and save it back atomically
     ///
     /// This is a convenience method that handles the load-modify-save pattern
-    /// with proper error handling.
-    pub fn update<F>(path: &Path, f: F) -> Result<Self>
+    /// with proper error handling, waiting up to `timeout` for each lock.
+    pub fn update<F>(path: &Path, timeout: Duration, f: F) -> Result<Self>
     where
         F: FnOnce(&mut UserMicrodoseState) -> Result<()>,
     {
-        let mut state = Self::load(path)?;
+        let mut state = Self::load(path, timeout)?;
         f(&mut state)?;
-        state.save(path)?;
+        state.save(path, timeout)?;
         Ok(state)
     }
 }
@@ -150,10 +159,12 @@ mod tests {
         state.last_mobility_def_id = Some("mobility_hip_cars".into());
 
         // Save
-        state.save(&state_path).unwrap();
+        state
+            .save(&state_path, crate::lock::DEFAULT_TIMEOUT)
+            .unwrap();
 
         // Load
-        let loaded = UserMicrodoseState::load(&state_path).unwrap();
+        let loaded = UserMicrodoseState::load(&state_path, crate::lock::DEFAULT_TIMEOUT).unwrap();
 
         assert_eq!(loaded.progressions.len(), 1);
         assert!(loaded.progressions.contains_key("emom_burpee_5m"));
@@ -168,7 +179,7 @@ mod tests {
         let temp_dir = tempfile::tempdir().unwrap();
         let state_path = temp_dir.path().join("nonexistent.json");
 
-        let state = UserMicrodoseState::load(&state_path).unwrap();
+        let state = UserMicrodoseState::load(&state_path, crate::lock::DEFAULT_TIMEOUT).unwrap();
         assert!(state.progressions.is_empty());
         assert_eq!(state.last_mobility_def_id, None);
     }
@@ -179,17 +190,19 @@ mod tests {
         let state_path = temp_dir.path().join("state.json");
 
         // Initialize empty state
-        UserMicrodoseState::default().save(&state_path).unwrap();
+        UserMicrodoseState::default()
+            .save(&state_path, crate::lock::DEFAULT_TIMEOUT)
+            .unwrap();
 
         // Update using the update helper
-        UserMicrodoseState::update(&state_path, |state| {
+        UserMicrodoseState::update(&state_path, crate::lock::DEFAULT_TIMEOUT, |state| {
             state.last_mobility_def_id = Some("test_mobility".into());
             Ok(())
         })
         .unwrap();
 
         // Verify update persisted
-        let loaded = UserMicrodoseState::load(&state_path).unwrap();
+        let loaded = UserMicrodoseState::load(&state_path, crate::lock::DEFAULT_TIMEOUT).unwrap();
         assert_eq!(loaded.last_mobility_def_id, Some("test_mobility".into()));
     }
 
@@ -201,20 +214,36 @@ mod tests {
         // Write invalid JSON
         std::fs::write(&state_path, "{ invalid json }").unwrap();
 
-        let result = UserMicrodoseState::load(&state_path);
+        let result = UserMicrodoseState::load(&state_path, crate::lock::DEFAULT_TIMEOUT);
         assert!(result.is_ok());
         let state = result.unwrap();
         assert!(state.progressions.is_empty());
         assert!(state.last_mobility_def_id.is_none());
     }
 
+    #[test]
+    fn test_load_gives_up_after_its_lock_timeout() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let state_path = temp_dir.path().join("state.json");
+        UserMicrodoseState::default()
+            .save(&state_path, crate::lock::DEFAULT_TIMEOUT)
+            .unwrap();
+        let held = File::open(&state_path).unwrap();
+        crate::lock::lock_exclusive(&held, &state_path).unwrap();
+
+        let err = UserMicrodoseState::load(&state_path, Duration::ZERO).unwrap_err();
+        assert!(matches!(err, Error::LockTimeout { .. }));
+    }
+
     #[test]
     fn test_atomic_save() {
         let temp_dir = tempfile::tempdir().unwrap();
         let state_path = temp_dir.path().join("state.json");
 
         let state = UserMicrodoseState::default();
-        state.save(&state_path).unwrap();
+        state
+            .save(&state_path, crate::lock::DEFAULT_TIMEOUT)
+            .unwrap();
 
         // Verify state file exists and no stray temp files remain
         assert!(state_path.exists());
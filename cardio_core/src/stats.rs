@@ -0,0 +1,609 @@
+//! Session statistics aggregation.
+//!
+//! Pure functions over loaded history, shared by the CLI (`krep stats`)
+//! and the FFI `krep_stats` call.
+
+use crate::bodyweight::BodyweightTrend;
+use crate::goal::GoalProgress;
+use crate::heart_rate::{HrComparison, RecoveryTrend};
+use crate::progression_log::ProgressionEvent;
+use crate::{Catalog, LocalTz, MicrodoseCategory, MovementStyle, SessionKind};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+
+/// Summary of real sessions over a window
+#[derive(Clone, Debug, Default, Serialize, PartialEq)]
+pub struct SessionSummary {
+    pub days: i64,
+    pub total_sessions: usize,
+    pub total_seconds: u64,
+    /// Session count keyed by category name (`vo2`, `gtg`, …, or `unknown`)
+    pub by_category: BTreeMap<String, usize>,
+}
+
+/// Full stats report
+#[derive(Clone, Debug, Default, Serialize, PartialEq)]
+pub struct StatsReport {
+    #[serde(flatten)]
+    pub sessions: SessionSummary,
+    /// Bodyweight trend over the same window, if any weigh-ins were logged
+    pub bodyweight: Option<BodyweightTrend>,
+    /// Progress toward weekly targets over the trailing 7 days
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub targets: Vec<TargetProgress>,
+    /// Logged HR against zone targets (needs a configured max HR)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub heart_rate: Vec<HrComparison>,
+    /// 60-second HR recovery over the window, if any was logged
+    pub hr_recovery: Option<RecoveryTrend>,
+    /// Progress toward the weekly dose goal this calendar week, if one is set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub goal: Option<GoalProgress>,
+    /// This week against last week (when requested)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub week_over_week: Option<WeekComparison>,
+    /// Minutes per category for each local calendar week in the window
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub time_in_category: Vec<CategoryWeek>,
+    /// Sessions during each rehab period overlapping the window
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub rehab: Vec<crate::rehab::RehabSummary>,
+    /// Sessions during each configured training phase overlapping the window
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub phases: Vec<crate::phase::PhaseSummary>,
+    /// Sessions per household member, you last (with `[household] members`)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub household: Vec<crate::household::MemberSummary>,
+}
+
+/// Sessions done toward one category's weekly target
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct TargetProgress {
+    pub category: MicrodoseCategory,
+    pub target: u32,
+    pub done: u32,
+}
+
+impl TargetProgress {
+    /// Sessions still needed to hit the target
+    pub fn remaining(&self) -> u32 {
+        self.target.saturating_sub(self.done)
+    }
+
+    /// Share of the target done, capped at 1.0
+    pub fn fraction(&self) -> f64 {
+        if self.target == 0 {
+            return 1.0;
+        }
+        (f64::from(self.done) / f64::from(self.target)).min(1.0)
+    }
+}
+
+/// Summarize real sessions (skipped entries are ignored)
+pub fn summarize_sessions(
+    sessions: &[SessionKind],
+    catalog: &Catalog,
+    days: i64,
+) -> SessionSummary {
+    let mut summary = SessionSummary {
+        days,
+        ..SessionSummary::default()
+    };
+
+    for session in sessions.iter().filter_map(|s| s.as_real()) {
+        summary.total_sessions += 1;
+        summary.total_seconds += u64::from(session.actual_duration_seconds.unwrap_or(0));
+        let category = catalog
+            .definition(&session.definition_id)
+            .map(|d| d.category.as_str())
+            .unwrap_or("unknown");
+        *summary.by_category.entry(category.to_string()).or_insert(0) += 1;
+    }
+
+    summary
+}
+
+/// One week's totals in a [`WeekComparison`]
+#[derive(Clone, Debug, Default, Serialize, PartialEq)]
+pub struct WeekTotals {
+    #[serde(flatten)]
+    pub sessions: SessionSummary,
+    /// Mean perceived RPE of sessions that recorded one
+    pub avg_rpe: Option<f64>,
+}
+
+/// The trailing 7 days against the 7 days before
+#[derive(Clone, Debug, Default, Serialize, PartialEq)]
+pub struct WeekComparison {
+    pub this_week: WeekTotals,
+    pub last_week: WeekTotals,
+}
+
+impl WeekComparison {
+    /// `(category, this week, last week)` for every category done in either week
+    pub fn category_counts(&self) -> Vec<(&str, usize, usize)> {
+        let this = &self.this_week.sessions.by_category;
+        let last = &self.last_week.sessions.by_category;
+        let mut categories: Vec<&str> =
+            this.keys().chain(last.keys()).map(String::as_str).collect();
+        categories.sort();
+        categories.dedup();
+        categories
+            .into_iter()
+            .map(|c| {
+                (
+                    c,
+                    this.get(c).copied().unwrap_or(0),
+                    last.get(c).copied().unwrap_or(0),
+                )
+            })
+            .collect()
+    }
+}
+
+/// Compare real sessions in `[now − 7d, now)` with `[now − 14d, now − 7d)`
+///
+/// `sessions` should cover at least the last 14 days.
+pub fn compare_weeks(
+    sessions: &[SessionKind],
+    catalog: &Catalog,
+    now: DateTime<Utc>,
+) -> WeekComparison {
+    let week = Duration::days(7);
+    let totals = |from: DateTime<Utc>, to: DateTime<Utc>| {
+        let in_week: Vec<SessionKind> = sessions
+            .iter()
+            .filter(|s| (from..to).contains(&s.timestamp()))
+            .cloned()
+            .collect();
+        let rpes: Vec<f64> = in_week
+            .iter()
+            .filter_map(|s| s.as_real()?.perceived_rpe)
+            .map(f64::from)
+            .collect();
+        WeekTotals {
+            sessions: summarize_sessions(&in_week, catalog, 7),
+            avg_rpe: (!rpes.is_empty()).then(|| rpes.iter().sum::<f64>() / rpes.len() as f64),
+        }
+    };
+
+    // Include `now` itself in this week
+    let end = now + Duration::seconds(1);
+    WeekComparison {
+        this_week: totals(end - week, end),
+        last_week: totals(end - week - week, end - week),
+    }
+}
+
+/// Minutes spent in each category during one local calendar week
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct CategoryWeek {
+    /// Monday of the week
+    pub week_start: NaiveDate,
+    /// Minutes keyed by category name (`vo2`, `gtg`, …), rounded
+    pub minutes: BTreeMap<String, u32>,
+}
+
+/// Minutes per category per local calendar week (Monday to Sunday)
+///
+/// Uses each session's actual duration, falling back to the definition's
+/// suggested duration when none was recorded. Sessions of unknown
+/// definitions are left out. Weeks are in chronological order.
+pub fn time_in_category(
+    sessions: &[SessionKind],
+    catalog: &Catalog,
+    tz: &LocalTz,
+) -> Vec<CategoryWeek> {
+    let mut seconds: BTreeMap<NaiveDate, BTreeMap<&str, u32>> = BTreeMap::new();
+    for session in sessions.iter().filter_map(|s| s.as_real()) {
+        let Some(definition) = catalog.definition(&session.definition_id) else {
+            continue;
+        };
+        let duration = session
+            .actual_duration_seconds
+            .unwrap_or(definition.suggested_duration_seconds);
+        let week = crate::goal::week_start(tz.date_of(session.performed_at));
+        *seconds
+            .entry(week)
+            .or_default()
+            .entry(definition.category.as_str())
+            .or_default() += duration;
+    }
+
+    seconds
+        .into_iter()
+        .map(|(week_start, by_category)| CategoryWeek {
+            week_start,
+            minutes: by_category
+                .into_iter()
+                .map(|(category, secs)| (category.to_string(), (secs + 30) / 60))
+                .collect(),
+        })
+        .collect()
+}
+
+/// Up/down marker for a change (`▲`, `▼` or `=`)
+pub fn trend_marker(current: f64, previous: f64) -> &'static str {
+    if current > previous {
+        "▲"
+    } else if current < previous {
+        "▼"
+    } else {
+        "="
+    }
+}
+
+/// Progress toward each weekly target from real sessions at or after `since`
+///
+/// Ordered by [`MicrodoseCategory::ALL`]; categories come from the catalog,
+/// so sessions of retired definitions don't count.
+pub fn target_progress(
+    sessions: &[SessionKind],
+    catalog: &Catalog,
+    targets: &HashMap<MicrodoseCategory, u32>,
+    since: DateTime<Utc>,
+) -> Vec<TargetProgress> {
+    MicrodoseCategory::ALL
+        .into_iter()
+        .filter_map(|category| {
+            let target = *targets.get(&category)?;
+            let done = sessions
+                .iter()
+                .filter_map(|s| s.as_real())
+                .filter(|s| s.performed_at >= since)
+                .filter(|s| {
+                    catalog
+                        .definition(&s.definition_id)
+                        .is_some_and(|d| d.category == category)
+                })
+                .count();
+            Some(TargetProgress {
+                category,
+                target,
+                done: done as u32,
+            })
+        })
+        .collect()
+}
+
+/// One category's arc of the weekly-target ring, in turns (1.0 = full circle)
+#[derive(Clone, Debug, PartialEq)]
+pub struct RingSegment {
+    pub category: MicrodoseCategory,
+    /// Where the arc starts, clockwise from the top
+    pub start: f64,
+    pub length: f64,
+    /// Part of `length` filled by sessions done
+    pub filled: f64,
+}
+
+/// Split the ring evenly between the targets, each arc filled by its
+/// [`TargetProgress::fraction`]
+pub fn ring_segments(progress: &[TargetProgress]) -> Vec<RingSegment> {
+    let length = 1.0 / progress.len().max(1) as f64;
+    progress
+        .iter()
+        .enumerate()
+        .map(|(i, p)| RingSegment {
+            category: p.category.clone(),
+            start: i as f64 * length,
+            length,
+            filled: length * p.fraction(),
+        })
+        .collect()
+}
+
+/// Sessions counted toward the targets over their total, with each
+/// category capped at its target (e.g. 7 of 18)
+pub fn ring_totals(progress: &[TargetProgress]) -> (u32, u32) {
+    progress.iter().fold((0, 0), |(done, target), p| {
+        (done + p.done.min(p.target), target + p.target)
+    })
+}
+
+/// A definition's progression at one moment, for charting
+#[derive(Clone, Debug, Serialize, PartialEq)]
+pub struct ProgressionPoint {
+    pub at: DateTime<Utc>,
+    /// Prescribed reps in effect
+    pub reps: i32,
+    pub level: u32,
+    pub style: MovementStyle,
+    /// Total reps actually logged (session points with recorded reps only)
+    pub realized_reps: Option<i32>,
+}
+
+/// Dated progression points for one definition, oldest first
+///
+/// Each progression-log event and each real session of the definition
+/// becomes a point carrying the progression in effect at that time.
+/// Sessions from before the first logged event are left out, since their
+/// progression is unknown.
+pub fn progression_series(
+    def_id: &str,
+    events: &[ProgressionEvent],
+    sessions: &[SessionKind],
+) -> Vec<ProgressionPoint> {
+    let mut events: Vec<&ProgressionEvent> = events
+        .iter()
+        .filter(|e| e.definition_id == def_id)
+        .collect();
+    events.sort_by_key(|e| e.changed_at);
+
+    let mut points: Vec<ProgressionPoint> = events
+        .iter()
+        .map(|e| ProgressionPoint {
+            at: e.changed_at,
+            reps: e.reps,
+            level: e.level,
+            style: e.style.clone(),
+            realized_reps: None,
+        })
+        .collect();
+
+    for session in sessions.iter().filter_map(|s| s.as_real()) {
+        if session.definition_id != def_id {
+            continue;
+        }
+        let Some(in_effect) = events
+            .iter()
+            .rev()
+            .find(|e| e.changed_at <= session.performed_at)
+        else {
+            continue;
+        };
+        let realized = session
+            .metrics_realized
+            .iter()
+            .filter_map(|m| m.total_reps())
+            .reduce(|a, b| a + b);
+        points.push(ProgressionPoint {
+            at: session.performed_at,
+            reps: in_effect.reps,
+            level: in_effect.level,
+            style: in_effect.style.clone(),
+            realized_reps: realized,
+        });
+    }
+
+    // Stable sort keeps an event ahead of a session logged at the same instant
+    points.sort_by_key(|p| p.at);
+    points
+}
+
+/// Render values as a unicode sparkline (flat input renders mid-height)
+pub fn sparkline(values: &[f64]) -> String {
+    const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    values
+        .iter()
+        .map(|v| {
+            if (max - min).abs() < f64::EPSILON {
+                BARS[3]
+            } else {
+                let idx = ((v - min) / (max - min) * (BARS.len() - 1) as f64).round() as usize;
+                BARS[idx.min(BARS.len() - 1)]
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{build_default_catalog, MicrodoseSession};
+    use chrono::Utc;
+
+    fn real(def_id: &str, seconds: u32) -> SessionKind {
+        SessionKind::Real(MicrodoseSession {
+            actual_duration_seconds: Some(seconds),
+            ..MicrodoseSession::new(def_id, Utc::now())
+        })
+    }
+
+    #[test]
+    fn test_compare_weeks() {
+        let catalog = build_default_catalog();
+        let now = Utc::now();
+        let at = |def_id: &str, days_ago: i64, rpe: Option<u8>| {
+            let mut session = real(def_id, 300);
+            if let SessionKind::Real(ref mut s) = session {
+                s.performed_at = now - Duration::days(days_ago);
+                s.perceived_rpe = rpe;
+            }
+            session
+        };
+        let sessions = vec![
+            at("emom_burpee_5m", 0, Some(8)),
+            at("emom_burpee_5m", 2, Some(6)),
+            at("gtg_pullup_band", 3, None),
+            at("emom_kb_swing_5m", 8, Some(9)),
+            at("mobility_hip_cars", 10, None),
+            at("emom_burpee_5m", 20, Some(5)),
+        ];
+
+        let comparison = compare_weeks(&sessions, &catalog, now);
+        assert_eq!(comparison.this_week.sessions.total_sessions, 3);
+        assert_eq!(comparison.last_week.sessions.total_sessions, 2);
+        assert_eq!(comparison.this_week.avg_rpe, Some(7.0));
+        assert_eq!(comparison.last_week.avg_rpe, Some(9.0));
+        assert_eq!(
+            comparison.category_counts(),
+            vec![("gtg", 1, 0), ("mobility", 0, 1), ("vo2", 2, 1)]
+        );
+
+        assert_eq!(trend_marker(3.0, 2.0), "▲");
+        assert_eq!(trend_marker(1.0, 2.0), "▼");
+        assert_eq!(trend_marker(2.0, 2.0), "=");
+    }
+
+    #[test]
+    fn test_summarize_sessions() {
+        let catalog = build_default_catalog();
+        let sessions = vec![
+            real("emom_burpee_5m", 300),
+            real("emom_kb_swing_5m", 300),
+            real("gtg_pullup_band", 30),
+            real("retired_drill", 60),
+            SessionKind::ShownButSkipped {
+                definition_id: "mobility_hip_cars".into(),
+                shown_at: Utc::now(),
+            },
+        ];
+
+        let summary = summarize_sessions(&sessions, &catalog, 7);
+        assert_eq!(summary.total_sessions, 4);
+        assert_eq!(summary.total_seconds, 690);
+        assert_eq!(summary.by_category["vo2"], 2);
+        assert_eq!(summary.by_category["gtg"], 1);
+        assert_eq!(summary.by_category["unknown"], 1);
+        assert!(!summary.by_category.contains_key("mobility"));
+    }
+
+    #[test]
+    fn test_time_in_category() {
+        use chrono::TimeZone;
+
+        let catalog = build_default_catalog();
+        let tz = LocalTz::from_name(Some("UTC")).unwrap();
+        // March 2024: the 4th and 11th are Mondays
+        let on = |def_id: &str, day: u32, seconds: Option<u32>| {
+            let mut session = real(def_id, 0);
+            if let SessionKind::Real(ref mut s) = session {
+                s.performed_at = Utc.with_ymd_and_hms(2024, 3, day, 12, 0, 0).unwrap();
+                s.actual_duration_seconds = seconds;
+            }
+            session
+        };
+        let sessions = vec![
+            on("emom_burpee_5m", 4, Some(300)),
+            on("emom_kb_swing_5m", 10, Some(290)),
+            on("mobility_hip_cars", 6, None),
+            on("retired_drill", 6, Some(600)),
+            on("emom_burpee_5m", 11, Some(300)),
+        ];
+
+        let weeks = time_in_category(&sessions, &catalog, &tz);
+        assert_eq!(weeks.len(), 2);
+        assert_eq!(
+            weeks[0].week_start,
+            NaiveDate::from_ymd_opt(2024, 3, 4).unwrap()
+        );
+        // Actual durations sum before rounding; missing ones use the suggestion
+        assert_eq!(weeks[0].minutes["vo2"], 10);
+        assert_eq!(weeks[0].minutes["mobility"], 2);
+        assert_eq!(weeks[0].minutes.len(), 2);
+        assert_eq!(weeks[1].minutes["vo2"], 5);
+    }
+
+    #[test]
+    fn test_target_progress() {
+        let catalog = build_default_catalog();
+        let sessions = vec![
+            real("emom_burpee_5m", 300),
+            real("gtg_pullup_band", 30),
+            real("gtg_pullup_band", 30),
+            SessionKind::ShownButSkipped {
+                definition_id: "emom_kb_swing_5m".into(),
+                shown_at: Utc::now(),
+            },
+        ];
+        let targets = HashMap::from([
+            (MicrodoseCategory::Vo2, 3),
+            (MicrodoseCategory::Gtg, 2),
+            (MicrodoseCategory::Mobility, 5),
+        ]);
+
+        let since = Utc::now() - chrono::Duration::days(7);
+        let progress = target_progress(&sessions, &catalog, &targets, since);
+        let summary: Vec<(MicrodoseCategory, u32, u32)> = progress
+            .iter()
+            .map(|p| (p.category.clone(), p.done, p.remaining()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (MicrodoseCategory::Vo2, 1, 2),
+                (MicrodoseCategory::Gtg, 2, 0),
+                (MicrodoseCategory::Mobility, 0, 5),
+            ]
+        );
+        assert_eq!(progress[1].fraction(), 1.0);
+
+        let ring = ring_segments(&progress);
+        assert_eq!(ring.len(), 3);
+        assert_eq!(ring[1].start, 1.0 / 3.0);
+        assert_eq!(ring[1].filled, ring[1].length);
+        assert_eq!(ring[2].filled, 0.0);
+        assert_eq!(ring_totals(&progress), (3, 10));
+        assert!(ring_segments(&[]).is_empty());
+
+        // Sessions before the window don't count
+        let later = Utc::now() + chrono::Duration::hours(1);
+        assert!(target_progress(&sessions, &catalog, &targets, later)
+            .iter()
+            .all(|p| p.done == 0));
+    }
+
+    #[test]
+    fn test_progression_series() {
+        let now = Utc::now();
+        let event = |hours_ago: i64, reps: i32, level: u32| ProgressionEvent {
+            changed_at: now - chrono::Duration::hours(hours_ago),
+            definition_id: "emom_burpee_5m".into(),
+            reps,
+            level,
+            style: MovementStyle::None,
+        };
+        let events = vec![
+            event(10, 4, 1),
+            event(48, 3, 0),
+            ProgressionEvent {
+                definition_id: "gtg_pullup_band".into(),
+                ..event(5, 9, 9)
+            },
+        ];
+
+        let at = |session: SessionKind, hours_ago: i64| match session {
+            SessionKind::Real(mut s) => {
+                s.performed_at = now - chrono::Duration::hours(hours_ago);
+                SessionKind::Real(s)
+            }
+            other => other,
+        };
+        let mut logged = real("emom_burpee_5m", 300);
+        if let SessionKind::Real(ref mut s) = logged {
+            s.metrics_realized = vec![crate::RealizedMetric::Reps {
+                key: "reps".into(),
+                per_set: vec![4, 4, 3],
+            }];
+        }
+        let sessions = vec![
+            at(logged, 2),
+            at(real("emom_burpee_5m", 300), 24),
+            // Before any logged event: progression unknown
+            at(real("emom_burpee_5m", 300), 72),
+            at(real("gtg_pullup_band", 30), 1),
+        ];
+
+        let series = progression_series("emom_burpee_5m", &events, &sessions);
+        let summary: Vec<(i32, u32, Option<i32>)> = series
+            .iter()
+            .map(|p| (p.reps, p.level, p.realized_reps))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![(3, 0, None), (3, 0, None), (4, 1, None), (4, 1, Some(11))]
+        );
+        assert!(series.windows(2).all(|w| w[0].at <= w[1].at));
+    }
+
+    #[test]
+    fn test_sparkline() {
+        assert_eq!(sparkline(&[1.0, 2.0, 3.0]), "▁▅█");
+        assert_eq!(sparkline(&[5.0, 5.0]), "▄▄");
+        assert_eq!(sparkline(&[]), "");
+    }
+}
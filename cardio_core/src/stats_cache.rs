@@ -0,0 +1,422 @@
+//! Aggregates cache for `krep stats`.
+//!
+//! The cache (`sessions.csv.stats`, JSON) holds, for each local calendar
+//! day, the number of sessions and seconds in each category. Logging a dose
+//! adds it ([`record_log`]) and rollup, which only moves sessions from the
+//! WAL into the archive, carries the totals over ([`record_rollup`]), so
+//! all-time totals and the streak never need the whole history read back.
+//!
+//! Like the byte-offset index this is a cache: it stores the WAL and
+//! archive lengths and the timezone it describes, and a missing, unreadable
+//! or stale cache (sessions written some other way, such as an import or a
+//! merge) just means a full recompute, which [`load_or_rebuild`] saves for
+//! next time.
+
+use crate::off_days::OffRange;
+use crate::{Catalog, LocalTz, MicrodoseSession, Result};
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Current cache format version; other versions are ignored and rebuilt
+const CACHE_VERSION: u32 = 1;
+
+/// Sessions and time in one category
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Totals {
+    pub sessions: usize,
+    pub seconds: u64,
+}
+
+/// Per-day totals over every logged session
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct StatsCache {
+    pub version: u32,
+    /// Timezone the days are local to (`system` or an IANA name)
+    pub timezone: String,
+    /// WAL and archive lengths in bytes when the cache was written
+    pub wal_len: u64,
+    pub csv_len: u64,
+    /// Totals keyed by local day, then category name (`vo2`, …, or `unknown`)
+    pub days: BTreeMap<NaiveDate, BTreeMap<String, Totals>>,
+}
+
+impl StatsCache {
+    fn new(tz: &LocalTz, wal_len: u64, csv_len: u64) -> Self {
+        Self {
+            version: CACHE_VERSION,
+            timezone: timezone_key(tz),
+            wal_len,
+            csv_len,
+            days: BTreeMap::new(),
+        }
+    }
+
+    /// Count a session under `category`
+    fn add(&mut self, session: &MicrodoseSession, category: &str, tz: &LocalTz) {
+        let totals = self
+            .days
+            .entry(tz.date_of(session.performed_at))
+            .or_default()
+            .entry(category.to_string())
+            .or_default();
+        totals.sessions += 1;
+        totals.seconds += u64::from(session.actual_duration_seconds.unwrap_or(0));
+    }
+
+    /// Totals across every day
+    pub fn all_time(&self) -> Totals {
+        self.by_category()
+            .values()
+            .fold(Totals::default(), |sum, t| Totals {
+                sessions: sum.sessions + t.sessions,
+                seconds: sum.seconds + t.seconds,
+            })
+    }
+
+    /// Totals per category across every day
+    pub fn by_category(&self) -> BTreeMap<String, Totals> {
+        let mut totals: BTreeMap<String, Totals> = BTreeMap::new();
+        for (category, day) in self.days.values().flatten() {
+            let total = totals.entry(category.clone()).or_default();
+            total.sessions += day.sessions;
+            total.seconds += day.seconds;
+        }
+        totals
+    }
+
+    /// Current streak, counted like [`crate::history::current_streak`]
+    pub fn streak(&self, off_days: &[OffRange], tz: &LocalTz, now: DateTime<Utc>) -> u32 {
+        crate::history::streak_ending(
+            |day| self.days.contains_key(&day),
+            off_days,
+            tz.date_of(now),
+        )
+    }
+}
+
+/// Cache file for a CSV archive: the archive path with `.stats` appended
+pub fn cache_path(csv_path: &Path) -> PathBuf {
+    let mut path = csv_path.as_os_str().to_owned();
+    path.push(".stats");
+    PathBuf::from(path)
+}
+
+/// Load the cache if it exists, is readable and matches the WAL, archive
+/// and timezone
+pub fn load(wal_path: &Path, csv_path: &Path, tz: &LocalTz) -> Option<StatsCache> {
+    let cache = read_raw(csv_path)?;
+    if cache.version != CACHE_VERSION
+        || cache.timezone != timezone_key(tz)
+        || cache.wal_len != file_len(wal_path)
+        || cache.csv_len != file_len(csv_path)
+    {
+        tracing::debug!("Stats cache for {:?} is stale", csv_path);
+        return None;
+    }
+    Some(cache)
+}
+
+/// The current cache, or a full recompute saved in its place (reading the
+/// WAL under its lock, taken within `timeout`)
+pub fn load_or_rebuild(
+    wal_path: &Path,
+    csv_path: &Path,
+    catalog: &Catalog,
+    tz: &LocalTz,
+    timeout: Duration,
+) -> Result<StatsCache> {
+    if let Some(cache) = load(wal_path, csv_path, tz) {
+        return Ok(cache);
+    }
+    tracing::info!("Rebuilding stats cache for {:?}", csv_path);
+    let cache = rebuild(wal_path, csv_path, catalog, tz, timeout)?;
+    // The totals are right either way; an unsaved cache is just rebuilt again
+    if let Err(e) = save(csv_path, &cache) {
+        tracing::warn!("Failed to save stats cache for {:?}: {}", csv_path, e);
+    }
+    Ok(cache)
+}
+
+/// Build a cache from every session in the WAL and archive
+///
+/// A session in both (after an interrupted rollup) is counted once. The
+/// WAL's lock is taken within `timeout`.
+pub fn rebuild(
+    wal_path: &Path,
+    csv_path: &Path,
+    catalog: &Catalog,
+    tz: &LocalTz,
+    timeout: Duration,
+) -> Result<StatsCache> {
+    let wal_len = file_len(wal_path);
+    let csv_len = file_len(csv_path);
+    let mut cache = StatsCache::new(tz, wal_len, csv_len);
+    let mut seen = HashSet::new();
+    let wal_sessions = if wal_path.exists() {
+        crate::wal::read_sessions(wal_path, timeout)?
+    } else {
+        Vec::new()
+    };
+    let csv_sessions = if csv_path.exists() {
+        Some(crate::history::CsvSessions::open(csv_path, None, None)?)
+    } else {
+        None
+    };
+    for session in wal_sessions
+        .into_iter()
+        .chain(csv_sessions.into_iter().flatten())
+    {
+        if seen.insert(session.id) {
+            cache.add(&session, category_of(catalog, &session), tz);
+        }
+    }
+    Ok(cache)
+}
+
+/// Count a session just appended to the WAL
+///
+/// `appended` is the bytes of the WAL the session's line occupies (see
+/// [`crate::wal::JsonlSink::append_range`]). A cache that didn't describe
+/// exactly the WAL before them is left alone, to be rebuilt on next use;
+/// the cache then describes the WAL up to their end, whatever other
+/// processes have appended since.
+pub fn record_log(
+    csv_path: &Path,
+    appended: std::ops::Range<u64>,
+    session: &MicrodoseSession,
+    category: &str,
+    tz: &LocalTz,
+) -> Result<()> {
+    let Some(mut cache) = read_raw(csv_path) else {
+        return Ok(());
+    };
+    if cache.version != CACHE_VERSION
+        || cache.timezone != timezone_key(tz)
+        || cache.wal_len != appended.start
+        || cache.csv_len != file_len(csv_path)
+    {
+        return Ok(());
+    }
+    cache.add(session, category, tz);
+    cache.wal_len = appended.end;
+    save(csv_path, &cache)
+}
+
+/// Carry the totals over a rollup that moved the WAL into the archive
+///
+/// `previous_wal_len` and `previous_csv_len` are the lengths before the
+/// rollup. A cache that didn't describe them is left alone.
+pub fn record_rollup(
+    wal_path: &Path,
+    csv_path: &Path,
+    previous_wal_len: u64,
+    previous_csv_len: u64,
+) -> Result<()> {
+    let Some(mut cache) = read_raw(csv_path) else {
+        return Ok(());
+    };
+    if cache.wal_len != previous_wal_len || cache.csv_len != previous_csv_len {
+        return Ok(());
+    }
+    cache.wal_len = file_len(wal_path);
+    cache.csv_len = file_len(csv_path);
+    save(csv_path, &cache)
+}
+
+fn category_of<'a>(catalog: &'a Catalog, session: &MicrodoseSession) -> &'a str {
+    catalog
+        .definition(&session.definition_id)
+        .map(|d| d.category.as_str())
+        .unwrap_or("unknown")
+}
+
+fn timezone_key(tz: &LocalTz) -> String {
+    match tz {
+        LocalTz::System => "system".to_string(),
+        LocalTz::Named(tz) => tz.name().to_string(),
+    }
+}
+
+/// Length of a file, 0 if it doesn't exist
+fn file_len(path: &Path) -> u64 {
+    std::fs::metadata(path).map_or(0, |m| m.len())
+}
+
+/// Read the cache file without checking it against the data
+fn read_raw(csv_path: &Path) -> Option<StatsCache> {
+    let contents = std::fs::read_to_string(cache_path(csv_path)).ok()?;
+    match serde_json::from_str(&contents) {
+        Ok(cache) => Some(cache),
+        Err(e) => {
+            tracing::warn!("Ignoring unreadable stats cache for {:?}: {}", csv_path, e);
+            None
+        }
+    }
+}
+
+/// Write the cache atomically next to the archive
+fn save(csv_path: &Path, cache: &StatsCache) -> Result<()> {
+    let path = cache_path(csv_path);
+    let dir = path.parent().unwrap_or_else(|| Path::new("."));
+    std::fs::create_dir_all(dir)?;
+    let temp = tempfile::NamedTempFile::new_in(dir)?;
+    serde_json::to_writer(temp.as_file(), cache)?;
+    temp.persist(&path).map_err(|e| crate::Error::Io(e.error))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wal::{JsonlSink, SessionSink};
+    use chrono::Duration;
+
+    fn session_at(at: DateTime<Utc>) -> MicrodoseSession {
+        MicrodoseSession {
+            actual_duration_seconds: Some(300),
+            ..MicrodoseSession::new("emom_burpee_5m", at)
+        }
+    }
+
+    #[test]
+    fn test_cache_follows_log_and_rollup() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let wal_path = temp_dir.path().join("wal").join("microdose_sessions.wal");
+        let csv_path = temp_dir.path().join("sessions.csv");
+        let catalog = crate::get_default_catalog();
+        let tz = LocalTz::from_name(Some("UTC")).unwrap();
+        let now = Utc::now();
+        let mut sink = JsonlSink::new(&wal_path);
+        sink.append(&session_at(now - Duration::days(1))).unwrap();
+
+        let cache = load_or_rebuild(
+            &wal_path,
+            &csv_path,
+            catalog,
+            &tz,
+            crate::lock::DEFAULT_TIMEOUT,
+        )
+        .unwrap();
+        assert_eq!(cache.all_time().sessions, 1);
+        assert_eq!(cache.streak(&[], &tz, now), 1);
+
+        // Logged: counted without a rebuild
+        let session = session_at(now);
+        let appended = sink.append_range(&session).unwrap();
+        assert_eq!(appended.end, file_len(&wal_path));
+        record_log(&csv_path, appended, &session, "vo2", &tz).unwrap();
+        let cache = load(&wal_path, &csv_path, &tz).unwrap();
+        assert_eq!(
+            cache.by_category()["vo2"],
+            Totals {
+                sessions: 2,
+                seconds: 600
+            }
+        );
+        assert_eq!(cache.streak(&[], &tz, now), 2);
+
+        // Rolled up: same totals, still current
+        crate::csv_rollup::wal_to_csv_and_archive(
+            &wal_path,
+            &csv_path,
+            crate::lock::DEFAULT_TIMEOUT,
+        )
+        .unwrap();
+        assert_eq!(load(&wal_path, &csv_path, &tz).unwrap(), {
+            let mut moved = cache.clone();
+            moved.wal_len = 0;
+            moved.csv_len = file_len(&csv_path);
+            moved
+        });
+        assert_eq!(
+            rebuild(
+                &wal_path,
+                &csv_path,
+                catalog,
+                &tz,
+                crate::lock::DEFAULT_TIMEOUT
+            )
+            .unwrap()
+            .all_time(),
+            cache.all_time()
+        );
+    }
+
+    #[test]
+    fn test_log_racing_another_append_leaves_the_cache_stale() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let wal_path = temp_dir.path().join("wal").join("microdose_sessions.wal");
+        let csv_path = temp_dir.path().join("sessions.csv");
+        let catalog = crate::get_default_catalog();
+        let tz = LocalTz::from_name(Some("UTC")).unwrap();
+        let mut sink = JsonlSink::new(&wal_path);
+        sink.append(&session_at(Utc::now())).unwrap();
+        load_or_rebuild(
+            &wal_path,
+            &csv_path,
+            catalog,
+            &tz,
+            crate::lock::DEFAULT_TIMEOUT,
+        )
+        .unwrap();
+
+        // Another process appends between this log and its cache update
+        let session = session_at(Utc::now());
+        let appended = sink.append_range(&session).unwrap();
+        JsonlSink::new(&wal_path)
+            .append(&session_at(Utc::now()))
+            .unwrap();
+        record_log(&csv_path, appended, &session, "vo2", &tz).unwrap();
+
+        // The cache doesn't claim the other session, so it's rebuilt
+        assert!(load(&wal_path, &csv_path, &tz).is_none());
+        let cache = load_or_rebuild(
+            &wal_path,
+            &csv_path,
+            catalog,
+            &tz,
+            crate::lock::DEFAULT_TIMEOUT,
+        )
+        .unwrap();
+        assert_eq!(cache.all_time().sessions, 3);
+    }
+
+    #[test]
+    fn test_stale_cache_is_rebuilt() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let wal_path = temp_dir.path().join("wal").join("microdose_sessions.wal");
+        let csv_path = temp_dir.path().join("sessions.csv");
+        let catalog = crate::get_default_catalog();
+        let tz = LocalTz::from_name(Some("UTC")).unwrap();
+        let mut sink = JsonlSink::new(&wal_path);
+        sink.append(&session_at(Utc::now())).unwrap();
+        load_or_rebuild(
+            &wal_path,
+            &csv_path,
+            catalog,
+            &tz,
+            crate::lock::DEFAULT_TIMEOUT,
+        )
+        .unwrap();
+
+        // Written without going through the cache (e.g. an import)
+        sink.append(&session_at(Utc::now())).unwrap();
+        assert!(load(&wal_path, &csv_path, &tz).is_none());
+        let cache = load_or_rebuild(
+            &wal_path,
+            &csv_path,
+            catalog,
+            &tz,
+            crate::lock::DEFAULT_TIMEOUT,
+        )
+        .unwrap();
+        assert_eq!(cache.all_time().sessions, 2);
+
+        // Days are local, so another timezone means a recompute
+        let tokyo = LocalTz::from_name(Some("Asia/Tokyo")).unwrap();
+        assert!(load(&wal_path, &csv_path, &tokyo).is_none());
+    }
+}
@@ -0,0 +1,225 @@
+//! Status file for home automation.
+//!
+//! A small JSON document with whether a dose is due, the last session and
+//! the streak, for a Home Assistant file or command-line sensor (e.g. to
+//! drive a "go move" light). The schema is stable: fields are only added,
+//! and [`SCHEMA_VERSION`] goes up if one ever changes meaning.
+
+use crate::config::StatusConfig;
+use crate::off_days::{self, OffRange};
+use crate::{LocalTz, SessionKind, WeeklyGoal};
+use chrono::{DateTime, Datelike, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Version of the status document
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Days of history read for the streak
+pub const STATUS_HISTORY_DAYS: i64 = 90;
+
+/// Status document
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Status {
+    pub schema_version: u32,
+    pub updated_at: DateTime<Utc>,
+    /// Whether to do a dose now: none for `due_after_minutes`, not an off
+    /// day and, with a weekly goal, doses still due today
+    pub due: bool,
+    /// Doses still due today: the weekly goal's remainder spread over the
+    /// days left in the week that aren't off days, or 1/0 following `due`
+    /// without a goal
+    pub doses_due: u32,
+    /// Whether today is an off day (holiday)
+    #[serde(default)]
+    pub off_day: bool,
+    pub last_session_at: Option<DateTime<Utc>>,
+    pub last_definition_id: Option<String>,
+    pub minutes_since_last_session: Option<i64>,
+    pub sessions_today: u32,
+    pub streak_days: u32,
+    /// This week's goal, if one is set
+    pub weekly_target: Option<u32>,
+    pub weekly_done: u32,
+}
+
+/// Build the status at `now`
+pub fn build(
+    sessions: &[SessionKind],
+    goal: Option<&WeeklyGoal>,
+    off_days: &[OffRange],
+    config: &StatusConfig,
+    tz: &LocalTz,
+    now: DateTime<Utc>,
+) -> Status {
+    let last = sessions
+        .iter()
+        .filter_map(|s| s.as_real())
+        .max_by_key(|s| s.performed_at);
+    let minutes_since = last.map(|s| (now - s.performed_at).num_minutes());
+    let rested = minutes_since.is_none_or(|m| m >= i64::from(config.due_after_minutes));
+
+    let today = tz.date_of(now);
+    let off_day = off_days::is_off(off_days, today);
+    let progress = goal.map(|goal| crate::goal::current_progress(goal, sessions, tz, now));
+    let doses_due = match &progress {
+        _ if off_day => 0,
+        Some(progress) => {
+            // Monday leaves up to 7 days including today, Sunday 1
+            let days_left = (0..7 - today.weekday().num_days_from_monday())
+                .filter(|&n| !off_days::is_off(off_days, today + Duration::days(i64::from(n))))
+                .count() as u32;
+            progress.remaining().div_ceil(days_left)
+        }
+        None => u32::from(rested),
+    };
+
+    Status {
+        schema_version: SCHEMA_VERSION,
+        updated_at: now,
+        due: rested && doses_due > 0,
+        doses_due,
+        off_day,
+        last_session_at: last.map(|s| s.performed_at),
+        last_definition_id: last.map(|s| s.definition_id.clone()),
+        minutes_since_last_session: minutes_since,
+        sessions_today: crate::history::sessions_today(sessions, tz, now) as u32,
+        streak_days: crate::goal::forgiving_streak(sessions, goal, off_days, tz, now),
+        weekly_target: progress.as_ref().map(|p| p.target),
+        weekly_done: progress.map_or(0, |p| p.done),
+    }
+}
+
+/// Write the status atomically, so a sensor never reads half a file
+#[cfg(feature = "fs")]
+pub fn write(path: &std::path::Path, status: &Status) -> crate::Result<()> {
+    use std::io::Write;
+
+    let dir = path.parent().unwrap_or_else(|| std::path::Path::new("."));
+    std::fs::create_dir_all(dir)?;
+    let mut temp = tempfile::NamedTempFile::new_in(dir)?;
+    serde_json::to_writer_pretty(&mut temp, status)?;
+    temp.write_all(b"\n")?;
+    temp.persist(path).map_err(|e| crate::Error::Io(e.error))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MicrodoseSession;
+    use chrono::TimeZone;
+
+    fn session(at: DateTime<Utc>) -> SessionKind {
+        SessionKind::Real(MicrodoseSession {
+            started_at: Some(at),
+            completed_at: Some(at),
+            actual_duration_seconds: Some(120),
+            ..MicrodoseSession::new("gtg_pullup_ladder", at)
+        })
+    }
+
+    #[test]
+    fn test_status_without_goal() {
+        let tz = LocalTz::from_name(Some("UTC")).unwrap();
+        let config = StatusConfig::default();
+        // Wednesday
+        let now = Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap();
+
+        let empty = build(&[], None, &[], &config, &tz, now);
+        assert!(empty.due);
+        assert_eq!(empty.doses_due, 1);
+        assert_eq!(empty.last_session_at, None);
+
+        let sessions = vec![
+            session(now - Duration::days(1)),
+            session(now - Duration::minutes(20)),
+        ];
+        let status = build(&sessions, None, &[], &config, &tz, now);
+        assert!(!status.due);
+        assert_eq!(status.doses_due, 0);
+        assert_eq!(status.minutes_since_last_session, Some(20));
+        assert_eq!(status.sessions_today, 1);
+        assert_eq!(status.streak_days, 2);
+        assert_eq!(status.weekly_target, None);
+    }
+
+    #[test]
+    fn test_status_with_goal() {
+        let tz = LocalTz::from_name(Some("UTC")).unwrap();
+        let config = StatusConfig::default();
+        // Wednesday: 5 days left including today
+        let now = Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap();
+        let goal = WeeklyGoal {
+            weekly_doses: 12,
+            travel_weekly_doses: None,
+        };
+        let sessions = vec![
+            session(now - Duration::hours(3)),
+            session(now - Duration::days(1)),
+        ];
+
+        let status = build(&sessions, Some(&goal), &[], &config, &tz, now);
+        assert_eq!(status.weekly_target, Some(12));
+        assert_eq!(status.weekly_done, 2);
+        assert_eq!(status.doses_due, 2);
+        assert!(status.due);
+
+        // Goal met: nothing due however long it has been
+        let goal = WeeklyGoal {
+            weekly_doses: 2,
+            travel_weekly_doses: None,
+        };
+        let status = build(&sessions, Some(&goal), &[], &config, &tz, now);
+        assert_eq!(status.doses_due, 0);
+        assert!(!status.due);
+
+        // Off from Friday: 10 doses over the 2 days left
+        let goal = WeeklyGoal {
+            weekly_doses: 12,
+            travel_weekly_doses: None,
+        };
+        let weekend: Vec<OffRange> = vec!["2024-05-03..2024-05-05".parse().unwrap()];
+        let status = build(&sessions, Some(&goal), &weekend, &config, &tz, now);
+        assert_eq!(status.doses_due, 5);
+        assert!(!status.off_day);
+
+        // On the day off itself nothing is due
+        let today: Vec<OffRange> = vec!["2024-05-01".parse().unwrap()];
+        let status = build(&sessions, Some(&goal), &today, &config, &tz, now);
+        assert!(status.off_day);
+        assert_eq!(status.doses_due, 0);
+        assert!(!status.due);
+    }
+
+    #[test]
+    fn test_schema_field_names() {
+        let tz = LocalTz::from_name(Some("UTC")).unwrap();
+        let now = Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap();
+        let json = serde_json::to_value(build(&[], None, &[], &StatusConfig::default(), &tz, now))
+            .unwrap();
+        let mut keys: Vec<&str> = json
+            .as_object()
+            .unwrap()
+            .keys()
+            .map(|k| k.as_str())
+            .collect();
+        keys.sort();
+        assert_eq!(
+            keys,
+            vec![
+                "doses_due",
+                "due",
+                "last_definition_id",
+                "last_session_at",
+                "minutes_since_last_session",
+                "off_day",
+                "schema_version",
+                "sessions_today",
+                "streak_days",
+                "updated_at",
+                "weekly_done",
+                "weekly_target"
+            ]
+        );
+    }
+}
@@ -0,0 +1,304 @@
+//! Storage abstraction for session history and user state.
+//!
+//! The prescription engine only needs recent history and progression state.
+//! This trait lets frontends without a filesystem (e.g. a wasm web UI backed
+//! by localStorage) supply those and reuse the exact same engine logic.
+
+use crate::{MicrodoseSession, Result, SessionKind, UserMicrodoseState};
+use chrono::{Duration, Utc};
+#[cfg(feature = "fs")]
+use std::path::{Path, PathBuf};
+
+/// Backend for persisting sessions and loading the engine's inputs
+pub trait Storage {
+    /// Persist a completed session
+    fn append_session(&mut self, session: &MicrodoseSession) -> Result<()>;
+
+    /// Load sessions from the last N days, newest first
+    fn load_recent_sessions(&self, days: i64) -> Result<Vec<SessionKind>>;
+
+    /// Load the user's progression state (default if none stored yet)
+    fn load_state(&self) -> Result<UserMicrodoseState>;
+
+    /// Persist the user's progression state
+    fn save_state(&mut self, state: &UserMicrodoseState) -> Result<()>;
+}
+
+/// In-memory storage (no filesystem required)
+///
+/// Useful for wasm frontends that serialize the contents themselves, and for tests.
+#[derive(Clone, Debug, Default)]
+pub struct MemoryStorage {
+    pub sessions: Vec<MicrodoseSession>,
+    pub state: UserMicrodoseState,
+}
+
+impl MemoryStorage {
+    /// Create an empty in-memory store
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn append_session(&mut self, session: &MicrodoseSession) -> Result<()> {
+        self.sessions.push(session.clone());
+        Ok(())
+    }
+
+    fn load_recent_sessions(&self, days: i64) -> Result<Vec<SessionKind>> {
+        let cutoff = Utc::now() - Duration::days(days);
+        let mut sessions: Vec<SessionKind> = self
+            .sessions
+            .iter()
+            .filter(|s| s.performed_at >= cutoff)
+            .cloned()
+            .map(SessionKind::Real)
+            .collect();
+
+        // Sort by timestamp, newest first
+        sessions.sort_by_key(|s| std::cmp::Reverse(s.timestamp()));
+        Ok(sessions)
+    }
+
+    fn load_state(&self) -> Result<UserMicrodoseState> {
+        Ok(self.state.clone())
+    }
+
+    fn save_state(&mut self, state: &UserMicrodoseState) -> Result<()> {
+        self.state = state.clone();
+        Ok(())
+    }
+}
+
+/// Filesystem storage using the standard data directory layout
+///
+/// - WAL: `<data_dir>/wal/microdose_sessions.wal`
+/// - State: `<data_dir>/wal/state.json`
+/// - CSV: `<data_dir>/sessions.csv`
+/// - Strength signal: `<data_dir>/strength/signal.json`
+///
+/// [`FileStorage::with_config`] applies the `[data]` path overrides and
+/// lock timeout.
+#[cfg(feature = "fs")]
+#[derive(Clone, Debug)]
+pub struct FileStorage {
+    pub wal_path: PathBuf,
+    pub csv_path: PathBuf,
+    pub state_path: PathBuf,
+    pub strength_signal_path: PathBuf,
+    pub strength_history_path: PathBuf,
+    pub readiness_signal_path: PathBuf,
+    /// How long reads and writes wait for another process's lock
+    pub lock_timeout: std::time::Duration,
+}
+
+#[cfg(feature = "fs")]
+impl FileStorage {
+    /// Create a file store rooted at the given data directory
+    pub fn new(data_dir: &Path) -> Self {
+        let wal_dir = data_dir.join("wal");
+        Self {
+            wal_path: wal_dir.join("microdose_sessions.wal"),
+            csv_path: data_dir.join("sessions.csv"),
+            state_path: wal_dir.join("state.json"),
+            strength_signal_path: data_dir.join("strength").join("signal.json"),
+            strength_history_path: data_dir.join(crate::strength::STRENGTH_HISTORY_FILE),
+            readiness_signal_path: data_dir.join(crate::readiness::READINESS_FILE),
+            lock_timeout: crate::lock::DEFAULT_TIMEOUT,
+        }
+    }
+
+    /// Create a file store rooted at `data_dir`, with any individually
+    /// configured paths (relative ones resolve against `data_dir`) and
+    /// `lock_timeout_secs`
+    pub fn with_config(data_dir: &Path, data: &crate::config::DataConfig) -> Self {
+        let defaults = Self::new(data_dir);
+        let resolve = |path: &Option<PathBuf>, default: PathBuf| {
+            path.as_ref().map_or(default, |p| data_dir.join(p))
+        };
+        Self {
+            wal_path: resolve(&data.wal_path, defaults.wal_path),
+            csv_path: resolve(&data.csv_path, defaults.csv_path),
+            state_path: resolve(&data.state_path, defaults.state_path),
+            strength_signal_path: resolve(
+                &data.strength_signal_path,
+                defaults.strength_signal_path,
+            ),
+            strength_history_path: resolve(
+                &data.strength_history_path,
+                defaults.strength_history_path,
+            ),
+            readiness_signal_path: resolve(
+                &data.readiness_signal_path,
+                defaults.readiness_signal_path,
+            ),
+            lock_timeout: data.lock_timeout(),
+        }
+    }
+
+    /// Sink appending to the WAL, waiting up to [`Self::lock_timeout`] for its lock
+    pub fn wal_sink(&self) -> crate::wal::JsonlSink {
+        crate::wal::JsonlSink::new(&self.wal_path).lock_timeout(self.lock_timeout)
+    }
+
+    /// Directory holding the WAL and its processed archives
+    pub fn wal_dir(&self) -> &Path {
+        self.wal_path.parent().unwrap_or_else(|| Path::new("."))
+    }
+
+    /// Create every directory this store and the logs in `data_dir` write
+    /// to, and check a file can be created in each
+    ///
+    /// Fails on a read-only data directory (live USB, locked-down machine),
+    /// letting frontends fall back to not persisting anything.
+    pub fn ensure_writable(&self, data_dir: &Path) -> Result<()> {
+        let parent = |path: &Path| {
+            path.parent()
+                .unwrap_or_else(|| Path::new("."))
+                .to_path_buf()
+        };
+        let mut dirs = vec![
+            data_dir.to_path_buf(),
+            self.wal_dir().to_path_buf(),
+            parent(&self.state_path),
+        ];
+        dirs.dedup();
+        for dir in dirs {
+            std::fs::create_dir_all(&dir)?;
+            tempfile::NamedTempFile::new_in(&dir)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "fs")]
+impl Storage for FileStorage {
+    fn append_session(&mut self, session: &MicrodoseSession) -> Result<()> {
+        use crate::wal::SessionSink;
+        self.wal_sink().append(session)
+    }
+
+    fn load_recent_sessions(&self, days: i64) -> Result<Vec<SessionKind>> {
+        crate::history::load_recent_sessions(
+            &self.wal_path,
+            &self.csv_path,
+            days,
+            self.lock_timeout,
+        )
+    }
+
+    fn load_state(&self) -> Result<UserMicrodoseState> {
+        UserMicrodoseState::load(&self.state_path, self.lock_timeout)
+    }
+
+    fn save_state(&mut self, state: &UserMicrodoseState) -> Result<()> {
+        state.save(&self.state_path, self.lock_timeout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_test_session(def_id: &str, days_ago: i64) -> MicrodoseSession {
+        let at = Utc::now() - Duration::days(days_ago);
+        MicrodoseSession {
+            started_at: Some(at),
+            completed_at: Some(at),
+            actual_duration_seconds: Some(300),
+            ..MicrodoseSession::new(def_id, at)
+        }
+    }
+
+    #[test]
+    fn test_memory_storage_filters_and_sorts() {
+        let mut storage = MemoryStorage::new();
+        storage
+            .append_session(&create_test_session("old", 3))
+            .unwrap();
+        storage
+            .append_session(&create_test_session("new", 1))
+            .unwrap();
+        storage
+            .append_session(&create_test_session("too_old", 10))
+            .unwrap();
+
+        let sessions = storage.load_recent_sessions(7).unwrap();
+        assert_eq!(sessions.len(), 2);
+        assert_eq!(sessions[0].definition_id(), "new");
+        assert_eq!(sessions[1].definition_id(), "old");
+    }
+
+    #[test]
+    fn test_memory_storage_state_roundtrip() {
+        let mut storage = MemoryStorage::new();
+        let state = UserMicrodoseState {
+            last_mobility_def_id: Some("mobility_hip_cars".into()),
+            ..Default::default()
+        };
+
+        storage.save_state(&state).unwrap();
+        let loaded = storage.load_state().unwrap();
+        assert_eq!(
+            loaded.last_mobility_def_id,
+            Some("mobility_hip_cars".into())
+        );
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn test_file_storage_roundtrip() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut storage = FileStorage::new(temp_dir.path());
+
+        storage
+            .append_session(&create_test_session("emom_burpee_5m", 0))
+            .unwrap();
+        let sessions = storage.load_recent_sessions(7).unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert!(storage.wal_path.exists());
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn test_ensure_writable() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let data_dir = temp_dir.path().join("krep");
+        FileStorage::new(&data_dir)
+            .ensure_writable(&data_dir)
+            .unwrap();
+        assert!(data_dir.join("wal").is_dir());
+
+        // A data directory that can't be created (its parent is a file)
+        let blocked = temp_dir.path().join("file");
+        std::fs::write(&blocked, "").unwrap();
+        let data_dir = blocked.join("krep");
+        assert!(FileStorage::new(&data_dir)
+            .ensure_writable(&data_dir)
+            .is_err());
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn test_file_storage_path_overrides() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let synced = temp_dir.path().join("Sync").join("sessions.csv");
+        let data = crate::config::DataConfig {
+            csv_path: Some(synced.clone()),
+            state_path: Some("state/state.json".into()),
+            ..Default::default()
+        };
+        let mut storage = FileStorage::with_config(temp_dir.path(), &data);
+
+        assert_eq!(storage.csv_path, synced);
+        assert_eq!(
+            storage.state_path,
+            temp_dir.path().join("state").join("state.json")
+        );
+        assert_eq!(storage.wal_path, FileStorage::new(temp_dir.path()).wal_path);
+
+        storage.save_state(&UserMicrodoseState::default()).unwrap();
+        assert!(temp_dir.path().join("state").join("state.json").exists());
+    }
+}
@@ -0,0 +1,658 @@
+//! One handle on a data directory for frontends.
+//!
+//! [`DataStore`] owns the paths (with the `[data]` overrides), the catalog,
+//! the user state and the external strength and readiness signals, and
+//! wraps the steps the CLI and tray both take: building the prescription
+//! context, logging a completed dose (WAL, daily note, status file,
+//! progression, state), recording skips and checking achievements. Its [`Clock`] is "now" for
+//! history cutoffs, skips and the status file, and its [`Observers`] hear
+//! about each of those steps (see [`crate::events`]).
+
+use crate::achievements::{self, Achievement};
+use crate::events::{self, Observers};
+use crate::progression::PersonalBest;
+use crate::readiness::{self, ReadinessSignal};
+use crate::strength::StrengthHistory;
+use crate::{
+    progression_log, status, strength, timing, ActiveSession, Catalog, Clock, Config,
+    ExternalStrengthSignal, FileStorage, MicrodoseCategory, MicrodoseDefinition, MicrodoseSession,
+    PrescribedMicrodose, Result, SessionKind, UserContext, UserMicrodoseState,
+};
+use chrono::{DateTime, Utc};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Days of history a prescription looks at
+pub const PRESCRIPTION_HISTORY_DAYS: i64 = 7;
+
+/// A data directory opened with its config
+#[derive(Clone, Debug)]
+pub struct DataStore {
+    pub data_dir: PathBuf,
+    pub paths: FileStorage,
+    pub config: Config,
+    pub catalog: &'static Catalog,
+    pub state: UserMicrodoseState,
+    pub strength_signal: Option<ExternalStrengthSignal>,
+    /// Every strength session in the signal file and strength history
+    pub strength_history: StrengthHistory,
+    /// Today's readiness signal (`readiness.json`), if any
+    pub readiness: Option<ReadinessSignal>,
+    pub clock: Arc<dyn Clock>,
+    pub observers: Observers,
+    /// User rules and progression policies (none unless
+    /// [`DataStore::with_scripts`] is called)
+    #[cfg(feature = "scripting")]
+    pub scripts: crate::scripting::Scripts,
+}
+
+/// What [`DataStore::log`] did besides appending the session
+#[derive(Clone, Debug, Default)]
+pub struct Logged {
+    /// A new AMRAP personal best
+    pub personal_best: Option<PersonalBest>,
+    /// Observer (e.g. daily note, status file) failures; the session itself
+    /// is saved
+    pub warnings: Vec<String>,
+}
+
+impl DataStore {
+    /// Open `data_dir`, loading the state, strength signal and history and
+    /// readiness signal, on the system clock, with the daily note and status
+    /// file subscribed
+    pub fn open(data_dir: &Path, config: &Config) -> Result<Self> {
+        let paths = FileStorage::with_config(data_dir, &config.data);
+        let state = UserMicrodoseState::load(&paths.state_path, paths.lock_timeout)?;
+        let signal_sessions = strength::load_strength_sessions(&paths.strength_signal_path)?;
+        let strength_signal = signal_sessions
+            .iter()
+            .max_by_key(|s| s.last_session_at)
+            .cloned();
+        let strength_history = StrengthHistory::new(
+            strength::read_history(&paths.strength_history_path, paths.lock_timeout)?
+                .into_iter()
+                .chain(signal_sessions),
+        );
+        let readiness = readiness::load_readiness(&paths.readiness_signal_path);
+        let mut observers = Observers::default();
+        observers.subscribe(Arc::new(events::DailyNote));
+        observers.subscribe(Arc::new(events::StatusFile));
+        Ok(Self {
+            data_dir: data_dir.to_path_buf(),
+            paths,
+            config: config.clone(),
+            catalog: crate::get_default_catalog(),
+            state,
+            strength_signal,
+            strength_history,
+            readiness,
+            clock: Arc::new(crate::SystemClock),
+            observers,
+            #[cfg(feature = "scripting")]
+            scripts: Default::default(),
+        })
+    }
+
+    /// Run on `clock` instead (e.g. a [`FixedClock`](crate::FixedClock)
+    /// for a dry run at another time)
+    pub fn with_clock(self, clock: Arc<dyn Clock>) -> Self {
+        Self { clock, ..self }
+    }
+
+    /// Let `scripts` pick doses and revise progression offers
+    #[cfg(feature = "scripting")]
+    pub fn with_scripts(self, scripts: crate::scripting::Scripts) -> Self {
+        Self { scripts, ..self }
+    }
+
+    /// The current time on the store's clock
+    pub fn now(&self) -> DateTime<Utc> {
+        self.clock.now()
+    }
+
+    /// Record the signal file's sessions in the strength history log
+    pub fn record_strength_history(&self) -> Result<()> {
+        strength::record_sessions(
+            &self.paths.strength_history_path,
+            self.strength_history.sessions(),
+            self.paths.lock_timeout,
+        )?;
+        Ok(())
+    }
+
+    /// Sessions from the last `days` days, newest first
+    pub fn recent_sessions(&self, days: i64) -> Result<Vec<SessionKind>> {
+        self.sessions_before(days, self.now())
+    }
+
+    fn sessions_before(&self, days: i64, now: DateTime<Utc>) -> Result<Vec<SessionKind>> {
+        crate::load_recent_sessions_at(
+            &self.paths.wal_path,
+            &self.paths.csv_path,
+            days,
+            self.config.data.exclude_anomalies,
+            now,
+            self.paths.lock_timeout,
+        )
+    }
+
+    /// Prescription context from the config and state at `now`
+    pub fn user_context(
+        &self,
+        recent_sessions: Vec<SessionKind>,
+        now: DateTime<Utc>,
+    ) -> UserContext {
+        let config = &self.config;
+        UserContext {
+            now,
+            user_state: self.state.clone(),
+            recent_sessions,
+            external_strength: self.strength_signal.clone(),
+            strength_history: self.strength_history.clone(),
+            readiness: self.readiness.clone(),
+            equipment_available: config.equipment.available.clone(),
+            rotation: config.prescription.rotation.clone(),
+            rotation_weights: config.rotation_weights_at(now),
+            warmup: config.warmup.blocks(),
+            weekly_targets: config.weekly_targets(),
+            max_hr: config.max_hr(),
+            vo2_gap_hours: config.prescription.vo2_gap_hours,
+            gtg_friendly_only: config.gtg_friendly_only_at(&self.state, now),
+            progression_paused: config.progression_step_at(now) == 0,
+            planned_strength: config.planned_strength_at(now),
+        }
+    }
+
+    /// The next dose after `recent_sessions` (which may include skips shown
+    /// this session), optionally from `category`
+    ///
+    /// With the `scripting` feature, a script's pick comes before the
+    /// built-in rules.
+    pub fn next_prescription(
+        &self,
+        recent_sessions: &[SessionKind],
+        category: Option<MicrodoseCategory>,
+        now: DateTime<Utc>,
+    ) -> Result<PrescribedMicrodose> {
+        let ctx = self.user_context(recent_sessions.to_vec(), now);
+        #[cfg(feature = "scripting")]
+        let prescription = match self
+            .scripts
+            .prescribe(self.catalog, &ctx, category.as_ref())
+        {
+            Some(prescription) => prescription,
+            None => crate::prescribe_next(self.catalog, &ctx, category)?,
+        };
+        #[cfg(not(feature = "scripting"))]
+        let prescription = crate::prescribe_next(self.catalog, &ctx, category)?;
+        let prescription = self.reviewed(&ctx, prescription);
+        self.warn_all(self.observers.prescribed(self, &prescription));
+        Ok(prescription)
+    }
+
+    /// A novelty-weighted "surprise me" dose
+    pub fn surprise_prescription(
+        &self,
+        recent_sessions: &[SessionKind],
+        now: DateTime<Utc>,
+    ) -> Result<PrescribedMicrodose> {
+        let ctx = self.user_context(recent_sessions.to_vec(), now);
+        let prescription = crate::prescribe_surprise(self.catalog, &ctx, crate::novelty::roll())?;
+        let prescription = self.reviewed(&ctx, prescription);
+        self.warn_all(self.observers.prescribed(self, &prescription));
+        Ok(prescription)
+    }
+
+    /// The prescription `definition` would get at `now`, for logging a dose
+    /// the user picked themselves (nothing is shown, so observers aren't told)
+    pub fn prescription_for(
+        &self,
+        definition: &MicrodoseDefinition,
+        recent_sessions: &[SessionKind],
+        now: DateTime<Utc>,
+    ) -> PrescribedMicrodose {
+        let ctx = self.user_context(recent_sessions.to_vec(), now);
+        let prescription = crate::engine::prescription(self.catalog, &ctx, definition);
+        self.reviewed(&ctx, prescription)
+    }
+
+    /// Append a completed dose of `prescription` and save the state
+    ///
+    /// Also applies the prescription's [`StateDelta`](crate::StateDelta)
+    /// (progression state, AMRAP personal best, mobility rotation), then
+    /// tells the observers (daily note, status file). Progression events
+    /// and achievements are left to [`DataStore::record_progression`] and
+    /// [`DataStore::check_achievements`], so a frontend can progress first.
+    pub fn log(
+        &mut self,
+        session: &MicrodoseSession,
+        prescription: &PrescribedMicrodose,
+    ) -> Result<Logged> {
+        #[cfg_attr(not(feature = "csv-archive"), allow(unused_variables))]
+        let appended = self.paths.wal_sink().append_range(session)?;
+        // A cache left behind is only stale: `krep stats` rebuilds it
+        #[cfg(feature = "csv-archive")]
+        if let Err(e) = crate::stats_cache::record_log(
+            &self.paths.csv_path,
+            appended,
+            session,
+            prescription.definition.category.as_str(),
+            &self.config.timezone(),
+        ) {
+            tracing::warn!("Failed to update the stats cache: {}", e);
+        }
+        let personal_best = prescription.state_delta(session).apply(&mut self.state);
+        if self
+            .state
+            .active_session
+            .as_ref()
+            .is_some_and(|active| active.definition_id == session.definition_id)
+        {
+            self.state.active_session = None;
+        }
+        self.save_state()?;
+
+        // The session and state are saved, so observers only warn
+        Ok(Logged {
+            personal_best,
+            warnings: self
+                .observers
+                .logged(self, session, &prescription.definition),
+        })
+    }
+
+    /// Record a skipped prescription for `krep stats --timing`
+    pub fn skip(&self, definition_id: &str, reason: Option<timing::SkipReason>) -> Result<()> {
+        self.append_skip(definition_id, reason, false)
+    }
+
+    /// Record a prescription whose popup was ignored until it closed
+    pub fn soft_skip(&self, definition_id: &str) -> Result<()> {
+        self.append_skip(definition_id, None, true)
+    }
+
+    fn append_skip(
+        &self,
+        definition_id: &str,
+        reason: Option<timing::SkipReason>,
+        soft: bool,
+    ) -> Result<()> {
+        let skip = timing::SkipEvent {
+            skipped_at: self.now(),
+            definition_id: definition_id.to_string(),
+            reason,
+            soft,
+        };
+        timing::append_skip(
+            &self.data_dir.join(timing::SKIP_LOG_FILE),
+            &skip,
+            self.paths.lock_timeout,
+        )?;
+        self.warn_all(self.observers.skipped(self, &skip));
+        Ok(())
+    }
+
+    /// Mark a dose as started (its timer is running), saving the state
+    ///
+    /// A different dose still in progress is recorded as abandoned and
+    /// returned; restarting the same dose just restarts its clock.
+    pub fn start(&mut self, definition_id: &str) -> Result<Option<timing::AbandonEvent>> {
+        let now = self.now();
+        let abandoned = match &self.state.active_session {
+            Some(active) if active.definition_id != definition_id => self.abandon_active(now)?,
+            _ => None,
+        };
+        self.state.active_session = Some(ActiveSession::new(definition_id, now));
+        self.save_state()?;
+        Ok(abandoned)
+    }
+
+    /// Pause the dose in progress (the phone rang), saving the state;
+    /// false if there is none or it is already paused
+    pub fn pause(&mut self) -> Result<bool> {
+        let now = self.now();
+        self.update_active(|active| active.pause(now))
+    }
+
+    /// Resume the paused dose in progress, saving the state; false if there
+    /// is none or it isn't paused
+    pub fn resume(&mut self) -> Result<bool> {
+        let now = self.now();
+        self.update_active(|active| active.resume(now))
+    }
+
+    fn update_active(&mut self, update: impl FnOnce(&mut ActiveSession) -> bool) -> Result<bool> {
+        let changed = self.state.active_session.as_mut().is_some_and(update);
+        if changed {
+            self.save_state()?;
+        }
+        Ok(changed)
+    }
+
+    /// The dose in progress, if it is `definition_id`
+    pub fn active_session(&self, definition_id: &str) -> Option<&ActiveSession> {
+        self.state
+            .active_session
+            .as_ref()
+            .filter(|active| active.definition_id == definition_id)
+    }
+
+    /// `session` timed by the dose in progress, if it is the same dose:
+    /// from its start to `finished_at`, less any pauses
+    pub fn timed(&self, session: MicrodoseSession, finished_at: DateTime<Utc>) -> MicrodoseSession {
+        match self.active_session(&session.definition_id) {
+            Some(active) => {
+                let paused = active.paused_seconds_at(finished_at);
+                session.timed(active.started_at, finished_at, paused)
+            }
+            None => session,
+        }
+    }
+
+    /// Record the dose in progress as abandoned once it has run for more than
+    /// `[session] abandon_after_minutes`, saving the state
+    ///
+    /// Time spent paused doesn't count, so a paused dose is never abandoned.
+    pub fn abandon_stale(&mut self) -> Result<Option<timing::AbandonEvent>> {
+        let now = self.now();
+        let timeout = self.config.session.abandon_after();
+        if self
+            .state
+            .active_session
+            .as_ref()
+            .is_none_or(|active| i64::from(active.elapsed_seconds_at(now)) < timeout.num_seconds())
+        {
+            return Ok(None);
+        }
+        let abandoned = self.abandon_active(now)?;
+        self.save_state()?;
+        Ok(abandoned)
+    }
+
+    /// Move the dose in progress to the abandon log (the state is not saved)
+    fn abandon_active(&mut self, now: DateTime<Utc>) -> Result<Option<timing::AbandonEvent>> {
+        let Some(active) = self.state.active_session.take() else {
+            return Ok(None);
+        };
+        let event = timing::AbandonEvent {
+            definition_id: active.definition_id,
+            started_at: active.started_at,
+            abandoned_at: now,
+        };
+        timing::append_abandon(
+            &self.data_dir.join(timing::ABANDON_LOG_FILE),
+            &event,
+            self.paths.lock_timeout,
+        )?;
+        self.warn_all(self.observers.abandoned(self, &event));
+        Ok(Some(event))
+    }
+
+    pub fn save_state(&self) -> Result<()> {
+        self.state
+            .save(&self.paths.state_path, self.paths.lock_timeout)
+    }
+
+    /// Append the progression changes since `before` to the audit log
+    pub fn record_progression(
+        &self,
+        before: &UserMicrodoseState,
+        now: DateTime<Utc>,
+    ) -> Result<()> {
+        let changes = progression_log::changes(before, &self.state, now);
+        progression_log::append_events(
+            &self.data_dir.join(progression_log::PROGRESSION_LOG_FILE),
+            &changes,
+            self.paths.lock_timeout,
+        )?;
+        self.warn_all(self.observers.progressed(self, &changes));
+        Ok(())
+    }
+
+    /// Unlock anything newly earned, saving the state if there is
+    pub fn check_achievements(&mut self, now: DateTime<Utc>) -> Result<Vec<&'static Achievement>> {
+        let unlocked =
+            achievements::check_data_dir(&self.data_dir, &mut self.state, &self.config, now)?;
+        if !unlocked.is_empty() {
+            self.save_state()?;
+        }
+        Ok(unlocked)
+    }
+
+    /// The status document at `now`
+    pub fn status(&self, now: DateTime<Utc>) -> Result<status::Status> {
+        let sessions = self.sessions_before(status::STATUS_HISTORY_DAYS, now)?;
+        Ok(status::build(
+            &sessions,
+            self.state.weekly_goal.as_ref(),
+            &self.config.off_days(&self.state),
+            &self.config.status,
+            &self.config.timezone(),
+            now,
+        ))
+    }
+
+    /// Refresh `[status] file`, if one is configured
+    pub fn write_status_file(&self) -> Result<()> {
+        match &self.config.status.file {
+            Some(path) => status::write(path, &self.status(self.now())?),
+            None => Ok(()),
+        }
+    }
+
+    /// `prescription` with the scripts' progression policies applied
+    #[cfg_attr(not(feature = "scripting"), allow(unused_variables, unused_mut))]
+    fn reviewed(
+        &self,
+        ctx: &UserContext,
+        mut prescription: PrescribedMicrodose,
+    ) -> PrescribedMicrodose {
+        #[cfg(feature = "scripting")]
+        self.scripts
+            .review_progression(self.catalog, ctx, &mut prescription);
+        prescription
+    }
+
+    /// Log observer failures that have no caller to report them to
+    fn warn_all(&self, warnings: Vec<String>) {
+        for warning in warnings {
+            tracing::warn!("{}", warning);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store(dir: &Path) -> DataStore {
+        DataStore::open(dir, &Config::default()).unwrap()
+    }
+
+    #[test]
+    fn test_log_and_reopen() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut data = store(temp_dir.path());
+        let now = Utc::now();
+
+        let prescription = data
+            .next_prescription(&[], Some(MicrodoseCategory::Mobility), now)
+            .unwrap();
+        let session = prescription.done_as_prescribed(now);
+        let logged = data.log(&session, &prescription).unwrap();
+        assert!(logged.warnings.is_empty());
+        data.skip(&prescription.definition.id, None).unwrap();
+
+        // Everything went to disk
+        let data = store(temp_dir.path());
+        let recent = data.recent_sessions(PRESCRIPTION_HISTORY_DAYS).unwrap();
+        assert_eq!(recent.len(), 1);
+        assert_eq!(
+            data.state.last_mobility_def_id.as_deref(),
+            Some(prescription.definition.id.as_str())
+        );
+        assert!(data
+            .state
+            .progressions
+            .contains_key(&prescription.definition.id));
+        let skips = timing::read_skips(
+            &temp_dir.path().join(timing::SKIP_LOG_FILE),
+            crate::lock::DEFAULT_TIMEOUT,
+        )
+        .unwrap();
+        assert_eq!(skips.len(), 1);
+        assert_eq!(data.status(now).unwrap().sessions_today, 1);
+    }
+
+    #[test]
+    fn test_signals_read_from_configured_paths() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let exports = temp_dir.path().join("exports");
+        std::fs::create_dir_all(&exports).unwrap();
+        std::fs::write(
+            exports.join("ring.json"),
+            r#"{"measured_at": "2024-01-15T06:30:00Z", "score": 72}"#,
+        )
+        .unwrap();
+        let mut config = Config::default();
+        config.data.readiness_signal_path = Some(exports.join("ring.json"));
+        config.data.strength_history_path = Some("exports/lifting.jsonl".into());
+
+        let data = DataStore::open(temp_dir.path(), &config).unwrap();
+        assert_eq!(data.readiness.unwrap().score, Some(72));
+        assert_eq!(
+            data.paths.strength_history_path,
+            exports.join("lifting.jsonl")
+        );
+    }
+
+    #[test]
+    fn test_fixed_clock_replays_history() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut data = store(temp_dir.path());
+        let now = Utc::now();
+        let prescription = data
+            .next_prescription(&[], Some(MicrodoseCategory::Mobility), now)
+            .unwrap();
+        data.log(&prescription.done_as_prescribed(now), &prescription)
+            .unwrap();
+
+        // An hour earlier the dose hadn't happened yet
+        let earlier = crate::FixedClock(now - chrono::Duration::hours(1));
+        let data = store(temp_dir.path()).with_clock(std::sync::Arc::new(earlier));
+        assert_eq!(data.now(), earlier.0);
+        assert!(data
+            .recent_sessions(PRESCRIPTION_HISTORY_DAYS)
+            .unwrap()
+            .is_empty());
+        data.soft_skip(&prescription.definition.id).unwrap();
+        let skips = timing::read_skips(
+            &temp_dir.path().join(timing::SKIP_LOG_FILE),
+            crate::lock::DEFAULT_TIMEOUT,
+        )
+        .unwrap();
+        assert_eq!(skips[0].skipped_at, earlier.0);
+    }
+
+    #[test]
+    fn test_started_dose_is_abandoned_after_timeout() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let start = Utc::now() - chrono::Duration::hours(3);
+        let at = |minutes| {
+            let clock = crate::FixedClock(start + chrono::Duration::minutes(minutes));
+            store(temp_dir.path()).with_clock(Arc::new(clock))
+        };
+        let abandon_log = temp_dir.path().join(timing::ABANDON_LOG_FILE);
+
+        // Logging the started dose finishes it
+        let mut data = at(0);
+        let prescription = data
+            .next_prescription(&[], Some(MicrodoseCategory::Mobility), start)
+            .unwrap();
+        let id = prescription.definition.id.clone();
+        assert!(data.start(&id).unwrap().is_none());
+        data.log(&prescription.done_as_prescribed(start), &prescription)
+            .unwrap();
+        assert!(at(0).state.active_session.is_none());
+
+        // Still within the timeout, then past it
+        at(10).start(&id).unwrap();
+        assert!(at(50).abandon_stale().unwrap().is_none());
+        let abandoned = at(75).abandon_stale().unwrap().unwrap();
+        assert_eq!(abandoned.definition_id, id);
+        assert_eq!(abandoned.started_at, start + chrono::Duration::minutes(10));
+        assert!(at(80).state.active_session.is_none());
+
+        // Starting another dose abandons the one in progress
+        at(90).start(&id).unwrap();
+        let replaced = at(95).start("gtg_pullup_band").unwrap().unwrap();
+        assert_eq!(replaced.definition_id, id);
+
+        let abandons = timing::read_abandons(&abandon_log, crate::lock::DEFAULT_TIMEOUT).unwrap();
+        assert_eq!(abandons.len(), 2);
+        // Abandoned doses are neither sessions nor skips
+        assert_eq!(at(100).recent_sessions(1).unwrap().len(), 1);
+        assert!(!temp_dir.path().join(timing::SKIP_LOG_FILE).exists());
+    }
+
+    #[test]
+    fn test_pauses_are_left_out_of_the_duration() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let start = Utc::now() - chrono::Duration::hours(1);
+        let at = |seconds| {
+            let clock = crate::FixedClock(start + chrono::Duration::seconds(seconds));
+            store(temp_dir.path()).with_clock(Arc::new(clock))
+        };
+
+        let mut data = at(0);
+        let prescription = data
+            .next_prescription(&[], Some(MicrodoseCategory::Mobility), start)
+            .unwrap();
+        let id = prescription.definition.id.clone();
+        assert!(!data.pause().unwrap());
+        data.start(&id).unwrap();
+
+        // The phone rang a minute in and the call took two
+        assert!(at(60).pause().unwrap());
+        assert!(!at(90).pause().unwrap());
+        assert!(at(180).resume().unwrap());
+        assert!(!at(190).resume().unwrap());
+        // Paused again when finished: that pause doesn't count either
+        at(240).pause().unwrap();
+
+        let data = at(300);
+        let active = data.active_session(&id).unwrap();
+        assert_eq!(active.elapsed_seconds_at(data.now()), 120);
+        let session = data.timed(prescription.done_as_prescribed(data.now()), data.now());
+        assert_eq!(session.started_at, Some(start));
+        assert_eq!(session.actual_duration_seconds, Some(120));
+        assert_eq!(session.paused_seconds, Some(180));
+    }
+
+    #[test]
+    fn test_paused_dose_is_not_abandoned() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let start = Utc::now() - chrono::Duration::hours(3);
+        let at = |minutes| {
+            let clock = crate::FixedClock(start + chrono::Duration::minutes(minutes));
+            store(temp_dir.path()).with_clock(Arc::new(clock))
+        };
+
+        let mut data = at(0);
+        let prescription = data
+            .next_prescription(&[], Some(MicrodoseCategory::Mobility), start)
+            .unwrap();
+        let id = prescription.definition.id.clone();
+        data.start(&id).unwrap();
+
+        // Paused ten minutes in, for longer than the timeout
+        assert!(at(10).pause().unwrap());
+        assert!(at(90).abandon_stale().unwrap().is_none());
+        assert!(at(120).resume().unwrap());
+
+        // Only the running time counts toward the timeout
+        assert!(at(160).abandon_stale().unwrap().is_none());
+        let abandoned = at(175).abandon_stale().unwrap().unwrap();
+        assert_eq!(abandoned.definition_id, id);
+    }
+}
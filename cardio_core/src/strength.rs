@@ -2,27 +2,71 @@
 //!
 //! This module loads strength training information from an external file
 //! to inform microdose prescription decisions.
+//!
+//! The signal file holds the latest session, or a list of sessions
+//! (`{"sessions": [...]}` or a bare array). Every session read is also
+//! appended to a history log (`strength_history.jsonl` in the data
+//! directory), so a [`StrengthHistory`] can answer more than "what was the
+//! last workout": the engine asks it for a lower-body session in the last
+//! day, and `krep stats --strength` for sessions per week and to compare
+//! skip rate and category mix on strength days against days without
+//! strength.
 
-use crate::{ExternalStrengthSignal, Result, StrengthSessionType};
-use chrono::{DateTime, Utc};
-use serde::Deserialize;
+use crate::timing::SkipEvent;
+use crate::{Catalog, ExternalStrengthSignal, LocalTz, Result, SessionKind, StrengthSessionType};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+#[cfg(feature = "fs")]
+use std::fs::{File, OpenOptions};
+#[cfg(feature = "fs")]
+use std::io::{BufRead, BufReader, Write};
 use std::path::Path;
 
-/// Strength signal file format (matches external system output)
+/// File name of the strength history log within the data directory
+pub const STRENGTH_HISTORY_FILE: &str = "strength_history.jsonl";
+
+/// Label for days without a strength session
+pub const NO_STRENGTH_LABEL: &str = "none";
+
+/// Fewest doses shown on a day type before it is called out as a pattern
+pub const MIN_PATTERN_DOSES: u32 = 3;
+
+/// One session in the signal file (matches external system output)
 #[derive(Debug, Deserialize)]
-struct StrengthSignalFile {
+struct StrengthSignalEntry {
+    #[serde(alias = "at")]
     last_session_at: DateTime<Utc>,
     session_type: String,
 }
 
+/// Strength signal file format: the latest session or a list of them
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum StrengthSignalFile {
+    Latest(StrengthSignalEntry),
+    Sessions { sessions: Vec<StrengthSignalEntry> },
+    List(Vec<StrengthSignalEntry>),
+}
+
 /// Load external strength training signal from a JSON file
 ///
-/// Returns None if file doesn't exist (user hasn't logged strength training).
-/// Returns an error if file exists but is malformed.
+/// The latest of the file's sessions. Returns None if file doesn't exist
+/// (user hasn't logged strength training) or can't be read.
 pub fn load_external_strength(path: &Path) -> Result<Option<ExternalStrengthSignal>> {
+    Ok(load_strength_sessions(path)?
+        .into_iter()
+        .max_by_key(|s| s.last_session_at))
+}
+
+/// Every session in the strength signal file, in file order
+///
+/// Empty if the file doesn't exist; a file that can't be read or parsed is
+/// logged and ignored.
+pub fn load_strength_sessions(path: &Path) -> Result<Vec<ExternalStrengthSignal>> {
     if !path.exists() {
         tracing::debug!("No strength signal file found at {:?}", path);
-        return Ok(None);
+        return Ok(Vec::new());
     }
 
     let contents = match std::fs::read_to_string(path) {
@@ -33,7 +77,7 @@ pub fn load_external_strength(path: &Path) -> Result<Option<ExternalStrengthSign
                 path,
                 e
             );
-            return Ok(None);
+            return Ok(Vec::new());
         }
     };
 
@@ -45,22 +89,104 @@ pub fn load_external_strength(path: &Path) -> Result<Option<ExternalStrengthSign
                 path,
                 e
             );
-            return Ok(None);
+            return Ok(Vec::new());
         }
     };
 
-    let session_type = parse_session_type(&file.session_type);
-
+    let entries = match file {
+        StrengthSignalFile::Latest(entry) => vec![entry],
+        StrengthSignalFile::Sessions { sessions } | StrengthSignalFile::List(sessions) => sessions,
+    };
+    let sessions: Vec<ExternalStrengthSignal> = entries
+        .into_iter()
+        .map(|entry| ExternalStrengthSignal {
+            last_session_at: entry.last_session_at,
+            session_type: parse_session_type(&entry.session_type),
+        })
+        .collect();
     tracing::info!(
-        "Loaded strength signal: {:?} at {}",
-        session_type,
-        file.last_session_at
+        "Loaded {} strength session(s) from {:?}",
+        sessions.len(),
+        path
     );
+    Ok(sessions)
+}
+
+/// Strength sessions, oldest first and one per start time, with the
+/// queries the engine and stats ask of them
+#[derive(Clone, Debug, Default)]
+pub struct StrengthHistory {
+    sessions: Vec<ExternalStrengthSignal>,
+}
+
+impl StrengthHistory {
+    /// Sort `sessions` and drop repeats of the same start time
+    pub fn new(sessions: impl IntoIterator<Item = ExternalStrengthSignal>) -> Self {
+        let mut sessions: Vec<_> = sessions.into_iter().collect();
+        sessions.sort_by_key(|s| s.last_session_at);
+        sessions.dedup_by_key(|s| s.last_session_at);
+        Self { sessions }
+    }
+
+    /// Oldest first
+    pub fn sessions(&self) -> &[ExternalStrengthSignal] {
+        &self.sessions
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sessions.is_empty()
+    }
+
+    /// The latest session at or before `now`
+    pub fn latest(&self, now: DateTime<Utc>) -> Option<&ExternalStrengthSignal> {
+        self.sessions
+            .iter()
+            .rev()
+            .find(|s| s.last_session_at <= now)
+    }
+
+    /// The latest `session_type` session less than `window` before `now`
+    /// (e.g. lower body in the last 24 hours)
+    pub fn last_within(
+        &self,
+        session_type: &StrengthSessionType,
+        window: Duration,
+        now: DateTime<Utc>,
+    ) -> Option<&ExternalStrengthSignal> {
+        last_within(&self.sessions, session_type, window, now)
+    }
+
+    /// Sessions from `since` up to `now`
+    pub fn count_between(&self, since: DateTime<Utc>, now: DateTime<Utc>) -> usize {
+        self.sessions
+            .iter()
+            .filter(|s| s.last_session_at >= since && s.last_session_at <= now)
+            .count()
+    }
+
+    /// Average sessions per week from `since` up to `now`
+    pub fn per_week(&self, since: DateTime<Utc>, now: DateTime<Utc>) -> f64 {
+        let weeks = (now - since).num_seconds() as f64 / Duration::weeks(1).num_seconds() as f64;
+        if weeks <= 0.0 {
+            return 0.0;
+        }
+        self.count_between(since, now) as f64 / weeks
+    }
+}
 
-    Ok(Some(ExternalStrengthSignal {
-        last_session_at: file.last_session_at,
-        session_type,
-    }))
+/// The latest `session_type` session among `sessions` less than `window`
+/// before `now` (future sessions don't count)
+pub fn last_within<'a>(
+    sessions: impl IntoIterator<Item = &'a ExternalStrengthSignal>,
+    session_type: &StrengthSessionType,
+    window: Duration,
+    now: DateTime<Utc>,
+) -> Option<&'a ExternalStrengthSignal> {
+    sessions
+        .into_iter()
+        .filter(|s| &s.session_type == session_type)
+        .filter(|s| s.last_session_at <= now && now - s.last_session_at < window)
+        .max_by_key(|s| s.last_session_at)
 }
 
 /// Parse session type string into enum
@@ -73,9 +199,229 @@ fn parse_session_type(s: &str) -> StrengthSessionType {
     }
 }
 
+/// Append a signal to the strength history unless it is already recorded
+///
+/// Returns whether the signal was new. Signals are keyed by session time, so
+/// re-reading an unchanged signal file is a no-op. Locks are taken within
+/// `timeout`.
+#[cfg(feature = "fs")]
+pub fn record_history(
+    path: &Path,
+    signal: &ExternalStrengthSignal,
+    timeout: std::time::Duration,
+) -> Result<bool> {
+    Ok(record_sessions(path, std::slice::from_ref(signal), timeout)? == 1)
+}
+
+/// Append the `signals` not already in the strength history, returning how
+/// many were new (taking locks within `timeout`)
+#[cfg(feature = "fs")]
+pub fn record_sessions(
+    path: &Path,
+    signals: &[ExternalStrengthSignal],
+    timeout: std::time::Duration,
+) -> Result<usize> {
+    let recorded: HashSet<DateTime<Utc>> = read_history(path, timeout)?
+        .iter()
+        .map(|s| s.last_session_at)
+        .collect();
+    let mut new: Vec<&ExternalStrengthSignal> = signals
+        .iter()
+        .filter(|s| !recorded.contains(&s.last_session_at))
+        .collect();
+    new.sort_by_key(|s| s.last_session_at);
+    new.dedup_by_key(|s| s.last_session_at);
+    if new.is_empty() {
+        return Ok(0);
+    }
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    crate::lock::lock_exclusive_within(&file, path, timeout)?;
+
+    let mut writer = std::io::BufWriter::new(&file);
+    for signal in &new {
+        let line = serde_json::to_string(signal)?;
+        writer.write_all(line.as_bytes())?;
+        writer.write_all(b"\n")?;
+        tracing::debug!("Recorded strength session at {}", signal.last_session_at);
+    }
+    writer.flush()?;
+    drop(writer);
+
+    fs2::FileExt::unlock(&file)?;
+    Ok(new.len())
+}
+
+/// Read the strength history, skipping malformed lines (with a shared
+/// lock, taken within `timeout`)
+#[cfg(feature = "fs")]
+pub fn read_history(
+    path: &Path,
+    timeout: std::time::Duration,
+) -> Result<Vec<ExternalStrengthSignal>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let file = File::open(path)?;
+    crate::lock::lock_shared_within(&file, path, timeout)?;
+
+    let mut history = Vec::new();
+    for (line_num, line) in BufReader::new(&file).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<ExternalStrengthSignal>(&line) {
+            Ok(signal) => history.push(signal),
+            Err(e) => {
+                tracing::warn!(
+                    "Failed to parse strength history at line {}: {}",
+                    line_num + 1,
+                    e
+                );
+            }
+        }
+    }
+
+    fs2::FileExt::unlock(&file)?;
+    Ok(history)
+}
+
+/// Doses shown on one type of day (lower, upper, ..., or no strength)
+#[derive(Clone, Debug, Default, Serialize, PartialEq, Eq)]
+pub struct StrengthDayStats {
+    /// Session type label, or [`NO_STRENGTH_LABEL`]
+    pub day_type: String,
+    /// Days of this type (strength days count even without doses)
+    pub days: u32,
+    pub completed: u32,
+    pub skipped: u32,
+    /// Completed doses per category
+    pub categories: BTreeMap<String, u32>,
+}
+
+impl StrengthDayStats {
+    pub fn shown(&self) -> u32 {
+        self.completed + self.skipped
+    }
+
+    /// Share of shown doses that were skipped (0.0 with nothing shown)
+    pub fn skip_rate(&self) -> f64 {
+        if self.shown() == 0 {
+            return 0.0;
+        }
+        f64::from(self.skipped) / f64::from(self.shown())
+    }
+}
+
+/// Short label for a strength session type (`lower`, `upper`, `full` or the
+/// signal's own name)
+pub fn day_label(session_type: &StrengthSessionType) -> &str {
+    match session_type {
+        StrengthSessionType::Lower => "lower",
+        StrengthSessionType::Upper => "upper",
+        StrengthSessionType::Full => "full",
+        StrengthSessionType::Other(other) => other,
+    }
+}
+
+/// Movement tags a session trains, spared before a planned session so the
+/// muscles arrive fresh
+pub fn trained_areas(session_type: &StrengthSessionType) -> &'static [&'static str] {
+    match session_type {
+        StrengthSessionType::Lower => &["legs", "posterior_chain"],
+        StrengthSessionType::Upper => &["upper_body", "pull"],
+        StrengthSessionType::Full => &["legs", "posterior_chain", "upper_body", "pull"],
+        StrengthSessionType::Other(_) => &[],
+    }
+}
+
+/// Skip rate and category mix per strength day type, since `since`
+///
+/// Each local day takes the type of its latest strength session. Days
+/// without one are grouped under [`NO_STRENGTH_LABEL`], which is always
+/// listed last; those only count when a dose was shown.
+pub fn adherence_by_strength_day(
+    catalog: &Catalog,
+    sessions: &[SessionKind],
+    skips: &[SkipEvent],
+    history: &[ExternalStrengthSignal],
+    tz: &LocalTz,
+    since: DateTime<Utc>,
+) -> Vec<StrengthDayStats> {
+    let mut sorted: Vec<&ExternalStrengthSignal> = history
+        .iter()
+        .filter(|s| s.last_session_at >= since)
+        .collect();
+    sorted.sort_by_key(|s| s.last_session_at);
+    let day_types: HashMap<NaiveDate, &str> = sorted
+        .into_iter()
+        .map(|s| (tz.date_of(s.last_session_at), day_label(&s.session_type)))
+        .collect();
+
+    let label_of = |date: NaiveDate| day_types.get(&date).copied().unwrap_or(NO_STRENGTH_LABEL);
+
+    let mut by_type: BTreeMap<&str, StrengthDayStats> = BTreeMap::new();
+    let mut dates: HashMap<&str, HashSet<NaiveDate>> = HashMap::new();
+    for (date, label) in &day_types {
+        by_type.entry(*label).or_default();
+        dates.entry(*label).or_default().insert(*date);
+    }
+
+    for session in sessions.iter().filter_map(|s| s.as_real()) {
+        if session.performed_at < since {
+            continue;
+        }
+        let date = tz.date_of(session.performed_at);
+        dates.entry(label_of(date)).or_default().insert(date);
+        let stats = by_type.entry(label_of(date)).or_default();
+        stats.completed += 1;
+        if let Some(definition) = catalog.definition(&session.definition_id) {
+            *stats
+                .categories
+                .entry(definition.category.as_str().to_string())
+                .or_default() += 1;
+        }
+    }
+    for skip in skips.iter().filter(|s| s.skipped_at >= since) {
+        let date = tz.date_of(skip.skipped_at);
+        dates.entry(label_of(date)).or_default().insert(date);
+        by_type.entry(label_of(date)).or_default().skipped += 1;
+    }
+    for (label, stats) in by_type.iter_mut() {
+        stats.day_type = label.to_string();
+        stats.days = dates.get(label).map_or(0, |d| d.len() as u32);
+    }
+
+    let none = by_type.remove(NO_STRENGTH_LABEL);
+    by_type.into_values().chain(none).collect()
+}
+
+/// The strength day type with the highest skip rate, if it stands out
+///
+/// Needs [`MIN_PATTERN_DOSES`] shown doses and a higher skip rate than days
+/// without strength (or any skips at all when there are none).
+pub fn worst_strength_day(stats: &[StrengthDayStats]) -> Option<&StrengthDayStats> {
+    let baseline = stats
+        .iter()
+        .find(|s| s.day_type == NO_STRENGTH_LABEL)
+        .filter(|s| s.shown() >= MIN_PATTERN_DOSES)
+        .map_or(0.0, |s| s.skip_rate());
+    stats
+        .iter()
+        .filter(|s| s.day_type != NO_STRENGTH_LABEL && s.shown() >= MIN_PATTERN_DOSES)
+        .filter(|s| s.skip_rate() > baseline)
+        .max_by(|a, b| a.skip_rate().total_cmp(&b.skip_rate()))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use chrono::{Datelike, TimeZone};
 
     #[test]
     fn test_load_strength_signal() {
@@ -144,4 +490,184 @@ mod tests {
         let signal = load_external_strength(&signal_path).unwrap().unwrap();
         assert_eq!(signal.session_type, StrengthSessionType::Upper);
     }
+
+    #[test]
+    fn test_load_session_list() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let signal_path = temp_dir.path().join("strength.json");
+
+        let json = r#"{"sessions": [
+            {"at": "2024-01-15T10:30:00Z", "session_type": "lower"},
+            {"at": "2024-01-17T10:30:00Z", "session_type": "upper"},
+            {"at": "2024-01-16T10:30:00Z", "session_type": "full"}
+        ]}"#;
+        std::fs::write(&signal_path, json).unwrap();
+        assert_eq!(load_strength_sessions(&signal_path).unwrap().len(), 3);
+        let latest = load_external_strength(&signal_path).unwrap().unwrap();
+        assert_eq!(latest.session_type, StrengthSessionType::Upper);
+
+        let json = r#"[{"last_session_at": "2024-01-15T10:30:00Z", "session_type": "lower"}]"#;
+        std::fs::write(&signal_path, json).unwrap();
+        assert_eq!(load_strength_sessions(&signal_path).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_history_queries() {
+        let history = StrengthHistory::new(vec![
+            signal(4, StrengthSessionType::Lower),
+            signal(11, StrengthSessionType::Upper),
+            signal(10, StrengthSessionType::Lower),
+            signal(10, StrengthSessionType::Lower),
+            signal(20, StrengthSessionType::Full),
+        ]);
+        assert_eq!(history.sessions().len(), 4);
+        let now = Utc.with_ymd_and_hms(2024, 3, 11, 12, 0, 0).unwrap();
+
+        // The upper session this morning doesn't hide yesterday's legs
+        let lower = history
+            .last_within(&StrengthSessionType::Lower, Duration::hours(36), now)
+            .unwrap();
+        assert_eq!(
+            lower.last_session_at,
+            signal(10, StrengthSessionType::Lower).last_session_at
+        );
+        assert!(history
+            .last_within(&StrengthSessionType::Lower, Duration::hours(24), now)
+            .is_none());
+        // Planned sessions ahead of now are ignored
+        assert_eq!(
+            history.latest(now).unwrap().session_type,
+            StrengthSessionType::Upper
+        );
+
+        let since = now - Duration::weeks(2);
+        assert_eq!(history.count_between(since, now), 3);
+        assert_eq!(history.per_week(since, now), 1.5);
+        assert_eq!(history.per_week(now, now), 0.0);
+    }
+
+    fn signal(day: u32, session_type: StrengthSessionType) -> ExternalStrengthSignal {
+        ExternalStrengthSignal {
+            last_session_at: Utc.with_ymd_and_hms(2024, 3, day, 7, 0, 0).unwrap(),
+            session_type,
+        }
+    }
+
+    fn done_on(day: u32, def_id: &str) -> SessionKind {
+        SessionKind::Real(crate::MicrodoseSession {
+            actual_duration_seconds: Some(300),
+            ..crate::MicrodoseSession::new(
+                def_id,
+                Utc.with_ymd_and_hms(2024, 3, day, 12, 0, 0).unwrap(),
+            )
+        })
+    }
+
+    fn skipped_on(day: u32) -> SkipEvent {
+        SkipEvent {
+            skipped_at: Utc.with_ymd_and_hms(2024, 3, day, 15, 0, 0).unwrap(),
+            definition_id: "emom_burpee_5m".into(),
+            reason: None,
+            soft: false,
+        }
+    }
+
+    #[test]
+    fn test_adherence_by_strength_day() {
+        let catalog = crate::build_default_catalog();
+        let tz = LocalTz::from_name(Some("UTC")).unwrap();
+        let since = Utc.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap();
+        let history = vec![
+            signal(4, StrengthSessionType::Lower),
+            signal(6, StrengthSessionType::Lower),
+            signal(5, StrengthSessionType::Upper),
+        ];
+        let sessions = vec![
+            done_on(4, "gtg_pullup_band"),
+            done_on(6, "gtg_pullup_band"),
+            done_on(5, "emom_burpee_5m"),
+            done_on(7, "emom_burpee_5m"),
+            done_on(7, "emom_kb_swing_5m"),
+            done_on(8, "emom_burpee_5m"),
+        ];
+        let skips = vec![skipped_on(4), skipped_on(4), skipped_on(6), skipped_on(8)];
+
+        let stats = adherence_by_strength_day(&catalog, &sessions, &skips, &history, &tz, since);
+        let labels: Vec<&str> = stats.iter().map(|s| s.day_type.as_str()).collect();
+        assert_eq!(labels, vec!["lower", "upper", NO_STRENGTH_LABEL]);
+
+        let lower = &stats[0];
+        assert_eq!((lower.days, lower.completed, lower.skipped), (2, 2, 3));
+        assert_eq!(lower.skip_rate(), 0.6);
+        assert_eq!(lower.categories.get("gtg"), Some(&2));
+
+        let none = &stats[2];
+        assert_eq!((none.days, none.completed, none.skipped), (2, 3, 1));
+
+        // Upper has too few doses to count as a pattern
+        assert_eq!(worst_strength_day(&stats).unwrap().day_type, "lower");
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn test_record_history_deduplicates() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join(STRENGTH_HISTORY_FILE);
+
+        assert!(record_history(
+            &path,
+            &signal(4, StrengthSessionType::Lower),
+            crate::lock::DEFAULT_TIMEOUT
+        )
+        .unwrap());
+        assert!(!record_history(
+            &path,
+            &signal(4, StrengthSessionType::Lower),
+            crate::lock::DEFAULT_TIMEOUT
+        )
+        .unwrap());
+        assert!(record_history(
+            &path,
+            &signal(5, StrengthSessionType::Full),
+            crate::lock::DEFAULT_TIMEOUT
+        )
+        .unwrap());
+
+        let history = read_history(&path, crate::lock::DEFAULT_TIMEOUT).unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[1].session_type, StrengthSessionType::Full);
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn test_record_sessions_appends_only_new() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join(STRENGTH_HISTORY_FILE);
+
+        let batch = vec![
+            signal(6, StrengthSessionType::Upper),
+            signal(4, StrengthSessionType::Lower),
+            signal(4, StrengthSessionType::Lower),
+        ];
+        assert_eq!(
+            record_sessions(&path, &batch, crate::lock::DEFAULT_TIMEOUT).unwrap(),
+            2
+        );
+        let more = vec![
+            signal(4, StrengthSessionType::Lower),
+            signal(8, StrengthSessionType::Full),
+        ];
+        assert_eq!(
+            record_sessions(&path, &more, crate::lock::DEFAULT_TIMEOUT).unwrap(),
+            1
+        );
+        assert_eq!(
+            record_sessions(&path, &[], crate::lock::DEFAULT_TIMEOUT).unwrap(),
+            0
+        );
+
+        let history = read_history(&path, crate::lock::DEFAULT_TIMEOUT).unwrap();
+        let days: Vec<u32> = history.iter().map(|s| s.last_session_at.day()).collect();
+        assert_eq!(days, vec![4, 6, 8]);
+    }
 }
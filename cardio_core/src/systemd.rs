@@ -0,0 +1,190 @@
+//! systemd user units for reminders and the nightly rollup.
+//!
+//! `krep install-systemd` writes these to `~/.config/systemd/user`: a timer
+//! that runs `krep remind` around the reminder hours (it notifies only at
+//! the hours the [adaptive cadence](crate::cadence) picks) and one that
+//! runs `krep rollup --cleanup` each night. Units are plain text so they
+//! can be previewed before anything is written.
+
+use chrono::NaiveTime;
+use std::path::Path;
+
+/// Reminder hours when there is not enough history to suggest any
+pub const DEFAULT_REMINDER_HOURS: [u32; 3] = [9, 13, 17];
+
+/// A unit file to write
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnitFile {
+    pub name: &'static str,
+    pub contents: String,
+}
+
+/// What the generated units run and when
+#[derive(Clone, Debug)]
+pub struct UnitOptions<'a> {
+    /// The krep binary
+    pub krep: &'a Path,
+    /// Global arguments passed to every krep invocation (e.g. `--data-dir`)
+    pub global_args: Vec<String>,
+    /// Local hours (0-23) to be reminded at, before adapting
+    pub reminder_hours: Vec<u32>,
+    /// Local time of the nightly rollup
+    pub rollup_at: NaiveTime,
+    /// IANA timezone for the timers; unset uses the system timezone
+    pub timezone: Option<&'a str>,
+}
+
+/// Reminder service and timer, rollup service and timer
+pub fn units(options: &UnitOptions) -> Vec<UnitFile> {
+    let krep = |args: &[&str]| {
+        std::iter::once(options.krep.to_string_lossy().into_owned())
+            .chain(options.global_args.iter().cloned())
+            .chain(args.iter().map(|arg| arg.to_string()))
+            .map(|arg| quote(&arg))
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    let timezone = options
+        .timezone
+        .map(|tz| format!(" {}", tz))
+        .unwrap_or_default();
+    let hours: Vec<String> = options
+        .reminder_hours
+        .iter()
+        .map(|hour| hour.to_string())
+        .collect();
+    let candidates: Vec<String> = crate::cadence::candidate_hours(&options.reminder_hours)
+        .iter()
+        .map(|hour| format!("{:02}", hour))
+        .collect();
+
+    vec![
+        UnitFile {
+            name: "krep-reminder.service",
+            contents: format!(
+                "[Unit]\n\
+                 Description=Krep microdose reminder\n\
+                 \n\
+                 [Service]\n\
+                 Type=oneshot\n\
+                 ExecStart={}\n",
+                krep(&["remind", "--hours", &hours.join(",")]),
+            ),
+        },
+        UnitFile {
+            name: "krep-reminder.timer",
+            contents: format!(
+                "[Unit]\n\
+                 Description=Krep microdose reminders\n\
+                 \n\
+                 [Timer]\n\
+                 OnCalendar=*-*-* {}:00:00{}\n\
+                 \n\
+                 [Install]\n\
+                 WantedBy=timers.target\n",
+                candidates.join(","),
+                timezone
+            ),
+        },
+        UnitFile {
+            name: "krep-rollup.service",
+            contents: format!(
+                "[Unit]\n\
+                 Description=Krep WAL rollup to CSV\n\
+                 \n\
+                 [Service]\n\
+                 Type=oneshot\n\
+                 ExecStart={}\n",
+                krep(&["rollup", "--cleanup"])
+            ),
+        },
+        UnitFile {
+            name: "krep-rollup.timer",
+            contents: format!(
+                "[Unit]\n\
+                 Description=Nightly krep rollup\n\
+                 \n\
+                 [Timer]\n\
+                 OnCalendar=*-*-* {}{}\n\
+                 Persistent=true\n\
+                 \n\
+                 [Install]\n\
+                 WantedBy=timers.target\n",
+                options.rollup_at.format("%H:%M:%S"),
+                timezone
+            ),
+        },
+    ]
+}
+
+/// Quote a word for an `ExecStart=` line (`%` is a specifier, so doubled)
+fn quote(word: &str) -> String {
+    let escaped = word.replace('%', "%%");
+    if escaped.is_empty()
+        || escaped
+            .chars()
+            .any(|c| c.is_whitespace() || matches!(c, '"' | '\'' | '\\' | ';' | '$'))
+    {
+        format!(
+            "\"{}\"",
+            escaped
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('$', "$$")
+        )
+    } else {
+        escaped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(krep: &Path) -> UnitOptions<'_> {
+        UnitOptions {
+            krep,
+            global_args: vec!["--data-dir".into(), "/home/me/My Data".into()],
+            reminder_hours: vec![7, 12, 18],
+            rollup_at: NaiveTime::from_hms_opt(3, 0, 0).unwrap(),
+            timezone: Some("Asia/Tokyo"),
+        }
+    }
+
+    #[test]
+    fn test_units() {
+        let units = units(&options(Path::new("/usr/local/bin/krep")));
+        let names: Vec<_> = units.iter().map(|u| u.name).collect();
+        assert_eq!(
+            names,
+            vec![
+                "krep-reminder.service",
+                "krep-reminder.timer",
+                "krep-rollup.service",
+                "krep-rollup.timer"
+            ]
+        );
+
+        assert!(units[0].contents.contains(
+            "ExecStart=/usr/local/bin/krep --data-dir \"/home/me/My Data\" remind --hours 7,12,18\n"
+        ));
+        assert!(units[1].contents.contains(
+            "OnCalendar=*-*-* 04,05,06,07,08,09,10,11,12,13,14,15,16,17,18,19,20,21:00:00 Asia/Tokyo\n"
+        ));
+        assert!(units[2].contents.contains(
+            "ExecStart=/usr/local/bin/krep --data-dir \"/home/me/My Data\" rollup --cleanup\n"
+        ));
+        assert!(units[3]
+            .contents
+            .contains("OnCalendar=*-*-* 03:00:00 Asia/Tokyo\nPersistent=true\n"));
+    }
+
+    #[test]
+    fn test_quote() {
+        assert_eq!(quote("rollup"), "rollup");
+        assert_eq!(quote("a b"), "\"a b\"");
+        assert_eq!(quote("100%"), "100%%");
+        assert_eq!(quote("say \"hi\""), "\"say \\\"hi\\\"\"");
+        assert_eq!(quote("$HOME"), "\"$$HOME\"");
+    }
+}
@@ -0,0 +1,133 @@
+//! Local-time handling.
+//!
+//! Sessions are stored in UTC. Anything that depends on the calendar
+//! ("today", streaks, time-of-day rules) goes through [`LocalTz`], which is
+//! the system timezone unless `[time] timezone` names an IANA zone.
+
+use crate::{Error, Result};
+use chrono::{DateTime, Local, LocalResult, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+
+/// Timezone used for calendar and time-of-day calculations
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum LocalTz {
+    /// The operating system's local timezone
+    #[default]
+    System,
+    /// An explicit IANA zone (e.g. `Asia/Tokyo`)
+    Named(chrono_tz::Tz),
+}
+
+impl LocalTz {
+    /// Parse a configured timezone name; `None` or an empty string means system local
+    pub fn from_name(name: Option<&str>) -> Result<Self> {
+        match name.map(str::trim) {
+            None | Some("") => Ok(LocalTz::System),
+            Some(name) => name
+                .parse::<chrono_tz::Tz>()
+                .map(LocalTz::Named)
+                .map_err(|_| Error::Config(format!("Unknown timezone '{}'", name))),
+        }
+    }
+
+    /// Local calendar date of a UTC instant
+    pub fn date_of(&self, t: DateTime<Utc>) -> NaiveDate {
+        match self {
+            LocalTz::System => t.with_timezone(&Local).date_naive(),
+            LocalTz::Named(tz) => t.with_timezone(tz).date_naive(),
+        }
+    }
+
+    /// Local wall-clock time of a UTC instant
+    pub fn time_of(&self, t: DateTime<Utc>) -> NaiveTime {
+        match self {
+            LocalTz::System => t.with_timezone(&Local).time(),
+            LocalTz::Named(tz) => t.with_timezone(tz).time(),
+        }
+    }
+
+    /// UTC instant at which a local calendar day begins
+    ///
+    /// If midnight doesn't exist locally (DST gap), the first valid instant
+    /// after it is used.
+    pub fn day_start_utc(&self, date: NaiveDate) -> DateTime<Utc> {
+        self.to_utc(date.and_time(NaiveTime::MIN))
+    }
+
+    /// UTC instant of a local wall-clock time
+    ///
+    /// Ambiguous times (DST fold) take the earlier instant; times in a DST
+    /// gap move forward to the first valid one.
+    pub fn to_utc(&self, local: NaiveDateTime) -> DateTime<Utc> {
+        match self {
+            LocalTz::System => resolve(Local.from_local_datetime(&local), &Local, local),
+            LocalTz::Named(tz) => resolve(tz.from_local_datetime(&local), tz, local),
+        }
+    }
+
+    /// Format a UTC instant in local time (chrono `strftime` syntax)
+    pub fn format(&self, t: DateTime<Utc>, fmt: &str) -> String {
+        match self {
+            LocalTz::System => t.with_timezone(&Local).format(fmt).to_string(),
+            LocalTz::Named(tz) => t.with_timezone(tz).format(fmt).to_string(),
+        }
+    }
+}
+
+fn resolve<Tz: TimeZone>(
+    result: LocalResult<DateTime<Tz>>,
+    tz: &Tz,
+    local: NaiveDateTime,
+) -> DateTime<Utc> {
+    match result {
+        LocalResult::Single(t) => t.with_timezone(&Utc),
+        LocalResult::Ambiguous(earliest, _) => earliest.with_timezone(&Utc),
+        // DST gaps are at most a couple of hours; step forward until valid
+        LocalResult::None => (1..=4)
+            .find_map(|h| {
+                tz.from_local_datetime(&(local + chrono::Duration::hours(h)))
+                    .earliest()
+            })
+            .map(|t| t.with_timezone(&Utc))
+            .unwrap_or_else(|| Utc.from_utc_datetime(&local)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokyo() -> LocalTz {
+        LocalTz::from_name(Some("Asia/Tokyo")).unwrap()
+    }
+
+    #[test]
+    fn test_from_name() {
+        assert_eq!(LocalTz::from_name(None).unwrap(), LocalTz::System);
+        assert_eq!(LocalTz::from_name(Some(" ")).unwrap(), LocalTz::System);
+        assert_eq!(tokyo(), LocalTz::Named(chrono_tz::Asia::Tokyo));
+        assert!(LocalTz::from_name(Some("Mars/Olympus")).is_err());
+    }
+
+    #[test]
+    fn test_date_uses_local_day_boundary() {
+        // 20:00 UTC is already the next morning in Tokyo (UTC+9)
+        let t = Utc.with_ymd_and_hms(2025, 3, 1, 20, 0, 0).unwrap();
+        assert_eq!(
+            tokyo().date_of(t),
+            NaiveDate::from_ymd_opt(2025, 3, 2).unwrap()
+        );
+        assert_eq!(
+            tokyo().time_of(t),
+            NaiveTime::from_hms_opt(5, 0, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_day_start_utc() {
+        let date = NaiveDate::from_ymd_opt(2025, 3, 2).unwrap();
+        assert_eq!(
+            tokyo().day_start_utc(date),
+            Utc.with_ymd_and_hms(2025, 3, 1, 15, 0, 0).unwrap()
+        );
+    }
+}
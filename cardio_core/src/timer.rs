@@ -0,0 +1,274 @@
+//! Countdown state for interval blocks (EMOM, Tabata, 30:30, ...).
+//!
+//! The timer is pure: frontends measure elapsed time themselves (with a
+//! [`Stopwatch`] that stands still while the dose is paused) and ask
+//! [`IntervalTimer::tick`] what to show, so the CLI loop and the tray's
+//! GLib timeout share the same phase logic. Reps scaled down partway
+//! through ("adjust" in the timer flows) are kept in [`RepAdjustments`] so
+//! the logged sets match what was done.
+
+use crate::{IntervalPhase, IntervalPhaseKind, IntervalScheme};
+use std::time::{Duration, Instant};
+
+/// Phases of an interval scheme laid out on a timeline
+#[derive(Clone, Debug)]
+pub struct IntervalTimer {
+    phases: Vec<IntervalPhase>,
+    rounds: u32,
+}
+
+/// Seconds at the end of a timer that are counted down with a cue each
+pub const COUNTDOWN_SECONDS: u32 = 10;
+
+/// Audible events during a timer (see [`crate::sound`])
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TimerCue {
+    /// Another full minute has passed
+    MinuteRollover,
+    /// One of the last [`COUNTDOWN_SECONDS`] seconds
+    Countdown,
+    /// The last phase ended
+    Complete,
+}
+
+/// What the timer shows at a given moment
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TimerTick {
+    pub phase: IntervalPhase,
+    /// Index into the phase list (changes when a new phase starts)
+    pub phase_index: usize,
+    pub remaining_seconds: u32,
+    pub rounds: u32,
+}
+
+impl IntervalTimer {
+    pub fn new(scheme: &IntervalScheme) -> Self {
+        Self {
+            phases: scheme.phases(),
+            rounds: scheme.rounds,
+        }
+    }
+
+    /// Length of the whole timer
+    pub fn total_seconds(&self) -> u32 {
+        self.phases.iter().map(|p| p.seconds).sum()
+    }
+
+    /// State after `elapsed_seconds`, or `None` once the last phase ends
+    pub fn tick(&self, elapsed_seconds: u32) -> Option<TimerTick> {
+        let mut start = 0;
+        for (phase_index, phase) in self.phases.iter().enumerate() {
+            let end = start + phase.seconds;
+            if elapsed_seconds < end {
+                return Some(TimerTick {
+                    phase: *phase,
+                    phase_index,
+                    remaining_seconds: end - elapsed_seconds,
+                    rounds: self.rounds,
+                });
+            }
+            start = end;
+        }
+        None
+    }
+
+    /// Round a change made at `elapsed_seconds` applies from: the current
+    /// work round, the next one during a rest, or the last once finished
+    pub fn round_at(&self, elapsed_seconds: u32) -> u32 {
+        match self.tick(elapsed_seconds) {
+            Some(tick) if tick.phase.kind == IntervalPhaseKind::Rest => tick.phase.round + 1,
+            Some(tick) => tick.phase.round,
+            None => self.rounds,
+        }
+    }
+
+    /// Cue for the second starting at `elapsed_seconds`, if any
+    ///
+    /// Frontends call this once per whole second; the final second
+    /// (`elapsed_seconds == total_seconds()`) is [`TimerCue::Complete`].
+    pub fn cue(&self, elapsed_seconds: u32) -> Option<TimerCue> {
+        let total = self.total_seconds();
+        let remaining = total.checked_sub(elapsed_seconds)?;
+        if remaining == 0 {
+            Some(TimerCue::Complete)
+        } else if remaining <= COUNTDOWN_SECONDS {
+            Some(TimerCue::Countdown)
+        } else if elapsed_seconds > 0 && elapsed_seconds.is_multiple_of(60) {
+            Some(TimerCue::MinuteRollover)
+        } else {
+            None
+        }
+    }
+}
+
+/// Elapsed time that stops while paused, read at instants the frontend
+/// supplies
+#[derive(Clone, Copy, Debug)]
+pub struct Stopwatch {
+    started: Instant,
+    paused: Duration,
+    paused_since: Option<Instant>,
+}
+
+impl Stopwatch {
+    pub fn new(started: Instant) -> Self {
+        Self {
+            started,
+            paused: Duration::ZERO,
+            paused_since: None,
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused_since.is_some()
+    }
+
+    /// Time since the start at `now`, less every pause
+    pub fn elapsed(&self, now: Instant) -> Duration {
+        let pausing = self
+            .paused_since
+            .map_or(Duration::ZERO, |at| now.saturating_duration_since(at));
+        now.saturating_duration_since(self.started)
+            .saturating_sub(self.paused + pausing)
+    }
+
+    /// Pause, or resume if paused, at `now`; returns whether it is paused
+    pub fn toggle(&mut self, now: Instant) -> bool {
+        match self.paused_since.take() {
+            Some(at) => {
+                self.paused += now.saturating_duration_since(at);
+                false
+            }
+            None => {
+                self.paused_since = Some(now);
+                true
+            }
+        }
+    }
+}
+
+/// Reps changed mid-dose, e.g. down from 7 to 5 swings for the last two
+/// minutes of an EMOM
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RepAdjustments {
+    /// (first round, reps from then on) in the order made
+    changes: Vec<(u32, i32)>,
+}
+
+impl RepAdjustments {
+    /// Do `reps` per round from `round` on
+    pub fn adjust(&mut self, round: u32, reps: i32) {
+        self.changes.push((round, reps));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Reps done in each of `rounds` rounds: `target` until the first change
+    pub fn per_set(&self, target: i32, rounds: u32) -> Vec<i32> {
+        (1..=rounds)
+            .map(|round| {
+                self.changes
+                    .iter()
+                    .rev()
+                    .find(|(from, _)| *from <= round)
+                    .map_or(target, |(_, reps)| *reps)
+            })
+            .collect()
+    }
+}
+
+impl TimerTick {
+    /// Display line, e.g. "Round 3/8 · WORK · 12s"
+    pub fn label(&self) -> String {
+        let kind = match self.phase.kind {
+            IntervalPhaseKind::Work => "WORK",
+            IntervalPhaseKind::Rest => "REST",
+        };
+        format!(
+            "Round {}/{} · {} · {}s",
+            self.phase.round, self.rounds, kind, self.remaining_seconds
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stopwatch_stands_still_while_paused() {
+        let start = Instant::now();
+        let at = |seconds| start + Duration::from_secs(seconds);
+        let mut stopwatch = Stopwatch::new(start);
+
+        assert!(stopwatch.toggle(at(30)));
+        assert_eq!(stopwatch.elapsed(at(100)), Duration::from_secs(30));
+        assert!(!stopwatch.toggle(at(120)));
+        assert!(!stopwatch.is_paused());
+        assert_eq!(stopwatch.elapsed(at(130)), Duration::from_secs(40));
+    }
+
+    #[test]
+    fn test_tabata_timeline() {
+        let timer = IntervalTimer::new(&IntervalScheme::tabata());
+        assert_eq!(timer.total_seconds(), 230);
+
+        let start = timer.tick(0).unwrap();
+        assert_eq!(start.label(), "Round 1/8 · WORK · 20s");
+
+        let rest = timer.tick(25).unwrap();
+        assert_eq!(rest.phase.kind, IntervalPhaseKind::Rest);
+        assert_eq!(rest.remaining_seconds, 5);
+        assert_eq!(rest.phase_index, 1);
+
+        let last = timer.tick(229).unwrap();
+        assert_eq!(last.label(), "Round 8/8 · WORK · 1s");
+        assert!(timer.tick(230).is_none());
+    }
+
+    #[test]
+    fn test_cues() {
+        let timer = IntervalTimer::new(&IntervalScheme::emom(5));
+        let cues: Vec<_> = (0..=301)
+            .filter_map(|t| timer.cue(t).map(|c| (t, c)))
+            .collect();
+        let minutes: Vec<_> = cues
+            .iter()
+            .filter(|(_, c)| *c == TimerCue::MinuteRollover)
+            .map(|(t, _)| *t)
+            .collect();
+        assert_eq!(minutes, [60, 120, 180, 240]);
+        assert_eq!(
+            cues.iter()
+                .filter(|(_, c)| *c == TimerCue::Countdown)
+                .count(),
+            COUNTDOWN_SECONDS as usize
+        );
+        assert_eq!(cues.last(), Some(&(300, TimerCue::Complete)));
+    }
+
+    #[test]
+    fn test_adjustments_apply_from_their_round() {
+        let timer = IntervalTimer::new(&IntervalScheme::tabata());
+        assert_eq!(timer.round_at(5), 1);
+        // During the rest after round 1
+        assert_eq!(timer.round_at(25), 2);
+        assert_eq!(timer.round_at(230), 8);
+
+        let mut adjustments = RepAdjustments::default();
+        assert_eq!(adjustments.per_set(7, 3), [7, 7, 7]);
+        adjustments.adjust(9, 5);
+        adjustments.adjust(10, 4);
+        assert_eq!(adjustments.per_set(7, 10), [7, 7, 7, 7, 7, 7, 7, 7, 5, 4]);
+    }
+
+    #[test]
+    fn test_emom_has_no_rest_phases() {
+        let timer = IntervalTimer::new(&IntervalScheme::emom(5));
+        assert_eq!(timer.total_seconds(), 300);
+        assert!((0..300).all(|t| timer.tick(t).unwrap().phase.kind == IntervalPhaseKind::Work));
+        assert_eq!(timer.tick(61).unwrap().phase.round, 2);
+    }
+}
@@ -0,0 +1,477 @@
+//! Completion-by-hour analysis for reminder times.
+//!
+//! Completed sessions come from history; skips, snoozed reminders and
+//! abandoned doses are appended to their own JSONL files (`skips.jsonl`,
+//! `snoozes.jsonl` and `abandoned.jsonl` in the data directory) because they
+//! never reach the WAL. `krep stats
+//! --timing` groups them by local hour and suggests the hours where doses
+//! actually get done.
+
+#[cfg(feature = "fs")]
+use crate::Result;
+use crate::{LocalTz, SessionKind};
+use chrono::{DateTime, Timelike, Utc};
+#[cfg(feature = "fs")]
+#[cfg(feature = "fs")]
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "fs")]
+use std::fs::{File, OpenOptions};
+#[cfg(feature = "fs")]
+use std::io::{BufRead, BufReader, Write};
+#[cfg(feature = "fs")]
+use std::path::Path;
+
+/// File name of the skip log within the data directory
+pub const SKIP_LOG_FILE: &str = "skips.jsonl";
+
+/// File name of the snooze log within the data directory
+pub const SNOOZE_LOG_FILE: &str = "snoozes.jsonl";
+
+/// File name of the abandoned-dose log within the data directory
+pub const ABANDON_LOG_FILE: &str = "abandoned.jsonl";
+
+/// Fewest shown doses (done + skipped) before an hour can be suggested
+pub const MIN_HOUR_SAMPLES: u32 = 2;
+
+/// Minimum gap between suggested reminder hours
+pub const REMINDER_SPACING_HOURS: u32 = 2;
+
+/// A prescription the user skipped
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct SkipEvent {
+    pub skipped_at: DateTime<Utc>,
+    pub definition_id: String,
+    /// Why, when picked in the tray popup
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<SkipReason>,
+    /// A soft skip: the tray popup closed itself after being ignored
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub soft: bool,
+}
+
+/// Why a prescription was skipped
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum SkipReason {
+    Busy,
+    Tired,
+    Sore,
+    NoEquipment,
+    Other,
+}
+
+impl SkipReason {
+    /// Every reason, in the order the popup offers them
+    pub const ALL: [SkipReason; 5] = [
+        SkipReason::Busy,
+        SkipReason::Tired,
+        SkipReason::Sore,
+        SkipReason::NoEquipment,
+        SkipReason::Other,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            SkipReason::Busy => "busy",
+            SkipReason::Tired => "tired",
+            SkipReason::Sore => "sore",
+            SkipReason::NoEquipment => "no equipment",
+            SkipReason::Other => "other",
+        }
+    }
+
+    /// Whether the skip says something about the time of day
+    ///
+    /// Soreness or missing equipment would have stopped the dose at any
+    /// hour, so reminder timing leaves those skips out.
+    pub fn about_timing(&self) -> bool {
+        !matches!(self, SkipReason::Sore | SkipReason::NoEquipment)
+    }
+}
+
+/// A reminder the user put off ("not now")
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct SnoozeEvent {
+    pub snoozed_at: DateTime<Utc>,
+    /// How long it was put off for
+    pub minutes: u32,
+}
+
+/// A started dose that was never finished (timed out or replaced by another)
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct AbandonEvent {
+    pub definition_id: String,
+    pub started_at: DateTime<Utc>,
+    pub abandoned_at: DateTime<Utc>,
+}
+
+/// Doses done, skipped and snoozed in one local hour of the day
+#[derive(Clone, Copy, Debug, Default, Serialize, PartialEq, Eq)]
+pub struct HourStats {
+    /// Local hour (0–23)
+    pub hour: u32,
+    pub completed: u32,
+    pub skipped: u32,
+    pub snoozed: u32,
+}
+
+impl HourStats {
+    pub fn shown(&self) -> u32 {
+        self.completed + self.skipped + self.snoozed
+    }
+
+    /// Share of shown doses that were done (0.0 with nothing shown)
+    pub fn completion_rate(&self) -> f64 {
+        if self.shown() == 0 {
+            return 0.0;
+        }
+        f64::from(self.completed) / f64::from(self.shown())
+    }
+}
+
+/// Completed, skipped and snoozed doses at or after `since`, per local hour
+///
+/// Always 24 entries, indexed by hour. Skips for a reason that isn't about
+/// the time of day (see [`SkipReason::about_timing`]) are left out.
+pub fn completion_by_hour(
+    sessions: &[SessionKind],
+    skips: &[SkipEvent],
+    snoozes: &[SnoozeEvent],
+    tz: &LocalTz,
+    since: DateTime<Utc>,
+) -> Vec<HourStats> {
+    let mut hours: Vec<HourStats> = (0..24)
+        .map(|hour| HourStats {
+            hour,
+            ..HourStats::default()
+        })
+        .collect();
+
+    for session in sessions.iter().filter_map(|s| s.as_real()) {
+        if session.performed_at >= since {
+            hours[tz.time_of(session.performed_at).hour() as usize].completed += 1;
+        }
+    }
+    for skip in skips
+        .iter()
+        .filter(|s| s.skipped_at >= since && s.reason.is_none_or(|r| r.about_timing()))
+    {
+        hours[tz.time_of(skip.skipped_at).hour() as usize].skipped += 1;
+    }
+    for snooze in snoozes.iter().filter(|s| s.snoozed_at >= since) {
+        hours[tz.time_of(snooze.snoozed_at).hour() as usize].snoozed += 1;
+    }
+
+    hours
+}
+
+/// Skips at or after `since` per reason given, most common first (skips
+/// without a reason aren't counted)
+pub fn skip_reasons(skips: &[SkipEvent], since: DateTime<Utc>) -> Vec<(SkipReason, u32)> {
+    let mut counts: Vec<(SkipReason, u32)> = SkipReason::ALL
+        .iter()
+        .map(|&reason| {
+            let count = skips
+                .iter()
+                .filter(|s| s.skipped_at >= since && s.reason == Some(reason))
+                .count() as u32;
+            (reason, count)
+        })
+        .filter(|&(_, count)| count > 0)
+        .collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    counts
+}
+
+/// Soft skips (popups left to close themselves) since `since`
+pub fn soft_skips(skips: &[SkipEvent], since: DateTime<Utc>) -> u32 {
+    skips
+        .iter()
+        .filter(|s| s.skipped_at >= since && s.soft)
+        .count() as u32
+}
+
+/// Up to `count` hours to be reminded at, earliest first
+///
+/// Hours need [`MIN_HOUR_SAMPLES`] shown doses and at least one completion;
+/// they are ranked by completion rate, then by completions, and kept
+/// [`REMINDER_SPACING_HOURS`] apart.
+pub fn suggest_reminder_hours(hours: &[HourStats], count: usize) -> Vec<u32> {
+    let mut ranked: Vec<&HourStats> = hours
+        .iter()
+        .filter(|h| h.shown() >= MIN_HOUR_SAMPLES && h.completed > 0)
+        .collect();
+    ranked.sort_by(|a, b| {
+        b.completion_rate()
+            .total_cmp(&a.completion_rate())
+            .then(b.completed.cmp(&a.completed))
+            .then(a.hour.cmp(&b.hour))
+    });
+
+    let mut chosen: Vec<u32> = Vec::new();
+    for stats in ranked {
+        if chosen.len() == count {
+            break;
+        }
+        if chosen
+            .iter()
+            .all(|h| h.abs_diff(stats.hour) >= REMINDER_SPACING_HOURS)
+        {
+            chosen.push(stats.hour);
+        }
+    }
+    chosen.sort();
+    chosen
+}
+
+/// Append a skip to the skip log (with an exclusive lock, taken within
+/// `timeout`)
+#[cfg(feature = "fs")]
+pub fn append_skip(path: &Path, event: &SkipEvent, timeout: std::time::Duration) -> Result<()> {
+    append_event(path, event, timeout)?;
+    tracing::debug!("Logged skip of {}", event.definition_id);
+    Ok(())
+}
+
+/// Read all skips, skipping malformed lines (with a shared lock, taken within
+/// `timeout`)
+#[cfg(feature = "fs")]
+pub fn read_skips(path: &Path, timeout: std::time::Duration) -> Result<Vec<SkipEvent>> {
+    read_events(path, "skip", timeout)
+}
+
+/// Append a snooze to the snooze log (with an exclusive lock, taken within
+/// `timeout`)
+#[cfg(feature = "fs")]
+pub fn append_snooze(path: &Path, event: &SnoozeEvent, timeout: std::time::Duration) -> Result<()> {
+    append_event(path, event, timeout)?;
+    tracing::debug!("Logged snooze for {} minutes", event.minutes);
+    Ok(())
+}
+
+/// Read all snoozes, skipping malformed lines (with a shared lock, taken within
+/// `timeout`)
+#[cfg(feature = "fs")]
+pub fn read_snoozes(path: &Path, timeout: std::time::Duration) -> Result<Vec<SnoozeEvent>> {
+    read_events(path, "snooze", timeout)
+}
+
+/// Append an abandoned dose to the abandon log (with an exclusive lock,
+/// taken within `timeout`)
+#[cfg(feature = "fs")]
+pub fn append_abandon(
+    path: &Path,
+    event: &AbandonEvent,
+    timeout: std::time::Duration,
+) -> Result<()> {
+    append_event(path, event, timeout)?;
+    tracing::debug!("Logged abandoned {}", event.definition_id);
+    Ok(())
+}
+
+/// Read all abandoned doses, skipping malformed lines (with a shared lock, taken within
+/// `timeout`)
+#[cfg(feature = "fs")]
+pub fn read_abandons(path: &Path, timeout: std::time::Duration) -> Result<Vec<AbandonEvent>> {
+    read_events(path, "abandoned dose", timeout)
+}
+
+/// Append one JSON line to `path` (with an exclusive lock, taken within
+/// `timeout`)
+#[cfg(feature = "fs")]
+fn append_event<T: Serialize>(path: &Path, event: &T, timeout: std::time::Duration) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    crate::lock::lock_exclusive_within(&file, path, timeout)?;
+
+    let mut writer = std::io::BufWriter::new(&file);
+    let line = serde_json::to_string(event)?;
+    writer.write_all(line.as_bytes())?;
+    writer.write_all(b"\n")?;
+    writer.flush()?;
+
+    fs2::FileExt::unlock(&file)?;
+    Ok(())
+}
+
+/// Read every JSON line of `path` (none if it doesn't exist), warning about
+/// malformed `kind` lines
+#[cfg(feature = "fs")]
+fn read_events<T: DeserializeOwned>(
+    path: &Path,
+    kind: &str,
+    timeout: std::time::Duration,
+) -> Result<Vec<T>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let file = File::open(path)?;
+    crate::lock::lock_shared_within(&file, path, timeout)?;
+
+    let mut events = Vec::new();
+    for (line_num, line) in BufReader::new(&file).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<T>(&line) {
+            Ok(event) => events.push(event),
+            Err(e) => {
+                tracing::warn!("Failed to parse {} at line {}: {}", kind, line_num + 1, e);
+            }
+        }
+    }
+
+    fs2::FileExt::unlock(&file)?;
+    Ok(events)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MicrodoseSession;
+    use chrono::TimeZone;
+
+    fn done_at(hour: u32) -> SessionKind {
+        SessionKind::Real(MicrodoseSession {
+            actual_duration_seconds: Some(300),
+            ..MicrodoseSession::new(
+                "emom_burpee_5m",
+                Utc.with_ymd_and_hms(2024, 3, 4, hour, 15, 0).unwrap(),
+            )
+        })
+    }
+
+    fn skipped_at(hour: u32) -> SkipEvent {
+        SkipEvent {
+            skipped_at: Utc.with_ymd_and_hms(2024, 3, 4, hour, 40, 0).unwrap(),
+            definition_id: "emom_burpee_5m".into(),
+            reason: None,
+            soft: false,
+        }
+    }
+
+    #[test]
+    fn test_completion_by_hour_and_suggestions() {
+        let tz = LocalTz::from_name(Some("UTC")).unwrap();
+        let since = Utc.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap();
+        let sessions = vec![
+            done_at(7),
+            done_at(7),
+            done_at(8),
+            done_at(8),
+            done_at(12),
+            done_at(18),
+            done_at(18),
+            done_at(21),
+        ];
+        let skips = vec![
+            skipped_at(8),
+            skipped_at(12),
+            skipped_at(15),
+            skipped_at(15),
+        ];
+
+        let snoozes = vec![SnoozeEvent {
+            snoozed_at: Utc.with_ymd_and_hms(2024, 3, 4, 12, 5, 0).unwrap(),
+            minutes: 30,
+        }];
+
+        let hours = completion_by_hour(&sessions, &skips, &snoozes, &tz, since);
+        assert_eq!(hours.len(), 24);
+        assert_eq!(hours[8].completed, 2);
+        assert_eq!(hours[8].skipped, 1);
+        assert_eq!(hours[12].snoozed, 1);
+        assert_eq!(hours[12].shown(), 3);
+        assert_eq!(hours[15].completion_rate(), 0.0);
+
+        // 7 and 18 are always done; 8 is next best but too close to 7;
+        // 21 has a single sample and 15 is never done
+        assert_eq!(suggest_reminder_hours(&hours, 3), vec![7, 12, 18]);
+        assert_eq!(suggest_reminder_hours(&hours, 1), vec![7]);
+
+        let later = Utc.with_ymd_and_hms(2024, 3, 5, 0, 0, 0).unwrap();
+        assert!(
+            suggest_reminder_hours(&completion_by_hour(&sessions, &skips, &[], &tz, later), 3)
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_skip_reasons() {
+        let tz = LocalTz::from_name(Some("UTC")).unwrap();
+        let since = Utc.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap();
+        let because = |hour: u32, reason: SkipReason| SkipEvent {
+            reason: Some(reason),
+            ..skipped_at(hour)
+        };
+        let skips = vec![
+            because(9, SkipReason::Busy),
+            because(9, SkipReason::Sore),
+            because(10, SkipReason::NoEquipment),
+            because(10, SkipReason::Busy),
+            skipped_at(10),
+            SkipEvent {
+                soft: true,
+                ..skipped_at(11)
+            },
+        ];
+
+        // Only busy and unexplained skips say 9 and 10 are bad times
+        let hours = completion_by_hour(&[], &skips, &[], &tz, since);
+        assert_eq!(hours[9].skipped, 1);
+        assert_eq!(hours[10].skipped, 2);
+        // An ignored popup counts against its hour too
+        assert_eq!(hours[11].skipped, 1);
+        assert_eq!(soft_skips(&skips, since), 1);
+
+        assert_eq!(
+            skip_reasons(&skips, since),
+            vec![
+                (SkipReason::Busy, 2),
+                (SkipReason::Sore, 1),
+                (SkipReason::NoEquipment, 1)
+            ]
+        );
+        let json = serde_json::to_string(&skips[2]).unwrap();
+        assert!(json.contains(r#""reason":"no_equipment""#));
+        assert!(!serde_json::to_string(&skips[4]).unwrap().contains("reason"));
+        assert!(!serde_json::to_string(&skips[4]).unwrap().contains("soft"));
+        assert!(serde_json::to_string(&skips[5])
+            .unwrap()
+            .contains(r#""soft":true"#));
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn test_append_and_read_skips() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join(SKIP_LOG_FILE);
+        assert!(read_skips(&path, crate::lock::DEFAULT_TIMEOUT)
+            .unwrap()
+            .is_empty());
+
+        append_skip(&path, &skipped_at(9), crate::lock::DEFAULT_TIMEOUT).unwrap();
+        append_skip(&path, &skipped_at(10), crate::lock::DEFAULT_TIMEOUT).unwrap();
+        assert_eq!(
+            read_skips(&path, crate::lock::DEFAULT_TIMEOUT).unwrap(),
+            vec![skipped_at(9), skipped_at(10)]
+        );
+
+        let path = temp_dir.path().join(SNOOZE_LOG_FILE);
+        let snooze = SnoozeEvent {
+            snoozed_at: Utc.with_ymd_and_hms(2024, 3, 4, 14, 0, 0).unwrap(),
+            minutes: 15,
+        };
+        append_snooze(&path, &snooze, crate::lock::DEFAULT_TIMEOUT).unwrap();
+        assert_eq!(
+            read_snoozes(&path, crate::lock::DEFAULT_TIMEOUT).unwrap(),
+            vec![snooze]
+        );
+    }
+}
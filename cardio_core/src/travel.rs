@@ -0,0 +1,79 @@
+//! Travel mode: bodyweight-only prescriptions for a while.
+//!
+//! Turning travel mode on stores an expiry in
+//! [`UserMicrodoseState::travel_mode_until`](crate::UserMicrodoseState);
+//! until then the engine only prescribes microdoses whose movements need
+//! no equipment ([`Catalog::without_equipment`](crate::Catalog::without_equipment)).
+//! The configured equipment list is left alone, so nothing needs undoing
+//! once home again.
+
+use crate::UserMicrodoseState;
+use chrono::{DateTime, Duration, Utc};
+
+/// How long travel mode lasts when no duration is given
+pub const DEFAULT_DAYS: u32 = 7;
+
+/// Expiry of travel mode if it is on at `now`
+pub fn active_until(state: &UserMicrodoseState, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    state.travel_mode_until.filter(|until| *until > now)
+}
+
+/// Whether travel mode is on at `now`
+pub fn is_active(state: &UserMicrodoseState, now: DateTime<Utc>) -> bool {
+    active_until(state, now).is_some()
+}
+
+/// Turn travel mode on for `days` days from `now`, returning the expiry
+pub fn turn_on(state: &mut UserMicrodoseState, now: DateTime<Utc>, days: u32) -> DateTime<Utc> {
+    let until = now + Duration::days(i64::from(days));
+    state.travel_mode_until = Some(until);
+    until
+}
+
+/// Turn travel mode off
+pub fn turn_off(state: &mut UserMicrodoseState) {
+    state.travel_mode_until = None;
+}
+
+/// Flip travel mode (on for [`DEFAULT_DAYS`]), returning the new expiry
+/// (`None` = now off)
+pub fn toggle(state: &mut UserMicrodoseState, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    if is_active(state, now) {
+        turn_off(state);
+        None
+    } else {
+        Some(turn_on(state, now, DEFAULT_DAYS))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_travel_mode_expires() {
+        let now = Utc::now();
+        let mut state = UserMicrodoseState::default();
+        assert!(!is_active(&state, now));
+
+        let until = turn_on(&mut state, now, 3);
+        assert_eq!(active_until(&state, now), Some(until));
+        assert!(is_active(&state, now + Duration::days(2)));
+        assert!(!is_active(&state, now + Duration::days(3)));
+
+        turn_off(&mut state);
+        assert!(!is_active(&state, now));
+    }
+
+    #[test]
+    fn test_toggle_restarts_expired_mode() {
+        let now = Utc::now();
+        let mut state = UserMicrodoseState::default();
+        turn_on(&mut state, now - Duration::days(10), 3);
+
+        // An expired travel mode counts as off, so toggling turns it back on
+        assert_eq!(toggle(&mut state, now), Some(now + Duration::days(7)));
+        assert_eq!(toggle(&mut state, now), None);
+        assert_eq!(state.travel_mode_until, None);
+    }
+}
@@ -24,6 +24,27 @@ pub enum MovementKind {
     Burpee,
     Pullup,
     MobilityDrill,
+    CoreDrill,
+    StairWalk,
+    Breathing,
+    WarmupDrill,
+    Squat,
+}
+
+impl MovementKind {
+    /// Whether the movement needs equipment (a kettlebell, a pull-up bar)
+    pub fn needs_equipment(&self) -> bool {
+        self.equipment().is_some()
+    }
+
+    /// The `[equipment] available` entry the movement needs, if any
+    pub fn equipment(&self) -> Option<&'static str> {
+        match self {
+            MovementKind::KettlebellSwing => Some("kettlebell"),
+            MovementKind::Pullup => Some("pullup_bar"),
+            _ => None,
+        }
+    }
 }
 
 /// Burpee variation styles
@@ -81,6 +102,22 @@ pub enum MetricSpec {
         step: i32,
         progressable: bool,
     },
+    /// Unilateral repetitions, counted per side (e.g., CARs, single-leg RDL)
+    ///
+    /// `default`/`min`/`max` are per side; total work is double.
+    RepsPerSide {
+        key: String,
+        default: i32,
+        min: i32,
+        max: i32,
+        step: i32,
+        progressable: bool,
+    },
+    /// As many reps as possible in the block's `duration_hint_seconds`
+    ///
+    /// The rep total is asked for on completion; the best total so far is
+    /// the target to beat next time.
+    AmrapReps { key: String },
     /// Band specification metric (e.g., pullup assistance band)
     Band {
         key: String,
@@ -89,6 +126,48 @@ pub enum MetricSpec {
     },
 }
 
+impl MetricSpec {
+    /// Default rep count for rep-based metrics (per side for `RepsPerSide`)
+    pub fn default_reps(&self) -> Option<i32> {
+        match self {
+            MetricSpec::Reps { default, .. } | MetricSpec::RepsPerSide { default, .. } => {
+                Some(*default)
+            }
+            MetricSpec::AmrapReps { .. } | MetricSpec::Band { .. } => None,
+        }
+    }
+
+    /// Whether reps are counted per side
+    pub fn is_per_side(&self) -> bool {
+        matches!(self, MetricSpec::RepsPerSide { .. })
+    }
+
+    /// Whether this is an as-many-reps-as-possible total
+    pub fn is_amrap(&self) -> bool {
+        matches!(self, MetricSpec::AmrapReps { .. })
+    }
+}
+
+/// A metric actually achieved during a session
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RealizedMetric {
+    /// Reps achieved in each set, in order (e.g. one entry per EMOM minute)
+    Reps { key: String, per_set: Vec<i32> },
+    /// Band actually used
+    Band { key: String, value: String },
+}
+
+impl RealizedMetric {
+    /// Total reps across all sets (None for non-rep metrics)
+    pub fn total_reps(&self) -> Option<i32> {
+        match self {
+            RealizedMetric::Reps { per_set, .. } => Some(per_set.iter().sum()),
+            RealizedMetric::Band { .. } => None,
+        }
+    }
+}
+
 // ============================================================================
 // Microdose Block and Definition Types
 // ============================================================================
@@ -99,16 +178,238 @@ pub struct MicrodoseBlock {
     pub movement_id: String,
     pub movement_style: MovementStyle,
     pub duration_hint_seconds: u32,
+    /// Work/rest structure; `None` is one continuous `duration_hint_seconds` effort
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interval: Option<IntervalScheme>,
+    /// Rep ladder; the block's rep metric is then the ladder peak
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ladder: Option<RepLadder>,
     pub metrics: Vec<MetricSpec>,
 }
 
+impl MicrodoseBlock {
+    /// Total time for the block, rest included
+    pub fn total_seconds(&self) -> u32 {
+        self.interval
+            .map_or(self.duration_hint_seconds, |i| i.total_seconds())
+    }
+}
+
+impl MicrodoseDefinition {
+    /// Time for the blocks back to back (interval blocks include their rests)
+    pub fn blocks_seconds(&self) -> u32 {
+        self.blocks.iter().map(MicrodoseBlock::total_seconds).sum()
+    }
+
+    /// Whether any block counts reps as an AMRAP total
+    pub fn is_amrap(&self) -> bool {
+        self.blocks
+            .iter()
+            .flat_map(|b| b.metrics.iter())
+            .any(MetricSpec::is_amrap)
+    }
+
+    /// First rep ladder among the blocks, if any
+    pub fn ladder(&self) -> Option<RepLadder> {
+        self.blocks.iter().find_map(|b| b.ladder)
+    }
+
+    /// First interval scheme among the blocks, if any (what a timer runs)
+    pub fn interval(&self) -> Option<IntervalScheme> {
+        self.blocks.iter().find_map(|b| b.interval)
+    }
+}
+
+/// Rep ladder climbing from `start` to a peak in `step`s (e.g. 1-2-3),
+/// optionally back down again (1-2-3-2-1)
+///
+/// The peak is the prescribed rep count, so progression extends the ladder.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RepLadder {
+    pub start: i32,
+    pub step: i32,
+    /// Come back down after the peak
+    pub descending: bool,
+}
+
+impl RepLadder {
+    /// Reps for each rung up to `peak` (and back down if descending)
+    pub fn rungs(&self, peak: i32) -> Vec<i32> {
+        let step = self.step.max(1);
+        let mut up: Vec<i32> = (0..)
+            .map(|i| self.start + i * step)
+            .take_while(|&reps| reps <= peak)
+            .collect();
+        if up.is_empty() {
+            up.push(peak);
+        }
+        if self.descending {
+            let down: Vec<i32> = up.iter().rev().skip(1).copied().collect();
+            up.extend(down);
+        }
+        up
+    }
+
+    /// Total reps across all rungs
+    pub fn total(&self, peak: i32) -> i32 {
+        self.rungs(peak).iter().sum()
+    }
+
+    /// Display form, e.g. "1-2-3-2-1"
+    pub fn label(&self, peak: i32) -> String {
+        self.rungs(peak)
+            .iter()
+            .map(|reps| reps.to_string())
+            .collect::<Vec<_>>()
+            .join("-")
+    }
+}
+
+/// Work/rest intervals repeated for a number of rounds (e.g. 30:30 × 10)
+///
+/// An EMOM is 60s of work with no separate rest: the rest is whatever is
+/// left of the minute once the reps are done.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct IntervalScheme {
+    pub work_seconds: u32,
+    pub rest_seconds: u32,
+    pub rounds: u32,
+}
+
+/// Whether an interval phase is work or rest
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum IntervalPhaseKind {
+    Work,
+    Rest,
+}
+
+/// One timed phase of an interval scheme (rounds are 1-based)
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct IntervalPhase {
+    pub kind: IntervalPhaseKind,
+    pub round: u32,
+    pub seconds: u32,
+}
+
+impl IntervalScheme {
+    /// Every minute on the minute for `minutes` rounds
+    pub fn emom(minutes: u32) -> Self {
+        Self {
+            work_seconds: 60,
+            rest_seconds: 0,
+            rounds: minutes,
+        }
+    }
+
+    /// Tabata protocol: 8 rounds of 20s work / 10s rest
+    pub fn tabata() -> Self {
+        Self {
+            work_seconds: 20,
+            rest_seconds: 10,
+            rounds: 8,
+        }
+    }
+
+    pub fn is_emom(&self) -> bool {
+        self.work_seconds == 60 && self.rest_seconds == 0
+    }
+
+    /// Total time; there is no rest after the final round
+    pub fn total_seconds(&self) -> u32 {
+        let rests = self.rounds.saturating_sub(1);
+        self.work_seconds * self.rounds + self.rest_seconds * rests
+    }
+
+    /// Short label, e.g. "30:30 × 10", "EMOM × 5" or "Tabata 20:10 × 8"
+    pub fn label(&self) -> String {
+        if self.is_emom() {
+            format!("EMOM × {}", self.rounds)
+        } else if *self == Self::tabata() {
+            "Tabata 20:10 × 8".to_string()
+        } else {
+            format!(
+                "{}:{} × {}",
+                self.work_seconds, self.rest_seconds, self.rounds
+            )
+        }
+    }
+
+    /// Timed phases in order, for driving a countdown timer
+    pub fn phases(&self) -> Vec<IntervalPhase> {
+        let mut phases = Vec::new();
+        for round in 1..=self.rounds {
+            phases.push(IntervalPhase {
+                kind: IntervalPhaseKind::Work,
+                round,
+                seconds: self.work_seconds,
+            });
+            if self.rest_seconds > 0 && round < self.rounds {
+                phases.push(IntervalPhase {
+                    kind: IntervalPhaseKind::Rest,
+                    round,
+                    seconds: self.rest_seconds,
+                });
+            }
+        }
+        phases
+    }
+}
+
 /// Category of microdose workout
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "config-schema", derive(schemars::JsonSchema))]
 #[serde(rename_all = "snake_case")]
 pub enum MicrodoseCategory {
     Vo2,
     Gtg,
     Mobility,
+    Core,
+    Zone2,
+    Breathwork,
+}
+
+impl MicrodoseCategory {
+    /// Every category, in default round-robin order
+    pub const ALL: [MicrodoseCategory; 6] = [
+        MicrodoseCategory::Vo2,
+        MicrodoseCategory::Gtg,
+        MicrodoseCategory::Mobility,
+        MicrodoseCategory::Core,
+        MicrodoseCategory::Zone2,
+        MicrodoseCategory::Breathwork,
+    ];
+
+    /// Stable lowercase name (matches the serde representation)
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MicrodoseCategory::Vo2 => "vo2",
+            MicrodoseCategory::Gtg => "gtg",
+            MicrodoseCategory::Mobility => "mobility",
+            MicrodoseCategory::Core => "core",
+            MicrodoseCategory::Zone2 => "zone2",
+            MicrodoseCategory::Breathwork => "breathwork",
+        }
+    }
+}
+
+impl std::str::FromStr for MicrodoseCategory {
+    type Err = crate::Error;
+
+    fn from_str(s: &str) -> crate::Result<Self> {
+        let name = s.trim().to_lowercase();
+        MicrodoseCategory::ALL
+            .into_iter()
+            .find(|c| c.as_str() == name)
+            .ok_or_else(|| {
+                let valid: Vec<&str> = MicrodoseCategory::ALL.iter().map(|c| c.as_str()).collect();
+                crate::Error::Other(format!(
+                    "Unknown category '{}' (expected one of: {})",
+                    s,
+                    valid.join(", ")
+                ))
+            })
+    }
 }
 
 /// A complete microdose workout definition
@@ -123,6 +424,16 @@ pub struct MicrodoseDefinition {
     pub reference_url: Option<String>,
 }
 
+impl MicrodoseDefinition {
+    /// Whether the primary rep metric is counted per side
+    pub fn reps_per_side(&self) -> bool {
+        self.blocks
+            .first()
+            .and_then(|b| b.metrics.iter().find(|m| m.default_reps().is_some()))
+            .is_some_and(MetricSpec::is_per_side)
+    }
+}
+
 // ============================================================================
 // Session and State Types
 // ============================================================================
@@ -136,10 +447,99 @@ pub struct MicrodoseSession {
     pub started_at: Option<DateTime<Utc>>,
     pub completed_at: Option<DateTime<Utc>>,
     pub actual_duration_seconds: Option<u32>,
-    pub metrics_realized: Vec<MetricSpec>,
+    #[serde(default)]
+    pub metrics_realized: Vec<RealizedMetric>,
     pub perceived_rpe: Option<u8>,
     pub avg_hr: Option<u8>,
     pub max_hr: Option<u8>,
+    /// Heart-rate drop (bpm) in the 60 seconds after finishing
+    #[serde(default)]
+    pub hr_recovery: Option<u8>,
+    /// Recorded HR series (CSV) relative to the data directory, kept for
+    /// analysis beyond avg/max (see [`crate::hr_recording`])
+    #[serde(default)]
+    pub hr_series_path: Option<String>,
+    /// Seconds spent paused between start and finish (not counted in
+    /// `actual_duration_seconds`)
+    #[serde(default)]
+    pub paused_seconds: Option<u32>,
+    /// Free-form context labels (e.g. "travel", "hotel-gym"), normalized by [`normalize_tags`]
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+impl MicrodoseSession {
+    /// A session of `definition_id` performed at `performed_at`, with a
+    /// fresh ID and nothing else recorded
+    pub fn new(definition_id: &str, performed_at: DateTime<Utc>) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            definition_id: definition_id.to_string(),
+            performed_at,
+            started_at: None,
+            completed_at: None,
+            actual_duration_seconds: None,
+            metrics_realized: vec![],
+            perceived_rpe: None,
+            avg_hr: None,
+            max_hr: None,
+            hr_recovery: None,
+            hr_series_path: None,
+            paused_seconds: None,
+            tags: vec![],
+        }
+    }
+
+    /// Whether the session carries any of the given tags (an empty filter matches everything)
+    pub fn has_any_tag(&self, tags: &[String]) -> bool {
+        tags.is_empty() || tags.iter().any(|t| self.tags.contains(t))
+    }
+
+    /// Per-set reps recorded for this session, if any
+    pub fn reps_per_set(&self) -> Option<&[i32]> {
+        self.metrics_realized.iter().find_map(|m| match m {
+            RealizedMetric::Reps { per_set, .. } => Some(per_set.as_slice()),
+            RealizedMetric::Band { .. } => None,
+        })
+    }
+
+    /// The session as actually timed: performed at `started_at`, with the
+    /// measured duration (minus `paused_seconds`) instead of the suggested one
+    pub fn timed(
+        mut self,
+        started_at: DateTime<Utc>,
+        completed_at: DateTime<Utc>,
+        paused_seconds: u32,
+    ) -> Self {
+        let seconds = (completed_at - started_at).num_seconds() - i64::from(paused_seconds);
+        self.performed_at = started_at;
+        self.started_at = Some(started_at);
+        self.completed_at = Some(completed_at);
+        self.actual_duration_seconds = Some(u32::try_from(seconds.max(0)).unwrap_or(u32::MAX));
+        self.paused_seconds = (paused_seconds > 0).then_some(paused_seconds);
+        self
+    }
+}
+
+/// Normalize user-entered tags: trimmed, lowercase, no empties or duplicates
+///
+/// Tags are stored `;`-separated in the CSV archive, so `;` is not allowed
+/// inside a tag and acts as a separator here alongside `,`.
+pub fn normalize_tags<I, S>(tags: I) -> Vec<String>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    let mut normalized: Vec<String> = Vec::new();
+    for raw in tags {
+        for tag in raw.as_ref().split([',', ';']) {
+            let tag = tag.trim().to_lowercase();
+            if !tag.is_empty() && !normalized.contains(&tag) {
+                normalized.push(tag);
+            }
+        }
+    }
+    normalized
 }
 
 /// Type-level distinction between real sessions and skipped prescriptions
@@ -197,6 +597,153 @@ pub struct ProgressionState {
 pub struct UserMicrodoseState {
     pub progressions: HashMap<String, ProgressionState>,
     pub last_mobility_def_id: Option<String>,
+    /// Best AMRAP rep totals by definition ID
+    #[serde(default)]
+    pub personal_bests: HashMap<String, i32>,
+    /// Recent soreness check-ins (pruned to the avoidance window)
+    #[serde(default)]
+    pub soreness: Vec<SorenessCheckin>,
+    /// Weekly dose goal set with `krep goal set`
+    #[serde(default)]
+    pub weekly_goal: Option<WeeklyGoal>,
+    /// Unlock time by achievement ID
+    #[serde(default)]
+    pub achievements: HashMap<String, DateTime<Utc>>,
+    /// Travel mode (bodyweight-only prescriptions) is on until this time
+    #[serde(default)]
+    pub travel_mode_until: Option<DateTime<Utc>>,
+    /// Office mode (GTG-friendly prescriptions only) is on until this time
+    #[serde(default)]
+    pub office_mode_until: Option<DateTime<Utc>>,
+    /// Rehab periods, oldest first (the last one may still be active)
+    #[serde(default)]
+    pub rehab: Vec<RehabPeriod>,
+    /// Off days set with `krep off`, in date order
+    #[serde(default)]
+    pub off_days: Vec<crate::off_days::OffRange>,
+    /// Dose started (timer running) but not yet logged
+    #[serde(default)]
+    pub active_session: Option<ActiveSession>,
+}
+
+/// A dose in progress: started, neither logged nor abandoned yet
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ActiveSession {
+    pub definition_id: String,
+    pub started_at: DateTime<Utc>,
+    /// Seconds paused so far, not counting a pause still running
+    #[serde(default)]
+    pub paused_seconds: u32,
+    /// Paused since this time
+    #[serde(default)]
+    pub paused_at: Option<DateTime<Utc>>,
+}
+
+impl ActiveSession {
+    pub fn new(definition_id: &str, started_at: DateTime<Utc>) -> Self {
+        Self {
+            definition_id: definition_id.to_string(),
+            started_at,
+            paused_seconds: 0,
+            paused_at: None,
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused_at.is_some()
+    }
+
+    /// Seconds paused up to `now`, including a pause still running
+    pub fn paused_seconds_at(&self, now: DateTime<Utc>) -> u32 {
+        let running = self
+            .paused_at
+            .map_or(0, |at| (now - at).num_seconds().max(0));
+        self.paused_seconds
+            .saturating_add(u32::try_from(running).unwrap_or(u32::MAX))
+    }
+
+    /// Seconds of work between the start and `now`
+    pub fn elapsed_seconds_at(&self, now: DateTime<Utc>) -> u32 {
+        let total = u32::try_from((now - self.started_at).num_seconds().max(0)).unwrap_or(u32::MAX);
+        total.saturating_sub(self.paused_seconds_at(now))
+    }
+
+    /// Pause at `now`; false if already paused
+    pub fn pause(&mut self, now: DateTime<Utc>) -> bool {
+        if self.is_paused() {
+            return false;
+        }
+        self.paused_at = Some(now);
+        true
+    }
+
+    /// Resume at `now`, adding the pause to [`Self::paused_seconds`]; false
+    /// if not paused
+    pub fn resume(&mut self, now: DateTime<Utc>) -> bool {
+        if !self.is_paused() {
+            return false;
+        }
+        self.paused_seconds = self.paused_seconds_at(now);
+        self.paused_at = None;
+        true
+    }
+}
+
+/// Doses to complete per calendar week (Monday to Sunday, local time)
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct WeeklyGoal {
+    pub weekly_doses: u32,
+    /// Lighter goal for weeks with a `travel`-tagged session
+    #[serde(default)]
+    pub travel_weekly_doses: Option<u32>,
+}
+
+/// A body area reported as sore
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SoreArea {
+    /// Body area, matched against movement tags (e.g. "hip", "shoulder")
+    pub area: String,
+    /// 1 (barely noticeable) to 5 (painful)
+    pub severity: u8,
+}
+
+/// Daily soreness check-in (empty `areas` = nothing sore)
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SorenessCheckin {
+    pub checked_at: DateTime<Utc>,
+    pub areas: Vec<SoreArea>,
+}
+
+/// An injury during which only mobility (and cleared movements) is prescribed
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RehabPeriod {
+    /// What is injured, e.g. "left knee"
+    pub injury: String,
+    pub started_at: DateTime<Utc>,
+    /// Planned end (`None` = until cleared)
+    #[serde(default)]
+    pub until: Option<DateTime<Utc>>,
+    /// When the injury was cleared, if before `until`
+    #[serde(default)]
+    pub cleared_at: Option<DateTime<Utc>>,
+    /// Movement IDs allowed again besides mobility
+    #[serde(default)]
+    pub cleared_movements: Vec<String>,
+}
+
+impl RehabPeriod {
+    /// When the period ended or will end (`None` = open-ended and not cleared)
+    pub fn end(&self) -> Option<DateTime<Utc>> {
+        match (self.until, self.cleared_at) {
+            (Some(until), Some(cleared)) => Some(until.min(cleared)),
+            (until, cleared) => until.or(cleared),
+        }
+    }
+
+    /// Whether `t` falls in the period
+    pub fn contains(&self, t: DateTime<Utc>) -> bool {
+        self.started_at <= t && self.end().is_none_or(|end| t < end)
+    }
 }
 
 /// Type of strength training session
@@ -222,8 +769,153 @@ pub struct UserContext {
     pub now: DateTime<Utc>,
     pub user_state: UserMicrodoseState,
     pub recent_sessions: Vec<SessionKind>,
+    /// The latest strength session
     pub external_strength: Option<ExternalStrengthSignal>,
+    /// Earlier strength sessions too, for rules that look further back
+    pub strength_history: crate::strength::StrengthHistory,
+    /// Today's readiness (HRV score, resting HR, sleep), if reported
+    pub readiness: Option<crate::readiness::ReadinessSignal>,
     pub equipment_available: Vec<String>,
+    /// Category round-robin order (empty = every category in [`MicrodoseCategory::ALL`] order)
+    pub rotation: Vec<MicrodoseCategory>,
+    /// Doses in a row per rotation turn (missing = 1)
+    pub rotation_weights: HashMap<MicrodoseCategory, u32>,
+    /// Warmup blocks prepended to VO2 prescriptions (empty = no warmup)
+    pub warmup: Vec<MicrodoseBlock>,
+    /// Sessions per trailing 7 days by category (empty = round-robin only)
+    pub weekly_targets: HashMap<MicrodoseCategory, u32>,
+    /// Max HR in bpm for zone targets (`None` = zones shown as % only)
+    pub max_hr: Option<u32>,
+    /// Hours since the last VO2 dose after which VO2 is prescribed next
+    pub vo2_gap_hours: u32,
+    /// Only prescribe GTG-friendly microdoses (e.g. an office profile)
+    pub gtg_friendly_only: bool,
+    /// Progression is paused (a deload phase): no "ready to progress" offers
+    pub progression_paused: bool,
+    /// Strength session scheduled later today: the muscles it trains are
+    /// spared like sore areas
+    pub planned_strength: Option<StrengthSessionType>,
+}
+
+impl UserContext {
+    /// A context at `now` with a fresh user state, no history, no equipment
+    /// and the default config's prescription settings; see
+    /// [`UserContextBuilder`] for the setters
+    pub fn builder(now: DateTime<Utc>) -> UserContextBuilder {
+        let config = crate::Config::default();
+        UserContextBuilder {
+            ctx: UserContext {
+                now,
+                user_state: UserMicrodoseState::default(),
+                recent_sessions: Vec::new(),
+                external_strength: None,
+                strength_history: Default::default(),
+                readiness: None,
+                equipment_available: Vec::new(),
+                rotation_weights: config.rotation_weights(),
+                warmup: config.warmup.blocks(),
+                weekly_targets: config.weekly_targets(),
+                max_hr: config.max_hr(),
+                vo2_gap_hours: config.prescription.vo2_gap_hours,
+                rotation: config.prescription.rotation,
+                gtg_friendly_only: false,
+                progression_paused: false,
+                planned_strength: None,
+            },
+        }
+    }
+}
+
+/// Builds a [`UserContext`] from [`UserContext::builder`]'s defaults, so
+/// tests and embedders only name the fields they care about
+#[derive(Clone, Debug)]
+pub struct UserContextBuilder {
+    ctx: UserContext,
+}
+
+impl UserContextBuilder {
+    pub fn user_state(mut self, user_state: UserMicrodoseState) -> Self {
+        self.ctx.user_state = user_state;
+        self
+    }
+
+    pub fn recent_sessions(mut self, recent_sessions: Vec<SessionKind>) -> Self {
+        self.ctx.recent_sessions = recent_sessions;
+        self
+    }
+
+    pub fn external_strength(mut self, signal: Option<ExternalStrengthSignal>) -> Self {
+        self.ctx.external_strength = signal;
+        self
+    }
+
+    pub fn strength_history(mut self, history: crate::strength::StrengthHistory) -> Self {
+        self.ctx.strength_history = history;
+        self
+    }
+
+    pub fn readiness(mut self, readiness: Option<crate::readiness::ReadinessSignal>) -> Self {
+        self.ctx.readiness = readiness;
+        self
+    }
+
+    pub fn equipment<I, S>(mut self, equipment: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.ctx.equipment_available = equipment.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn rotation(mut self, rotation: Vec<MicrodoseCategory>) -> Self {
+        self.ctx.rotation = rotation;
+        self
+    }
+
+    pub fn rotation_weights(mut self, weights: HashMap<MicrodoseCategory, u32>) -> Self {
+        self.ctx.rotation_weights = weights;
+        self
+    }
+
+    pub fn warmup(mut self, warmup: Vec<MicrodoseBlock>) -> Self {
+        self.ctx.warmup = warmup;
+        self
+    }
+
+    pub fn weekly_targets(mut self, targets: HashMap<MicrodoseCategory, u32>) -> Self {
+        self.ctx.weekly_targets = targets;
+        self
+    }
+
+    pub fn max_hr(mut self, max_hr: Option<u32>) -> Self {
+        self.ctx.max_hr = max_hr;
+        self
+    }
+
+    pub fn vo2_gap_hours(mut self, hours: u32) -> Self {
+        self.ctx.vo2_gap_hours = hours;
+        self
+    }
+
+    pub fn gtg_friendly_only(mut self, gtg_friendly_only: bool) -> Self {
+        self.ctx.gtg_friendly_only = gtg_friendly_only;
+        self
+    }
+
+    pub fn progression_paused(mut self, paused: bool) -> Self {
+        self.ctx.progression_paused = paused;
+        self
+    }
+
+    pub fn planned_strength(mut self, planned: Option<StrengthSessionType>) -> Self {
+        self.ctx.planned_strength = planned;
+        self
+    }
+
+    pub fn build(self) -> UserContext {
+        self.ctx
+    }
 }
 
 // ============================================================================
@@ -231,8 +923,22 @@ pub struct UserContext {
 // ============================================================================
 
 /// The complete catalog of movements and microdose definitions
+///
+/// Per-category and per-tag lookups are indexed when the catalog is built
+/// with [`Catalog::new`] and kept in sync by its mutators, such as
+/// [`Catalog::insert_microdose`].
 #[derive(Clone, Debug)]
 pub struct Catalog {
-    pub movements: HashMap<String, Movement>,
-    pub microdoses: HashMap<String, MicrodoseDefinition>,
+    pub(crate) movements: HashMap<String, Movement>,
+    pub(crate) microdoses: HashMap<String, MicrodoseDefinition>,
+    pub(crate) index: CatalogIndex,
+}
+
+/// Microdose IDs, all of them and by category and movement tag, each
+/// sorted by ID
+#[derive(Clone, Debug, Default)]
+pub(crate) struct CatalogIndex {
+    pub(crate) all: Vec<String>,
+    pub(crate) by_category: HashMap<MicrodoseCategory, Vec<String>>,
+    pub(crate) by_tag: HashMap<String, Vec<String>>,
 }
@@ -2,12 +2,113 @@
 //!
 //! Sessions are append to a JSONL (JSON Lines) file with file locking
 //! to ensure safe concurrent access.
+//!
+//! Each line is a versioned envelope,
+//! `{"v":1,"kind":"session","data":{...}}`, so other record kinds and
+//! later schema changes can share the log. Readers of sessions skip other
+//! kinds, still read the bare session lines written before the envelope
+//! (version 0), and stop with [`Error::SchemaVersion`] at a version newer
+//! than [`WAL_VERSION`].
+//!
+//! The log is append-only. Editing a session ([`update_session`]) appends an
+//! `amendment` record holding the whole edited session, and readers yield
+//! each session in its original place with its last amendment applied.
+//!
+//! Appends keep an offset index beside the WAL (see [`crate::wal_index`]),
+//! which lets [`read_sessions_since`] skip records older than its window.
 
-use crate::{MicrodoseSession, Result};
-use fs2::FileExt;
+use crate::{Error, MicrodoseSession, Result};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
 use std::fs::{File, OpenOptions};
-use std::io::{BufRead, BufReader, Write};
+use std::io::{BufRead, BufReader, Seek, SeekFrom, Write};
+use std::ops::Range;
 use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Envelope version this build writes (and the newest it reads)
+pub const WAL_VERSION: u32 = 1;
+
+/// Envelope `kind` of a session record
+pub const SESSION_KIND: &str = "session";
+
+/// Envelope `kind` of an amendment: the edited version of an earlier session
+pub const AMENDMENT_KIND: &str = "amendment";
+
+/// A record as written to the WAL
+#[derive(Serialize)]
+struct Envelope<'a, T> {
+    v: u32,
+    kind: &'a str,
+    data: &'a T,
+}
+
+/// A WAL record that session readers act on
+pub(crate) enum Record {
+    Session(MicrodoseSession),
+    /// Replaces the earlier session with the same ID
+    Amendment(MicrodoseSession),
+}
+
+/// `session` as a WAL line (without the newline)
+pub fn encode_session(session: &MicrodoseSession) -> Result<String> {
+    encode(SESSION_KIND, session)
+}
+
+/// `session`, as edited, as a WAL amendment line (without the newline)
+pub fn encode_amendment(session: &MicrodoseSession) -> Result<String> {
+    encode(AMENDMENT_KIND, session)
+}
+
+fn encode(kind: &str, session: &MicrodoseSession) -> Result<String> {
+    Ok(serde_json::to_string(&Envelope {
+        v: WAL_VERSION,
+        kind,
+        data: session,
+    })?)
+}
+
+/// The session on a WAL line, or `None` for another kind of record
+///
+/// Lines without a `v` are bare version 0 sessions. A version newer than
+/// [`WAL_VERSION`] is an [`Error::SchemaVersion`] naming `path`.
+pub fn decode_session(line: &[u8], path: &Path) -> Result<Option<MicrodoseSession>> {
+    Ok(match decode_record(line, path)? {
+        Some(Record::Session(session)) => Some(session),
+        _ => None,
+    })
+}
+
+impl Record {
+    /// The session recorded, or its edited version
+    pub(crate) fn session(&self) -> &MicrodoseSession {
+        match self {
+            Record::Session(session) | Record::Amendment(session) => session,
+        }
+    }
+}
+
+/// The session or amendment on a WAL line, or `None` for another kind
+pub(crate) fn decode_record(line: &[u8], path: &Path) -> Result<Option<Record>> {
+    let mut record: serde_json::Value = serde_json::from_slice(line)?;
+    let Some(version) = record.get("v") else {
+        return Ok(Some(Record::Session(serde_json::from_value(record)?)));
+    };
+    let version: u32 = serde_json::from_value(version.clone())?;
+    if version > WAL_VERSION {
+        return Err(Error::SchemaVersion {
+            path: path.to_path_buf(),
+            found: version,
+            supported: WAL_VERSION,
+        });
+    }
+    let wrap = match record.get("kind").and_then(|kind| kind.as_str()) {
+        Some(SESSION_KIND) => Record::Session,
+        Some(AMENDMENT_KIND) => Record::Amendment,
+        _ => return Ok(None),
+    };
+    Ok(Some(wrap(serde_json::from_value(record["data"].take())?)))
+}
 
 /// Session sink trait for persisting sessions
 pub trait SessionSink {
@@ -17,12 +118,22 @@ pub trait SessionSink {
 /// JSONL-based session sink with file locking
 pub struct JsonlSink {
     path: PathBuf,
+    lock_timeout: Duration,
 }
 
 impl JsonlSink {
     /// Create a new JSONL sink for the given path
     pub fn new(path: impl Into<PathBuf>) -> Self {
-        Self { path: path.into() }
+        Self {
+            path: path.into(),
+            lock_timeout: crate::lock::DEFAULT_TIMEOUT,
+        }
+    }
+
+    /// How long an append waits for another process's lock on the WAL
+    pub fn lock_timeout(mut self, timeout: Duration) -> Self {
+        self.lock_timeout = timeout;
+        self
     }
 
     /// Ensure the parent directory exists
@@ -34,84 +145,208 @@ impl JsonlSink {
     }
 }
 
-impl SessionSink for JsonlSink {
-    fn append(&mut self, session: &MicrodoseSession) -> Result<()> {
+impl JsonlSink {
+    /// Append `session`, returning the bytes of the WAL its line occupies
+    ///
+    /// The range is taken under the lock, so other processes' appends never
+    /// fall inside it.
+    pub fn append_range(&mut self, session: &MicrodoseSession) -> Result<Range<u64>> {
         self.ensure_parent_dir()?;
 
-        // Open file for appending
-        let file = OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(&self.path)?;
-
-        // Acquire exclusive lock
-        file.lock_exclusive()?;
+        let file = lock_current(&self.path, self.lock_timeout)?;
 
         // Write session as JSON line
-        let mut writer = std::io::BufWriter::new(&file);
-        let line = serde_json::to_string(session)?;
-        writer.write_all(line.as_bytes())?;
-        writer.write_all(b"\n")?;
-        writer.flush()?;
+        let result = append_locked(&file, &self.path, &encode_session(session)?, session);
 
         // Lock is automatically released when file is dropped
-        file.unlock()?;
+        fs2::FileExt::unlock(&file)?;
+        let range = result?;
 
         tracing::debug!("Appended session {} to WAL", session.id);
-        Ok(())
+        Ok(range)
     }
 }
 
-/// Read all sessions from a WAL file
-pub fn read_sessions(path: &Path) -> Result<Vec<MicrodoseSession>> {
-    read_sessions_internal(path, None)
+impl SessionSink for JsonlSink {
+    fn append(&mut self, session: &MicrodoseSession) -> Result<()> {
+        self.append_range(session).map(drop)
+    }
 }
 
-/// Read sessions from a WAL file since a specific cutoff date
-///
-/// This is more memory-efficient for large WAL files as it stops allocating
-/// sessions older than the cutoff. For users with thousands of sessions,
-/// this can save significant memory.
-pub fn read_sessions_since(
+/// Append `line`, recording `session`, to `file` (the WAL at `path`,
+/// locked for appending) and note it in the offset index, returning the
+/// bytes written
+fn append_locked(
+    file: &File,
     path: &Path,
-    cutoff: chrono::DateTime<chrono::Utc>,
-) -> Result<Vec<MicrodoseSession>> {
-    read_sessions_internal(path, Some(cutoff))
+    line: &str,
+    session: &MicrodoseSession,
+) -> Result<Range<u64>> {
+    let previous_len = file.metadata()?.len();
+    let mut writer = std::io::BufWriter::new(file);
+    writer.write_all(line.as_bytes())?;
+    writer.write_all(b"\n")?;
+    writer.flush()?;
+
+    // The index only speeds up reads; a failure here leaves it stale, which
+    // readers detect and fall back to a full scan
+    if let Err(e) = crate::wal_index::record_append(path, previous_len, session.performed_at) {
+        tracing::warn!("Failed to update WAL index for {:?}: {}", path, e);
+    }
+    Ok(previous_len..previous_len + line.len() as u64 + 1)
 }
 
-/// Internal helper to read sessions with optional date filtering
-fn read_sessions_internal(
+/// Open the WAL at `path` for appending (creating it if needed) and take its
+/// exclusive lock, waiting up to `timeout`
+///
+/// A rollup holding the lock may archive the WAL meanwhile, so this starts
+/// over on the file now at the path until the locked file is still there.
+pub(crate) fn lock_current(path: &Path, timeout: Duration) -> Result<File> {
+    loop {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        crate::lock::lock_exclusive_within(&file, path, timeout)?;
+        if is_current(&file, path) {
+            return Ok(file);
+        }
+        fs2::FileExt::unlock(&file)?;
+    }
+}
+
+/// Whether `file` is still the file at `path` (not renamed away or replaced)
+#[cfg(unix)]
+pub(crate) fn is_current(file: &File, path: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    match (file.metadata(), std::fs::metadata(path)) {
+        (Ok(open), Ok(current)) => open.dev() == current.dev() && open.ino() == current.ino(),
+        (Ok(_), Err(_)) => false,
+        // Can't tell; keep the file already locked
+        (Err(_), _) => true,
+    }
+}
+
+/// Whether `file` is still the file at `path`; other platforms don't let
+/// an open file be renamed or replaced
+#[cfg(not(unix))]
+pub(crate) fn is_current(_file: &File, _path: &Path) -> bool {
+    true
+}
+
+/// Read all sessions from a WAL file, waiting up to `timeout` for its lock
+pub fn read_sessions(path: &Path, timeout: Duration) -> Result<Vec<MicrodoseSession>> {
+    let mut sessions = Vec::new();
+    for_each_session(path, timeout, |session| {
+        sessions.push(session);
+        Ok(())
+    })?;
+    tracing::debug!("Read {} sessions from WAL", sessions.len());
+    Ok(sessions)
+}
+
+/// Call `f` with each session in file order, holding the shared lock
+/// (taken within `timeout`)
+///
+/// Nothing is collected, so memory stays flat however long the WAL has
+/// grown. A missing WAL has no sessions; an error from `f`, or a record
+/// from a newer krep, stops the read.
+pub fn for_each_session(
     path: &Path,
-    cutoff: Option<chrono::DateTime<chrono::Utc>>,
-) -> Result<Vec<MicrodoseSession>> {
+    timeout: Duration,
+    f: impl FnMut(MicrodoseSession) -> Result<()>,
+) -> Result<()> {
     if !path.exists() {
-        return Ok(Vec::new());
+        return Ok(());
     }
 
     let file = File::open(path)?;
     // Acquire shared lock for reading
-    file.lock_shared()?;
+    crate::lock::lock_shared_within(&file, path, timeout)?;
+    let result = for_each_session_in(&file, path, 0, f);
+    fs2::FileExt::unlock(&file)?;
+    result
+}
 
-    let reader = BufReader::new(&file);
+/// Read all sessions from `file` (opened from `path`), which the caller
+/// has already locked
+#[cfg(feature = "csv-archive")]
+pub(crate) fn read_sessions_locked(file: &File, path: &Path) -> Result<Vec<MicrodoseSession>> {
     let mut sessions = Vec::new();
+    for_each_session_in(file, path, 0, |session| {
+        sessions.push(session);
+        Ok(())
+    })?;
+    Ok(sessions)
+}
+
+/// Call `f` with each session in `file` from byte `start` on
+///
+/// An amendment whose session comes before `start` (or was lost to a
+/// corrupt line) is yielded after the rest.
+fn for_each_session_in(
+    file: &File,
+    path: &Path,
+    start: u64,
+    mut f: impl FnMut(MicrodoseSession) -> Result<()>,
+) -> Result<()> {
+    // Amendments follow the session they edit, so gather the last one for
+    // each session (and the order they were first made) before yielding any
+    let mut amended: HashMap<uuid::Uuid, (usize, MicrodoseSession)> = HashMap::new();
+    for_each_record(file, path, start, Some(AMENDMENT_KIND), |record| {
+        if let Record::Amendment(session) = record {
+            let order = amended.get(&session.id).map_or(amended.len(), |(n, _)| *n);
+            amended.insert(session.id, (order, session));
+        }
+        Ok(())
+    })?;
+
+    let mut seen = HashSet::new();
+    for_each_record(file, path, start, None, |record| match record {
+        Record::Session(session) => {
+            seen.insert(session.id);
+            match amended.get(&session.id) {
+                Some((_, edited)) => f(edited.clone()),
+                None => f(session),
+            }
+        }
+        Record::Amendment(_) => Ok(()),
+    })?;
+
+    let mut orphans: Vec<_> = amended
+        .into_iter()
+        .filter(|(id, _)| !seen.contains(id))
+        .map(|(_, amendment)| amendment)
+        .collect();
+    orphans.sort_by_key(|(order, _)| *order);
+    orphans.into_iter().try_for_each(|(_, session)| f(session))
+}
 
+/// Call `f` with each record in `file` from byte `start` on, skipping lines
+/// that don't mention `only` (when given) without parsing them
+fn for_each_record(
+    mut file: &File,
+    path: &Path,
+    start: u64,
+    only: Option<&str>,
+    mut f: impl FnMut(Record) -> Result<()>,
+) -> Result<()> {
+    file.seek(SeekFrom::Start(start))?;
+    let reader = BufReader::new(file);
     for (line_num, line_result) in reader.lines().enumerate() {
         let line = line_result?;
-        if line.trim().is_empty() {
+        if line.trim().is_empty() || only.is_some_and(|kind| !line.contains(kind)) {
             continue;
         }
 
-        match serde_json::from_str::<MicrodoseSession>(&line) {
-            Ok(session) => {
-                // Filter by cutoff date if provided
-                if let Some(cutoff_date) = cutoff {
-                    if session.performed_at >= cutoff_date {
-                        sessions.push(session);
-                    }
-                    // Skip old sessions without allocating
-                } else {
-                    sessions.push(session);
-                }
+        match decode_record(line.as_bytes(), path) {
+            Ok(Some(record)) => f(record)?,
+            Ok(None) => {}
+            Err(e @ Error::SchemaVersion { .. }) => return Err(e),
+            Err(e) if start > 0 => {
+                tracing::warn!(
+                    "Failed to parse session at line {} after byte {}: {}",
+                    line_num + 1,
+                    start,
+                    e
+                );
             }
             Err(e) => {
                 tracing::warn!("Failed to parse session at line {}: {}", line_num + 1, e);
@@ -119,9 +354,90 @@ fn read_sessions_internal(
             }
         }
     }
+    Ok(())
+}
 
-    file.unlock()?;
-    tracing::debug!("Read {} sessions from WAL", sessions.len());
+/// Edit the session with `id`, returning the updated session
+///
+/// `f` edits the session as last recorded; if it fails, or no session has
+/// that ID, nothing is written. Otherwise the edited session is appended as
+/// an amendment, under the exclusive lock and to the WAL now at `path`, so
+/// the log stays append-only and a rollup can't archive it mid-edit. The
+/// lock is taken within `timeout`.
+pub fn update_session(
+    path: &Path,
+    id: uuid::Uuid,
+    timeout: Duration,
+    f: impl FnOnce(&mut MicrodoseSession) -> Result<()>,
+) -> Result<Option<MicrodoseSession>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let file = lock_current(path, timeout)?;
+    let result = amend_locked(&file, path, id, f);
+    fs2::FileExt::unlock(&file)?;
+
+    if let Ok(Some(_)) = result {
+        tracing::debug!("Amended session {} in WAL", id);
+    }
+    result
+}
+
+/// [`update_session`] with `file`, the WAL at `path`, locked for appending
+fn amend_locked(
+    file: &File,
+    path: &Path,
+    id: uuid::Uuid,
+    f: impl FnOnce(&mut MicrodoseSession) -> Result<()>,
+) -> Result<Option<MicrodoseSession>> {
+    let mut found = None;
+    for_each_session_in(&File::open(path)?, path, 0, |session| {
+        if session.id == id {
+            found = Some(session);
+        }
+        Ok(())
+    })?;
+    let Some(mut session) = found else {
+        return Ok(None);
+    };
+
+    f(&mut session)?;
+    append_locked(file, path, &encode_amendment(&session)?, &session)?;
+    Ok(Some(session))
+}
+
+/// Read sessions from a WAL file since a specific cutoff date
+///
+/// Imports and merges append historical sessions after recent ones, so the
+/// WAL isn't in date order. With a current index (see
+/// [`crate::wal_index`]) reading starts at the first run of records that
+/// reaches the window; otherwise every entry is parsed. Only sessions in
+/// the window are kept, in file order. The shared lock is taken within
+/// `timeout`.
+pub fn read_sessions_since(
+    path: &Path,
+    cutoff: chrono::DateTime<chrono::Utc>,
+    timeout: Duration,
+) -> Result<Vec<MicrodoseSession>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let file = File::open(path)?;
+    crate::lock::lock_shared_within(&file, path, timeout)?;
+    // Appends wait for the shared lock, so the index can't go stale mid-read
+    let start = crate::wal_index::start_offset(path, cutoff).unwrap_or(0);
+    let mut sessions = Vec::new();
+    let result = for_each_session_in(&file, path, start, |session| {
+        if session.performed_at >= cutoff {
+            sessions.push(session);
+        }
+        Ok(())
+    });
+    fs2::FileExt::unlock(&file)?;
+    result?;
+    tracing::debug!("Read {} sessions from WAL since {}", sessions.len(), cutoff);
     Ok(sessions)
 }
 
@@ -133,19 +449,64 @@ mod tests {
 
     fn create_test_session() -> MicrodoseSession {
         MicrodoseSession {
-            id: Uuid::new_v4(),
-            definition_id: "test_def".into(),
-            performed_at: Utc::now(),
             started_at: Some(Utc::now()),
             completed_at: Some(Utc::now()),
             actual_duration_seconds: Some(300),
-            metrics_realized: vec![],
             perceived_rpe: Some(7),
             avg_hr: Some(145),
             max_hr: Some(165),
+            ..MicrodoseSession::new("test_def", Utc::now())
         }
     }
 
+    #[test]
+    fn test_append_waiting_on_a_rollup_goes_to_the_new_wal() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let wal_path = temp_dir.path().join("test.wal");
+        let processed = temp_dir.path().join("test.wal.processed");
+        JsonlSink::new(&wal_path)
+            .append(&create_test_session())
+            .unwrap();
+
+        // A rollup holds the lock while it archives the WAL
+        let held = File::open(&wal_path).unwrap();
+        crate::lock::lock_exclusive(&held, &wal_path).unwrap();
+        let session = create_test_session();
+        let writer = {
+            let (wal_path, session) = (wal_path.clone(), session.clone());
+            std::thread::spawn(move || JsonlSink::new(&wal_path).append(&session))
+        };
+        std::thread::sleep(Duration::from_millis(50));
+        std::fs::rename(&wal_path, &processed).unwrap();
+        fs2::FileExt::unlock(&held).unwrap();
+        writer.join().unwrap().unwrap();
+
+        assert_eq!(
+            read_sessions(&processed, crate::lock::DEFAULT_TIMEOUT)
+                .unwrap()
+                .len(),
+            1
+        );
+        let sessions = read_sessions(&wal_path, crate::lock::DEFAULT_TIMEOUT).unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].id, session.id);
+    }
+
+    #[test]
+    fn test_append_gives_up_after_its_lock_timeout() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let wal_path = temp_dir.path().join("test.wal");
+        std::fs::write(&wal_path, "").unwrap();
+        let held = File::open(&wal_path).unwrap();
+        crate::lock::lock_exclusive(&held, &wal_path).unwrap();
+
+        let err = JsonlSink::new(&wal_path)
+            .lock_timeout(Duration::ZERO)
+            .append(&create_test_session())
+            .unwrap_err();
+        assert!(matches!(err, Error::LockTimeout { .. }));
+    }
+
     #[test]
     fn test_append_and_read_single_session() {
         let temp_dir = tempfile::tempdir().unwrap();
@@ -159,7 +520,7 @@ mod tests {
         sink.append(&session).unwrap();
 
         // Read back
-        let sessions = read_sessions(&wal_path).unwrap();
+        let sessions = read_sessions(&wal_path, crate::lock::DEFAULT_TIMEOUT).unwrap();
         assert_eq!(sessions.len(), 1);
         assert_eq!(sessions[0].id, session_id);
     }
@@ -178,16 +539,229 @@ mod tests {
         }
 
         // Read back
-        let sessions = read_sessions(&wal_path).unwrap();
+        let sessions = read_sessions(&wal_path, crate::lock::DEFAULT_TIMEOUT).unwrap();
         assert_eq!(sessions.len(), 5);
     }
 
+    #[test]
+    fn test_read_sessions_since_finds_sessions_out_of_order() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let wal_path = temp_dir.path().join("test.wal");
+        let now = Utc::now();
+
+        // Recent doses, then an import appending older history after them
+        let mut sink = JsonlSink::new(&wal_path);
+        for hours_ago in (0..100).rev().chain(100..1000) {
+            let mut session = create_test_session();
+            session.performed_at = now - chrono::Duration::hours(hours_ago);
+            sink.append(&session).unwrap();
+        }
+
+        let cutoff = now - chrono::Duration::hours(240);
+        let recent = read_sessions_since(&wal_path, cutoff, crate::lock::DEFAULT_TIMEOUT).unwrap();
+        let expected: Vec<_> = read_sessions(&wal_path, crate::lock::DEFAULT_TIMEOUT)
+            .unwrap()
+            .into_iter()
+            .filter(|s| s.performed_at >= cutoff)
+            .map(|s| s.id)
+            .collect();
+        assert_eq!(expected.len(), 241);
+        assert_eq!(
+            recent.into_iter().map(|s| s.id).collect::<Vec<_>>(),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_read_sessions_since_skips_torn_line() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let wal_path = temp_dir.path().join("test.wal");
+
+        let session = create_test_session();
+        let mut sink = JsonlSink::new(&wal_path);
+        sink.append(&session).unwrap();
+        // An append interrupted mid-write
+        let mut file = OpenOptions::new().append(true).open(&wal_path).unwrap();
+        file.write_all(b"{\"id\":\"trunc").unwrap();
+
+        let recent = read_sessions_since(
+            &wal_path,
+            Utc::now() - chrono::Duration::days(7),
+            crate::lock::DEFAULT_TIMEOUT,
+        )
+        .unwrap();
+        assert_eq!(recent.len(), 1);
+        assert_eq!(recent[0].id, session.id);
+    }
+
+    #[test]
+    fn test_update_session() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let wal_path = temp_dir.path().join("test.wal");
+
+        let first = create_test_session();
+        let second = create_test_session();
+        let mut sink = JsonlSink::new(&wal_path);
+        sink.append(&first).unwrap();
+        sink.append(&second).unwrap();
+        let before = std::fs::read_to_string(&wal_path).unwrap();
+
+        let updated = update_session(&wal_path, second.id, crate::lock::DEFAULT_TIMEOUT, |s| {
+            s.avg_hr = Some(150);
+            Ok(())
+        })
+        .unwrap()
+        .unwrap();
+        assert_eq!(updated.avg_hr, Some(150));
+
+        // The edit is appended; the sessions read back in their places
+        let after = std::fs::read_to_string(&wal_path).unwrap();
+        assert!(after.starts_with(&before));
+        assert!(after
+            .lines()
+            .last()
+            .unwrap()
+            .starts_with(r#"{"v":1,"kind":"amendment","#));
+        let sessions = read_sessions(&wal_path, crate::lock::DEFAULT_TIMEOUT).unwrap();
+        assert_eq!(sessions.len(), 2);
+        assert_eq!(sessions[1].id, second.id);
+        assert_eq!(sessions[1].avg_hr, Some(150));
+
+        // A later edit starts from the amended session and wins
+        update_session(&wal_path, second.id, crate::lock::DEFAULT_TIMEOUT, |s| {
+            assert_eq!(s.avg_hr, Some(150));
+            s.max_hr = Some(171);
+            Ok(())
+        })
+        .unwrap()
+        .unwrap();
+        let sessions = read_sessions(&wal_path, crate::lock::DEFAULT_TIMEOUT).unwrap();
+        assert_eq!(
+            (sessions[1].avg_hr, sessions[1].max_hr),
+            (Some(150), Some(171))
+        );
+        let after = std::fs::read_to_string(&wal_path).unwrap();
+
+        // A failed edit or an unknown ID leaves the WAL alone
+        let failed = update_session(&wal_path, first.id, crate::lock::DEFAULT_TIMEOUT, |_| {
+            Err(crate::Error::Other("no samples".into()))
+        });
+        assert!(failed.is_err());
+        assert!(update_session(
+            &wal_path,
+            Uuid::new_v4(),
+            crate::lock::DEFAULT_TIMEOUT,
+            |_| Ok(())
+        )
+        .unwrap()
+        .is_none());
+        assert_eq!(std::fs::read_to_string(&wal_path).unwrap(), after);
+    }
+
+    #[test]
+    fn test_update_waiting_on_a_rollup_leaves_the_archive_alone() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let wal_path = temp_dir.path().join("test.wal");
+        let processed = temp_dir.path().join("test.wal.processed");
+        let session = create_test_session();
+        JsonlSink::new(&wal_path).append(&session).unwrap();
+        let archived = std::fs::read_to_string(&wal_path).unwrap();
+
+        // A rollup holds the lock while it archives the WAL
+        let held = File::open(&wal_path).unwrap();
+        crate::lock::lock_exclusive(&held, &wal_path).unwrap();
+        let updater = {
+            let wal_path = wal_path.clone();
+            std::thread::spawn(move || {
+                update_session(&wal_path, session.id, crate::lock::DEFAULT_TIMEOUT, |s| {
+                    s.avg_hr = Some(150);
+                    Ok(())
+                })
+            })
+        };
+        std::thread::sleep(Duration::from_millis(50));
+        std::fs::rename(&wal_path, &processed).unwrap();
+        fs2::FileExt::unlock(&held).unwrap();
+
+        // The session is in the archive now, not the new WAL
+        assert!(updater.join().unwrap().unwrap().is_none());
+        assert_eq!(std::fs::read_to_string(&processed).unwrap(), archived);
+        assert!(read_sessions(&wal_path, crate::lock::DEFAULT_TIMEOUT)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_versioned_envelope() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let wal_path = temp_dir.path().join("test.wal");
+
+        // A bare session from before the envelope, then an enveloped one
+        // and a record of another kind
+        let old = create_test_session();
+        let new = create_test_session();
+        let mut file = File::create(&wal_path).unwrap();
+        writeln!(file, "{}", serde_json::to_string(&old).unwrap()).unwrap();
+        JsonlSink::new(&wal_path).append(&new).unwrap();
+        let mut file = OpenOptions::new().append(true).open(&wal_path).unwrap();
+        writeln!(file, r#"{{"v":1,"kind":"weigh_in","data":{{"kg":80.5}}}}"#).unwrap();
+
+        let line = std::fs::read_to_string(&wal_path).unwrap();
+        let line = line.lines().nth(1).unwrap();
+        assert!(line.starts_with(r#"{"v":1,"kind":"session","data":{"#));
+        let ids: Vec<_> = read_sessions(&wal_path, crate::lock::DEFAULT_TIMEOUT)
+            .unwrap()
+            .into_iter()
+            .map(|s| s.id)
+            .collect();
+        assert_eq!(ids, vec![old.id, new.id]);
+
+        // A bare session is amended in the envelope
+        update_session(&wal_path, old.id, crate::lock::DEFAULT_TIMEOUT, |s| {
+            s.avg_hr = Some(150);
+            Ok(())
+        })
+        .unwrap()
+        .unwrap();
+        let contents = std::fs::read_to_string(&wal_path).unwrap();
+        assert!(contents
+            .lines()
+            .last()
+            .unwrap()
+            .starts_with(r#"{"v":1,"kind":"amendment","#));
+        let sessions = read_sessions(&wal_path, crate::lock::DEFAULT_TIMEOUT).unwrap();
+        assert_eq!(sessions[0].id, old.id);
+        assert_eq!(sessions[0].avg_hr, Some(150));
+
+        // A newer krep's records stop the read instead of being dropped
+        let mut file = OpenOptions::new().append(true).open(&wal_path).unwrap();
+        writeln!(file, r#"{{"v":2,"kind":"session","data":{{}}}}"#).unwrap();
+        for err in [
+            read_sessions(&wal_path, crate::lock::DEFAULT_TIMEOUT).unwrap_err(),
+            read_sessions_since(
+                &wal_path,
+                Utc::now() - chrono::Duration::days(7),
+                crate::lock::DEFAULT_TIMEOUT,
+            )
+            .unwrap_err(),
+        ] {
+            assert!(matches!(
+                err,
+                crate::Error::SchemaVersion {
+                    found: 2,
+                    supported: WAL_VERSION,
+                    ..
+                }
+            ));
+        }
+    }
+
     #[test]
     fn test_read_empty_wal() {
         let temp_dir = tempfile::tempdir().unwrap();
         let wal_path = temp_dir.path().join("nonexistent.wal");
 
-        let sessions = read_sessions(&wal_path).unwrap();
+        let sessions = read_sessions(&wal_path, crate::lock::DEFAULT_TIMEOUT).unwrap();
         assert!(sessions.is_empty());
     }
 }
@@ -0,0 +1,346 @@
+//! Byte-offset index over the WAL.
+//!
+//! Imports and merges append historical sessions after recent ones, so the
+//! WAL isn't in date order and a read of recent sessions can't stop at the
+//! first old record. The index (`microdose_sessions.wal.idx`, JSON) records
+//! where each run of records starts and the range of `performed_at` in it,
+//! letting [`start_offset`] skip every run that ends before a cutoff.
+//!
+//! Like [`crate::csv_index`], the index is a cache: it stores the WAL
+//! length it describes, and a missing, unreadable or stale index just
+//! means a full scan. Appends update it under the WAL's exclusive lock,
+//! rebuilding it when needed, and rollup removes it with the WAL.
+
+use crate::wal::decode_record;
+use crate::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::io::BufRead;
+use std::path::{Path, PathBuf};
+
+/// Current index format version; other versions are ignored and rebuilt
+const INDEX_VERSION: u32 = 1;
+
+/// Records per chunk before appends start a new one
+const CHUNK_RECORDS: usize = 1000;
+
+/// Index over a WAL
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct WalIndex {
+    pub version: u32,
+    /// WAL length in bytes when the index was written
+    pub wal_len: u64,
+    pub chunks: Vec<Chunk>,
+}
+
+/// A run of consecutive records
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Chunk {
+    /// Byte offset of the chunk's first record
+    pub offset: u64,
+    pub records: usize,
+    pub earliest: DateTime<Utc>,
+    pub latest: DateTime<Utc>,
+}
+
+impl Chunk {
+    fn new(offset: u64, at: DateTime<Utc>) -> Self {
+        Self {
+            offset,
+            records: 1,
+            earliest: at,
+            latest: at,
+        }
+    }
+
+    fn add(&mut self, at: DateTime<Utc>) {
+        self.records += 1;
+        self.earliest = self.earliest.min(at);
+        self.latest = self.latest.max(at);
+    }
+}
+
+/// Index file for a WAL: the WAL path with `.idx` appended
+pub fn index_path(wal_path: &Path) -> PathBuf {
+    let mut path = wal_path.as_os_str().to_owned();
+    path.push(".idx");
+    PathBuf::from(path)
+}
+
+/// Load the index if it exists, is readable and matches the WAL length
+pub fn load(wal_path: &Path) -> Option<WalIndex> {
+    let contents = std::fs::read_to_string(index_path(wal_path)).ok()?;
+    let index: WalIndex = match serde_json::from_str(&contents) {
+        Ok(index) => index,
+        Err(e) => {
+            tracing::warn!("Ignoring unreadable WAL index for {:?}: {}", wal_path, e);
+            return None;
+        }
+    };
+    let wal_len = std::fs::metadata(wal_path).ok()?.len();
+    if index.version != INDEX_VERSION || index.wal_len != wal_len {
+        tracing::debug!("WAL index for {:?} is stale", wal_path);
+        return None;
+    }
+    Some(index)
+}
+
+/// Where to start reading for sessions at or after `cutoff`
+///
+/// `None` means there is no usable index and the whole file must be read.
+/// An offset at the end of the file means no chunk can match.
+pub fn start_offset(wal_path: &Path, cutoff: DateTime<Utc>) -> Option<u64> {
+    let index = load(wal_path)?;
+    Some(
+        index
+            .chunks
+            .iter()
+            .filter(|chunk| chunk.latest >= cutoff)
+            .map(|chunk| chunk.offset)
+            .min()
+            .unwrap_or(index.wal_len),
+    )
+}
+
+/// Record a session (or amendment) performed at `performed_at` just
+/// appended to the WAL, whose length was `previous_len` before
+///
+/// The caller holds the WAL's exclusive lock. If the existing index doesn't
+/// describe exactly `previous_len` bytes it is rebuilt from the WAL, which
+/// takes in the new record too.
+pub(crate) fn record_append(
+    wal_path: &Path,
+    previous_len: u64,
+    performed_at: DateTime<Utc>,
+) -> Result<()> {
+    let index = match read_raw(wal_path) {
+        Some(mut index) if index.version == INDEX_VERSION && index.wal_len == previous_len => {
+            match index.chunks.last_mut() {
+                Some(chunk) if chunk.records < CHUNK_RECORDS => chunk.add(performed_at),
+                _ => index.chunks.push(Chunk::new(previous_len, performed_at)),
+            }
+            index.wal_len = std::fs::metadata(wal_path)?.len();
+            index
+        }
+        _ => {
+            tracing::info!("Rebuilding WAL index for {:?}", wal_path);
+            rebuild(wal_path)?
+        }
+    };
+    save(wal_path, &index)
+}
+
+/// Build an index by scanning the whole WAL
+///
+/// A record from a newer krep is an error, so that readers fall back to the
+/// full scan that reports it rather than skip past it.
+pub fn rebuild(wal_path: &Path) -> Result<WalIndex> {
+    let mut reader = std::io::BufReader::new(std::fs::File::open(wal_path)?);
+    let mut chunks = Vec::new();
+    let mut current: Option<Chunk> = None;
+    let mut offset = 0;
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        let read = reader.read_until(b'\n', &mut line)?;
+        if read == 0 {
+            break;
+        }
+        let line_offset = offset;
+        offset += read as u64;
+
+        // Corrupt lines are still inside a chunk's byte range; they just
+        // don't widen its date range
+        let at = match decode_record(line.trim_ascii(), wal_path) {
+            Ok(Some(record)) => record.session().performed_at,
+            Ok(None) => continue,
+            Err(e @ crate::Error::SchemaVersion { .. }) => return Err(e),
+            Err(_) => continue,
+        };
+        match current.as_mut() {
+            Some(chunk) => chunk.add(at),
+            None => current = Some(Chunk::new(line_offset, at)),
+        }
+        if current.as_ref().is_some_and(|c| c.records >= CHUNK_RECORDS) {
+            chunks.extend(current.take());
+        }
+    }
+    chunks.extend(current);
+
+    Ok(WalIndex {
+        version: INDEX_VERSION,
+        wal_len: offset,
+        chunks,
+    })
+}
+
+/// Read the index file without checking it against the WAL
+fn read_raw(wal_path: &Path) -> Option<WalIndex> {
+    let contents = std::fs::read_to_string(index_path(wal_path)).ok()?;
+    serde_json::from_str(&contents).ok()
+}
+
+/// Write the index atomically next to the WAL
+fn save(wal_path: &Path, index: &WalIndex) -> Result<()> {
+    let path = index_path(wal_path);
+    let dir = path.parent().unwrap_or_else(|| Path::new("."));
+    let temp = tempfile::NamedTempFile::new_in(dir)?;
+    serde_json::to_writer(temp.as_file(), index)?;
+    temp.persist(&path).map_err(|e| crate::Error::Io(e.error))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wal::{read_sessions, read_sessions_since, JsonlSink, SessionSink};
+    use crate::MicrodoseSession;
+    use chrono::Duration;
+
+    fn session_at(at: DateTime<Utc>) -> MicrodoseSession {
+        MicrodoseSession::new("burpee_emom", at)
+    }
+
+    #[test]
+    fn test_appends_keep_the_index_current() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let wal_path = temp_dir.path().join("microdose_sessions.wal");
+        let now = Utc::now();
+
+        // A long history, then recent doses
+        let mut sink = JsonlSink::new(&wal_path);
+        for days_ago in (30..30 + CHUNK_RECORDS as i64 + 10).rev() {
+            sink.append(&session_at(now - Duration::days(days_ago)))
+                .unwrap();
+        }
+        for hours_ago in (0..5).rev() {
+            sink.append(&session_at(now - Duration::hours(hours_ago)))
+                .unwrap();
+        }
+
+        let index = load(&wal_path).unwrap();
+        assert_eq!(index, rebuild(&wal_path).unwrap());
+        assert_eq!(index.chunks.len(), 2);
+        assert_eq!(index.chunks[0].records, CHUNK_RECORDS);
+
+        // Reading the last week starts at the second chunk
+        let cutoff = now - Duration::days(7);
+        assert_eq!(
+            start_offset(&wal_path, cutoff),
+            Some(index.chunks[1].offset)
+        );
+        assert_eq!(
+            read_sessions_since(&wal_path, cutoff, crate::lock::DEFAULT_TIMEOUT)
+                .unwrap()
+                .len(),
+            5
+        );
+    }
+
+    #[test]
+    fn test_imported_history_after_recent_doses_is_still_read() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let wal_path = temp_dir.path().join("microdose_sessions.wal");
+        let now = Utc::now();
+
+        let mut sink = JsonlSink::new(&wal_path);
+        for days_ago in (30..30 + CHUNK_RECORDS as i64).rev() {
+            sink.append(&session_at(now - Duration::days(days_ago)))
+                .unwrap();
+        }
+        // An import appends a week of history after today's dose
+        let today = session_at(now);
+        sink.append(&today).unwrap();
+        for days_ago in 1..8 {
+            sink.append(&session_at(now - Duration::days(days_ago)))
+                .unwrap();
+        }
+
+        let cutoff = now - Duration::days(3) - Duration::minutes(1);
+        let recent = read_sessions_since(&wal_path, cutoff, crate::lock::DEFAULT_TIMEOUT).unwrap();
+        let expected: Vec<_> = read_sessions(&wal_path, crate::lock::DEFAULT_TIMEOUT)
+            .unwrap()
+            .into_iter()
+            .filter(|s| s.performed_at >= cutoff)
+            .map(|s| s.id)
+            .collect();
+        assert_eq!(expected.len(), 4);
+        assert_eq!(
+            recent.into_iter().map(|s| s.id).collect::<Vec<_>>(),
+            expected
+        );
+        assert!(start_offset(&wal_path, cutoff).unwrap() > 0);
+    }
+
+    #[test]
+    fn test_amendment_of_a_skipped_session_is_read() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let wal_path = temp_dir.path().join("microdose_sessions.wal");
+        let now = Utc::now();
+
+        let mut sink = JsonlSink::new(&wal_path);
+        let old = session_at(now - Duration::days(400));
+        sink.append(&old).unwrap();
+        for days_ago in (30..30 + CHUNK_RECORDS as i64).rev() {
+            sink.append(&session_at(now - Duration::days(days_ago)))
+                .unwrap();
+        }
+        // Correcting a mistyped date moves the session into the window
+        crate::wal::update_session(&wal_path, old.id, crate::lock::DEFAULT_TIMEOUT, |s| {
+            s.performed_at = now - Duration::days(1);
+            Ok(())
+        })
+        .unwrap()
+        .unwrap();
+
+        let cutoff = now - Duration::days(7);
+        assert!(start_offset(&wal_path, cutoff).unwrap() > 0);
+        let recent = read_sessions_since(&wal_path, cutoff, crate::lock::DEFAULT_TIMEOUT).unwrap();
+        assert_eq!(recent.len(), 1);
+        assert_eq!(recent[0].id, old.id);
+    }
+
+    #[test]
+    fn test_stale_index_means_a_full_scan() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let wal_path = temp_dir.path().join("microdose_sessions.wal");
+        let now = Utc::now();
+
+        let mut sink = JsonlSink::new(&wal_path);
+        sink.append(&session_at(now - Duration::days(30))).unwrap();
+        assert!(load(&wal_path).is_some());
+
+        // A line written behind the index's back
+        let mut contents = std::fs::read_to_string(&wal_path).unwrap();
+        contents.push_str(&crate::wal::encode_session(&session_at(now)).unwrap());
+        contents.push('\n');
+        std::fs::write(&wal_path, contents).unwrap();
+        assert_eq!(start_offset(&wal_path, now - Duration::days(7)), None);
+        assert_eq!(
+            read_sessions_since(
+                &wal_path,
+                now - Duration::days(7),
+                crate::lock::DEFAULT_TIMEOUT
+            )
+            .unwrap()
+            .len(),
+            1
+        );
+
+        // The next append rebuilds it
+        sink.append(&session_at(now)).unwrap();
+        assert_eq!(load(&wal_path).unwrap(), rebuild(&wal_path).unwrap());
+    }
+
+    #[test]
+    fn test_newer_record_is_not_indexed_past() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let wal_path = temp_dir.path().join("microdose_sessions.wal");
+        std::fs::write(&wal_path, "{\"v\":99,\"kind\":\"session\",\"data\":{}}\n").unwrap();
+
+        assert!(matches!(
+            rebuild(&wal_path),
+            Err(crate::Error::SchemaVersion { .. })
+        ));
+    }
+}
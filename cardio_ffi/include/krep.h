@@ -0,0 +1,64 @@
+/*
+ * C API for the Krep cardio microdose core (libkrep).
+ *
+ * All functions take a UTF-8 JSON request string and return a newly
+ * allocated JSON response string:
+ *   {"ok": true, "data": ...}  or  {"ok": false, "error": "...", "code": "..."}
+ *
+ * "error" is a message for people; "code" is stable and meant for branching:
+ * "io", "json", "csv", "toml", "sqlite", "config", "catalog_validation",
+ * "state", "lock_timeout", "prescription", "corruption", "not_found",
+ * "schema_version" or "other".
+ *
+ * Every returned string must be released with krep_string_free().
+ */
+#ifndef KREP_H
+#define KREP_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* {"data_dir": "...", "category": "vo2", "surprise": false}  (all fields optional;
+ *  "surprise" favours definitions not done recently, ignoring "category")
+ * data: {"definition", "reps", "per_side", "style",
+ *        "warmup": [{"movement_id", "duration_hint_seconds", ...}],
+ *        "hr_target": null | {"zone": {"low_pct", "high_pct", "measure"}, "max_hr"},
+ *        "rationale": ["Rehab mode (left knee): mobility only", ...],
+ *        "ready_to_progress": false} */
+char *krep_prescribe(const char *request_json);
+
+/* {"definition_id": "...", "data_dir": "...", "actual_duration_seconds": 300,
+ *  "perceived_rpe": 7, "avg_hr": 140, "max_hr": 165, "hr_recovery": 28,
+ *  "metrics_realized": [{"type": "reps", "key": "reps", "per_set": [5, 5, 4]}],
+ *  "tags": ["travel"]}
+ *  (definition_id required) */
+char *krep_log_session(const char *request_json);
+
+/* {"data_dir": "...", "days": 7, "tags": ["travel"], "compare": false}  (all fields optional)
+ * data: {"days", "total_sessions", "total_seconds", "by_category",
+ *        "bodyweight": null | {"latest_kg", "change_kg", "slope_kg_per_week", ...},
+ *        "targets": [{"category", "target", "done"}]  (omitted without weekly_targets),
+ *        "heart_rate": [{"category", "zone", "sessions_with_hr", "met_zone",
+ *                        "mean_avg_hr", "mean_max_hr"}]  (omitted without max HR),
+ *        "hr_recovery": null | {"latest_bpm", "mean_bpm", "change_bpm", "series_bpm"},
+ *        "goal": {"week_start", "target", "done", "travel"}  (omitted without a goal),
+ *        "week_over_week": {"this_week", "last_week"}  (only with "compare"; each has
+ *                          "total_sessions", "total_seconds", "by_category", "avg_rpe"),
+ *        "time_in_category": [{"week_start", "minutes": {"vo2": 15, ...}}]
+ *                            (calendar weeks, Monday start; omitted without sessions),
+ *        "rehab": [{"injury", "started_at", "ended_at", "sessions", "total_seconds",
+ *                   "by_category"}]  (rehab periods in the window; omitted without any),
+ *        "phases": [{"name", "start", "end", "current", "sessions", "total_seconds",
+ *                    "by_category"}]  (configured phases in the window; omitted without any),
+ *        "household": [{"name", "today", "sessions", "total_seconds", "by_category"}]
+ *                     (household members, then "you"; omitted without [household] members)} */
+char *krep_stats(const char *request_json);
+
+void krep_string_free(char *ptr);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* KREP_H */
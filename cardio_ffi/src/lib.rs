@@ -0,0 +1,431 @@
+//! C FFI surface for cardio_core.
+//!
+//! Every function takes a UTF-8 JSON request string and returns a newly
+//! allocated JSON response string, so non-Rust frontends (a GNOME extension
+//! helper, a Flutter app) can reuse the core logic without mirroring its types.
+//!
+//! Responses use a common envelope:
+//! - `{"ok": true, "data": ...}` on success
+//! - `{"ok": false, "error": "...", "code": "not_found"}` on failure, with
+//!   the stable [`Error::code`](cardio_core::Error::code) to branch on
+//!
+//! Returned strings must be released with [`krep_string_free`].
+//! See `include/krep.h` for the C declarations.
+
+use cardio_core::bodyweight::{self, read_bodyweight, BODYWEIGHT_FILE};
+use cardio_core::goal;
+use cardio_core::heart_rate::{self, HrTarget};
+use cardio_core::history::filter_by_tags;
+use cardio_core::household;
+use cardio_core::phase;
+use cardio_core::rehab;
+use cardio_core::stats::{
+    compare_weeks, summarize_sessions, target_progress, time_in_category, StatsReport,
+};
+use cardio_core::{
+    normalize_tags, store, Config, DataStore, Error, MicrodoseBlock, MicrodoseCategory,
+    MicrodoseDefinition, MicrodoseSession, MovementStyle, RealizedMetric, Result, SessionKind,
+};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::PathBuf;
+
+/// Request for `krep_prescribe`
+#[derive(Debug, Deserialize)]
+struct PrescribeRequest {
+    data_dir: Option<PathBuf>,
+    category: Option<MicrodoseCategory>,
+    /// Novelty-weighted pick across the catalog (ignores `category`)
+    #[serde(default)]
+    surprise: bool,
+}
+
+/// Request for `krep_log_session`
+#[derive(Debug, Deserialize)]
+struct LogSessionRequest {
+    data_dir: Option<PathBuf>,
+    definition_id: String,
+    actual_duration_seconds: Option<u32>,
+    perceived_rpe: Option<u8>,
+    avg_hr: Option<u8>,
+    max_hr: Option<u8>,
+    /// HR drop in the 60 seconds after finishing
+    hr_recovery: Option<u8>,
+    #[serde(default)]
+    metrics_realized: Vec<RealizedMetric>,
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+/// Request for `krep_stats`
+#[derive(Debug, Deserialize)]
+struct StatsRequest {
+    data_dir: Option<PathBuf>,
+    #[serde(default = "default_stats_days")]
+    days: i64,
+    /// Only count sessions carrying one of these tags (empty = all)
+    #[serde(default)]
+    tags: Vec<String>,
+    /// Include this week vs last week (same tag filter)
+    #[serde(default)]
+    compare: bool,
+}
+
+fn default_stats_days() -> i64 {
+    7
+}
+
+/// Prescription returned to the caller
+#[derive(Debug, Serialize)]
+struct PrescriptionResponse {
+    definition: MicrodoseDefinition,
+    reps: Option<i32>,
+    /// `reps` is counted per side (total work is double)
+    per_side: bool,
+    style: Option<MovementStyle>,
+    /// Generated warmup blocks to do first (VO2 only; empty when disabled)
+    warmup: Vec<MicrodoseBlock>,
+    /// Heart-rate zone to work in (VO2 and Zone 2 only)
+    hr_target: Option<HrTarget>,
+    /// Why the choice was restricted (e.g. rehab mode)
+    rationale: Vec<String>,
+    /// Recent sessions felt easy: offer to progress now
+    ready_to_progress: bool,
+}
+
+/// JSON envelope for every response
+#[derive(Debug, Serialize)]
+struct Response<T> {
+    ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data: Option<T>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    code: Option<&'static str>,
+}
+
+/// Resolve the data directory from the request or the user's config
+fn resolve_data_dir(data_dir: Option<PathBuf>, config: &Config) -> PathBuf {
+    data_dir.unwrap_or_else(|| config.data.data_dir.clone())
+}
+
+fn prescribe(request: PrescribeRequest) -> Result<PrescriptionResponse> {
+    let config = Config::load()?;
+    let data_dir = resolve_data_dir(request.data_dir, &config);
+    let store = DataStore::open(&data_dir, &config)?;
+
+    let now = store.now();
+    let recent_sessions = store.recent_sessions(store::PRESCRIPTION_HISTORY_DAYS)?;
+    let prescription = if request.surprise {
+        store.surprise_prescription(&recent_sessions, now)?
+    } else {
+        store.next_prescription(&recent_sessions, request.category, now)?
+    };
+    let per_side = prescription.definition.reps_per_side();
+
+    Ok(PrescriptionResponse {
+        definition: prescription.definition,
+        reps: prescription.reps,
+        per_side,
+        style: prescription.style,
+        warmup: prescription.warmup,
+        hr_target: prescription.hr_target,
+        rationale: prescription.rationale,
+        ready_to_progress: prescription.ready_to_progress,
+    })
+}
+
+fn log_session(request: LogSessionRequest) -> Result<MicrodoseSession> {
+    let config = Config::load()?;
+    let data_dir = resolve_data_dir(request.data_dir, &config);
+    let mut store = DataStore::open(&data_dir, &config)?;
+
+    let definition = store
+        .catalog
+        .definition(&request.definition_id)
+        .ok_or_else(|| {
+            Error::NotFound(format!("Unknown definition ID: {}", request.definition_id))
+        })?;
+
+    let now = store.now();
+    let recent_sessions = store.recent_sessions(store::PRESCRIPTION_HISTORY_DAYS)?;
+    let prescription = store.prescription_for(definition, &recent_sessions, now);
+    let session = MicrodoseSession {
+        id: uuid::Uuid::new_v4(),
+        definition_id: definition.id.clone(),
+        performed_at: now,
+        started_at: Some(now),
+        completed_at: Some(now),
+        actual_duration_seconds: Some(
+            request
+                .actual_duration_seconds
+                .unwrap_or(definition.suggested_duration_seconds),
+        ),
+        metrics_realized: request.metrics_realized,
+        perceived_rpe: request.perceived_rpe,
+        avg_hr: request.avg_hr,
+        max_hr: request.max_hr,
+        hr_recovery: request.hr_recovery,
+        hr_series_path: None,
+        paused_seconds: None,
+        tags: normalize_tags(&request.tags),
+    };
+
+    // The same bookkeeping as the CLI and tray (state, stats cache,
+    // observers, progression log, achievements); observer failures have
+    // nowhere to go over FFI and the session is saved regardless
+    let state_before = store.state.clone();
+    store.log(&session, &prescription)?;
+    store.record_progression(&state_before, now)?;
+    store.check_achievements(now)?;
+
+    Ok(session)
+}
+
+fn stats(request: StatsRequest) -> Result<StatsReport> {
+    let config = Config::load()?;
+    let data_dir = resolve_data_dir(request.data_dir, &config);
+    let store = DataStore::open(&data_dir, &config)?;
+    let catalog = store.catalog;
+
+    let tags = normalize_tags(&request.tags);
+    let sessions = store.recent_sessions(request.days)?;
+    let sessions: Vec<SessionKind> = filter_by_tags(&sessions, &tags)
+        .into_iter()
+        .cloned()
+        .collect();
+
+    let weigh_ins = read_bodyweight(&data_dir.join(BODYWEIGHT_FILE), config.data.lock_timeout())?;
+    let user_state = &store.state;
+    let now = store.now();
+    let since = now - chrono::Duration::days(request.days);
+    let tz = config.timezone();
+
+    // Targets always cover the trailing week of all sessions, like the engine
+    let week = store.recent_sessions(7)?;
+    let targets = target_progress(
+        &week,
+        catalog,
+        &config.weekly_targets(),
+        now - chrono::Duration::days(7),
+    );
+    let heart_rate = config
+        .max_hr()
+        .map(|max_hr| heart_rate::compare_sessions(&sessions, catalog, max_hr))
+        .unwrap_or_default();
+
+    let week_over_week = if request.compare {
+        let fortnight = store.recent_sessions(14)?;
+        let fortnight: Vec<SessionKind> = filter_by_tags(&fortnight, &tags)
+            .into_iter()
+            .cloned()
+            .collect();
+        Some(compare_weeks(&fortnight, catalog, now))
+    } else {
+        None
+    };
+
+    Ok(StatsReport {
+        sessions: summarize_sessions(&sessions, catalog, request.days),
+        bodyweight: bodyweight::trend(&weigh_ins, since),
+        targets,
+        heart_rate,
+        hr_recovery: heart_rate::recovery_trend(&sessions),
+        goal: user_state
+            .weekly_goal
+            .as_ref()
+            .map(|goal| goal::current_progress(goal, &week, &tz, now)),
+        week_over_week,
+        time_in_category: time_in_category(&sessions, catalog, &tz),
+        rehab: rehab::summarize(&user_state.rehab, &sessions, catalog, since, now),
+        phases: phase::summarize(
+            &config.phases,
+            &sessions,
+            catalog,
+            &tz,
+            tz.date_of(since),
+            tz.date_of(now),
+        ),
+        household: household::load(&config, &data_dir, catalog, request.days, now)?,
+    })
+}
+
+/// Parse a JSON request from a C string
+///
+/// # Safety
+/// `request` must be null or point to a valid NUL-terminated string.
+unsafe fn parse_request<T: DeserializeOwned>(request: *const c_char) -> Result<T> {
+    if request.is_null() {
+        return Err(Error::Other("Request pointer is null".into()));
+    }
+    let json = CStr::from_ptr(request)
+        .to_str()
+        .map_err(|e| Error::Other(format!("Request is not valid UTF-8: {}", e)))?;
+    Ok(serde_json::from_str(json)?)
+}
+
+/// Serialize a result into the response envelope and hand ownership to the caller
+fn respond<T: Serialize>(result: Result<T>) -> *mut c_char {
+    let response = match result {
+        Ok(data) => Response {
+            ok: true,
+            data: Some(data),
+            error: None,
+            code: None,
+        },
+        Err(e) => Response {
+            ok: false,
+            data: None,
+            error: Some(e.to_string()),
+            code: Some(e.code()),
+        },
+    };
+
+    let json = serde_json::to_string(&response).unwrap_or_else(|e| {
+        format!(
+            r#"{{"ok":false,"error":"Failed to serialize response: {}","code":"json"}}"#,
+            e.to_string().replace('"', "'")
+        )
+    });
+
+    CString::new(json)
+        .map(CString::into_raw)
+        .unwrap_or(std::ptr::null_mut())
+}
+
+/// Run an FFI entry point, converting panics into error responses
+fn guarded<T, F>(f: F) -> *mut c_char
+where
+    T: Serialize,
+    F: FnOnce() -> Result<T>,
+{
+    match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(result) => respond(result),
+        Err(_) => respond::<()>(Err(Error::Other("Internal panic in krep".into()))),
+    }
+}
+
+/// Prescribe the next microdose
+///
+/// Request: `{"data_dir": "...", "category": "vo2"}` (both optional).
+///
+/// # Safety
+/// `request` must be null or point to a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn krep_prescribe(request: *const c_char) -> *mut c_char {
+    guarded(|| prescribe(parse_request(request)?))
+}
+
+/// Log a completed session to the WAL
+///
+/// Request: `{"definition_id": "...", "data_dir": "...", "perceived_rpe": 7, ...}`.
+///
+/// # Safety
+/// `request` must be null or point to a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn krep_log_session(request: *const c_char) -> *mut c_char {
+    guarded(|| log_session(parse_request(request)?))
+}
+
+/// Summarize sessions from the last N days
+///
+/// Request: `{"data_dir": "...", "days": 7}` (both optional).
+///
+/// # Safety
+/// `request` must be null or point to a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn krep_stats(request: *const c_char) -> *mut c_char {
+    guarded(|| stats(parse_request(request)?))
+}
+
+/// Release a string returned by any `krep_*` function
+///
+/// # Safety
+/// `ptr` must be null or a pointer previously returned by this library,
+/// and must not be used after this call.
+#[no_mangle]
+pub unsafe extern "C" fn krep_string_free(ptr: *mut c_char) {
+    if !ptr.is_null() {
+        drop(CString::from_raw(ptr));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    fn call(f: unsafe extern "C" fn(*const c_char) -> *mut c_char, request: &str) -> Value {
+        let request = CString::new(request).unwrap();
+        unsafe {
+            let ptr = f(request.as_ptr());
+            let json = CStr::from_ptr(ptr).to_str().unwrap().to_string();
+            krep_string_free(ptr);
+            serde_json::from_str(&json).unwrap()
+        }
+    }
+
+    fn data_dir_request(dir: &std::path::Path, extra: &str) -> String {
+        format!(
+            r#"{{"data_dir": {}{}}}"#,
+            serde_json::to_string(dir).unwrap(),
+            extra
+        )
+    }
+
+    #[test]
+    fn test_prescribe_returns_definition() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let response = call(
+            krep_prescribe,
+            &data_dir_request(temp_dir.path(), r#", "category": "mobility""#),
+        );
+
+        assert_eq!(response["ok"], true);
+        assert_eq!(response["data"]["definition"]["category"], "mobility");
+    }
+
+    #[test]
+    fn test_log_session_then_stats() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let response = call(
+            krep_log_session,
+            &data_dir_request(temp_dir.path(), r#", "definition_id": "emom_burpee_5m""#),
+        );
+        assert_eq!(response["ok"], true);
+        // Logged like the CLI does: the dose's progression starts being tracked
+        let state = cardio_core::UserMicrodoseState::load(
+            &temp_dir.path().join("wal/state.json"),
+            cardio_core::lock::DEFAULT_TIMEOUT,
+        )
+        .unwrap();
+        assert!(state.progressions.contains_key("emom_burpee_5m"));
+
+        let stats = call(krep_stats, &data_dir_request(temp_dir.path(), ""));
+        assert_eq!(stats["ok"], true);
+        assert_eq!(stats["data"]["total_sessions"], 1);
+        assert_eq!(stats["data"]["by_category"]["vo2"], 1);
+    }
+
+    #[test]
+    fn test_invalid_request_returns_error() {
+        let response = call(krep_prescribe, "not json");
+        assert_eq!(response["ok"], false);
+        assert!(response["error"].is_string());
+        assert_eq!(response["code"], "json");
+    }
+
+    #[test]
+    fn test_unknown_definition_returns_error() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let response = call(
+            krep_log_session,
+            &data_dir_request(temp_dir.path(), r#", "definition_id": "nope""#),
+        );
+        assert_eq!(response["ok"], false);
+        assert_eq!(response["code"], "not_found");
+    }
+}
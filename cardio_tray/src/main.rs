@@ -1,42 +1,41 @@
-use libadwaita as adw;
 use adw::prelude::*;
 use adw::Application;
 use cardio_core::{
-    get_default_catalog, increase_intensity, load_external_strength, load_recent_sessions, BandSpec,
-    Config, ExternalStrengthSignal, JsonlSink, MicrodoseCategory, MicrodoseSession, MovementStyle,
-    PrescribedMicrodose, ProgressionState, SessionKind, SessionSink, UserContext,
-    UserMicrodoseState,
+    achievements, circuit,
+    config::{ReferenceMedia, SoundConfig},
+    csv_rollup, get_default_catalog, goal, household, increase_intensity,
+    load_recent_sessions_with, normalize_tags, office, progression_log, reference, soreness,
+    sound::{cue_duration, SoundPlayer},
+    stats, store,
+    timer::{IntervalTimer, RepAdjustments, Stopwatch, TimerCue},
+    timing, travel, BandSpec, Config, ConfigWatcher, DataStore, FileStorage, IntervalScheme,
+    MicrodoseCategory, MicrodoseSession, MovementStyle, PrescribedMicrodose, RealizedMetric,
+    SessionKind, SorenessCheckin, UserMicrodoseState,
 };
 use chrono::{DateTime, Utc};
 use dirs;
+use glib::{self, ControlFlow};
 use gtk::prelude::{BoxExt, ButtonExt, WidgetExt};
 use gtk4 as gtk;
-use glib::{self, ControlFlow};
-use ksni;
+use libadwaita as adw;
 use serde_json;
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::collections::HashSet;
-use std::io::Write;
-use std::path::{Path, PathBuf};
+use std::path::PathBuf;
 use std::rc::Rc;
-use std::sync::mpsc::{channel, Sender};
-use std::time::Duration;
-use tracing::Level;
+use std::time::{Duration, Instant};
 use uuid::Uuid;
 
+mod ring;
+mod tray;
+
+use ring::ProgressRing;
+use tray::TrayEvent;
+
 struct LoadedData {
-    config: Config,
-    data_dir: PathBuf,
-    wal_path: PathBuf,
-    csv_path: PathBuf,
-    state_path: PathBuf,
-    strength_path: PathBuf,
-    // Use reference to cached catalog for performance
-    catalog: &'static cardio_core::Catalog,
-    user_state: UserMicrodoseState,
+    store: DataStore,
     recent_sessions: Vec<SessionKind>,
     warnings: Vec<String>,
-    strength_signal: Option<ExternalStrengthSignal>,
 }
 
 struct UiState {
@@ -44,82 +43,8 @@ struct UiState {
     skipped_ids: HashSet<String>,
     prescription: PrescribedMicrodose,
     ctx_now: DateTime<Utc>,
-}
-
-#[derive(Debug)]
-enum TrayEvent {
-    Activate,
-    WatcherOnline,
-    WatcherOffline,
-}
-
-struct KrepTray {
-    tx: Sender<TrayEvent>,
-}
-
-impl ksni::Tray for KrepTray {
-    fn icon_name(&self) -> String {
-        // Fallback to a well-known icon so the indicator is always visible
-        "applications-system".into()
-    }
-
-    fn icon_pixmap(&self) -> Vec<ksni::Icon> {
-        vec![solid_icon(24, 0xFF2ECC71)]
-    }
-
-    fn id(&self) -> String {
-        "krep-tray".into()
-    }
-
-    fn title(&self) -> String {
-        "Krep".into()
-    }
-
-    fn status(&self) -> ksni::Status {
-        ksni::Status::Active
-    }
-
-    fn tool_tip(&self) -> ksni::ToolTip {
-        ksni::ToolTip {
-            icon_name: self.icon_name(),
-            icon_pixmap: self.icon_pixmap(),
-            title: "Krep".into(),
-            description: "Microdose cardio assistant".into(),
-        }
-    }
-
-    fn menu(&self) -> Vec<ksni::MenuItem<Self>> {
-        vec![ksni::MenuItem::Standard(
-            ksni::menu::StandardItem {
-                label: "Microdose Now".into(),
-                activate: Box::new(|this: &mut Self| {
-                    let _ = this.tx.send(TrayEvent::Activate);
-                }),
-                ..Default::default()
-            },
-        )]
-    }
-
-    fn watcher_online(&self) {
-        let _ = self.tx.send(TrayEvent::WatcherOnline);
-    }
-
-    fn watcher_offine(&self) -> bool {
-        let _ = self.tx.send(TrayEvent::WatcherOffline);
-        true
-    }
-}
-
-fn solid_icon(size: i32, argb: u32) -> ksni::Icon {
-    let mut data = Vec::with_capacity((size * size * 4) as usize);
-    for _ in 0..(size * size) {
-        data.extend_from_slice(&argb.to_be_bytes());
-    }
-    ksni::Icon {
-        width: size,
-        height: size,
-        data,
-    }
+    /// Opened from "Surprise Me": skips re-roll a novelty pick
+    surprise: bool,
 }
 
 fn init_logging() {
@@ -128,27 +53,9 @@ fn init_logging() {
         .join("krep")
         .join("krep_tray.log");
 
-    let _ = std::fs::create_dir_all(
-        log_path
-            .parent()
-            .unwrap_or_else(|| Path::new(".")),
-    );
-
-    let subscriber = tracing_subscriber::fmt()
-        .with_max_level(Level::INFO)
-        .with_writer(move || -> Box<dyn Write + Send> {
-            match std::fs::File::options()
-                .create(true)
-                .append(true)
-                .open(&log_path)
-            {
-                Ok(f) => Box::new(f),
-                Err(_) => Box::new(std::io::stdout()),
-            }
-        })
-        .finish();
-
-    let _ = tracing::subscriber::set_global_default(subscriber);
+    // Rotation limits and format come from [logging]; KREP_LOG_FORMAT overrides
+    let options = Config::load().map(|c| c.logging).unwrap_or_default();
+    cardio_core::logging::init_with_config("info", &options, Some(&log_path));
 }
 
 fn main() {
@@ -169,23 +76,101 @@ fn setup_tray(app: &Application) {
     // Prevent the app from quitting when the window is closed.
     Box::leak(Box::new(app.hold()));
 
-    let (tx, rx) = channel::<TrayEvent>();
+    let mut backend = tray::spawn_backend();
 
-    let _svc = ksni::TrayService::new(KrepTray { tx }).spawn();
+    // Keep the active config in memory and live-reload it on edits
+    let config = Rc::new(RefCell::new(match Config::load() {
+        Ok(config) => config,
+        Err(err) => {
+            tracing::error!("Failed to load config: {}; using defaults", err);
+            Config::default()
+        }
+    }));
+    for problem in config.borrow().validate_with_catalog(get_default_catalog()) {
+        tracing::warn!("Config problem: {}", problem);
+    }
+    let mut config_watcher =
+        match ConfigWatcher::new(&Config::default_config_path(), config.borrow().clone()) {
+            Ok(watcher) => Some(watcher),
+            Err(err) => {
+                tracing::warn!("Config live-reload disabled: {}", err);
+                None
+            }
+        };
 
     let app_weak = app.downgrade();
     let mut watcher_seen = false;
     let mut warned_no_watcher = false;
     eprintln!("[krep-tray] Tray service started. Waiting for watcher/events...");
     let app_clone_for_loop = app_weak.clone();
+    let config_for_loop = config.clone();
+    let mut tooltip_refreshed: Option<Instant> = None;
+    let mut rollup_checked: Option<Instant> = None;
+    let mut auto_rollup: Option<std::thread::JoinHandle<()>> = None;
     glib::timeout_add_local(Duration::from_millis(300), move || {
-        while let Ok(event) = rx.try_recv() {
+        if let Some(watcher) = config_watcher.as_mut() {
+            if watcher.poll() {
+                *config_for_loop.borrow_mut() = watcher.current().clone();
+                tooltip_refreshed = None;
+            }
+        }
+
+        if tooltip_refreshed.is_none_or(|at| at.elapsed() >= TOOLTIP_REFRESH) {
+            tooltip_refreshed = Some(Instant::now());
+            let tooltip = {
+                let config = config_for_loop.borrow();
+                [goal_tooltip(&config), household_tooltip(&config)]
+                    .into_iter()
+                    .flatten()
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            };
+            backend.set_tooltip(if tooltip.is_empty() {
+                tray::DEFAULT_TOOLTIP
+            } else {
+                &tooltip
+            });
+            // Modes may have expired or been switched from the CLI
+            backend.set_travel_mode(travel_mode_on(&config_for_loop.borrow()));
+            backend.set_office_mode(office_mode_on(&config_for_loop.borrow()));
+            backend.set_badge(doses_due(&config_for_loop.borrow()));
+        }
+
+        // Users who never run `krep rollup` still keep a small WAL
+        if rollup_checked.is_none_or(|at| at.elapsed() >= AUTO_ROLLUP_CHECK)
+            && auto_rollup
+                .as_ref()
+                .is_none_or(|running| running.is_finished())
+        {
+            rollup_checked = Some(Instant::now());
+            auto_rollup = start_auto_rollup(&config_for_loop.borrow());
+        }
+
+        for event in backend.poll_events() {
             match event {
                 TrayEvent::Activate => {
                     if let Some(app) = app_clone_for_loop.upgrade() {
-                        show_prescription_window(&app);
+                        show_prescription_window(&app, &config_for_loop.borrow(), false);
+                    }
+                }
+                TrayEvent::Surprise => {
+                    if let Some(app) = app_clone_for_loop.upgrade() {
+                        show_prescription_window(&app, &config_for_loop.borrow(), true);
+                    }
+                }
+                TrayEvent::ShowStats => {
+                    if let Some(app) = app_clone_for_loop.upgrade() {
+                        show_stats_window(&app, &config_for_loop.borrow());
                     }
                 }
+                TrayEvent::ToggleTravelMode => {
+                    let on = toggle_travel_mode(&config_for_loop.borrow());
+                    backend.set_travel_mode(on);
+                }
+                TrayEvent::ToggleOfficeMode => {
+                    let on = toggle_office_mode(&config_for_loop.borrow());
+                    backend.set_office_mode(on);
+                }
                 TrayEvent::WatcherOnline => {
                     watcher_seen = true;
                     warned_no_watcher = false;
@@ -204,7 +189,7 @@ fn setup_tray(app: &Application) {
                 "[krep-tray] No StatusNotifier watcher detected. Ensure the AppIndicator/SNI extension is enabled in GNOME. Falling back to showing the popup window once."
             );
             if let Some(app) = app_clone_for_loop.upgrade() {
-                show_prescription_window(&app);
+                show_prescription_window(&app, &config_for_loop.borrow(), false);
             }
         }
         ControlFlow::Continue
@@ -213,59 +198,178 @@ fn setup_tray(app: &Application) {
     // Immediately show the popup at startup as a fallback
     if let Some(app) = app_weak.upgrade() {
         eprintln!("[krep-tray] Showing popup once as startup fallback.");
-        show_prescription_window(&app);
+        show_prescription_window(&app, &config.borrow(), false);
     }
 }
 
-fn load_data() -> cardio_core::Result<LoadedData> {
-    let config = Config::load()?;
-    let data_dir = config.data.data_dir.clone();
-    let wal_dir = data_dir.join("wal");
-    std::fs::create_dir_all(&wal_dir)?;
+/// How often the tray tooltip's goal progress is recomputed
+const TOOLTIP_REFRESH: Duration = Duration::from_secs(60);
 
-    let state_path = wal_dir.join("state.json");
-    let wal_path = wal_dir.join("microdose_sessions.wal");
-    let csv_path = data_dir.join("sessions.csv");
-    let strength_path = data_dir.join("strength").join("signal.json");
+/// How often the tray checks whether a rollup is due (`[data] auto_rollup`)
+const AUTO_ROLLUP_CHECK: Duration = Duration::from_secs(15 * 60);
 
-    let mut warnings = Vec::new();
+/// Roll the WAL up into the CSV archive on a background thread if it holds
+/// sessions from an earlier day or has grown past `auto_rollup_wal_kb`
+///
+/// The WAL stays locked for the whole rollup, so doses logged meanwhile
+/// (here or from the CLI) wait and then start a new WAL.
+fn start_auto_rollup(config: &Config) -> Option<std::thread::JoinHandle<()>> {
+    if !config.data.auto_rollup {
+        return None;
+    }
+    let storage = FileStorage::with_config(&config.data.data_dir, &config.data);
+    let max_bytes = config.data.auto_rollup_wal_kb * 1024;
+    let tz = config.timezone();
+    Some(std::thread::spawn(
+        move || match csv_rollup::auto_rollup_due(
+            &storage.wal_path,
+            max_bytes,
+            &tz,
+            Utc::now(),
+            storage.lock_timeout,
+        ) {
+            Ok(true) => {
+                match csv_rollup::wal_to_csv_and_archive(
+                    &storage.wal_path,
+                    &storage.csv_path,
+                    storage.lock_timeout,
+                ) {
+                    Ok(count) => {
+                        tracing::info!("Rolled up {} sessions to {:?}", count, storage.csv_path)
+                    }
+                    Err(err) => tracing::error!("Automatic rollup failed: {}", err),
+                }
+            }
+            Ok(false) => {}
+            Err(err) => tracing::warn!("Failed to check whether a rollup is due: {}", err),
+        },
+    ))
+}
 
-    // Use cached catalog for performance (eliminates 50+ allocations)
-    let catalog = get_default_catalog();
+/// Weekly goal progress for the tray tooltip (`None` without a goal)
+fn goal_tooltip(config: &Config) -> Option<String> {
+    let storage = FileStorage::with_config(&config.data.data_dir, &config.data);
+    let state = UserMicrodoseState::load(&storage.state_path, storage.lock_timeout).ok()?;
+    let weekly_goal = state.weekly_goal?;
+    let week = load_recent_sessions_with(
+        &storage.wal_path,
+        &storage.csv_path,
+        7,
+        config.data.exclude_anomalies,
+        storage.lock_timeout,
+    )
+    .ok()?;
+    let progress = goal::current_progress(&weekly_goal, &week, &config.timezone(), Utc::now());
+    Some(format!(
+        "Weekly goal: {}/{} doses{}",
+        progress.done,
+        progress.target,
+        if progress.met() { " ✓" } else { "" }
+    ))
+}
+
+/// Doses still due today for the tray icon badge, as in `krep status`
+/// (0 once the day's plan is done, on off days, or if history can't be read)
+fn doses_due(config: &Config) -> u32 {
+    DataStore::open(&config.data.data_dir, config)
+        .and_then(|store| store.status(store.now()))
+        .map_or(0, |status| status.doses_due)
+}
 
-    // Load state - error handling is built into load() function
-    let user_state = match UserMicrodoseState::load(&state_path) {
-        Ok(state) => state,
-        Err(e) => {
-            warnings.push(format!("State load failed: {}; using defaults.", e));
-            UserMicrodoseState::default()
+/// Today's doses per household member for the tray tooltip, e.g.
+/// "Anna: 4 doses today, you: 1" (`None` without `[household] members`)
+fn household_tooltip(config: &Config) -> Option<String> {
+    let members = household::load(
+        config,
+        &config.data.data_dir,
+        get_default_catalog(),
+        1,
+        Utc::now(),
+    )
+    .map_err(|err| tracing::warn!("Failed to load household doses: {}", err))
+    .ok()?;
+    household::today_line(&members)
+}
+
+/// Whether travel mode is on now
+fn travel_mode_on(config: &Config) -> bool {
+    let storage = FileStorage::with_config(&config.data.data_dir, &config.data);
+    UserMicrodoseState::load(&storage.state_path, storage.lock_timeout)
+        .is_ok_and(|state| travel::is_active(&state, Utc::now()))
+}
+
+/// Flip travel mode from the tray menu, returning whether it is now on
+fn toggle_travel_mode(config: &Config) -> bool {
+    let storage = FileStorage::with_config(&config.data.data_dir, &config.data);
+    let mut until = None;
+    let result = UserMicrodoseState::update(&storage.state_path, storage.lock_timeout, |state| {
+        until = travel::toggle(state, Utc::now());
+        Ok(())
+    });
+    match result {
+        Ok(_) => {
+            tracing::info!("Travel mode {}", if until.is_some() { "on" } else { "off" });
+            until.is_some()
         }
-    };
+        Err(err) => {
+            tracing::error!("Failed to toggle travel mode: {}", err);
+            travel_mode_on(config)
+        }
+    }
+}
 
-    // Load strength signal - error handling is built into load_external_strength()
-    let strength_signal = match load_external_strength(&strength_path) {
-        Ok(sig) => sig,
-        Err(e) => {
-            warnings.push(format!("Strength signal load failed: {}; ignoring.", e));
-            None
+/// Whether office mode was switched on (office hours don't tick the menu item)
+fn office_mode_on(config: &Config) -> bool {
+    let storage = FileStorage::with_config(&config.data.data_dir, &config.data);
+    UserMicrodoseState::load(&storage.state_path, storage.lock_timeout)
+        .is_ok_and(|state| office::active_until(&state, Utc::now()).is_some())
+}
+
+/// Flip office mode from the tray menu (on until the end of the day),
+/// returning whether it is now on
+fn toggle_office_mode(config: &Config) -> bool {
+    let storage = FileStorage::with_config(&config.data.data_dir, &config.data);
+    let mut until = None;
+    let result = UserMicrodoseState::update(&storage.state_path, storage.lock_timeout, |state| {
+        until = office::toggle(state, &config.timezone(), Utc::now());
+        Ok(())
+    });
+    match result {
+        Ok(_) => {
+            tracing::info!("Office mode {}", if until.is_some() { "on" } else { "off" });
+            until.is_some()
         }
-    };
+        Err(err) => {
+            tracing::error!("Failed to toggle office mode: {}", err);
+            office_mode_on(config)
+        }
+    }
+}
+
+fn load_data(config: Config) -> cardio_core::Result<LoadedData> {
+    let mut store = DataStore::open(&config.data.data_dir, &config)?;
+    std::fs::create_dir_all(store.paths.wal_dir())?;
 
-    // Load history
-    let recent_sessions = load_recent_sessions(&wal_path, &csv_path, 7)?;
+    let mut warnings = Vec::new();
+    if let Err(e) = store.record_strength_history() {
+        warnings.push(format!("Strength history write failed: {}", e));
+    }
+    match store.abandon_stale() {
+        Ok(Some(abandoned)) => {
+            tracing::info!(
+                "Recorded unfinished {} as abandoned",
+                abandoned.definition_id
+            )
+        }
+        Ok(None) => {}
+        Err(e) => warnings.push(format!("Abandoned dose check failed: {}", e)),
+    }
+    let recent_sessions = store.recent_sessions(store::PRESCRIPTION_HISTORY_DAYS)?;
 
     Ok(LoadedData {
-        config,
-        data_dir,
-        wal_path,
-        csv_path,
-        state_path,
-        strength_path,
-        catalog,
-        user_state,
+        store,
         recent_sessions,
         warnings,
-        strength_signal,
     })
 }
 
@@ -273,20 +377,44 @@ fn compute_prescription(
     loaded: &LoadedData,
     ctx_now: DateTime<Utc>,
     recent: &[SessionKind],
+    surprise: bool,
 ) -> cardio_core::Result<PrescribedMicrodose> {
-    let mut ctx = UserContext {
-        now: ctx_now,
-        user_state: loaded.user_state.clone(),
-        recent_sessions: recent.to_vec(),
-        external_strength: loaded.strength_signal.clone(),
-        equipment_available: loaded.config.equipment.available.clone(),
-    };
+    if surprise {
+        loaded.store.surprise_prescription(recent, ctx_now)
+    } else {
+        loaded.store.next_prescription(recent, None, ctx_now)
+    }
+}
 
-    cardio_core::prescribe_next(&loaded.catalog, &mut ctx, None)
+/// Progress toward `[prescription] weekly_targets` over the trailing 7 days
+fn weekly_target_progress(
+    loaded: &LoadedData,
+    sessions: &[SessionKind],
+) -> Vec<stats::TargetProgress> {
+    stats::target_progress(
+        sessions,
+        loaded.store.catalog,
+        &loaded.store.config.weekly_targets(),
+        Utc::now() - chrono::Duration::days(7),
+    )
+}
+
+/// How long the popup stays open after Do It, so the ring can be seen to move
+const RING_LINGER: Duration = Duration::from_millis(1200);
+
+fn show_prescription_window(app: &Application, config: &Config, surprise: bool) {
+    open_prescription_window(app, config, surprise, true);
 }
 
-fn show_prescription_window(app: &Application) {
-    let loaded = match load_data() {
+/// The prescription popup; `may_remind_again` is false for a re-reminder,
+/// so an ignored popup comes back at most once
+fn open_prescription_window(
+    app: &Application,
+    config: &Config,
+    surprise: bool,
+    may_remind_again: bool,
+) {
+    let loaded = match load_data(config.clone()) {
         Ok(data) => data,
         Err(err) => {
             tracing::error!("Failed to load data: {}", err);
@@ -294,22 +422,51 @@ fn show_prescription_window(app: &Application) {
         }
     };
 
-    let ctx_now = Utc::now();
-    let prescription = match compute_prescription(&loaded, ctx_now, &loaded.recent_sessions) {
-        Ok(p) => p,
-        Err(err) => {
-            tracing::error!("Failed to prescribe: {}", err);
-            return;
-        }
-    };
+    let ctx_now = loaded.store.now();
+    let prescription =
+        match compute_prescription(&loaded, ctx_now, &loaded.recent_sessions, surprise) {
+            Ok(p) => p,
+            Err(err) => {
+                tracing::error!("Failed to prescribe: {}", err);
+                return;
+            }
+        };
 
+    // The check-in only fits in the full window
+    let compact = compact_popup(&loaded, &prescription, ctx_now);
     let ui_state = Rc::new(RefCell::new(UiState {
         loaded,
         skipped_ids: HashSet::new(),
         prescription,
         ctx_now,
+        surprise,
     }));
 
+    let window = if compact {
+        present_compact_window(app, ui_state.clone())
+    } else {
+        present_full_window(app, ui_state.clone())
+    };
+    close_when_ignored(app, &window, ui_state, may_remind_again);
+}
+
+/// Whether `[tray] popup` picks the compact popup for `prescription`
+fn compact_popup(
+    loaded: &LoadedData,
+    prescription: &PrescribedMicrodose,
+    now: DateTime<Utc>,
+) -> bool {
+    let tz = loaded.store.config.timezone();
+    loaded
+        .store
+        .config
+        .tray
+        .popup
+        .compact_for(&prescription.definition)
+        && !soreness::needs_checkin(&loaded.store.state.soreness, &tz, now)
+}
+
+fn present_full_window(app: &Application, state: Rc<RefCell<UiState>>) -> adw::ApplicationWindow {
     let window = adw::ApplicationWindow::builder()
         .application(app)
         .default_width(320)
@@ -324,7 +481,323 @@ fn show_prescription_window(app: &Application) {
     content.set_margin_end(12);
     window.set_content(Some(&content));
 
-    build_prescription_ui(&content, ui_state.clone(), &window);
+    build_prescription_ui(&content, state, &window);
+
+    window.present();
+    window
+}
+
+/// Width of the compact popup
+const COMPACT_WIDTH: i32 = 240;
+
+/// The compact popup: name, reps, Done and Skip, with Details… opening the
+/// full window for the same prescription. GTK 4 leaves window placement to
+/// the desktop, so it opens where new windows do rather than at the icon
+fn present_compact_window(
+    app: &Application,
+    state: Rc<RefCell<UiState>>,
+) -> adw::ApplicationWindow {
+    let window = adw::ApplicationWindow::builder()
+        .application(app)
+        .default_width(COMPACT_WIDTH)
+        .resizable(false)
+        .title("Krep")
+        .build();
+
+    let content = gtk::Box::new(gtk::Orientation::Vertical, 8);
+    content.set_margin_top(10);
+    content.set_margin_bottom(10);
+    content.set_margin_start(10);
+    content.set_margin_end(10);
+    window.set_content(Some(&content));
+
+    build_compact_ui(&content, state, &window);
+
+    window.present();
+    window
+}
+
+fn build_compact_ui(
+    container: &gtk::Box,
+    state: Rc<RefCell<UiState>>,
+    window: &adw::ApplicationWindow,
+) {
+    while let Some(child) = container.first_child() {
+        container.remove(&child);
+    }
+
+    let state_ref = state.borrow();
+    let prescription = &state_ref.prescription;
+
+    let title = gtk::Label::new(Some(&prescription.definition.name));
+    title.set_wrap(true);
+    title.add_css_class("title-4");
+    container.append(&title);
+
+    let target = match prescription.reps_label() {
+        Some(reps) => format!("Reps: {}", reps),
+        None => format!(
+            "~{} sec",
+            prescription.definition.suggested_duration_seconds
+        ),
+    };
+    let target_label = gtk::Label::new(Some(&target));
+    target_label.set_wrap(true);
+    container.append(&target_label);
+
+    let buttons = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+    buttons.set_homogeneous(true);
+    let done = gtk::Button::with_mnemonic("_Done");
+    done.add_css_class("suggested-action");
+    done.update_property(&[gtk::accessible::Property::Description(
+        "Log the dose as prescribed",
+    )]);
+    let skip = gtk::Button::with_mnemonic("_Skip");
+    skip.update_property(&[gtk::accessible::Property::Description(
+        "Skip and show another dose",
+    )]);
+    let details = gtk::Button::with_mnemonic("De_tails…");
+    details.update_property(&[gtk::accessible::Property::Description(
+        "Open the full window for this dose",
+    )]);
+    buttons.append(&done);
+    buttons.append(&skip);
+    buttons.append(&details);
+    container.append(&buttons);
+
+    window.set_default_widget(Some(&done));
+    window.set_focus(Some(&done));
+
+    {
+        let state = state.clone();
+        let window = window.clone();
+        done.connect_clicked(move |_| {
+            let mut state = state.borrow_mut();
+            match log_session(
+                &mut state,
+                Vec::new(),
+                None,
+                None,
+                None,
+                &RepAdjustments::default(),
+            ) {
+                Ok(unlocked) => {
+                    if let Some(app) = window.application() {
+                        announce_achievements(&app, &unlocked);
+                    }
+                }
+                Err(err) => tracing::error!("Failed to log session: {}", err),
+            }
+            window.close();
+        });
+    }
+
+    {
+        let state = state.clone();
+        let container = container.clone();
+        let window = window.clone();
+        skip.connect_clicked(move |_| {
+            if let Err(err) = handle_skip(&state, None) {
+                tracing::error!("Failed to skip: {}", err);
+                window.close();
+                return;
+            }
+            // The next dose may need the full window (a timer, a circuit)
+            let compact = {
+                let state = state.borrow();
+                compact_popup(&state.loaded, &state.prescription, state.ctx_now)
+            };
+            if compact {
+                build_compact_ui(&container, state.clone(), &window);
+            } else {
+                show_details(&window, state.clone());
+            }
+        });
+    }
+
+    {
+        let state = state.clone();
+        let window = window.clone();
+        details.connect_clicked(move |_| show_details(&window, state.clone()));
+    }
+}
+
+/// Swap the compact popup for the full window on the same prescription
+fn show_details(compact: &adw::ApplicationWindow, state: Rc<RefCell<UiState>>) {
+    if let Some(app) = compact.application().and_downcast::<Application>() {
+        present_full_window(&app, state);
+    }
+    compact.close();
+}
+
+/// Close the popup if it's left untouched for `[tray] auto_close_minutes`,
+/// logging a soft skip and re-reminding as `[tray]` says
+fn close_when_ignored(
+    app: &Application,
+    window: &adw::ApplicationWindow,
+    state: Rc<RefCell<UiState>>,
+    may_remind_again: bool,
+) {
+    let config = state.borrow().loaded.store.config.clone();
+    let Some(minutes) = config.tray.auto_close_minutes else {
+        return;
+    };
+
+    // Any click or key press in the popup means it wasn't ignored
+    let touched = Rc::new(Cell::new(false));
+    let click = gtk::GestureClick::new();
+    click.set_propagation_phase(gtk::PropagationPhase::Capture);
+    {
+        let touched = touched.clone();
+        click.connect_pressed(move |_, _, _, _| touched.set(true));
+    }
+    window.add_controller(click);
+    let keys = gtk::EventControllerKey::new();
+    keys.set_propagation_phase(gtk::PropagationPhase::Capture);
+    {
+        let touched = touched.clone();
+        keys.connect_key_pressed(move |_, _, _, _| {
+            touched.set(true);
+            glib::Propagation::Proceed
+        });
+    }
+    window.add_controller(keys);
+
+    let app = app.downgrade();
+    let window = window.downgrade();
+    glib::timeout_add_local_once(Duration::from_secs(u64::from(minutes) * 60), move || {
+        let Some(window) = window.upgrade().filter(|w| w.is_visible()) else {
+            return;
+        };
+        if touched.get() {
+            return;
+        }
+        if config.tray.soft_skip_on_close {
+            if let Err(err) = log_soft_skip(&state) {
+                tracing::error!("Failed to log soft skip: {}", err);
+            }
+        }
+        window.close();
+
+        let Some(delay) = config
+            .tray
+            .remind_again_minutes
+            .filter(|_| may_remind_again)
+        else {
+            return;
+        };
+        glib::timeout_add_local_once(Duration::from_secs(u64::from(delay) * 60), move || {
+            // Not if a dose was logged meanwhile or the day's plan is done
+            if doses_due(&config) == 0 {
+                return;
+            }
+            if let Some(app) = app.upgrade() {
+                open_prescription_window(&app, &config, false, false);
+            }
+        });
+    });
+}
+
+/// Days of sessions charted in the stats window
+const STATS_WINDOW_DAYS: i64 = 30;
+
+/// Progression sparklines per definition over the last [`STATS_WINDOW_DAYS`]
+fn show_stats_window(app: &Application, config: &Config) {
+    let loaded = match load_data(config.clone()) {
+        Ok(data) => data,
+        Err(err) => {
+            tracing::error!("Failed to load data: {}", err);
+            return;
+        }
+    };
+    let events = match progression_log::read_events(
+        &loaded
+            .store
+            .data_dir
+            .join(progression_log::PROGRESSION_LOG_FILE),
+        loaded.store.paths.lock_timeout,
+    ) {
+        Ok(events) => events,
+        Err(err) => {
+            tracing::error!("Failed to read progression log: {}", err);
+            return;
+        }
+    };
+    let sessions = loaded
+        .store
+        .recent_sessions(STATS_WINDOW_DAYS)
+        .unwrap_or_else(|err| {
+            tracing::warn!("Failed to load sessions for stats: {}", err);
+            Vec::new()
+        });
+    let since = Utc::now() - chrono::Duration::days(STATS_WINDOW_DAYS);
+
+    let window = adw::ApplicationWindow::builder()
+        .application(app)
+        .default_width(360)
+        .default_height(320)
+        .title("Krep Progress")
+        .build();
+
+    let content = gtk::Box::new(gtk::Orientation::Vertical, 8);
+    content.set_margin_top(12);
+    content.set_margin_bottom(12);
+    content.set_margin_start(12);
+    content.set_margin_end(12);
+    window.set_content(Some(&content));
+
+    let targets = weekly_target_progress(&loaded, &sessions);
+    if !targets.is_empty() {
+        let ring = ProgressRing::new(targets);
+        content.append(&ring.area);
+    }
+
+    let heading = gtk::Label::new(Some(&format!("Last {} days", STATS_WINDOW_DAYS)));
+    heading.add_css_class("title-3");
+    content.append(&heading);
+
+    let mut def_ids: Vec<&str> = events.iter().map(|e| e.definition_id.as_str()).collect();
+    def_ids.sort();
+    def_ids.dedup();
+    let mut rows = 0;
+    for def_id in def_ids {
+        let series: Vec<_> = stats::progression_series(def_id, &events, &sessions)
+            .into_iter()
+            .filter(|p| p.at >= since)
+            .collect();
+        let (Some(first), Some(last)) = (series.first(), series.last()) else {
+            continue;
+        };
+        let reps: Vec<f64> = series.iter().map(|p| f64::from(p.reps)).collect();
+        let name = loaded
+            .store
+            .catalog
+            .definition(def_id)
+            .map_or(def_id, |d| d.name.as_str());
+
+        let name_label = gtk::Label::new(Some(name));
+        name_label.set_xalign(0.0);
+        content.append(&name_label);
+        let trend_label = gtk::Label::new(Some(&format!(
+            "{}  {} → {} reps (level {})",
+            stats::sparkline(&reps),
+            first.reps,
+            last.reps,
+            last.level
+        )));
+        trend_label.set_xalign(0.0);
+        trend_label.set_margin_bottom(4);
+        // Screen readers can't make sense of the sparkline blocks
+        trend_label.update_property(&[gtk::accessible::Property::Label(&format!(
+            "{}: {} to {} reps, level {}",
+            name, first.reps, last.reps, last.level
+        ))]);
+        content.append(&trend_label);
+        rows += 1;
+    }
+    if rows == 0 {
+        content.append(&gtk::Label::new(Some("No progression logged yet.")));
+    }
 
     window.present();
 }
@@ -345,13 +818,76 @@ fn build_prescription_ui(
         let warning = gtk::Label::new(Some("⚠ Some data could not be loaded. Defaults used."));
         warning.set_wrap(true);
         warning.add_css_class("warning");
+        warning.update_property(&[gtk::accessible::Property::Label(
+            "Warning: some data could not be loaded. Defaults used.",
+        )]);
         container.append(&warning);
     }
 
+    let tz = state_ref.loaded.store.config.timezone();
+    // Focus starts on the check-in when one is due, else on Do It
+    let mut first_focus: Option<gtk::Widget> = None;
+    if soreness::needs_checkin(
+        &state_ref.loaded.store.state.soreness,
+        &tz,
+        state_ref.ctx_now,
+    ) {
+        let checkin_row = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+        let sore_entry = gtk::Entry::new();
+        sore_entry.set_placeholder_text(Some("Any soreness? (e.g. hip:3) blank = none"));
+        sore_entry.set_hexpand(true);
+        sore_entry.update_property(&[
+            gtk::accessible::Property::Label("Soreness check-in"),
+            gtk::accessible::Property::Description(
+                "Sore areas with severity 1 to 5, e.g. hip:3, shoulder:2. Leave blank for none.",
+            ),
+        ]);
+        let check_in = gtk::Button::with_mnemonic("Check _In");
+        check_in.update_property(&[gtk::accessible::Property::Description(
+            "Save today's soreness check-in",
+        )]);
+        // Enter in the entry checks in
+        {
+            let check_in = check_in.clone();
+            sore_entry.connect_activate(move |_| check_in.emit_clicked());
+        }
+        first_focus = Some(sore_entry.clone().upcast());
+        checkin_row.append(&sore_entry);
+        checkin_row.append(&check_in);
+        container.append(&checkin_row);
+
+        let state = state.clone();
+        let container = container.clone();
+        let window = window.clone();
+        check_in.connect_clicked(move |_| {
+            if let Err(err) = handle_checkin(&state, sore_entry.text().as_str()) {
+                tracing::warn!("Soreness check-in rejected: {}", err);
+                // Not only the red outline: high-contrast themes and screen readers miss it
+                sore_entry.add_css_class("error");
+                sore_entry.set_tooltip_text(Some("Not understood. Use area:severity, e.g. hip:3"));
+                sore_entry.update_state(&[gtk::accessible::State::Invalid(
+                    gtk::AccessibleInvalidState::True,
+                )]);
+                return;
+            }
+            build_prescription_ui(&container, state.clone(), &window);
+        });
+    }
+
+    // Header: weekly-target ring beside the dose name
+    let header = gtk::Box::new(gtk::Orientation::Horizontal, 12);
+    let targets = weekly_target_progress(&state_ref.loaded, &state_ref.loaded.recent_sessions);
+    let ring = (!targets.is_empty()).then(|| ProgressRing::new(targets));
+    if let Some(ring) = &ring {
+        header.append(&ring.area);
+    }
     let title = gtk::Label::new(Some(&prescription.definition.name));
+    title.set_wrap(true);
+    title.set_hexpand(true);
     title.set_margin_bottom(6);
     title.add_css_class("title-2");
-    container.append(&title);
+    header.append(&title);
+    container.append(&header);
 
     let duration = gtk::Label::new(Some(&format!(
         "Duration: ~{} sec",
@@ -360,7 +896,156 @@ fn build_prescription_ui(
     duration.set_margin_bottom(6);
     container.append(&duration);
 
-    if let Some(reps) = prescription.reps {
+    if let Some(warmup) = prescription.warmup_label(state_ref.loaded.store.catalog) {
+        let warmup_label = gtk::Label::new(Some(&format!("Warmup first: {}", warmup)));
+        warmup_label.set_wrap(true);
+        warmup_label.set_margin_bottom(4);
+        container.append(&warmup_label);
+    }
+
+    if let Some(target) = prescription.hr_target {
+        let hr_label = gtk::Label::new(Some(&format!("Target HR: {}", target.label())));
+        hr_label.set_margin_bottom(4);
+        container.append(&hr_label);
+    }
+
+    for line in &prescription.rationale {
+        let rationale_label = gtk::Label::new(Some(line));
+        rationale_label.set_wrap(true);
+        rationale_label.set_margin_bottom(4);
+        container.append(&rationale_label);
+    }
+
+    if prescription.ready_to_progress {
+        let ready_label = gtk::Label::new(Some("Recent sessions felt easy - ready to progress"));
+        ready_label.set_wrap(true);
+        ready_label.set_margin_bottom(4);
+        container.append(&ready_label);
+    }
+
+    for interval in prescription
+        .definition
+        .blocks
+        .iter()
+        .filter_map(|b| b.interval)
+    {
+        let interval_label = gtk::Label::new(Some(&format!("Intervals: {}", interval.label())));
+        interval_label.set_margin_bottom(4);
+        container.append(&interval_label);
+    }
+
+    let adjustments: Rc<RefCell<RepAdjustments>> = Rc::new(RefCell::new(RepAdjustments::default()));
+    if let Some(interval) = prescription.definition.interval() {
+        let timer_row = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+        let start_timer = gtk::Button::with_mnemonic("Start _Timer");
+        let timer_label = gtk::Label::new(None);
+        timer_label.add_css_class("title-3");
+        start_timer.update_property(&[gtk::accessible::Property::Description(
+            "Count down the work and rest phases",
+        )]);
+        start_timer.update_relation(&[gtk::accessible::Relation::DescribedBy(&[
+            timer_label.upcast_ref()
+        ])]);
+        let pause_timer = gtk::Button::with_mnemonic("_Pause");
+        pause_timer.set_sensitive(false);
+        pause_timer.update_property(&[gtk::accessible::Property::Description(
+            "Stop the countdown until resumed; paused time is not counted",
+        )]);
+        timer_row.append(&start_timer);
+        timer_row.append(&pause_timer);
+        timer_row.append(&timer_label);
+        container.append(&timer_row);
+
+        let stopwatch: Rc<Cell<Option<Stopwatch>>> = Rc::new(Cell::new(None));
+
+        // Flat rep targets can be scaled down mid-timer
+        let adjustable =
+            prescription.definition.ladder().is_none() && !prescription.definition.is_amrap();
+        let adjust_button = gtk::Button::with_mnemonic("_Adjust");
+        adjust_button.set_sensitive(false);
+        if let (Some(target), true) = (prescription.reps, adjustable) {
+            let adjust_row = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+            let adjust_entry = gtk::Entry::new();
+            adjust_entry.set_placeholder_text(Some(&target.to_string()));
+            adjust_entry.set_input_purpose(gtk::InputPurpose::Digits);
+            adjust_entry.set_width_chars(5);
+            adjust_entry
+                .update_property(&[gtk::accessible::Property::Label("Reps from this round on")]);
+            adjust_button.update_property(&[gtk::accessible::Property::Description(
+                "Record the reps done from the current round on",
+            )]);
+            let adjust_label = gtk::Label::new(None);
+            adjust_row.append(&adjust_entry);
+            adjust_row.append(&adjust_button);
+            adjust_row.append(&adjust_label);
+            container.append(&adjust_row);
+
+            let adjustments = adjustments.clone();
+            let stopwatch = stopwatch.clone();
+            let timer = IntervalTimer::new(&interval);
+            adjust_button.connect_clicked(move |_| {
+                let (Some(running), Ok(reps)) =
+                    (stopwatch.get(), adjust_entry.text().trim().parse::<i32>())
+                else {
+                    return;
+                };
+                if reps < 0 {
+                    return;
+                }
+                let round = timer.round_at(running.elapsed(Instant::now()).as_secs() as u32);
+                adjustments.borrow_mut().adjust(round, reps);
+                adjust_label.set_text(&format!("{} reps from round {}", reps, round));
+            });
+        }
+
+        let sounds = state_ref.loaded.store.config.sound.clone();
+        {
+            let state = state.clone();
+            let stopwatch = stopwatch.clone();
+            let pause_timer = pause_timer.clone();
+            let adjust_button = adjust_button.clone();
+            start_timer.connect_clicked(move |button| {
+                button.set_sensitive(false);
+                // Closed without Do It, the dose times out as abandoned
+                let mut state = state.borrow_mut();
+                let definition_id = state.prescription.definition.id.clone();
+                if let Err(err) = state.loaded.store.start(&definition_id) {
+                    tracing::error!("Failed to record the dose start: {}", err);
+                }
+                stopwatch.set(Some(Stopwatch::new(Instant::now())));
+                pause_timer.set_sensitive(true);
+                adjust_button.set_sensitive(adjustable);
+                start_interval_timer(
+                    &interval,
+                    &timer_label,
+                    &pause_timer,
+                    stopwatch.clone(),
+                    &sounds,
+                );
+            });
+        }
+        let state = state.clone();
+        pause_timer.connect_clicked(move |button| {
+            let Some(mut running) = stopwatch.get() else {
+                return;
+            };
+            let paused = running.toggle(Instant::now());
+            stopwatch.set(Some(running));
+            button.set_label(if paused { "_Resume" } else { "_Pause" });
+            let mut state = state.borrow_mut();
+            let store = &mut state.loaded.store;
+            let recorded = if paused {
+                store.pause()
+            } else {
+                store.resume()
+            };
+            if let Err(err) = recorded {
+                tracing::error!("Failed to record the pause: {}", err);
+            }
+        });
+    }
+
+    if let Some(reps) = prescription.reps_label() {
         let reps_label = gtk::Label::new(Some(&format!("Reps: {}", reps)));
         reps_label.set_margin_bottom(4);
         container.append(&reps_label);
@@ -369,46 +1054,263 @@ fn build_prescription_ui(
     if let Some(style) = &prescription.style {
         let style_text = format!("Style: {}", format_style(style));
         let style_label = gtk::Label::new(Some(&style_text));
+        style_label.set_wrap(true);
         style_label.set_margin_bottom(4);
         container.append(&style_label);
     }
 
-    if let Some(url) = &prescription.definition.reference_url {
+    if let Some(url) = prescription.reference_url(state_ref.loaded.store.catalog) {
+        let name = &prescription.definition.name;
+        let media = match state_ref.loaded.store.config.tray.reference_media {
+            ReferenceMedia::Link => None,
+            ReferenceMedia::Thumbnail => reference_thumbnail(url, name),
+            ReferenceMedia::Player => {
+                reference_player(url).or_else(|| reference_thumbnail(url, name))
+            }
+        };
+        if let Some(media) = media {
+            container.append(&media);
+        }
         let link = gtk::LinkButton::with_label(url, "Learn");
+        link.update_property(&[gtk::accessible::Property::Label(&format!(
+            "Learn {} (opens the reference video)",
+            prescription.definition.name
+        ))]);
         container.append(&link);
     }
 
+    // Circuits: a row per block, logged once every block is done or skipped
+    let checklist = circuit::is_circuit(&prescription.definition).then(|| {
+        Rc::new(RefCell::new(circuit::Checklist::new(
+            &prescription.definition,
+        )))
+    });
+    let mut block_rows = Vec::new();
+    if checklist.is_some() {
+        let hint = gtk::Label::new(Some("Tick off each block (or skip it) to log the circuit"));
+        hint.set_wrap(true);
+        hint.set_margin_bottom(4);
+        container.append(&hint);
+        for (index, block) in prescription.definition.blocks.iter().enumerate() {
+            let row = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+            let done = gtk::CheckButton::with_label(&format!(
+                "{}. {}",
+                index + 1,
+                circuit::block_label(block, state_ref.loaded.store.catalog)
+            ));
+            done.set_hexpand(true);
+            done.update_property(&[gtk::accessible::Property::Description(
+                "Mark this block done",
+            )]);
+            let reps_entry = gtk::Entry::new();
+            reps_entry.set_placeholder_text(Some("Reps"));
+            reps_entry.set_input_purpose(gtk::InputPurpose::Digits);
+            reps_entry.set_width_chars(5);
+            reps_entry.update_property(&[gtk::accessible::Property::Label(&format!(
+                "Reps done in block {}",
+                index + 1
+            ))]);
+            let skip_block = gtk::ToggleButton::with_label("Skip");
+            skip_block
+                .update_property(&[gtk::accessible::Property::Description("Skip this block")]);
+            row.append(&done);
+            row.append(&reps_entry);
+            row.append(&skip_block);
+            container.append(&row);
+            block_rows.push((index, done, reps_entry, skip_block));
+        }
+    }
+
+    // AMRAPs ask for the rep total on completion
+    let amrap_entry = gtk::Entry::new();
+    amrap_entry.set_placeholder_text(Some("Total reps (AMRAP)"));
+    amrap_entry.set_input_purpose(gtk::InputPurpose::Digits);
+    amrap_entry.set_activates_default(true);
+    amrap_entry.update_property(&[gtk::accessible::Property::Label("Total reps for the AMRAP")]);
+    amrap_entry.set_visible(prescription.definition.is_amrap());
+    container.append(&amrap_entry);
+
+    // Optional recovery check: HR drop in the minute after a VO2 dose
+    let recovery_entry = gtk::Entry::new();
+    recovery_entry.set_placeholder_text(Some("HR drop after 60s (bpm)"));
+    recovery_entry.set_input_purpose(gtk::InputPurpose::Digits);
+    recovery_entry.set_activates_default(true);
+    recovery_entry.update_property(&[
+        gtk::accessible::Property::Label("Heart-rate drop"),
+        gtk::accessible::Property::Description(
+            "Beats per minute your heart rate fell in the 60 seconds after finishing",
+        ),
+    ]);
+    recovery_entry.set_visible(
+        state_ref.loaded.store.config.heart_rate.recovery_check
+            && prescription.definition.category == MicrodoseCategory::Vo2,
+    );
+    container.append(&recovery_entry);
+
+    let tags_entry = gtk::Entry::new();
+    tags_entry.set_placeholder_text(Some("Tags (e.g. travel, hotel-gym)"));
+    tags_entry.set_activates_default(true);
+    tags_entry.update_property(&[
+        gtk::accessible::Property::Label("Tags"),
+        gtk::accessible::Property::Description("Optional, comma-separated, e.g. travel, hotel-gym"),
+    ]);
+    container.append(&tags_entry);
+
+    // Two rows so the buttons still fit with large text
     let button_row = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+    let secondary_row = gtk::Box::new(gtk::Orientation::Horizontal, 6);
     container.append(&button_row);
-
-    let do_it = gtk::Button::with_label("Do It");
-    let skip = gtk::Button::with_label("Skip");
-    let harder = gtk::Button::with_label("Harder Next Time");
-    let cancel = gtk::Button::with_label("Cancel");
+    container.append(&secondary_row);
+
+    let do_it = gtk::Button::with_mnemonic("_Do It");
+    let skip = gtk::Button::with_mnemonic("_Skip");
+    // Ready to progress: the same upgrade, offered as a one-tap prompt
+    let harder = gtk::Button::with_mnemonic(if prescription.ready_to_progress {
+        "Progress Now? Go _Harder"
+    } else {
+        "_Harder Next Time"
+    });
+    let cancel = gtk::Button::with_mnemonic("_Cancel");
+    do_it.add_css_class("suggested-action");
+    do_it.update_property(&[gtk::accessible::Property::Description(
+        "Log this microdose as done and close",
+    )]);
+    skip.update_property(&[gtk::accessible::Property::Description(
+        "Suggest a different microdose",
+    )]);
+    harder.update_property(&[gtk::accessible::Property::Description(
+        "Raise the intensity of this microdose and close without logging",
+    )]);
+    cancel.update_property(&[gtk::accessible::Property::Description(
+        "Close without logging",
+    )]);
 
     button_row.append(&do_it);
     button_row.append(&skip);
-    button_row.append(&harder);
-    button_row.append(&cancel);
+    secondary_row.append(&harder);
+    secondary_row.append(&cancel);
+
+    // Skip asks why (optional), for reminder timing and `krep stats --timing`
+    let reason_row = gtk::FlowBox::new();
+    reason_row.set_selection_mode(gtk::SelectionMode::None);
+    reason_row.set_visible(false);
+    let reason_label = gtk::Label::new(Some("Why skip?"));
+    reason_row.insert(&reason_label, -1);
+    let mut reason_buttons = Vec::new();
+    for reason in timing::SkipReason::ALL {
+        let button = gtk::Button::with_label(&capitalize(reason.label()));
+        button.update_relation(&[gtk::accessible::Relation::DescribedBy(&[
+            reason_label.upcast_ref()
+        ])]);
+        reason_row.insert(&button, -1);
+        reason_buttons.push((Some(reason), button));
+    }
+    let just_skip = gtk::Button::with_label("Just Skip");
+    just_skip.update_property(&[gtk::accessible::Property::Description(
+        "Skip without giving a reason",
+    )]);
+    reason_row.insert(&just_skip, -1);
+    reason_buttons.push((None, just_skip));
+    container.append(&reason_row);
+
+    if let Some(checklist) = &checklist {
+        do_it.set_sensitive(false);
+        for (index, done, reps_entry, skip_block) in block_rows {
+            {
+                let checklist = checklist.clone();
+                let do_it = do_it.clone();
+                let skip_block = skip_block.clone();
+                done.connect_toggled(move |done| {
+                    if done.is_active() {
+                        skip_block.set_active(false);
+                    }
+                    update_block(&checklist, index, done, &skip_block, &do_it);
+                });
+            }
+            {
+                let checklist = checklist.clone();
+                let do_it = do_it.clone();
+                let done = done.clone();
+                skip_block.connect_toggled(move |skip_block| {
+                    if skip_block.is_active() {
+                        done.set_active(false);
+                    }
+                    update_block(&checklist, index, &done, skip_block, &do_it);
+                });
+            }
+            let checklist = checklist.clone();
+            reps_entry.connect_changed(move |entry| {
+                checklist
+                    .borrow_mut()
+                    .set_reps(index, entry.text().trim().parse().ok());
+            });
+        }
+    }
+
+    // Enter in an entry logs the dose
+    window.set_default_widget(Some(&do_it));
+    window.set_focus(Some(&first_focus.unwrap_or_else(|| do_it.clone().upcast())));
 
     {
         let state = state.clone();
         let window = window.clone();
-        do_it.connect_clicked(move |_| {
+        let tags_entry = tags_entry.clone();
+        let amrap_entry = amrap_entry.clone();
+        let recovery_entry = recovery_entry.clone();
+        let checklist = checklist.clone();
+        let adjustments = adjustments.clone();
+        let ring = ring.clone();
+        do_it.connect_clicked(move |do_it| {
             let mut state = state.borrow_mut();
-            if let Err(err) = log_session(&mut state) {
-                tracing::error!("Failed to log session: {}", err);
+            let tags = normalize_tags([tags_entry.text().as_str()]);
+            let amrap_total = amrap_entry.text().trim().parse::<i32>().ok();
+            let hr_recovery = recovery_entry.text().trim().parse::<u8>().ok();
+            let blocks = checklist.as_ref().map(|c| c.borrow().clone());
+            let adjustments = adjustments.borrow().clone();
+            match log_session(
+                &mut state,
+                tags,
+                amrap_total,
+                hr_recovery,
+                blocks.as_ref(),
+                &adjustments,
+            ) {
+                Ok(unlocked) => {
+                    if let Some(app) = window.application() {
+                        announce_achievements(&app, &unlocked);
+                    }
+                }
+                Err(err) => tracing::error!("Failed to log session: {}", err),
+            }
+            match &ring {
+                Some(ring) => {
+                    ring.update(weekly_target_progress(
+                        &state.loaded,
+                        &state.loaded.recent_sessions,
+                    ));
+                    do_it.set_sensitive(false);
+                    let window = window.clone();
+                    glib::timeout_add_local_once(RING_LINGER, move || window.close());
+                }
+                None => window.close(),
             }
-            window.close();
         });
     }
 
     {
+        let first_reason = reason_buttons[0].1.clone();
+        skip.connect_clicked(move |_| {
+            reason_row.set_visible(true);
+            first_reason.grab_focus();
+        });
+    }
+
+    for (reason, button) in reason_buttons {
         let state = state.clone();
         let container = container.clone();
         let window = window.clone();
-        skip.connect_clicked(move |_| {
-            if let Err(err) = handle_skip(&state) {
+        button.connect_clicked(move |_| {
+            if let Err(err) = handle_skip(&state, reason) {
                 tracing::error!("Failed to skip: {}", err);
                 window.close();
                 return;
@@ -437,8 +1339,191 @@ fn build_prescription_ui(
     }
 }
 
-fn log_session(state: &mut UiState) -> cardio_core::Result<()> {
+/// Record a circuit block's row and enable Do It once every block is resolved
+fn update_block(
+    checklist: &RefCell<circuit::Checklist>,
+    index: usize,
+    done: &gtk::CheckButton,
+    skipped: &gtk::ToggleButton,
+    do_it: &gtk::Button,
+) {
+    let status = if done.is_active() {
+        circuit::BlockStatus::Done
+    } else if skipped.is_active() {
+        circuit::BlockStatus::Skipped
+    } else {
+        circuit::BlockStatus::Pending
+    };
+    let mut checklist = checklist.borrow_mut();
+    checklist.set_status(index, status);
+    do_it.set_sensitive(checklist.complete());
+}
+
+/// Height of the reference thumbnail or player
+const REFERENCE_MEDIA_HEIGHT: i32 = 180;
+
+/// Clickable thumbnail for a YouTube reference link
+///
+/// Cached under the user cache directory; the first showing fetches it in
+/// the background through GIO (needs gvfs for https).
+fn reference_thumbnail(url: &str, name: &str) -> Option<gtk::Widget> {
+    let thumbnail_url = reference::thumbnail_url(url)?;
+    let path = reference::thumbnail_cache_path(&dirs::cache_dir()?.join("krep"), url)?;
+
+    let picture = gtk::Picture::new();
+    picture.set_can_shrink(true);
+    picture.set_size_request(-1, REFERENCE_MEDIA_HEIGHT);
+    picture.set_alternative_text(Some(&format!("Reference video for {}", name)));
+    if path.exists() {
+        picture.set_filename(Some(&path));
+    } else {
+        let weak = picture.downgrade();
+        glib::spawn_future_local(async move {
+            match gio::File::for_uri(&thumbnail_url)
+                .load_contents_future()
+                .await
+            {
+                Ok((bytes, _)) => {
+                    if let Err(err) = save_thumbnail(&path, &bytes) {
+                        tracing::warn!("Failed to cache thumbnail {:?}: {}", path, err);
+                        return;
+                    }
+                    if let Some(picture) = weak.upgrade() {
+                        picture.set_filename(Some(&path));
+                    }
+                }
+                Err(err) => tracing::warn!("Failed to fetch thumbnail {}: {}", thumbnail_url, err),
+            }
+        });
+    }
+
+    let button = gtk::LinkButton::new(url);
+    button.set_child(Some(&picture));
+    button.update_property(&[gtk::accessible::Property::Label(&format!(
+        "Play the reference video for {}",
+        name
+    ))]);
+    Some(button.upcast())
+}
+
+/// Write through a temporary file so a failed download never looks cached
+fn save_thumbnail(path: &std::path::Path, bytes: &[u8]) -> std::io::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let partial = path.with_extension("part");
+    std::fs::write(&partial, bytes)?;
+    std::fs::rename(&partial, path)
+}
+
+/// Embedded player for a YouTube reference link
+#[cfg(feature = "webkit")]
+fn reference_player(url: &str) -> Option<gtk::Widget> {
+    use webkit6::prelude::WebViewExt;
+
+    let embed_url = reference::embed_url(url)?;
+    let view = webkit6::WebView::new();
+    view.set_size_request(-1, REFERENCE_MEDIA_HEIGHT);
+    view.load_uri(&embed_url);
+    Some(view.upcast())
+}
+
+#[cfg(not(feature = "webkit"))]
+fn reference_player(_url: &str) -> Option<gtk::Widget> {
+    tracing::warn!("reference_media = \"player\" needs a build with the `webkit` feature; showing the thumbnail");
+    None
+}
+
+/// Drive an interval countdown on a label until the last phase ends,
+/// playing `[sound]` cues along the way
+fn start_interval_timer(
+    interval: &IntervalScheme,
+    label: &gtk::Label,
+    pause: &gtk::Button,
+    stopwatch: Rc<Cell<Option<Stopwatch>>>,
+    sounds: &SoundConfig,
+) {
+    let timer = IntervalTimer::new(interval);
+    let mut player = SoundPlayer::new(sounds);
+    let style = sounds.style;
+    let mut last_second = None;
+    let label = label.downgrade();
+    let pause = pause.downgrade();
+    let done_text = format!("Time! ({})", interval.label());
+
+    glib::timeout_add_local(Duration::from_millis(250), move || {
+        let (Some(label), Some(running)) = (label.upgrade(), stopwatch.get()) else {
+            // Window closed mid-timer
+            return ControlFlow::Break;
+        };
+        let elapsed = running.elapsed(Instant::now()).as_secs() as u32;
+        if let Some(player) = &player {
+            if last_second != Some(elapsed) {
+                last_second = Some(elapsed);
+                if let Some(cue) = timer.cue(elapsed) {
+                    player.play(cue);
+                }
+            }
+        }
+        match timer.tick(elapsed) {
+            Some(tick) if running.is_paused() => {
+                label.set_text(&format!("Paused · {}", tick.label()));
+                ControlFlow::Continue
+            }
+            Some(tick) => {
+                label.set_text(&tick.label());
+                ControlFlow::Continue
+            }
+            None => {
+                label.set_text(&done_text);
+                if let Some(pause) = pause.upgrade() {
+                    pause.set_sensitive(false);
+                }
+                // Dropping the player cuts playback, so keep it until the last cue ends
+                if let Some(player) = player.take() {
+                    glib::timeout_add_local_once(
+                        cue_duration(style, TimerCue::Complete),
+                        move || drop(player),
+                    );
+                }
+                ControlFlow::Break
+            }
+        }
+    });
+}
+
+fn log_session(
+    state: &mut UiState,
+    tags: Vec<String>,
+    amrap_total: Option<i32>,
+    hr_recovery: Option<u8>,
+    blocks: Option<&circuit::Checklist>,
+    adjustments: &RepAdjustments,
+) -> cardio_core::Result<Vec<&'static achievements::Achievement>> {
     let prescription = state.prescription.clone();
+    let state_before = state.loaded.store.state.clone();
+    let amrap_total = amrap_total.filter(|_| prescription.definition.is_amrap());
+    let mut metrics_realized = amrap_total
+        .map(|total| {
+            vec![RealizedMetric::Reps {
+                key: "reps".into(),
+                per_set: vec![total],
+            }]
+        })
+        .unwrap_or_default();
+    if let Some(blocks) = blocks {
+        metrics_realized.extend(blocks.metrics(&prescription.definition));
+    }
+    // Scaled mid-timer: the target until then, the adjusted reps after
+    if let (Some(target), Some(interval)) = (prescription.reps, prescription.definition.interval())
+    {
+        if !adjustments.is_empty() {
+            metrics_realized.push(RealizedMetric::Reps {
+                key: "reps".into(),
+                per_set: adjustments.per_set(target, interval.rounds),
+            });
+        }
+    }
 
     let session = MicrodoseSession {
         id: Uuid::new_v4(),
@@ -447,37 +1532,58 @@ fn log_session(state: &mut UiState) -> cardio_core::Result<()> {
         started_at: Some(state.ctx_now),
         completed_at: Some(state.ctx_now),
         actual_duration_seconds: Some(prescription.definition.suggested_duration_seconds),
-        metrics_realized: vec![],
+        metrics_realized,
         perceived_rpe: None,
         avg_hr: None,
         max_hr: None,
+        hr_recovery,
+        hr_series_path: None,
+        paused_seconds: None,
+        tags,
     };
+    // Started with Start Timer: the real start, finish, duration and pauses
+    let store = &state.loaded.store;
+    let session = store.timed(session, store.now());
 
-    let mut sink = JsonlSink::new(&state.loaded.wal_path);
-    sink.append(&session)?;
-
-    // Track mobility rotation and persist state
-    if prescription.definition.category == MicrodoseCategory::Mobility {
-        state.loaded.user_state.last_mobility_def_id =
-            Some(prescription.definition.id.clone());
-    }
+    let dose = state.loaded.store.log(&session, &prescription)?;
+    // Counted right away by the weekly-target ring
     state
         .loaded
-        .user_state
-        .progressions
-        .entry(prescription.definition.id.clone())
-        .or_insert_with(|| ProgressionState {
-            reps: prescription.reps.unwrap_or(0),
-            style: prescription.style.unwrap_or(MovementStyle::None),
-            level: 0,
-            last_upgraded: None,
-        });
+        .recent_sessions
+        .insert(0, SessionKind::Real(session));
+    for warning in &dose.warnings {
+        tracing::warn!("{}", warning);
+    }
+    if let Some(pb) = dose.personal_best {
+        tracing::info!(
+            "New personal best: {} reps (was {:?})",
+            pb.total,
+            pb.previous
+        );
+    }
 
-    state.loaded.user_state.save(&state.loaded.state_path)?;
-    Ok(())
+    let store = &mut state.loaded.store;
+    store.record_progression(&state_before, state.ctx_now)?;
+    store.check_achievements(state.ctx_now)
+}
+
+/// Desktop notification for each newly unlocked achievement
+fn announce_achievements(app: &gtk::Application, unlocked: &[&'static achievements::Achievement]) {
+    for achievement in unlocked {
+        tracing::info!("Achievement unlocked: {}", achievement.name);
+        let notification = gtk::gio::Notification::new(&format!("🏆 {}", achievement.name));
+        notification.set_body(Some(achievement.description));
+        app.send_notification(
+            Some(&format!("achievement-{}", achievement.id)),
+            &notification,
+        );
+    }
 }
 
-fn handle_skip(state: &Rc<RefCell<UiState>>) -> cardio_core::Result<()> {
+fn handle_skip(
+    state: &Rc<RefCell<UiState>>,
+    reason: Option<timing::SkipReason>,
+) -> cardio_core::Result<()> {
     let mut state = state.borrow_mut();
     let def_id = state.prescription.definition.id.clone();
     state.skipped_ids.insert(def_id.clone());
@@ -491,20 +1597,60 @@ fn handle_skip(state: &Rc<RefCell<UiState>>) -> cardio_core::Result<()> {
     let mut recent = state.loaded.recent_sessions.clone();
     recent.insert(0, skipped);
 
-    let next = compute_prescription(&state.loaded, state.ctx_now, &recent)?;
+    // Skips never reach the WAL; log them for completion-by-hour stats
+    state
+        .loaded
+        .store
+        .skip(&state.prescription.definition.id, reason)?;
+
+    let next = compute_prescription(&state.loaded, state.ctx_now, &recent, state.surprise)?;
     state.prescription = next;
     state.loaded.recent_sessions = recent;
     Ok(())
 }
 
+/// Record an ignored popup's prescription as a soft skip
+fn log_soft_skip(state: &Rc<RefCell<UiState>>) -> cardio_core::Result<()> {
+    let state = state.borrow();
+    state
+        .loaded
+        .store
+        .soft_skip(&state.prescription.definition.id)
+}
+
+fn handle_checkin(state: &Rc<RefCell<UiState>>, answer: &str) -> cardio_core::Result<()> {
+    let mut state = state.borrow_mut();
+    let areas = soreness::parse_areas(answer)?;
+    let tz = state.loaded.store.config.timezone();
+    let checkin = SorenessCheckin {
+        checked_at: state.ctx_now,
+        areas,
+    };
+    soreness::record_checkin(&mut state.loaded.store.state.soreness, checkin, &tz);
+    state.loaded.store.save_state()?;
+
+    // Re-prescribe so sore areas are avoided right away
+    let next = compute_prescription(
+        &state.loaded,
+        state.ctx_now,
+        &state.loaded.recent_sessions,
+        state.surprise,
+    )?;
+    state.prescription = next;
+    Ok(())
+}
+
 fn mark_harder(state: &mut UiState) -> cardio_core::Result<()> {
+    let store = &mut state.loaded.store;
+    let state_before = store.state.clone();
     increase_intensity(
         &state.prescription.definition.id,
-        &mut state.loaded.user_state,
-        &state.loaded.config,
+        &mut store.state,
+        &store.config,
+        state.ctx_now,
     );
-    state.loaded.user_state.save(&state.loaded.state_path)?;
-    Ok(())
+    store.save_state()?;
+    store.record_progression(&state_before, state.ctx_now)
 }
 
 fn format_style(style: &MovementStyle) -> String {
@@ -515,3 +1661,12 @@ fn format_style(style: &MovementStyle) -> String {
         other => format!("{:?}", other),
     }
 }
+
+/// First letter upper-cased, for button labels ("no equipment" -> "No equipment")
+fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
@@ -0,0 +1,118 @@
+//! Weekly-target progress ring for the popup header and the stats window.
+//!
+//! Each category with a `weekly_targets` entry gets an equal arc of the
+//! ring, filled as its sessions over the trailing 7 days add up; the middle
+//! shows the sessions counted over the total target.
+
+use cardio_core::stats::{self, TargetProgress};
+use cardio_core::MicrodoseCategory;
+use gtk::prelude::*;
+use gtk4 as gtk;
+use std::cell::RefCell;
+use std::f64::consts::PI;
+use std::rc::Rc;
+
+/// Width and height of the ring widget
+const RING_SIZE: i32 = 72;
+
+/// Stroke width of the arcs
+const RING_WIDTH: f64 = 8.0;
+
+/// Gap left between category arcs, in turns
+const SEGMENT_GAP: f64 = 0.015;
+
+/// A ring drawing weekly-target progress; [`ProgressRing::update`] redraws it
+#[derive(Clone)]
+pub struct ProgressRing {
+    pub area: gtk::DrawingArea,
+    progress: Rc<RefCell<Vec<TargetProgress>>>,
+}
+
+impl ProgressRing {
+    pub fn new(progress: Vec<TargetProgress>) -> Self {
+        let area = gtk::DrawingArea::new();
+        area.set_content_width(RING_SIZE);
+        area.set_content_height(RING_SIZE);
+        area.set_halign(gtk::Align::Center);
+        let ring = ProgressRing {
+            area,
+            progress: Rc::new(RefCell::new(Vec::new())),
+        };
+        {
+            let progress = ring.progress.clone();
+            ring.area.set_draw_func(move |_, cr, width, height| {
+                draw(cr, width, height, &progress.borrow());
+            });
+        }
+        ring.update(progress);
+        ring
+    }
+
+    /// Show new progress (e.g. right after a dose is logged)
+    pub fn update(&self, progress: Vec<TargetProgress>) {
+        let description = describe(&progress);
+        self.area.set_tooltip_text(Some(&description));
+        self.area
+            .update_property(&[gtk::accessible::Property::Label(&description)]);
+        *self.progress.borrow_mut() = progress;
+        self.area.queue_draw();
+    }
+}
+
+/// "Weekly targets: vo2 1 of 3, gtg 2 of 2" for tooltips and screen readers
+fn describe(progress: &[TargetProgress]) -> String {
+    let parts: Vec<String> = progress
+        .iter()
+        .map(|p| format!("{} {} of {}", p.category.as_str(), p.done, p.target))
+        .collect();
+    format!("Weekly targets: {}", parts.join(", "))
+}
+
+/// Arc colour per category (RGB, 0.0-1.0)
+fn colour(category: &MicrodoseCategory) -> (f64, f64, f64) {
+    match category {
+        MicrodoseCategory::Vo2 => (0.88, 0.27, 0.22),
+        MicrodoseCategory::Gtg => (0.20, 0.52, 0.89),
+        MicrodoseCategory::Mobility => (0.18, 0.70, 0.42),
+        MicrodoseCategory::Core => (0.96, 0.62, 0.15),
+        MicrodoseCategory::Zone2 => (0.60, 0.35, 0.80),
+        MicrodoseCategory::Breathwork => (0.30, 0.70, 0.75),
+    }
+}
+
+fn draw(cr: &gtk::cairo::Context, width: i32, height: i32, progress: &[TargetProgress]) {
+    let (cx, cy) = (f64::from(width) / 2.0, f64::from(height) / 2.0);
+    let radius = cx.min(cy) - RING_WIDTH / 2.0;
+    // Turns clockwise from the top to cairo's angles
+    let angle = |turns: f64| -PI / 2.0 + turns * 2.0 * PI;
+
+    cr.set_line_width(RING_WIDTH);
+    for segment in stats::ring_segments(progress) {
+        let (r, g, b) = colour(&segment.category);
+        let gap = if progress.len() > 1 { SEGMENT_GAP } else { 0.0 };
+        let end = segment.start + segment.length - gap;
+
+        cr.set_source_rgba(r, g, b, 0.2);
+        cr.arc(cx, cy, radius, angle(segment.start), angle(end));
+        let _ = cr.stroke();
+
+        if segment.filled > 0.0 {
+            cr.set_source_rgb(r, g, b);
+            let filled_end = (segment.start + segment.filled).min(end);
+            cr.arc(cx, cy, radius, angle(segment.start), angle(filled_end));
+            let _ = cr.stroke();
+        }
+    }
+
+    let (done, target) = stats::ring_totals(progress);
+    let text = format!("{}/{}", done, target);
+    cr.set_source_rgb(0.5, 0.5, 0.5);
+    cr.set_font_size(14.0);
+    if let Ok(extents) = cr.text_extents(&text) {
+        cr.move_to(
+            cx - extents.width() / 2.0 - extents.x_bearing(),
+            cy - extents.height() / 2.0 - extents.y_bearing(),
+        );
+        let _ = cr.show_text(&text);
+    }
+}
@@ -0,0 +1,432 @@
+//! Platform tray backends.
+//!
+//! - Linux: StatusNotifierItem over DBus via `ksni`
+//! - Windows/macOS: `Shell_NotifyIcon` / `NSStatusItem` via `tray-icon`
+//!
+//! The GTK main loop polls the active backend for [`TrayEvent`]s, so the
+//! popup code is identical on every platform.
+
+/// Events surfaced by a tray backend
+#[derive(Debug)]
+pub enum TrayEvent {
+    Activate,
+    /// Open the popup with a novelty-weighted pick
+    Surprise,
+    ShowStats,
+    ToggleTravelMode,
+    ToggleOfficeMode,
+    WatcherOnline,
+    WatcherOffline,
+}
+
+/// Tooltip text when there is no goal progress to show
+pub const DEFAULT_TOOLTIP: &str = "Microdose cardio assistant";
+
+/// A running tray icon
+pub trait TrayBackend {
+    /// Drain pending events without blocking (called from the GTK main loop)
+    fn poll_events(&mut self) -> Vec<TrayEvent>;
+
+    /// Replace the tooltip text (e.g. weekly goal progress)
+    fn set_tooltip(&mut self, text: &str);
+
+    /// Tick or untick the travel mode menu item
+    fn set_travel_mode(&mut self, on: bool);
+
+    /// Tick or untick the office mode menu item
+    fn set_office_mode(&mut self, on: bool);
+
+    /// Show how many doses are due on the icon (0 clears the badge)
+    fn set_badge(&mut self, count: u32);
+}
+
+/// Start the tray backend for the current platform
+pub fn spawn_backend() -> Box<dyn TrayBackend> {
+    #[cfg(target_os = "linux")]
+    {
+        Box::new(ksni_backend::KsniBackend::spawn())
+    }
+
+    #[cfg(any(target_os = "windows", target_os = "macos"))]
+    {
+        match tray_icon_backend::TrayIconBackend::spawn() {
+            Ok(backend) => Box::new(backend),
+            Err(err) => {
+                tracing::error!("Failed to create tray icon: {}", err);
+                Box::new(NoTray)
+            }
+        }
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "windows", target_os = "macos")))]
+    {
+        tracing::warn!("No tray backend for this platform; popup fallback only");
+        Box::new(NoTray)
+    }
+}
+
+/// Backend used when no tray icon could be created (popup fallback only)
+#[allow(dead_code)]
+struct NoTray;
+
+impl TrayBackend for NoTray {
+    fn poll_events(&mut self) -> Vec<TrayEvent> {
+        Vec::new()
+    }
+
+    fn set_tooltip(&mut self, _text: &str) {}
+
+    fn set_travel_mode(&mut self, _on: bool) {}
+
+    fn set_office_mode(&mut self, _on: bool) {}
+
+    fn set_badge(&mut self, _count: u32) {}
+}
+
+/// Solid-colour ARGB icon used by every backend
+fn solid_icon_argb(size: i32, argb: u32) -> Vec<u8> {
+    let mut data = Vec::with_capacity((size * size * 4) as usize);
+    for _ in 0..(size * size) {
+        data.extend_from_slice(&argb.to_be_bytes());
+    }
+    data
+}
+
+const ICON_SIZE: i32 = 24;
+const ICON_ARGB: u32 = 0xFF2ECC71;
+const BADGE_ARGB: u32 = 0xFFE74C3C;
+const BADGE_TEXT_ARGB: u32 = 0xFFFFFFFF;
+
+/// 3×5 pixel digits for the badge, one row per entry, leftmost pixel in bit 2
+const BADGE_DIGITS: [[u8; 5]; 10] = [
+    [0b111, 0b101, 0b101, 0b101, 0b111],
+    [0b010, 0b110, 0b010, 0b010, 0b111],
+    [0b111, 0b001, 0b111, 0b100, 0b111],
+    [0b111, 0b001, 0b111, 0b001, 0b111],
+    [0b101, 0b101, 0b111, 0b001, 0b001],
+    [0b111, 0b100, 0b111, 0b001, 0b111],
+    [0b111, 0b100, 0b111, 0b101, 0b111],
+    [0b111, 0b001, 0b001, 0b001, 0b001],
+    [0b111, 0b101, 0b111, 0b101, 0b111],
+    [0b111, 0b101, 0b111, 0b001, 0b111],
+];
+
+/// Shown for more than 9 doses
+const BADGE_PLUS: [u8; 5] = [0b000, 0b010, 0b111, 0b010, 0b000];
+
+/// The icon with a red badge in the bottom-right corner showing `count`
+/// (no badge for 0)
+fn badge_icon_argb(size: i32, argb: u32, count: u32) -> Vec<u8> {
+    let mut data = solid_icon_argb(size, argb);
+    if count == 0 {
+        return data;
+    }
+    let glyph = match count {
+        1..=9 => BADGE_DIGITS[count as usize],
+        _ => BADGE_PLUS,
+    };
+
+    // A circle over the bottom-right 7/12 of the icon, glyph scaled to fit
+    let badge = size * 7 / 12;
+    let origin = size - badge;
+    let scale = (badge / 7).max(1);
+    let glyph_x = origin + (badge - 3 * scale) / 2;
+    let glyph_y = origin + (badge - 5 * scale) / 2;
+    let centre = f64::from(origin) + f64::from(badge) / 2.0;
+    let radius = f64::from(badge) / 2.0;
+    for y in origin..size {
+        for x in origin..size {
+            let (dx, dy) = (f64::from(x) + 0.5 - centre, f64::from(y) + 0.5 - centre);
+            if dx * dx + dy * dy > radius * radius {
+                continue;
+            }
+            let (gx, gy) = ((x - glyph_x) / scale, (y - glyph_y) / scale);
+            let lit = x >= glyph_x
+                && y >= glyph_y
+                && gx < 3
+                && gy < 5
+                && glyph[gy as usize] & (0b100 >> gx) != 0;
+            let colour = if lit { BADGE_TEXT_ARGB } else { BADGE_ARGB };
+            let at = ((y * size + x) * 4) as usize;
+            data[at..at + 4].copy_from_slice(&colour.to_be_bytes());
+        }
+    }
+    data
+}
+
+#[cfg(target_os = "linux")]
+mod ksni_backend {
+    use super::{badge_icon_argb, TrayBackend, TrayEvent, DEFAULT_TOOLTIP, ICON_ARGB, ICON_SIZE};
+    use std::sync::mpsc::{channel, Receiver, Sender};
+
+    struct KrepTray {
+        tx: Sender<TrayEvent>,
+        tooltip: String,
+        travel_mode: bool,
+        office_mode: bool,
+        /// Doses due, drawn as a badge on the icon
+        badge: u32,
+    }
+
+    impl ksni::Tray for KrepTray {
+        fn icon_name(&self) -> String {
+            // Fallback to a well-known icon so the indicator is always visible
+            "applications-system".into()
+        }
+
+        fn icon_pixmap(&self) -> Vec<ksni::Icon> {
+            vec![ksni::Icon {
+                width: ICON_SIZE,
+                height: ICON_SIZE,
+                data: badge_icon_argb(ICON_SIZE, ICON_ARGB, self.badge),
+            }]
+        }
+
+        fn id(&self) -> String {
+            "krep-tray".into()
+        }
+
+        fn title(&self) -> String {
+            "Krep".into()
+        }
+
+        fn status(&self) -> ksni::Status {
+            ksni::Status::Active
+        }
+
+        fn tool_tip(&self) -> ksni::ToolTip {
+            ksni::ToolTip {
+                icon_name: self.icon_name(),
+                icon_pixmap: self.icon_pixmap(),
+                title: "Krep".into(),
+                description: self.tooltip.clone(),
+            }
+        }
+
+        fn menu(&self) -> Vec<ksni::MenuItem<Self>> {
+            vec![
+                ksni::MenuItem::Standard(ksni::menu::StandardItem {
+                    label: "Microdose Now".into(),
+                    activate: Box::new(|this: &mut Self| {
+                        let _ = this.tx.send(TrayEvent::Activate);
+                    }),
+                    ..Default::default()
+                }),
+                ksni::MenuItem::Standard(ksni::menu::StandardItem {
+                    label: "Surprise Me".into(),
+                    activate: Box::new(|this: &mut Self| {
+                        let _ = this.tx.send(TrayEvent::Surprise);
+                    }),
+                    ..Default::default()
+                }),
+                ksni::MenuItem::Standard(ksni::menu::StandardItem {
+                    label: "Progress".into(),
+                    activate: Box::new(|this: &mut Self| {
+                        let _ = this.tx.send(TrayEvent::ShowStats);
+                    }),
+                    ..Default::default()
+                }),
+                ksni::MenuItem::Checkmark(ksni::menu::CheckmarkItem {
+                    label: "Travel Mode (No Equipment)".into(),
+                    checked: self.travel_mode,
+                    activate: Box::new(|this: &mut Self| {
+                        let _ = this.tx.send(TrayEvent::ToggleTravelMode);
+                    }),
+                    ..Default::default()
+                }),
+                ksni::MenuItem::Checkmark(ksni::menu::CheckmarkItem {
+                    label: "Office Mode (Low Sweat)".into(),
+                    checked: self.office_mode,
+                    activate: Box::new(|this: &mut Self| {
+                        let _ = this.tx.send(TrayEvent::ToggleOfficeMode);
+                    }),
+                    ..Default::default()
+                }),
+            ]
+        }
+
+        fn watcher_online(&self) {
+            let _ = self.tx.send(TrayEvent::WatcherOnline);
+        }
+
+        fn watcher_offine(&self) -> bool {
+            let _ = self.tx.send(TrayEvent::WatcherOffline);
+            true
+        }
+    }
+
+    /// StatusNotifierItem tray running on ksni's DBus thread
+    pub struct KsniBackend {
+        rx: Receiver<TrayEvent>,
+        handle: ksni::Handle<KrepTray>,
+    }
+
+    impl KsniBackend {
+        pub fn spawn() -> Self {
+            let (tx, rx) = channel::<TrayEvent>();
+            let service = ksni::TrayService::new(KrepTray {
+                tx,
+                tooltip: DEFAULT_TOOLTIP.into(),
+                travel_mode: false,
+                office_mode: false,
+                badge: 0,
+            });
+            let handle = service.handle();
+            service.spawn();
+            Self { rx, handle }
+        }
+    }
+
+    impl TrayBackend for KsniBackend {
+        fn poll_events(&mut self) -> Vec<TrayEvent> {
+            self.rx.try_iter().collect()
+        }
+
+        fn set_tooltip(&mut self, text: &str) {
+            let text = text.to_string();
+            self.handle.update(move |tray| tray.tooltip = text);
+        }
+
+        fn set_travel_mode(&mut self, on: bool) {
+            self.handle.update(move |tray| tray.travel_mode = on);
+        }
+
+        fn set_office_mode(&mut self, on: bool) {
+            self.handle.update(move |tray| tray.office_mode = on);
+        }
+
+        fn set_badge(&mut self, count: u32) {
+            self.handle.update(move |tray| tray.badge = count);
+        }
+    }
+}
+
+#[cfg(any(target_os = "windows", target_os = "macos"))]
+mod tray_icon_backend {
+    use super::{badge_icon_argb, TrayBackend, TrayEvent, DEFAULT_TOOLTIP, ICON_ARGB, ICON_SIZE};
+    use tray_icon::menu::{CheckMenuItem, Menu, MenuEvent, MenuId, MenuItem};
+    use tray_icon::{
+        Icon, MouseButton, MouseButtonState, TrayIcon, TrayIconBuilder, TrayIconEvent,
+    };
+
+    /// The icon with a doses-due badge (tray-icon wants RGBA; ours is ARGB)
+    fn icon(badge: u32) -> Result<Icon, tray_icon::BadIcon> {
+        let rgba: Vec<u8> = badge_icon_argb(ICON_SIZE, ICON_ARGB, badge)
+            .chunks_exact(4)
+            .flat_map(|px| [px[1], px[2], px[3], px[0]])
+            .collect();
+        Icon::from_rgba(rgba, ICON_SIZE as u32, ICON_SIZE as u32)
+    }
+
+    /// Native tray icon (Shell_NotifyIcon on Windows, NSStatusItem on macOS)
+    pub struct TrayIconBackend {
+        // Dropping the icon removes it from the tray
+        tray: TrayIcon,
+        activate_id: MenuId,
+        surprise_id: MenuId,
+        stats_id: MenuId,
+        travel: CheckMenuItem,
+        office: CheckMenuItem,
+        announced: bool,
+        badge: u32,
+    }
+
+    impl TrayIconBackend {
+        pub fn spawn() -> Result<Self, Box<dyn std::error::Error>> {
+            let menu = Menu::new();
+            let activate = MenuItem::new("Microdose Now", true, None);
+            menu.append(&activate)?;
+            let surprise = MenuItem::new("Surprise Me", true, None);
+            menu.append(&surprise)?;
+            let stats = MenuItem::new("Progress", true, None);
+            menu.append(&stats)?;
+            let travel = CheckMenuItem::new("Travel Mode (No Equipment)", true, false, None);
+            menu.append(&travel)?;
+            let office = CheckMenuItem::new("Office Mode (Low Sweat)", true, false, None);
+            menu.append(&office)?;
+
+            let tray = TrayIconBuilder::new()
+                .with_menu(Box::new(menu))
+                .with_tooltip(format!("Krep - {}", DEFAULT_TOOLTIP))
+                .with_icon(icon(0)?)
+                .build()?;
+
+            Ok(Self {
+                tray,
+                activate_id: activate.id().clone(),
+                surprise_id: surprise.id().clone(),
+                stats_id: stats.id().clone(),
+                travel,
+                office,
+                announced: false,
+                badge: 0,
+            })
+        }
+    }
+
+    impl TrayBackend for TrayIconBackend {
+        fn poll_events(&mut self) -> Vec<TrayEvent> {
+            let mut events = Vec::new();
+
+            // Native trays are always present, so report the "watcher" once
+            if !self.announced {
+                self.announced = true;
+                events.push(TrayEvent::WatcherOnline);
+            }
+
+            while let Ok(event) = MenuEvent::receiver().try_recv() {
+                if event.id == self.activate_id {
+                    events.push(TrayEvent::Activate);
+                } else if event.id == self.surprise_id {
+                    events.push(TrayEvent::Surprise);
+                } else if event.id == self.stats_id {
+                    events.push(TrayEvent::ShowStats);
+                } else if event.id == *self.travel.id() {
+                    events.push(TrayEvent::ToggleTravelMode);
+                } else if event.id == *self.office.id() {
+                    events.push(TrayEvent::ToggleOfficeMode);
+                }
+            }
+
+            while let Ok(event) = TrayIconEvent::receiver().try_recv() {
+                if let TrayIconEvent::Click {
+                    button: MouseButton::Left,
+                    button_state: MouseButtonState::Up,
+                    ..
+                } = event
+                {
+                    events.push(TrayEvent::Activate);
+                }
+            }
+
+            events
+        }
+
+        fn set_tooltip(&mut self, text: &str) {
+            if let Err(err) = self.tray.set_tooltip(Some(format!("Krep - {}", text))) {
+                tracing::warn!("Failed to update tray tooltip: {}", err);
+            }
+        }
+
+        fn set_travel_mode(&mut self, on: bool) {
+            self.travel.set_checked(on);
+        }
+
+        fn set_office_mode(&mut self, on: bool) {
+            self.office.set_checked(on);
+        }
+
+        fn set_badge(&mut self, count: u32) {
+            if count == self.badge {
+                return;
+            }
+            self.badge = count;
+            let result = icon(count)
+                .map_err(|e| e.to_string())
+                .and_then(|icon| self.tray.set_icon(Some(icon)).map_err(|e| e.to_string()));
+            if let Err(err) = result {
+                tracing::warn!("Failed to update tray icon badge: {}", err);
+            }
+        }
+    }
+}